@@ -0,0 +1,151 @@
+//! Finding benchmarks that never change: those that no comparison of consecutive recent datums
+//! found significantly different, which may only be using up machine time, and so are candidates
+//! for fewer `proc_execs` or removal.
+
+use crate::{
+    App, ConfidenceLevel, DEFAULT_MIN_SAMPLES, OutputFormat, SummaryStats, csv_field, diff_rows,
+    new_table, units,
+};
+use comfy_table::{Cell, CellAlignment};
+use std::collections::{BTreeMap, HashMap};
+use std::process;
+use std::time::Duration;
+
+/// How one benchmark fared in the comparisons of consecutive datums.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Streak {
+    /// The number of comparisons with enough samples on both sides to tell a difference.
+    pub(crate) comparisons: usize,
+    /// How many of those found a significant difference.
+    pub(crate) significant: usize,
+    /// The total time of the benchmark's pexecs in every datum, in milliseconds.
+    pub(crate) runtime_ms: f64,
+}
+
+/// How each benchmark fared in the comparisons of each datum of `summaries`, oldest first, with
+/// the next.
+pub(crate) fn streaks(summaries: &[HashMap<String, SummaryStats>]) -> BTreeMap<String, Streak> {
+    let mut streaks = BTreeMap::<String, Streak>::new();
+    for summary in summaries {
+        for (k, s) in summary {
+            streaks.entry(k.clone()).or_default().runtime_ms += s.mean * s.n as f64;
+        }
+    }
+    for pair in summaries.windows(2) {
+        let common = |a: &HashMap<String, SummaryStats>, b: &HashMap<String, SummaryStats>| {
+            a.iter()
+                .filter(|(k, _)| b.contains_key(*k))
+                .map(|(k, s)| (k.clone(), s.clone()))
+                .collect::<HashMap<_, _>>()
+        };
+        let (old, new) = (common(&pair[0], &pair[1]), common(&pair[1], &pair[0]));
+        for row in diff_rows(&old, &new, DEFAULT_MIN_SAMPLES, false) {
+            if row.too_few {
+                continue;
+            }
+            let streak = streaks.get_mut(row.key).unwrap();
+            streak.comparisons += 1;
+            streak.significant += usize::from(row.significant);
+        }
+    }
+    streaks
+}
+
+/// The benchmarks of `streaks` that were compared but never significantly different, with the
+/// most time spent on them first.
+pub(crate) fn never_significant(streaks: &BTreeMap<String, Streak>) -> Vec<(&str, &Streak)> {
+    let mut never = streaks
+        .iter()
+        .filter(|(_, s)| s.comparisons > 0 && s.significant == 0)
+        .map(|(k, s)| (k.as_str(), s))
+        .collect::<Vec<_>>();
+    never.sort_by(|(k1, s1), (k2, s2)| s2.runtime_ms.total_cmp(&s1.runtime_ms).then(k1.cmp(k2)));
+    never
+}
+
+impl App {
+    /// Compare each of the newest `window` datums with the next, and report the benchmarks that
+    /// were never significantly different.
+    pub(crate) fn cmd_advise(
+        &self,
+        window: usize,
+        confidence: ConfidenceLevel,
+        format: OutputFormat,
+    ) {
+        let ids = self.store.ids();
+        if ids.len() < 2 {
+            eprintln!("error: need at least two datums to compare");
+            process::exit(1);
+        }
+        let ids = &ids[ids.len().saturating_sub(window)..];
+        // Summaries are cached, so that long histories stay quick to scan.
+        let aliases = self.key_aliases();
+        let summaries = ids
+            .iter()
+            .map(|id| {
+                let mut summary = self.load_summary(*id, confidence);
+                for (old, new) in aliases.renames(summary.keys(), |_| true) {
+                    crate::aliases::rename_key(&mut summary, &old, &new);
+                }
+                summary
+            })
+            .collect::<Vec<_>>();
+        let streaks = streaks(&summaries);
+        let never = never_significant(&streaks);
+        let time = |ms: f64| units::format_duration(Duration::from_secs_f64(ms / 1000.));
+        match format {
+            OutputFormat::Table => {
+                let (first, last) = (ids[0], ids[ids.len() - 1]);
+                println!(
+                    "comparing consecutive datums from Datum{first} to Datum{last} \
+                     ({confidence}% confidence)\n"
+                );
+                if never.is_empty() {
+                    println!("every benchmark was significantly different at least once");
+                    return;
+                }
+                let mut table = new_table(false);
+                let right = |s: String| Cell::new(s).set_alignment(CellAlignment::Right);
+                table.set_header(vec![
+                    Cell::new("Benchmark"),
+                    right("Comparisons".to_owned()),
+                    right("Time spent".to_owned()),
+                ]);
+                for (k, s) in &never {
+                    table.add_row(vec![
+                        Cell::new(k),
+                        right(s.comparisons.to_string()),
+                        right(time(s.runtime_ms)),
+                    ]);
+                }
+                println!("{table}");
+                let total = never.iter().map(|(_, s)| s.runtime_ms).sum::<f64>();
+                println!(
+                    "\n{} benchmarks were never significantly different, taking {} in all: \
+                     consider running them with fewer proc_execs, or not at all",
+                    never.len(),
+                    time(total)
+                );
+            }
+            OutputFormat::Csv => {
+                println!("benchmark,comparisons,runtime_ms");
+                for (k, s) in &never {
+                    println!("{},{},{}", csv_field(k), s.comparisons, s.runtime_ms);
+                }
+            }
+            OutputFormat::Json => {
+                let rows = never
+                    .iter()
+                    .map(|(k, s)| {
+                        serde_json::json!({
+                            "benchmark": k,
+                            "comparisons": s.comparisons,
+                            "runtime_ms": s.runtime_ms,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                println!("{}", serde_json::Value::Array(rows));
+            }
+        }
+    }
+}
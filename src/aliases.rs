@@ -0,0 +1,127 @@
+//! Following benchmarks and executors across renames: the `aliases` of a benchmark or executor
+//! are the names it used to have, under which older datums recorded its keys. Commands that
+//! match keys across datums rename those old keys to the current ones.
+
+use crate::config::Config;
+use crate::{BenchKey, ResultFile};
+use std::collections::HashMap;
+
+/// The current benchmark key of each old key that the config's aliases stand for.
+#[derive(Debug, Default)]
+pub(crate) struct KeyAliases(HashMap<String, String>);
+
+impl KeyAliases {
+    /// The old keys of every benchmark key in `config`, which [Config::validate] has checked
+    /// stand for only one current key each.
+    pub(crate) fn new(config: &Config) -> Self {
+        let mut current = HashMap::new();
+        for (exec_name, executor) in &config.executors {
+            let exec_names = [exec_name].into_iter().chain(&executor.aliases);
+            let exec_names = exec_names.collect::<Vec<_>>();
+            for suite in config.suites.values() {
+                for (bench_name, bench) in &suite.benchmarks {
+                    let key = |benchmark: &String, executor: &String| BenchKey {
+                        benchmark: benchmark.clone(),
+                        executor: executor.clone(),
+                        extra_args: bench.extra_args.clone(),
+                    };
+                    let new = key(bench_name, exec_name).to_string();
+                    for benchmark in [bench_name].into_iter().chain(&bench.aliases) {
+                        for executor in &exec_names {
+                            let old = key(benchmark, executor).to_string();
+                            if old != new {
+                                current.insert(old, new.clone());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        KeyAliases(current)
+    }
+
+    /// The current key of `key`, if it's an old one.
+    pub(crate) fn current(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    /// The old keys among `keys` to rename, sorted, with the current key of each: those whose
+    /// current key isn't already among `keys`, and is `wanted`.
+    ///
+    /// If several old keys have the same current key, only the first is renamed.
+    pub(crate) fn renames<'a>(
+        &self,
+        keys: impl IntoIterator<Item = &'a String>,
+        wanted: impl Fn(&str) -> bool,
+    ) -> Vec<(String, String)> {
+        let mut keys = keys.into_iter().map(String::as_str).collect::<Vec<_>>();
+        keys.sort();
+        keys.dedup();
+        let mut renames = Vec::<(String, String)>::new();
+        for old in &keys {
+            let Some(new) = self.current(old) else {
+                continue;
+            };
+            if wanted(new)
+                && keys.binary_search(&new).is_err()
+                && !renames.iter().any(|(_, n)| n == new)
+            {
+                renames.push((old.to_string(), new.to_owned()));
+            }
+        }
+        renames
+    }
+
+    /// Rename the old keys of `results` whose current keys are in `other`, so that they're
+    /// matched with them, returning the note saying so, if any were renamed.
+    pub(crate) fn follow(
+        &self,
+        name: &str,
+        results: &mut ResultFile,
+        other: &ResultFile,
+    ) -> Option<String> {
+        let renames = self.renames(results.data.keys().chain(results.failures.keys()), |k| {
+            other.data.contains_key(k) || other.failures.contains_key(k)
+        });
+        for (old, new) in &renames {
+            results.rename_key(old, new);
+        }
+        note(name, &renames)
+    }
+}
+
+/// Rename key `old` to `new` in `map`, if it's there and `new` isn't.
+pub(crate) fn rename_key<T>(map: &mut HashMap<String, T>, old: &str, new: &str) {
+    if !map.contains_key(new)
+        && let Some(v) = map.remove(old)
+    {
+        map.insert(new.to_owned(), v);
+    }
+}
+
+impl ResultFile {
+    /// Rename benchmark key `old` to `new` throughout.
+    pub(crate) fn rename_key(&mut self, old: &str, new: &str) {
+        rename_key(&mut self.data, old, new);
+        for data in self.metrics.values_mut() {
+            rename_key(data, old, new);
+        }
+        rename_key(&mut self.params, old, new);
+        rename_key(&mut self.timestamps, old, new);
+        rename_key(&mut self.failures, old, new);
+    }
+}
+
+/// The note saying which of `name`'s keys were renamed, if any were.
+pub(crate) fn note(name: &str, renames: &[(String, String)]) -> Option<String> {
+    (!renames.is_empty()).then(|| {
+        let renames = renames
+            .iter()
+            .map(|(old, new)| format!("{old} (now {new})"))
+            .collect::<Vec<_>>();
+        format!(
+            "note: {name} recorded benchmarks under old names: {}",
+            renames.join(", ")
+        )
+    })
+}
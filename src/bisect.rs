@@ -0,0 +1,290 @@
+//! Finding the commit which made a benchmark slower, by building and benchmarking the commits
+//! between a good and a bad one in the manner of `git bisect`.
+//!
+//! The commits are checked out in a separate worktree, so that the user's own tree isn't
+//! disturbed, and the executors built from the repository are pointed into it.
+
+use crate::config::Config;
+use crate::{
+    App, BenchOptions, ConfidenceLevel, DEFAULT_MIN_SAMPLES, ResultFile, diff_rows, git,
+    higher_is_better, parse_percent, runner,
+};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// The number of pexecs per benchmark for each commit, unless the config asks for fewer or
+/// `--proc-execs` says otherwise. Bisecting runs many benchmarks, and large regressions are the
+/// ones worth bisecting, so fewer samples usually suffice.
+const BISECT_PROC_EXECS: usize = 10;
+
+/// How to look for the first bad commit.
+pub(crate) struct BisectOptions {
+    pub(crate) good: String,
+    pub(crate) bad: String,
+    /// A commit is bad if a benchmark is significantly worse than in `good` by more than this
+    /// percentage.
+    pub(crate) threshold: f64,
+    pub(crate) benchmarks: Vec<String>,
+    pub(crate) proc_execs: Option<usize>,
+    /// Commits not to test, e.g. because they're known not to build.
+    pub(crate) skip: Vec<String>,
+    /// A directory in the repository to bisect.
+    pub(crate) repo: PathBuf,
+    pub(crate) metric: String,
+    pub(crate) confidence: ConfidenceLevel,
+}
+
+/// Parse a percentage, with or without a trailing `%`.
+pub(crate) fn parse_threshold(s: &str) -> Result<f64, String> {
+    parse_percent(s.strip_suffix('%').unwrap_or(s))
+}
+
+/// A search for the first bad commit in a list of commits, the last of which is bad.
+pub(crate) struct Search {
+    /// The first commit which may be the first bad one.
+    lo: usize,
+    /// The earliest commit known to be bad.
+    hi: usize,
+    /// The commits which can't be tested.
+    skipped: BTreeSet<usize>,
+}
+
+impl Search {
+    /// Search `n` commits, where `n` is at least 1.
+    pub(crate) fn new(n: usize, skipped: BTreeSet<usize>) -> Self {
+        Search {
+            lo: 0,
+            hi: n - 1,
+            skipped,
+        }
+    }
+
+    /// The commit to test next, which is the untested one nearest the middle of those that may be
+    /// the first bad one, or `None` if there's nothing left to test.
+    pub(crate) fn next(&self) -> Option<usize> {
+        let mid = self.lo + (self.hi - self.lo) / 2;
+        (self.lo..self.hi)
+            .filter(|i| !self.skipped.contains(i))
+            .min_by_key(|i| (i.abs_diff(mid), *i))
+    }
+
+    pub(crate) fn good(&mut self, i: usize) {
+        self.lo = i + 1;
+    }
+
+    pub(crate) fn bad(&mut self, i: usize) {
+        self.hi = i;
+    }
+
+    pub(crate) fn skip(&mut self, i: usize) {
+        self.skipped.insert(i);
+    }
+
+    /// Once there's nothing left to test, the first bad commit, or if skipped commits hide it,
+    /// the commits it may be, oldest first.
+    pub(crate) fn result(&self) -> Result<usize, Vec<usize>> {
+        if self.lo == self.hi {
+            Ok(self.hi)
+        } else {
+            Err((self.lo..=self.hi).collect())
+        }
+    }
+}
+
+/// The benchmarks in which `candidate` is significantly worse than `good` in `metric` by more
+/// than `threshold` percent, with their changes, or why the two can't be compared.
+pub(crate) fn regressions(
+    good: &ResultFile,
+    candidate: &ResultFile,
+    metric: &str,
+    threshold: f64,
+    confidence: ConfidenceLevel,
+) -> Result<Vec<String>, String> {
+    let (good, candidate) = (good.for_metric(metric)?, candidate.for_metric(metric)?);
+    if !candidate.failures.is_empty() {
+        let mut failed = candidate.failures.keys().cloned().collect::<Vec<_>>();
+        failed.sort();
+        return Err(format!("benchmarks failed: {}", failed.join(", ")));
+    }
+    let mut s1 = good.summarise(confidence);
+    let mut s2 = candidate.summarise(confidence);
+    s1.retain(|k, _| s2.contains_key(k));
+    s2.retain(|k, _| s1.contains_key(k));
+    if s1.is_empty() {
+        return Err("no benchmarks in common with the good commit".to_owned());
+    }
+    Ok(
+        diff_rows(&s1, &s2, DEFAULT_MIN_SAMPLES, higher_is_better(metric))
+            .iter()
+            .filter(|r| r.regresses_beyond(threshold))
+            .map(|r| format!("{} ({:+.1}%)", r.key, r.change))
+            .collect(),
+    )
+}
+
+/// The path `path` would have in `worktree`, if it's in the repository whose top level is
+/// `toplevel`.
+fn in_worktree(path: &Path, toplevel: &Path, worktree: &Path) -> Option<PathBuf> {
+    let path = path
+        .canonicalize()
+        .or_else(|_| std::path::absolute(path))
+        .ok()?;
+    Some(worktree.join(path.strip_prefix(toplevel).ok()?))
+}
+
+/// Point the executors in `config` that are built in the repository whose top level is
+/// `toplevel` at `worktree` instead, returning their names, sorted.
+fn rebase_executors(config: &mut Config, toplevel: &Path, worktree: &Path) -> Vec<String> {
+    let mut rebased = Vec::new();
+    for (name, executor) in &mut config.executors {
+        let Some(build) = &mut executor.build else {
+            continue;
+        };
+        let Some(dir) = in_worktree(&build.dir, toplevel, worktree) else {
+            continue;
+        };
+        if let Some(path) = in_worktree(&executor.path, toplevel, worktree) {
+            executor.path = path;
+        }
+        build.dir = dir;
+        rebased.push(name.clone());
+    }
+    rebased.sort();
+    rebased
+}
+
+impl App {
+    /// Look for the first commit between `opts.good` and `opts.bad` in which a benchmark is
+    /// worse than in `opts.good` by more than the threshold.
+    pub(crate) fn cmd_bisect(&self, opts: &BisectOptions) {
+        if let Err(e) = self.bisect(opts) {
+            eprintln!("error: {e}");
+            process::exit(1);
+        }
+    }
+
+    fn bisect(&self, opts: &BisectOptions) -> Result<(), String> {
+        let toplevel = git::toplevel(&opts.repo)?;
+        let toplevel = toplevel.canonicalize().unwrap_or(toplevel);
+        let good = git::resolve(&toplevel, &opts.good)?;
+        let bad = git::resolve(&toplevel, &opts.bad)?;
+        let commits = git::commits_between(&toplevel, &good, &bad)?;
+        if commits.is_empty() {
+            return Err(format!("{} isn't a descendant of {}", opts.bad, opts.good));
+        }
+        let mut skipped = BTreeSet::new();
+        for rev in &opts.skip {
+            let hash = git::resolve(&toplevel, rev)?;
+            match commits.iter().position(|c| *c == hash) {
+                Some(i) => skipped.insert(i),
+                None => return Err(format!("{rev} isn't between the good and bad commits")),
+            };
+        }
+
+        let worktree = git::Worktree::add(&toplevel, &good)?;
+        let load_config = || {
+            let mut config = self.load_config();
+            config.proc_execs = opts
+                .proc_execs
+                .unwrap_or(config.proc_execs.min(BISECT_PROC_EXECS));
+            config.retain_benchmarks(&opts.benchmarks)?;
+            let rebased = rebase_executors(&mut config, &toplevel, worktree.path());
+            Ok::<_, String>((config, rebased))
+        };
+        let (config, rebased) = load_config()?;
+        if rebased.is_empty() {
+            return Err(format!(
+                "no executor is built (with a `build` table) in {}, so there's nothing to bisect",
+                toplevel.display()
+            ));
+        }
+        if config.proc_execs < DEFAULT_MIN_SAMPLES {
+            return Err(format!(
+                "benchmarks need at least {DEFAULT_MIN_SAMPLES} pexecs to differ significantly"
+            ));
+        }
+        println!(
+            "haste: bisecting {} commits in {}, rebuilding {} with {} pexecs per benchmark",
+            commits.len(),
+            toplevel.display(),
+            rebased.join(", "),
+            config.proc_execs
+        );
+
+        // Build and benchmark `rev`, returning the datum's ID and results.
+        let step = |rev: &str, role: &str| {
+            let (mut config, _) = load_config()?;
+            worktree.checkout(rev)?;
+            runner::build_executors(&config)?;
+            let bench_opts = BenchOptions {
+                comment: Some(format!(
+                    "bisect {role}: {}",
+                    git::describe(worktree.path(), rev)?
+                )),
+                skip_build: true,
+                keep_going: true,
+                quiet: true,
+                ..Default::default()
+            };
+            let id = self.bench(&mut config, &bench_opts);
+            Ok::<_, String>((id, self.load_results(id)))
+        };
+
+        let describe = |i: usize| git::describe(&toplevel, &commits[i]);
+        let (good_id, good_results) =
+            step(&good, "good").map_err(|e| format!("can't benchmark the good commit: {e}"))?;
+        let mut tested = vec![(git::describe(&toplevel, &good)?, good_id, "good".to_owned())];
+        let mut search = Search::new(commits.len(), skipped);
+        while let Some(i) = search.next() {
+            let commit = describe(i)?;
+            println!("haste: testing {commit}");
+            let (id, verdict) = match step(&commits[i], "candidate") {
+                Err(e) => (None, Err(e)),
+                Ok((id, results)) => {
+                    let worse = regressions(
+                        &good_results,
+                        &results,
+                        &opts.metric,
+                        opts.threshold,
+                        opts.confidence,
+                    );
+                    (Some(id), worse)
+                }
+            };
+            let verdict = match verdict {
+                Ok(worse) if worse.is_empty() => {
+                    search.good(i);
+                    "good".to_owned()
+                }
+                Ok(worse) => {
+                    search.bad(i);
+                    format!("bad: {}", worse.join(", "))
+                }
+                Err(e) => {
+                    search.skip(i);
+                    format!("skipped: {e}")
+                }
+            };
+            println!("haste: {commit}: {verdict}");
+            if let Some(id) = id {
+                tested.push((commit, id, verdict));
+            }
+        }
+
+        println!("\nhaste: datums, compared with Datum{good_id}:");
+        for (commit, id, verdict) in &tested {
+            println!("  Datum{id} {commit}: {verdict}");
+        }
+        match search.result() {
+            Ok(i) => println!("\nhaste: the first bad commit is {}", describe(i)?),
+            Err(candidates) => {
+                println!("\nhaste: skipped commits hide the first bad commit, which is one of:");
+                for i in candidates {
+                    println!("  {}", describe(i)?);
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+//! A cache of the summary statistics of each datum, so that commands which scan many datums don't
+//! have to re-read and re-summarise every sample each time.
+//!
+//! Each datum's summaries are kept in `<state dir>/cache/<id>.toml`, along with a fingerprint of
+//! the datum from its store. If the fingerprint no longer matches, the datum has changed and the
+//! cached summaries are ignored. The cache is only ever an optimisation, so failing to read or
+//! write it is never an error.
+
+use crate::{ConfidenceLevel, SummaryStats};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The directory, inside the state directory, holding the cache.
+const CACHE_DIR: &str = "cache";
+
+#[derive(Default, Deserialize, Serialize)]
+struct CacheFile {
+    /// The fingerprint of the datum the summaries were computed from.
+    fingerprint: String,
+    /// Summaries of the time metric, by confidence level.
+    summaries: HashMap<String, HashMap<String, SummaryStats>>,
+}
+
+pub(crate) struct SummaryCache {
+    dir: PathBuf,
+}
+
+impl SummaryCache {
+    pub(crate) fn new(state_dir: &Path) -> Self {
+        SummaryCache {
+            dir: state_dir.join(CACHE_DIR),
+        }
+    }
+
+    fn path(&self, id: usize) -> PathBuf {
+        self.dir.join(format!("{id}.toml"))
+    }
+
+    /// Read the cache file of datum `id`, if it matches `fingerprint`.
+    fn read(&self, id: usize, fingerprint: &str) -> Option<CacheFile> {
+        let text = fs::read_to_string(self.path(id)).ok()?;
+        let file = toml::from_str::<CacheFile>(&text).ok()?;
+        (file.fingerprint == fingerprint).then_some(file)
+    }
+
+    /// The cached summaries of datum `id` at `confidence`, if it still has `fingerprint`.
+    pub(crate) fn get(
+        &self,
+        id: usize,
+        fingerprint: &str,
+        confidence: ConfidenceLevel,
+    ) -> Option<HashMap<String, SummaryStats>> {
+        self.read(id, fingerprint)?
+            .summaries
+            .remove(&confidence.to_string())
+    }
+
+    /// Cache the summaries of datum `id`, which has `fingerprint`, at `confidence`.
+    pub(crate) fn put(
+        &self,
+        id: usize,
+        fingerprint: &str,
+        confidence: ConfidenceLevel,
+        summaries: &HashMap<String, SummaryStats>,
+    ) {
+        let mut file = self.read(id, fingerprint).unwrap_or_else(|| CacheFile {
+            fingerprint: fingerprint.to_owned(),
+            ..Default::default()
+        });
+        file.summaries
+            .insert(confidence.to_string(), summaries.clone());
+        let Ok(text) = toml::to_string(&file) else {
+            return;
+        };
+        if fs::create_dir_all(&self.dir).is_err() {
+            return;
+        }
+        // Write then rename, so that a concurrent reader never sees a partial file.
+        let tmp = self.dir.join(format!("{id}.toml.{}", std::process::id()));
+        if fs::write(&tmp, text).is_ok() && fs::rename(&tmp, self.path(id)).is_err() {
+            fs::remove_file(&tmp).ok();
+        }
+    }
+
+    /// Forget the summaries of datum `id`.
+    pub(crate) fn remove(&self, id: usize) {
+        fs::remove_file(self.path(id)).ok();
+    }
+}
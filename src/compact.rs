@@ -0,0 +1,145 @@
+//! A compact binary encoding of a datum's results, for datums whose TOML would be unwieldy.
+//!
+//! The encoding starts with [MAGIC] and a version byte, then holds everything but the samples and
+//! timestamps as length-prefixed TOML, followed by the samples of each metric and the timestamps
+//! of each benchmark as little-endian binary. Samples are stored as their bit patterns, so they
+//! decode to exactly the values encoded.
+
+use crate::{ResultFile, TIME_METRIC};
+use std::collections::HashMap;
+
+/// The bytes a compact encoding starts with.
+const MAGIC: &[u8] = b"HASTEBIN";
+/// The version of the encoding.
+const VERSION: u8 = 1;
+
+/// Is `bytes` a compact encoding, rather than TOML?
+pub(crate) fn is_compact(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+fn put_u64(out: &mut Vec<u8>, n: u64) {
+    out.extend(n.to_le_bytes());
+}
+
+fn put_str(out: &mut Vec<u8>, s: &str) {
+    put_u64(out, s.len() as u64);
+    out.extend(s.as_bytes());
+}
+
+/// Encode `results` compactly.
+pub(crate) fn encode(results: &ResultFile) -> Vec<u8> {
+    let header = ResultFile {
+        data: HashMap::new(),
+        metrics: HashMap::new(),
+        timestamps: HashMap::new(),
+        ..results.clone()
+    };
+    let mut out = MAGIC.to_vec();
+    out.push(VERSION);
+    put_str(&mut out, &toml::to_string(&header).unwrap());
+
+    let mut series = [(TIME_METRIC, &results.data)]
+        .into_iter()
+        .chain(results.metrics.iter().map(|(m, d)| (m.as_str(), d)))
+        .flat_map(|(metric, data)| data.iter().map(move |(k, v)| (metric, k, v)))
+        .collect::<Vec<_>>();
+    // Sorted, so that the same results always encode the same way.
+    series.sort_by_key(|(metric, k, _)| (*metric, *k));
+    put_u64(&mut out, series.len() as u64);
+    for (metric, k, samples) in series {
+        put_str(&mut out, metric);
+        put_str(&mut out, k);
+        put_u64(&mut out, samples.len() as u64);
+        for x in samples {
+            put_u64(&mut out, x.to_bits());
+        }
+    }
+
+    let mut timestamps = results.timestamps.iter().collect::<Vec<_>>();
+    timestamps.sort_by_key(|(k, _)| *k);
+    put_u64(&mut out, timestamps.len() as u64);
+    for (k, ts) in timestamps {
+        put_str(&mut out, k);
+        put_u64(&mut out, ts.len() as u64);
+        for t in ts {
+            put_u64(&mut out, *t);
+        }
+    }
+    out
+}
+
+/// Reads the parts of an encoding in turn.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8], String> {
+        if self.bytes.len() < n {
+            return Err("truncated compact results".to_owned());
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    /// A length, which can't be longer than what's left to read `per_item` bytes at a time, so
+    /// that a corrupt length can't make us allocate without bound.
+    fn len(&mut self, per_item: usize) -> Result<usize, String> {
+        let n = self.u64()?;
+        match usize::try_from(n) {
+            Ok(n) if n.saturating_mul(per_item) <= self.bytes.len() => Ok(n),
+            _ => Err(format!("corrupt compact results: bad length {n}")),
+        }
+    }
+
+    fn str(&mut self) -> Result<String, String> {
+        let n = self.len(1)?;
+        String::from_utf8(self.take(n)?.to_vec())
+            .map_err(|_| "corrupt compact results: a string isn't UTF-8".to_owned())
+    }
+}
+
+/// Decode results encoded by [encode].
+pub(crate) fn decode(bytes: &[u8]) -> Result<ResultFile, String> {
+    let mut r = Reader { bytes };
+    if r.take(MAGIC.len())? != MAGIC {
+        return Err("not compact results".to_owned());
+    }
+    let version = r.take(1)?[0];
+    if version != VERSION {
+        return Err(format!(
+            "compact results version {version} isn't supported by this version of haste"
+        ));
+    }
+    let mut results = toml::from_str::<ResultFile>(&r.str()?).map_err(|e| e.to_string())?;
+    for _ in 0..r.len(1)? {
+        let metric = r.str()?;
+        let key = r.str()?;
+        let samples = (0..r.len(8)?)
+            .map(|_| Ok(f64::from_bits(r.u64()?)))
+            .collect::<Result<Vec<_>, String>>()?;
+        let data = if metric == TIME_METRIC {
+            &mut results.data
+        } else {
+            results.metrics.entry(metric).or_default()
+        };
+        data.insert(key, samples);
+    }
+    for _ in 0..r.len(1)? {
+        let key = r.str()?;
+        let ts = (0..r.len(8)?)
+            .map(|_| r.u64())
+            .collect::<Result<Vec<_>, String>>()?;
+        results.timestamps.insert(key, ts);
+    }
+    if !r.bytes.is_empty() {
+        return Err("corrupt compact results: trailing bytes".to_owned());
+    }
+    Ok(results)
+}
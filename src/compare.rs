@@ -0,0 +1,85 @@
+//! Comparing the distributions of two sets of samples, for drilling into a single benchmark.
+
+use crate::stats::{Descriptive, bimodal, ks_test};
+use crate::{NumFormat, histogram};
+
+/// The number of decimal places to show statistics with.
+const STATS_PRECISION: usize = 3;
+
+/// Count how many of each of `a` and `b` fall into each of the equal-width buckets of a histogram
+/// spanning both, returning the lower bound of each bucket, the width of the buckets and the
+/// counts.
+pub(crate) fn histogram_counts(a: &[f64], b: &[f64]) -> (f64, f64, Vec<[usize; 2]>) {
+    let (lo, width, counts) = histogram::bin_counts(&[a, b], histogram::DEFAULT_BINS);
+    (
+        lo,
+        width,
+        counts.into_iter().map(|c| [c[0], c[1]]).collect(),
+    )
+}
+
+/// The name and value of each statistic shown, in order.
+fn rows(s: &Descriptive) -> [(&'static str, Option<f64>); 7] {
+    [
+        ("mean", Some(s.mean)),
+        ("stddev", s.stddev),
+        ("min", Some(s.min)),
+        ("q1", Some(s.q1)),
+        ("median", Some(s.median)),
+        ("q3", Some(s.q3)),
+        ("max", Some(s.max)),
+    ]
+}
+
+/// Print a comparison of the distributions of `a` and `b`, headed `header1` and `header2`.
+pub(crate) fn print_comparison(header1: &str, a: &[f64], header2: &str, b: &[f64], unit: &str) {
+    let stats = [Descriptive::from_samples(a), Descriptive::from_samples(b)];
+    let values = stats
+        .iter()
+        .flat_map(|s| rows(s).map(|(_, v)| v.unwrap_or(0.)))
+        .collect::<Vec<_>>();
+    let fmt = NumFormat::new(&values, STATS_PRECISION, false, false);
+    let width = fmt.width.max(header1.len()).max(header2.len());
+    println!("{:8} {header1:>width$} {header2:>width$}", "");
+    println!("{:8} {:>width$} {:>width$}", "n", stats[0].n, stats[1].n);
+    let render = |v: Option<f64>| v.map_or("n/a".to_owned(), |v| fmt.render(v));
+    for ((name, v1), (_, v2)) in rows(&stats[0]).into_iter().zip(rows(&stats[1])) {
+        println!("{name:8} {:>width$} {:>width$}", render(v1), render(v2));
+    }
+
+    for (header, samples) in [(header1, a), (header2, b)] {
+        if let Some([lo, hi]) = bimodal(samples) {
+            println!(
+                "\n{header} looks bimodal: {} samples around {} {unit}, {} around {} {unit}",
+                lo.n,
+                fmt.render(lo.mean),
+                hi.n,
+                fmt.render(hi.mean)
+            );
+        }
+    }
+
+    let (d, p) = ks_test(a, b);
+    println!("\nKolmogorov-Smirnov: D = {d:.4}, p = {p:.4}");
+
+    let (lo, bucket_width, counts) = histogram_counts(a, b);
+    let most = counts.iter().flatten().copied().max().unwrap_or(1);
+    let bounds = histogram::bounds(lo, bucket_width, counts.len());
+    let bound_fmt = NumFormat::new(&bounds, STATS_PRECISION, false, false);
+    let bar = |count: usize| histogram::bar(count, most);
+    let range_width = bound_fmt.width * 2 + 3;
+    let bar_width = histogram::BAR_WIDTH + 4;
+    println!(
+        "\n{:range_width$}  {header1:bar_width$}  {header2}",
+        format!("({unit})")
+    );
+    for (i, [c1, c2]) in counts.iter().enumerate() {
+        println!(
+            "{} - {}  {}  {}",
+            bound_fmt.format(bounds[i]),
+            bound_fmt.format(bounds[i + 1]),
+            bar(*c1),
+            bar(*c2)
+        );
+    }
+}
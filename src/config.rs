@@ -1,8 +1,10 @@
 //! The haste config file, using serde.
 
-use serde::Deserialize;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use crate::nice;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
@@ -10,13 +12,799 @@ pub struct Config {
     /// The number of process executions (repetitions using fresh processes).
     pub(crate) proc_execs: usize,
     /// The number of in-process iterations (iterations inside each process).
+    ///
+    /// Zero leaves each harness to choose its own, which it must say it can in its answer to the
+    /// probe (see [crate::probe]): it's passed `auto` instead of a number, and reports the number
+    /// it ran on stdout as `@@HASTE_ITERS_USED: <n>`.
     pub(crate) inproc_iters: usize,
     /// The binaries to benchmark with.
     ///
-    /// Each entry in the `HashMap` is a name mapping to the path to the binary.
-    pub(crate) executors: HashMap<String, PathBuf>,
+    /// Each entry in the `HashMap` is a name mapping to either the path to the binary, or a table
+    /// describing how to build it (see [Executor]). For benchmarks with a `command`, the path may be
+    /// the literal string `shell` to run the command as-is.
+    pub(crate) executors: HashMap<String, Executor>,
     /// The benchmark suites to use.
     pub(crate) suites: HashMap<String, Suite>,
+    /// Named groups of executors that are compared with each other, e.g. a debug and a release
+    /// build, for `ab --group` and `compare --group`. Each lists its executors in order, the
+    /// first being the baseline the others are compared with.
+    #[serde(default)]
+    pub(crate) executor_groups: HashMap<String, Vec<String>>,
+    /// Named sets of benchmarks, which suites can share with `benchmarks_from`.
+    #[serde(default)]
+    pub(crate) benchmark_sets: HashMap<String, HashMap<String, Benchmark>>,
+    /// Collect hardware performance counters (instructions, cycles, task-clock) using `perf stat`
+    /// (Linux only).
+    #[serde(default)]
+    pub(crate) perf: bool,
+    /// If present, calibrate the number of in-process iterations of each benchmark before running
+    /// it, overriding `inproc_iters`.
+    pub(crate) calibrate: Option<Calibrate>,
+    /// If present, send each new datum to an external service.
+    pub(crate) notify: Option<Notify>,
+    /// If present, run benchmarks over `ssh` on this host (e.g. `user@host`), unless an executor
+    /// specifies its own `remote`.
+    pub(crate) remote: Option<String>,
+    /// If present, how to comment datums made without a `--comment`.
+    pub(crate) auto_comment: Option<AutoComment>,
+    /// If present, kill any pexec that runs for longer than this (in seconds), unless its suite
+    /// or benchmark sets its own `max_pexec_secs`.
+    pub(crate) max_pexec_secs: Option<f64>,
+    /// How to encode the samples of new datums in the filesystem store. If absent, datums are
+    /// stored as TOML unless that would be large.
+    pub(crate) storage: Option<Storage>,
+    /// How many bytes of each of a pexec's stdout and stderr to keep. Output beyond this is
+    /// discarded from the middle, so that its start and end are kept. Defaults to
+    /// [DEFAULT_MAX_OUTPUT_BYTES].
+    pub(crate) max_output_bytes: Option<usize>,
+    /// When `diff` warns that the datums' environments may have changed between them.
+    #[serde(default)]
+    pub(crate) env_warnings: EnvWarnings,
+    /// Record the CPU's frequency and temperature immediately before and after each pexec, where
+    /// Linux's sysfs reports them, for `show --env`.
+    #[serde(default)]
+    pub(crate) sample_system: bool,
+    /// If present, the niceness (from -20, the highest priority, to 19, the lowest) to run pexecs
+    /// with, whatever haste's own.
+    pub(crate) nice: Option<i32>,
+    /// If present, the niceness to run haste itself, and the builds it runs, with.
+    pub(crate) haste_nice: Option<i32>,
+    /// If present, discard and rerun any pexec that takes more than this many times longer than
+    /// the CPU time it uses (Linux only), as a sign that it was held up by something other than
+    /// the benchmark. Benchmarks that mostly wait, e.g. on I/O, should leave this unset.
+    pub(crate) max_wall_cpu_ratio: Option<f64>,
+}
+
+/// How many bytes of each of a pexec's stdout and stderr are kept by default.
+pub(crate) const DEFAULT_MAX_OUTPUT_BYTES: usize = 4 * 1024 * 1024;
+
+/// How the filesystem store encodes the samples of a datum.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Storage {
+    /// Human-readable TOML.
+    Toml,
+    /// A compact binary encoding, which is much smaller and quicker to read.
+    Compact,
+}
+
+/// Where to take the comments of datums from when none is given.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AutoComment {
+    /// The branch and commit checked out in the current directory's git repository.
+    Git,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Notify {
+    /// The URL to POST a JSON summary of each new datum to.
+    pub(crate) url: String,
+    /// Extra HTTP headers to send.
+    #[serde(default)]
+    pub(crate) headers: HashMap<String, String>,
+    /// The name of an environment variable holding a bearer token to authenticate with.
+    pub(crate) token_env: Option<String>,
+    /// How long to wait for the request to complete (in seconds).
+    #[serde(default = "default_notify_timeout")]
+    pub(crate) timeout_secs: u64,
+}
+
+fn default_notify_timeout() -> u64 {
+    10
+}
+
+/// When `diff` warns that the environment may have changed between the datums, which could
+/// explain small differences between them.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct EnvWarnings {
+    /// Warn if the datums were collected more than this many days apart.
+    #[serde(default = "default_env_max_days")]
+    pub(crate) max_days: f64,
+    /// Warn if the machine was rebooted, or its kernel changed, between the datums.
+    #[serde(default = "default_env_reboots")]
+    pub(crate) reboots: bool,
+}
+
+impl Default for EnvWarnings {
+    fn default() -> Self {
+        EnvWarnings {
+            max_days: default_env_max_days(),
+            reboots: default_env_reboots(),
+        }
+    }
+}
+
+fn default_env_max_days() -> f64 {
+    7.
+}
+
+fn default_env_reboots() -> bool {
+    true
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(try_from = "ExecutorDef")]
+pub struct Executor {
+    /// The path to the binary.
+    pub(crate) path: PathBuf,
+    /// If present, how to build the binary before benchmarking.
+    pub(crate) build: Option<Build>,
+    /// If present, the host to run benchmarks on over `ssh`. `path` is then a path on that host.
+    pub(crate) remote: Option<String>,
+    /// If the binary doesn't exist, skip the executor rather than failing.
+    pub(crate) allow_missing: bool,
+    /// What to multiply `inproc_iters` by for this executor, so that much slower or faster
+    /// executors can run for a similar time.
+    pub(crate) iters_scale: f64,
+    /// Names the executor used to have, under which older datums recorded it.
+    pub(crate) aliases: Vec<String>,
+}
+
+/// The forms an executor can take in the config file.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ExecutorDef {
+    /// Just the path to the binary.
+    Path(PathBuf),
+    Table(ExecutorTable),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExecutorTable {
+    /// The path to the binary. Defaults to the build artifact if there is a `build` table.
+    path: Option<PathBuf>,
+    build: Option<Build>,
+    remote: Option<String>,
+    #[serde(default)]
+    allow_missing: bool,
+    iters_scale: Option<f64>,
+    #[serde(default)]
+    aliases: Vec<String>,
+}
+
+impl TryFrom<ExecutorDef> for Executor {
+    type Error = String;
+
+    fn try_from(def: ExecutorDef) -> Result<Self, Self::Error> {
+        match def {
+            ExecutorDef::Path(path) => Ok(Executor {
+                path,
+                build: None,
+                remote: None,
+                allow_missing: false,
+                iters_scale: 1.,
+                aliases: Vec::new(),
+            }),
+            ExecutorDef::Table(ExecutorTable {
+                path,
+                build,
+                remote,
+                allow_missing,
+                iters_scale,
+                aliases,
+            }) => {
+                let path = match (path, &build) {
+                    (Some(path), _) => path,
+                    (None, Some(build)) => build.dir.join(&build.artifact),
+                    (None, None) => return Err("executor needs a path or a build table".to_owned()),
+                };
+                let iters_scale = iters_scale.unwrap_or(1.);
+                if !(iters_scale > 0. && iters_scale.is_finite()) {
+                    return Err(format!(
+                        "iters_scale must be a positive number, not {iters_scale}"
+                    ));
+                }
+                Ok(Executor {
+                    path,
+                    build,
+                    remote,
+                    allow_missing,
+                    iters_scale,
+                    aliases,
+                })
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Build {
+    /// The shell command that builds the executor.
+    pub(crate) command: String,
+    /// The directory to run the build command in.
+    #[serde(default = "default_build_dir")]
+    pub(crate) dir: PathBuf,
+    /// The path to the built binary, relative to `dir`.
+    pub(crate) artifact: PathBuf,
+}
+
+fn default_build_dir() -> PathBuf {
+    PathBuf::from(".")
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Calibrate {
+    /// The desired duration of a process execution (in milliseconds).
+    pub(crate) target_ms: f64,
+    /// The number of in-process iterations to measure when calibrating.
+    #[serde(default = "default_calibrate_iters")]
+    pub(crate) initial_iters: usize,
+}
+
+fn default_calibrate_iters() -> usize {
+    1
+}
+
+impl Config {
+    /// The host that benchmarks for the executor `executor_name` run on, or `None` if they run
+    /// locally.
+    pub(crate) fn remote_for(&self, executor_name: &str) -> Option<&str> {
+        self.executors[executor_name]
+            .remote
+            .as_deref()
+            .or(self.remote.as_deref())
+    }
+
+    /// The number of in-process iterations to run benchmarks with under the executor
+    /// `executor_name`: `inproc_iters` scaled by its `iters_scale`, rounded, and at least one,
+    /// unless it's zero, which leaves harnesses to choose.
+    pub(crate) fn inproc_iters_for(&self, executor_name: &str) -> usize {
+        if self.inproc_iters == 0 {
+            return 0;
+        }
+        let scaled = self.inproc_iters as f64 * self.executors[executor_name].iters_scale;
+        (scaled.round() as usize).max(1)
+    }
+
+    /// How long a pexec of `bench` in `suite` may run before it's killed: the first of the
+    /// benchmark's, the suite's, and the global `max_pexec_secs` that is set, if any.
+    pub(crate) fn max_pexec_for(&self, suite: &Suite, bench: &Benchmark) -> Option<Duration> {
+        bench
+            .max_pexec_secs
+            .or(suite.max_pexec_secs)
+            .or(self.max_pexec_secs)
+            .map(Duration::from_secs_f64)
+    }
+
+    /// How long a pexec of `bench` in `suite` is expected to take, in milliseconds: the first of
+    /// the benchmark's and the suite's `expected_ms` that is set, if any.
+    pub(crate) fn expected_ms_for(&self, suite: &Suite, bench: &Benchmark) -> Option<f64> {
+        bench.expected_ms.or(suite.expected_ms)
+    }
+
+    /// How many unrecorded pexecs of `bench` in `suite` to run with the executor `executor_name`
+    /// before measuring it: the first of the benchmark's and the suite's `warmup_pexecs` that
+    /// gives a number for the executor, if any. The `warm` cache policy needs at least one.
+    pub(crate) fn warmup_pexecs_for(
+        &self,
+        suite: &Suite,
+        bench: &Benchmark,
+        executor_name: &str,
+    ) -> usize {
+        let n = [&bench.warmup_pexecs, &suite.warmup_pexecs]
+            .into_iter()
+            .flatten()
+            .find_map(|w| w.for_executor(executor_name))
+            .unwrap_or(0);
+        match suite.cache_policy {
+            CachePolicy::Warm => n.max(1),
+            _ => n,
+        }
+    }
+
+    /// How many bytes of each of a pexec's stdout and stderr to keep.
+    pub(crate) fn max_output_bytes(&self) -> usize {
+        self.max_output_bytes.unwrap_or(DEFAULT_MAX_OUTPUT_BYTES)
+    }
+
+    /// The local executors whose binaries don't exist, with their paths, sorted by name.
+    ///
+    /// Executors with a build table are only checked if `check_built`, since building them
+    /// creates their binaries.
+    pub(crate) fn missing_executors(&self, check_built: bool) -> Vec<(String, PathBuf)> {
+        let mut missing = self
+            .executors
+            .iter()
+            .filter(|(name, e)| {
+                self.remote_for(name).is_none()
+                    && e.path.as_os_str() != SHELL_EXECUTOR
+                    && (check_built || e.build.is_none())
+                    && !binary_exists(&e.path)
+            })
+            .map(|(name, e)| (name.clone(), e.path.clone()))
+            .collect::<Vec<_>>();
+        missing.sort();
+        missing
+    }
+
+    /// Remove all benchmarks not named in `names`, unless `names` is empty.
+    ///
+    /// It is an error for a name not to match any benchmark.
+    pub(crate) fn retain_benchmarks(&mut self, names: &[String]) -> Result<(), String> {
+        if names.is_empty() {
+            return Ok(());
+        }
+        for name in names {
+            if !self
+                .suites
+                .values()
+                .any(|s| s.benchmarks.contains_key(name))
+            {
+                return Err(format!("no benchmark named {name}"));
+            }
+        }
+        for suite in self.suites.values_mut() {
+            suite.benchmarks.retain(|name, _| names.contains(name));
+        }
+        Ok(())
+    }
+
+    /// Add the benchmarks of the sets each suite names in `benchmarks_from` to its `benchmarks`.
+    ///
+    /// A suite's own benchmarks override those of the same name in its sets. It is an error to
+    /// name an undefined set, or for two sets to define the same benchmark differently (unless the
+    /// suite overrides it).
+    pub(crate) fn expand_benchmark_sets(&mut self) -> Result<(), String> {
+        let mut suite_names = self.suites.keys().cloned().collect::<Vec<_>>();
+        suite_names.sort();
+        for suite_name in suite_names {
+            let suite = self.suites.get_mut(&suite_name).unwrap();
+            // Which set each inherited benchmark came from.
+            let mut from = HashMap::<&str, &str>::new();
+            for set_name in &suite.benchmarks_from {
+                let Some(set) = self.benchmark_sets.get(set_name) else {
+                    return Err(format!(
+                        "suite {suite_name}: no benchmark set named {set_name}"
+                    ));
+                };
+                let mut bench_names = set.keys().collect::<Vec<_>>();
+                bench_names.sort();
+                for bench_name in bench_names {
+                    let inline = suite.benchmarks.contains_key(bench_name)
+                        && !from.contains_key(bench_name.as_str());
+                    if inline {
+                        continue;
+                    }
+                    match from.get(bench_name.as_str()) {
+                        Some(other)
+                            if self.benchmark_sets[*other][bench_name] != set[bench_name] =>
+                        {
+                            return Err(format!(
+                                "suite {suite_name}: benchmark {bench_name} is defined \
+                                 differently in benchmark sets {other} and {set_name}"
+                            ));
+                        }
+                        Some(_) => (),
+                        None => {
+                            from.insert(bench_name, set_name);
+                            suite
+                                .benchmarks
+                                .insert(bench_name.clone(), set[bench_name].clone());
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that executor, suite and benchmark names can be told apart and shown in benchmark
+    /// keys, reporting every offending name.
+    ///
+    /// Names which differ only by case are rejected (though a benchmark may have exactly the
+    /// same name in several suites), as are empty names, names with leading or trailing
+    /// whitespace and, for executors and benchmarks, names containing `/`, which separates the
+    /// parts of a key.
+    fn check_names(&self) -> Result<(), String> {
+        // Each name, with a description of where it's defined.
+        let described = |kind: &str, names: Vec<&String>| {
+            let mut names = names
+                .into_iter()
+                .map(|n| (format!("{kind} {n}"), n.clone()))
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+        let execs = described("executor", self.executors.keys().collect());
+        let suites = described("suite", self.suites.keys().collect());
+        let mut benches = Vec::new();
+        for (suite, suite_name) in &suites {
+            let names = self.suites[suite_name].benchmarks.keys().collect();
+            benches.extend(
+                described("benchmark", names)
+                    .into_iter()
+                    .map(|(what, n)| (format!("{suite}: {what}"), n)),
+            );
+        }
+
+        let mut problems = Vec::new();
+        for (names, in_keys) in [(&execs, true), (&suites, false), (&benches, true)] {
+            let mut lowercase = HashMap::<String, &(String, String)>::new();
+            for entry @ (what, name) in names {
+                if name.is_empty() {
+                    problems.push(format!("{what}: the name is empty"));
+                } else if name.trim() != name {
+                    problems.push(format!(
+                        "{what}: the name has leading or trailing whitespace"
+                    ));
+                }
+                if in_keys && name.contains('/') {
+                    problems.push(format!("{what}: the name contains '/'"));
+                }
+                match lowercase.get(&name.to_lowercase()) {
+                    Some((other, other_name)) if other_name != name => {
+                        problems.push(format!("{other} and {what} differ only by case"));
+                    }
+                    Some(_) => (),
+                    None => {
+                        lowercase.insert(name.to_lowercase(), entry);
+                    }
+                }
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("bad names:\n  {}", problems.join("\n  ")))
+        }
+    }
+
+    /// Check that `nice` and `haste_nice` are niceness values, and that `nice` isn't used with
+    /// remote executors, whose pexecs run on another host.
+    pub(crate) fn check_nice(&self) -> Result<(), String> {
+        for (name, nice) in [("nice", self.nice), ("haste_nice", self.haste_nice)] {
+            if let Some(n) = nice
+                && !nice::RANGE.contains(&n)
+            {
+                return Err(format!(
+                    "{name} must be from {} to {}, not {n}",
+                    nice::RANGE.start(),
+                    nice::RANGE.end()
+                ));
+            }
+        }
+        if self.nice.is_some() && self.executors.keys().any(|e| self.remote_for(e).is_some()) {
+            return Err("nice can't be set for remote executors".to_owned());
+        }
+        Ok(())
+    }
+
+    /// The executors of the executor group `name`, baseline first.
+    pub(crate) fn executor_group(&self, name: &str) -> Result<&[String], String> {
+        self.executor_groups
+            .get(name)
+            .map(Vec::as_slice)
+            .ok_or_else(|| format!("no executor group named {name}"))
+    }
+
+    /// Check that each executor group has at least two executors, each of them defined once.
+    fn check_executor_groups(&self) -> Result<(), String> {
+        let mut names = self.executor_groups.keys().collect::<Vec<_>>();
+        names.sort();
+        for name in names {
+            let members = &self.executor_groups[name];
+            if members.len() < 2 {
+                return Err(format!(
+                    "executor group {name} needs at least two executors to compare"
+                ));
+            }
+            for (i, member) in members.iter().enumerate() {
+                if !self.executors.contains_key(member) {
+                    return Err(format!("executor group {name}: no executor named {member}"));
+                }
+                if members[..i].contains(member) {
+                    return Err(format!(
+                        "executor group {name}: executor {member} is listed twice"
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that each old name in an executor's or benchmark's `aliases` stands for only one
+    /// current executor or benchmark, so that the keys of older datums can be renamed
+    /// unambiguously. As a benchmark may be defined in several suites, those of the same name may
+    /// share aliases.
+    fn check_aliases(&self) -> Result<(), String> {
+        let mut exec_names = self.executors.keys().collect::<Vec<_>>();
+        exec_names.sort();
+        let mut claimed = HashMap::<&str, &str>::new();
+        for name in exec_names {
+            for alias in &self.executors[name].aliases {
+                if self.executors.contains_key(alias) {
+                    return Err(format!(
+                        "executor {name}: alias {alias} is the name of an executor"
+                    ));
+                }
+                match claimed.insert(alias, name) {
+                    Some(other) if other != name => {
+                        return Err(format!(
+                            "executors {other} and {name} both have the alias {alias}"
+                        ));
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        let mut suite_names = self.suites.keys().collect::<Vec<_>>();
+        suite_names.sort();
+        let bench_names = self
+            .suites
+            .values()
+            .flat_map(|s| s.benchmarks.keys())
+            .collect::<HashSet<_>>();
+        let mut claimed = HashMap::<&str, (&str, &str)>::new();
+        for suite_name in suite_names {
+            let suite = &self.suites[suite_name];
+            let mut names = suite.benchmarks.keys().collect::<Vec<_>>();
+            names.sort();
+            for name in names {
+                for alias in &suite.benchmarks[name].aliases {
+                    if bench_names.contains(alias) {
+                        return Err(format!(
+                            "suite {suite_name}: benchmark {name}: alias {alias} is the name of \
+                             a benchmark"
+                        ));
+                    }
+                    match claimed.insert(alias, (suite_name, name)) {
+                        Some((other_suite, other)) if other != name => {
+                            return Err(format!(
+                                "suite {other_suite}: benchmark {other} and suite {suite_name}: \
+                                 benchmark {name} both have the alias {alias}"
+                            ));
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that no benchmark must run `after` itself, directly or through other benchmarks,
+    /// reporting the first such cycle found.
+    fn check_after(&self) -> Result<(), String> {
+        // The benchmarks each benchmark runs after, by name, as benchmarks of the same name in
+        // different suites are ordered together.
+        let mut after = BTreeMap::<&str, BTreeSet<&str>>::new();
+        for suite in self.suites.values() {
+            for (name, bench) in &suite.benchmarks {
+                after
+                    .entry(name)
+                    .or_default()
+                    .extend(bench.after.iter().map(String::as_str));
+            }
+        }
+        // A depth-first search, in which `path` is the chain of benchmarks being visited.
+        fn visit<'a>(
+            name: &'a str,
+            after: &BTreeMap<&'a str, BTreeSet<&'a str>>,
+            path: &mut Vec<&'a str>,
+            done: &mut BTreeSet<&'a str>,
+        ) -> Result<(), String> {
+            if let Some(i) = path.iter().position(|n| *n == name) {
+                let mut cycle = path[i..].to_vec();
+                cycle.push(name);
+                return Err(format!(
+                    "benchmarks must run after each other in a cycle: {}",
+                    cycle.join(" -> ")
+                ));
+            }
+            if !done.insert(name) {
+                return Ok(());
+            }
+            path.push(name);
+            for dep in after.get(name).into_iter().flatten() {
+                visit(dep, after, path, done)?;
+            }
+            path.pop();
+            Ok(())
+        }
+        let mut done = BTreeSet::new();
+        for name in after.keys() {
+            visit(name, &after, &mut Vec::new(), &mut done)?;
+        }
+        Ok(())
+    }
+
+    /// Check the config for errors that deserialisation can't catch.
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        self.check_names()?;
+        self.check_aliases()?;
+        if self.perf && self.executors.keys().any(|e| self.remote_for(e).is_some()) {
+            return Err("perf counters can't be collected for remote executors".to_owned());
+        }
+        self.check_nice()?;
+        self.check_executor_groups()?;
+        if self.inproc_iters == 0 && self.calibrate.is_some() {
+            return Err(
+                "calibrate can't be used with inproc_iters = 0, which leaves harnesses to choose"
+                    .to_owned(),
+            );
+        }
+        let mut exec_names = self.executors.keys().collect::<Vec<_>>();
+        exec_names.sort();
+        for exec_name in exec_names {
+            if self.executors[exec_name].build.is_some() && self.remote_for(exec_name).is_some() {
+                return Err(format!(
+                    "executor {exec_name}: remote executors can't have a build table"
+                ));
+            }
+        }
+        check_secs("max_pexec_secs", self.max_pexec_secs)?;
+        if let Some(r) = self.max_wall_cpu_ratio
+            && !(r.is_finite() && r >= 1.)
+        {
+            return Err(format!("max_wall_cpu_ratio must be at least 1, not {r}"));
+        }
+        let max_days = self.env_warnings.max_days;
+        if !(max_days.is_finite() && max_days >= 0.) {
+            return Err(format!(
+                "env_warnings.max_days must be zero or more, not {max_days}"
+            ));
+        }
+        let mut suite_names = self.suites.keys().collect::<Vec<_>>();
+        suite_names.sort();
+        for suite_name in suite_names {
+            let suite = &self.suites[suite_name];
+            check_secs("max_pexec_secs", suite.max_pexec_secs)
+                .and_then(|()| check_secs("max_suite_secs", suite.max_suite_secs))
+                .and_then(|()| check_secs("expected_ms", suite.expected_ms))
+                .map_err(|e| format!("suite {suite_name}: {e}"))?;
+            for name in suite.warmup_pexecs.iter().flat_map(WarmupPexecs::executors) {
+                if !self.executors.contains_key(name) {
+                    return Err(format!(
+                        "suite {suite_name}: warmup_pexecs given for unknown executor {name}"
+                    ));
+                }
+            }
+            if let Some(Harness::PerExecutor(map)) = &suite.harness {
+                let mut names = map.keys().collect::<Vec<_>>();
+                names.sort();
+                for name in names {
+                    if name != DEFAULT_HARNESS && !self.executors.contains_key(name) {
+                        return Err(format!(
+                            "suite {suite_name}: harness specified for unknown executor {name}"
+                        ));
+                    }
+                }
+            }
+            let mut bench_names = suite.benchmarks.keys().collect::<Vec<_>>();
+            bench_names.sort();
+            for bench_name in &bench_names {
+                let bench = &suite.benchmarks[*bench_name];
+                check_secs("max_pexec_secs", bench.max_pexec_secs)
+                    .and_then(|()| check_secs("expected_ms", bench.expected_ms))
+                    .map_err(|e| format!("suite {suite_name}: benchmark {bench_name}: {e}"))?;
+                if bench.command.is_some() && self.inproc_iters == 0 {
+                    return Err(format!(
+                        "suite {suite_name}: benchmark {bench_name} has a command, so can't be \
+                         run with inproc_iters = 0 (only harnesses can choose their iterations)"
+                    ));
+                }
+                if bench.command.is_some() && !bench.extra_args.is_empty() {
+                    return Err(format!(
+                        "suite {suite_name}: benchmark {bench_name} has both a command and \
+                         extra_args (extra_args are only for harnesses)"
+                    ));
+                }
+                for name in bench
+                    .executors
+                    .iter()
+                    .flatten()
+                    .chain(&bench.exclude_executors)
+                {
+                    if !self.executors.contains_key(name) {
+                        return Err(format!(
+                            "suite {suite_name}: benchmark {bench_name} names unknown executor \
+                             {name}"
+                        ));
+                    }
+                }
+                if !self.executors.keys().any(|e| bench.runs_on(e)) {
+                    return Err(format!(
+                        "suite {suite_name}: benchmark {bench_name} doesn't run with any executor"
+                    ));
+                }
+                // The directories of remote benchmarks are on the remote host, so can't be
+                // checked here.
+                for name in bench.warmup_pexecs.iter().flat_map(WarmupPexecs::executors) {
+                    if !self.executors.contains_key(name) {
+                        return Err(format!(
+                            "suite {suite_name}: benchmark {bench_name}: warmup_pexecs given for \
+                             unknown executor {name}"
+                        ));
+                    }
+                }
+                for r in &bench.resources {
+                    if r.is_empty() || r.trim() != r {
+                        return Err(format!(
+                            "suite {suite_name}: benchmark {bench_name}: bad resource name {r:?}"
+                        ));
+                    }
+                }
+                for name in &bench.after {
+                    if !self
+                        .suites
+                        .values()
+                        .any(|s| s.benchmarks.contains_key(name))
+                    {
+                        return Err(format!(
+                            "suite {suite_name}: benchmark {bench_name} runs after unknown \
+                             benchmark {name}"
+                        ));
+                    }
+                }
+                let any_local = self.executors.keys().any(|e| self.remote_for(e).is_none());
+                if bench.dir.is_some() && any_local && !suite.dir_for(bench).is_dir() {
+                    return Err(format!(
+                        "suite {suite_name}: benchmark {bench_name}: directory {} does not exist",
+                        suite.dir_for(bench).display()
+                    ));
+                }
+            }
+            if suite.cache_policy == CachePolicy::Cold {
+                if !cfg!(target_os = "linux") {
+                    return Err(format!(
+                        "suite {suite_name}: cache_policy = \"cold\" is only supported on Linux"
+                    ));
+                }
+                if self.executors.keys().any(|e| self.remote_for(e).is_some()) {
+                    return Err(format!(
+                        "suite {suite_name}: cache_policy = \"cold\" can't be used with remote \
+                         executors"
+                    ));
+                }
+            }
+            if let Some(name) = &suite.calibration_benchmark
+                && suite.benchmarks.contains_key(name)
+            {
+                return Err(format!(
+                    "suite {suite_name}: calibration_benchmark {name} is also a benchmark"
+                ));
+            }
+            let needs_harness = suite.calibration_benchmark.is_some()
+                || suite.benchmarks.values().any(|b| b.command.is_none());
+            let mut exec_names = self.executors.keys().collect::<Vec<_>>();
+            exec_names.sort();
+            for exec_name in exec_names {
+                if needs_harness && suite.harness_for(exec_name).is_none() {
+                    return Err(format!(
+                        "suite {suite_name}: no harness for executor {exec_name} (and no default)"
+                    ));
+                }
+            }
+        }
+        self.check_after()
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -28,20 +816,255 @@ pub struct Suite {
     ///
     /// The harness should accept arguments of the form:
     /// ```
-    /// <harness> <benchmark-name> <inproc-iters> [<extra-arg0> ... <extra_argN>]
+    /// <harness> <benchmark-name> <inproc-iters> [<suite-extra-args>...] [<extra-args>...]
     /// ```
-    pub(crate) harness: PathBuf,
+    ///
+    /// This may only be omitted if every benchmark in the suite has a `command`.
+    pub(crate) harness: Option<Harness>,
     /// Extra environment to apply when running benchmarks in this suite (if any).
     #[serde(default)]
     pub(crate) env: HashMap<String, String>,
+    /// Extra arguments to pass to the harness for every benchmark in this suite, before each
+    /// benchmark's own `extra_args`.
+    ///
+    /// Unlike a benchmark's `extra_args`, these aren't part of the benchmark's key, so changing
+    /// them doesn't make it a different benchmark: `diff` instead reports the change as a
+    /// difference in parameters. Benchmarks with a `command` don't use them.
+    #[serde(default)]
+    pub(crate) extra_args: Vec<String>,
     /// Benchmarks in this suite.
+    #[serde(default)]
     pub(crate) benchmarks: HashMap<String, Benchmark>,
+    /// The names of benchmark sets whose benchmarks are also in this suite, unless overridden by
+    /// `benchmarks`.
+    #[serde(default)]
+    pub(crate) benchmarks_from: Vec<String>,
+    /// How to treat the operating system's file caches between process executions.
+    #[serde(default)]
+    pub(crate) cache_policy: CachePolicy,
+    /// If present, the name of a benchmark that does no work, which the harness is run with
+    /// `proc_execs` times for each executor before the suite's benchmarks, to measure the
+    /// harness's own overhead. It must not be one of `benchmarks`.
+    pub(crate) calibration_benchmark: Option<String>,
+    /// What the suite is, for people who don't know it by name.
+    pub(crate) description: Option<String>,
+    /// If present, kill any pexec of this suite that runs for longer than this (in seconds),
+    /// unless its benchmark sets its own `max_pexec_secs`. Overrides the global setting.
+    pub(crate) max_pexec_secs: Option<f64>,
+    /// If present, once the pexecs of this suite have taken this long (in seconds) with an
+    /// executor, skip the rest of its pexecs with that executor.
+    pub(crate) max_suite_secs: Option<f64>,
+    /// If present, how many unrecorded pexecs to run of each of this suite's benchmarks before
+    /// measuring it, unless the benchmark sets its own `warmup_pexecs`.
+    pub(crate) warmup_pexecs: Option<WarmupPexecs>,
+    /// If present, how long a pexec of each of this suite's benchmarks is expected to take (in
+    /// milliseconds), unless the benchmark sets its own `expected_ms`.
+    pub(crate) expected_ms: Option<f64>,
 }
 
+/// How the operating system's file caches are treated between process executions, to reduce
+/// the variance of I/O-heavy benchmarks.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CachePolicy {
+    /// Leave the caches alone.
+    #[default]
+    None,
+    /// Run one unrecorded process execution of each benchmark before measuring it (or its
+    /// `warmup_pexecs`, if more), so that the files it uses are cached for every measured one.
+    Warm,
+    /// Drop the caches before every process execution (Linux only, and requires permission to
+    /// write to `/proc/sys/vm/drop_caches`).
+    Cold,
+}
+
+impl std::fmt::Display for CachePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CachePolicy::None => "none",
+            CachePolicy::Warm => "warm",
+            CachePolicy::Cold => "cold",
+        })
+    }
+}
+
+impl Suite {
+    /// The directory to run `bench` in.
+    pub(crate) fn dir_for(&self, bench: &Benchmark) -> PathBuf {
+        match &bench.dir {
+            Some(dir) => self.dir.join(dir),
+            None => self.dir.clone(),
+        }
+    }
+
+    /// The path to run `harness` by when running `bench`.
+    ///
+    /// Relative harness paths are relative to the suite's `dir`, even if the benchmark runs
+    /// somewhere else. Bare names are left alone, to be looked up in `PATH`.
+    pub(crate) fn resolve_harness(&self, harness: &Path, bench: &Benchmark) -> PathBuf {
+        let bare = harness.parent().is_none_or(|p| p.as_os_str().is_empty());
+        let Some(bench_dir) = &bench.dir else {
+            return harness.to_owned();
+        };
+        if harness.is_absolute() || bare {
+            harness.to_owned()
+        } else if bench_dir
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            // Climb back up to the suite's directory. This works on remote hosts too.
+            let mut path = PathBuf::new();
+            for c in bench_dir.components() {
+                if let Component::Normal(_) = c {
+                    path.push("..");
+                }
+            }
+            path.join(harness)
+        } else if self.dir.is_absolute() {
+            self.dir.join(harness)
+        } else {
+            std::env::current_dir()
+                .unwrap()
+                .join(&self.dir)
+                .join(harness)
+        }
+    }
+
+    /// The harness to use when running this suite with the executor `executor_name`.
+    pub(crate) fn harness_for(&self, executor_name: &str) -> Option<&Path> {
+        match self.harness.as_ref()? {
+            Harness::Path(p) => Some(p),
+            Harness::PerExecutor(map) => map
+                .get(executor_name)
+                .or_else(|| map.get(DEFAULT_HARNESS))
+                .map(PathBuf::as_path),
+        }
+    }
+}
+
+/// Check that the setting `name`, if present, is a positive number of seconds.
+fn check_secs(name: &str, secs: Option<f64>) -> Result<(), String> {
+    match secs {
+        Some(s) if !(s.is_finite() && s > 0.) => Err(format!("{name} must be positive, not {s}")),
+        _ => Ok(()),
+    }
+}
+
+/// Does the binary at `path` exist? A bare name is looked up in `PATH`, as it will be when run.
+fn binary_exists(path: &Path) -> bool {
+    if path.parent().is_none_or(|p| p.as_os_str().is_empty()) {
+        std::env::var_os("PATH")
+            .is_some_and(|paths| std::env::split_paths(&paths).any(|d| d.join(path).is_file()))
+    } else {
+        path.exists()
+    }
+}
+
+/// The executor path which means "run the benchmark command directly with the shell".
+pub(crate) const SHELL_EXECUTOR: &str = "shell";
+
+/// The key in a per-executor harness map used for executors that aren't otherwise listed.
+const DEFAULT_HARNESS: &str = "default";
+
 #[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Harness {
+    /// The same harness is used for all executors.
+    Path(PathBuf),
+    /// A map from executor name to the harness to use for it.
+    ///
+    /// The optional `default` entry is used for executors not in the map.
+    PerExecutor(HashMap<String, PathBuf>),
+}
+
+/// A benchmark's settings. The default is a benchmark with no settings of its own, which runs
+/// exactly as its suite says.
+#[derive(Clone, Default, Deserialize, Debug, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct Benchmark {
     /// Extra arguments to pass to this benchmark (if any).
     #[serde(default)]
     pub(crate) extra_args: Vec<String>,
+    /// A shell command to run instead of using the suite's harness.
+    ///
+    /// The command is run with `sh -c` and timed by haste. The number of in-process iterations is
+    /// available as `$ITERS`, and the executor as `$EXECUTOR`. Unless the executor is `shell`,
+    /// the command is prefixed with the executor.
+    pub(crate) command: Option<String>,
+    /// The directory to run this benchmark in, instead of the suite's `dir`.
+    ///
+    /// A relative path is relative to the suite's `dir`.
+    pub(crate) dir: Option<PathBuf>,
+    /// If present, run this benchmark only with these executors.
+    pub(crate) executors: Option<Vec<String>>,
+    /// Never run this benchmark with these executors.
+    #[serde(default)]
+    pub(crate) exclude_executors: Vec<String>,
+    /// What the benchmark does, for people who don't know it by name.
+    pub(crate) description: Option<String>,
+    /// A link to the benchmark's source or issue tracker, which reports link its name to.
+    pub(crate) url: Option<String>,
+    /// If present, kill any pexec of this benchmark that runs for longer than this (in seconds).
+    /// Overrides the suite's and the global settings.
+    pub(crate) max_pexec_secs: Option<f64>,
+    /// Things, such as a network port, that only one pexec can use at a time, so benchmarks which
+    /// share one are never run concurrently. As haste runs one pexec at a time, they only need
+    /// naming for now.
+    #[serde(default)]
+    pub(crate) resources: Vec<String>,
+    /// The benchmarks (in any suite) whose pexecs must all have run before any of this one's,
+    /// e.g. one that generates a dataset this one reads. Benchmarks not being run are ignored.
+    #[serde(default)]
+    pub(crate) after: Vec<String>,
+    /// If present, how many unrecorded pexecs of this benchmark to run before measuring it.
+    /// Overrides the suite's setting, except for executors a per-executor map doesn't list.
+    pub(crate) warmup_pexecs: Option<WarmupPexecs>,
+    /// Names the benchmark used to have, under which older datums recorded it.
+    #[serde(default)]
+    pub(crate) aliases: Vec<String>,
+    /// If present, how long a pexec of this benchmark is expected to take (in milliseconds), for
+    /// predicting how long a run will take. Overrides the suite's setting. As haste runs one pexec
+    /// at a time, the hints don't change the order pexecs run in.
+    pub(crate) expected_ms: Option<f64>,
+}
+
+/// How many unrecorded pexecs of a benchmark to run before measuring it, e.g. to fill a JIT's
+/// persistent cache.
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum WarmupPexecs {
+    /// The same number for every executor.
+    All(usize),
+    /// A map from executor name to the number for it.
+    PerExecutor(HashMap<String, usize>),
+}
+
+impl WarmupPexecs {
+    /// The number of warmup pexecs for the executor `executor_name`, if this says.
+    fn for_executor(&self, executor_name: &str) -> Option<usize> {
+        match self {
+            WarmupPexecs::All(n) => Some(*n),
+            WarmupPexecs::PerExecutor(map) => map.get(executor_name).copied(),
+        }
+    }
+
+    /// The executor names this gives numbers for, sorted.
+    fn executors(&self) -> Vec<&String> {
+        let mut names = match self {
+            WarmupPexecs::All(_) => Vec::new(),
+            WarmupPexecs::PerExecutor(map) => map.keys().collect(),
+        };
+        names.sort();
+        names
+    }
+}
+
+impl Benchmark {
+    /// Should this benchmark be run with the executor `executor_name`?
+    pub(crate) fn runs_on(&self, executor_name: &str) -> bool {
+        self.executors
+            .as_ref()
+            .is_none_or(|es| es.iter().any(|e| e == executor_name))
+            && !self.exclude_executors.iter().any(|e| e == executor_name)
+    }
 }
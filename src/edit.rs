@@ -0,0 +1,34 @@
+//! Editing the metadata of datums after they're collected, which also recreates the `extra.toml`
+//! of a datum that has lost it.
+
+use crate::App;
+use std::process;
+
+impl App {
+    /// Set the comment of datum `id` to `comment`, or remove it if `clear`, writing its metadata
+    /// back even if neither is given, so that missing metadata is recreated.
+    ///
+    /// Metadata that can't be read is left alone, rather than replaced by the defaults.
+    pub(crate) fn cmd_edit(&self, id: usize, comment: Option<String>, clear: bool) {
+        if !self.store.contains(id) {
+            eprintln!("error: no such datum {id}");
+            process::exit(1);
+        }
+        let recreating = !self.store.has_extra(id);
+        let mut extra = self.load_extra(id);
+        if comment.is_some() || clear {
+            extra.comment = comment;
+        }
+        if let Err(e) = self.store.update_extra(id, &extra) {
+            eprintln!("error: {e}");
+            process::exit(1);
+        }
+        if recreating {
+            println!("haste: recreated the metadata of datum {id}");
+        }
+        println!(
+            "Datum{id}: {}",
+            extra.comment.as_deref().unwrap_or("(no comment)")
+        );
+    }
+}
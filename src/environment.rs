@@ -0,0 +1,197 @@
+//! Recording the environment a datum was collected in, so that comparisons can warn when the
+//! environment may have changed between datums in ways which affect performance.
+
+use crate::config::EnvWarnings;
+use crate::{ExtraToml, new_table, stats};
+use comfy_table::{Cell, CellAlignment, Table};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// The file holding Linux's random ID of the current boot.
+const BOOT_ID: &str = "/proc/sys/kernel/random/boot_id";
+/// Where Linux's sysfs is mounted.
+pub(crate) const SYSFS: &str = "/sys";
+/// A fall in the CPU's frequency over a run, as a fraction, which is worth warning about.
+const FREQUENCY_DROP: f64 = 0.05;
+
+/// The CPU's reported frequency and temperature at one moment. Either is `None` if it couldn't
+/// be read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Reading {
+    /// The mean current frequency of the CPU's frequency policies, in MHz.
+    pub(crate) mhz: Option<f64>,
+    /// The highest temperature of the thermal zones, in degrees Celsius.
+    pub(crate) celsius: Option<f64>,
+}
+
+/// The readings taken immediately before and after a pexec.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct PexecReadings {
+    pub(crate) key: String,
+    pub(crate) before: Reading,
+    pub(crate) after: Reading,
+}
+
+/// The numbers in the files `dir/<prefix>*/file` that could be read.
+fn read_numbers(dir: &Path, prefix: &str, file: &str) -> Vec<f64> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter(|e| e.file_name().to_string_lossy().starts_with(prefix))
+        .filter_map(|e| fs::read_to_string(e.path().join(file)).ok())
+        .filter_map(|s| s.trim().parse::<f64>().ok())
+        .collect()
+}
+
+/// Read the CPU's frequency and temperature from the sysfs mounted at `sysfs`.
+pub(crate) fn read(sysfs: &Path) -> Reading {
+    let khz = read_numbers(
+        &sysfs.join("devices/system/cpu/cpufreq"),
+        "policy",
+        "scaling_cur_freq",
+    );
+    let millicelsius = read_numbers(&sysfs.join("class/thermal"), "thermal_zone", "temp");
+    Reading {
+        mhz: (!khz.is_empty()).then(|| stats::mean(&khz) / 1000.),
+        celsius: millicelsius.into_iter().map(|t| t / 1000.).reduce(f64::max),
+    }
+}
+
+/// A warning about what can't be read from the sysfs mounted at `sysfs`, if anything.
+pub(crate) fn unreadable(sysfs: &Path) -> Option<String> {
+    let reading = read(sysfs);
+    let missing = [
+        (reading.mhz.is_none(), "CPU frequency"),
+        (reading.celsius.is_none(), "temperature"),
+    ]
+    .into_iter()
+    .filter_map(|(missing, what)| missing.then_some(what))
+    .collect::<Vec<_>>();
+    (!missing.is_empty()).then(|| {
+        format!(
+            "sample_system is set, but the {} can't be read from {}, so won't be recorded",
+            missing.join(" and "),
+            sysfs.display()
+        )
+    })
+}
+
+/// The minimum, median and maximum of `values`, or `None` if there are none.
+pub(crate) fn spread(values: &[f64]) -> Option<[f64; 3]> {
+    let sorted = stats::sorted(values);
+    Some([
+        *sorted.first()?,
+        stats::quantile(&sorted, 0.5),
+        *sorted.last()?,
+    ])
+}
+
+/// A table of the minimum, median and maximum CPU frequency and temperature of each benchmark key
+/// in `readings`, or `None` if nothing was read.
+pub(crate) fn readings_table(readings: &[PexecReadings]) -> Option<Table> {
+    let mut by_key = BTreeMap::<&str, Vec<Reading>>::new();
+    for r in readings {
+        by_key
+            .entry(&r.key)
+            .or_default()
+            .extend([r.before, r.after]);
+    }
+    let cell = |values: Vec<f64>, places: usize| {
+        let s = spread(&values).map_or("n/a".to_owned(), |[lo, median, hi]| {
+            format!("{lo:.places$} / {median:.places$} / {hi:.places$}")
+        });
+        Cell::new(s).set_alignment(CellAlignment::Right)
+    };
+    let mut table = new_table(false);
+    table.set_header(vec![
+        Cell::new("Benchmark"),
+        Cell::new("CPU MHz (min / median / max)").set_alignment(CellAlignment::Right),
+        Cell::new("°C (min / median / max)").set_alignment(CellAlignment::Right),
+    ]);
+    let mut any = false;
+    for (k, rs) in by_key {
+        let mhz = rs.iter().filter_map(|r| r.mhz).collect::<Vec<_>>();
+        let celsius = rs.iter().filter_map(|r| r.celsius).collect::<Vec<_>>();
+        any |= !mhz.is_empty() || !celsius.is_empty();
+        table.add_row(vec![Cell::new(k), cell(mhz, 0), cell(celsius, 1)]);
+    }
+    any.then_some(table)
+}
+
+/// The median CPU frequencies (in MHz) of the first and last quarters of `readings`, taken in
+/// the order the pexecs ran, if the frequency fell by more than [FREQUENCY_DROP] between them.
+pub(crate) fn frequency_drop(readings: &[PexecReadings]) -> Option<(f64, f64)> {
+    let mhz = readings
+        .iter()
+        .flat_map(|r| [r.before.mhz, r.after.mhz])
+        .flatten()
+        .collect::<Vec<_>>();
+    let quarter = mhz.len() / 4;
+    if quarter == 0 {
+        return None;
+    }
+    let first = stats::quantile(&stats::sorted(&mhz[..quarter]), 0.5);
+    let last = stats::quantile(&stats::sorted(&mhz[mhz.len() - quarter..]), 0.5);
+    (last < first * (1. - FREQUENCY_DROP)).then_some((first, last))
+}
+
+/// The release of the running kernel, if it can be found.
+pub(crate) fn kernel() -> Option<String> {
+    let output = Command::new("uname").arg("-r").output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// The ID of the current boot, on Linux.
+pub(crate) fn boot_id() -> Option<String> {
+    fs::read_to_string(BOOT_ID)
+        .ok()
+        .map(|id| id.trim().to_owned())
+}
+
+/// Describe how the environments of the datums `extra1` and `extra2` differ, by the standards of
+/// `warnings`. Differences in the machine itself are only looked for if `same_hosts`.
+pub(crate) fn drift(
+    extra1: &ExtraToml,
+    extra2: &ExtraToml,
+    same_hosts: bool,
+    warnings: &EnvWarnings,
+) -> Vec<String> {
+    let mut drift = Vec::new();
+    if let (Some(t1), Some(t2)) = (extra1.start_time, extra2.start_time) {
+        let days = t1.abs_diff(t2) as f64 / (24. * 60. * 60.);
+        if days > warnings.max_days {
+            drift.push(format!("they were collected {days:.1} days apart"));
+        }
+    }
+    if same_hosts && warnings.reboots {
+        match (
+            (&extra1.kernel, &extra2.kernel),
+            (&extra1.boot_id, &extra2.boot_id),
+        ) {
+            ((Some(k1), Some(k2)), _) if k1 != k2 => {
+                drift.push(format!("the kernel changed from {k1} to {k2}"));
+            }
+            (_, (Some(b1), Some(b2))) if b1 != b2 => {
+                drift.push("the machine was rebooted between them".to_owned());
+            }
+            _ => (),
+        }
+    }
+    for (which, extra) in [("first", extra1), ("second", extra2)] {
+        if let Some((first, last)) = frequency_drop(&extra.system_readings) {
+            drift.push(format!(
+                "the CPU frequency fell from {first:.0} to {last:.0} MHz during the {which} \
+                 datum's run"
+            ));
+        }
+    }
+    drift
+}
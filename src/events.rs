@@ -0,0 +1,157 @@
+//! A stream of the events of a benchmarking run as it happens, for programs such as dashboards to
+//! follow: `haste bench --events jsonl[:<path|fd>]`.
+//!
+//! Each event is one JSON object on a line of its own, written as soon as it happens. Every object
+//! has the [VERSION] of its schema, the time in milliseconds since the Unix epoch, and the name of
+//! the event, alongside the event's own fields:
+//!
+//! ```text
+//! {"version":1,"time_ms":1700000000000,"event":"pexec_started","key":"fib/sh/","pexec":0}
+//! ```
+//!
+//! Unlike the run log, whose lines are for people, the schema only changes along with [VERSION].
+
+use crate::runner;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The version of the schema of events, which changes whenever an event's fields do.
+pub(crate) const VERSION: u32 = 1;
+
+/// Where events are written.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Destination {
+    Stdout,
+    /// A file, which is truncated, or a fifo.
+    Path(PathBuf),
+    /// A file descriptor which haste inherited already open.
+    Fd(u32),
+}
+
+/// Parse `--events`: `jsonl` for stdout, `jsonl:<fd>` for an inherited file descriptor, or
+/// `jsonl:<path>` for anything else. A file whose name is a number can be given as `./<n>`.
+pub(crate) fn parse_destination(s: &str) -> Result<Destination, String> {
+    let (format, dest) = match s.split_once(':') {
+        Some((format, dest)) => (format, Some(dest)),
+        None => (s, None),
+    };
+    if format != "jsonl" {
+        return Err(format!(
+            "unknown event format '{format}' (expected 'jsonl')"
+        ));
+    }
+    Ok(match dest {
+        None => Destination::Stdout,
+        Some("") => return Err("no path or file descriptor after 'jsonl:'".to_owned()),
+        Some(d) => match d.parse() {
+            Ok(fd) => Destination::Fd(fd),
+            Err(_) => Destination::Path(d.into()),
+        },
+    })
+}
+
+/// A benchmark that a run plans to run, as listed by [Event::RunStarted].
+#[derive(Debug, Serialize)]
+pub(crate) struct PlannedJob {
+    pub(crate) key: String,
+    pub(crate) suite: String,
+    pub(crate) inproc_iters: usize,
+    /// The number of measured pexecs planned.
+    pub(crate) pexecs: usize,
+    /// The number of unrecorded pexecs run before the first measured one.
+    pub(crate) warmup_pexecs: usize,
+    /// The benchmark's `expected_ms` hint, which isn't part of the event.
+    #[serde(skip)]
+    pub(crate) expected_ms: Option<f64>,
+}
+
+/// Something that happened during a run.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(crate) enum Event<'a> {
+    /// The run is about to start its first pexec.
+    RunStarted { jobs: Vec<PlannedJob> },
+    /// Measured pexec number `pexec` (counting from 0) of `key` has started.
+    PexecStarted { key: &'a str, pexec: usize },
+    /// A measured pexec succeeded, taking `elapsed_ms`.
+    PexecFinished {
+        key: &'a str,
+        pexec: usize,
+        elapsed_ms: f64,
+    },
+    /// A pexec of `key` failed: measured pexec number `pexec`, or a warmup pexec, which has no
+    /// number, if `warmup`. `status` is as recorded in the datum's failures, e.g. `1` or `timeout`.
+    BenchmarkFailed {
+        key: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pexec: Option<usize>,
+        warmup: bool,
+        status: &'a str,
+    },
+    /// The run is over and its results are stored as datum `datum`.
+    RunFinished { datum: usize },
+}
+
+/// One line of the stream.
+#[derive(Serialize)]
+struct Line<'a> {
+    version: u32,
+    time_ms: u64,
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+}
+
+/// An open stream of events.
+pub(crate) struct EventStream(RefCell<Box<dyn Write>>);
+
+impl EventStream {
+    /// Open `dest` for writing events to.
+    ///
+    /// Opening a fifo waits until something opens it for reading.
+    pub(crate) fn open(dest: &Destination) -> Result<Self, String> {
+        let out: Box<dyn Write> = match dest {
+            Destination::Stdout => Box::new(io::stdout()),
+            Destination::Path(path) => Box::new(
+                OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(path)
+                    .map_err(|e| format!("can't open events file {}: {e}", path.display()))?,
+            ),
+            // Reopening the descriptor, rather than taking it over, leaves it safely open.
+            Destination::Fd(fd) => Box::new(
+                OpenOptions::new()
+                    .write(true)
+                    .open(format!("/dev/fd/{fd}"))
+                    .map_err(|e| format!("can't open file descriptor {fd} for events: {e}"))?,
+            ),
+        };
+        Ok(EventStream(RefCell::new(out)))
+    }
+
+    /// Write `event` and flush it.
+    ///
+    /// As with the run log, failing to write an event never stops a run, so errors are ignored.
+    pub(crate) fn emit(&self, event: &Event) {
+        runner::between_pexecs("writing an event");
+        let time_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let line = Line {
+            version: VERSION,
+            time_ms,
+            event,
+        };
+        let mut out = self.0.borrow_mut();
+        if let Ok(json) = serde_json::to_string(&line) {
+            writeln!(out, "{json}").ok();
+        }
+        out.flush().ok();
+    }
+}
@@ -0,0 +1,231 @@
+//! Experiments: named groups of the datums made during one investigation. While an experiment is
+//! active, every datum created is marked as part of it, so that the investigation can be listed,
+//! summarised and removed as a whole rather than picked out of everything else by ID.
+
+use crate::{App, ExtraToml, format_timestamp, new_table};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::{fs, process};
+
+/// The file in the state directory recording which experiment is active.
+const STATE_FILE: &str = "state.toml";
+
+/// The contents of `state.toml`.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct State {
+    /// The experiment that datums created now are part of, if any.
+    pub(crate) experiment: Option<String>,
+}
+
+impl State {
+    /// Make `name` the active experiment, unless another is already active: experiments don't
+    /// nest or overlap.
+    pub(crate) fn start(&mut self, name: &str) -> Result<(), String> {
+        if name.is_empty() || name.trim() != name {
+            return Err(format!(
+                "experiment names can't be empty or have surrounding whitespace: {name:?}"
+            ));
+        }
+        match &self.experiment {
+            Some(active) if active == name => Err(format!("experiment {name} is already active")),
+            Some(active) => Err(format!(
+                "experiment {active} is still active: end it with `haste experiment end` before \
+                 starting {name}"
+            )),
+            None => {
+                self.experiment = Some(name.to_owned());
+                Ok(())
+            }
+        }
+    }
+
+    /// End the active experiment, returning its name.
+    pub(crate) fn end(&mut self) -> Result<String, String> {
+        self.experiment
+            .take()
+            .ok_or_else(|| "no experiment is active".to_owned())
+    }
+}
+
+/// The datums of one experiment.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Summary {
+    pub(crate) name: String,
+    /// The IDs of its datums, in order.
+    pub(crate) ids: Vec<usize>,
+    /// When its first datum started being collected, if known.
+    pub(crate) first: Option<u64>,
+    /// When its last datum finished being collected, if known.
+    pub(crate) last: Option<u64>,
+}
+
+/// Summarise the experiments of the datums `extras`, in ID order, as the experiments were started.
+pub(crate) fn summarise(extras: &[(usize, ExtraToml)]) -> Vec<Summary> {
+    let mut summaries = Vec::<Summary>::new();
+    for (id, extra) in extras {
+        let Some(name) = &extra.experiment else {
+            continue;
+        };
+        let i = match summaries.iter().position(|s| s.name == *name) {
+            Some(i) => i,
+            None => {
+                summaries.push(Summary {
+                    name: name.clone(),
+                    ids: Vec::new(),
+                    first: None,
+                    last: None,
+                });
+                summaries.len() - 1
+            }
+        };
+        let s = &mut summaries[i];
+        s.ids.push(*id);
+        s.first = match (s.first, extra.start_time) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        s.last = s.last.max(extra.end_time.or(extra.start_time));
+    }
+    summaries
+}
+
+impl App {
+    fn state_file(&self) -> PathBuf {
+        self.state_dir.join(STATE_FILE)
+    }
+
+    /// Load the state directory's `state.toml`, which is empty if there isn't one.
+    pub(crate) fn load_state(&self) -> Result<State, String> {
+        let path = self.state_file();
+        match fs::read_to_string(&path) {
+            Ok(s) => toml::from_str(&s).map_err(|e| format!("{}: {e}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(State::default()),
+            Err(e) => Err(format!("failed to read {}: {e}", path.display())),
+        }
+    }
+
+    /// Replace the state directory's `state.toml` with `state`, never leaving it partially
+    /// written.
+    fn save_state(&self, state: &State) -> Result<(), String> {
+        let path = self.state_file();
+        let tmp = path.with_extension("toml.tmp");
+        fs::create_dir_all(&self.state_dir)
+            .and_then(|_| fs::write(&tmp, toml::to_string(state).unwrap()))
+            .and_then(|_| fs::rename(&tmp, &path))
+            .map_err(|e| format!("failed to write {}: {e}", path.display()))
+    }
+
+    /// The experiment that a datum created now is part of, if any. A datum is worth more than its
+    /// experiment, so if `state.toml` can't be read, that's only warned about.
+    pub(crate) fn active_experiment(&self) -> Option<String> {
+        self.load_state()
+            .unwrap_or_else(|e| {
+                eprintln!("warning: {e}: the datum isn't marked as part of any experiment");
+                State::default()
+            })
+            .experiment
+    }
+
+    /// The metadata of every datum, in ID order. Unreadable metadata is warned about and treated
+    /// as empty.
+    fn all_extras(&self) -> Vec<(usize, ExtraToml)> {
+        self.store
+            .ids()
+            .into_iter()
+            .map(|id| {
+                let extra = self.store.try_load_extra(id).unwrap_or_else(|e| {
+                    eprintln!("warning: {}", e.lines().next().unwrap_or(""));
+                    ExtraToml::default()
+                });
+                (id, extra)
+            })
+            .collect()
+    }
+
+    /// The IDs of the datums that are part of experiment `name`.
+    pub(crate) fn experiment_ids(&self, name: &str) -> Vec<usize> {
+        self.all_extras()
+            .into_iter()
+            .filter(|(_, e)| e.experiment.as_deref() == Some(name))
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    fn update_state(&self, update: impl FnOnce(&mut State) -> Result<String, String>) -> String {
+        let mut state = self.load_state().unwrap_or_else(|e| {
+            eprintln!("error: {e}");
+            process::exit(1);
+        });
+        let name = update(&mut state).unwrap_or_else(|e| {
+            eprintln!("error: {e}");
+            process::exit(1);
+        });
+        if let Err(e) = self.save_state(&state) {
+            eprintln!("error: {e}");
+            process::exit(1);
+        }
+        name
+    }
+
+    /// Make `name` the active experiment, which every datum created until it's ended is part of.
+    pub(crate) fn cmd_experiment_start(&self, name: &str) {
+        self.update_state(|s| s.start(name).map(|_| name.to_owned()));
+        match self.experiment_ids(name).len() {
+            0 => println!("haste: started experiment {name}"),
+            n => println!("haste: resumed experiment {name}, which has {n} datums"),
+        }
+    }
+
+    /// End the active experiment.
+    pub(crate) fn cmd_experiment_end(&self) {
+        let name = self.update_state(State::end);
+        let n = self.experiment_ids(&name).len();
+        println!("haste: ended experiment {name}, which has {n} datums");
+    }
+
+    /// List each experiment, with how many datums it has and when they were collected.
+    pub(crate) fn cmd_experiment_list(&self) {
+        let active = self.load_state().unwrap_or_else(|e| {
+            eprintln!("error: {e}");
+            process::exit(1);
+        });
+        let mut summaries = summarise(&self.all_extras());
+        if let Some(name) = &active.experiment
+            && !summaries.iter().any(|s| s.name == *name)
+        {
+            summaries.push(Summary {
+                name: name.clone(),
+                ids: Vec::new(),
+                first: None,
+                last: None,
+            });
+        }
+        if summaries.is_empty() {
+            println!("haste: no experiments");
+            return;
+        }
+        let mut table = new_table(false);
+        table.set_header(vec!["Experiment", "Datums", "From", "To"]);
+        for s in summaries {
+            let name = if active.experiment.as_ref() == Some(&s.name) {
+                format!("{} (active)", s.name)
+            } else {
+                s.name
+            };
+            let when = |ts| {
+                if s.ids.is_empty() {
+                    String::new()
+                } else {
+                    format_timestamp(ts)
+                }
+            };
+            table.add_row(vec![
+                name,
+                s.ids.len().to_string(),
+                when(s.first),
+                when(s.last),
+            ]);
+        }
+        println!("{table}");
+    }
+}
@@ -0,0 +1,303 @@
+//! Explaining one row of a diff, as `haste diff --explain` does: every step from the samples to
+//! the row's classification, so that e.g. a 4% change called indistinguishable can be seen to be
+//! within the noise.
+
+use crate::{
+    DiffOptions, MAX_FIELLER_G, RatioCi, ResultFile, SignificanceTest, Statistic, SummaryStats,
+    fieller_g, higher_is_better, stats, tested_diff_rows,
+};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// One side of the comparison.
+pub(crate) struct Side<'a> {
+    pub(crate) name: &'a str,
+    /// The samples of the compared metric, before any trimming.
+    pub(crate) results: &'a ResultFile,
+    /// The summaries the diff compares, by benchmark key.
+    pub(crate) summaries: &'a HashMap<String, SummaryStats>,
+}
+
+/// The key of `sides` that `key` names: `key` itself, or the only key of the benchmark `key`, if
+/// it has just one.
+pub(crate) fn resolve_key(key: &str, sides: &[Side; 2]) -> Result<String, String> {
+    let [s1, s2] = sides;
+    let mut common = s1
+        .summaries
+        .keys()
+        .filter(|k| s2.summaries.contains_key(*k))
+        .collect::<Vec<_>>();
+    common.sort();
+    if common.iter().any(|k| *k == key) {
+        return Ok(key.to_owned());
+    }
+    let of_benchmark = common
+        .iter()
+        .filter(|k| k.split_once('/').is_some_and(|(b, _)| b == key))
+        .map(|k| k.as_str())
+        .collect::<Vec<_>>();
+    match (
+        of_benchmark.as_slice(),
+        [s1, s2].map(|s| s.summaries.contains_key(key)),
+    ) {
+        ([k], _) => Ok((*k).to_owned()),
+        ([], [true, false]) => Err(format!("only {} has {key}", s1.name)),
+        ([], [false, true]) => Err(format!("only {} has {key}", s2.name)),
+        ([], _) => Err(format!(
+            "{} and {} have no results for {key} to compare",
+            s1.name, s2.name
+        )),
+        (keys, _) => Err(format!(
+            "benchmark {key} has several keys, so give one of: {}",
+            keys.join(", ")
+        )),
+    }
+}
+
+fn num(x: f64) -> String {
+    format!("{x:.4}")
+}
+
+fn interval(s: &SummaryStats) -> String {
+    let (lo, hi) = s.bounds();
+    format!("[{}, {}]", num(lo), num(hi))
+}
+
+/// How `side`'s summary of `key` was derived.
+fn explain_side(out: &mut String, key: &str, side: &Side, opts: &DiffOptions) {
+    let samples = &side.results.data[key];
+    let s = &side.summaries[key];
+    let list = |samples: &[f64]| {
+        samples
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    writeln!(out, "{}:", side.name).unwrap();
+    writeln!(out, "  samples ({}): {}", samples.len(), list(samples)).unwrap();
+    let mean_steps = |out: &mut String, samples: &[f64], what: &str| {
+        let m = stats::mean_interval(samples, opts.confidence.zval());
+        writeln!(out, "  {what}: {}", num(m.mean)).unwrap();
+        match (m.stddev, m.stderr) {
+            (Some(sd), Some(se)) => {
+                writeln!(out, "  standard deviation: {}", num(sd)).unwrap();
+                writeln!(
+                    out,
+                    "  standard error: {} / √{} = {}",
+                    num(sd),
+                    m.n,
+                    num(se)
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "  critical value: z = {} (the normal distribution's, for a two-sided {}% \
+                     interval)",
+                    num(m.critical),
+                    opts.confidence
+                )
+                .unwrap();
+                writeln!(
+                    out,
+                    "  interval: {} ± {} × {} = {} ± {} = {}",
+                    num(m.mean),
+                    num(m.critical),
+                    num(se),
+                    num(m.mean),
+                    num(m.half_width),
+                    interval(s)
+                )
+                .unwrap();
+            }
+            _ => writeln!(
+                out,
+                "  interval: a single sample has no deviation, so {} ± 0",
+                num(m.mean)
+            )
+            .unwrap(),
+        }
+    };
+    match opts.stat {
+        Statistic::Mean => mean_steps(out, samples, "mean"),
+        Statistic::TrimmedMean(percent) => {
+            let trimmed = stats::trim(samples, percent);
+            writeln!(
+                out,
+                "  after trimming {percent}% from each end ({} of {} samples): {}",
+                trimmed.len(),
+                samples.len(),
+                list(&trimmed)
+            )
+            .unwrap();
+            mean_steps(out, &trimmed, &format!("{percent}% trimmed mean"));
+        }
+        Statistic::Percentile(percentile) => {
+            let p = percentile / 100.;
+            writeln!(out, "  p{percentile}: {}", num(s.mean)).unwrap();
+            writeln!(
+                out,
+                "  interval: the wider side of a {}% bootstrap interval from {} resamples: {} ± {} \
+                 = {}",
+                opts.confidence,
+                stats::BOOTSTRAP_RESAMPLES,
+                num(s.mean),
+                num(s.ci),
+                interval(s)
+            )
+            .unwrap();
+            if samples.len() < stats::quantile_min_samples(p) {
+                writeln!(
+                    out,
+                    "  (widened to at least the range of the samples, as it takes {} to place \
+                     p{percentile})",
+                    stats::quantile_min_samples(p)
+                )
+                .unwrap();
+            }
+        }
+    }
+}
+
+/// Explain how the diff of `sides` classifies `key`: how each side was summarised, how the two
+/// were compared, and what that makes the row.
+pub(crate) fn explain(key: &str, sides: &[Side; 2], opts: &DiffOptions) -> String {
+    let [side1, side2] = sides;
+    let (s1, s2) = (&side1.summaries[key], &side2.summaries[key]);
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{key}: {} vs. {}, comparing the {} of {} at {}% confidence\n",
+        side1.name,
+        side2.name,
+        opts.stat.describe(),
+        opts.metric,
+        opts.confidence
+    )
+    .unwrap();
+    for side in sides {
+        explain_side(&mut out, key, side, opts);
+        writeln!(out).unwrap();
+    }
+
+    let (d1, d2) = (
+        HashMap::from([(key.to_owned(), s1.clone())]),
+        HashMap::from([(key.to_owned(), s2.clone())]),
+    );
+    let rows = tested_diff_rows(
+        &d1,
+        &d2,
+        opts.min_samples,
+        higher_is_better(&opts.metric),
+        opts.test,
+    );
+    let row = &rows[0];
+    writeln!(out, "comparison:").unwrap();
+    match row.ratio {
+        Some(r) => writeln!(
+            out,
+            "  ratio: {} / {} = {} (a change of {:+.2}%)",
+            num(s2.mean),
+            num(s1.mean),
+            num(r),
+            row.change
+        )
+        .unwrap(),
+        None => writeln!(
+            out,
+            "  ratio: none, as {}'s value is too close to zero to divide by",
+            side1.name
+        )
+        .unwrap(),
+    }
+    let overlap = |out: &mut String| {
+        let ((l1, u1), (l2, u2)) = (s1.bounds(), s2.bounds());
+        let gap = l2.max(l1) - u1.min(u2);
+        if s1.ci_overlaps(s2) {
+            writeln!(
+                out,
+                "  the intervals {} and {} overlap (by {}), so the difference may be noise",
+                interval(s1),
+                interval(s2),
+                num(-gap)
+            )
+            .unwrap();
+        } else {
+            writeln!(
+                out,
+                "  the intervals {} and {} don't overlap ({} apart), so the values differ",
+                interval(s1),
+                interval(s2),
+                num(gap)
+            )
+            .unwrap();
+        }
+    };
+    match (opts.test, row.ratio_ci) {
+        (SignificanceTest::RatioCi, Some(ci)) => {
+            writeln!(
+                out,
+                "  Fieller's g = ({} / {})² = {}",
+                num(s1.ci),
+                num(s1.mean),
+                num(fieller_g(s1))
+            )
+            .unwrap();
+            match ci {
+                RatioCi::Bounds(lo, hi) => writeln!(
+                    out,
+                    "  the ratio's interval is [{}, {}], which {} 1, so the values {}",
+                    num(lo),
+                    num(hi),
+                    if ci.excludes_one() {
+                        "excludes"
+                    } else {
+                        "includes"
+                    },
+                    if ci.excludes_one() {
+                        "differ"
+                    } else {
+                        "may be the same"
+                    }
+                )
+                .unwrap(),
+                RatioCi::Unstable => writeln!(
+                    out,
+                    "  g is at least {MAX_FIELLER_G}, so {}'s interval is too wide for the \
+                     ratio's to mean anything, and the values can't be told apart",
+                    side1.name
+                )
+                .unwrap(),
+            }
+        }
+        (SignificanceTest::RatioCi, None) => {
+            writeln!(
+                out,
+                "  the ratio has no interval, so the intervals are compared instead"
+            )
+            .unwrap();
+            overlap(&mut out);
+        }
+        (SignificanceTest::Overlap, _) => overlap(&mut out),
+    }
+    if row.too_few {
+        writeln!(
+            out,
+            "  but with fewer than {} samples on a side (--min-samples), the values are never \
+             told apart",
+            opts.min_samples
+        )
+        .unwrap();
+    } else if [s1.mean, s1.ci, s2.mean, s2.ci]
+        .iter()
+        .any(|x| !x.is_finite())
+    {
+        writeln!(
+            out,
+            "  but the summaries aren't all numbers, so the values can't be told apart"
+        )
+        .unwrap();
+    }
+    writeln!(out, "\nresult: {}", row.summary()).unwrap();
+    out
+}
@@ -0,0 +1,136 @@
+//! Extending a datum with the benchmarks it lacks, so that benchmarks added to the config can be
+//! compared with an existing baseline without re-running the ones the baseline already has.
+
+use crate::config::Config;
+use crate::{BenchKey, ExtraToml, ResultFile, split_key};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// A record of a datum made by extending an earlier datum with benchmarks it lacked.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Composite {
+    /// The ID of the datum extended.
+    pub(crate) source: usize,
+    /// The benchmark keys measured for this datum, rather than copied from `source`, sorted.
+    pub(crate) measured: Vec<String>,
+}
+
+/// Restrict `config` to the benchmark keys missing from `base` (neither measured nor failed),
+/// with the executors `base` was run with, returning those keys, sorted.
+///
+/// The number of pexecs is set to that of `base`, if its benchmarks agree on one.
+pub(crate) fn restrict_to_missing(config: &mut Config, base: &ResultFile) -> Vec<String> {
+    let present = base
+        .data
+        .keys()
+        .chain(base.failures.keys())
+        .collect::<HashSet<_>>();
+    let executors = present
+        .iter()
+        .filter_map(|k| Some(split_key(k)?.1))
+        .collect::<HashSet<_>>();
+    let measured = restrict_keys(config, |key, executor| {
+        executors.contains(executor) && !present.contains(&key.to_owned())
+    });
+    let mut proc_execs = base.params.values().map(|p| p.proc_execs);
+    if let Some(n) = proc_execs.next()
+        && proc_execs.all(|m| m == n)
+    {
+        config.proc_execs = n;
+    }
+    measured
+}
+
+/// Restrict `config` to the benchmark keys that are `wanted`, given each key and its executor,
+/// returning those keys, sorted.
+pub(crate) fn restrict_keys(
+    config: &mut Config,
+    wanted: impl Fn(&str, &str) -> bool,
+) -> Vec<String> {
+    let mut kept = Vec::new();
+    let mut used = HashSet::new();
+    for suite in config.suites.values_mut() {
+        suite.benchmarks.retain(|bench_name, bench| {
+            let mut names = config
+                .executors
+                .keys()
+                .filter(|e| bench.runs_on(e))
+                .filter(|e| {
+                    let key = BenchKey {
+                        benchmark: bench_name.into(),
+                        executor: (*e).into(),
+                        extra_args: bench.extra_args.clone(),
+                    }
+                    .to_string();
+                    if !wanted(&key, e) {
+                        return false;
+                    }
+                    kept.push(key);
+                    true
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            names.sort();
+            used.extend(names.iter().cloned());
+            bench.exclude_executors.clear();
+            bench.executors = Some(names);
+            bench.executors.as_ref().is_some_and(|n| !n.is_empty())
+        });
+    }
+    config.suites.retain(|_, s| !s.benchmarks.is_empty());
+    config.executors.retain(|e, _| used.contains(e));
+    kept.sort();
+    kept
+}
+
+/// Add the results and per-benchmark metadata of the datum `base` to those of the new `results`
+/// and `extra`, which were measured for the benchmarks `base` lacked.
+pub(crate) fn merge(
+    results: &mut ResultFile,
+    extra: &mut ExtraToml,
+    base: ResultFile,
+    mut base_extra: ExtraToml,
+) {
+    results.data.extend(base.data);
+    for (metric, data) in base.metrics {
+        results.metrics.entry(metric).or_default().extend(data);
+    }
+    results.params.extend(base.params);
+    results.timestamps.extend(base.timestamps);
+    results.failures.extend(base.failures);
+
+    extra.comment = extra.comment.take().or(base_extra.comment);
+    extra.executor_group = extra.executor_group.take().or(base_extra.executor_group);
+    extra.truncated |= base_extra.truncated;
+    extra.skipped_pexecs.extend(base_extra.skipped_pexecs);
+    extra.over_budget.extend(base_extra.over_budget);
+    extra.calibrated_iters.extend(base_extra.calibrated_iters);
+    extra.overheads.extend(base_extra.overheads);
+    extra.warmup_pexecs.extend(base_extra.warmup_pexecs);
+    extra.discarded_pexecs.extend(base_extra.discarded_pexecs);
+    // The base's pexecs ran first.
+    base_extra
+        .system_readings
+        .append(&mut extra.system_readings);
+    extra.system_readings = base_extra.system_readings;
+    // Where both have something, it's the new run's that's current.
+    base_extra.builds.extend(extra.builds.drain());
+    extra.builds = base_extra.builds;
+    base_extra.hosts.extend(extra.hosts.drain());
+    extra.hosts = base_extra.hosts;
+    base_extra.descriptions.extend(extra.descriptions.drain());
+    extra.descriptions = base_extra.descriptions;
+    if let Some(mut urls) = base_extra.urls {
+        urls.extend(extra.urls.take().unwrap_or_default());
+        extra.urls = Some(urls);
+    }
+    base_extra.harnesses.extend(extra.harnesses.drain());
+    extra.harnesses = base_extra.harnesses;
+    base_extra.suites.extend(extra.suites.drain());
+    extra.suites = base_extra.suites;
+    base_extra
+        .cache_policies
+        .extend(extra.cache_policies.drain());
+    extra.cache_policies = base_extra.cache_policies;
+    extra.interleaving = extra.interleaving.take().or(base_extra.interleaving);
+}
@@ -0,0 +1,129 @@
+//! Checking that every datum in the store can be loaded and makes sense.
+
+use crate::{App, ExtraToml, ResultFile, TIME_METRIC, colour_enabled, new_table};
+use comfy_table::{Cell, Color};
+use std::process;
+
+impl App {
+    /// Check every datum in the store, printing the status of each, and exit non-zero if any is
+    /// broken. If `repair`, also quarantine the broken datums, so that other commands no longer
+    /// see them.
+    ///
+    /// Datums whose results are fine but which have no metadata are warned about, but aren't
+    /// broken: nothing that can be recreated from them is lost.
+    pub(crate) fn cmd_fsck(&self, repair: bool) {
+        let mut table = new_table(colour_enabled());
+        table.set_header(vec!["ID", "Status", "Problems"]);
+        let mut broken = Vec::new();
+        let mut no_extra = Vec::new();
+        for id in self.store.ids() {
+            let mut problems = match (
+                self.store.try_load_results(id),
+                self.store.try_load_extra(id),
+            ) {
+                (Ok(results), Ok(extra)) => datum_problems(&results, &extra),
+                (results, extra) => results.err().into_iter().chain(extra.err()).collect(),
+            };
+            let status = if problems.is_empty() && !self.store.has_extra(id) {
+                no_extra.push(id);
+                problems.push("no extra.toml, so no metadata".to_owned());
+                Cell::new("warning").fg(Color::Yellow)
+            } else if problems.is_empty() {
+                Cell::new("ok").fg(Color::Green)
+            } else {
+                broken.push(id);
+                Cell::new("broken").fg(Color::Red)
+            };
+            // Parse errors quote the offending lines, which are too much for a table.
+            let problems = problems
+                .iter()
+                .map(|p| p.lines().next().unwrap_or(""))
+                .collect::<Vec<_>>();
+            table.add_row(vec![Cell::new(id), status, Cell::new(problems.join("\n"))]);
+        }
+        println!("{table}");
+        if !no_extra.is_empty() {
+            println!();
+            println!(
+                "{} datums have no extra.toml: `haste edit <id>` recreates it",
+                no_extra.len()
+            );
+        }
+        if broken.is_empty() {
+            return;
+        }
+        println!();
+        println!(
+            "{} of {} datums are broken",
+            broken.len(),
+            self.store.ids().len()
+        );
+        if repair {
+            for id in &broken {
+                match self.store.quarantine(*id) {
+                    Ok(to) => println!("haste: moved datum {id} to {to}"),
+                    Err(e) => eprintln!("error: {e}"),
+                }
+            }
+        } else {
+            println!("use --repair to move them out of the way");
+        }
+        process::exit(1);
+    }
+}
+
+/// What's wrong with a datum that loaded successfully: samples that are missing or not numbers,
+/// and numbers of pexecs that don't agree with each other.
+pub(crate) fn datum_problems(results: &ResultFile, extra: &ExtraToml) -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Err(e) = results.validate() {
+        problems.push(e);
+    }
+    let mut metrics = results.metrics.iter().collect::<Vec<_>>();
+    metrics.sort_by_key(|(m, _)| *m);
+    let sources = [(TIME_METRIC, &results.data)]
+        .into_iter()
+        .chain(metrics.into_iter().map(|(m, d)| (m.as_str(), d)));
+    for (metric, data) in sources {
+        let mut keys = data.keys().collect::<Vec<_>>();
+        keys.sort();
+        for k in keys {
+            let n = data[k].len();
+            if n == 0 {
+                problems.push(format!("{k}: no {metric} samples"));
+            } else if metric != TIME_METRIC && n != results.data.get(k).map_or(0, Vec::len) {
+                problems.push(format!(
+                    "{k}: {n} {metric} samples but {} time samples",
+                    results.data.get(k).map_or(0, Vec::len)
+                ));
+            }
+        }
+    }
+    let mut keys = results.data.keys().collect::<Vec<_>>();
+    keys.sort();
+    for k in keys {
+        let n = results.data[k].len();
+        if let Some(ts) = results.timestamps.get(k)
+            && ts.len() != n
+        {
+            problems.push(format!("{k}: {n} samples but {} timestamps", ts.len()));
+        }
+        if let Some(p) = results.params.get(k)
+            && n > p.proc_execs
+        {
+            problems.push(format!(
+                "{k}: {n} samples but only {} pexecs were to be run",
+                p.proc_execs
+            ));
+        }
+        if let Some(rounds) = extra.interleaving.as_ref().and_then(|i| i.pairs.get(k))
+            && rounds.len() != n
+        {
+            problems.push(format!(
+                "{k}: {n} samples but {} paired rounds",
+                rounds.len()
+            ));
+        }
+    }
+    problems
+}
@@ -0,0 +1,111 @@
+//! Describing the state of a git repository, for commenting datums automatically, and checking
+//! out its commits in a separate worktree, for `haste bisect`.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Run `git` with `args` in `dir`, returning its trimmed stdout.
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let out = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| format!("couldn't run git: {e}"))?;
+    if !out.status.success() {
+        return Err(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&out.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).trim().to_owned())
+}
+
+/// A comment describing the commit checked out in the repository containing `dir`, of the form
+/// `branch @ hash "subject"`.
+///
+/// The branch is `HEAD` if no branch is checked out.
+pub(crate) fn comment(dir: &Path) -> Result<String, String> {
+    let branch = git(dir, &["rev-parse", "--abbrev-ref", "HEAD"])?;
+    let commit = git(dir, &["log", "-1", "--pretty=%h %s"])?;
+    let (hash, subject) = commit.split_once(' ').unwrap_or((&commit, ""));
+    Ok(format!("{branch} @ {hash} \"{subject}\""))
+}
+
+/// The full hash of the commit that `rev` names in the repository containing `dir`.
+pub(crate) fn resolve(dir: &Path, rev: &str) -> Result<String, String> {
+    git(
+        dir,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{rev}^{{commit}}"),
+        ],
+    )
+    .map_err(|_| format!("{rev} doesn't name a commit"))
+}
+
+/// The commits after `good` up to and including `bad` that descend from `good`, oldest first.
+pub(crate) fn commits_between(dir: &Path, good: &str, bad: &str) -> Result<Vec<String>, String> {
+    let range = format!("{good}..{bad}");
+    let commits = git(dir, &["rev-list", "--reverse", "--ancestry-path", &range])?;
+    Ok(commits.lines().map(str::to_owned).collect())
+}
+
+/// The short hash and subject of the commit `rev`, in the form `hash "subject"`.
+pub(crate) fn describe(dir: &Path, rev: &str) -> Result<String, String> {
+    let commit = git(dir, &["log", "-1", "--pretty=%h %s", rev])?;
+    let (hash, subject) = commit.split_once(' ').unwrap_or((&commit, ""));
+    Ok(format!("{hash} \"{subject}\""))
+}
+
+/// The top-level directory of the repository containing `dir`.
+pub(crate) fn toplevel(dir: &Path) -> Result<PathBuf, String> {
+    git(dir, &["rev-parse", "--show-toplevel"]).map(PathBuf::from)
+}
+
+/// A temporary worktree of a repository, with a detached `HEAD`, which is removed when dropped.
+pub(crate) struct Worktree {
+    repo: PathBuf,
+    dir: tempfile::TempDir,
+}
+
+impl Worktree {
+    /// Add a worktree of the repository containing `repo`, with `rev` checked out.
+    pub(crate) fn add(repo: &Path, rev: &str) -> Result<Self, String> {
+        let dir = tempfile::Builder::new()
+            .prefix("haste-worktree-")
+            .tempdir()
+            .map_err(|e| format!("couldn't make a directory for a worktree: {e}"))?;
+        let path = dir.path().to_str().unwrap();
+        git(repo, &["worktree", "add", "--detach", "--quiet", path, rev])?;
+        Ok(Worktree {
+            repo: repo.to_owned(),
+            dir,
+        })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    /// Check out `rev`, discarding any changes to tracked files, such as those a build made.
+    pub(crate) fn checkout(&self, rev: &str) -> Result<(), String> {
+        git(
+            self.path(),
+            &["checkout", "--detach", "--force", "--quiet", rev],
+        )
+        .map(|_| ())
+    }
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        let path = self.dir.path().to_str().unwrap();
+        if let Err(e) = git(&self.repo, &["worktree", "remove", "--force", path]) {
+            eprintln!("warning: {e} (`git worktree prune` will tidy up)");
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! ASCII histograms of samples, for eyeballing the shape of their distribution.
+
+use crate::NumFormat;
+
+/// The default number of bins in a histogram.
+pub(crate) const DEFAULT_BINS: usize = 10;
+/// The maximum width of a histogram bar.
+pub(crate) const BAR_WIDTH: usize = 30;
+/// The number of decimal places to show bin bounds with.
+const BOUND_PRECISION: usize = 3;
+
+/// Count how many of each of `sides` fall into each of `nbins` equal-width bins spanning them all,
+/// returning the lower bound of the first bin, the width of the bins and the counts of each bin.
+///
+/// If every sample is the same, there is only one bin.
+pub(crate) fn bin_counts(sides: &[&[f64]], nbins: usize) -> (f64, f64, Vec<Vec<usize>>) {
+    let all = || sides.iter().flat_map(|s| s.iter().copied());
+    let lo = all().fold(f64::INFINITY, f64::min);
+    let hi = all().fold(f64::NEG_INFINITY, f64::max);
+    let nbins = if hi > lo { nbins.max(1) } else { 1 };
+    let width = if hi > lo {
+        (hi - lo) / nbins as f64
+    } else {
+        1.
+    };
+    let mut counts = vec![vec![0; sides.len()]; nbins];
+    for (side, samples) in sides.iter().enumerate() {
+        for x in *samples {
+            let bin = (((x - lo) / width) as usize).min(nbins - 1);
+            counts[bin][side] += 1;
+        }
+    }
+    (lo, width, counts)
+}
+
+/// The bounds of each of the bins, from the lower bound of the first to the upper of the last.
+pub(crate) fn bounds(lo: f64, width: f64, nbins: usize) -> Vec<f64> {
+    (0..=nbins).map(|i| lo + width * i as f64).collect()
+}
+
+/// A bar representing `count` out of a maximum of `most`, padded to [BAR_WIDTH], followed by the
+/// count.
+pub(crate) fn bar(count: usize, most: usize) -> String {
+    let len = (count * BAR_WIDTH).div_ceil(most.max(1));
+    format!("{:BAR_WIDTH$} {count:>3}", "#".repeat(len))
+}
+
+/// Render a histogram of `samples` with `nbins` bins, one line per bin, each indented by
+/// `indent` spaces.
+pub(crate) fn render(samples: &[f64], nbins: usize, indent: usize) -> String {
+    if samples.is_empty() {
+        return format!("{:indent$}(no samples)\n", "");
+    }
+    let (lo, width, counts) = bin_counts(&[samples], nbins);
+    let bounds = bounds(lo, width, counts.len());
+    let fmt = NumFormat::new(&bounds, BOUND_PRECISION, false, false);
+    let most = counts.iter().flatten().copied().max().unwrap_or(1);
+    let mut out = String::new();
+    for (i, c) in counts.iter().enumerate() {
+        out.push_str(&format!(
+            "{:indent$}{} - {}  {}\n",
+            "",
+            fmt.format(bounds[i]),
+            fmt.format(bounds[i + 1]),
+            bar(c[0], most)
+        ));
+    }
+    out
+}
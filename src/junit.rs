@@ -0,0 +1,113 @@
+//! Writing a comparison of two datums as a JUnit XML report, so that CI systems show significant
+//! regressions as failed tests.
+
+use crate::{DiffOptions, DiffRow, TIME_METRIC, metric_unit, split_key};
+use std::collections::HashMap;
+
+/// Escape `s` for use in XML text or attribute values.
+pub(crate) fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// The class name and test name of the benchmark `key`: `suite/executor` (or just the executor if
+/// the suite isn't known), and the benchmark with its extra arguments.
+fn names(key: &str, suites: &HashMap<String, String>) -> (String, String) {
+    let Some((bench, exec)) = split_key(key) else {
+        return (String::new(), key.to_owned());
+    };
+    let class = match suites.get(key) {
+        Some(suite) => format!("{suite}/{exec}"),
+        None => exec.to_owned(),
+    };
+    (class, bench.trim_end_matches('/').to_owned())
+}
+
+/// A JUnit report with a test case for each of `rows`, comparing the datums called `names`, and
+/// for each of the `failed` benchmarks, which are given with the datums they failed in.
+///
+/// Rows which are significantly worse by more than `opts.fail_on_regression` percent (or by any
+/// amount, if it isn't set) are failures, and failed benchmarks are errors.
+pub(crate) fn report(
+    rows: &[DiffRow],
+    failed: &[(String, String)],
+    suites: &HashMap<String, String>,
+    [name1, name2]: [&str; 2],
+    opts: &DiffOptions,
+) -> String {
+    let threshold = opts.fail_on_regression.unwrap_or(0.);
+    let unit = metric_unit(&opts.metric).map_or(String::new(), |u| format!(" {u}"));
+    let mut cases = Vec::new();
+    let mut failures = 0;
+    for row in rows {
+        let (class, name) = names(row.key, suites);
+        // The time attribute is in seconds, and only makes sense for times.
+        let time = if opts.metric == TIME_METRIC {
+            format!(" time=\"{:.6}\"", row.s2.mean / 1000.)
+        } else {
+            String::new()
+        };
+        let mut case = format!(
+            "    <testcase classname=\"{}\" name=\"{}\"{time}",
+            escape(&class),
+            escape(&name)
+        );
+        if row.regresses_beyond(threshold) {
+            failures += 1;
+            let ratio = row.ratio.map_or("n/a".to_owned(), |r| format!("{r:.3}"));
+            let message = format!(
+                "{name2} is {}: {} mean {:.3}{unit} ±{:.3} vs. {:.3}{unit} ±{:.3} in {name1}, \
+                 ratio {ratio}",
+                row.summary(),
+                opts.metric,
+                row.s2.mean,
+                row.s2.ci,
+                row.s1.mean,
+                row.s1.ci,
+            );
+            let details = format!(
+                "{name1}: {} samples\n{name2}: {} samples\nsignificant at {}% confidence",
+                row.s1.n, row.s2.n, opts.confidence
+            );
+            case.push_str(&format!(
+                ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>",
+                escape(&message),
+                escape(&details)
+            ));
+        } else {
+            case.push_str("/>");
+        }
+        cases.push(case);
+    }
+    for (key, datums) in failed {
+        let (class, name) = names(key, suites);
+        cases.push(format!(
+            "    <testcase classname=\"{}\" name=\"{}\">\n      \
+             <error message=\"failed in {}\"/>\n    </testcase>",
+            escape(&class),
+            escape(&name),
+            escape(datums)
+        ));
+    }
+    let tests = rows.len() + failed.len();
+    let suite = escape(&format!("haste diff {name1} {name2}"));
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <testsuites name=\"{suite}\" tests=\"{tests}\" failures=\"{failures}\" errors=\"{}\">\n  \
+         <testsuite name=\"{suite}\" tests=\"{tests}\" failures=\"{failures}\" errors=\"{}\">\n\
+         {}\n  </testsuite>\n</testsuites>\n",
+        failed.len(),
+        failed.len(),
+        cases.join("\n")
+    )
+}
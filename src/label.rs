@@ -0,0 +1,69 @@
+//! Free-form `key=value` labels attached to datums after they're collected, so that they can be
+//! grouped and selected by more than their comment.
+
+use crate::{App, ExtraToml};
+use std::collections::HashMap;
+use std::process;
+
+/// Parse a `key=value` label. The value may be empty or contain `=`, but the key may not.
+pub(crate) fn parse(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((k, v)) if !k.trim().is_empty() => Ok((k.to_owned(), v.to_owned())),
+        _ => Err(format!("expected a label of the form key=value, not {s:?}")),
+    }
+}
+
+/// Does `extra` have every label in `filters`?
+pub(crate) fn matches(extra: &ExtraToml, filters: &[(String, String)]) -> bool {
+    filters
+        .iter()
+        .all(|(k, v)| extra.labels.get(k).is_some_and(|l| l == v))
+}
+
+/// `labels` as `key=value` pairs sorted by key, separated by spaces.
+pub(crate) fn format(labels: &HashMap<String, String>) -> String {
+    let mut labels = labels.iter().collect::<Vec<_>>();
+    labels.sort();
+    labels
+        .iter()
+        .map(|(k, v)| format!("{k}={v}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl App {
+    /// Remove the labels with the keys `remove` from datum `id`, then set the labels `set`,
+    /// replacing any with the same keys.
+    pub(crate) fn cmd_label(&self, id: usize, set: &[(String, String)], remove: &[String]) {
+        if !self.store.contains(id) {
+            eprintln!("error: no such datum {id}");
+            process::exit(1);
+        }
+        let mut extra = match self.store.try_load_extra(id) {
+            Ok(extra) => extra,
+            Err(e) => {
+                eprintln!("error: {e}");
+                process::exit(1);
+            }
+        };
+        for k in remove {
+            if !extra.labels.contains_key(k) {
+                eprintln!("error: Datum{id} has no label {k}");
+                process::exit(1);
+            }
+        }
+        for k in remove {
+            extra.labels.remove(k);
+        }
+        extra.labels.extend(set.iter().cloned());
+        if let Err(e) = self.store.update_extra(id, &extra) {
+            eprintln!("error: {e}");
+            process::exit(1);
+        }
+        if extra.labels.is_empty() {
+            println!("Datum{id}: no labels");
+        } else {
+            println!("Datum{id}: {}", format(&extra.labels));
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! A log of everything that happens during a benchmarking run.
+//!
+//! Each event is written as one line, either as `[<time>] <event> key=value ...` or as a JSON
+//! object, so that logs of long runs can be grepped or processed later.
+
+use crate::{format_timestamp_ms, runner};
+use clap::ValueEnum;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How log lines are written.
+#[derive(Copy, Clone, Debug, Default, PartialEq, ValueEnum)]
+pub(crate) enum LogFormat {
+    /// `[<time>] <event> key=value ...`
+    #[default]
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+/// A log file which events are appended to.
+pub(crate) struct RunLog {
+    path: PathBuf,
+    file: File,
+    format: LogFormat,
+}
+
+impl RunLog {
+    /// Open the log at `path` for appending, creating it (and its directory) if necessary.
+    pub(crate) fn create(path: &Path, format: LogFormat) -> Result<Self, String> {
+        if let Some(dir) = path.parent()
+            && !dir.as_os_str().is_empty()
+        {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("can't create directory {}: {e}", dir.display()))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("can't open log file {}: {e}", path.display()))?;
+        Ok(RunLog {
+            path: path.to_owned(),
+            file,
+            format,
+        })
+    }
+
+    /// The path of the log file.
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append an event with the given fields to the log.
+    ///
+    /// Failing to write the log never stops a run, so errors are ignored.
+    pub(crate) fn event(&self, event: &str, fields: &[(&str, String)]) {
+        runner::between_pexecs("writing the run log");
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let line = format_line(self.format, now, event, fields);
+        (&self.file).write_all(line.as_bytes()).ok();
+    }
+}
+
+/// Format one log line (including its newline) for an event at `time_ms`.
+pub(crate) fn format_line(
+    format: LogFormat,
+    time_ms: u64,
+    event: &str,
+    fields: &[(&str, String)],
+) -> String {
+    match format {
+        LogFormat::Text => {
+            let mut line = format!("[{}] {event}", format_timestamp_ms(time_ms));
+            for (k, v) in fields {
+                // Quote values that would otherwise be ambiguous to split on.
+                if v.is_empty() || v.contains([' ', '"', '=', '\n']) {
+                    line.push_str(&format!(" {k}={v:?}"));
+                } else {
+                    line.push_str(&format!(" {k}={v}"));
+                }
+            }
+            line.push('\n');
+            line
+        }
+        LogFormat::Json => {
+            let mut obj = serde_json::Map::new();
+            obj.insert("time_ms".to_owned(), time_ms.into());
+            obj.insert("event".to_owned(), event.into());
+            for (k, v) in fields {
+                obj.insert((*k).to_owned(), v.as_str().into());
+            }
+            format!("{}\n", serde_json::Value::Object(obj))
+        }
+    }
+}
@@ -1,53 +1,531 @@
 use clap::{Parser, Subcommand, ValueEnum};
-use comfy_table::{Cell, CellAlignment, Color, Table};
+use comfy_table::{Attribute, Cell, CellAlignment, Color, Table};
+use rand::{SeedableRng, rngs::StdRng, seq::SliceRandom};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{HashMap, HashSet},
+    cell::RefCell,
+    collections::{BTreeSet, HashMap, HashSet},
     env, fmt, fs,
-    path::PathBuf,
-    process,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process, thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
+mod advise;
+mod aliases;
+mod bisect;
+mod cache;
+mod compact;
+mod compare;
 mod config;
+mod edit;
+mod environment;
+mod events;
+mod experiment;
+mod explain;
+mod extend;
+mod fsck;
+mod git;
+mod histogram;
+mod junit;
+mod label;
+mod log;
+mod multidiff;
+mod nice;
+mod notify;
+mod order;
+mod output;
+mod paired;
+mod plot;
+mod predict;
+mod probe;
+mod rank;
+mod refine;
+mod remote;
+mod report;
 mod runner;
+mod selftest;
+mod smoke;
+mod stability;
+mod stats;
+mod store;
+mod summary;
+mod suspend;
+mod targets;
+mod tui;
+mod units;
+mod variants;
+mod within;
 
-#[derive(Copy, Clone, Debug, Default, PartialEq, ValueEnum)]
-enum ConfidenceLevel {
-    #[value(name = "90")]
-    CL90,
-    #[value(name = "95")]
-    CL95,
-    #[default]
-    #[value(name = "99")]
-    CL99,
-}
+use cache::SummaryCache;
+use config::CachePolicy;
+use log::{LogFormat, RunLog};
+use store::StoreKind;
+
+/// A confidence level, as a percentage in (0, 100).
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct ConfidenceLevel(f64);
 
 impl ConfidenceLevel {
+    const CL99: Self = Self(99.);
+
+    /// The critical value of the standard normal distribution for this (two-sided) confidence
+    /// level.
     fn zval(self) -> f64 {
+        inverse_normal_cdf(1. - (1. - self.0 / 100.) / 2.)
+    }
+
+    fn as_percent(self) -> f64 {
+        self.0
+    }
+}
+
+impl Default for ConfidenceLevel {
+    fn default() -> Self {
+        Self::CL99
+    }
+}
+
+impl fmt::Display for ConfidenceLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // `f64`'s `Display` omits a trailing `.0`, so whole percentages show as e.g. `99`.
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::str::FromStr for ConfidenceLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<f64>() {
+            Ok(p) if p > 0. && p < 100. => Ok(Self(p)),
+            _ => Err(format!(
+                "confidence level must be a percentage between 0 and 100 (exclusive), not {s}"
+            )),
+        }
+    }
+}
+
+/// The percentage trimmed from each end by `--stat trimmed-mean` if none is given.
+const DEFAULT_TRIM_PERCENT: f64 = 10.;
+
+/// The statistic that datums are compared by.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+enum Statistic {
+    #[default]
+    Mean,
+    /// The mean of the samples left after removing this percentage from each end.
+    TrimmedMean(f64),
+    /// This percentile of the samples, with a bootstrapped confidence interval.
+    Percentile(f64),
+}
+
+impl Statistic {
+    /// A description for table headers, e.g. `10% trimmed mean`.
+    fn describe(&self) -> String {
         match self {
-            Self::CL90 => 1.645,
-            Self::CL95 => 1.96,
-            Self::CL99 => 2.576,
+            Self::Mean => "mean".to_owned(),
+            Self::TrimmedMean(p) => format!("{p}% trimmed mean"),
+            Self::Percentile(p) => format!("p{p}"),
         }
     }
+}
 
-    fn as_percent(self) -> u8 {
+impl fmt::Display for Statistic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Self::CL90 => 90,
-            Self::CL95 => 95,
-            Self::CL99 => 99,
+            Self::Mean => write!(f, "mean"),
+            Self::TrimmedMean(p) => write!(f, "trimmed-mean:{p}"),
+            Self::Percentile(p) => write!(f, "p{p}"),
+        }
+    }
+}
+
+impl std::str::FromStr for Statistic {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, percent) = match s.split_once(':') {
+            Some((name, p)) => (name, Some(p)),
+            None => (s, None),
+        };
+        match (name, percent) {
+            ("mean", None) => Ok(Self::Mean),
+            ("trimmed-mean", None) => Ok(Self::TrimmedMean(DEFAULT_TRIM_PERCENT)),
+            ("trimmed-mean", Some(p)) => match p.parse::<f64>() {
+                Ok(p) if (0. ..=50.).contains(&p) => Ok(Self::TrimmedMean(p)),
+                _ => Err(format!(
+                    "the percentage trimmed from each end must be between 0 and 50, not {p}"
+                )),
+            },
+            (name, None) if let Some(p) = name.strip_prefix('p') => match p.parse::<f64>() {
+                Ok(p) if p > 0. && p < 100. => Ok(Self::Percentile(p)),
+                _ => Err(format!(
+                    "the percentile must be between 0 and 100 (exclusive), not {p}"
+                )),
+            },
+            _ => Err(format!(
+                "unknown statistic {s} (expected mean, trimmed-mean[:<percent>] or p<percentile>)"
+            )),
         }
     }
 }
 
+/// The inverse of the standard normal cumulative distribution function, for `p` in (0, 1).
+///
+/// This uses Peter Acklam's rational approximation, which has a relative error of less than
+/// 1.15e-9.
+fn inverse_normal_cdf(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969683028665376e+01,
+        2.209460984245205e+02,
+        -2.759285104469687e+02,
+        1.38357751867269e+02,
+        -3.066479806614716e+01,
+        2.506628277459239e+00,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e+01,
+        1.615858368580409e+02,
+        -1.556989798598866e+02,
+        6.680131188771972e+01,
+        -1.328068155288572e+01,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-03,
+        -3.223964580411365e-01,
+        -2.400758277161838e+00,
+        -2.549732539343734e+00,
+        4.374664141464968e+00,
+        2.938163982698783e+00,
+    ];
+    const D: [f64; 4] = [
+        7.784695709041462e-03,
+        3.224671290700398e-01,
+        2.445134137142996e+00,
+        3.754408661907416e+00,
+    ];
+    // The boundary between the central and tail regions.
+    const P_LOW: f64 = 0.02425;
+
+    // The tails are symmetric.
+    let tail = |p: f64| {
+        let q = (-2. * p.ln()).sqrt();
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.)
+    };
+    if p < P_LOW {
+        tail(p)
+    } else if p > 1. - P_LOW {
+        -tail(1. - p)
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.)
+    }
+}
+
 /// The `extra.toml` file for a datum
 #[derive(Default, Serialize, Deserialize)]
 struct ExtraToml {
     comment: Option<String>,
+    /// When the benchmark run started (seconds since the Unix epoch).
+    start_time: Option<u64>,
+    /// When the benchmark run ended (seconds since the Unix epoch).
+    end_time: Option<u64>,
+    /// How long the benchmark run took (in seconds).
+    duration_secs: Option<f64>,
+    /// Was the run cut short by `--max-duration`?
+    #[serde(default)]
+    truncated: bool,
+    /// For a truncated run, the number of pexecs that were not run, per benchmark key.
+    #[serde(default)]
+    skipped_pexecs: HashMap<String, usize>,
+    /// The number of pexecs that were not run because their suite's `max_suite_secs` ran out,
+    /// per benchmark key.
+    #[serde(default)]
+    over_budget: HashMap<String, usize>,
+    /// The number of in-process iterations chosen by calibration, per benchmark key.
+    ///
+    /// Empty if the run wasn't calibrated, in which case every benchmark used the configured
+    /// `inproc_iters`.
+    #[serde(default)]
+    calibrated_iters: HashMap<String, usize>,
+    /// The executors built before the run, by executor name.
+    #[serde(default)]
+    builds: HashMap<String, BuildRecord>,
+    /// The hostname of the machine that each executor ran on, by executor name.
+    #[serde(default)]
+    hosts: HashMap<String, String>,
+    /// If the pexecs of each benchmark alternated between executors, how.
+    interleaving: Option<Interleaving>,
+    /// The version of haste that created the datum.
+    haste_version: Option<String>,
+    /// The command line that created the datum.
+    #[serde(default)]
+    args: Vec<String>,
+    /// The config file used to create the datum.
+    config_file: Option<PathBuf>,
+    /// The cache policy of each suite, by suite name.
+    #[serde(default)]
+    cache_policies: HashMap<String, CachePolicy>,
+    /// The mean harness overhead (in milliseconds) measured for each benchmark key, for suites
+    /// with a `calibration_benchmark`.
+    #[serde(default)]
+    overheads: HashMap<String, f64>,
+    /// The number of unrecorded warmup pexecs run of each benchmark key that had any.
+    #[serde(default)]
+    warmup_pexecs: HashMap<String, usize>,
+    /// The number of measured pexecs of each benchmark key that had any discarded and rerun
+    /// because their times couldn't be trusted, e.g. as the machine was suspended.
+    #[serde(default)]
+    discarded_pexecs: HashMap<String, usize>,
+    /// Was the datum made by `haste smoke --save`, with one pexec of each benchmark?
+    #[serde(default)]
+    smoke: bool,
+    /// The executor group whose executors the datum was made to compare, e.g. by `ab --group`.
+    executor_group: Option<String>,
+    /// The niceness pexecs were run with, if set.
+    nice: Option<i32>,
+    /// The niceness haste itself was run with, if set.
+    haste_nice: Option<i32>,
+    /// The benchmark keys that weren't run because the config restricts which executors the
+    /// benchmark runs with.
+    #[serde(default)]
+    restricted: Vec<String>,
+    /// The executors which weren't run because their binaries were missing.
+    #[serde(default)]
+    skipped_executors: Vec<String>,
+    /// The configured description of each benchmark key that has one.
+    #[serde(default)]
+    descriptions: HashMap<String, String>,
+    /// The configured URL of each benchmark key that has one.
+    ///
+    /// Datums collected by older versions of haste lack this.
+    urls: Option<HashMap<String, String>>,
+    /// The suite of each benchmark key.
+    ///
+    /// Datums collected by older versions of haste lack this.
+    #[serde(default)]
+    suites: HashMap<String, String>,
+    /// The configured description of each suite that has one.
+    #[serde(default)]
+    suite_descriptions: HashMap<String, String>,
+    /// The seed of the random number generator the run used.
+    seed: Option<u64>,
+    /// If the datum was made by `bench --extend`, the datum it extended and what was measured.
+    composite: Option<extend::Composite>,
+    /// If the datum was made by `haste refine`, the datum it refined and how.
+    refinement: Option<refine::Refinement>,
+    /// The release of the kernel that haste ran on.
+    kernel: Option<String>,
+    /// The ID of the boot during which haste ran, on Linux.
+    boot_id: Option<String>,
+    /// Labels attached with `haste label`.
+    #[serde(default)]
+    labels: HashMap<String, String>,
+    /// The experiment that was active when the datum was created, if any.
+    experiment: Option<String>,
+    /// With `sample_system`, the CPU's frequency and temperature around each pexec, in the order
+    /// they ran.
+    #[serde(default)]
+    system_readings: Vec<environment::PexecReadings>,
+    /// What each suite's harness said it supports when probed, by suite and executor.
+    #[serde(default)]
+    harnesses: HashMap<String, HashMap<String, probe::Capabilities>>,
+}
+
+/// A record of how pexecs were interleaved between executors.
+///
+/// Within each benchmark, the executors took turns in the order of `executors`, so each round of
+/// turns gives a pair of samples measured under nearly the same conditions.
+#[derive(Serialize, Deserialize)]
+struct Interleaving {
+    executors: Vec<String>,
+    /// The benchmark key of each pexec, in the order they ran.
+    order: Vec<String>,
+    /// Benchmark key -> the round each sample was measured in, in the same order as the samples.
+    ///
+    /// Datums collected by older versions of haste lack this.
+    #[serde(default)]
+    pairs: HashMap<String, Vec<usize>>,
+}
+
+/// A record of an executor built before benchmarking.
+#[derive(Serialize, Deserialize)]
+struct BuildRecord {
+    /// The build command.
+    command: String,
+    /// How long the build took (in seconds).
+    duration_secs: f64,
+    /// The SHA-256 hash of the resulting binary.
+    sha256: String,
+}
+
+impl ExtraToml {
+    /// Describe the executors which ran on different hosts in `self` and `other`.
+    fn host_mismatches(&self, other: &ExtraToml) -> Vec<String> {
+        let mut names = self
+            .hosts
+            .keys()
+            .filter(|n| other.hosts.contains_key(*n))
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+            .into_iter()
+            .filter(|n| self.hosts[*n] != other.hosts[*n])
+            .map(|n| format!("{n}: {} vs. {}", self.hosts[n], other.hosts[n]))
+            .collect()
+    }
+
+    /// Describe how the versions of haste that created two datums differ, if they might behave
+    /// differently.
+    ///
+    /// Following Cargo's rules, versions are compatible if their leftmost non-zero components are
+    /// the same.
+    fn version_mismatch(&self, other: &ExtraToml) -> Option<String> {
+        let compat = |v: &str| {
+            let mut parts = v.split('.');
+            let major = parts.next().unwrap_or("").to_owned();
+            match (major.as_str(), parts.next()) {
+                ("0", Some(minor)) => format!("0.{minor}"),
+                _ => major,
+            }
+        };
+        match (&self.haste_version, &other.haste_version) {
+            (Some(v1), Some(v2)) if compat(v1) != compat(v2) => Some(format!("{v1} vs. {v2}")),
+            _ => None,
+        }
+    }
+
+    fn duration_str(&self) -> String {
+        match self.duration_secs {
+            Some(secs) => units::format_duration(Duration::from_secs_f64(secs)),
+            None => "unknown".to_owned(),
+        }
+    }
+}
+
+/// Format a Unix timestamp (in seconds) as a UTC date and time.
+///
+/// Returns "unknown" if there's no timestamp.
+fn format_timestamp(ts: Option<u64>) -> String {
+    let Some(ts) = ts else {
+        return "unknown".to_owned();
+    };
+    // Convert days since the epoch into a civil date, as per:
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = i64::try_from(ts / 86400).unwrap() + 719468;
+    let secs = ts % 86400;
+    let era = days.div_euclid(146097);
+    let doe = days.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC",
+        secs / 3600,
+        (secs % 3600) / 60,
+        secs % 60
+    )
+}
+
+/// Write a table of every time sample in `data` to `out`, in the order the pexecs ran.
+fn write_raw_samples(out: &mut dyn Write, data: &ResultFile) -> io::Result<()> {
+    let mut table = Table::new();
+    table.load_preset(comfy_table::presets::NOTHING);
+    table.set_header(vec![
+        Cell::new("Benchmark").set_alignment(CellAlignment::Left),
+        Cell::new("Pexec").set_alignment(CellAlignment::Right),
+        Cell::new("Started").set_alignment(CellAlignment::Left),
+        Cell::new("Time (ms)").set_alignment(CellAlignment::Right),
+    ]);
+    let mut keys = data.data.keys().collect::<Vec<_>>();
+    keys.sort();
+    for k in keys {
+        let timestamps = data.timestamps.get(k);
+        for (i, v) in data.data[k].iter().enumerate() {
+            let started = timestamps
+                .and_then(|t| t.get(i))
+                .map_or("unknown".to_owned(), |ms| format_timestamp_ms(*ms));
+            table.add_row(vec![
+                Cell::new(k),
+                Cell::new(i).set_alignment(CellAlignment::Right),
+                Cell::new(started),
+                Cell::new(format!("{v:.3}")).set_alignment(CellAlignment::Right),
+            ]);
+        }
+    }
+    writeln!(out, "{table}")
+}
+
+/// Format a Unix timestamp in milliseconds as a UTC date and time.
+fn format_timestamp_ms(ms: u64) -> String {
+    let s = format_timestamp(Some(ms / 1000));
+    format!("{}.{:03} UTC", s.strip_suffix(" UTC").unwrap(), ms % 1000)
+}
+
+/// Parse a non-negative percentage, e.g. `2.5`.
+fn parse_percent(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(p) if p.is_finite() && p >= 0. => Ok(p),
+        _ => Err(format!("'{s}' is not a non-negative percentage")),
+    }
+}
+
+/// Parse a duration such as `90s`, `45m` or `1h30m`.
+///
+/// A bare number is interpreted as seconds.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    if s.is_empty() {
+        return Err("empty duration".to_owned());
+    }
+    if let Ok(secs) = s.parse::<u64>() {
+        return Ok(Duration::from_secs(secs));
+    }
+    let mut secs = 0;
+    let mut num = String::new();
+    for c in s.chars() {
+        if c.is_ascii_digit() {
+            num.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => return Err(format!("invalid duration unit '{c}' in '{s}'")),
+        };
+        let Ok(n) = num.parse::<u64>() else {
+            return Err(format!("missing number before '{c}' in '{s}'"));
+        };
+        secs += n * unit;
+        num.clear();
+    }
+    if !num.is_empty() {
+        return Err(format!("missing unit after '{num}' in '{s}'"));
+    }
+    Ok(Duration::from_secs(secs))
+}
+
+/// Seconds since the Unix epoch.
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
 }
 
 /// The name of the hidden directory we store state inside.
 const DOT_DIR: &str = ".haste";
+/// The largest random seed: seeds are stored in TOML, whose integers are signed 64-bit.
+const MAX_SEED: u64 = i64::MAX as u64;
 /// The name of the haste config file.
 const DEFAULT_CONFIG_FILE: &str = "haste.toml";
 
@@ -59,6 +537,102 @@ struct BenchKey {
     extra_args: Vec<String>,
 }
 
+/// Split the string form of a [BenchKey] into the benchmark and its arguments (as
+/// `<benchmark>/<extra-args>`), and the executor.
+fn split_key(key: &str) -> Option<(String, &str)> {
+    let (bench, rest) = key.split_once('/')?;
+    let (exec, args) = rest.split_once('/')?;
+    Some((format!("{bench}/{args}"), exec))
+}
+
+impl BenchKey {
+    /// The key whose string form is `key`. As that joins the extra arguments with `-`, it can't
+    /// always be split back into them (see [runner::check_keys]), so they're taken from `params`
+    /// if it records them, and otherwise split on `-`.
+    fn parse(key: &str, params: &HashMap<String, KeyParams>) -> Option<Self> {
+        let (benchmark, rest) = key.split_once('/')?;
+        let (executor, args) = rest.split_once('/')?;
+        let extra_args = match params.get(key) {
+            Some(p) => p.extra_args.clone(),
+            None if args.is_empty() => Vec::new(),
+            None => args.split('-').map(str::to_owned).collect(),
+        };
+        Some(BenchKey {
+            benchmark: benchmark.to_owned(),
+            executor: executor.to_owned(),
+            extra_args,
+        })
+    }
+}
+
+/// A benchmark run with an executor with different `extra_args` in two datums, so that its keys
+/// differ.
+#[derive(Debug, PartialEq)]
+struct ArgsChange {
+    /// The benchmark's key in the first datum, and in the second.
+    keys: (String, String),
+    /// `<benchmark>/<executor>`.
+    name: String,
+    before: Vec<String>,
+    after: Vec<String>,
+}
+
+impl fmt::Display for ArgsChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let how = match (self.before.is_empty(), self.after.is_empty()) {
+            (true, _) => "added",
+            (_, true) => "removed",
+            _ => "changed",
+        };
+        write!(
+            f,
+            "{}: {:?} -> {:?} (arguments {how})",
+            self.name, self.before, self.after
+        )
+    }
+}
+
+/// The benchmarks of `lhs_only`, the keys only in the first datum, and `rhs_only`, those only in
+/// the second, which differ only in their extra arguments, sorted. A benchmark and executor with
+/// more than one key on either side can't be paired up, so isn't included.
+fn args_changes(
+    lhs_only: &[String],
+    rhs_only: &[String],
+    params1: &HashMap<String, KeyParams>,
+    params2: &HashMap<String, KeyParams>,
+) -> Vec<ArgsChange> {
+    let by_name = |keys: &[String], params| {
+        let mut by_name = HashMap::<String, Vec<BenchKey>>::new();
+        for k in keys {
+            if let Some(key) = BenchKey::parse(k, params) {
+                let name = format!("{}/{}", key.benchmark, key.executor);
+                by_name.entry(name).or_default().push(key);
+            }
+        }
+        by_name
+    };
+    let (lhs, mut rhs) = (by_name(lhs_only, params1), by_name(rhs_only, params2));
+    let mut changes = lhs
+        .into_iter()
+        .filter_map(
+            |(name, mut before)| match (before.len(), rhs.remove(&name)) {
+                (1, Some(mut after)) if after.len() == 1 => {
+                    let (before, after) = (before.remove(0), after.remove(0));
+                    Some(ArgsChange {
+                        keys: (before.to_string(), after.to_string()),
+                        name,
+                        before: before.extra_args,
+                        after: after.extra_args,
+                    })
+                }
+                _ => None,
+            },
+        )
+        .collect::<Vec<_>>();
+    changes.sort_by(|a, b| a.name.cmp(&b.name));
+    changes
+}
+
 impl fmt::Display for BenchKey {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -71,6 +645,7 @@ impl fmt::Display for BenchKey {
     }
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize)]
 struct SummaryStats {
     /// Sample arithmentic mean.
     mean: f64,
@@ -78,501 +653,10582 @@ struct SummaryStats {
     ///
     /// We report the mean +/- this value.
     ci: f64,
+    /// The number of samples summarised.
+    n: usize,
+    /// Do the samples look like they fall into two clusters, in which case the mean may describe
+    /// neither of them?
+    #[serde(default)]
+    bimodal: bool,
 }
 
 impl SummaryStats {
-    fn new(mean: f64, ci: f64) -> Self {
-        Self { mean, ci }
+    fn new(mean: f64, ci: f64, n: usize) -> Self {
+        Self {
+            mean,
+            ci,
+            n,
+            bimodal: false,
+        }
+    }
+
+    /// Summarise a non-empty collection of samples.
+    fn from_samples(samples: &[f64], confidence: ConfidenceLevel) -> Self {
+        // Compute a confidence interval, as per:
+        // https://www.dummies.com/article/academics-the-arts/math/statistics/how-to-calculate-a-confidence-interval-for-a-population-mean-when-you-know-its-standard-deviation-169722/
+        let interval = stats::mean_interval(samples, confidence.zval());
+        Self {
+            bimodal: stats::bimodal(samples).is_some(),
+            ..Self::new(interval.mean, interval.half_width, interval.n)
+        }
+    }
+
+    /// Summarise `samples` by their `p`th quantile (0 < `p` < 1), with a bootstrapped confidence
+    /// interval drawn with `rng`, as if it were the mean.
+    ///
+    /// With fewer than [stats::quantile_min_samples] samples, there's no telling how far beyond
+    /// them the quantile may be, so the interval is widened to at least the range of the samples.
+    fn from_quantile(
+        samples: &[f64],
+        p: f64,
+        confidence: ConfidenceLevel,
+        rng: &mut StdRng,
+    ) -> Self {
+        let sorted = stats::sorted(samples);
+        let mut ci = stats::bootstrap_quantile_ci(&sorted, p, confidence.as_percent(), rng);
+        if samples.len() < stats::quantile_min_samples(p) {
+            ci = ci.max(sorted[sorted.len() - 1] - sorted[0]);
+        }
+        Self {
+            bimodal: stats::bimodal(samples).is_some(),
+            ..Self::new(stats::quantile(&sorted, p), ci, samples.len())
+        }
+    }
+
+    /// The lower and upper bounds of the confidence interval.
+    fn bounds(&self) -> (f64, f64) {
+        (self.mean - self.ci, self.mean + self.ci)
     }
 
     /// Determine if two confidence intervals overlap.
     fn ci_overlaps(&self, other: &Self) -> bool {
-        let l1 = self.mean - self.ci;
-        let u1 = self.mean + self.ci;
-        let l2 = other.mean - other.ci;
-        let u2 = other.mean + other.ci;
+        let (l1, u1) = self.bounds();
+        let (l2, u2) = other.bounds();
         l1 <= u2 && l2 <= u1
     }
 }
 
-/// Computes a consistent width for fomatting floats in a colum so they all line up nicely.
-fn compute_f64_format(fs: &[f64]) -> usize {
-    let mut max_width = 1;
-    for f in fs {
-        let s = format!("{:.0}", f);
-        if s.len() > max_width {
-            max_width = s.len();
+/// The minimum number of samples that a benchmark needs for noise calibration: each half of a
+/// split must have at least two samples to have a confidence interval.
+const NOISE_MIN_SAMPLES: usize = 4;
+/// Benchmarks whose false-positive rate exceeds the expected rate by more than this factor are
+/// flagged as noisy.
+const NOISE_FLAG_FACTOR: f64 = 2.;
+
+/// Count how many of `reps` random half-splits of `samples` are judged significantly different.
+///
+/// Returns `None` if there are too few samples to split.
+fn noise_false_positives(
+    samples: &[f64],
+    confidence: ConfidenceLevel,
+    reps: usize,
+    rng: &mut StdRng,
+) -> Option<usize> {
+    if samples.len() < NOISE_MIN_SAMPLES {
+        return None;
+    }
+    let mut shuffled = samples.to_vec();
+    let mut fps = 0;
+    for _ in 0..reps {
+        shuffled.shuffle(rng);
+        let (lhs, rhs) = shuffled.split_at(shuffled.len() / 2);
+        let s1 = SummaryStats::from_samples(lhs, confidence);
+        let s2 = SummaryStats::from_samples(rhs, confidence);
+        if !s1.ci_overlaps(&s2) {
+            fps += 1;
         }
     }
-    max_width
+    Some(fps)
 }
 
-/// The results file for a datum.
-#[derive(Serialize, Deserialize, Debug, Default)]
-struct ResultFile {
-    // String benchmark key -> collection of process execution times (in milliseconds).
-    data: HashMap<String, Vec<f64>>,
+/// The column header for a datum's values of `metric`.
+fn datum_header(id: usize, metric: &str) -> String {
+    named_header(&format!("Datum{id}"), metric)
 }
 
-impl ResultFile {
-    fn summarise(&self, confidence: ConfidenceLevel) -> HashMap<String, SummaryStats> {
-        let mut summaries = HashMap::new();
-        for (k, invocs) in &self.data {
-            let n = f64::from(u32::try_from(invocs.len()).unwrap());
-            let mean = invocs.iter().sum::<f64>() / n;
-
-            // Compute a confidence interval, as per:
-            // https://www.dummies.com/article/academics-the-arts/math/statistics/how-to-calculate-a-confidence-interval-for-a-population-mean-when-you-know-its-standard-deviation-169722/
-            let ci = if invocs.len() > 1 {
-                let variance = invocs.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.);
-                let std_dev = variance.sqrt();
-                confidence.zval() * std_dev / n.sqrt()
-            } else {
-                // Avoid division by zero in case there is a single sample.
-                // In this case, report a CI of +/- 0.
-                0.
-            };
+/// The column header for the values of `metric` of the datum called `name`.
+///
+/// Durations shown in human units carry their own, so the header doesn't give one.
+fn named_header(name: &str, metric: &str) -> String {
+    match metric_unit(metric) {
+        Some(_) if human_durations(metric) => name.to_owned(),
+        Some(unit) => format!("{name} ({unit})"),
+        None => name.to_owned(),
+    }
+}
 
-            let summary = SummaryStats::new(mean, ci);
-            summaries.insert(k.to_owned(), summary);
+/// A datum given on the command line as `<id>`, or `<path>:<id>` for a datum in the state
+/// directory at `path` rather than the current one.
+#[derive(Clone, Debug, PartialEq)]
+struct DatumRef {
+    state_dir: Option<PathBuf>,
+    id: usize,
+}
+
+impl DatumRef {
+    /// Datum `id` of the current state directory.
+    fn local(id: usize) -> Self {
+        Self {
+            state_dir: None,
+            id,
         }
-        summaries
     }
 
-    /// Check the results have the same data dimensionality.
-    ///
-    /// Returns `Ok(())` iff the same set of benchmarks were run and the same number of invocations
-    /// and iterations were run (on a per-benchmark basis).
-    ///
-    /// Each set of results is assumed to be consistent in isolation.
-    fn same_dims(&self, other: &ResultFile) -> Result<(), String> {
-        let self_keys: HashSet<&String> = HashSet::from_iter(self.data.keys());
-        let other_keys: HashSet<&String> = HashSet::from_iter(other.data.keys());
-        if self_keys != other_keys {
-            let mut lhs_diff = self_keys
-                .difference(&other_keys)
-                .cloned()
-                .map(|x| x.to_owned())
-                .collect::<Vec<_>>();
-            lhs_diff.sort();
-            let mut rhs_diff = other_keys
-                .difference(&self_keys)
-                .cloned()
-                .map(|x| x.to_owned())
-                .collect::<Vec<_>>();
-            rhs_diff.sort();
-            return Err(format!(
-                "results files contain different benchmarks:\n  lhs lacks: {}\n  rhs lacks: {}",
-                lhs_diff.join(", "),
-                rhs_diff.join(", ")
-            ));
+    /// How to refer to the datum in output. If `qualify`, the state directory is included, so
+    /// that datums in different stores with the same ID can be told apart.
+    fn name(&self, qualify: bool) -> String {
+        match (&self.state_dir, qualify) {
+            (Some(dir), true) => format!("{}:Datum{}", dir.display(), self.id),
+            (None, true) => format!("{DOT_DIR}:Datum{}", self.id),
+            (_, false) => format!("Datum{}", self.id),
         }
-        for (k, v1) in &self.data {
-            let v2 = &other.data[k];
-            if v1.len() != v2.len() {
-                return Err(format!("different number of process executions for {k}"));
-            }
+    }
+}
+
+impl std::str::FromStr for DatumRef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_id = |id: &str| {
+            id.parse::<usize>()
+                .map_err(|_| format!("invalid datum ID '{id}'"))
+        };
+        let Some((dir, id)) = s.rsplit_once(':') else {
+            return Ok(Self::local(parse_id(s)?));
+        };
+        if dir.is_empty() {
+            return Err(format!("no state directory before ':' in '{s}'"));
         }
-        Ok(())
+        let dir = match (dir.strip_prefix("~/"), env::var_os("HOME")) {
+            (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+            _ => PathBuf::from(dir),
+        };
+        Ok(Self {
+            state_dir: Some(dir),
+            id: parse_id(id)?,
+        })
     }
 }
 
-struct App {
-    /// The directory where persistent state is stored.
-    state_dir: PathBuf,
-    /// The path to the config file.
-    config_file: PathBuf,
+/// How to format a column of numbers so that they all line up nicely.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct NumFormat {
+    width: usize,
+    /// The number of decimal places.
+    precision: usize,
+    /// Show a `+` on positive values.
+    signed: bool,
+    /// Separate groups of thousands with commas.
+    thousands: bool,
+    /// If the numbers are durations in milliseconds to be shown in human units, the scale to
+    /// show them in.
+    scale: Option<units::Scale>,
 }
 
-impl App {
-    fn new(config_file: Option<PathBuf>) -> Self {
-        let state_dir = [env::current_dir().unwrap().to_str().unwrap(), DOT_DIR]
+/// The most decimal places [NumFormat] will use for small values.
+const MAX_PRECISION: usize = 6;
+
+impl NumFormat {
+    /// Work out how to format `fs` with at least `precision` decimal places.
+    ///
+    /// If every value is smaller than 1, more decimal places are used so that the largest has two
+    /// significant figures, rather than everything rounding to 0.
+    fn new(fs: &[f64], precision: usize, signed: bool, thousands: bool) -> Self {
+        let max_abs = fs
             .iter()
-            .collect();
-        if !fs::exists(&state_dir).unwrap() {
-            fs::create_dir(&state_dir).unwrap();
-        }
-        let config_file = config_file.unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_FILE));
-        Self {
-            state_dir,
-            config_file,
+            .filter(|f| f.is_finite())
+            .fold(0., |m: f64, f| m.max(f.abs()));
+        let precision = if max_abs > 0. && max_abs < 1. {
+            let needed = (-max_abs.log10()).floor() as usize + 2;
+            precision.max(needed.min(MAX_PRECISION))
+        } else {
+            precision
+        };
+        NumFormat {
+            width: 1,
+            precision,
+            signed,
+            thousands,
+            scale: None,
         }
+        .fit(fs)
     }
 
-    /// Determine the next available datum ID.
-    ///
-    /// The first ID issued is zero.
-    fn next_id(&self) -> usize {
-        let mut max: isize = -1;
-        for d in fs::read_dir(&self.state_dir).unwrap() {
-            let num = d
-                .unwrap()
-                .path()
-                .file_name()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .parse::<isize>()
-                .unwrap();
-            if num > max {
-                max = num;
+    /// Show `fs`, which are durations in milliseconds, in the human units suiting the largest.
+    fn human(mut self, fs: &[f64]) -> Self {
+        let max_abs = fs
+            .iter()
+            .filter(|f| f.is_finite())
+            .fold(0., |m: f64, f| m.max(f.abs()));
+        self.scale = Some(units::Scale::for_max(max_abs));
+        self.fit(fs)
+    }
+
+    /// Make the column wide enough for all of `fs`.
+    fn fit(mut self, fs: &[f64]) -> Self {
+        self.width = fs
+            .iter()
+            .map(|f| self.render(*f).chars().count())
+            .max()
+            .unwrap_or(1)
+            .max(1);
+        self
+    }
+
+    /// Format `f` without any padding.
+    fn render(&self, f: f64) -> String {
+        if let Some(scale) = self.scale
+            && f.is_finite()
+        {
+            return scale.render(f, self.precision, self.signed);
+        }
+        let s = if self.signed {
+            format!("{f:+.0$}", self.precision)
+        } else {
+            format!("{f:.0$}", self.precision)
+        };
+        if !self.thousands || !f.is_finite() {
+            return s;
+        }
+        let (sign, rest) = s.split_at(s.find(|c: char| c.is_ascii_digit()).unwrap_or(0));
+        let (int, frac) = rest.split_at(rest.find('.').unwrap_or(rest.len()));
+        let mut grouped = String::new();
+        for (i, c) in int.chars().enumerate() {
+            if i > 0 && (int.len() - i) % 3 == 0 {
+                grouped.push(',');
             }
+            grouped.push(c);
         }
-        usize::try_from(max + 1).unwrap()
+        format!("{sign}{grouped}{frac}")
     }
 
-    /// Store a new datum and return the ID.
-    fn store_datum(&self, results: ResultFile, comment: Option<String>) -> usize {
-        let id = self.next_id();
-        let datum_dir = self.get_datum_dir(id);
-        fs::create_dir(&datum_dir).unwrap();
-        let res_path = self.get_datum_results_path(id);
-        let tml = toml::to_string(&results).unwrap();
-        fs::write(res_path, tml).unwrap();
-
-        // Write out the extra metadata.
-        // FIXME: consider merging this into the main toml file.
-        let extra_data = toml::to_string(&ExtraToml { comment }).unwrap();
-        let extra_path = self.get_datum_extra_path(id);
-        std::fs::write(extra_path, extra_data).unwrap();
+    /// Format `f`, right-aligned to the width of the column.
+    fn format(&self, f: f64) -> String {
+        format!("{:>1$}", self.render(f), self.width)
+    }
 
-        id
+    /// Right-align `s` (e.g. a placeholder for a missing value) to the width of the column.
+    fn pad(&self, s: &str) -> String {
+        format!("{s:>0$}", self.width)
     }
+}
+
+/// The results file for a datum.
+/// A warning that the benchmarks `keys` of the datum called `name` have too few samples for `stat`
+/// to be estimated reliably, or `None` if `keys` is empty.
+fn too_few_warning(name: &str, stat: Statistic, keys: &[String]) -> Option<String> {
+    let Statistic::Percentile(p) = stat else {
+        return None;
+    };
+    (!keys.is_empty()).then(|| {
+        format!(
+            "{name}: fewer than {} samples is too few to estimate the {stat} of {}, so its \
+             confidence interval has been widened to at least the range of the samples",
+            stats::quantile_min_samples(p / 100.),
+            keys.join(", ")
+        )
+    })
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+struct ResultFile {
+    // String benchmark key -> collection of process execution times (in milliseconds).
+    data: HashMap<String, Vec<f64>>,
+    // Metric name -> string benchmark key -> collection of per-pexec values.
+    //
+    // This holds additional metrics (e.g. perf counters) beyond the wall-clock time in `data`.
+    #[serde(default)]
+    metrics: HashMap<String, HashMap<String, Vec<f64>>>,
+    // String benchmark key -> the parameters the benchmark was run with.
+    //
+    // Datums collected by older versions of haste lack this.
+    #[serde(default)]
+    params: HashMap<String, KeyParams>,
+    // String benchmark key -> when each process execution started (in milliseconds since the Unix
+    // epoch), in the same order as `data`.
+    //
+    // Datums collected by older versions of haste lack this.
+    #[serde(default)]
+    timestamps: HashMap<String, Vec<u64>>,
+    // String benchmark key -> how its failing pexecs failed, for datums run with `--keep-going`.
+    #[serde(default)]
+    failures: HashMap<String, Failure>,
+}
+
+/// The parameters that a benchmark was run with.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct KeyParams {
+    inproc_iters: usize,
+    proc_execs: usize,
+    extra_args: Vec<String>,
+    /// The suite's `extra_args`, which were passed to the harness before `extra_args`.
+    #[serde(default)]
+    suite_args: Vec<String>,
+    /// The harness used to run the benchmark.
+    #[serde(default)]
+    harness: Option<PathBuf>,
+    /// The command run for the benchmark, if it didn't use a harness.
+    #[serde(default)]
+    command: Option<String>,
+}
 
-    fn get_datum_dir(&self, id: usize) -> PathBuf {
-        [DOT_DIR, &id.to_string()].iter().collect::<PathBuf>()
+/// How a benchmark's pexecs failed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Failure {
+    /// The number of pexecs that failed.
+    count: usize,
+    /// The exit status of the last failing pexec, e.g. `1` or `signal`.
+    status: String,
+    /// The end of the last failing pexec's stderr.
+    stderr: String,
+}
+
+/// The name of the metric stored in `ResultFile::data`.
+const TIME_METRIC: &str = "time";
+/// The name of the throughput metric derived from the time metric: in-process iterations per
+/// second.
+const IPS_METRIC: &str = "ips";
+/// The name of the metric recording how many in-process iterations each pexec ran, for benchmarks
+/// whose harnesses chose for themselves (with `inproc_iters = 0`).
+const ITERS_METRIC: &str = "iters";
+
+/// The unit to display for a metric, if it has one.
+fn metric_unit(metric: &str) -> Option<&'static str> {
+    match metric {
+        TIME_METRIC | "task-clock" => Some("ms"),
+        IPS_METRIC => Some("iters/s"),
+        ITERS_METRIC => Some("iters"),
+        _ => None,
     }
+}
 
-    fn get_datum_results_path(&self, id: usize) -> PathBuf {
-        let mut p = self.get_datum_dir(id);
-        p.push("data.toml");
-        p
+/// Are the values of `metric` durations to be shown in human units (see [units::human])?
+fn human_durations(metric: &str) -> bool {
+    metric_unit(metric) == Some("ms") && units::human()
+}
+
+/// Is a bigger value of `metric` better? For most metrics, e.g. times, smaller values are better.
+fn higher_is_better(metric: &str) -> bool {
+    metric == IPS_METRIC
+}
+
+impl ResultFile {
+    /// Return results containing only the given metric (as `data`).
+    fn for_metric(&self, metric: &str) -> Result<ResultFile, String> {
+        let data = if metric == TIME_METRIC {
+            self.data.clone()
+        } else if metric == IPS_METRIC {
+            self.ips()?
+        } else if let Some(data) = self.metrics.get(metric) {
+            data.clone()
+        } else {
+            return Err(format!(
+                "no data for metric {metric} (available: {})",
+                self.available_metrics().join(", ")
+            ));
+        };
+        Ok(ResultFile {
+            data,
+            params: self.params.clone(),
+            timestamps: self.timestamps.clone(),
+            failures: self.failures.clone(),
+            ..Default::default()
+        })
     }
 
-    fn get_datum_extra_path(&self, id: usize) -> PathBuf {
-        let mut p = self.get_datum_dir(id);
-        p.push("extra.toml");
-        p
+    /// The metrics that [ResultFile::for_metric] may find, sorted.
+    fn available_metrics(&self) -> Vec<&str> {
+        let mut available = vec![TIME_METRIC, IPS_METRIC];
+        available.extend(self.metrics.keys().map(String::as_str));
+        available.sort();
+        available
     }
 
-    fn load_extra(&self, id: usize) -> ExtraToml {
-        let path = self.get_datum_extra_path(id);
-        if let Ok(data) = std::fs::read_to_string(path) {
-            toml::from_str(&data).unwrap()
-        } else {
-            ExtraToml::default()
+    /// The in-process iterations per second of each pexec.
+    ///
+    /// Fails if the number of iterations wasn't recorded for any benchmark, as it may not be what
+    /// the current config says.
+    fn ips(&self) -> Result<HashMap<String, Vec<f64>>, String> {
+        let chosen = self.metrics.get(ITERS_METRIC);
+        let mut unrecorded = self
+            .data
+            .keys()
+            .filter(|k| {
+                chosen.is_none_or(|c| !c.contains_key(*k))
+                    && self.params.get(*k).is_none_or(|p| p.inproc_iters == 0)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if !unrecorded.is_empty() {
+            unrecorded.sort();
+            return Err(format!(
+                "the {IPS_METRIC} metric is unavailable, as the number of in-process iterations \
+                 wasn't recorded for: {}",
+                unrecorded.join(", ")
+            ));
         }
+        Ok(self
+            .data
+            .iter()
+            .map(|(k, samples)| {
+                let ips = match chosen.and_then(|c| c.get(k)) {
+                    Some(iters) => samples
+                        .iter()
+                        .zip(iters)
+                        .map(|(ms, iters)| iters * 1000. / ms)
+                        .collect(),
+                    None => {
+                        let iters = self.params[k].inproc_iters as f64;
+                        samples.iter().map(|ms| iters * 1000. / ms).collect()
+                    }
+                };
+                (k.clone(), ips)
+            })
+            .collect())
     }
 
-    /// Run benchmarks and store the results as a new datum.
+    /// Subtract the harness overhead for each benchmark key in `overheads` from its time samples,
+    /// clamping at zero. Keys without an overhead are left alone.
     ///
-    /// If successful, the new datum is printed to stdout.
-    fn cmd_bench(&self, comment: Option<String>) {
-        let config_path = self.config_file.display();
-        let config_text = fs::read_to_string(&self.config_file).unwrap_or_else(|e| {
-            eprintln!("error: failed to read {config_path}: {e}");
-            process::exit(1);
-        });
-        let config: config::Config = match toml::from_str(&config_text) {
-            Ok(d) => d,
-            Err(e) => {
-                eprintln!("Unable to parse {config_path}: {e}");
-                std::process::exit(1);
+    /// Returns the keys whose times were within noise of the overhead, whose adjusted times are
+    /// therefore meaningless.
+    fn subtract_overhead(
+        &mut self,
+        overheads: &HashMap<String, f64>,
+        confidence: ConfidenceLevel,
+    ) -> Vec<String> {
+        let mut noisy = Vec::new();
+        for (k, samples) in &mut self.data {
+            let Some(overhead) = overheads.get(k) else {
+                continue;
+            };
+            let s = SummaryStats::from_samples(samples, confidence);
+            if s.mean - s.ci <= *overhead {
+                noisy.push(k.clone());
             }
-        };
-        let results = runner::run(&config);
-        let id = self.store_datum(results, comment.to_owned());
-        let comment_s = comment.unwrap_or("".to_owned());
-        println!("haste: created datum {id} {comment_s}");
+            for x in samples.iter_mut() {
+                *x = (*x - overhead).max(0.);
+            }
+        }
+        noisy.sort();
+        noisy
     }
 
-    fn cmd_diff(&self, id1: usize, id2: usize, confidence: ConfidenceLevel) {
-        let tml1 = fs::read_to_string(self.get_datum_results_path(id1)).unwrap();
-        let tml2 = fs::read_to_string(self.get_datum_results_path(id2)).unwrap();
-        let data1 = toml::from_str::<ResultFile>(&tml1).unwrap();
-        let data2 = toml::from_str::<ResultFile>(&tml2).unwrap();
-
-        if let Err(e) = data1.same_dims(&data2) {
-            eprintln!("{e}");
-            process::exit(1);
+    /// Replace each benchmark's samples with those left after trimming `percent`% from each end.
+    ///
+    /// Fails if that would leave no samples for any benchmark.
+    fn trim(&mut self, percent: f64) -> Result<(), String> {
+        let mut keys = self.data.keys().cloned().collect::<Vec<_>>();
+        keys.sort();
+        for k in keys {
+            let samples = self.data.get_mut(&k).unwrap();
+            let trimmed = stats::trim(samples, percent);
+            if trimmed.is_empty() {
+                return Err(format!(
+                    "{k}: trimming {percent}% from each end of its {} samples leaves none",
+                    samples.len()
+                ));
+            }
+            *samples = trimmed;
         }
+        Ok(())
+    }
 
-        let data1 = data1.summarise(confidence);
-        let data2 = data2.summarise(confidence);
-
-        // Compute the formatting of our data.
-        let means = data1
-            .iter()
-            .chain(&data2)
-            .map(|(_, s)| s.mean)
-            .collect::<Vec<f64>>();
-        let mean_width = compute_f64_format(&means);
-        let cis = data1
-            .iter()
-            .chain(&data2)
-            .map(|(_, s)| s.ci)
-            .collect::<Vec<f64>>();
-        let ci_width = compute_f64_format(&cis);
-        let mut ratios = Vec::new();
-        for (key, s1) in data1.iter() {
-            let s2 = &data2[key];
-            ratios.push(s2.mean / s1.mean);
-        }
-        let ratio_width = compute_f64_format(&ratios) + 3;
-
-        let mut sig_rows = Vec::new();
-        let mut insig_rows = Vec::new();
-        for (k, v1) in &data1 {
-            let mut row = Vec::new();
-            let v2 = &data2[k];
-            let ratio = v2.mean / v1.mean;
-            let change = (ratio - 1.0) * 100.0;
-            let abs_change = change.abs();
-
-            row.push(Cell::new(k));
-            let v1_cell = Cell::new(format!("{:mean_width$.0} ±{:ci_width$.0}", v1.mean, v1.ci));
-            row.push(v1_cell.set_alignment(CellAlignment::Right));
-            let v2_cell = Cell::new(format!("{:mean_width$.0} ±{:ci_width$.0}", v2.mean, v2.ci));
-            row.push(v2_cell.set_alignment(CellAlignment::Right));
-            let ratio_cell = Cell::new(format!("{ratio:>ratio_width$.2}"));
-            row.push(ratio_cell.set_alignment(CellAlignment::Right));
-
-            if !v1.ci_overlaps(v2) {
-                let change_cell = if change < 0.0 {
-                    Cell::new(format!("{abs_change:.2}% faster")).fg(Color::Green)
-                } else {
-                    Cell::new(format!("{abs_change:.2}% slower")).fg(Color::Red)
-                };
-                row.push(change_cell);
-                sig_rows.push((change, row));
-            } else {
-                row.push(Cell::new("indistinguishable".to_owned()).fg(Color::Magenta));
-                insig_rows.push((change, row));
+    /// Summarise each benchmark by `stat`, drawing any random numbers it needs from `rng`. Also
+    /// returns the benchmarks with too few samples to estimate `stat`, sorted.
+    fn summarise_by(
+        &self,
+        stat: Statistic,
+        confidence: ConfidenceLevel,
+        rng: &mut StdRng,
+    ) -> Result<(HashMap<String, SummaryStats>, Vec<String>), String> {
+        match stat {
+            Statistic::Mean => Ok((self.summarise(confidence), Vec::new())),
+            Statistic::TrimmedMean(percent) => {
+                let mut trimmed = self.clone();
+                trimmed.trim(percent)?;
+                Ok((trimmed.summarise(confidence), Vec::new()))
+            }
+            Statistic::Percentile(percentile) => {
+                let p = percentile / 100.;
+                // Sorted, so that the same seed always gives the same intervals.
+                let mut keys = self.data.keys().collect::<Vec<_>>();
+                keys.sort();
+                let mut summaries = HashMap::new();
+                let mut too_few = Vec::new();
+                for k in keys {
+                    let samples = &self.data[k];
+                    if samples.len() < stats::quantile_min_samples(p) {
+                        too_few.push(k.clone());
+                    }
+                    let summary = SummaryStats::from_quantile(samples, p, confidence, rng);
+                    summaries.insert(k.clone(), summary);
+                }
+                Ok((summaries, too_few))
             }
         }
+    }
 
-        let mut table = Table::new();
-        table.load_preset(comfy_table::presets::NOTHING);
-        table.set_header(vec![
-            Cell::new("Benchmark").set_alignment(CellAlignment::Left),
-            Cell::new(format!("Datum{id1} (ms)")).set_alignment(CellAlignment::Right),
-            Cell::new(format!("Datum{id2} (ms)")).set_alignment(CellAlignment::Right),
-            Cell::new("Ratio").set_alignment(CellAlignment::Right),
-            Cell::new("Summary").set_alignment(CellAlignment::Left),
-        ]);
-        // Sort the rows first by significance, then by speedup, descending.
-        sig_rows.sort_by(|(c1, _), (c2, _)| c1.partial_cmp(c2).unwrap());
-        for (_, row) in sig_rows {
-            table.add_row(row);
+    fn summarise(&self, confidence: ConfidenceLevel) -> HashMap<String, SummaryStats> {
+        let mut summaries = HashMap::new();
+        for (k, invocs) in &self.data {
+            let summary = SummaryStats::from_samples(invocs, confidence);
+            summaries.insert(k.to_owned(), summary);
         }
-        // Insignifcant results: sort by speedup, descending.
-        insig_rows.sort_by(|(c1, _), (c2, _)| c1.partial_cmp(c2).unwrap());
-        for (_, row) in insig_rows {
-            table.add_row(row);
+        summaries
+    }
+
+    /// The results for the executor `executor`, keyed by benchmark keys with the executor removed,
+    /// so that they can be compared with the results of another executor.
+    fn for_executor(&self, executor: &str) -> ResultFile {
+        let strip = |key: &str| {
+            let (bench, exec) = split_key(key)?;
+            (exec == executor).then_some(bench)
+        };
+        let filter = |data: &HashMap<String, Vec<f64>>| {
+            data.iter()
+                .filter_map(|(k, v)| Some((strip(k)?, v.clone())))
+                .collect::<HashMap<_, _>>()
+        };
+        ResultFile {
+            data: filter(&self.data),
+            metrics: self
+                .metrics
+                .iter()
+                .map(|(m, data)| (m.clone(), filter(data)))
+                .collect(),
+            params: self
+                .params
+                .iter()
+                .filter_map(|(k, p)| Some((strip(k)?, p.clone())))
+                .collect(),
+            timestamps: self
+                .timestamps
+                .iter()
+                .filter_map(|(k, t)| Some((strip(k)?, t.clone())))
+                .collect(),
+            failures: self
+                .failures
+                .iter()
+                .filter_map(|(k, f)| Some((strip(k)?, f.clone())))
+                .collect(),
         }
+    }
 
-        // If there's any extra metadata, print it.
-        let extra1 = self.load_extra(id1);
-        let extra2 = self.load_extra(id2);
-        if extra1.comment.is_some() || extra2.comment.is_some() {
-            let no_comment = "(no comment)".to_owned();
-            println!(
-                "Datum{id1}: {}",
-                extra1.comment.unwrap_or(no_comment.clone())
-            );
-            println!("Datum{id2}: {}\n", extra2.comment.unwrap_or(no_comment));
+    /// Keep only the results of the benchmarks named in `benchmarks`, whatever their executors and
+    /// extra arguments.
+    fn retain_benchmarks(&mut self, benchmarks: &[String]) {
+        let wanted = |key: &String| {
+            key.split_once('/')
+                .is_some_and(|(b, _)| benchmarks.iter().any(|w| w == b))
+        };
+        self.data.retain(|k, _| wanted(k));
+        for data in self.metrics.values_mut() {
+            data.retain(|k, _| wanted(k));
+        }
+        self.params.retain(|k, _| wanted(k));
+        self.timestamps.retain(|k, _| wanted(k));
+        self.failures.retain(|k, _| wanted(k));
+    }
+
+    /// Check that every sample is a finite number.
+    fn validate(&self) -> Result<(), String> {
+        let mut metrics = self.metrics.iter().collect::<Vec<_>>();
+        metrics.sort_by_key(|(m, _)| *m);
+        let sources = [(TIME_METRIC, &self.data)]
+            .into_iter()
+            .chain(metrics.into_iter().map(|(m, d)| (m.as_str(), d)));
+        for (metric, data) in sources {
+            let mut keys = data.keys().collect::<Vec<_>>();
+            keys.sort();
+            for key in keys {
+                if let Some(i) = data[key].iter().position(|x| !x.is_finite()) {
+                    return Err(format!(
+                        "{key}: {metric} sample {i} is {} (is the datum corrupt?)",
+                        data[key][i]
+                    ));
+                }
+            }
         }
+        Ok(())
+    }
 
-        println!("confidence level: {}%\n", confidence.as_percent());
-        println!("{table}");
+    /// Discard the benchmarks that are not present in both sets of results.
+    ///
+    /// Returns the keys that were discarded from `self` and from `other` respectively.
+    fn retain_common(&mut self, other: &mut ResultFile) -> (Vec<String>, Vec<String>) {
+        let mut lhs_only = self
+            .data
+            .keys()
+            .filter(|k| !other.data.contains_key(*k))
+            .cloned()
+            .collect::<Vec<_>>();
+        lhs_only.sort();
+        let mut rhs_only = other
+            .data
+            .keys()
+            .filter(|k| !self.data.contains_key(*k))
+            .cloned()
+            .collect::<Vec<_>>();
+        rhs_only.sort();
+        for k in &lhs_only {
+            self.data.remove(k);
+        }
+        for k in &rhs_only {
+            other.data.remove(k);
+        }
+        (lhs_only, rhs_only)
     }
 
-    fn cmd_list(&self) {
-        let mut ids = Vec::new();
-        for ent in fs::read_dir(&self.state_dir).unwrap() {
-            let ent = ent.unwrap();
-            if let Ok(id) = ent
-                .path()
-                .file_name()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .parse::<usize>()
+    /// Discard the benchmarks that failed in either set of results.
+    ///
+    /// Returns the discarded keys.
+    fn remove_failed(&mut self, other: &mut ResultFile) -> Vec<String> {
+        let mut failed = self
+            .failures
+            .keys()
+            .chain(other.failures.keys())
+            .cloned()
+            .collect::<Vec<_>>();
+        failed.sort();
+        failed.dedup();
+        for k in &failed {
+            self.data.remove(k);
+            other.data.remove(k);
+        }
+        failed
+    }
+
+    /// Check that the benchmarks common to both sets of results were run with the same parameters.
+    ///
+    /// Returns a description of each mismatching parameter. Benchmarks lacking recorded
+    /// parameters on either side can't be checked and are ignored. Differing numbers of
+    /// in-process iterations are only reported if `compare_iters`.
+    fn param_mismatches(&self, other: &ResultFile, compare_iters: bool) -> Vec<String> {
+        let mut keys = self
+            .params
+            .keys()
+            .filter(|k| other.params.contains_key(*k))
+            .collect::<Vec<_>>();
+        keys.sort();
+        let mut mismatches = Vec::new();
+        for k in keys {
+            let p1 = &self.params[k];
+            let p2 = &other.params[k];
+            if compare_iters && p1.inproc_iters != p2.inproc_iters {
+                let iters = |n| match n {
+                    0 => runner::AUTO_ITERS.to_owned(),
+                    n => n.to_string(),
+                };
+                mismatches.push(format!(
+                    "{k}: inproc_iters {} vs. {}",
+                    iters(p1.inproc_iters),
+                    iters(p2.inproc_iters)
+                ));
+            }
+            if p1.proc_execs != p2.proc_execs {
+                mismatches.push(format!(
+                    "{k}: proc_execs {} vs. {}",
+                    p1.proc_execs, p2.proc_execs
+                ));
+            }
+            if p1.extra_args != p2.extra_args {
+                mismatches.push(format!(
+                    "{k}: extra_args {:?} vs. {:?}",
+                    p1.extra_args, p2.extra_args
+                ));
+            }
+            if p1.suite_args != p2.suite_args {
+                mismatches.push(format!(
+                    "{k}: suite extra_args {:?} vs. {:?}",
+                    p1.suite_args, p2.suite_args
+                ));
+            }
+            if p1.command != p2.command {
+                mismatches.push(format!(
+                    "{k}: command {:?} vs. {:?}",
+                    p1.command, p2.command
+                ));
+            }
+            if let (Some(h1), Some(h2)) = (&p1.harness, &p2.harness)
+                && h1 != h2
             {
-                ids.push(id);
+                mismatches.push(format!(
+                    "{k}: harness {} vs. {}",
+                    h1.display(),
+                    h2.display()
+                ));
             }
         }
-        ids.sort();
-        for id in ids {
-            let extra = self.load_extra(id);
-            println!("{id:3}: {}", extra.comment.unwrap_or("".into()));
+        mismatches
+    }
+
+    /// Check the results have the same data dimensionality.
+    ///
+    /// Fails unless the same set of benchmarks were run with the same number of iterations (on a
+    /// per-benchmark basis), and if `strict`, the same number of process executions. Otherwise,
+    /// returns the benchmarks with different numbers of process executions, sorted, which are
+    /// still comparable as independent samples. Benchmarks that failed in either set of results
+    /// are ignored, as they can't be compared anyway.
+    ///
+    /// Each set of results is assumed to be consistent in isolation. `self_iters` and `other_iters`
+    /// are the calibrated in-process iteration counts of each set of results (empty if they
+    /// weren't calibrated).
+    fn same_dims(
+        &self,
+        other: &ResultFile,
+        self_iters: &HashMap<String, usize>,
+        other_iters: &HashMap<String, usize>,
+        strict: bool,
+    ) -> Result<Vec<String>, String> {
+        let failed =
+            |k: &&String| self.failures.contains_key(*k) || other.failures.contains_key(*k);
+        let self_keys: HashSet<&String> = self.data.keys().filter(|k| !failed(k)).collect();
+        let other_keys: HashSet<&String> = other.data.keys().filter(|k| !failed(k)).collect();
+        if self_keys != other_keys {
+            let mut lhs_diff = self_keys
+                .difference(&other_keys)
+                .cloned()
+                .map(|x| x.to_owned())
+                .collect::<Vec<_>>();
+            lhs_diff.sort();
+            let mut rhs_diff = other_keys
+                .difference(&self_keys)
+                .cloned()
+                .map(|x| x.to_owned())
+                .collect::<Vec<_>>();
+            rhs_diff.sort();
+            return Err(format!(
+                "results files contain different benchmarks:\n  lhs lacks: {}\n  rhs lacks: {}",
+                lhs_diff.join(", "),
+                rhs_diff.join(", ")
+            ));
+        }
+        let mut counts_differ = Vec::new();
+        for k in self_keys {
+            let (v1, v2) = (&self.data[k], &other.data[k]);
+            if v1.len() != v2.len() {
+                if strict {
+                    return Err(format!(
+                        "different number of process executions for {k}: {} vs. {}\n\
+                         drop --strict-dims to compare it anyway",
+                        v1.len(),
+                        v2.len()
+                    ));
+                }
+                counts_differ.push(k.clone());
+            }
+            match (self_iters.get(k), other_iters.get(k)) {
+                (None, None) => (),
+                (Some(i1), Some(i2)) if i1 == i2 => (),
+                (i1, i2) => {
+                    let fmt_iters = |i: Option<&usize>| {
+                        i.map_or("uncalibrated".to_owned(), |i| format!("{i} calibrated"))
+                    };
+                    return Err(format!(
+                        "different number of in-process iterations for {k}: {} vs. {}",
+                        fmt_iters(i1),
+                        fmt_iters(i2)
+                    ));
+                }
+            }
         }
+        counts_differ.sort();
+        Ok(counts_differ)
     }
 }
 
-#[derive(Parser)]
-#[command(version, about, subcommand_required = true)]
-struct Cli {
-    /// Path to the haste configuration file (defaults to haste.toml).
-    #[arg(short = 'f', long, global = true, value_name = "FILE")]
-    file: Option<PathBuf>,
+/// Means at or below this are too close to zero to divide by.
+const MIN_RATIO_BASE: f64 = 1e-9;
 
-    #[command(subcommand)]
-    mode: Mode,
+/// The ratio of `s2`'s mean to `s1`'s, or `None` if `s1`'s mean is (nearly) zero.
+fn ratio(s1: &SummaryStats, s2: &SummaryStats) -> Option<f64> {
+    (s1.mean.abs() > MIN_RATIO_BASE).then(|| s2.mean / s1.mean)
 }
 
-#[derive(Subcommand, Debug)]
-enum Mode {
-    /// Run benchmarks and store the results into a new datum.
-    #[clap(visible_alias = "b")]
-    Bench {
-        /// Attach a comment to the datum.
-        #[clap(short, long, num_args(1))]
-        comment: Option<String>,
-    },
-    /// Compare two datums.
-    #[clap(visible_alias = "d")]
-    Diff {
-        id1: usize,
-        id2: usize,
-        /// Confidence level for the interval.
-        #[arg(short, long, value_enum, default_value_t = ConfidenceLevel::default())]
-        confidence: ConfidenceLevel,
-    },
-    /// List datums.
-    #[clap(visible_alias = "l")]
-    List,
+/// The confidence interval of the ratio of two means.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum RatioCi {
+    Bounds(f64, f64),
+    /// The baseline's confidence interval is too wide relative to its mean for the ratio's to be
+    /// meaningful.
+    Unstable,
 }
 
-fn main() {
-    let cli = Cli::parse();
-    let app = App::new(cli.file);
-    match cli.mode {
-        Mode::Bench { comment } => app.cmd_bench(comment),
-        Mode::Diff {
-            id1,
-            id2,
-            confidence,
-        } => app.cmd_diff(id1, id2, confidence),
-        Mode::List => app.cmd_list(),
+impl RatioCi {
+    fn half_width(&self) -> Option<f64> {
+        match self {
+            RatioCi::Bounds(lo, hi) => Some((hi - lo) / 2.),
+            RatioCi::Unstable => None,
+        }
+    }
+
+    fn excludes_one(&self) -> bool {
+        matches!(self, RatioCi::Bounds(lo, hi) if *lo > 1. || *hi < 1.)
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::{App, ConfidenceLevel, DEFAULT_CONFIG_FILE, SummaryStats};
-    use clap::ValueEnum;
-    use std::path::PathBuf;
+/// Fieller's `g` for a baseline, `(ci / mean)²`, at or beyond which the ratio's interval is
+/// [RatioCi::Unstable]. As `g` approaches 1 the interval grows without bound, and beyond it the
+/// interval is the whole line; by a quarter (a baseline interval half as wide as the mean) it's
+/// already too wide to say much.
+const MAX_FIELLER_G: f64 = 0.25;
 
-    #[test]
-    fn cis_overlap() {
-        let s1 = SummaryStats::new(10., 5.);
-        let s2 = SummaryStats::new(5., 8.);
-        let s3 = SummaryStats::new(50.6, 20.6667);
-        let s4 = SummaryStats::new(-0.5, 0.1);
-        let s5 = SummaryStats::new(-0.5, 0.2);
-        assert!(s1.ci_overlaps(&s2));
-        assert!(s2.ci_overlaps(&s1));
-        assert!(s1.ci_overlaps(&s1));
-        assert!(s2.ci_overlaps(&s2));
-        assert!(!s1.ci_overlaps(&s3));
-        assert!(!s3.ci_overlaps(&s1));
-        assert!(s1.ci_overlaps(&s1));
-        assert!(s2.ci_overlaps(&s2));
-        assert!(s3.ci_overlaps(&s3));
-        assert!(s4.ci_overlaps(&s5));
-        assert!(s5.ci_overlaps(&s4));
-        assert!(!s4.ci_overlaps(&s1));
-        assert!(s4.ci_overlaps(&s2));
-        assert!(!s4.ci_overlaps(&s3));
-        assert!(!s5.ci_overlaps(&s1));
-        assert!(s5.ci_overlaps(&s2));
-        assert!(!s5.ci_overlaps(&s3));
+/// Fieller's `g` for the baseline `s1`: how wide its confidence interval is relative to its mean.
+fn fieller_g(s1: &SummaryStats) -> f64 {
+    (s1.ci / s1.mean).powi(2)
+}
+
+/// The confidence interval of the ratio of `s2`'s mean to `s1`'s by Fieller's theorem, treating
+/// their confidence intervals as the same multiple of the standard errors of independent means,
+/// or `None` if there's no ratio or the intervals aren't numbers.
+fn ratio_ci(s1: &SummaryStats, s2: &SummaryStats) -> Option<RatioCi> {
+    let r = ratio(s1, s2)?;
+    if !(r.is_finite() && s1.ci.is_finite() && s2.ci.is_finite()) {
+        return None;
     }
+    let g = fieller_g(s1);
+    if g >= MAX_FIELLER_G {
+        return Some(RatioCi::Unstable);
+    }
+    let spread = (s2.ci.powi(2) * (1. - g) + (r * s1.ci).powi(2)).sqrt() / s1.mean.abs();
+    Some(RatioCi::Bounds(
+        (r - spread) / (1. - g),
+        (r + spread) / (1. - g),
+    ))
+}
 
-    #[test]
-    fn test_default_config_path() {
-        use std::fs;
+/// How a benchmark's results differ between two datums.
+#[derive(Clone)]
+struct DiffRow<'a> {
+    key: &'a str,
+    s1: &'a SummaryStats,
+    s2: &'a SummaryStats,
+    ratio: Option<f64>,
+    ratio_ci: Option<RatioCi>,
+    /// The percentage change from `s1` to `s2`, which is infinite if `s1`'s mean is zero.
+    change: f64,
+    /// Are the results significantly different?
+    significant: bool,
+    /// Does either side have fewer samples than the minimum? If so, the row is never significant.
+    too_few: bool,
+    /// Is an increase an improvement, as for throughput, rather than a slowdown?
+    higher_is_better: bool,
+}
 
-        // Create a temporary directory for testing
-        let temp_dir = std::env::temp_dir().join("haste_test_default");
-        let _ = fs::remove_dir_all(&temp_dir);
-        fs::create_dir_all(&temp_dir).unwrap();
+impl DiffRow<'_> {
+    fn is_regression(&self) -> bool {
+        self.significant && !self.improved()
+    }
 
-        let original_dir = std::env::current_dir().unwrap();
-        std::env::set_current_dir(&temp_dir).unwrap();
+    /// Is this a regression of more than `percent`%?
+    fn regresses_beyond(&self, percent: f64) -> bool {
+        self.is_regression() && self.change.abs() > percent
+    }
 
-        // Create the app with no config specified
-        let app = App::new(None);
+    /// Did the value change in the better direction (or not change at all)?
+    fn improved(&self) -> bool {
+        if self.higher_is_better {
+            self.change > 0.
+        } else {
+            self.change < 0.
+        }
+    }
 
-        // Should use the default CONFIG_FILE
-        assert_eq!(app.config_file, PathBuf::from(DEFAULT_CONFIG_FILE));
+    /// Describe the change, e.g. `12.50% faster`.
+    fn summary(&self) -> String {
+        let abs_change = self.change.abs();
+        if self.too_few {
+            "indistinguishable (too few samples)".to_owned()
+        } else if !self.significant {
+            "indistinguishable".to_owned()
+        } else if self.change.is_infinite() {
+            if self.improved() { "faster" } else { "slower" }.to_owned()
+        } else if self.improved() {
+            format!("{abs_change:.2}% faster")
+        } else {
+            format!("{abs_change:.2}% slower")
+        }
+    }
 
-        // Cleanup
-        std::env::set_current_dir(original_dir).unwrap();
-        let _ = fs::remove_dir_all(&temp_dir);
+    /// Do the samples of either side look bimodal?
+    fn bimodal(&self) -> bool {
+        self.s1.bimodal || self.s2.bimodal
     }
 
-    #[test]
-    fn test_config_file_loading() {
-        use std::fs;
+    /// The number of samples on each side, e.g. `10/7`.
+    fn samples(&self) -> String {
+        format!("{}/{}", self.s1.n, self.s2.n)
+    }
 
-        let temp_dir = std::env::temp_dir().join("haste_test_loading");
-        let _ = fs::remove_dir_all(&temp_dir);
-        fs::create_dir_all(&temp_dir).unwrap();
+    /// Should the sample counts be highlighted, because they differ or are too few?
+    fn samples_suspect(&self) -> bool {
+        self.too_few || self.s1.n != self.s2.n
+    }
 
-        // Create a test config file
-        let config_content = r#"
-        proc_execs = 2
-        inproc_iters = 3
+    /// Format the first datum's value, the second datum's value, the delta, and the ratio.
+    fn columns(&self, fmts: &DiffFormats) -> [String; 4] {
+        let value =
+            |s: &SummaryStats| format!("{} ±{}", fmts.mean.format(s.mean), fmts.ci.format(s.ci));
+        let ratio = match self.ratio {
+            Some(ratio) => fmts.ratio.format(ratio),
+            None if self.change.is_infinite() => fmts.ratio.pad("∞"),
+            None => fmts.ratio.pad("n/a"),
+        };
+        let ratio_ci = match self.ratio_ci {
+            // Left-aligned, so that the `±` stays next to the ratio beside an `unstable`.
+            Some(RatioCi::Bounds(lo, hi)) => format!(
+                "{:<1$}",
+                format!("±{}", fmts.ratio_ci.render((hi - lo) / 2.)),
+                fmts.ratio_ci.width + 1
+            ),
+            Some(RatioCi::Unstable) => format!("{:>1$}", "unstable", fmts.ratio_ci.width + 1),
+            None => " ".repeat(fmts.ratio_ci.width + 1),
+        };
+        let ratio = format!("{ratio} {ratio_ci}");
+        [
+            value(self.s1),
+            value(self.s2),
+            fmts.delta.format(self.s2.mean - self.s1.mean),
+            ratio,
+        ]
+    }
+}
 
-        [executors]
-        test_exec = "/bin/sh"
+/// The default minimum number of samples each side of a comparison needs for a difference to be
+/// considered significant.
+const DEFAULT_MIN_SAMPLES: usize = 5;
 
-        [suites.test_suite]
-        dir = "."
-        harness = "test.sh"
+/// The default percentage of benchmarks with identical samples in two datums beyond which `diff`
+/// warns that the datums may be copies of each other.
+const DEFAULT_DUPLICATE_THRESHOLD: f64 = 50.;
 
-        [suites.test_suite.benchmarks.test_bench]
-        extra_args = ["arg1"]
-        "#;
-        let config_path = temp_dir.join("test_config.toml");
-        fs::write(&config_path, config_content).unwrap();
+/// How to decide whether a benchmark's results differ significantly between two datums.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+enum SignificanceTest {
+    /// The confidence intervals of the two datums don't overlap.
+    #[default]
+    Overlap,
+    /// The confidence interval of the ratio excludes 1. Benchmarks whose ratio has no interval,
+    /// because the baseline is (nearly) zero, fall back to `overlap`.
+    RatioCi,
+}
 
-        // Parse the config
-        let config_text = fs::read_to_string(&config_path).unwrap();
-        let config: crate::config::Config = toml::from_str(&config_text).unwrap();
+/// Compare `data1` with `data2`, which must have the same keys, of a metric which is better when
+/// smaller unless `higher_is_better`.
+///
+/// Benchmarks with fewer than `min_samples` samples on either side are never significant. The rows
+/// are sorted first by significance, then by improvement, descending, then by key.
+fn diff_rows<'a>(
+    data1: &'a HashMap<String, SummaryStats>,
+    data2: &'a HashMap<String, SummaryStats>,
+    min_samples: usize,
+    higher_is_better: bool,
+) -> Vec<DiffRow<'a>> {
+    tested_diff_rows(
+        data1,
+        data2,
+        min_samples,
+        higher_is_better,
+        SignificanceTest::Overlap,
+    )
+}
 
-        // Verify parsed values
-        assert_eq!(config.proc_execs, 2);
-        assert_eq!(config.inproc_iters, 3);
-        assert_eq!(config.executors.len(), 1);
-        assert!(config.executors.contains_key("test_exec"));
-        assert_eq!(config.suites.len(), 1);
-        assert!(config.suites.contains_key("test_suite"));
+/// Compare `data1` with `data2` like [diff_rows], deciding significance by `test`.
+fn tested_diff_rows<'a>(
+    data1: &'a HashMap<String, SummaryStats>,
+    data2: &'a HashMap<String, SummaryStats>,
+    min_samples: usize,
+    higher_is_better: bool,
+    test: SignificanceTest,
+) -> Vec<DiffRow<'a>> {
+    let mut rows = data1
+        .iter()
+        .map(|(k, s1)| {
+            let s2 = &data2[k];
+            let ratio = ratio(s1, s2);
+            let ratio_ci = ratio_ci(s1, s2);
+            // Without a ratio, the change can only be unbounded (or nothing).
+            let change = match ratio {
+                Some(ratio) => (ratio - 1.0) * 100.0,
+                None if s2.mean > s1.mean => f64::INFINITY,
+                None => 0.,
+            };
+            // Summaries of empty sample lists aren't numbers, and so can't be told apart.
+            let comparable = [s1.mean, s1.ci, s2.mean, s2.ci]
+                .iter()
+                .all(|x| x.is_finite());
+            let too_few = s1.n.min(s2.n) < min_samples;
+            let differ = match (test, ratio_ci) {
+                (SignificanceTest::RatioCi, Some(ci)) => ci.excludes_one(),
+                _ => !s1.ci_overlaps(s2),
+            };
+            DiffRow {
+                key: k,
+                s1,
+                s2,
+                ratio,
+                ratio_ci,
+                change,
+                significant: comparable && !too_few && differ,
+                too_few,
+                higher_is_better,
+            }
+        })
+        .collect::<Vec<_>>();
+    rows.sort_by(|r1, r2| {
+        let by_change = if higher_is_better {
+            r2.change.total_cmp(&r1.change)
+        } else {
+            r1.change.total_cmp(&r2.change)
+        };
+        r2.significant
+            .cmp(&r1.significant)
+            .then(by_change)
+            .then(r1.key.cmp(r2.key))
+    });
+    rows
+}
 
-        // Cleanup
-        let _ = fs::remove_dir_all(&temp_dir);
+/// How to format the columns of a comparison so that they line up.
+struct DiffFormats {
+    mean: NumFormat,
+    ci: NumFormat,
+    delta: NumFormat,
+    ratio: NumFormat,
+    /// The half-widths of the ratios' confidence intervals.
+    ratio_ci: NumFormat,
+}
+
+impl DiffFormats {
+    /// The formats for `rows`, whose values are shown in human units if they're `durations`.
+    fn new(rows: &[DiffRow], thousands: bool, durations: bool) -> Self {
+        let means = rows
+            .iter()
+            .flat_map(|r| [r.s1.mean, r.s2.mean])
+            .collect::<Vec<f64>>();
+        let mean = NumFormat::new(&means, 0, false, thousands);
+        let cis = rows
+            .iter()
+            .flat_map(|r| [r.s1.ci, r.s2.ci])
+            .collect::<Vec<f64>>();
+        let deltas = rows
+            .iter()
+            .map(|r| r.s2.mean - r.s1.mean)
+            .collect::<Vec<f64>>();
+        let ratios = rows.iter().filter_map(|r| r.ratio).collect::<Vec<f64>>();
+        let ratio = NumFormat::new(&ratios, 2, false, thousands);
+        let ratio_cis = rows
+            .iter()
+            .filter_map(|r| r.ratio_ci?.half_width())
+            .collect::<Vec<f64>>();
+        // The half-widths are as precise as the ratios, even if they're all small.
+        let mut ratio_ci = NumFormat {
+            precision: ratio.precision,
+            ..NumFormat::new(&ratio_cis, ratio.precision, false, thousands)
+        }
+        .fit(&ratio_cis);
+        if rows.iter().any(|r| r.ratio_ci == Some(RatioCi::Unstable)) {
+            ratio_ci.width = ratio_ci.width.max("unstable".len() - 1);
+        }
+        let mut fmts = DiffFormats {
+            mean,
+            ci: NumFormat::new(&cis, 0, false, thousands),
+            delta: NumFormat::new(&deltas, mean.precision, true, thousands),
+            ratio,
+            ratio_ci,
+        };
+        if durations {
+            fmts.mean = fmts.mean.human(&means);
+            fmts.ci = fmts.ci.human(&cis);
+            fmts.delta = fmts.delta.human(&deltas);
+        }
+        fmts
     }
+}
 
-    #[test]
-    fn confidence_level_default() {
-        assert_eq!(ConfidenceLevel::default(), ConfidenceLevel::CL99);
+/// Should output be coloured? Only if stdout is a terminal and the user hasn't set `NO_COLOR`.
+///
+/// comfy-table already checks for a terminal itself; this lets text outside tables agree with
+/// it.
+fn colour_enabled() -> bool {
+    std::io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+}
+
+/// Explain how to read a table made by [diff_table] comparing `name1` with `name2`.
+fn diff_legend(
+    name1: &str,
+    name2: &str,
+    metric: &str,
+    confidence: ConfidenceLevel,
+    min_samples: usize,
+    colour: bool,
+) -> String {
+    let mut parts = Vec::new();
+    if colour {
+        parts.push(format!(
+            "green/red = significantly faster/slower at {confidence}% confidence"
+        ));
+    } else {
+        parts.push(format!("significance is at {confidence}% confidence"));
     }
+    parts.push("± is the half-width of the confidence interval".to_owned());
+    let what = match metric {
+        TIME_METRIC => "time".to_owned(),
+        IPS_METRIC => "iterations per second, higher is better".to_owned(),
+        _ => metric.to_owned(),
+    };
+    parts.push(format!("ratio = {name2} / {name1} ({what})"));
+    parts.push(format!(
+        "n = samples in each, rows with fewer than {min_samples} are indistinguishable"
+    ));
+    parts.join("; ")
+}
 
-    #[test]
-    fn confidence_level_from_str() {
-        assert_eq!(
-            ConfidenceLevel::from_str("90", false).unwrap(),
-            ConfidenceLevel::CL90
-        );
-        assert_eq!(
-            ConfidenceLevel::from_str("95", false).unwrap(),
-            ConfidenceLevel::CL95
-        );
-        assert_eq!(
-            ConfidenceLevel::from_str("99", false).unwrap(),
-            ConfidenceLevel::CL99
-        );
+/// Check that two datums were collected with the same parameters (including the number of
+/// in-process iterations if `compare_iters`), or if `force`, warn that they weren't. Also warn if
+/// they were made by incompatible versions of haste, or on different hosts.
+fn check_comparable(
+    data1: &ResultFile,
+    extra1: &ExtraToml,
+    data2: &ResultFile,
+    extra2: &ExtraToml,
+    compare_iters: bool,
+    force: bool,
+) -> Result<(), String> {
+    let mismatches = data1.param_mismatches(data2, compare_iters);
+    if !mismatches.is_empty() {
+        if !force {
+            let mut msg = "datums were collected with different parameters:\n".to_owned();
+            for m in &mismatches {
+                msg.push_str(&format!("  {m}\n"));
+            }
+            msg.push_str("use --force to compare them anyway");
+            return Err(msg);
+        }
+        println!("WARNING: datums were collected with different parameters:");
+        for m in &mismatches {
+            println!("  {m}");
+        }
+        println!("WARNING: results may be meaningless\n");
+    }
+    if let Some(m) = extra1.version_mismatch(extra2) {
+        println!("WARNING: datums were created by incompatible versions of haste: {m}");
+        println!("WARNING: statistics or timing may have changed between them\n");
+    }
+    let host_mismatches = extra1.host_mismatches(extra2);
+    if !host_mismatches.is_empty() {
+        println!("WARNING: datums were collected on different hosts:");
+        for m in &host_mismatches {
+            println!("  {m}");
+        }
+        println!();
+    }
+    Ok(())
+}
+
+/// Write the descriptions of `keys` and of the suites recorded in `extras` to `out`, preferring
+/// those of later datums if they differ.
+fn write_descriptions<'a>(
+    out: &mut dyn Write,
+    keys: impl Iterator<Item = &'a str>,
+    extras: &[&ExtraToml],
+) -> io::Result<()> {
+    let latest = |get: fn(&ExtraToml) -> &HashMap<String, String>, k: &str| {
+        extras.iter().rev().find_map(|e| get(e).get(k)).cloned()
+    };
+    let mut keys = keys.collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+    let mut suites = extras
+        .iter()
+        .flat_map(|e| e.suite_descriptions.keys())
+        .collect::<Vec<_>>();
+    suites.sort();
+    suites.dedup();
+    let mut lines = suites
+        .iter()
+        .filter_map(|s| {
+            Some(format!(
+                "  suite {s}: {}",
+                latest(|e| &e.suite_descriptions, s)?
+            ))
+        })
+        .collect::<Vec<_>>();
+    lines.extend(
+        keys.iter()
+            .filter_map(|k| Some(format!("  {k}: {}", latest(|e| &e.descriptions, k)?))),
+    );
+    if lines.is_empty() {
+        writeln!(out, "\nno benchmarks or suites have a description")
+    } else {
+        writeln!(out, "\ndescriptions:\n{}", lines.join("\n"))
+    }
+}
+
+/// An empty table without borders, coloured if `colour`.
+fn new_table(colour: bool) -> Table {
+    let mut table = Table::new();
+    table.load_preset(comfy_table::presets::NOTHING);
+    if !colour {
+        table.force_no_tty();
+    }
+    table
+}
+
+/// Formats which line up the means, and the confidence intervals, of `summaries`, in human units
+/// if they're `durations`.
+fn mean_ci_formats<'a>(
+    summaries: impl Iterator<Item = &'a SummaryStats> + Clone,
+    thousands: bool,
+    durations: bool,
+) -> (NumFormat, NumFormat) {
+    let means = summaries.clone().map(|s| s.mean).collect::<Vec<_>>();
+    let cis = summaries.map(|s| s.ci).collect::<Vec<_>>();
+    let (mean, ci) = (
+        NumFormat::new(&means, 0, false, thousands),
+        NumFormat::new(&cis, 0, false, thousands),
+    );
+    if durations {
+        (mean.human(&means), ci.human(&cis))
+    } else {
+        (mean, ci)
+    }
+}
+
+/// Appended to rows whose samples look bimodal.
+const BIMODAL_MARKER: &str = " bimodal?";
+/// Explains [BIMODAL_MARKER], for printing after tables which have it.
+const BIMODAL_NOTE: &str = "bimodal? = the samples fall into two clusters, so the mean may describe \
+     neither: see `haste compare-bench`";
+
+/// Make a table comparing `data1` with `data2`, which must have the same keys.
+///
+/// Also returns the number of benchmarks that are significantly worse in `data2`.
+fn diff_table(
+    data1: &HashMap<String, SummaryStats>,
+    data2: &HashMap<String, SummaryStats>,
+    header1: &str,
+    header2: &str,
+    thousands: bool,
+    min_samples: usize,
+    metric: &str,
+) -> (Table, usize) {
+    let rows = diff_rows(data1, data2, min_samples, higher_is_better(metric));
+    rows_table(
+        &rows,
+        header1,
+        header2,
+        thousands,
+        human_durations(metric),
+        0,
+        &HashMap::new(),
+    )
+}
+
+/// Make a table of `rows`, like [diff_table] and with the values in human units if they're
+/// `durations`, with a blank line after the first `pinned` rows,
+/// and with the notes in `notes` appended to the summaries of their benchmarks.
+fn rows_table(
+    rows: &[DiffRow],
+    header1: &str,
+    header2: &str,
+    thousands: bool,
+    durations: bool,
+    pinned: usize,
+    notes: &HashMap<String, String>,
+) -> (Table, usize) {
+    let fmts = DiffFormats::new(rows, thousands, durations);
+
+    let mut table = new_table(colour_enabled());
+    table.set_header(vec![
+        Cell::new("Benchmark").set_alignment(CellAlignment::Left),
+        Cell::new("n").set_alignment(CellAlignment::Right),
+        Cell::new(header1).set_alignment(CellAlignment::Right),
+        Cell::new(header2).set_alignment(CellAlignment::Right),
+        Cell::new("Delta").set_alignment(CellAlignment::Right),
+        Cell::new("Ratio").set_alignment(CellAlignment::Right),
+        Cell::new("Summary").set_alignment(CellAlignment::Left),
+    ]);
+    for (i, row) in rows.iter().enumerate() {
+        if i == pinned && pinned > 0 {
+            table.add_row(Vec::<Cell>::new());
+        }
+        let mut samples = Cell::new(row.samples()).set_alignment(CellAlignment::Right);
+        if row.samples_suspect() {
+            samples = samples.fg(Color::Yellow);
+        }
+        let mut cells = vec![Cell::new(row.key), samples];
+        let [v1, v2, delta, ratio] = row.columns(&fmts);
+        for col in [v1, v2, delta] {
+            cells.push(Cell::new(col).set_alignment(CellAlignment::Right));
+        }
+        let ratio = Cell::new(ratio).set_alignment(CellAlignment::Right);
+        let colour = match (row.significant, row.improved()) {
+            (false, _) => {
+                cells.push(ratio.add_attribute(Attribute::Dim));
+                Color::Magenta
+            }
+            (true, true) => {
+                cells.push(ratio.fg(Color::Green));
+                Color::Green
+            }
+            (true, false) => {
+                cells.push(ratio.fg(Color::Red));
+                Color::Red
+            }
+        };
+        let mut summary = row.summary();
+        if row.bimodal() {
+            summary.push_str(BIMODAL_MARKER);
+        }
+        if let Some(note) = notes.get(row.key) {
+            summary.push_str(note);
+        }
+        cells.push(Cell::new(summary).fg(colour));
+        table.add_row(cells);
+    }
+    let regressions = rows.iter().filter(|r| r.is_regression()).count();
+    (table, regressions)
+}
+
+/// The benchmarks that failed in either of the datums called `name1` and `name2`, sorted, each
+/// with the names of the datums it failed in, e.g. `Datum1 and Datum2`.
+fn failed_keys(
+    name1: &str,
+    failures1: &HashMap<String, Failure>,
+    name2: &str,
+    failures2: &HashMap<String, Failure>,
+) -> Vec<(String, String)> {
+    let mut keys = failures1.keys().chain(failures2.keys()).collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .map(|k| {
+            let names = [(name1, failures1), (name2, failures2)]
+                .iter()
+                .filter(|(_, f)| f.contains_key(k))
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>();
+            (k.clone(), names.join(" and "))
+        })
+        .collect()
+}
+
+/// Add a row to `table`, made by [diff_table], for each benchmark that failed in either datum.
+fn add_failed_rows(
+    table: &mut Table,
+    name1: &str,
+    failures1: &HashMap<String, Failure>,
+    name2: &str,
+    failures2: &HashMap<String, Failure>,
+) {
+    for (k, names) in failed_keys(name1, failures1, name2, failures2) {
+        let mut cells = vec![Cell::new(k)];
+        cells.extend((0..5).map(|_| Cell::new("")));
+        cells.push(Cell::new(format!("failed on {names}")).fg(Color::Red));
+        table.add_row(cells);
+    }
+}
+
+/// The datums to compare when looking for regressions over the last `window` datums in `ids`: the
+/// one `window` steps before the newest (or the oldest, if there aren't that many) and the newest.
+///
+/// Returns `None` if there are fewer than two datums.
+fn window_ends(ids: &[usize], window: usize) -> Option<(usize, usize)> {
+    let newest = ids.len().checked_sub(1)?;
+    let base = newest.saturating_sub(window.max(1));
+    (base != newest).then(|| (ids[base], ids[newest]))
+}
+
+/// The benchmarks whose samples are bit-for-bit identical in `data1` and `data2`, sorted, and how
+/// many benchmarks the two have in common.
+fn identical_samples(
+    data1: &HashMap<String, Vec<f64>>,
+    data2: &HashMap<String, Vec<f64>>,
+) -> (Vec<String>, usize) {
+    let mut identical = Vec::new();
+    let mut common = 0;
+    for (k, v1) in data1 {
+        let Some(v2) = data2.get(k) else {
+            continue;
+        };
+        common += 1;
+        if v1.len() == v2.len() && v1.iter().zip(v2).all(|(a, b)| a.to_bits() == b.to_bits()) {
+            identical.push(k.clone());
+        }
+    }
+    identical.sort();
+    (identical, common)
+}
+
+/// A warning that the datums called `name1` and `name2` may be copies of each other, because more
+/// than `threshold` percent of the `common` benchmarks they share have `identical` samples, or
+/// `None` if not. `same` lists the metadata the datums also share, e.g. `start time`.
+fn duplicate_warning(
+    name1: &str,
+    name2: &str,
+    identical: &[String],
+    common: usize,
+    threshold: f64,
+    same: &[&str],
+) -> Option<String> {
+    if identical.is_empty() || identical.len() as f64 * 100. <= threshold * common as f64 {
+        return None;
+    }
+    let mut w = if identical.len() == common {
+        format!(
+            "{name1} and {name2} may be copies of each other: the samples of all {common} \
+             benchmarks they share are identical"
+        )
+    } else {
+        format!(
+            "{name1} and {name2} may be copies of each other: the samples of {} of the {common} \
+             benchmarks they share are identical: {}",
+            identical.len(),
+            identical.join(", ")
+        )
+    };
+    if !same.is_empty() {
+        w.push_str(&format!(
+            "\nWARNING: they also have the same {}",
+            same.join(", ")
+        ));
+    }
+    Some(w)
+}
+
+/// Restrict the results of `name1`, `results1`, and `name2`, `results2`, to the benchmarks named in
+/// `benchmarks`, or say which of those neither datum has, and which benchmarks they do have.
+fn select_benchmarks(
+    benchmarks: &[String],
+    (name1, results1): (&str, &mut ResultFile),
+    (name2, results2): (&str, &mut ResultFile),
+) -> Result<(), String> {
+    let available = [&*results1, &*results2]
+        .iter()
+        .flat_map(|r| r.data.keys().chain(r.failures.keys()))
+        .filter_map(|k| Some(k.split_once('/')?.0))
+        .collect::<BTreeSet<_>>();
+    let unknown = benchmarks
+        .iter()
+        .filter(|b| !available.contains(b.as_str()))
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    if !unknown.is_empty() {
+        return Err(format!(
+            "error: neither {name1} nor {name2} has a benchmark named {}: they have {}",
+            unknown.join(", "),
+            available.into_iter().collect::<Vec<_>>().join(", ")
+        ));
+    }
+    results1.retain_benchmarks(benchmarks);
+    results2.retain_benchmarks(benchmarks);
+    Ok(())
+}
+
+/// The error for comparing a metric which only one of two datums has: the datum called `name`,
+/// whose `results` lack it (as `error` explains), and the datum called `other_name`.
+///
+/// Datums from before a metric was collected are often compared with newer ones which have it, so
+/// this says which metrics can be compared instead.
+fn lacking_metric(
+    name: &str,
+    results: &ResultFile,
+    other_name: &str,
+    other: &ResultFile,
+    error: String,
+) -> String {
+    let theirs = other.available_metrics();
+    let common = results
+        .available_metrics()
+        .into_iter()
+        .filter(|m| theirs.contains(m))
+        .collect::<Vec<_>>();
+    format!(
+        "{name}: {error}, though {other_name} has it\nmetrics both datums have: {}",
+        common.join(", ")
+    )
+}
+
+/// Subtract the harness overheads recorded in `extra` from `data` (the time results of the datum
+/// called `name`), warning about benchmarks that are within noise of the overhead.
+fn apply_overheads(
+    name: &str,
+    data: &mut ResultFile,
+    extra: &ExtraToml,
+    confidence: ConfidenceLevel,
+) -> Result<(), String> {
+    if extra.overheads.is_empty() {
+        return Err(format!(
+            "{name} has no harness overhead measurements (see `calibration_benchmark`)"
+        ));
+    }
+    let noisy = data.subtract_overhead(&extra.overheads, confidence);
+    if !noisy.is_empty() {
+        println!("WARNING: {name}: within noise of the harness overhead (clamped at zero):");
+        for k in &noisy {
+            println!("  {k}");
+        }
+        println!();
+    }
+    Ok(())
+}
+
+/// The kind of store in `state_dir`: SQLite if a database exists, otherwise the filesystem.
+/// The state directory to use from the directory `cwd`: `state_dir` if given, otherwise for
+/// commands which store datums (`creates`), the `.haste` next to `config_file`, and for other
+/// commands the nearest existing `.haste` found by [find_state_dir].
+///
+/// If there's no existing `.haste`, this is the one that would be in `cwd`, which is left
+/// uncreated, so the store is just empty.
+fn resolve_state_dir(
+    cwd: &Path,
+    config_file: &Path,
+    state_dir: Option<PathBuf>,
+    creates: bool,
+) -> PathBuf {
+    if let Some(dir) = state_dir {
+        return cwd.join(dir);
+    }
+    if creates {
+        let config_dir = config_file.parent().unwrap_or(Path::new(""));
+        return cwd.join(config_dir).join(DOT_DIR);
+    }
+    find_state_dir(cwd).unwrap_or_else(|| cwd.join(DOT_DIR))
+}
+
+/// The nearest `.haste` directory in `start` or its parents, like git finds `.git`.
+///
+/// The search stops at the root of a git repository, so that a project's datums are never mixed
+/// up with those of a project it's checked out inside.
+fn find_state_dir(start: &Path) -> Option<PathBuf> {
+    for dir in start.ancestors() {
+        let state_dir = dir.join(DOT_DIR);
+        if state_dir.is_dir() {
+            return Some(state_dir);
+        }
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+    None
+}
+
+fn detect_store_kind(state_dir: &Path) -> StoreKind {
+    if fs::exists(state_dir.join(store::SQLITE_DB)).unwrap() {
+        StoreKind::Sqlite
+    } else {
+        StoreKind::Fs
+    }
+}
+
+/// A column of `haste stat`'s output.
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+enum StatColumn {
+    /// The number of samples.
+    N,
+    Mean,
+    /// The sample standard deviation.
+    Stddev,
+    /// The sample variance.
+    Variance,
+    Min,
+    /// The first quartile.
+    Q1,
+    Median,
+    /// The third quartile.
+    Q3,
+    Max,
+    /// The interquartile range.
+    Iqr,
+    /// The adjusted Fisher-Pearson skewness.
+    Skewness,
+}
+
+impl StatColumn {
+    fn name(self) -> String {
+        self.to_possible_value().unwrap().get_name().to_owned()
+    }
+
+    /// The value of this column for `d`, or `None` if there are too few samples to compute it.
+    fn value(self, d: &stats::Descriptive) -> Option<f64> {
+        match self {
+            StatColumn::N => Some(d.n as f64),
+            StatColumn::Mean => Some(d.mean),
+            StatColumn::Stddev => d.stddev,
+            StatColumn::Variance => d.variance,
+            StatColumn::Min => Some(d.min),
+            StatColumn::Q1 => Some(d.q1),
+            StatColumn::Median => Some(d.median),
+            StatColumn::Q3 => Some(d.q3),
+            StatColumn::Max => Some(d.max),
+            StatColumn::Iqr => Some(d.iqr()),
+            StatColumn::Skewness => d.skewness,
+        }
+    }
+}
+
+/// How to print machine-readable output.
+#[derive(Copy, Clone, Debug, Default, PartialEq, ValueEnum)]
+enum OutputFormat {
+    /// A table for humans.
+    #[default]
+    Table,
+    /// Comma-separated values, with a header row.
+    Csv,
+    /// A JSON array with one object per row.
+    Json,
+}
+
+/// Quote `s` for use as a CSV field, if it needs it.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+/// Format the `columns` of descriptive statistics for each benchmark key in `rows` as `format`.
+///
+/// Statistics which can't be computed (e.g. the standard deviation of a single sample) are `n/a`
+/// in tables, empty in CSV and `null` in JSON.
+fn format_stats(
+    rows: &[(&str, stats::Descriptive)],
+    columns: &[StatColumn],
+    format: OutputFormat,
+) -> String {
+    match format {
+        OutputFormat::Table => {
+            let mut table = Table::new();
+            table.load_preset(comfy_table::presets::NOTHING);
+            let mut header = vec![Cell::new("Benchmark").set_alignment(CellAlignment::Left)];
+            header.extend(
+                columns
+                    .iter()
+                    .map(|c| Cell::new(c.name()).set_alignment(CellAlignment::Right)),
+            );
+            table.set_header(header);
+            // Each column is formatted separately, as their magnitudes vary wildly.
+            let fmts = columns
+                .iter()
+                .map(|c| {
+                    let values = rows
+                        .iter()
+                        .filter_map(|(_, d)| c.value(d))
+                        .collect::<Vec<_>>();
+                    let precision = if *c == StatColumn::N { 0 } else { 3 };
+                    NumFormat::new(&values, precision, false, false)
+                })
+                .collect::<Vec<_>>();
+            for (k, d) in rows {
+                let mut cells = vec![Cell::new(k)];
+                for (c, fmt) in columns.iter().zip(&fmts) {
+                    let v = c.value(d).map_or("n/a".to_owned(), |v| fmt.render(v));
+                    cells.push(Cell::new(v).set_alignment(CellAlignment::Right));
+                }
+                table.add_row(cells);
+            }
+            format!("{table}\n")
+        }
+        OutputFormat::Csv => {
+            let mut out = "benchmark".to_owned();
+            for c in columns {
+                out.push(',');
+                out.push_str(&c.name());
+            }
+            out.push('\n');
+            for (k, d) in rows {
+                out.push_str(&csv_field(k));
+                for c in columns {
+                    out.push(',');
+                    if let Some(v) = c.value(d) {
+                        out.push_str(&v.to_string());
+                    }
+                }
+                out.push('\n');
+            }
+            out
+        }
+        OutputFormat::Json => {
+            let rows = rows
+                .iter()
+                .map(|(k, d)| {
+                    let mut obj = serde_json::Map::new();
+                    obj.insert("benchmark".to_owned(), (*k).into());
+                    for c in columns {
+                        let v = match (c, c.value(d)) {
+                            (StatColumn::N, _) => d.n.into(),
+                            (_, Some(v)) => v.into(),
+                            (_, None) => serde_json::Value::Null,
+                        };
+                        obj.insert(c.name(), v);
+                    }
+                    serde_json::Value::Object(obj)
+                })
+                .collect::<Vec<_>>();
+            format!("{}\n", serde_json::to_string_pretty(&rows).unwrap())
+        }
+    }
+}
+
+/// Where a run is logged to by default until the datum's ID is known.
+const PARTIAL_LOG: &str = "run.log";
+
+/// Log `event` to `log`, if there is one.
+fn log_event(log: &Option<RunLog>, event: &str, fields: &[(&str, String)]) {
+    if let Some(log) = log {
+        log.event(event, fields);
+    }
+}
+
+/// How to run benchmarks for a new datum.
+#[derive(Default)]
+struct BenchOptions {
+    comment: Option<String>,
+    max_duration: Option<Duration>,
+    verbose: bool,
+    skip_build: bool,
+    /// If non-empty, alternate the pexecs of each benchmark between these executors.
+    interleave: Vec<String>,
+    /// If present, log the run to this file, or to the new datum's log file if `None`.
+    log_file: Option<Option<PathBuf>>,
+    log_format: LogFormat,
+    /// If present, where to stream the run's events to.
+    events: Option<events::Destination>,
+    /// Skip executors whose binaries don't exist, rather than failing.
+    skip_missing_executors: bool,
+    /// Record failing pexecs and carry on, rather than stopping at the first failure.
+    keep_going: bool,
+    /// Don't print a summary of the results at the end of the run.
+    quiet: bool,
+    /// If present, run only the benchmarks missing from this datum, and store them together with
+    /// its results.
+    extend: Option<usize>,
+    /// The order to run pexecs in, if not the default.
+    order: Option<runner::Order>,
+    /// Print the pexecs that would be run, in order, rather than running them.
+    dry_run: bool,
+    /// Mark the datum as made by `haste smoke`.
+    smoke: bool,
+    /// If present, the niceness to run pexecs with, overriding the config.
+    nice: Option<i32>,
+    /// If present, the niceness to run haste with, overriding the config.
+    haste_nice: Option<i32>,
+    /// The executor group whose executors the run compares, if any, to record in the datum.
+    executor_group: Option<String>,
+    /// If present, the datum whose samples of the benchmarks run are added to, to make the new
+    /// datum.
+    refine: Option<refine::Refining>,
+}
+
+/// How to compare two datums.
+struct DiffOptions {
+    confidence: ConfidenceLevel,
+    relaxed: bool,
+    metric: String,
+    force: bool,
+    /// Separate groups of thousands in numbers with commas.
+    thousands: bool,
+    /// Subtract the measured harness overhead from each benchmark's times.
+    subtract_overhead: bool,
+    /// Benchmarks with fewer samples than this on either side are never significant.
+    min_samples: usize,
+    /// The statistic to compare.
+    stat: Statistic,
+    /// Show the configured descriptions of the benchmarks after the table.
+    describe: bool,
+    /// Print only what's the same each time the same datums are compared: no colour, and no
+    /// durations.
+    deterministic: bool,
+    /// If present, also write a JUnit report of the comparison to this file.
+    junit: Option<PathBuf>,
+    /// If present, fail if any benchmark is significantly worse by more than this percentage.
+    fail_on_regression: Option<f64>,
+    /// If present, also plot the comparison as an SVG in this file.
+    plot: Option<PathBuf>,
+    /// Give the plot a log scale.
+    log_scale: bool,
+    /// Note if the environment may have changed between the datums.
+    env_warnings: bool,
+    /// Fail, rather than warn, if the datums have different numbers of pexecs of a benchmark.
+    strict_dims: bool,
+    /// The order to show the benchmarks in.
+    order: order::RowOrder,
+    /// Targets to note the benchmarks that cross between the datums.
+    targets: Vec<targets::Target>,
+    /// How to decide whether benchmarks differ significantly.
+    test: SignificanceTest,
+    /// Where to write the comparison.
+    output: output::OutputTarget,
+    /// Warn that the datums may be copies if more than this percentage of their benchmarks have
+    /// identical samples.
+    duplicate_threshold: f64,
+    /// If not empty, compare only the benchmarks with these names.
+    benchmarks: Vec<String>,
+    /// If present, explain how this benchmark key's row was derived rather than showing the
+    /// table.
+    explain: Option<String>,
+}
+
+impl Default for DiffOptions {
+    /// The options of `haste diff` with no flags.
+    fn default() -> Self {
+        DiffOptions {
+            confidence: ConfidenceLevel::default(),
+            relaxed: false,
+            metric: TIME_METRIC.to_owned(),
+            force: false,
+            thousands: false,
+            subtract_overhead: false,
+            min_samples: DEFAULT_MIN_SAMPLES,
+            stat: Statistic::Mean,
+            describe: false,
+            deterministic: false,
+            junit: None,
+            fail_on_regression: None,
+            plot: None,
+            log_scale: false,
+            env_warnings: true,
+            strict_dims: false,
+            order: Default::default(),
+            targets: Vec::new(),
+            test: SignificanceTest::Overlap,
+            output: Default::default(),
+            duplicate_threshold: DEFAULT_DUPLICATE_THRESHOLD,
+            benchmarks: Vec::new(),
+            explain: None,
+        }
+    }
+}
+
+/// How to show a datum.
+struct ShowOptions {
+    confidence: ConfidenceLevel,
+    /// Separate groups of thousands in numbers with commas.
+    thousands: bool,
+    /// Also show every sample.
+    raw: bool,
+    /// Subtract the measured harness overhead from each benchmark's times.
+    subtract_overhead: bool,
+    metric: String,
+    /// If present, show a histogram with this many bins of each benchmark's samples.
+    histogram_bins: Option<usize>,
+    /// Show the configured descriptions of the benchmarks after the table.
+    describe: bool,
+    /// Show the spread of the CPU frequency and temperature readings of each benchmark.
+    env: bool,
+    /// Where to write what's shown.
+    output: output::OutputTarget,
+}
+
+/// How often `haste watch` checks for changes.
+const WATCH_POLL: Duration = Duration::from_millis(250);
+/// How long files must be unchanged before `haste watch` starts benchmarking.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(1000);
+
+struct App {
+    /// The directory where persistent state is stored.
+    state_dir: PathBuf,
+    /// The path to the config file.
+    config_file: PathBuf,
+    /// Where datums are stored.
+    store: Box<dyn store::Store>,
+    store_kind: StoreKind,
+    /// Summaries of datums computed by previous commands.
+    cache: SummaryCache,
+    /// Ignore cached summaries, recomputing them.
+    no_cache: bool,
+    /// The seed of `rng`.
+    seed: u64,
+    /// The random number generator used by every stochastic analysis, so that `--seed` makes
+    /// them all reproducible.
+    rng: RefCell<StdRng>,
+}
+
+impl App {
+    /// Create the app. If `store_kind` is `None`, the SQLite backend is used if a database already
+    /// exists, otherwise the filesystem backend is used. If `seed` is `None`, a random one is used.
+    ///
+    /// The state directory is found by [resolve_state_dir], and is only created if `creates`, for
+    /// commands which store datums.
+    fn new(
+        config_file: Option<PathBuf>,
+        state_dir: Option<PathBuf>,
+        creates: bool,
+        store_kind: Option<StoreKind>,
+        no_cache: bool,
+        seed: Option<u64>,
+    ) -> Self {
+        let config_file = config_file.unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_FILE));
+        let state_dir = resolve_state_dir(
+            &env::current_dir().unwrap(),
+            &config_file,
+            state_dir,
+            creates,
+        );
+        if creates && !fs::exists(&state_dir).unwrap() {
+            fs::create_dir_all(&state_dir).unwrap();
+        }
+        let store_kind = store_kind.unwrap_or_else(|| detect_store_kind(&state_dir));
+        let store = store::open(store_kind, state_dir.clone());
+        let cache = SummaryCache::new(&state_dir);
+        let seed = seed.unwrap_or_else(|| rand::random_range(..=MAX_SEED));
+        Self {
+            state_dir,
+            config_file,
+            store,
+            store_kind,
+            cache,
+            no_cache,
+            seed,
+            rng: RefCell::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+
+    /// Store a new datum, recording how it was created, and return the ID.
+    fn store_datum(&self, results: ResultFile, mut extra: ExtraToml) -> usize {
+        runner::between_pexecs("storing a datum");
+        extra.haste_version = Some(env!("CARGO_PKG_VERSION").to_owned());
+        extra.args = env::args().collect();
+        extra.config_file =
+            Some(fs::canonicalize(&self.config_file).unwrap_or_else(|_| self.config_file.clone()));
+        extra.experiment = self.active_experiment();
+        self.store.store(&results, &extra)
+    }
+
+    /// Load the results of datum `id`, exiting if they're invalid.
+    fn load_results(&self, id: usize) -> ResultFile {
+        self.try_load_results(id).unwrap_or_else(|e| {
+            eprintln!("error: {e}");
+            process::exit(1);
+        })
+    }
+
+    /// Load the results of datum `id`, checking that they're valid.
+    fn try_load_results(&self, id: usize) -> Result<ResultFile, String> {
+        if !self.store.contains(id) {
+            return Err(format!("no such datum {id}"));
+        }
+        let results = self.store.load_results(id);
+        results.validate().map_err(|e| format!("Datum{id}: {e}"))?;
+        Ok(results)
+    }
+
+    fn load_extra(&self, id: usize) -> ExtraToml {
+        self.store.try_load_extra(id).unwrap_or_else(|e| {
+            eprintln!("error: {e}");
+            process::exit(1);
+        })
+    }
+
+    /// The note that the datum `r`, called `name`, has no metadata, if it hasn't.
+    fn missing_extra_note(&self, r: &DatumRef, name: &str) -> Option<String> {
+        let has_extra = match &r.state_dir {
+            None => self.store.has_extra(r.id),
+            Some(dir) => store::open(detect_store_kind(dir), dir.clone()).has_extra(r.id),
+        };
+        (!has_extra).then(|| {
+            format!(
+                "note: {name} has no extra.toml, so how it was made is unknown (`haste edit {}` \
+                 recreates it)",
+                r.id
+            )
+        })
+    }
+
+    /// Load the results and metadata of the datum `r`, which may be in another state directory.
+    fn load_ref(&self, r: &DatumRef) -> Result<(ResultFile, ExtraToml), String> {
+        let Some(dir) = &r.state_dir else {
+            return Ok((
+                self.try_load_results(r.id)?,
+                self.store.try_load_extra(r.id)?,
+            ));
+        };
+        if !dir.is_dir() {
+            return Err(format!("no such state directory {}", dir.display()));
+        }
+        let store = store::open(detect_store_kind(dir), dir.clone());
+        if !store.contains(r.id) {
+            return Err(format!("no such datum {}", r.name(true)));
+        }
+        let results = store.load_results(r.id);
+        results
+            .validate()
+            .map_err(|e| format!("{}: {e}", r.name(true)))?;
+        Ok((results, store.try_load_extra(r.id)?))
+    }
+
+    /// The summary statistics of the time samples of datum `id`, from the cache if possible.
+    fn load_summary(
+        &self,
+        id: usize,
+        confidence: ConfidenceLevel,
+    ) -> HashMap<String, SummaryStats> {
+        if !self.store.contains(id) {
+            eprintln!("error: no such datum {id}");
+            process::exit(1);
+        }
+        let fingerprint = self.store.fingerprint(id);
+        if !self.no_cache
+            && let Some(summaries) = self.cache.get(id, &fingerprint, confidence)
+        {
+            return summaries;
+        }
+        let summaries = self.load_results(id).summarise(confidence);
+        self.cache.put(id, &fingerprint, confidence, &summaries);
+        summaries
+    }
+
+    /// The URL of each benchmark key recorded in `extras`, preferring those of later datums if
+    /// they differ. Datums that predate recording URLs use those in the config, if there is one.
+    fn benchmark_urls(&self, extras: &[&ExtraToml]) -> HashMap<String, String> {
+        let mut current = None;
+        let mut urls = HashMap::new();
+        for extra in extras {
+            let recorded = extra.urls.as_ref().unwrap_or_else(|| {
+                current.get_or_insert_with(|| {
+                    if self.config_file.exists() {
+                        runner::key_urls(&self.load_config())
+                    } else {
+                        HashMap::new()
+                    }
+                })
+            });
+            urls.extend(recorded.iter().map(|(k, u)| (k.clone(), u.clone())));
+        }
+        urls
+    }
+
+    /// The old names of the benchmark keys in the config, if there is one.
+    fn key_aliases(&self) -> aliases::KeyAliases {
+        if self.config_file.exists() {
+            aliases::KeyAliases::new(&self.load_config())
+        } else {
+            aliases::KeyAliases::default()
+        }
+    }
+
+    /// When to warn that the environment may have changed between datums: as configured, or the
+    /// defaults if there's no config file.
+    fn env_thresholds(&self) -> config::EnvWarnings {
+        if self.config_file.exists() {
+            self.load_config().env_warnings
+        } else {
+            config::EnvWarnings::default()
+        }
+    }
+
+    /// Load and validate the config file, exiting if it's broken.
+    fn load_config(&self) -> config::Config {
+        let config_path = self.config_file.display();
+        let config_text = fs::read_to_string(&self.config_file).unwrap_or_else(|e| {
+            eprintln!("error: failed to read {config_path}: {e}");
+            process::exit(1);
+        });
+        let mut config: config::Config = match toml::from_str(&config_text) {
+            Ok(d) => d,
+            Err(e) => {
+                eprintln!("Unable to parse {config_path}: {e}");
+                std::process::exit(1);
+            }
+        };
+        if let Err(e) = config
+            .expand_benchmark_sets()
+            .and_then(|()| config.validate())
+        {
+            eprintln!("error: {config_path}: {e}");
+            process::exit(1);
+        }
+        config
+    }
+
+    /// Run benchmarks and store the results as a new datum.
+    ///
+    /// If successful, the new datum is printed to stdout.
+    fn cmd_bench(
+        &self,
+        mut opts: BenchOptions,
+        perf: bool,
+        comment_from_git: bool,
+        no_notify: bool,
+    ) {
+        let mut config = self.load_config();
+        config.perf |= perf;
+        if config.perf
+            && let Err(e) = runner::check_perf()
+        {
+            eprintln!("error: {e}");
+            process::exit(1);
+        }
+        if opts.comment.is_none()
+            && (comment_from_git || config.auto_comment == Some(config::AutoComment::Git))
+        {
+            // Not being in a repository shouldn't stop the benchmarks from running.
+            match git::comment(Path::new(".")) {
+                Ok(c) => opts.comment = Some(c),
+                Err(e) => eprintln!("warning: not commenting the datum: {e}"),
+            }
+        }
+        let id = self.bench(&mut config, &opts);
+
+        if let Some(notify) = &config.notify
+            && !no_notify
+        {
+            // The datum is safely stored, so failing to send it isn't fatal.
+            match self.notify(notify, id) {
+                Ok(()) => println!("haste: sent datum {id} to {}", notify.url),
+                Err(e) => eprintln!("warning: {e}"),
+            }
+        }
+    }
+
+    /// Run the benchmarks in `config` and store the results as a new datum, returning its ID.
+    ///
+    /// Executors whose binaries are missing are removed from `config` if they may be skipped.
+    fn bench(&self, config: &mut config::Config, opts: &BenchOptions) -> usize {
+        if let Err(e) = runner::check_keys(config) {
+            eprintln!("error: {e}");
+            process::exit(1);
+        }
+        // Which benchmarks had restricted executors has to be worked out before the config is
+        // restricted to those an extended datum lacks.
+        let restricted = opts.extend.map(|_| runner::restricted_keys(config));
+        let extending = opts.extend.map(|source| {
+            let base = self.load_results(source);
+            let measured = extend::restrict_to_missing(config, &base);
+            if measured.is_empty() {
+                eprintln!("error: Datum{source} already has every benchmark in the config");
+                process::exit(1);
+            }
+            println!(
+                "haste: extending Datum{source} with {} benchmarks: {}",
+                measured.len(),
+                measured.join(", ")
+            );
+            (base, extend::Composite { source, measured })
+        });
+        let (skipped, missing): (Vec<_>, Vec<_>) = config
+            .missing_executors(opts.skip_build)
+            .into_iter()
+            .partition(|(name, _)| {
+                opts.skip_missing_executors || config.executors[name].allow_missing
+            });
+        if !missing.is_empty() {
+            eprintln!("error: executor binaries not found:");
+            for (name, path) in &missing {
+                eprintln!("  {name}: {}", path.display());
+            }
+            eprintln!(
+                "use --skip-missing-executors, or set allow_missing = true for the executor, to \
+                 run without them"
+            );
+            process::exit(1);
+        }
+        for (name, path) in &skipped {
+            println!(
+                "WARNING: skipping executor {name}: {} does not exist",
+                path.display()
+            );
+            config.executors.remove(name);
+        }
+        if config.executors.is_empty() {
+            eprintln!("error: no executors left to run");
+            process::exit(1);
+        }
+        let skipped_executors = skipped
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        config.nice = opts.nice.or(config.nice);
+        config.haste_nice = opts.haste_nice.or(config.haste_nice);
+        if let Err(e) = config.check_nice() {
+            eprintln!("error: {e}");
+            process::exit(1);
+        }
+        if let Some(n) = config.haste_nice
+            && let Err(e) = nice::set(n)
+        {
+            eprintln!("error: can't set haste's niceness to {n}: {e}");
+            process::exit(1);
+        }
+        let config = &*config;
+        let plan_opts = runner::RunOptions {
+            max_duration: opts.max_duration,
+            order: opts.order,
+            seed: self.seed,
+            ..Default::default()
+        };
+        // A smoke run's few in-process iterations would make every hint look out of date.
+        if !opts.smoke {
+            self.predict_run(config, &plan_opts, opts.verbose || opts.dry_run);
+        }
+        if opts.dry_run {
+            let pexecs = runner::planned_pexecs(config, &plan_opts);
+            println!("haste: would run {} pexecs:", pexecs.len());
+            for (key, n) in pexecs {
+                println!("  [{}/{}] {key}", n + 1, config.proc_execs);
+            }
+            process::exit(0);
+        }
+        if config
+            .suites
+            .values()
+            .any(|s| s.cache_policy == CachePolicy::Cold)
+            && let Err(e) = runner::check_drop_caches()
+        {
+            eprintln!("error: {e}");
+            process::exit(1);
+        }
+        let hosts = remote::executor_hosts(config).unwrap_or_else(|e| {
+            eprintln!("error: {e}");
+            process::exit(1);
+        });
+        let log = self.open_run_log(config, opts);
+        let events = opts.events.as_ref().map(|dest| {
+            events::EventStream::open(dest).unwrap_or_else(|e| {
+                eprintln!("error: {e}");
+                process::exit(1);
+            })
+        });
+        for name in &skipped_executors {
+            log_event(
+                &log,
+                "warning",
+                &[("message", format!("skipped missing executor {name}"))],
+            );
+        }
+        let start_time = unix_now();
+        let start = Instant::now();
+        let builds = if opts.skip_build {
+            HashMap::new()
+        } else {
+            runner::build_executors(config).unwrap_or_else(|e| {
+                eprintln!("error: {e}");
+                process::exit(1);
+            })
+        };
+        let calibrated_iters = match (&opts.refine, &config.calibrate) {
+            (Some(r), _) => r.inproc_iters.clone(),
+            (None, Some(c)) => runner::calibrate(config, c.target_ms, c.initial_iters),
+            (None, None) => HashMap::new(),
+        };
+        for (name, b) in &builds {
+            log_event(
+                &log,
+                "build",
+                &[
+                    ("executor", name.clone()),
+                    ("command", b.command.clone()),
+                    ("duration_secs", format!("{:.3}", b.duration_secs)),
+                    ("sha256", b.sha256.clone()),
+                ],
+            );
+        }
+        let run_opts = runner::RunOptions {
+            max_duration: opts.max_duration,
+            inproc_iters: calibrated_iters,
+            verbose: opts.verbose,
+            interleave: opts.interleave.clone(),
+            keep_going: opts.keep_going,
+            log,
+            events,
+            order: opts.order,
+            seed: self.seed,
+        };
+        let harnesses = probe::probe_suites(config);
+        if let Err(e) = probe::check_auto_iters(config, &harnesses) {
+            eprintln!("error: {e}");
+            process::exit(1);
+        }
+        let outcome = runner::run(config, &run_opts);
+        let mut extra = ExtraToml {
+            comment: opts.comment.clone(),
+            start_time: Some(start_time),
+            end_time: Some(unix_now()),
+            duration_secs: Some(start.elapsed().as_secs_f64()),
+            truncated: !outcome.skipped_pexecs.is_empty(),
+            skipped_pexecs: outcome.skipped_pexecs,
+            over_budget: outcome.over_budget,
+            calibrated_iters: run_opts.inproc_iters,
+            builds,
+            hosts,
+            interleaving: (!opts.interleave.is_empty()).then(|| Interleaving {
+                executors: opts.interleave.clone(),
+                order: outcome.order,
+                pairs: outcome.pairs,
+            }),
+            overheads: outcome.overheads,
+            warmup_pexecs: outcome.warmup_pexecs,
+            discarded_pexecs: outcome.discarded_pexecs,
+            smoke: opts.smoke,
+            executor_group: opts.executor_group.clone(),
+            system_readings: outcome.system_readings,
+            harnesses,
+            restricted: restricted.unwrap_or_else(|| runner::restricted_keys(config)),
+            skipped_executors,
+            descriptions: runner::key_descriptions(config),
+            urls: Some(runner::key_urls(config)),
+            suites: summary::key_suites(config),
+            suite_descriptions: config
+                .suites
+                .iter()
+                .filter_map(|(name, s)| Some((name.clone(), s.description.clone()?)))
+                .collect(),
+            seed: Some(self.seed),
+            kernel: environment::kernel(),
+            nice: config.nice,
+            haste_nice: config.haste_nice,
+            boot_id: environment::boot_id(),
+            cache_policies: config
+                .suites
+                .iter()
+                .map(|(name, suite)| (name.clone(), suite.cache_policy))
+                .collect(),
+            ..Default::default()
+        };
+        let log = run_opts.log;
+        if extra.truncated {
+            let skipped = extra.skipped_pexecs.values().sum::<usize>();
+            println!("haste: time budget exhausted, skipped {skipped} pexecs");
+            log_event(
+                &log,
+                "warning",
+                &[(
+                    "message",
+                    format!("time budget exhausted, skipped {skipped} pexecs"),
+                )],
+            );
+        }
+        if !extra.over_budget.is_empty() {
+            let skipped = extra.over_budget.values().sum::<usize>();
+            let message = format!("suites ran out of max_suite_secs, skipped {skipped} pexecs");
+            println!("haste: {message}");
+            log_event(&log, "warning", &[("message", message)]);
+        }
+        let mut results = outcome.results;
+        if !results.failures.is_empty() {
+            let mut failed = results.failures.keys().cloned().collect::<Vec<_>>();
+            failed.sort();
+            let message = format!("{} benchmarks failed: {}", failed.len(), failed.join(", "));
+            println!("WARNING: {message}");
+            log_event(&log, "warning", &[("message", message)]);
+        }
+        if !opts.quiet && !results.data.is_empty() {
+            let table = summary::summary_table(
+                &results,
+                &summary::key_suites(config),
+                ConfidenceLevel::default(),
+                false,
+            );
+            println!("\nconfidence level: {}%\n", ConfidenceLevel::default());
+            println!("{table}\n");
+        }
+        if let Some((base, composite)) = extending {
+            let source = composite.source;
+            extra.composite = Some(composite);
+            extend::merge(&mut results, &mut extra, base, self.load_extra(source));
+        }
+        if let Some(refining) = &opts.refine {
+            let source = refining.source;
+            extra = refine::merge_extra(self.load_extra(source), extra, &results, refining);
+            results = refine::append(self.load_results(source), results).unwrap_or_else(|e| {
+                eprintln!("error: can't refine Datum{source}: {e}");
+                process::exit(1);
+            });
+        }
+        self.store.set_storage(config.storage);
+        let took = extra.duration_str();
+        let id = self.store_datum(results, extra);
+        match &opts.comment {
+            Some(c) => println!("haste: created datum {id} {c} (took {took})"),
+            None => println!("haste: created datum {id} (took {took})"),
+        }
+        log_event(&log, "datum_created", &[("id", id.to_string())]);
+        if let Some(events) = &run_opts.events {
+            events.emit(&events::Event::RunFinished { datum: id });
+        }
+        if let Some(log) = log
+            && let Some(None) = opts.log_file
+        {
+            // The ID wasn't known until now, so the log was written somewhere temporary.
+            let path = self.store.log_path(id);
+            if let Err(e) = fs::create_dir_all(path.parent().unwrap())
+                .and_then(|_| fs::rename(log.path(), &path))
+            {
+                eprintln!("warning: can't move log to {}: {e}", path.display());
+            }
+        }
+        id
+    }
+
+    /// Open the run log requested by `opts`, logging the start of the run, or exit on failure.
+    fn open_run_log(&self, config: &config::Config, opts: &BenchOptions) -> Option<RunLog> {
+        let path = match opts.log_file.as_ref()? {
+            Some(path) => path.clone(),
+            None => {
+                let path = self.state_dir.join(PARTIAL_LOG);
+                // Don't append to the log of an earlier run that didn't finish.
+                fs::remove_file(&path).ok();
+                path
+            }
+        };
+        let log = RunLog::create(&path, opts.log_format).unwrap_or_else(|e| {
+            eprintln!("error: {e}");
+            process::exit(1);
+        });
+        let mut executors = config.executors.keys().cloned().collect::<Vec<_>>();
+        executors.sort();
+        let benchmarks = config
+            .suites
+            .values()
+            .map(|s| s.benchmarks.len())
+            .sum::<usize>();
+        let mut fields = vec![
+            ("config", self.config_file.display().to_string()),
+            ("executors", executors.join(",")),
+            ("benchmarks", benchmarks.to_string()),
+            ("proc_execs", config.proc_execs.to_string()),
+            ("inproc_iters", config.inproc_iters.to_string()),
+        ];
+        if let Some(d) = opts.max_duration {
+            fields.push(("max_duration", format!("{}s", d.as_secs_f64())));
+        }
+        if let Some(c) = &opts.comment {
+            fields.push(("comment", c.clone()));
+        }
+        log.event("run_start", &fields);
+        Some(log)
+    }
+
+    /// Run benchmarks with two executors, alternating between them, and compare them. The
+    /// executors are `executors`, or those of executor group `group`, the first being the
+    /// baseline.
+    ///
+    /// Exits non-zero if the second is significantly slower than the first for any benchmark.
+    ///
+    /// With `paired`, the executors are compared pexec by pexec, using the rounds in which they
+    /// alternated, and with `show_both` the unpaired comparison is shown too.
+    fn cmd_ab(
+        &self,
+        executors: &[String],
+        group: Option<&str>,
+        benchmarks: &[String],
+        confidence: ConfidenceLevel,
+        paired: bool,
+        show_both: bool,
+    ) {
+        let mut config = self.load_config();
+        let executors = match group {
+            Some(g) => config
+                .executor_group(g)
+                .unwrap_or_else(|e| {
+                    eprintln!("error: {e}");
+                    process::exit(1);
+                })
+                .to_vec(),
+            None => executors.to_vec(),
+        };
+        let [base, new] = &executors[..] else {
+            match group {
+                Some(g) => eprintln!(
+                    "error: ab compares two executors, but executor group {g} has {}",
+                    executors.len()
+                ),
+                None => eprintln!("error: ab needs exactly two executors"),
+            }
+            process::exit(1);
+        };
+        let (base, new) = (base.as_str(), new.as_str());
+        for name in [base, new] {
+            if !config.executors.contains_key(name) {
+                eprintln!("error: no executor named {name}");
+                process::exit(1);
+            }
+        }
+        if base == new {
+            eprintln!("error: can't compare {base} with itself");
+            process::exit(1);
+        }
+        config
+            .executors
+            .retain(|name, _| name == base || name == new);
+        if let Err(e) = config.retain_benchmarks(benchmarks) {
+            eprintln!("error: {e}");
+            process::exit(1);
+        }
+        let opts = BenchOptions {
+            comment: Some(format!("ab: {base} vs. {new}")),
+            interleave: vec![base.to_owned(), new.to_owned()],
+            executor_group: group.map(str::to_owned),
+            // The comparison printed afterwards says more than the summary would.
+            quiet: true,
+            ..Default::default()
+        };
+        let id = self.bench(&mut config, &opts);
+        println!();
+
+        let data = self.load_results(id);
+        let mut base_data = data.for_executor(base);
+        let mut new_data = data.for_executor(new);
+        // Benchmarks restricted to one of the executors can't be compared.
+        let (base_only, new_only) = base_data.retain_common(&mut new_data);
+        for (name, only) in [(base, base_only), (new, new_only)] {
+            if !only.is_empty() {
+                println!("only run with {name}: {}", only.join(", "));
+            }
+        }
+        let base_data = base_data.summarise(confidence);
+        let new_data = new_data.summarise(confidence);
+        println!("confidence level: {confidence}%\n");
+        let mut regressions = 0;
+        if !paired || show_both {
+            let (table, unpaired_regressions) = diff_table(
+                &base_data,
+                &new_data,
+                &named_header(base, TIME_METRIC),
+                &named_header(new, TIME_METRIC),
+                false,
+                DEFAULT_MIN_SAMPLES,
+                TIME_METRIC,
+            );
+            if show_both {
+                println!("unpaired:\n");
+            }
+            println!("{table}");
+            println!(
+                "\n{}",
+                diff_legend(
+                    base,
+                    new,
+                    TIME_METRIC,
+                    confidence,
+                    DEFAULT_MIN_SAMPLES,
+                    colour_enabled()
+                )
+            );
+            regressions = unpaired_regressions;
+        }
+        if paired {
+            let extra = self.load_extra(id);
+            let rows = paired::paired_rows(
+                &data,
+                extra.interleaving.as_ref(),
+                base,
+                new,
+                confidence,
+                DEFAULT_MIN_SAMPLES,
+            )
+            .unwrap_or_else(|e| {
+                eprintln!("error: Datum{id}: {e}");
+                process::exit(1);
+            });
+            if show_both {
+                println!("\npaired:\n");
+            }
+            println!(
+                "{}",
+                paired::paired_table(&rows, base, new, colour_enabled())
+            );
+            println!(
+                "\n{new} - {base} is the mean difference between the pexecs of each round, ± the \
+                 half-width of its confidence interval; it is significant when the interval \
+                 excludes zero; benchmarks with fewer than {DEFAULT_MIN_SAMPLES} pairs are \
+                 indistinguishable"
+            );
+            regressions = rows.iter().filter(|r| r.is_regression()).count();
+        }
+        if regressions > 0 {
+            println!(
+                "\nhaste: {new} is significantly slower than {base} for {regressions} benchmarks"
+            );
+            process::exit(1);
+        }
+    }
+
+    /// Print descriptive statistics of every benchmark in a datum.
+    fn cmd_stat(&self, id: usize, metric: &str, columns: &[StatColumn], format: OutputFormat) {
+        let data = self
+            .load_results(id)
+            .for_metric(metric)
+            .unwrap_or_else(|e| {
+                eprintln!("error: Datum{id}: {e}");
+                process::exit(1);
+            });
+        let mut keys = data
+            .data
+            .iter()
+            .filter(|(_, samples)| !samples.is_empty())
+            .map(|(k, _)| k.as_str())
+            .collect::<Vec<_>>();
+        keys.sort();
+        let rows = keys
+            .into_iter()
+            .map(|k| (k, stats::Descriptive::from_samples(&data.data[k])))
+            .collect::<Vec<_>>();
+        print!("{}", format_stats(&rows, columns, format));
+    }
+
+    /// Compare the distributions of one benchmark key in two datums.
+    fn cmd_compare_bench(&self, id1: usize, id2: usize, key: &str, metric: &str) {
+        let load = |id| {
+            let data = self
+                .load_results(id)
+                .for_metric(metric)
+                .unwrap_or_else(|e| {
+                    eprintln!("error: Datum{id}: {e}");
+                    process::exit(1);
+                });
+            match data.data.get(key) {
+                Some(samples) if !samples.is_empty() => samples.clone(),
+                _ => {
+                    let mut keys = data.data.keys().cloned().collect::<Vec<_>>();
+                    keys.sort();
+                    eprintln!(
+                        "error: Datum{id} has no samples for {key} (available: {})",
+                        keys.join(", ")
+                    );
+                    process::exit(1);
+                }
+            }
+        };
+        let (a, b) = (load(id1), load(id2));
+        println!("{key}\n");
+        compare::print_comparison(
+            &format!("Datum{id1}"),
+            &a,
+            &format!("Datum{id2}"),
+            &b,
+            metric_unit(metric).unwrap_or(metric),
+        );
+    }
+
+    /// Compare the newest datum with the one `window` datums before it, printing the benchmarks that
+    /// got significantly slower and exiting non-zero if there are any.
+    fn cmd_regressions(&self, window: usize, confidence: ConfidenceLevel) {
+        let ids = self.store.ids();
+        let Some((base, newest)) = window_ends(&ids, window) else {
+            eprintln!("error: need at least two datums to look for regressions");
+            process::exit(1);
+        };
+        if ids.len() <= window {
+            println!(
+                "note: only {} datums before Datum{newest}, so comparing with the oldest",
+                ids.len() - 1
+            );
+        }
+        let mut old_data = self.load_summary(base, confidence);
+        let mut new_data = self.load_summary(newest, confidence);
+        let only_in = |a: &HashMap<String, SummaryStats>, b: &HashMap<String, SummaryStats>| {
+            let mut keys = a
+                .keys()
+                .filter(|k| !b.contains_key(*k))
+                .cloned()
+                .collect::<Vec<_>>();
+            keys.sort();
+            keys
+        };
+        let renames = self
+            .key_aliases()
+            .renames(old_data.keys(), |k| new_data.contains_key(k));
+        for (old, new) in &renames {
+            aliases::rename_key(&mut old_data, old, new);
+        }
+        if let Some(note) = aliases::note(&format!("Datum{base}"), &renames) {
+            println!("{note}");
+        }
+        let disappeared = only_in(&old_data, &new_data);
+        let appeared = only_in(&new_data, &old_data);
+        old_data.retain(|k, _| new_data.contains_key(k));
+        new_data.retain(|k, _| old_data.contains_key(k));
+        let regressed = diff_rows(&old_data, &new_data, DEFAULT_MIN_SAMPLES, false)
+            .into_iter()
+            .filter(|r| r.is_regression())
+            .map(|r| r.key.to_owned())
+            .collect::<HashSet<_>>();
+
+        println!("comparing Datum{base} with Datum{newest} ({confidence}% confidence)\n");
+        if regressed.is_empty() {
+            println!("no regressions");
+        } else {
+            let only_regressed = |data: HashMap<String, SummaryStats>| {
+                data.into_iter()
+                    .filter(|(k, _)| regressed.contains(k))
+                    .collect::<HashMap<_, _>>()
+            };
+            let (table, _) = diff_table(
+                &only_regressed(old_data),
+                &only_regressed(new_data),
+                &datum_header(base, TIME_METRIC),
+                &datum_header(newest, TIME_METRIC),
+                false,
+                DEFAULT_MIN_SAMPLES,
+                TIME_METRIC,
+            );
+            println!("{table}");
+        }
+        if !appeared.is_empty() {
+            println!("\nnew since Datum{base}: {}", appeared.join(", "));
+        }
+        if !disappeared.is_empty() {
+            println!("\ngone since Datum{base}: {}", disappeared.join(", "));
+        }
+        if !regressed.is_empty() {
+            println!(
+                "\nhaste: {} benchmarks got significantly slower",
+                regressed.len()
+            );
+            process::exit(1);
+        }
+    }
+
+    /// Re-run benchmarks whenever any of `paths` changes, diffing each new datum against
+    /// `baseline`.
+    ///
+    /// If `paths` is empty, the (local) executor binaries are watched.
+    fn cmd_watch(
+        &self,
+        paths: Vec<PathBuf>,
+        baseline: usize,
+        benchmarks: &[String],
+        proc_execs: Option<usize>,
+        confidence: ConfidenceLevel,
+    ) {
+        if !self.store.contains(baseline) {
+            eprintln!("error: no such datum {baseline}");
+            process::exit(1);
+        }
+        let load_config = || {
+            let mut config = self.load_config();
+            if let Some(n) = proc_execs {
+                config.proc_execs = n;
+            }
+            if let Err(e) = config.retain_benchmarks(benchmarks) {
+                eprintln!("error: {e}");
+                process::exit(1);
+            }
+            config
+        };
+        let paths = if paths.is_empty() {
+            let config = load_config();
+            let mut paths = config
+                .executors
+                .iter()
+                .filter(|(name, e)| {
+                    e.path.as_os_str() != config::SHELL_EXECUTOR
+                        && config.remote_for(name).is_none()
+                })
+                .map(|(_, e)| e.path.clone())
+                .collect::<Vec<_>>();
+            paths.sort();
+            paths
+        } else {
+            paths
+        };
+        if paths.is_empty() {
+            eprintln!("error: nothing to watch (no local executor binaries)");
+            process::exit(1);
+        }
+        // The new datums typically have fewer benchmarks and samples than the baseline, and may
+        // have been run with a different `--proc-execs`, hence a relaxed and forced diff.
+        let diff_opts = DiffOptions {
+            confidence,
+            relaxed: true,
+            force: true,
+            env_warnings: false,
+            ..Default::default()
+        };
+        // The executors are rebuilt by the user: that's what we're watching for.
+        let bench_opts = BenchOptions {
+            comment: Some(format!("watch (baseline {baseline})")),
+            skip_build: true,
+            quiet: true,
+            ..Default::default()
+        };
+
+        runner::install_interrupt_handler();
+        let mtimes = || {
+            paths
+                .iter()
+                .map(|p| fs::metadata(p).and_then(|m| m.modified()).ok())
+                .collect::<Vec<_>>()
+        };
+        let stop = || {
+            println!("haste: stopped watching");
+            process::exit(0);
+        };
+        println!("haste: watching {} paths (Ctrl-C to stop)", paths.len());
+        let mut last = mtimes();
+        loop {
+            thread::sleep(WATCH_POLL);
+            if runner::interrupted() {
+                stop();
+            }
+            let mut now = mtimes();
+            if now == last {
+                continue;
+            }
+            // Wait for things to settle down, as builds tend to write files several times.
+            loop {
+                thread::sleep(WATCH_DEBOUNCE);
+                if runner::interrupted() {
+                    stop();
+                }
+                let next = mtimes();
+                if next == now {
+                    break;
+                }
+                now = next;
+            }
+            println!("haste: change detected, benchmarking");
+            let id = self.bench(&mut load_config(), &bench_opts);
+            if let Err(e) = self.diff(&DatumRef::local(baseline), &DatumRef::local(id), &diff_opts)
+            {
+                eprintln!("error: {e}");
+            }
+            // Changes made while benchmarking are ignored.
+            last = mtimes();
+            println!("haste: watching {} paths (Ctrl-C to stop)", paths.len());
+        }
+    }
+
+    /// Send a datum to the configured notification URL.
+    fn notify(&self, notify: &config::Notify, id: usize) -> Result<(), String> {
+        let (results, extra) = (self.load_results(id), self.load_extra(id));
+        let payload = notify::payload(id, &extra, &results, ConfidenceLevel::default());
+        notify::post(notify, &payload)
+    }
+
+    /// Re-send an existing datum to the configured notification URL, returning that URL.
+    fn renotify(&self, id: usize) -> Result<String, String> {
+        let config = self.load_config();
+        let notify = notify::configured(config.notify.as_ref(), &self.config_file)?;
+        self.notify(notify, id)?;
+        Ok(notify.url.clone())
+    }
+
+    /// Re-send datum `id`, exiting if it can't be sent.
+    fn cmd_notify(&self, id: usize) {
+        match self.renotify(id) {
+            Ok(url) => println!("haste: sent datum {id} to {url}"),
+            Err(e) => {
+                eprintln!("error: {e}");
+                process::exit(1);
+            }
+        }
+    }
+
+    fn cmd_diff(&self, r1: &DatumRef, r2: &DatumRef, opts: &DiffOptions) {
+        if let Err(e) = self.diff(r1, r2, opts) {
+            eprintln!("{e}");
+            process::exit(1);
+        }
+    }
+
+    /// Print a comparison of two datums, or return why they can't be compared.
+    fn diff(&self, r1: &DatumRef, r2: &DatumRef, opts: &DiffOptions) -> Result<(), String> {
+        self.diff_to(r1, r2, opts, &mut io::stdout().lock(), colour_enabled())
+    }
+
+    /// Write a comparison of two datums, and the notes and warnings it needs, to `out` (in
+    /// colour, if `colour`) and to the output file, or return why they can't be compared.
+    ///
+    /// Either datum may be in another state directory, in which case both are named with their
+    /// state directories throughout.
+    fn diff_to(
+        &self,
+        r1: &DatumRef,
+        r2: &DatumRef,
+        opts: &DiffOptions,
+        out: &mut dyn Write,
+        colour: bool,
+    ) -> Result<(), String> {
+        let DiffOptions {
+            confidence,
+            relaxed,
+            ref metric,
+            force,
+            thousands,
+            subtract_overhead,
+            min_samples,
+            stat,
+            describe,
+            deterministic,
+            ref junit,
+            fail_on_regression,
+            ref plot,
+            log_scale: _,
+            env_warnings,
+            strict_dims,
+            ref order,
+            ref targets,
+            test,
+            output: _,
+            duplicate_threshold,
+            ref benchmarks,
+            ref explain,
+        } = *opts;
+        let io_err = |e: io::Error| format!("error: {e}");
+        opts.output.check().map_err(|e| format!("error: {e}"))?;
+        let qualify = r1.state_dir.is_some() || r2.state_dir.is_some();
+        let (name1, name2) = (r1.name(qualify), r2.name(qualify));
+        let (mut results1, extra1) = self.load_ref(r1).map_err(|e| format!("error: {e}"))?;
+        let (mut results2, extra2) = self.load_ref(r2).map_err(|e| format!("error: {e}"))?;
+        for (r, name) in [(r1, &name1), (r2, &name2)] {
+            if let Some(note) = self.missing_extra_note(r, name) {
+                writeln!(out, "{note}").map_err(io_err)?;
+            }
+        }
+        let aliases = self.key_aliases();
+        if let Some(note) = aliases.follow(&name1, &mut results1, &results2) {
+            writeln!(out, "{note}").map_err(io_err)?;
+        }
+        if let Some(note) = aliases.follow(&name2, &mut results2, &results1) {
+            writeln!(out, "{note}").map_err(io_err)?;
+        }
+        if !benchmarks.is_empty() {
+            select_benchmarks(benchmarks, (&name1, &mut results1), (&name2, &mut results2))?;
+        }
+        let (mut data1, mut data2) =
+            match (results1.for_metric(metric), results2.for_metric(metric)) {
+                (Ok(d1), Ok(d2)) => (d1, d2),
+                (Err(e), Ok(_)) => {
+                    return Err(lacking_metric(&name1, &results1, &name2, &results2, e));
+                }
+                (Ok(_), Err(e)) => {
+                    return Err(lacking_metric(&name2, &results2, &name1, &results1, e));
+                }
+                (Err(e), Err(_)) => return Err(format!("{name1}: {e}")),
+            };
+        let (identical, common) = identical_samples(&data1.data, &data2.data);
+        let mut same = Vec::new();
+        let dir = |r: &DatumRef| {
+            r.state_dir
+                .as_deref()
+                .unwrap_or(&self.state_dir)
+                .canonicalize()
+                .ok()
+        };
+        if r1.id == r2.id && dir(r1).is_some() && dir(r1) == dir(r2) {
+            same.push("state directory and ID");
+        }
+        if extra1.start_time.is_some() && extra1.start_time == extra2.start_time {
+            same.push("start time");
+        }
+        if extra1.end_time.is_some() && extra1.end_time == extra2.end_time {
+            same.push("finish time");
+        }
+        if !results1.timestamps.is_empty() && results1.timestamps == results2.timestamps {
+            same.push("pexec timestamps");
+        }
+        if let Some(w) = duplicate_warning(
+            &name1,
+            &name2,
+            &identical,
+            common,
+            duplicate_threshold,
+            &same,
+        ) {
+            writeln!(out, "WARNING: {w}\n").map_err(io_err)?;
+        }
+        for (name, extra) in [(&name1, &extra1), (&name2, &extra2)] {
+            if let Some(c) = &extra.composite {
+                writeln!(
+                    out,
+                    "WARNING: {name} extends Datum{} with {} benchmarks measured in a later run, \
+                     so its samples span two sessions\n",
+                    c.source,
+                    c.measured.len()
+                )
+                .map_err(io_err)?;
+            }
+            if let Some(r) = &extra.refinement {
+                writeln!(
+                    out,
+                    "WARNING: {name} refines Datum{} with more pexecs of {} benchmarks measured in \
+                     a later run, so their samples span two sessions\n",
+                    r.source,
+                    r.extra_pexecs.len()
+                )
+                .map_err(io_err)?;
+            }
+        }
+        if subtract_overhead {
+            if metric != TIME_METRIC {
+                return Err("--subtract-overhead only applies to the time metric".to_owned());
+            }
+            apply_overheads(&name1, &mut data1, &extra1, confidence)?;
+            apply_overheads(&name2, &mut data2, &extra2, confidence)?;
+        }
+
+        let failures1 = data1.failures.clone();
+        let failures2 = data2.failures.clone();
+        for (name, extra) in [(&name1, &extra1), (&name2, &extra2)] {
+            if extra.smoke {
+                writeln!(
+                    out,
+                    "WARNING: {name} was made by `haste smoke`, with one pexec of each benchmark"
+                )
+                .map_err(io_err)?;
+            }
+        }
+        if relaxed {
+            data1.remove_failed(&mut data2);
+            let (mut lhs_only, mut rhs_only) = data1.retain_common(&mut data2);
+            let changes = args_changes(&lhs_only, &rhs_only, &data1.params, &data2.params);
+            if !changes.is_empty() {
+                writeln!(
+                    out,
+                    "extra_args changed between {name1} and {name2}, so not compared:"
+                )
+                .map_err(io_err)?;
+                for c in &changes {
+                    writeln!(out, "  {c}").map_err(io_err)?;
+                }
+                lhs_only.retain(|k| !changes.iter().any(|c| c.keys.0 == *k));
+                rhs_only.retain(|k| !changes.iter().any(|c| c.keys.1 == *k));
+            }
+            if !lhs_only.is_empty() {
+                writeln!(out, "only in {name1}: {}", lhs_only.join(", ")).map_err(io_err)?;
+            }
+            if !rhs_only.is_empty() {
+                writeln!(out, "only in {name2}: {}", rhs_only.join(", ")).map_err(io_err)?;
+            }
+            for (name, extra, missing) in
+                [(&name1, &extra1, &rhs_only), (&name2, &extra2, &lhs_only)]
+            {
+                let restricted = missing
+                    .iter()
+                    .filter(|k| extra.restricted.contains(k))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if !restricted.is_empty() {
+                    writeln!(
+                        out,
+                        "not run in {name} because its config restricts their executors: {}",
+                        restricted.join(", ")
+                    )
+                    .map_err(io_err)?;
+                }
+                let over_budget = missing
+                    .iter()
+                    .filter(|k| extra.over_budget.contains_key(*k))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if !over_budget.is_empty() {
+                    writeln!(
+                        out,
+                        "not run in {name} because their suites ran out of max_suite_secs: {}",
+                        over_budget.join(", ")
+                    )
+                    .map_err(io_err)?;
+                }
+            }
+        } else {
+            for (name, extra) in [(&name1, &extra1), (&name2, &extra2)] {
+                if extra.truncated {
+                    return Err(format!(
+                        "{name} was truncated by --max-duration: use --relaxed to compare it"
+                    ));
+                }
+                if !extra.over_budget.is_empty() {
+                    let mut keys = extra.over_budget.keys().cloned().collect::<Vec<_>>();
+                    keys.sort();
+                    return Err(format!(
+                        "{name} skipped pexecs of benchmarks whose suites ran out of \
+                         max_suite_secs: {}\nuse --relaxed to compare it",
+                        keys.join(", ")
+                    ));
+                }
+            }
+            let no_iters = HashMap::new();
+            let (iters1, iters2) = if metric == IPS_METRIC {
+                (&no_iters, &no_iters)
+            } else {
+                (&extra1.calibrated_iters, &extra2.calibrated_iters)
+            };
+            let counts_differ = data1
+                .same_dims(&data2, iters1, iters2, strict_dims)
+                .map_err(|mut e| {
+                    if !e.starts_with("results files contain different benchmarks") {
+                        return e;
+                    }
+                    for (name, extra) in [(&name1, &extra1), (&name2, &extra2)] {
+                        if !extra.restricted.is_empty() {
+                            e.push_str(&format!(
+                                "\n{name}'s config restricts which executors some benchmarks \
+                                 run with, so it lacks: {}",
+                                extra.restricted.join(", ")
+                            ));
+                        }
+                    }
+                    e.push_str("\nuse --relaxed to compare the benchmarks both have");
+                    e
+                })?;
+            if !counts_differ.is_empty() {
+                writeln!(
+                    out,
+                    "WARNING: {name1} and {name2} have different numbers of pexecs of {}, which \
+                     are compared as independent samples (see the n column)\n",
+                    counts_differ.join(", ")
+                )
+                .map_err(io_err)?;
+            }
+            data1.remove_failed(&mut data2);
+        }
+
+        // Iterations per second are comparable whatever the number of iterations.
+        check_comparable(
+            &data1,
+            &extra1,
+            &data2,
+            &extra2,
+            metric != IPS_METRIC,
+            force,
+        )?;
+        if env_warnings {
+            let same_hosts = extra1.host_mismatches(&extra2).is_empty();
+            let drift = environment::drift(&extra1, &extra2, same_hosts, &self.env_thresholds());
+            if !drift.is_empty() {
+                writeln!(
+                    out,
+                    "NOTE: the environment may have changed between the datums:"
+                )
+                .map_err(io_err)?;
+                for d in &drift {
+                    writeln!(out, "  {d}").map_err(io_err)?;
+                }
+                writeln!(
+                    out,
+                    "NOTE: small differences may be due to this (use --no-env-warnings to hide \
+                     this)\n"
+                )
+                .map_err(io_err)?;
+            }
+        }
+
+        for (name, extra) in [(&name1, &extra1), (&name2, &extra2)] {
+            let missing = probe::unsupported(metric, &extra.harnesses);
+            if !missing.is_empty() {
+                writeln!(
+                    out,
+                    "WARNING: {name}: the harnesses of {} don't report {metric}\n",
+                    missing.join(", ")
+                )
+                .map_err(io_err)?;
+            }
+        }
+
+        let rng = &mut self.rng.borrow_mut();
+        let mut summarise = |data: &ResultFile, name: &str| {
+            let (summaries, too_few) = data
+                .summarise_by(stat, confidence, rng)
+                .map_err(|e| format!("{name}: {e}"))?;
+            if let Some(w) = too_few_warning(name, stat, &too_few) {
+                writeln!(out, "WARNING: {w}\n").map_err(io_err)?;
+            }
+            Ok::<_, String>(summaries)
+        };
+        let summaries1 = summarise(&data1, &name1)?;
+        let summaries2 = summarise(&data2, &name2)?;
+        if let Some(key) = explain {
+            let sides = [
+                explain::Side {
+                    name: &name1,
+                    results: &data1,
+                    summaries: &summaries1,
+                },
+                explain::Side {
+                    name: &name2,
+                    results: &data2,
+                    summaries: &summaries2,
+                },
+            ];
+            let key = explain::resolve_key(key, &sides).map_err(|e| format!("error: {e}"))?;
+            let explanation = explain::explain(&key, &sides, opts);
+            return opts
+                .output
+                .write_to(out, colour, |out, _| write!(out, "{explanation}"))
+                .map_err(|e| format!("error: {e}"));
+        }
+        let (data1, data2) = (summaries1, summaries2);
+
+        let header = |name: &str| match stat {
+            Statistic::Mean => named_header(name, metric),
+            _ => named_header(&format!("{name} [{}]", stat.describe()), metric),
+        };
+        let rows = tested_diff_rows(&data1, &data2, min_samples, higher_is_better(metric), test);
+        let rows = order::arrange(rows, |r| r.key, |r| Some(r), order);
+        if let Some(w) = order::unmatched_warning(&rows.unmatched) {
+            writeln!(out, "WARNING: {w}\n").map_err(io_err)?;
+        }
+        let notes = targets::crossings(targets, metric, &data1, &data2);
+        let (mut table, _) = rows_table(
+            &rows.rows,
+            &header(&name1),
+            &header(&name2),
+            thousands,
+            human_durations(metric),
+            rows.pinned,
+            &notes,
+        );
+        add_failed_rows(&mut table, &name1, &failures1, &name2, &failures2);
+
+        let render = |out: &mut dyn Write, colour: bool| {
+            let colour = colour && !deterministic;
+            let mut table = table.clone();
+            if !colour {
+                table.force_no_tty();
+            }
+            // If there's any extra metadata, print it.
+            if extra1.comment.is_some() || extra2.comment.is_some() {
+                let no_comment = "(no comment)".to_owned();
+                writeln!(
+                    out,
+                    "{name1}: {}",
+                    extra1.comment.clone().unwrap_or(no_comment.clone())
+                )?;
+                writeln!(
+                    out,
+                    "{name2}: {}\n",
+                    extra2.comment.clone().unwrap_or(no_comment)
+                )?;
+            }
+            if !deterministic {
+                writeln!(out, "{name1} duration: {}", extra1.duration_str())?;
+                writeln!(out, "{name2} duration: {}\n", extra2.duration_str())?;
+            }
+
+            if metric != TIME_METRIC {
+                writeln!(out, "metric: {metric}")?;
+            }
+            if stat != Statistic::Mean {
+                writeln!(out, "statistic: {}", stat.describe())?;
+            }
+            if test == SignificanceTest::RatioCi {
+                writeln!(
+                    out,
+                    "significance: the confidence interval of the ratio excludes 1"
+                )?;
+            }
+            writeln!(out, "confidence level: {confidence}%\n")?;
+            writeln!(out, "{table}")?;
+            writeln!(
+                out,
+                "\n{}",
+                diff_legend(&name1, &name2, metric, confidence, min_samples, colour)
+            )?;
+            if data1.values().chain(data2.values()).any(|s| s.bimodal) {
+                writeln!(out, "{BIMODAL_NOTE}")?;
+            }
+            if describe {
+                let keys = data1
+                    .keys()
+                    .chain(failures1.keys())
+                    .chain(failures2.keys())
+                    .map(String::as_str);
+                write_descriptions(out, keys, &[&extra1, &extra2])?;
+            }
+            Ok(())
+        };
+        opts.output
+            .write_to(out, colour, render)
+            .map_err(|e| format!("error: {e}"))?;
+
+        let rows = rows.rows;
+        if let Some(path) = junit {
+            let mut suites = extra1.suites.clone();
+            suites.extend(extra2.suites.clone());
+            let failed = failed_keys(&name1, &failures1, &name2, &failures2);
+            let xml = junit::report(&rows, &failed, &suites, [&name1, &name2], opts);
+            fs::write(path, xml)
+                .map_err(|e| format!("error: failed to write {}: {e}", path.display()))?;
+        }
+        if let Some(path) = plot {
+            let comments = [extra1.comment.as_deref(), extra2.comment.as_deref()];
+            let urls = self.benchmark_urls(&[&extra1, &extra2]);
+            let svg = plot::render(&rows, [&name1, &name2], comments, &urls, opts);
+            fs::write(path, svg)
+                .map_err(|e| format!("error: failed to write {}: {e}", path.display()))?;
+        }
+        if let Some(percent) = fail_on_regression {
+            let n = rows.iter().filter(|r| r.regresses_beyond(percent)).count();
+            if n > 0 {
+                return Err(format!(
+                    "\nhaste: {n} benchmarks are significantly worse in {name2} by more than \
+                     {percent}%"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy all datums into a store of kind `to`, which must be empty.
+    fn cmd_migrate(&self, to: StoreKind) {
+        if to == self.store_kind {
+            eprintln!("error: already using the {to:?} store");
+            process::exit(1);
+        }
+        let target = store::open(to, self.state_dir.clone());
+        if !target.ids().is_empty() {
+            eprintln!("error: the target store already contains datums");
+            process::exit(1);
+        }
+        let ids = self.store.ids();
+        for &id in &ids {
+            target.insert(id, &self.load_results(id), &self.load_extra(id));
+        }
+        println!("haste: migrated {} datums", ids.len());
+        match to {
+            StoreKind::Sqlite => {
+                println!("haste: the datum directories in {DOT_DIR} can now be removed")
+            }
+            StoreKind::Fs => {
+                // Move the database out of the way, otherwise it'd still be used by default.
+                let db = self.state_dir.join(store::SQLITE_DB);
+                let backup = db.with_extension("db.migrated");
+                fs::rename(&db, &backup).unwrap();
+                println!("haste: moved {} to {}", db.display(), backup.display());
+            }
+        }
+    }
+
+    /// Re-encode the samples of every datum compactly.
+    fn cmd_compact(&self) {
+        let (mut converted, mut before, mut after) = (0, 0, 0);
+        for id in self.store.ids() {
+            match self.store.compact(id) {
+                Ok(Some((b, a))) => {
+                    converted += 1;
+                    before += b;
+                    after += a;
+                }
+                Ok(None) => (),
+                Err(e) => {
+                    eprintln!("error: datum {id}: {e}");
+                    process::exit(1);
+                }
+            }
+        }
+        if converted == 0 {
+            println!("haste: every datum is already compact");
+            return;
+        }
+        println!(
+            "haste: compacted {converted} datums from {before} to {after} bytes ({:+.1}%)",
+            (after as f64 / before.max(1) as f64 - 1.) * 100.
+        );
+    }
+
+    /// Estimate the false-positive rate of the significance test for a datum.
+    ///
+    /// Each benchmark's samples are repeatedly split at random into two halves, which are then
+    /// compared as if they came from two different datums. Since both halves measure the same
+    /// thing, any "significant" difference is a false positive.
+    fn cmd_noise(&self, id: usize, confidence: ConfidenceLevel, reps: usize) {
+        let data = self.load_results(id);
+        let mut rng = self.rng.borrow_mut();
+        let expected = 1. - confidence.as_percent() / 100.;
+
+        let mut keys = data.data.keys().collect::<Vec<_>>();
+        keys.sort();
+
+        let mut table = Table::new();
+        table.load_preset(comfy_table::presets::NOTHING);
+        table.set_header(vec![
+            Cell::new("Benchmark").set_alignment(CellAlignment::Left),
+            Cell::new("Samples").set_alignment(CellAlignment::Right),
+            Cell::new("False positives").set_alignment(CellAlignment::Right),
+            Cell::new("Rate").set_alignment(CellAlignment::Right),
+            Cell::new("Summary").set_alignment(CellAlignment::Left),
+        ]);
+
+        let mut total_fps = 0;
+        let mut total_reps = 0;
+        for k in keys {
+            let samples = &data.data[k];
+            let mut row = vec![
+                Cell::new(k),
+                Cell::new(samples.len()).set_alignment(CellAlignment::Right),
+            ];
+            let Some(fps) = noise_false_positives(samples, confidence, reps, &mut rng) else {
+                row.push(Cell::new("-").set_alignment(CellAlignment::Right));
+                row.push(Cell::new("-").set_alignment(CellAlignment::Right));
+                row.push(Cell::new("too few samples").fg(Color::Yellow));
+                table.add_row(row);
+                continue;
+            };
+            total_fps += fps;
+            total_reps += reps;
+            let rate = fps as f64 / reps as f64;
+            row.push(Cell::new(fps).set_alignment(CellAlignment::Right));
+            row.push(Cell::new(format!("{:.2}%", rate * 100.)).set_alignment(CellAlignment::Right));
+            if rate > NOISE_FLAG_FACTOR * expected {
+                row.push(Cell::new("noisy").fg(Color::Red));
+            } else {
+                row.push(Cell::new("ok").fg(Color::Green));
+            }
+            table.add_row(row);
+        }
+
+        println!("confidence level: {confidence}%");
+        println!("expected false-positive rate: {:.2}%", expected * 100.);
+        println!("repetitions: {reps} (seed {})\n", self.seed);
+        println!("{table}");
+        if total_reps > 0 {
+            let overall = total_fps as f64 / total_reps as f64;
+            println!("\noverall false-positive rate: {:.2}%", overall * 100.);
+        }
+    }
+
+    /// Show the metadata and summary statistics of a datum.
+    fn cmd_show(&self, id: usize, opts: &ShowOptions) {
+        let written = opts.output.check().and_then(|()| {
+            opts.output
+                .write(|out, colour| self.show(id, opts, out, colour))
+        });
+        if let Err(e) = written {
+            eprintln!("error: {e}");
+            process::exit(1);
+        }
+    }
+
+    /// Write the metadata and summary statistics of a datum to `out`.
+    fn show(
+        &self,
+        id: usize,
+        opts: &ShowOptions,
+        out: &mut dyn Write,
+        colour: bool,
+    ) -> io::Result<()> {
+        let ShowOptions {
+            confidence,
+            thousands,
+            raw,
+            subtract_overhead,
+            ref metric,
+            histogram_bins,
+            describe,
+            env,
+            output: _,
+        } = *opts;
+        let mut data = self
+            .load_results(id)
+            .for_metric(metric)
+            .unwrap_or_else(|e| {
+                eprintln!("error: Datum{id}: {e}");
+                process::exit(1);
+            });
+        if subtract_overhead && metric != TIME_METRIC {
+            eprintln!("error: --subtract-overhead only applies to the time metric");
+            process::exit(1);
+        }
+        let extra = self.load_extra(id);
+        writeln!(
+            out,
+            "Datum{id}: {}",
+            extra.comment.as_deref().unwrap_or("(no comment)")
+        )?;
+        if let Some(note) = self.missing_extra_note(&DatumRef::local(id), &format!("Datum{id}")) {
+            writeln!(out, "{note}")?;
+        }
+        if !extra.labels.is_empty() {
+            writeln!(out, "labels: {}", label::format(&extra.labels))?;
+        }
+        if let Some(experiment) = &extra.experiment {
+            writeln!(out, "experiment: {experiment}")?;
+        }
+        let missing = probe::unsupported(metric, &extra.harnesses);
+        if !missing.is_empty() {
+            writeln!(
+                out,
+                "WARNING: the harnesses of {} don't report {metric}",
+                missing.join(", ")
+            )?;
+        }
+        writeln!(out, "started: {}", format_timestamp(extra.start_time))?;
+        writeln!(out, "finished: {}", format_timestamp(extra.end_time))?;
+        writeln!(out, "duration: {}", extra.duration_str())?;
+        if extra.truncated {
+            let skipped = extra.skipped_pexecs.values().sum::<usize>();
+            writeln!(out, "truncated: {skipped} pexecs skipped by --max-duration")?;
+        }
+        if extra.smoke {
+            writeln!(
+                out,
+                "smoke run: one pexec of each benchmark, by `haste smoke`"
+            )?;
+        }
+        if let Some(group) = &extra.executor_group {
+            writeln!(out, "executor group: {group}")?;
+        }
+        if let Some(r) = &extra.refinement {
+            writeln!(
+                out,
+                "refined: Datum{} with more pexecs of the benchmarks whose comparison with \
+                 Datum{} was borderline",
+                r.source, r.against
+            )?;
+            for (k, n) in &r.extra_pexecs {
+                writeln!(out, "  extra pexecs of {k}: {n}")?;
+            }
+        }
+        if !extra.over_budget.is_empty() {
+            let skipped = extra.over_budget.values().sum::<usize>();
+            writeln!(
+                out,
+                "over budget: {skipped} pexecs skipped by max_suite_secs"
+            )?;
+        }
+        let mut builds = extra.builds.iter().collect::<Vec<_>>();
+        builds.sort_by_key(|(name, _)| *name);
+        for (name, b) in builds {
+            writeln!(
+                out,
+                "built {name}: `{}` in {} (sha256 {})",
+                b.command,
+                units::format_duration(Duration::from_secs_f64(b.duration_secs)),
+                b.sha256
+            )?;
+        }
+        let mut hosts = extra.hosts.iter().collect::<Vec<_>>();
+        hosts.sort();
+        for (name, host) in hosts {
+            writeln!(out, "ran {name} on: {host}")?;
+        }
+        let mut policies = extra
+            .cache_policies
+            .iter()
+            .filter(|(_, p)| **p != CachePolicy::None)
+            .collect::<Vec<_>>();
+        policies.sort_by_key(|(name, _)| *name);
+        for (name, policy) in policies {
+            writeln!(out, "suite {name} cache policy: {policy}")?;
+        }
+        if !extra.skipped_executors.is_empty() {
+            writeln!(
+                out,
+                "skipped missing executors: {}",
+                extra.skipped_executors.join(", ")
+            )?;
+        }
+        let mut failures = data.failures.iter().collect::<Vec<_>>();
+        failures.sort_by_key(|(k, _)| *k);
+        for (k, f) in failures {
+            writeln!(
+                out,
+                "failed: {k}: {} pexecs, last exit status {}",
+                f.count, f.status
+            )?;
+            for line in f.stderr.lines() {
+                writeln!(out, "  | {line}")?;
+            }
+        }
+        if let Some(seed) = extra.seed {
+            writeln!(out, "seed: {seed}")?;
+        }
+        if let Some(n) = extra.nice {
+            writeln!(out, "pexec niceness: {n}")?;
+        }
+        if let Some(n) = extra.haste_nice {
+            writeln!(out, "haste niceness: {n}")?;
+        }
+        if let Some(v) = &extra.haste_version {
+            writeln!(out, "haste version: {v}")?;
+        }
+        if !extra.args.is_empty() {
+            writeln!(out, "command line: {}", extra.args.join(" "))?;
+        }
+        if let Some(path) = &extra.config_file {
+            writeln!(out, "config file: {}", path.display())?;
+        }
+        let mut overheads = extra.overheads.iter().collect::<Vec<_>>();
+        overheads.sort_by_key(|(k, _)| *k);
+        for (k, overhead) in overheads {
+            writeln!(out, "harness overhead for {k}: {overhead:.3}ms")?;
+        }
+        let mut warmups = extra.warmup_pexecs.iter().collect::<Vec<_>>();
+        warmups.sort();
+        for (k, n) in warmups {
+            writeln!(out, "warmup pexecs of {k}: {n}")?;
+        }
+        let mut discarded = extra.discarded_pexecs.iter().collect::<Vec<_>>();
+        discarded.sort();
+        for (k, n) in discarded {
+            writeln!(out, "discarded and rerun pexecs of {k}: {n}")?;
+        }
+        writeln!(out)?;
+
+        // The raw samples are shown as they were measured.
+        let raw_data = raw.then(|| data.clone());
+        if subtract_overhead
+            && let Err(e) = apply_overheads(&format!("Datum{id}"), &mut data, &extra, confidence)
+        {
+            eprintln!("error: {e}");
+            process::exit(1);
+        }
+        let summaries = data.summarise(confidence);
+        let mut keys = summaries.keys().collect::<Vec<_>>();
+        keys.sort();
+        let (mean_fmt, ci_fmt) =
+            mean_ci_formats(summaries.values(), thousands, human_durations(metric));
+
+        let calibrated = !extra.calibrated_iters.is_empty();
+        let mut table = new_table(colour);
+        let mut header = vec![
+            Cell::new("Benchmark").set_alignment(CellAlignment::Left),
+            Cell::new("Samples").set_alignment(CellAlignment::Right),
+        ];
+        if calibrated {
+            header.push(Cell::new("Iters").set_alignment(CellAlignment::Right));
+        }
+        header.push(Cell::new(datum_header(id, metric)).set_alignment(CellAlignment::Right));
+        table.set_header(header);
+        for k in keys {
+            let s = &summaries[k];
+            let name = if s.bimodal {
+                format!("{k}{BIMODAL_MARKER}")
+            } else {
+                k.to_owned()
+            };
+            let mut row = vec![
+                Cell::new(name),
+                Cell::new(data.data[k].len()).set_alignment(CellAlignment::Right),
+            ];
+            if calibrated {
+                let iters = extra
+                    .calibrated_iters
+                    .get(k)
+                    .map_or("?".to_owned(), |i| i.to_string());
+                row.push(Cell::new(iters).set_alignment(CellAlignment::Right));
+            }
+            row.push(
+                Cell::new(format!(
+                    "{} ±{}",
+                    mean_fmt.format(s.mean),
+                    ci_fmt.format(s.ci)
+                ))
+                .set_alignment(CellAlignment::Right),
+            );
+            table.add_row(row);
+        }
+        writeln!(out, "confidence level: {confidence}%\n")?;
+        writeln!(out, "{table}")?;
+        if summaries.values().any(|s| s.bimodal) {
+            writeln!(out, "\n{BIMODAL_NOTE}")?;
+        }
+        if describe {
+            write_descriptions(out, summaries.keys().map(String::as_str), &[&extra])?;
+        }
+        if env {
+            match environment::readings_table(&extra.system_readings) {
+                Some(table) => writeln!(out, "\nreadings before and after each pexec:\n{table}")?,
+                None => writeln!(
+                    out,
+                    "\nno CPU frequency or temperature readings were recorded (they need \
+                     sample_system = true in the config, and a readable sysfs)"
+                )?,
+            }
+        }
+
+        if let Some(bins) = histogram_bins {
+            let mut keys = data.data.keys().collect::<Vec<_>>();
+            keys.sort();
+            for k in keys {
+                match metric_unit(metric) {
+                    Some(unit) => writeln!(out, "\n{k} ({unit}):")?,
+                    None => writeln!(out, "\n{k}:")?,
+                }
+                write!(out, "{}", histogram::render(&data.data[k], bins, 2))?;
+            }
+        }
+        if let Some(raw_data) = raw_data {
+            writeln!(out)?;
+            write_raw_samples(out, &raw_data)?;
+        }
+        Ok(())
+    }
+
+    /// Delete the datums `ids`, or if `labels` isn't empty, those with all of `labels`. Their IDs
+    /// are never reused.
+    fn cmd_rm(&self, ids: &[usize], labels: &[(String, String)], experiment: Option<&str>) {
+        let selected;
+        let ids = if labels.is_empty() && experiment.is_none() {
+            ids
+        } else {
+            selected = self
+                .store
+                .ids()
+                .into_iter()
+                .filter(|id| {
+                    let extra = self.load_extra(*id);
+                    label::matches(&extra, labels)
+                        && experiment.is_none_or(|e| extra.experiment.as_deref() == Some(e))
+                })
+                .collect::<Vec<_>>();
+            if selected.is_empty() {
+                match experiment {
+                    Some(e) if labels.is_empty() => {
+                        println!("haste: no datums are part of experiment {e}")
+                    }
+                    Some(e) => {
+                        println!("haste: no datums of experiment {e} have those labels")
+                    }
+                    None => println!("haste: no datums have those labels"),
+                }
+                return;
+            }
+            &selected
+        };
+        // Check every ID first, so that a typo doesn't leave a partial deletion.
+        for id in ids {
+            if !self.store.contains(*id) {
+                eprintln!("error: no such datum {id}");
+                process::exit(1);
+            }
+        }
+        for id in ids {
+            if let Err(e) = self.store.delete(*id) {
+                eprintln!("error: {e}");
+                process::exit(1);
+            }
+            self.cache.remove(*id);
+            println!("haste: deleted datum {id}");
+        }
+    }
+
+    /// List the datums with all of `labels` and, if given, part of `experiment`.
+    fn cmd_list(&self, long: bool, labels: &[(String, String)], experiment: Option<&str>) {
+        let ids = self.store.ids();
+        for id in ids {
+            // One datum's unreadable metadata shouldn't hide the rest.
+            let extra = self.store.try_load_extra(id).unwrap_or_else(|e| {
+                eprintln!("warning: {}", e.lines().next().unwrap_or(""));
+                ExtraToml::default()
+            });
+            if !label::matches(&extra, labels)
+                || experiment.is_some_and(|e| extra.experiment.as_deref() != Some(e))
+            {
+                continue;
+            }
+            let tags = if extra.labels.is_empty() {
+                String::new()
+            } else {
+                format!(" {{{}}}", label::format(&extra.labels))
+            };
+            if long {
+                println!(
+                    "{id:3}: {}{tags} [started {}, took {}]",
+                    extra.comment.as_deref().unwrap_or(""),
+                    format_timestamp(extra.start_time),
+                    extra.duration_str()
+                );
+            } else {
+                println!("{id:3}: {}{tags}", extra.comment.unwrap_or("".into()));
+            }
+        }
+    }
+
+    /// Print the configured benchmarks of each suite, with their extra arguments, the executors
+    /// they run with, and their descriptions.
+    fn cmd_list_benchmarks(&self) {
+        let config = self.load_config();
+        let mut exec_names = config.executors.keys().collect::<Vec<_>>();
+        exec_names.sort();
+        let mut suite_names = config.suites.keys().collect::<Vec<_>>();
+        suite_names.sort();
+        for (i, suite_name) in suite_names.into_iter().enumerate() {
+            let suite = &config.suites[suite_name];
+            if i > 0 {
+                println!();
+            }
+            match &suite.description {
+                Some(d) => println!("suite {suite_name}: {d}"),
+                None => println!("suite {suite_name}"),
+            }
+            let mut table = new_table(true);
+            table.set_header(vec!["Benchmark", "Extra args", "Executors", "Description"]);
+            let mut bench_names = suite.benchmarks.keys().collect::<Vec<_>>();
+            bench_names.sort();
+            for bench_name in bench_names {
+                let bench = &suite.benchmarks[bench_name];
+                let runs_on = exec_names
+                    .iter()
+                    .filter(|e| bench.runs_on(e))
+                    .map(|e| e.as_str())
+                    .collect::<Vec<_>>();
+                let executors = if runs_on.len() == exec_names.len() {
+                    "(all)".to_owned()
+                } else {
+                    runs_on.join(", ")
+                };
+                table.add_row(vec![
+                    bench_name.clone(),
+                    bench.extra_args.join(" "),
+                    executors,
+                    bench.description.clone().unwrap_or_default(),
+                ]);
+            }
+            println!("{table}");
+        }
+    }
+}
+
+#[derive(Parser)]
+#[command(version, about, subcommand_required = true)]
+struct Cli {
+    /// Path to the haste configuration file (defaults to haste.toml).
+    #[arg(short = 'f', long, global = true, value_name = "FILE")]
+    file: Option<PathBuf>,
+
+    /// The storage backend to use (defaults to sqlite if a database exists, otherwise fs).
+    #[arg(long, global = true, value_enum)]
+    store: Option<StoreKind>,
+
+    /// The directory holding the datums (defaults to the nearest `.haste` in the current
+    /// directory or its parents, up to the root of a git repository, except that commands which
+    /// store datums use the `.haste` next to the config file).
+    #[arg(long, global = true, value_name = "DIR")]
+    state_dir: Option<PathBuf>,
+
+    /// Recompute summaries of datums rather than using those cached by earlier commands.
+    #[arg(long, global = true)]
+    no_cache: bool,
+
+    /// Seed the random number generator used by analyses such as noise estimation, so that their
+    /// output can be reproduced (defaults to a random seed, which is shown in the output).
+    #[arg(long, global = true, value_parser = clap::value_parser!(u64).range(..=MAX_SEED))]
+    seed: Option<u64>,
+
+    /// How to show durations (defaults to human if stdout is a terminal, otherwise raw).
+    #[arg(long, global = true, value_enum)]
+    units: Option<units::Units>,
+
+    #[command(subcommand)]
+    mode: Mode,
+}
+
+#[derive(Subcommand, Debug)]
+enum Mode {
+    /// Run benchmarks and store the results into a new datum.
+    ///
+    /// Each benchmark process is run with these environment variables set:
+    ///
+    ///   HASTE_EXECUTOR      the name of the executor
+    ///   HASTE_BENCHMARK     the name of the benchmark
+    ///   HASTE_PEXEC         the index of the process execution (starting from 0)
+    ///   HASTE_INPROC_ITERS  the number of in-process iterations
+    #[clap(visible_alias = "b", verbatim_doc_comment)]
+    Bench {
+        /// Attach a comment to the datum.
+        #[clap(short, long, num_args(1))]
+        comment: Option<String>,
+        /// Without `--comment`, comment the datum with the branch and commit checked out in the
+        /// current directory's git repository, e.g. `main @ 1a2b3c4 "Inline small traces"`. This
+        /// is the default if the config sets `auto_comment = "git"`.
+        #[arg(long, conflicts_with = "comment")]
+        comment_from_git: bool,
+        /// Stop starting new process executions after this long (e.g. `45m` or `1h30m`).
+        ///
+        /// Unless `--order` says otherwise, benchmarks are run round-robin in this mode so that
+        /// they get similar numbers of samples.
+        #[arg(long, value_parser = parse_duration)]
+        max_duration: Option<Duration>,
+        /// Also collect hardware performance counters using `perf stat` (Linux only).
+        #[arg(long)]
+        perf: bool,
+        /// Print the command used for each process execution.
+        #[arg(short, long)]
+        verbose: bool,
+        /// Don't run the build commands of executors (use the existing binaries).
+        #[arg(long)]
+        skip_build: bool,
+        /// Skip executors whose binaries don't exist, rather than failing.
+        #[arg(long)]
+        skip_missing_executors: bool,
+        /// If a benchmark fails, record the failure in the datum and carry on with the rest.
+        #[arg(long)]
+        keep_going: bool,
+        /// Don't print a summary of the results at the end of the run.
+        #[arg(short, long)]
+        quiet: bool,
+        /// Don't send the datum to the URL in the config's `[notify]` section.
+        #[arg(long)]
+        no_notify: bool,
+        /// Log every pexec (its command, start and end times, exit status and duration) to a file.
+        ///
+        /// If no path is given, the log is kept with the new datum (e.g. `.haste/<id>/run.log`).
+        #[arg(long, value_name = "PATH", num_args = 0..=1)]
+        log_file: Option<Option<PathBuf>>,
+        /// The format of log lines.
+        #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+        log_format: LogFormat,
+        /// Stream the run's events, one JSON object per line, for other programs to follow.
+        ///
+        /// Events go to stdout with `jsonl`, to an inherited file descriptor with e.g.
+        /// `jsonl:3`, and to a file or fifo with `jsonl:<path>`.
+        #[arg(long, value_name = "jsonl[:<path|fd>]", value_parser = events::parse_destination)]
+        events: Option<events::Destination>,
+        /// The order to run pexecs in. Defaults to `round-robin` with `--max-duration`, and
+        /// `grouped` otherwise.
+        #[arg(long, value_enum)]
+        order: Option<runner::Order>,
+        /// Print the pexecs that would be run, in order, without running them.
+        #[arg(long)]
+        dry_run: bool,
+        /// Run only the benchmarks in the config which datum ID lacks, with the executors and
+        /// number of pexecs it was run with, and make a new datum of its results and theirs.
+        #[arg(long, value_name = "ID")]
+        extend: Option<usize>,
+        /// Run pexecs with this niceness (from -20, the highest priority, to 19, the lowest),
+        /// overriding the config's `nice`. Only privileged users can set it lower than haste's.
+        #[arg(
+            long,
+            allow_negative_numbers = true,
+            value_parser = clap::builder::RangedI64ValueParser::<i32>::new().range(-20..=19)
+        )]
+        nice: Option<i32>,
+        /// Run haste itself, and the builds it runs, with this niceness, overriding the config's
+        /// `haste_nice`.
+        #[arg(
+            long,
+            allow_negative_numbers = true,
+            value_parser = clap::builder::RangedI64ValueParser::<i32>::new().range(-20..=19)
+        )]
+        haste_nice: Option<i32>,
+    },
+    /// Compare two datums.
+    #[clap(visible_alias = "d")]
+    Diff {
+        /// The baseline datum: an ID, or `<path>:<id>` for a datum in the state directory `path`.
+        id1: DatumRef,
+        /// The datum to compare with the baseline, in the same form as `id1`.
+        id2: DatumRef,
+        /// Confidence level for the interval.
+        #[arg(short, long, default_value_t = ConfidenceLevel::default())]
+        confidence: ConfidenceLevel,
+        /// Compare only the benchmarks common to both datums, even if some were truncated or
+        /// skipped.
+        #[arg(long)]
+        relaxed: bool,
+        /// The metric to compare (e.g. `instructions` for data collected with `--perf`, or `ips`
+        /// for in-process iterations per second).
+        #[arg(short, long, default_value = TIME_METRIC)]
+        metric: String,
+        /// Compare datums even if their benchmarks were run with different parameters.
+        #[arg(long)]
+        force: bool,
+        /// Separate groups of thousands in numbers with commas.
+        #[arg(long)]
+        thousands: bool,
+        /// Subtract the harness overhead measured by each suite's `calibration_benchmark`.
+        #[arg(long)]
+        subtract_overhead: bool,
+        /// Treat benchmarks with fewer samples than this on either side as indistinguishable.
+        #[arg(long, default_value_t = DEFAULT_MIN_SAMPLES)]
+        min_samples: usize,
+        /// The statistic to compare: `mean`, `trimmed-mean[:<percent>]` for the mean after
+        /// removing `percent`% (default 10) of the samples from each end, or `p<NN>` for the NNth
+        /// percentile with a bootstrapped confidence interval.
+        #[arg(long, default_value_t = Statistic::Mean)]
+        stat: Statistic,
+        /// Compare several metrics at once, e.g. `time,instructions`, with a ratio column for
+        /// each. Metrics missing from either datum are shown as `n/a`.
+        #[arg(
+            long,
+            value_delimiter = ',',
+            conflicts_with_all = ["metric", "relaxed", "subtract_overhead", "strict_dims"]
+        )]
+        metrics: Vec<String>,
+        /// With `--metrics`, also show each datum's values of each metric.
+        #[arg(long, requires = "metrics")]
+        wide: bool,
+        /// The output format, with `--metrics`.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table, requires = "metrics")]
+        format: OutputFormat,
+        /// Show the configured description of each benchmark after the table.
+        #[arg(long)]
+        describe: bool,
+        /// Make the output the same every time the same datums are compared, for pinning in
+        /// tests: no colour, and no durations.
+        #[arg(long)]
+        deterministic: bool,
+        /// Also write the comparison to this file as a JUnit XML report, with a test case per
+        /// benchmark that fails if it's significantly worse (by more than the
+        /// `--fail-on-regression` percentage, if given).
+        #[arg(long, value_name = "PATH", conflicts_with = "metrics")]
+        junit: Option<PathBuf>,
+        /// Exit non-zero if any benchmark is significantly worse by more than this percentage
+        /// (e.g. `0` for any significant regression).
+        #[arg(long, value_name = "PERCENT", value_parser = parse_percent, conflicts_with = "metrics")]
+        fail_on_regression: Option<f64>,
+        /// Also plot each benchmark's mean and confidence interval in either datum to this file,
+        /// as an SVG.
+        #[arg(long, value_name = "PATH", conflicts_with = "metrics")]
+        plot: Option<PathBuf>,
+        /// Give the plot a log scale, for benchmarks whose values differ by orders of magnitude.
+        #[arg(long, requires = "plot")]
+        log_scale: bool,
+        /// Don't note when the datums were collected far apart in time, or with a reboot or
+        /// kernel change between them.
+        #[arg(long)]
+        no_env_warnings: bool,
+        /// Refuse to compare benchmarks with different numbers of pexecs in either datum, rather
+        /// than warning about them.
+        #[arg(long, conflicts_with = "relaxed")]
+        strict_dims: bool,
+        /// Show the benchmarks whose keys match this pattern, in which `*` matches anything and
+        /// `?` any one character, at the top. May be repeated, to pin several in that order.
+        #[arg(long, value_name = "PATTERN")]
+        pin: Vec<String>,
+        /// Sort the benchmarks that aren't pinned by this, rather than by significance and then
+        /// change (or by name, with `--metrics`, which sorts by the first metric).
+        #[arg(long, value_enum)]
+        sort_by: Option<order::SortBy>,
+        /// Reverse the order of the benchmarks that aren't pinned.
+        #[arg(long)]
+        reverse: bool,
+        /// Note the benchmarks whose results cross their targets between the datums, with the
+        /// targets in this file (default: `targets.toml` next to the config file; see `haste
+        /// targets`).
+        #[arg(long, value_name = "PATH", num_args = 0..=1, conflicts_with = "metrics")]
+        targets: Option<Option<PathBuf>>,
+        /// How to decide whether a benchmark differs significantly between the datums.
+        #[arg(long, value_enum, default_value_t = SignificanceTest::Overlap)]
+        test: SignificanceTest,
+        /// Also write the comparison to this file, without colour, creating the directories it's
+        /// in. With `--metrics`, it's written in the `--format` given, and the terminal shows a
+        /// table.
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+        /// Replace the `--output` file if it already exists.
+        #[arg(long, requires = "output")]
+        overwrite: bool,
+        /// Only write the comparison to the `--output` file, not to the terminal.
+        #[arg(long, requires = "output")]
+        quiet: bool,
+        /// Warn that the datums may be copies of each other (e.g. a restored archive) if more
+        /// than this percentage of the benchmarks they share have identical samples.
+        #[arg(
+            long,
+            value_name = "PERCENT",
+            value_parser = parse_percent,
+            default_value_t = DEFAULT_DUPLICATE_THRESHOLD
+        )]
+        duplicate_threshold: f64,
+        /// Compare only the benchmarks with these names, whatever their executors and extra
+        /// arguments, e.g. `haste diff 12 13 fasta nbody`.
+        #[arg(value_name = "BENCHMARK")]
+        benchmarks: Vec<String>,
+        /// Rather than the table, show how the row of this benchmark key (or of this benchmark,
+        /// if it has only one key) is derived: each side's samples, summary and interval, how
+        /// they're compared, and what that makes the row.
+        #[arg(
+            long,
+            value_name = "KEY",
+            conflicts_with_all = ["metrics", "junit", "plot", "fail_on_regression", "targets"]
+        )]
+        explain: Option<String>,
+    },
+    /// Write a plain-text report comparing two datums, for sharing by email or in an issue.
+    Report {
+        id1: usize,
+        id2: usize,
+        /// Confidence level for the interval.
+        #[arg(short, long, default_value_t = ConfidenceLevel::default())]
+        confidence: ConfidenceLevel,
+        /// Treat benchmarks with fewer samples than this on either side as indistinguishable.
+        #[arg(long, default_value_t = DEFAULT_MIN_SAMPLES)]
+        min_samples: usize,
+        /// Also write the report to this file, creating the directories it's in.
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+        /// Replace the `--output` file if it already exists.
+        #[arg(long, requires = "output")]
+        overwrite: bool,
+        /// Only write the report to the `--output` file, not to the terminal.
+        #[arg(long, requires = "output")]
+        quiet: bool,
+    },
+    /// List datums.
+    #[clap(visible_alias = "l")]
+    List {
+        /// Also show when each datum was collected and how long it took.
+        #[arg(short, long)]
+        long: bool,
+        /// Only list datums with this label (`key=value`). May be repeated, to require several.
+        #[arg(long, value_parser = label::parse)]
+        label: Vec<(String, String)>,
+        /// Only list the datums of this experiment (see `haste experiment`).
+        #[arg(long, value_name = "NAME")]
+        experiment: Option<String>,
+    },
+    /// List the configured benchmarks, with their descriptions.
+    ListBenchmarks,
+    /// Show the contents of a datum.
+    #[clap(visible_alias = "s")]
+    Show {
+        id: usize,
+        /// Confidence level for the interval.
+        #[arg(short, long, default_value_t = ConfidenceLevel::default())]
+        confidence: ConfidenceLevel,
+        /// Separate groups of thousands in numbers with commas.
+        #[arg(long)]
+        thousands: bool,
+        /// Also show every sample, with when its pexec started.
+        #[arg(long)]
+        raw: bool,
+        /// Subtract the harness overhead measured by each suite's `calibration_benchmark`.
+        #[arg(long)]
+        subtract_overhead: bool,
+        /// The metric to show (e.g. `ips` for in-process iterations per second).
+        #[arg(short, long, default_value = TIME_METRIC)]
+        metric: String,
+        /// Also show a histogram of each benchmark's samples.
+        #[arg(long)]
+        histogram: bool,
+        /// The number of bins in each histogram.
+        #[arg(long, default_value_t = histogram::DEFAULT_BINS, requires = "histogram")]
+        bins: usize,
+        /// Show the configured description of each benchmark after the table.
+        #[arg(long)]
+        describe: bool,
+        /// Also show the minimum, median and maximum CPU frequency and temperature read around
+        /// each benchmark's pexecs, if the config set `sample_system`.
+        #[arg(long)]
+        env: bool,
+        /// Also write what's shown to this file, without colour, creating the directories it's
+        /// in.
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+        /// Replace the `--output` file if it already exists.
+        #[arg(long, requires = "output")]
+        overwrite: bool,
+        /// Only write to the `--output` file, not to the terminal.
+        #[arg(long, requires = "output")]
+        quiet: bool,
+    },
+    /// Delete datums.
+    ///
+    /// The IDs of deleted datums are never reused.
+    Rm {
+        #[arg(
+            required_unless_present_any = ["label", "experiment"],
+            conflicts_with_all = ["label", "experiment"]
+        )]
+        ids: Vec<usize>,
+        /// Instead of listing IDs, delete every datum with this label (`key=value`). May be
+        /// repeated, to require several.
+        #[arg(long, value_parser = label::parse)]
+        label: Vec<(String, String)>,
+        /// Instead of listing IDs, delete every datum of this experiment (with `--label`, only
+        /// those with the labels).
+        #[arg(long, value_name = "NAME")]
+        experiment: Option<String>,
+    },
+    /// Edit a datum's metadata.
+    ///
+    /// Run without options, this recreates the metadata (`extra.toml`) of a datum which has lost
+    /// it, e.g. by being copied without it, although what it said is lost.
+    Edit {
+        id: usize,
+        /// Set the datum's comment.
+        #[arg(long, conflicts_with = "no_comment")]
+        comment: Option<String>,
+        /// Remove the datum's comment.
+        #[arg(long)]
+        no_comment: bool,
+    },
+    /// Attach `key=value` labels to a datum, or remove them, for `list --label` and
+    /// `rm --label` to select datums by.
+    Label {
+        id: usize,
+        /// The labels to set, replacing any with the same keys.
+        #[arg(value_parser = label::parse, required_unless_present = "remove")]
+        labels: Vec<(String, String)>,
+        /// Remove the label with this key. May be repeated.
+        #[arg(long, value_name = "KEY")]
+        remove: Vec<String>,
+    },
+    /// Group the datums of one investigation into a named experiment, for `list --experiment`
+    /// and `rm --experiment` to select them by.
+    Experiment {
+        #[command(subcommand)]
+        action: ExperimentAction,
+    },
+    /// Copy all datums to a different storage backend, or re-encode them compactly.
+    Migrate {
+        /// The backend to copy to.
+        #[arg(long, value_enum, required_unless_present = "compact")]
+        to: Option<StoreKind>,
+        /// Re-encode the samples of every datum in the filesystem store compactly, reporting the
+        /// space saved.
+        #[arg(long, conflicts_with = "to")]
+        compact: bool,
+    },
+    /// Check a datum against fixed targets for its benchmarks, exiting non-zero if any fails.
+    ///
+    /// The targets file has a `[[target]]` table for each target, e.g. `benchmark = "fasta/*"`
+    /// (a pattern in which `*` matches anything), `value = 800` and `direction = "below"` (or
+    /// `"above"`), and optionally `metric` (default `time`). A benchmark passes if its confidence
+    /// interval is on the right side of the target, fails if it's on the wrong side, and is warned
+    /// about if it straddles the target.
+    Targets {
+        id: usize,
+        /// The targets file (default: `targets.toml` next to the config file).
+        #[arg(long, value_name = "PATH")]
+        targets: Option<PathBuf>,
+        /// Confidence level for the interval.
+        #[arg(short, long, default_value_t = ConfidenceLevel::default())]
+        confidence: ConfidenceLevel,
+    },
+    /// Check the config, and probe each suite's harness (with `--haste-probe`) for the version of
+    /// the harness protocol and the metrics it supports.
+    Check,
+    /// Run every benchmark once with every executor, with one in-process iteration, to check that
+    /// they all work, exiting non-zero if any fails.
+    ///
+    /// No warmup pexecs, calibration or cache dropping are done, and the results aren't stored
+    /// unless `--save` is given.
+    Smoke {
+        /// The number of in-process iterations to run each benchmark with.
+        #[arg(
+            long,
+            default_value_t = 1,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        iters: usize,
+        /// Store the results as a datum, marked as a smoke run.
+        #[arg(long)]
+        save: bool,
+        /// Don't build executors that have a `build` table.
+        #[arg(long)]
+        skip_build: bool,
+    },
+    /// Benchmark, store and diff tiny synthetic harnesses in a temporary directory, checking the
+    /// results, and exiting non-zero if any check fails.
+    ///
+    /// Only the wall-clock times are checked: other metrics, such as the counters collected with
+    /// `bench --perf`, aren't, so their collection, storage and diffing may be broken even if the
+    /// self-test passes.
+    #[command(hide = true)]
+    Selftest {
+        /// Keep the temporary directory, with its config, harnesses and datums.
+        #[arg(long)]
+        keep: bool,
+    },
+    /// Check that every datum can be loaded and is consistent, exiting non-zero if any is broken.
+    Fsck {
+        /// Move broken datums out of the way (by renaming their directories with a `.broken`
+        /// suffix), so that other commands no longer see them.
+        #[arg(long)]
+        repair: bool,
+    },
+    /// Send an existing datum to the URL in the config's `[notify]` section.
+    Notify { id: usize },
+    /// Run benchmarks with two executors, alternating pexecs between them, and compare them.
+    ///
+    /// Exits non-zero if the second executor is significantly slower for any benchmark.
+    Ab {
+        /// The executors to compare: first the baseline, then the new one.
+        #[arg(
+            short,
+            long = "executor",
+            num_args(1),
+            required_unless_present = "group"
+        )]
+        executors: Vec<String>,
+        /// Compare the two executors of this executor group, rather than giving them with
+        /// `--executor`.
+        #[arg(long, conflicts_with = "executors")]
+        group: Option<String>,
+        /// Only run benchmarks with this name (may be repeated).
+        #[arg(long)]
+        benchmark: Vec<String>,
+        /// Confidence level for the interval.
+        #[arg(short, long, default_value_t = ConfidenceLevel::default())]
+        confidence: ConfidenceLevel,
+        /// Compare the executors round by round, which is more sensitive to small differences.
+        #[arg(long)]
+        paired: bool,
+        /// With --paired, also show the unpaired comparison.
+        #[arg(long, requires = "paired")]
+        show_both: bool,
+    },
+    /// Compare the distributions of a single benchmark in two datums in detail.
+    ///
+    /// Shows descriptive statistics, a Kolmogorov-Smirnov test and histograms, which can reveal
+    /// changes in shape (e.g. a new bimodal split) that barely move the mean.
+    CompareBench {
+        id1: usize,
+        id2: usize,
+        /// The benchmark key, as shown by `diff` (e.g. `fib/lua/`).
+        key: String,
+        /// The metric to compare (e.g. `instructions` for data collected with `--perf`).
+        #[arg(short, long, default_value = TIME_METRIC)]
+        metric: String,
+    },
+    /// Compare the variants of benchmarks run with different extra_args in one datum.
+    ///
+    /// Each benchmark run with an executor under several extra_args is compared with its variant
+    /// run with the baseline extra_args.
+    DiffArgs {
+        id: usize,
+        /// Only compare benchmarks with this name (may be repeated).
+        #[arg(long)]
+        benchmark: Vec<String>,
+        /// Only compare benchmarks run with this executor (may be repeated).
+        #[arg(short, long)]
+        executor: Vec<String>,
+        /// The extra_args of the variant to compare the others with, separated by whitespace.
+        #[arg(long, default_value = "", allow_hyphen_values = true)]
+        baseline_args: String,
+        /// The metric to compare (e.g. `instructions` for data collected with `--perf`).
+        #[arg(short, long, default_value = TIME_METRIC)]
+        metric: String,
+        /// Confidence level for the interval.
+        #[arg(short, long, default_value_t = ConfidenceLevel::default())]
+        confidence: ConfidenceLevel,
+    },
+    /// List the benchmarks that got significantly slower over recent datums.
+    ///
+    /// The newest datum is compared with the datum `--window` datums before it. Exits non-zero if
+    /// any benchmark regressed.
+    Regressions {
+        /// How many datums back to compare with.
+        #[arg(
+            short,
+            long,
+            default_value_t = 5,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        window: usize,
+        /// Confidence level for the interval.
+        #[arg(short, long, default_value_t = ConfidenceLevel::default())]
+        confidence: ConfidenceLevel,
+    },
+    /// Browse and compare datums interactively.
+    Tui {
+        /// Confidence level for the interval.
+        #[arg(short, long, default_value_t = ConfidenceLevel::default())]
+        confidence: ConfidenceLevel,
+    },
+    /// Re-run benchmarks whenever files change, diffing each new datum against a baseline.
+    ///
+    /// Executors are not rebuilt.
+    #[clap(visible_alias = "w")]
+    Watch {
+        /// The files to watch (default: the executor binaries).
+        paths: Vec<PathBuf>,
+        /// The datum to diff each new datum against.
+        #[arg(long)]
+        baseline: usize,
+        /// Only run benchmarks with this name (may be repeated).
+        #[arg(long)]
+        benchmark: Vec<String>,
+        /// Override the configured number of process executions.
+        #[arg(long)]
+        proc_execs: Option<usize>,
+        /// Confidence level for the interval.
+        #[arg(short, long, default_value_t = ConfidenceLevel::default())]
+        confidence: ConfidenceLevel,
+    },
+    /// Find the first commit between a good and a bad one which made a benchmark slower, by
+    /// building and benchmarking commits in the manner of `git bisect`.
+    ///
+    /// Commits are checked out in a temporary worktree, leaving the repository's own tree alone,
+    /// and the executors whose `build` directories are in the repository are built there. Each
+    /// commit's results are stored as a datum, with a comment naming the commit.
+    Bisect {
+        /// A commit without the regression.
+        #[arg(long)]
+        good: String,
+        /// A later commit with the regression.
+        #[arg(long)]
+        bad: String,
+        /// Treat a commit as bad if any benchmark is significantly worse than in the good commit
+        /// by more than this percentage (e.g. `3%`).
+        #[arg(long, value_parser = bisect::parse_threshold)]
+        threshold: f64,
+        /// Only run benchmarks with this name (may be repeated).
+        #[arg(long)]
+        benchmark: Vec<String>,
+        /// The number of process executions of each benchmark for each commit (default: the
+        /// configured number, up to 10).
+        #[arg(long)]
+        proc_execs: Option<usize>,
+        /// Don't test this commit, e.g. because it doesn't build (may be repeated). Commits whose
+        /// builds or benchmarks fail are skipped anyway.
+        #[arg(long)]
+        skip: Vec<String>,
+        /// A directory in the repository to bisect.
+        #[arg(long, default_value = ".")]
+        repo: PathBuf,
+        /// The metric to compare.
+        #[arg(short, long, default_value = TIME_METRIC)]
+        metric: String,
+        /// Confidence level for the interval.
+        #[arg(short, long, default_value_t = ConfidenceLevel::default())]
+        confidence: ConfidenceLevel,
+    },
+    /// Show descriptive statistics of every benchmark in a datum.
+    Stat {
+        id: usize,
+        /// The metric to describe (e.g. `instructions` for data collected with `--perf`).
+        #[arg(short, long, default_value = TIME_METRIC)]
+        metric: String,
+        /// The statistics to show, separated by commas.
+        #[arg(
+            long,
+            value_enum,
+            value_delimiter = ',',
+            default_value = "n,mean,stddev,variance,min,median,max,iqr,skewness"
+        )]
+        columns: Vec<StatColumn>,
+        /// The output format.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    /// Rank the executors of a datum from fastest to slowest, per benchmark and overall.
+    Rank {
+        id: usize,
+        /// Confidence level for the interval. Executors whose intervals overlap are ranked jointly.
+        #[arg(short, long, default_value_t = ConfidenceLevel::default())]
+        confidence: ConfidenceLevel,
+        /// The output format.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    /// Rank datums, e.g. from different machines, from the least to the most noisy.
+    Stability {
+        /// The datums: IDs, or `<path>:<id>` for datums in the state directory `path`.
+        #[arg(required = true)]
+        ids: Vec<DatumRef>,
+        /// The metric whose noise to measure.
+        #[arg(short, long, default_value = TIME_METRIC)]
+        metric: String,
+        /// The output format.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    /// Compare the executors of one datum, each with the first, the baseline.
+    Compare {
+        id: usize,
+        /// Compare the executors of this executor group. Defaults to the group the datum was made
+        /// for, e.g. by `ab --group`.
+        #[arg(long, conflicts_with = "executors")]
+        group: Option<String>,
+        /// The executors to compare, rather than a group's: first the baseline, then the others.
+        #[arg(short, long = "executor", num_args(1))]
+        executors: Vec<String>,
+        /// Confidence level for the interval.
+        #[arg(short, long, default_value_t = ConfidenceLevel::default())]
+        confidence: ConfidenceLevel,
+        /// The output format.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    /// Run the benchmarks whose comparison of a datum with a baseline is borderline for more
+    /// pexecs, and store the datum with their samples added as a new datum.
+    ///
+    /// A comparison is borderline if making both confidence intervals `--margin`% narrower or
+    /// wider would change whether it's significant. The new datum is compared with the baseline.
+    Refine {
+        id: usize,
+        /// The baseline datum that `id` is compared with.
+        #[arg(long, value_name = "ID")]
+        against: usize,
+        /// The most pexecs to add to each borderline benchmark. They're all run as many times as
+        /// the one needing the most should need to narrow its confidence interval by the margin,
+        /// up to this.
+        #[arg(
+            long,
+            default_value_t = 10,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        max_extra_pexecs: usize,
+        /// How close to significance, as a percentage of the combined half-widths of the
+        /// confidence intervals, a comparison is borderline.
+        #[arg(long, value_name = "PERCENT", value_parser = parse_percent, default_value_t = refine::DEFAULT_MARGIN)]
+        margin: f64,
+        /// Confidence level for the interval.
+        #[arg(short, long, default_value_t = ConfidenceLevel::default())]
+        confidence: ConfidenceLevel,
+    },
+    /// Estimate the false-positive rate of diffs by comparing random halves of a datum.
+    Noise {
+        id: usize,
+        /// Confidence level for the interval.
+        #[arg(short, long, default_value_t = ConfidenceLevel::default())]
+        confidence: ConfidenceLevel,
+        /// The number of random splits to make per benchmark.
+        #[arg(short, long, default_value_t = 1000)]
+        reps: usize,
+    },
+    /// List the benchmarks that no comparison of consecutive recent datums found significantly
+    /// different, with the time spent on them.
+    Advise {
+        /// The number of newest datums to compare, each with the next.
+        #[arg(
+            short,
+            long,
+            default_value_t = 10,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(2..)
+        )]
+        window: usize,
+        /// Confidence level for the interval.
+        #[arg(short, long, default_value_t = ConfidenceLevel::default())]
+        confidence: ConfidenceLevel,
+        /// The output format.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ExperimentAction {
+    /// Start an experiment: every datum created until it's ended is part of it. Starting an
+    /// experiment again adds more datums to it.
+    Start { name: String },
+    /// End the active experiment.
+    End,
+    /// List the experiments, with how many datums each has and when they were collected.
+    List,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Some(u) = cli.units {
+        units::set(u);
+    }
+    let creates = matches!(
+        cli.mode,
+        Mode::Bench { .. }
+            | Mode::Watch { .. }
+            | Mode::Ab { .. }
+            | Mode::Refine { .. }
+            | Mode::Bisect { .. }
+            | Mode::Smoke { save: true, .. }
+            | Mode::Experiment { .. }
+    );
+    let app = App::new(
+        cli.file,
+        cli.state_dir,
+        creates,
+        cli.store,
+        cli.no_cache,
+        cli.seed,
+    );
+    match cli.mode {
+        Mode::Bench {
+            comment,
+            comment_from_git,
+            max_duration,
+            perf,
+            verbose,
+            skip_build,
+            skip_missing_executors,
+            keep_going,
+            quiet,
+            no_notify,
+            log_file,
+            log_format,
+            events,
+            extend,
+            order,
+            dry_run,
+            nice,
+            haste_nice,
+        } => {
+            let opts = BenchOptions {
+                comment,
+                max_duration,
+                verbose,
+                skip_build,
+                log_file,
+                log_format,
+                events,
+                skip_missing_executors,
+                keep_going,
+                quiet,
+                extend,
+                order,
+                dry_run,
+                nice,
+                haste_nice,
+                ..Default::default()
+            };
+            app.cmd_bench(opts, perf, comment_from_git, no_notify)
+        }
+        Mode::Diff {
+            id1,
+            id2,
+            confidence,
+            relaxed,
+            metric,
+            force,
+            thousands,
+            subtract_overhead,
+            min_samples,
+            stat,
+            metrics,
+            wide,
+            format,
+            describe,
+            deterministic,
+            junit,
+            fail_on_regression,
+            plot,
+            log_scale,
+            no_env_warnings,
+            strict_dims,
+            pin,
+            sort_by,
+            reverse,
+            targets,
+            test,
+            output,
+            overwrite,
+            quiet,
+            duplicate_threshold,
+            benchmarks,
+            explain,
+        } => {
+            let targets = targets.map_or_else(Vec::new, |path| {
+                let path = path.unwrap_or_else(|| app.default_targets_file());
+                targets::load(&path).unwrap_or_else(|e| {
+                    eprintln!("error: {e}");
+                    process::exit(1);
+                })
+            });
+            let opts = DiffOptions {
+                confidence,
+                relaxed,
+                metric,
+                force,
+                thousands,
+                subtract_overhead,
+                min_samples,
+                stat,
+                describe,
+                deterministic,
+                junit,
+                fail_on_regression,
+                plot,
+                log_scale,
+                env_warnings: !no_env_warnings,
+                strict_dims,
+                order: order::RowOrder {
+                    pins: pin,
+                    sort_by,
+                    reverse,
+                },
+                targets,
+                test,
+                output: output::OutputTarget {
+                    path: output,
+                    overwrite,
+                    quiet,
+                },
+                duplicate_threshold,
+                benchmarks,
+                explain,
+            };
+            if metrics.is_empty() {
+                app.cmd_diff(&id1, &id2, &opts)
+            } else {
+                app.cmd_diff_metrics(&id1, &id2, &metrics, wide, format, &opts)
+            }
+        }
+        Mode::Report {
+            id1,
+            id2,
+            confidence,
+            min_samples,
+            output,
+            overwrite,
+            quiet,
+        } => {
+            let output = output::OutputTarget {
+                path: output,
+                overwrite,
+                quiet,
+            };
+            app.cmd_report(id1, id2, confidence, min_samples, &output)
+        }
+        Mode::List {
+            long,
+            label,
+            experiment,
+        } => app.cmd_list(long, &label, experiment.as_deref()),
+        Mode::ListBenchmarks => app.cmd_list_benchmarks(),
+        Mode::Show {
+            id,
+            confidence,
+            thousands,
+            raw,
+            subtract_overhead,
+            metric,
+            histogram,
+            bins,
+            describe,
+            env,
+            output,
+            overwrite,
+            quiet,
+        } => {
+            let opts = ShowOptions {
+                confidence,
+                thousands,
+                raw,
+                subtract_overhead,
+                metric,
+                histogram_bins: histogram.then_some(bins),
+                describe,
+                env,
+                output: output::OutputTarget {
+                    path: output,
+                    overwrite,
+                    quiet,
+                },
+            };
+            app.cmd_show(id, &opts)
+        }
+        Mode::Rank {
+            id,
+            confidence,
+            format,
+        } => app.cmd_rank(id, confidence, format),
+        Mode::Stability {
+            ids,
+            metric,
+            format,
+        } => app.cmd_stability(&ids, &metric, format),
+        Mode::Noise {
+            id,
+            confidence,
+            reps,
+        } => app.cmd_noise(id, confidence, reps),
+        Mode::Advise {
+            window,
+            confidence,
+            format,
+        } => app.cmd_advise(window, confidence, format),
+        Mode::Notify { id } => app.cmd_notify(id),
+        Mode::Migrate { to: Some(to), .. } => app.cmd_migrate(to),
+        Mode::Migrate { to: None, .. } => app.cmd_compact(),
+        Mode::Check => app.cmd_check(),
+        Mode::Smoke {
+            iters,
+            save,
+            skip_build,
+        } => app.cmd_smoke(iters, save, skip_build),
+        Mode::Targets {
+            id,
+            targets,
+            confidence,
+        } => app.cmd_targets(id, targets.as_deref(), confidence),
+        Mode::Selftest { keep } => app.cmd_selftest(keep),
+        Mode::Fsck { repair } => app.cmd_fsck(repair),
+        Mode::Rm {
+            ids,
+            label,
+            experiment,
+        } => app.cmd_rm(&ids, &label, experiment.as_deref()),
+        Mode::Edit {
+            id,
+            comment,
+            no_comment,
+        } => app.cmd_edit(id, comment, no_comment),
+        Mode::Label { id, labels, remove } => app.cmd_label(id, &labels, &remove),
+        Mode::Experiment { action } => match action {
+            ExperimentAction::Start { name } => app.cmd_experiment_start(&name),
+            ExperimentAction::End => app.cmd_experiment_end(),
+            ExperimentAction::List => app.cmd_experiment_list(),
+        },
+        Mode::Watch {
+            paths,
+            baseline,
+            benchmark,
+            proc_execs,
+            confidence,
+        } => app.cmd_watch(paths, baseline, &benchmark, proc_execs, confidence),
+        Mode::Bisect {
+            good,
+            bad,
+            threshold,
+            benchmark,
+            proc_execs,
+            skip,
+            repo,
+            metric,
+            confidence,
+        } => app.cmd_bisect(&bisect::BisectOptions {
+            good,
+            bad,
+            threshold,
+            benchmarks: benchmark,
+            proc_execs,
+            skip,
+            repo,
+            metric,
+            confidence,
+        }),
+        Mode::Stat {
+            id,
+            metric,
+            columns,
+            format,
+        } => app.cmd_stat(id, &metric, &columns, format),
+        Mode::CompareBench {
+            id1,
+            id2,
+            key,
+            metric,
+        } => app.cmd_compare_bench(id1, id2, &key, &metric),
+        Mode::DiffArgs {
+            id,
+            benchmark,
+            executor,
+            baseline_args,
+            metric,
+            confidence,
+        } => app.cmd_diff_args(
+            id,
+            &benchmark,
+            &executor,
+            &baseline_args,
+            &metric,
+            confidence,
+        ),
+        Mode::Regressions { window, confidence } => app.cmd_regressions(window, confidence),
+        Mode::Tui { confidence } => {
+            if let Err(e) = tui::run(&app, confidence) {
+                eprintln!("error: {e}");
+                process::exit(1);
+            }
+        }
+        Mode::Ab {
+            executors,
+            group,
+            benchmark,
+            confidence,
+            paired,
+            show_both,
+        } => app.cmd_ab(
+            &executors,
+            group.as_deref(),
+            &benchmark,
+            confidence,
+            paired,
+            show_both,
+        ),
+        Mode::Refine {
+            id,
+            against,
+            max_extra_pexecs,
+            margin,
+            confidence,
+        } => app.cmd_refine(id, against, max_extra_pexecs, margin, confidence),
+        Mode::Compare {
+            id,
+            group,
+            executors,
+            confidence,
+            format,
+        } => app.cmd_compare(id, group.as_deref(), &executors, confidence, format),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        App, ConfidenceLevel, DEFAULT_CONFIG_FILE, DEFAULT_MIN_SAMPLES, DatumRef, ExtraToml,
+        Failure, Interleaving, KeyParams, NumFormat, ResultFile, SummaryStats, diff_rows,
+        diff_table, format_timestamp, format_timestamp_ms, noise_false_positives, parse_duration,
+        runner, window_ends,
+    };
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+    use std::collections::HashMap;
+    use std::env;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    #[test]
+    fn cis_overlap() {
+        let s1 = SummaryStats::new(10., 5., 10);
+        let s2 = SummaryStats::new(5., 8., 10);
+        let s3 = SummaryStats::new(50.6, 20.6667, 10);
+        let s4 = SummaryStats::new(-0.5, 0.1, 10);
+        let s5 = SummaryStats::new(-0.5, 0.2, 10);
+        assert!(s1.ci_overlaps(&s2));
+        assert!(s2.ci_overlaps(&s1));
+        assert!(s1.ci_overlaps(&s1));
+        assert!(s2.ci_overlaps(&s2));
+        assert!(!s1.ci_overlaps(&s3));
+        assert!(!s3.ci_overlaps(&s1));
+        assert!(s1.ci_overlaps(&s1));
+        assert!(s2.ci_overlaps(&s2));
+        assert!(s3.ci_overlaps(&s3));
+        assert!(s4.ci_overlaps(&s5));
+        assert!(s5.ci_overlaps(&s4));
+        assert!(!s4.ci_overlaps(&s1));
+        assert!(s4.ci_overlaps(&s2));
+        assert!(!s4.ci_overlaps(&s3));
+        assert!(!s5.ci_overlaps(&s1));
+        assert!(s5.ci_overlaps(&s2));
+        assert!(!s5.ci_overlaps(&s3));
+    }
+
+    #[test]
+    fn test_default_config_path() {
+        use std::fs;
+
+        // Create a temporary directory for testing
+        let temp_dir = std::env::temp_dir().join("haste_test_default");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        let original_dir = std::env::current_dir().unwrap();
+        std::env::set_current_dir(&temp_dir).unwrap();
+
+        // Create the app with no config specified
+        let app = App::new(None, None, false, None, false, None);
+
+        // Should use the default CONFIG_FILE
+        assert_eq!(app.config_file, PathBuf::from(DEFAULT_CONFIG_FILE));
+
+        // Cleanup
+        std::env::set_current_dir(original_dir).unwrap();
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn test_config_file_loading() {
+        use std::fs;
+
+        let temp_dir = std::env::temp_dir().join("haste_test_loading");
+        let _ = fs::remove_dir_all(&temp_dir);
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        // Create a test config file
+        let config_content = r#"
+        proc_execs = 2
+        inproc_iters = 3
+
+        [executors]
+        test_exec = "/bin/sh"
+
+        [suites.test_suite]
+        dir = "."
+        harness = "test.sh"
+
+        [suites.test_suite.benchmarks.test_bench]
+        extra_args = ["arg1"]
+        "#;
+        let config_path = temp_dir.join("test_config.toml");
+        fs::write(&config_path, config_content).unwrap();
+
+        // Parse the config
+        let config_text = fs::read_to_string(&config_path).unwrap();
+        let config: crate::config::Config = toml::from_str(&config_text).unwrap();
+
+        // Verify parsed values
+        assert_eq!(config.proc_execs, 2);
+        assert_eq!(config.inproc_iters, 3);
+        assert_eq!(config.executors.len(), 1);
+        assert!(config.executors.contains_key("test_exec"));
+        assert_eq!(config.suites.len(), 1);
+        assert!(config.suites.contains_key("test_suite"));
+
+        // Cleanup
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[test]
+    fn confidence_level_default() {
+        assert_eq!(ConfidenceLevel::default(), ConfidenceLevel::CL99);
+    }
+
+    #[test]
+    fn confidence_level_from_str() {
+        assert_eq!(
+            ConfidenceLevel::from_str("90").unwrap(),
+            ConfidenceLevel(90.)
+        );
+        assert_eq!(
+            ConfidenceLevel::from_str("95").unwrap(),
+            ConfidenceLevel(95.)
+        );
+        assert_eq!(
+            ConfidenceLevel::from_str("99").unwrap(),
+            ConfidenceLevel::CL99
+        );
+        assert_eq!(
+            ConfidenceLevel::from_str("99.9").unwrap(),
+            ConfidenceLevel(99.9)
+        );
+        for bad in ["0", "100", "-5", "abc", "NaN"] {
+            assert!(ConfidenceLevel::from_str(bad).is_err());
+        }
+        assert_eq!(ConfidenceLevel(99.9).to_string(), "99.9");
+        assert_eq!(ConfidenceLevel::CL99.to_string(), "99");
+    }
+
+    #[test]
+    fn confidence_level_zval() {
+        // Known quantiles of the standard normal distribution.
+        for (level, z) in [
+            (50., 0.6745),
+            (80., 1.2816),
+            (90., 1.6449),
+            (95., 1.9600),
+            (99., 2.5758),
+            (99.9, 3.2905),
+            (99.99, 3.8906),
+        ] {
+            let zval = ConfidenceLevel(level).zval();
+            assert!((zval - z).abs() < 0.0001, "{level}%: {zval} vs. {z}");
+        }
+    }
+
+    #[test]
+    fn noise_false_positives_split() {
+        let mut rng = StdRng::seed_from_u64(0);
+        // Too few samples to split.
+        assert_eq!(
+            noise_false_positives(&[1., 2., 3.], ConfidenceLevel::CL99, 10, &mut rng),
+            None
+        );
+        // Identical samples can never be significantly different.
+        assert_eq!(
+            noise_false_positives(&[5.; 10], ConfidenceLevel::CL99, 10, &mut rng),
+            Some(0)
+        );
+        // The same seed gives the same splits.
+        let samples = [1., 2., 1., 3., 2., 9., 1., 2., 2., 1.];
+        let fps1 = noise_false_positives(
+            &samples,
+            ConfidenceLevel(90.),
+            100,
+            &mut StdRng::seed_from_u64(1),
+        );
+        let fps2 = noise_false_positives(
+            &samples,
+            ConfidenceLevel(90.),
+            100,
+            &mut StdRng::seed_from_u64(1),
+        );
+        assert!(fps1.unwrap() <= 100);
+        assert_eq!(fps1, fps2);
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(format_timestamp(None), "unknown");
+        assert_eq!(format_timestamp(Some(0)), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(Some(951782400)), "2000-02-29 00:00:00 UTC");
+        assert_eq!(
+            format_timestamp(Some(1760451119)),
+            "2025-10-14 14:11:59 UTC"
+        );
+        assert_eq!(
+            format_timestamp_ms(1_700_000_000_123),
+            "2023-11-14 22:13:20.123 UTC"
+        );
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("45m"), Ok(Duration::from_secs(45 * 60)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(90 * 60)));
+        assert_eq!(parse_duration("2d"), Ok(Duration::from_secs(2 * 86400)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("10x").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("1h30").is_err());
+    }
+
+    #[test]
+    fn retain_common() {
+        let mut r1 = ResultFile {
+            data: HashMap::from([("a".to_owned(), vec![1.]), ("b".to_owned(), vec![2.])]),
+            ..Default::default()
+        };
+        let mut r2 = ResultFile {
+            data: HashMap::from([("b".to_owned(), vec![3.]), ("c".to_owned(), vec![4.])]),
+            ..Default::default()
+        };
+        let (lhs, rhs) = r1.retain_common(&mut r2);
+        assert_eq!(lhs, vec!["a".to_owned()]);
+        assert_eq!(rhs, vec!["c".to_owned()]);
+        assert_eq!(r1.data.keys().collect::<Vec<_>>(), vec!["b"]);
+        assert_eq!(r2.data.keys().collect::<Vec<_>>(), vec!["b"]);
+    }
+
+    #[test]
+    fn perf_output_parsing() {
+        let out = "# started on Tue Oct 14 10:00:00 2026\n\n\
+                   23.52,msec,task-clock:u,23520000,100.00,0.952,CPUs utilized\n\
+                   50123456,,cycles:u,23400000,100.00,2.131,GHz\n\
+                   90123456,,instructions:u,23400000,100.00,1.80,insn per cycle\n";
+        let counters = crate::runner::parse_perf_output(out).unwrap();
+        assert_eq!(counters["task-clock"], 23.52);
+        assert_eq!(counters["cycles"], 50123456.);
+        assert_eq!(counters["instructions"], 90123456.);
+
+        let out = "23.52,msec,task-clock:u,23520000,100.00,,\n\
+                   <not supported>,,cycles:u,0,100.00,,\n\
+                   <not supported>,,instructions:u,0,100.00,,\n";
+        assert!(crate::runner::parse_perf_output(out).is_err());
+        assert!(crate::runner::parse_perf_output("").is_err());
+    }
+
+    #[test]
+    fn metric_selection() {
+        let rf = ResultFile {
+            data: HashMap::from([("a".to_owned(), vec![1.])]),
+            metrics: HashMap::from([(
+                "instructions".to_owned(),
+                HashMap::from([("a".to_owned(), vec![100.])]),
+            )]),
+            ..Default::default()
+        };
+        assert_eq!(rf.for_metric("time").unwrap().data["a"], vec![1.]);
+        assert_eq!(rf.for_metric("instructions").unwrap().data["a"], vec![100.]);
+        assert!(rf.for_metric("cycles").is_err());
+    }
+
+    #[test]
+    fn ips_metric() {
+        let params = |inproc_iters| KeyParams {
+            inproc_iters,
+            proc_execs: 5,
+            extra_args: vec![],
+            suite_args: vec![],
+            harness: None,
+            command: None,
+        };
+        // Doubling the iterations and taking a little less than twice as long is an improvement.
+        let rf1 = ResultFile {
+            data: HashMap::from([("a".to_owned(), vec![100., 101., 99., 100., 100.])]),
+            params: HashMap::from([("a".to_owned(), params(10))]),
+            ..Default::default()
+        };
+        let rf2 = ResultFile {
+            data: HashMap::from([("a".to_owned(), vec![180., 181., 179., 180., 180.])]),
+            params: HashMap::from([("a".to_owned(), params(20))]),
+            ..Default::default()
+        };
+        let ips1 = rf1.for_metric("ips").unwrap();
+        let ips2 = rf2.for_metric("ips").unwrap();
+        assert_eq!(ips1.data["a"][0], 100.);
+        assert!(ips1.param_mismatches(&ips2, false).is_empty());
+        let stats1 = ips1.summarise(ConfidenceLevel::CL99);
+        let stats2 = ips2.summarise(ConfidenceLevel::CL99);
+        let rows = diff_rows(&stats1, &stats2, DEFAULT_MIN_SAMPLES, true);
+        assert!(rows[0].significant && !rows[0].is_regression());
+        assert_eq!(rows[0].summary(), "11.11% faster");
+
+        // Without recorded iteration counts, the metric can't be derived.
+        let old = ResultFile {
+            data: rf1.data.clone(),
+            ..Default::default()
+        };
+        assert_eq!(
+            old.for_metric("ips").unwrap_err(),
+            "the ips metric is unavailable, as the number of in-process iterations wasn't \
+             recorded for: a"
+        );
+    }
+
+    #[test]
+    fn calibration() {
+        use crate::runner::calibrated_iters;
+        assert_eq!(calibrated_iters(2000., 1, 40.), 50);
+        assert_eq!(calibrated_iters(2000., 10, 40.), 500);
+        assert_eq!(calibrated_iters(100., 1, 1000.), 1);
+        assert_eq!(calibrated_iters(100., 2, 0.), 2000);
+
+        let rf = ResultFile {
+            data: HashMap::from([("a".to_owned(), vec![1., 2.])]),
+            ..Default::default()
+        };
+        let none = HashMap::new();
+        let iters50 = HashMap::from([("a".to_owned(), 50)]);
+        let iters60 = HashMap::from([("a".to_owned(), 60)]);
+        assert!(rf.same_dims(&rf, &none, &none, true).is_ok());
+        assert!(rf.same_dims(&rf, &iters50, &iters50, true).is_ok());
+        assert!(rf.same_dims(&rf, &iters50, &iters60, true).is_err());
+        assert!(rf.same_dims(&rf, &none, &iters60, true).is_err());
+    }
+
+    #[test]
+    fn param_mismatches() {
+        let params = |inproc_iters, proc_execs, extra_args: &[&str]| KeyParams {
+            inproc_iters,
+            proc_execs,
+            extra_args: extra_args.iter().map(|x| x.to_string()).collect(),
+            suite_args: vec![],
+            harness: None,
+            command: None,
+        };
+        let rf1 = ResultFile {
+            params: HashMap::from([
+                ("a".to_owned(), params(10, 5, &["x"])),
+                ("b".to_owned(), params(10, 5, &[])),
+                ("c".to_owned(), params(10, 5, &[])),
+            ]),
+            ..Default::default()
+        };
+        let rf2 = ResultFile {
+            params: HashMap::from([
+                ("a".to_owned(), params(30, 5, &["x"])),
+                ("b".to_owned(), params(10, 3, &["y"])),
+            ]),
+            ..Default::default()
+        };
+        assert!(rf1.param_mismatches(&rf1, true).is_empty());
+        assert_eq!(
+            rf1.param_mismatches(&rf2, true),
+            vec![
+                "a: inproc_iters 10 vs. 30",
+                "b: proc_execs 5 vs. 3",
+                "b: extra_args [] vs. [\"y\"]"
+            ]
+        );
+        // Old datums without parameters can't be checked.
+        assert!(
+            rf1.param_mismatches(&ResultFile::default(), true)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn per_executor_harness() {
+        use std::path::Path;
+
+        let config_content = r#"
+        proc_execs = 1
+        inproc_iters = 1
+
+        [executors]
+        lua = "/usr/bin/lua"
+        luajit = "/usr/bin/luajit"
+        pywrap = "/usr/bin/pywrap"
+
+        [suites.s1]
+        dir = "."
+        harness = { pywrap = "harness.py", default = "harness.lua" }
+        benchmarks = { b = {} }
+
+        [suites.s2]
+        dir = "."
+        harness = "harness.sh"
+        benchmarks = {}
+        "#;
+        let config: crate::config::Config = toml::from_str(config_content).unwrap();
+        assert!(config.validate().is_ok());
+        let s1 = &config.suites["s1"];
+        assert_eq!(s1.harness_for("pywrap"), Some(Path::new("harness.py")));
+        assert_eq!(s1.harness_for("lua"), Some(Path::new("harness.lua")));
+        let s2 = &config.suites["s2"];
+        assert_eq!(s2.harness_for("lua"), Some(Path::new("harness.sh")));
+
+        // No default, so `luajit` has no harness.
+        let no_default = config_content.replace("default", "lua");
+        let config: crate::config::Config = toml::from_str(&no_default).unwrap();
+        assert_eq!(
+            config.validate(),
+            Err("suite s1: no harness for executor luajit (and no default)".to_owned())
+        );
+
+        // Unknown executor names are rejected.
+        let unknown = config_content.replace("{ pywrap =", "{ python =");
+        let config: crate::config::Config = toml::from_str(&unknown).unwrap();
+        assert_eq!(
+            config.validate(),
+            Err("suite s1: harness specified for unknown executor python".to_owned())
+        );
+    }
+
+    #[test]
+    fn benchmark_sets() {
+        let config_content = r#"
+        proc_execs = 1
+        inproc_iters = 1
+
+        [executors]
+        lua = "/usr/bin/lua"
+
+        [benchmark_sets.standard]
+        a = {}
+        b = { extra_args = ["1"] }
+
+        [benchmark_sets.extra]
+        b = { extra_args = ["1"] }
+        c = {}
+
+        [suites.jit]
+        dir = "."
+        harness = "jit.sh"
+        benchmarks_from = ["standard", "extra"]
+        benchmarks = { a = { extra_args = ["big"] } }
+
+        [suites.interp]
+        dir = "."
+        harness = "interp.sh"
+        benchmarks_from = ["standard"]
+        "#;
+        let mut config: crate::config::Config = toml::from_str(config_content).unwrap();
+        config.expand_benchmark_sets().unwrap();
+        assert!(config.validate().is_ok());
+        let jit = &config.suites["jit"].benchmarks;
+        let mut names = jit.keys().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["a", "b", "c"]);
+        // Inline benchmarks override those in sets.
+        assert_eq!(jit["a"].extra_args, ["big"]);
+        assert_eq!(config.suites["interp"].benchmarks.len(), 2);
+
+        let undefined = config_content.replace(r#"["standard"]"#, r#"["nonstandard"]"#);
+        let mut config: crate::config::Config = toml::from_str(&undefined).unwrap();
+        assert_eq!(
+            config.expand_benchmark_sets(),
+            Err("suite interp: no benchmark set named nonstandard".to_owned())
+        );
+
+        let conflicting = config_content.replace(
+            r#"b = { extra_args = ["1"] }
+        c"#,
+            r#"b = { extra_args = ["2"] }
+        c"#,
+        );
+        let mut config: crate::config::Config = toml::from_str(&conflicting).unwrap();
+        assert_eq!(
+            config.expand_benchmark_sets(),
+            Err(
+                "suite jit: benchmark b is defined differently in benchmark sets standard and \
+                 extra"
+                    .to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn command_benchmarks() {
+        let config_content = r#"
+        proc_execs = 1
+        inproc_iters = 1
+
+        [executors]
+        sh = "shell"
+
+        [suites.s1]
+        dir = "."
+
+        [suites.s1.benchmarks.grep]
+        command = "grep -r foo ."
+        "#;
+        let config: crate::config::Config = toml::from_str(config_content).unwrap();
+        assert!(config.validate().is_ok());
+
+        // A harness is needed as soon as there is a non-command benchmark.
+        let with_harness_bench = format!("{config_content}\n[suites.s1.benchmarks.other]");
+        let config: crate::config::Config = toml::from_str(&with_harness_bench).unwrap();
+        assert_eq!(
+            config.validate(),
+            Err("suite s1: no harness for executor sh (and no default)".to_owned())
+        );
+
+        // Commands don't take extra_args.
+        let ambiguous = format!("{config_content}\nextra_args = [\"x\"]");
+        let config: crate::config::Config = toml::from_str(&ambiguous).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn executor_forms() {
+        let config_content = r#"
+        proc_execs = 1
+        inproc_iters = 1
+
+        [executors]
+        plain = "/usr/bin/python3"
+        built = { build = { command = "cargo build --release", dir = "..", artifact = "target/release/ykpython" } }
+        explicit = { path = "/opt/bin/lua", build = { command = "make", artifact = "lua" } }
+
+        [suites]
+        "#;
+        let config: crate::config::Config = toml::from_str(config_content).unwrap();
+        let plain = &config.executors["plain"];
+        assert_eq!(plain.path, PathBuf::from("/usr/bin/python3"));
+        assert!(plain.build.is_none());
+        let built = &config.executors["built"];
+        assert_eq!(built.path, PathBuf::from("../target/release/ykpython"));
+        assert_eq!(
+            built.build.as_ref().unwrap().command,
+            "cargo build --release"
+        );
+        let explicit = &config.executors["explicit"];
+        assert_eq!(explicit.path, PathBuf::from("/opt/bin/lua"));
+        assert_eq!(explicit.build.as_ref().unwrap().dir, PathBuf::from("."));
+
+        let config_content = r#"
+        proc_execs = 1
+        inproc_iters = 1
+        executors = { broken = {} }
+        suites = {}
+        "#;
+        assert!(toml::from_str::<crate::config::Config>(config_content).is_err());
+    }
+
+    #[test]
+    fn store_backends() {
+        use crate::store::{self, StoreKind};
+        let results = ResultFile {
+            data: HashMap::from([("a/x/".to_owned(), vec![1.5, 2.25, 3.])]),
+            metrics: HashMap::from([(
+                "instructions".to_owned(),
+                HashMap::from([("a/x/".to_owned(), vec![100., 101., 102.])]),
+            )]),
+            params: HashMap::from([(
+                "a/x/".to_owned(),
+                KeyParams {
+                    inproc_iters: 5,
+                    proc_execs: 3,
+                    extra_args: vec![],
+                    suite_args: vec![],
+                    harness: Some(PathBuf::from("h.sh")),
+                    command: None,
+                },
+            )]),
+            timestamps: HashMap::from([(
+                "a/x/".to_owned(),
+                vec![1_700_000_000_000, 1_700_000_000_100, 1_700_000_000_250],
+            )]),
+            failures: HashMap::from([(
+                "b/x/".to_owned(),
+                Failure {
+                    count: 2,
+                    status: "1".to_owned(),
+                    stderr: "oops".to_owned(),
+                },
+            )]),
+        };
+        let extra = ExtraToml {
+            comment: Some("hello".to_owned()),
+            ..Default::default()
+        };
+        for kind in [StoreKind::Fs, StoreKind::Sqlite] {
+            let dir = tempfile::tempdir().unwrap();
+            let store = store::open(kind, dir.path().to_owned());
+            assert!(store.ids().is_empty());
+            assert_eq!(store.store(&results, &extra), 0);
+            assert_eq!(
+                store.store(&ResultFile::default(), &ExtraToml::default()),
+                1
+            );
+            store.insert(5, &results, &extra);
+            assert_eq!(store.store(&results, &extra), 6);
+            assert_eq!(store.ids(), vec![0, 1, 5, 6]);
+
+            let loaded = store.load_results(0);
+            assert_eq!(loaded.data, results.data);
+            assert_eq!(loaded.metrics, results.metrics);
+            assert_eq!(loaded.params, results.params);
+            assert_eq!(loaded.timestamps, results.timestamps);
+            assert_eq!(loaded.failures, results.failures);
+            assert_eq!(
+                store.try_load_extra(0).unwrap().comment.as_deref(),
+                Some("hello")
+            );
+            assert!(store.load_results(1).data.is_empty());
+            assert_eq!(store.try_load_extra(1).unwrap().comment, None);
+        }
+    }
+
+    #[test]
+    fn regression_window() {
+        assert_eq!(window_ends(&[], 5), None);
+        assert_eq!(window_ends(&[3], 5), None);
+        assert_eq!(window_ends(&[0, 1, 2], 5), Some((0, 2)));
+        assert_eq!(window_ends(&[0, 1, 4, 7, 8], 1), Some((7, 8)));
+        assert_eq!(window_ends(&[0, 1, 4, 7, 8], 3), Some((1, 8)));
+        assert_eq!(window_ends(&[0, 1, 4, 7, 8], 4), Some((0, 8)));
+    }
+
+    #[test]
+    fn version_mismatch() {
+        let extra = |v: Option<&str>| ExtraToml {
+            haste_version: v.map(str::to_owned),
+            ..Default::default()
+        };
+        let mismatch = |v1, v2| extra(v1).version_mismatch(&extra(v2));
+        assert_eq!(mismatch(Some("0.1.0"), Some("0.1.7")), None);
+        assert_eq!(
+            mismatch(Some("0.1.0"), Some("0.2.0")).as_deref(),
+            Some("0.1.0 vs. 0.2.0")
+        );
+        assert_eq!(mismatch(Some("1.2.0"), Some("1.9.3")), None);
+        assert!(mismatch(Some("1.2.0"), Some("2.0.0")).is_some());
+        // Datums from before versions were recorded can't be checked.
+        assert_eq!(mismatch(None, Some("2.0.0")), None);
+    }
+
+    #[test]
+    fn stat() {
+        use crate::stats::{self, Descriptive};
+        use crate::{OutputFormat, StatColumn, format_stats};
+        assert_eq!(stats::variance(&[1.]), None);
+        assert_eq!(stats::variance(&[1., 3.]), Some(2.));
+        assert_eq!(
+            stats::stddev(&[2., 4., 4., 4., 5., 5., 7., 9.]),
+            Some(2.138089935299395)
+        );
+        // Symmetric samples aren't skewed, while a long right tail is positively skewed.
+        assert_eq!(stats::skewness(&[1., 2., 3.]), Some(0.));
+        assert!(stats::skewness(&[1., 1., 1., 10.]).unwrap() > 1.);
+        assert_eq!(stats::skewness(&[1., 2.]), None);
+        assert_eq!(stats::skewness(&[3., 3., 3.]), None);
+
+        let rows = [
+            ("a/x/", Descriptive::from_samples(&[1., 2., 3., 4.])),
+            ("b,c/x/", Descriptive::from_samples(&[5.])),
+        ];
+        let columns = [StatColumn::N, StatColumn::Stddev, StatColumn::Iqr];
+        assert_eq!(
+            format_stats(&rows, &columns, OutputFormat::Csv),
+            "benchmark,n,stddev,iqr\na/x/,4,1.2909944487358056,1.5\n\"b,c/x/\",1,,0\n"
+        );
+        let table = format_stats(&rows, &columns, OutputFormat::Table);
+        assert!(table.lines().nth(2).unwrap().contains("n/a"), "{table}");
+        let json = format_stats(&rows, &columns, OutputFormat::Json);
+        let v = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+        assert_eq!(v[1]["benchmark"], "b,c/x/");
+        assert_eq!(v[1]["n"], 1);
+        assert!(v[1]["stddev"].is_null());
+    }
+
+    #[test]
+    fn report_wrap() {
+        use crate::report::wrap;
+        use crate::stats::geomean;
+        assert_eq!(wrap("aa bb cc dd", 8, 2), "  aa bb\n  cc dd\n");
+        // Over-long words get their own line rather than being broken.
+        assert_eq!(wrap("a abcdefghij b", 6, 0), "a\nabcdefghij\nb\n");
+        assert_eq!(wrap("  ", 10, 2), "");
+        assert!(wrap("± ± ±", 5, 0).lines().all(|l| l.chars().count() <= 5));
+        assert!((geomean(&[0.5, 2.]) - 1.).abs() < 1e-12);
+        assert!((geomean(&[1., 4.]) - 2.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn ks_test() {
+        use crate::compare::histogram_counts;
+        use crate::stats::{Descriptive, ks_test, quantile};
+        let a = [1., 2., 3., 4., 5.];
+        let b = [6., 7., 8., 9., 10.];
+        let (d, p) = ks_test(&a, &b);
+        assert_eq!(d, 1.);
+        assert!((p - 0.0038).abs() < 0.0001, "{p}");
+        assert_eq!(ks_test(&a, &a), (0., 1.));
+        let (d, p) = ks_test(&[1., 2., 3.], &[2., 3., 4.]);
+        assert!((d - 1. / 3.).abs() < 1e-12);
+        assert!(p > 0.5);
+        // Ties between the samples move both distribution functions together.
+        assert_eq!(ks_test(&[1., 1., 2.], &[1., 2., 2.]).0, 1. / 3.);
+
+        assert_eq!(quantile(&[1., 2., 3., 4.], 0.5), 2.5);
+        assert_eq!(quantile(&[1., 2., 3., 4.], 0.25), 1.75);
+        let s = Descriptive::from_samples(&[4., 1., 3., 2.]);
+        assert_eq!((s.min, s.median, s.max), (1., 2.5, 4.));
+        assert_eq!(s.mean, 2.5);
+
+        let (lo, width, counts) = histogram_counts(&a, &b);
+        assert_eq!((lo, width), (1., 0.9));
+        assert_eq!(counts.len(), 10);
+        assert_eq!(counts.iter().map(|c| c[0]).sum::<usize>(), 5);
+        assert_eq!(counts[9], [0, 1]);
+        assert_eq!(histogram_counts(&[2.], &[2., 2.]).2, vec![[1, 2]]);
+    }
+
+    #[test]
+    fn bench_summary() {
+        let results = ResultFile {
+            data: HashMap::from([
+                ("b/e1/".to_owned(), vec![1., 2., 3.]),
+                ("b/e2/".to_owned(), vec![4., 4., 4.]),
+                ("c/e1/x".to_owned(), vec![10., 30.]),
+                ("d/e2/".to_owned(), vec![5.]),
+            ]),
+            ..Default::default()
+        };
+        let suites = HashMap::from([
+            ("b/e1/".to_owned(), "s1".to_owned()),
+            ("b/e2/".to_owned(), "s1".to_owned()),
+            ("c/e1/x".to_owned(), "s1".to_owned()),
+            ("d/e2/".to_owned(), "s2".to_owned()),
+        ]);
+        let table = crate::summary::summary_table(&results, &suites, ConfidenceLevel::CL99, false);
+        let lines = table
+            .lines()
+            .map(|l| l.split_whitespace().collect::<String>())
+            .collect::<Vec<_>>();
+        assert_eq!(lines[0], "SuiteBenchmarke1(ms)e2(ms)");
+        assert!(lines[1].starts_with("s1b/2±"), "{}", lines[1]);
+        assert!(lines[1].ends_with("[1,3]4±0[4,4]"), "{}", lines[1]);
+        assert!(lines[2].starts_with("c/x20±") && lines[2].ends_with("[10,30]"));
+        assert!(lines[3].starts_with("s2d/5±"));
+    }
+
+    #[test]
+    fn histogram_rendering() {
+        use crate::histogram::render;
+        let samples = [1., 1.5, 2., 2., 2., 4., 5.];
+        assert_eq!(
+            render(&samples, 4, 2),
+            "  1.000 - 2.000  ####################             2\n  \
+             2.000 - 3.000  ##############################   3\n  \
+             3.000 - 4.000                                   0\n  \
+             4.000 - 5.000  ####################             2\n"
+        );
+        // Identical samples all go in one bin.
+        assert_eq!(
+            render(&[7., 7.], 10, 0),
+            "7.000 - 8.000  ##############################   2\n"
+        );
+        assert_eq!(render(&[], 10, 2), "  (no samples)\n");
+    }
+
+    #[test]
+    fn run_log_lines() {
+        use crate::log::{LogFormat, format_line};
+        let fields = [
+            ("key", "a/x/".to_owned()),
+            ("cmd", "sh -c \"x\"".to_owned()),
+            ("comment", String::new()),
+        ];
+        assert_eq!(
+            format_line(LogFormat::Text, 1_700_000_000_123, "pexec_start", &fields),
+            "[2023-11-14 22:13:20.123 UTC] pexec_start key=a/x/ cmd=\"sh -c \\\"x\\\"\" \
+             comment=\"\"\n"
+        );
+        let line = format_line(LogFormat::Json, 5, "pexec_start", &fields);
+        assert!(line.ends_with('\n'));
+        let v = serde_json::from_str::<serde_json::Value>(&line).unwrap();
+        assert_eq!(v["time_ms"], 5);
+        assert_eq!(v["event"], "pexec_start");
+        assert_eq!(v["cmd"], "sh -c \"x\"");
+    }
+
+    #[test]
+    fn pexec_timestamps() {
+        // Datums from before timestamps were recorded still load.
+        let old = toml::from_str::<ResultFile>("[data]\n\"a/x/\" = [1.0, 2.0]\n").unwrap();
+        assert!(old.timestamps.is_empty());
+        assert_eq!(old.summarise(ConfidenceLevel::CL99)["a/x/"].mean, 1.5);
+
+        let new = ResultFile {
+            data: HashMap::from([("a/x/".to_owned(), vec![1., 2.])]),
+            timestamps: HashMap::from([("a/x/".to_owned(), vec![1_700_000_000_123, 5])]),
+            ..Default::default()
+        };
+        let loaded = toml::from_str::<ResultFile>(&toml::to_string(&new).unwrap()).unwrap();
+        assert_eq!(loaded.timestamps, new.timestamps);
+    }
+
+    #[test]
+    fn datum_ids() {
+        use crate::store::{self, StoreKind};
+        let results = ResultFile::default();
+        let extra = ExtraToml::default();
+        for kind in [StoreKind::Fs, StoreKind::Sqlite] {
+            let dir = tempfile::tempdir().unwrap();
+            let store = store::open(kind, dir.path().to_owned());
+            // A store with gaps, as left by deletions.
+            for id in [7, 0, 3] {
+                store.insert(id, &results, &extra);
+            }
+            assert_eq!(store.ids(), vec![0, 3, 7]);
+            assert!(store.contains(3) && !store.contains(4));
+            assert_eq!(store.store(&results, &extra), 8);
+
+            // Deleted IDs, even the highest, aren't reused.
+            store.delete(8).unwrap();
+            store.delete(3).unwrap();
+            assert!(store.delete(3).is_err());
+            assert_eq!(store.ids(), vec![0, 7]);
+            assert_eq!(store.store(&results, &extra), 9);
+
+            // Two stores for the same directory, as two haste processes would have, never
+            // allocate the same ID.
+            let other = store::open(kind, dir.path().to_owned());
+            let a = store.store(&results, &extra);
+            let b = other.store(&results, &extra);
+            assert_eq!((a, b), (10, 11));
+            assert_eq!(store.ids(), vec![0, 7, 9, 10, 11]);
+        }
+
+        // An ID claimed by another process which hasn't written its datum yet isn't reused.
+        let dir = tempfile::tempdir().unwrap();
+        let store = store::open(StoreKind::Fs, dir.path().to_owned());
+        assert_eq!(store.store(&results, &extra), 0);
+        fs::write(dir.path().join("last_id"), "0\n").unwrap();
+        fs::remove_dir_all(dir.path().join("0")).unwrap();
+        fs::create_dir(dir.path().join("1")).unwrap();
+        assert_eq!(store.store(&results, &extra), 2);
+    }
+
+    /// An app keeping its state in `dir`.
+    fn test_app(dir: &std::path::Path, kind: crate::store::StoreKind, seed: u64) -> App {
+        App {
+            state_dir: dir.to_owned(),
+            config_file: PathBuf::from(DEFAULT_CONFIG_FILE),
+            store: crate::store::open(kind, dir.to_owned()),
+            store_kind: kind,
+            cache: crate::cache::SummaryCache::new(dir),
+            no_cache: false,
+            seed,
+            rng: std::cell::RefCell::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+
+    #[test]
+    fn seeded_reports() {
+        use crate::store::StoreKind;
+        let dir = tempfile::tempdir().unwrap();
+        let mut rng = StdRng::seed_from_u64(1);
+        let data = (0..5)
+            .map(|i| {
+                let samples = (0..20).map(|_| rng.random_range(0.0..100.)).collect();
+                (format!("b{i}/e/"), samples)
+            })
+            .collect::<HashMap<_, Vec<f64>>>();
+        let results = ResultFile {
+            data,
+            ..Default::default()
+        };
+        let report = |seed| {
+            let app = test_app(dir.path(), StoreKind::Fs, seed);
+            if app.store.ids().is_empty() {
+                app.store.store(&results, &ExtraToml::default());
+                app.store.store(&results, &ExtraToml::default());
+            }
+            app.report(0, 1, ConfidenceLevel::CL99, DEFAULT_MIN_SAMPLES)
+        };
+        let r1 = report(42);
+        assert_eq!(r1, report(42));
+        assert!(r1.contains("random seed 42"));
+        assert_ne!(r1, report(43));
+
+        // Every seed that can be given can be stored.
+        let extra = ExtraToml {
+            seed: Some(super::MAX_SEED),
+            ..Default::default()
+        };
+        assert!(toml::to_string(&extra).is_ok());
+    }
+
+    #[test]
+    fn cross_store_refs() {
+        use crate::store::StoreKind;
+        assert_eq!("3".parse::<DatumRef>(), Ok(DatumRef::local(3)));
+        let r = "a:b/.haste:12".parse::<DatumRef>().unwrap();
+        assert_eq!(r.state_dir, Some(PathBuf::from("a:b/.haste")));
+        assert_eq!(r.id, 12);
+        assert_eq!(r.name(true), "a:b/.haste:Datum12");
+        assert_eq!(r.name(false), "Datum12");
+        assert_eq!(DatumRef::local(3).name(true), ".haste:Datum3");
+        assert!(":3".parse::<DatumRef>().is_err());
+        assert!("x:y".parse::<DatumRef>().is_err());
+
+        let results = |ms| ResultFile {
+            data: HashMap::from([("b/e/".to_owned(), vec![ms])]),
+            ..Default::default()
+        };
+        let here = tempfile::tempdir().unwrap();
+        let there = tempfile::tempdir().unwrap();
+        let app = test_app(here.path(), StoreKind::Fs, 0);
+        app.store.store(&results(1.), &ExtraToml::default());
+        let other = crate::store::open(StoreKind::Sqlite, there.path().to_owned());
+        let extra = ExtraToml {
+            comment: Some("server A".to_owned()),
+            ..Default::default()
+        };
+        other.store(&results(2.), &extra);
+
+        let remote = DatumRef {
+            state_dir: Some(there.path().to_owned()),
+            id: 0,
+        };
+        let (r, e) = app.load_ref(&remote).unwrap();
+        assert_eq!(r.data["b/e/"], vec![2.]);
+        assert_eq!(e.comment.as_deref(), Some("server A"));
+        assert_eq!(
+            app.load_ref(&DatumRef::local(0)).unwrap().0.data["b/e/"],
+            vec![1.]
+        );
+        let missing = DatumRef { id: 1, ..remote };
+        assert!(
+            app.load_ref(&missing)
+                .is_err_and(|e| e.contains("no such datum"))
+        );
+    }
+
+    #[test]
+    fn summary_cache() {
+        use crate::store::StoreKind;
+        use std::time::Instant;
+        let conf = ConfidenceLevel::CL99;
+        let mut rng = StdRng::seed_from_u64(0);
+        // A datum the size of a big suite: 60 benchmarks of 200 samples each.
+        let data = (0..60)
+            .map(|i| {
+                let samples = (0..200).map(|_| rng.random_range(0.0..100.)).collect();
+                (format!("b{i}/e/"), samples)
+            })
+            .collect::<HashMap<_, Vec<f64>>>();
+        let results = ResultFile {
+            data,
+            ..Default::default()
+        };
+        for kind in [StoreKind::Fs, StoreKind::Sqlite] {
+            let dir = tempfile::tempdir().unwrap();
+            let mut app = test_app(dir.path(), kind, 0);
+            let id = app.store.store(&results, &ExtraToml::default());
+
+            let start = Instant::now();
+            let first = app.load_summary(id, conf);
+            let uncached = start.elapsed();
+            let start = Instant::now();
+            let second = app.load_summary(id, conf);
+            let cached = start.elapsed();
+            assert!(dir.path().join("cache").join("0.toml").exists());
+            assert!(
+                cached * 2 < uncached,
+                "{kind:?}: {cached:?} vs. {uncached:?}"
+            );
+            assert_eq!(first.len(), second.len());
+            for (k, s) in &first {
+                assert_eq!(
+                    (s.mean, s.ci, s.n),
+                    (second[k].mean, second[k].ci, second[k].n)
+                );
+            }
+            // Other confidence levels are cached alongside.
+            assert_ne!(
+                app.load_summary(id, ConfidenceLevel(90.))["b0/e/"].ci,
+                first["b0/e/"].ci
+            );
+            assert_eq!(app.load_summary(id, conf)["b0/e/"].ci, first["b0/e/"].ci);
+
+            // A stale cache entry is never used, even with --no-cache off.
+            let bogus = HashMap::from([("b0/e/".to_owned(), SummaryStats::new(1., 1., 1))]);
+            app.cache.put(id, "stale", conf, &bogus);
+            assert_eq!(app.load_summary(id, conf).len(), 60);
+            // Whereas a matching one is, unless --no-cache is given.
+            let fingerprint = app.store.fingerprint(id);
+            app.cache.put(id, &fingerprint, conf, &bogus);
+            assert_eq!(app.load_summary(id, conf).len(), 1);
+            app.no_cache = true;
+            assert_eq!(app.load_summary(id, conf).len(), 60);
+        }
+    }
+
+    #[test]
+    fn remote_executors() {
+        let config_content = r#"
+        proc_execs = 1
+        inproc_iters = 1
+        remote = "bench@quiet"
+
+        [executors]
+        a = "/usr/bin/python3"
+        b = { path = "/usr/bin/pypy", remote = "other" }
+
+        [suites]
+        "#;
+        let config: crate::config::Config = toml::from_str(config_content).unwrap();
+        assert_eq!(config.remote_for("a"), Some("bench@quiet"));
+        assert_eq!(config.remote_for("b"), Some("other"));
+        assert!(config.validate().is_ok());
+        let with_perf = format!("perf = true\n{config_content}");
+        let config: crate::config::Config = toml::from_str(&with_perf).unwrap();
+        assert!(config.validate().is_err());
+
+        // Run the wrapper script locally, as the remote host would.
+        let dir = tempfile::tempdir().unwrap();
+        let harness = dir.path().join("it's a harness.sh");
+        fs::write(
+            &harness,
+            "#!/bin/sh\necho \"$2 $FOO\" >&2; echo PEXEC_WALLCLOCK_MS=12.5 > \"$1\"",
+        )
+        .unwrap();
+        fs::set_permissions(&harness, fs::Permissions::from_mode(0o755)).unwrap();
+        let script = crate::remote::script(
+            dir.path(),
+            &[("FOO", "a 'b' c")],
+            harness.to_str().unwrap(),
+            &["bench".to_owned()],
+            true,
+        );
+        let output = Command::new("sh").arg("-c").arg(&script).output().unwrap();
+        assert!(output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let (own, trailer) = crate::remote::split_trailer(&stderr).unwrap();
+        assert_eq!(own, "bench a 'b' c\n");
+        assert_eq!(trailer.harness_out, "PEXEC_WALLCLOCK_MS=12.5\n");
+        assert!(trailer.elapsed_ms >= 0.);
+        assert!(crate::remote::split_trailer("no trailer").is_err());
+    }
+
+    #[test]
+    fn retain_benchmarks() {
+        let config_content = r#"
+        proc_execs = 1
+        inproc_iters = 1
+        executors = { sh = "shell" }
+
+        [suites.s1]
+        dir = "."
+        benchmarks = { a = { command = "true" }, b = { command = "true" } }
+
+        [suites.s2]
+        dir = "."
+        benchmarks = { a = { command = "true" }, c = { command = "true" } }
+        "#;
+        let mut config: crate::config::Config = toml::from_str(config_content).unwrap();
+        config.retain_benchmarks(&[]).unwrap();
+        assert_eq!(config.suites["s1"].benchmarks.len(), 2);
+        assert_eq!(
+            config.retain_benchmarks(&["a".to_owned(), "d".to_owned()]),
+            Err("no benchmark named d".to_owned())
+        );
+        config.retain_benchmarks(&["a".to_owned()]).unwrap();
+        assert!(config.suites.values().all(|s| s.benchmarks.len() == 1));
+        assert!(
+            config
+                .suites
+                .values()
+                .all(|s| s.benchmarks.contains_key("a"))
+        );
+    }
+
+    #[test]
+    fn for_executor() {
+        let rf = ResultFile {
+            data: HashMap::from([
+                ("fib/base/".to_owned(), vec![1.]),
+                ("fib/new/".to_owned(), vec![2.]),
+                ("sort/new/10-x".to_owned(), vec![3.]),
+            ]),
+            ..Default::default()
+        };
+        let new = rf.for_executor("new");
+        assert_eq!(
+            new.data,
+            HashMap::from([
+                ("fib/".to_owned(), vec![2.]),
+                ("sort/10-x".to_owned(), vec![3.])
+            ])
+        );
+        assert_eq!(rf.for_executor("base").data.len(), 1);
+        assert!(rf.for_executor("other").data.is_empty());
+    }
+
+    #[test]
+    fn pathological_datums() {
+        let rf: ResultFile = toml::from_str(
+            r#"
+            [data]
+            "a/x/" = [1.0, 2.0]
+            "b/x/" = [1.0, nan]
+
+            [metrics.cycles]
+            "a/x/" = [inf]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            rf.validate(),
+            Err("b/x/: time sample 1 is NaN (is the datum corrupt?)".to_owned())
+        );
+
+        let conf = ConfidenceLevel::default();
+        let zero = HashMap::from([
+            ("zero".to_owned(), SummaryStats::new(0., 0., 10)),
+            ("both".to_owned(), SummaryStats::new(0., 0., 10)),
+            ("empty".to_owned(), SummaryStats::from_samples(&[], conf)),
+            ("normal".to_owned(), SummaryStats::new(10., 1., 10)),
+        ]);
+        let other = HashMap::from([
+            ("zero".to_owned(), SummaryStats::new(5., 1., 10)),
+            ("both".to_owned(), SummaryStats::new(0., 0., 10)),
+            ("empty".to_owned(), SummaryStats::new(5., 1., 10)),
+            ("normal".to_owned(), SummaryStats::new(20., 1., 10)),
+        ]);
+        let (table, regressions) = diff_table(
+            &zero,
+            &other,
+            "d1",
+            "d2",
+            false,
+            DEFAULT_MIN_SAMPLES,
+            "time",
+        );
+        let table = table.to_string();
+        let row = |k: &str| {
+            table
+                .lines()
+                .find(|l| l.trim_start().starts_with(k))
+                .unwrap()
+        };
+        assert!(row("zero").contains('∞'));
+        assert!(row("zero").contains("slower"));
+        assert!(row("both").contains("n/a"));
+        assert!(row("normal").contains("2.00"));
+        assert_eq!(regressions, 2);
+    }
+
+    #[test]
+    fn multi_metric_diff() {
+        use crate::multidiff::metric_summaries;
+        use crate::{DiffOptions, Statistic};
+        let results = |ms: Vec<f64>, instrs: Option<Vec<f64>>| ResultFile {
+            data: HashMap::from([("a/e/".to_owned(), ms.clone()), ("b/e/".to_owned(), ms)]),
+            metrics: instrs
+                .map(|i| {
+                    HashMap::from([(
+                        "instructions".to_owned(),
+                        HashMap::from([("a/e/".to_owned(), i)]),
+                    )])
+                })
+                .unwrap_or_default(),
+            ..Default::default()
+        };
+        let opts = DiffOptions {
+            min_samples: 1,
+            env_warnings: false,
+            ..Default::default()
+        };
+        let r1 = results(vec![1., 2., 3.], Some(vec![10., 10.]));
+        let r2 = results(vec![4., 5., 6.], Some(vec![20., 20.]));
+        let names = ["Datum1", "Datum2"];
+        let mut rng = StdRng::seed_from_u64(0);
+        let (t1, t2) = metric_summaries(&r1, &r2, names, "time", &opts, &mut rng)
+            .unwrap()
+            .unwrap();
+        assert_eq!((t1.len(), t2["a/e/"].mean), (2, 5.));
+        // Each metric has its own intervals, and only the benchmarks it was collected for.
+        let (i1, i2) = metric_summaries(&r1, &r2, names, "instructions", &opts, &mut rng)
+            .unwrap()
+            .unwrap();
+        assert_eq!(i1.keys().collect::<Vec<_>>(), ["a/e/"]);
+        assert_eq!((i1["a/e/"].ci, i2["a/e/"].mean), (0., 20.));
+        // A metric missing from either side isn't an error.
+        let r3 = results(vec![1., 2.], None);
+        assert!(
+            metric_summaries(&r1, &r3, names, "instructions", &opts, &mut rng)
+                .unwrap()
+                .is_none()
+        );
+        let opts = DiffOptions {
+            stat: Statistic::TrimmedMean(50.),
+            ..opts
+        };
+        assert!(metric_summaries(&r1, &r3, names, "time", &opts, &mut rng).is_err());
+    }
+
+    #[test]
+    fn trimmed_mean() {
+        use crate::{Statistic, stats};
+        // 10% of 20 samples is 2 from each end.
+        let samples = (1..=20).map(f64::from).rev().collect::<Vec<_>>();
+        let trimmed = stats::trim(&samples, 10.);
+        assert_eq!(trimmed, (3..=18).map(f64::from).collect::<Vec<_>>());
+        // A single outlier in 10 samples is removed by 10%, and 19% of 10 still rounds down to 1,
+        // whereas 5% rounds down to nothing.
+        let outlier = [1., 1., 1., 1., 1., 1., 1., 1., 2., 100.];
+        assert_eq!(stats::mean(&outlier), 11.);
+        assert_eq!(stats::mean(&stats::trim(&outlier, 10.)), 1.125);
+        assert_eq!(stats::trim(&outlier, 19.), stats::trim(&outlier, 10.));
+        assert_eq!(stats::mean(&stats::trim(&outlier, 5.)), 11.);
+        // Too few samples for anything to be trimmed.
+        assert_eq!(stats::trim(&[3., 1.], 10.), [1., 3.]);
+
+        assert_eq!("mean".parse(), Ok(Statistic::Mean));
+        assert_eq!("trimmed-mean".parse(), Ok(Statistic::TrimmedMean(10.)));
+        assert_eq!("trimmed-mean:25".parse(), Ok(Statistic::TrimmedMean(25.)));
+        assert!("trimmed-mean:60".parse::<Statistic>().is_err());
+        assert!("median".parse::<Statistic>().is_err());
+        let s = Statistic::TrimmedMean(2.5);
+        assert_eq!(s.to_string().parse(), Ok(s));
+
+        let mut data = ResultFile {
+            data: HashMap::from([
+                ("a/e/".to_owned(), outlier.to_vec()),
+                ("b/e/".to_owned(), vec![1., 2.]),
+            ]),
+            ..Default::default()
+        };
+        assert!(data.clone().trim(10.).is_ok());
+        let e = data.trim(50.).unwrap_err();
+        assert_eq!(
+            e,
+            "a/e/: trimming 50% from each end of its 10 samples leaves none"
+        );
+    }
+
+    #[test]
+    fn min_samples() {
+        let data1 = HashMap::from([
+            ("few".to_owned(), SummaryStats::new(10., 0.1, 3)),
+            ("uneven".to_owned(), SummaryStats::new(10., 0.1, 10)),
+        ]);
+        let data2 = HashMap::from([
+            ("few".to_owned(), SummaryStats::new(20., 0.1, 10)),
+            ("uneven".to_owned(), SummaryStats::new(20., 0.1, 7)),
+        ]);
+        let rows = diff_rows(&data1, &data2, DEFAULT_MIN_SAMPLES, false);
+        let few = rows.iter().find(|r| r.key == "few").unwrap();
+        assert!(few.too_few && !few.significant);
+        assert_eq!(few.summary(), "indistinguishable (too few samples)");
+        assert_eq!(few.samples(), "3/10");
+        let uneven = rows.iter().find(|r| r.key == "uneven").unwrap();
+        assert!(uneven.significant && uneven.samples_suspect());
+        assert_eq!(uneven.samples(), "10/7");
+
+        // With a lower minimum, a real difference shows through.
+        let rows = diff_rows(&data1, &data2, 3, false);
+        assert!(rows.iter().all(|r| r.significant));
+        let (table, regressions) = diff_table(&data1, &data2, "d1", "d2", false, 3, "time");
+        assert_eq!(regressions, 2);
+        assert!(table.to_string().contains("3/10"));
+    }
+
+    #[test]
+    fn rank() {
+        use crate::rank::{rank_benchmark, rank_overall};
+        use crate::split_key;
+        use std::collections::BTreeMap;
+        assert_eq!(
+            split_key("fib/jit/10-x"),
+            Some(("fib/10-x".to_owned(), "jit"))
+        );
+        assert_eq!(split_key("nonsense"), None);
+
+        let fast = SummaryStats::new(10., 1., 10);
+        let close = SummaryStats::new(11., 1., 10);
+        let slow = SummaryStats::new(20., 1., 10);
+        let placings = rank_benchmark(&[("c", &slow), ("b", &close), ("a", &fast)]);
+        let ranks = placings
+            .iter()
+            .map(|p| (p.executor.as_str(), p.rank, p.joint))
+            .collect::<Vec<_>>();
+        // `a` and `b` overlap, so tie for first, and there is no second.
+        assert_eq!(ranks, [("a", 1, true), ("b", 1, true), ("c", 3, false)]);
+
+        let benchmarks = BTreeMap::from([
+            ("x/".to_owned(), vec![("a", &fast), ("b", &slow)]),
+            ("y/".to_owned(), vec![("a", &slow), ("b", &fast)]),
+            ("z/".to_owned(), vec![("a", &fast), ("b", &close)]),
+            // Not run by every executor, so not counted.
+            ("only/".to_owned(), vec![("a", &slow)]),
+        ]);
+        let (overall, counted) = rank_overall(&benchmarks, &["a", "b"]);
+        assert_eq!(counted, 3);
+        assert_eq!(overall[0].executor, "a");
+        assert!((overall[0].value - 2f64.cbrt()).abs() < 1e-12);
+        assert!((overall[1].value - (2. * 1.1f64).cbrt()).abs() < 1e-12);
+        assert_eq!((overall[1].rank, overall[1].joint), (2, false));
+    }
+
+    #[test]
+    fn diff_legend() {
+        use crate::diff_legend;
+        let conf = ConfidenceLevel::default();
+        let legend = diff_legend("Datum3", "Datum4", "time", conf, 5, true);
+        assert!(legend.starts_with("green/red = significantly faster/slower at 99% confidence"));
+        assert!(legend.contains("ratio = Datum4 / Datum3 (time)"));
+        assert!(legend.contains("fewer than 5"));
+        let legend = diff_legend("lua", "luajit", "instructions", conf, 3, false);
+        assert!(!legend.contains("green"));
+        assert!(legend.contains("ratio = luajit / lua (instructions)"));
+    }
+
+    #[test]
+    fn num_format() {
+        let render = |fs: &[f64], precision, signed, thousands| {
+            let fmt = NumFormat::new(fs, precision, signed, thousands);
+            fs.iter().map(|f| fmt.format(*f)).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            render(&[1., 22.4, 333.6], 0, false, false),
+            ["  1", " 22", "334"]
+        );
+        // Decimal places count towards the width.
+        assert_eq!(render(&[0.98, 1.234], 2, false, false), ["0.98", "1.23"]);
+        assert_eq!(render(&[-1.5, 10.], 2, false, false), ["-1.50", "10.00"]);
+        assert_eq!(render(&[-1.5, 10.], 1, true, false), [" -1.5", "+10.0"]);
+        // Sub-millisecond values get enough precision not to be 0.
+        assert_eq!(
+            render(&[0.034, 0.0012], 0, false, false),
+            ["0.034", "0.001"]
+        );
+        assert_eq!(render(&[0.5, 0.], 0, false, false), ["0.50", "0.00"]);
+        assert_eq!(render(&[0.5, 2.], 0, false, false), ["0", "2"]);
+        assert_eq!(
+            render(&[999., 1234.6, -1234567.], 0, false, true),
+            ["       999", "     1,235", "-1,234,567"]
+        );
+        assert_eq!(
+            render(&[1234.5, -12.], 1, true, true),
+            ["+1,234.5", "   -12.0"]
+        );
+        assert_eq!(render(&[f64::NAN, 1.], 0, false, true), ["NaN", "  1"]);
+        assert_eq!(render(&[], 0, false, false), Vec::<String>::new());
+    }
+
+    #[test]
+    fn benchmark_dirs() {
+        use std::path::Path;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("data/small")).unwrap();
+        let config_content = format!(
+            r#"
+            proc_execs = 1
+            inproc_iters = 1
+            executors = {{ e = "/bin/e" }}
+
+            [suites.s]
+            dir = "{}"
+            harness = "./harness.sh"
+
+            [suites.s.benchmarks]
+            top = {{}}
+            nested = {{ dir = "data/small" }}
+            absolute = {{ dir = "/tmp" }}
+            "#,
+            dir.path().display()
+        );
+        let config: crate::config::Config = toml::from_str(&config_content).unwrap();
+        assert!(config.validate().is_ok());
+        let suite = &config.suites["s"];
+        let harness = Path::new("./harness.sh");
+        let bench = |name: &str| &suite.benchmarks[name];
+        assert_eq!(suite.dir_for(bench("top")), dir.path());
+        assert_eq!(
+            suite.dir_for(bench("nested")),
+            dir.path().join("data/small")
+        );
+        assert_eq!(suite.dir_for(bench("absolute")), Path::new("/tmp"));
+        assert_eq!(suite.resolve_harness(harness, bench("top")), harness);
+        assert_eq!(
+            suite.resolve_harness(harness, bench("nested")),
+            Path::new("../.././harness.sh")
+        );
+        assert_eq!(
+            suite.resolve_harness(harness, bench("absolute")),
+            dir.path().join("./harness.sh")
+        );
+        // Harnesses found in `PATH` are left alone.
+        assert_eq!(
+            suite.resolve_harness(Path::new("harness"), bench("nested")),
+            Path::new("harness")
+        );
+
+        let missing = config_content.replace("data/small", "data/big");
+        let config: crate::config::Config = toml::from_str(&missing).unwrap();
+        assert!(config.validate().unwrap_err().contains("does not exist"));
+    }
+
+    #[test]
+    fn harness_env() {
+        let dir = tempfile::tempdir().unwrap();
+        let harness = dir.path().join("harness.sh");
+        fs::write(
+            &harness,
+            "#!/bin/sh\n\
+             echo \"$HASTE_EXECUTOR $HASTE_BENCHMARK $HASTE_PEXEC $HASTE_INPROC_ITERS\" >> log\n\
+             echo PEXEC_WALLCLOCK_MS=1 > \"$1\"\n",
+        )
+        .unwrap();
+        fs::set_permissions(&harness, fs::Permissions::from_mode(0o755)).unwrap();
+        let config_content = format!(
+            r#"
+            proc_execs = 2
+            inproc_iters = 7
+            executors = {{ myexec = "/bin/true" }}
+
+            [suites.s]
+            dir = "{}"
+            harness = "./harness.sh"
+            benchmarks = {{ fib = {{}} }}
+            "#,
+            dir.path().display()
+        );
+        let config: crate::config::Config = toml::from_str(&config_content).unwrap();
+        runner::run(&config, &runner::RunOptions::default());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("log")).unwrap(),
+            "myexec fib 0 7\nmyexec fib 1 7\n"
+        );
+    }
+
+    #[test]
+    fn suite_extra_args() {
+        let dir = tempfile::tempdir().unwrap();
+        let harness = dir.path().join("harness.sh");
+        // Log every argument after the output file, one per line.
+        fs::write(
+            &harness,
+            "#!/bin/sh\n\
+             out=$1; shift\n\
+             printf '%s\\n' \"$@\" >> log\n\
+             echo PEXEC_WALLCLOCK_MS=1 > \"$out\"\n",
+        )
+        .unwrap();
+        fs::set_permissions(&harness, fs::Permissions::from_mode(0o755)).unwrap();
+        let config_content = |suite_args: &str| {
+            format!(
+                r#"
+                proc_execs = 1
+                inproc_iters = 3
+                executors = {{ e = "/bin/true" }}
+
+                [suites.s]
+                dir = "{}"
+                harness = "./harness.sh"
+                extra_args = {suite_args}
+                benchmarks = {{ fib = {{ extra_args = ["30"] }} }}
+                "#,
+                dir.path().display()
+            )
+        };
+        let config: crate::config::Config =
+            toml::from_str(&config_content(r#"["--warmup", "3", "--json"]"#)).unwrap();
+        let outcome = runner::run(&config, &runner::RunOptions::default());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("log")).unwrap(),
+            "/bin/true\nfib\n3\n--warmup\n3\n--json\n30\n"
+        );
+        // Suite arguments don't change the key, but they are a parameter.
+        let r1 = outcome.results;
+        assert_eq!(
+            r1.params["fib/e/30"].suite_args,
+            ["--warmup", "3", "--json"]
+        );
+
+        let config: crate::config::Config = toml::from_str(&config_content("[]")).unwrap();
+        let r2 = runner::run(&config, &runner::RunOptions::default()).results;
+        assert!(
+            r1.same_dims(&r2, &HashMap::new(), &HashMap::new(), true)
+                .is_ok()
+        );
+        assert_eq!(
+            r1.param_mismatches(&r2, true),
+            ["fib/e/30: suite extra_args [\"--warmup\", \"3\", \"--json\"] vs. []"]
+        );
+    }
+
+    #[test]
+    fn iters_scale() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_content = |scale: &str| {
+            format!(
+                r#"
+                proc_execs = 1
+                inproc_iters = 10
+                executors = {{ fast = "/bin/sh", slow = {{ path = "/bin/sh", iters_scale = {scale} }} }}
+
+                [suites.s]
+                dir = "{}"
+                benchmarks = {{ b = {{ command = "-c true" }} }}
+                "#,
+                dir.path().display()
+            )
+        };
+        let config: crate::config::Config = toml::from_str(&config_content("0.26")).unwrap();
+        assert_eq!(config.inproc_iters_for("fast"), 10);
+        assert_eq!(config.inproc_iters_for("slow"), 3);
+        let tiny: crate::config::Config = toml::from_str(&config_content("0.001")).unwrap();
+        assert_eq!(tiny.inproc_iters_for("slow"), 1);
+        assert!(toml::from_str::<crate::config::Config>(&config_content("0")).is_err());
+
+        let results = runner::run(&config, &runner::RunOptions::default()).results;
+        assert_eq!(results.params["b/fast/"].inproc_iters, 10);
+        assert_eq!(results.params["b/slow/"].inproc_iters, 3);
+        // Datums run with different scales can't be compared without --force.
+        let other: crate::config::Config = toml::from_str(&config_content("1")).unwrap();
+        let other = runner::run(&other, &runner::RunOptions::default()).results;
+        assert_eq!(
+            results.param_mismatches(&other, true),
+            ["b/slow/: inproc_iters 3 vs. 10"]
+        );
+    }
+
+    #[test]
+    fn key_collisions() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = |args1: &str, args2: &str| {
+            let content = format!(
+                r#"
+                proc_execs = 1
+                inproc_iters = 1
+                executors = {{ e = "/bin/sh" }}
+
+                [suites.s1]
+                dir = "{0}"
+                harness = "h"
+                benchmarks = {{ b = {{ extra_args = {args1} }} }}
+
+                [suites.s2]
+                dir = "{0}"
+                harness = "h"
+                benchmarks = {{ b = {{ extra_args = {args2} }} }}
+                "#,
+                dir.path().display()
+            );
+            toml::from_str::<crate::config::Config>(&content).unwrap()
+        };
+        let e = runner::check_keys(&config(r#"["a-b"]"#, r#"["a", "b"]"#)).unwrap_err();
+        assert!(e.contains("\n  b/e/a-b:\n"), "{e}");
+        assert!(e.contains(r#"suite s1: benchmark b with executor e and extra_args ["a-b"]"#));
+        assert!(e.contains(r#"suite s2: benchmark b with executor e and extra_args ["a", "b"]"#));
+        assert!(runner::check_keys(&config("[]", r#"[""]"#)).is_err());
+        assert!(runner::check_keys(&config(r#"["a"]"#, r#"["b"]"#)).is_ok());
+    }
+
+    #[test]
+    fn keep_going() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_content = |cmd: &str| {
+            format!(
+                r#"
+                proc_execs = 3
+                inproc_iters = 1
+                executors = {{ sh = "shell" }}
+
+                [suites.s]
+                dir = "{}"
+                benchmarks = {{ ok = {{ command = "true" }}, bad = {{ command = "{cmd}" }} }}
+                "#,
+                dir.path().display()
+            )
+        };
+        let opts = runner::RunOptions {
+            keep_going: true,
+            ..Default::default()
+        };
+        let config: crate::config::Config =
+            toml::from_str(&config_content("seq 30 >&2; exit 3")).unwrap();
+        let failed = runner::run(&config, &opts).results;
+        assert_eq!(failed.data["ok/sh/"].len(), 3);
+        assert!(!failed.data.contains_key("bad/sh/"));
+        let f = &failed.failures["bad/sh/"];
+        assert_eq!((f.count, f.status.as_str()), (3, "3"));
+        // Only the end of stderr is kept.
+        assert!(f.stderr.starts_with("...\n11\n") && f.stderr.ends_with("\n30"));
+
+        // Failed benchmarks don't stop the rest being compared.
+        let config: crate::config::Config = toml::from_str(&config_content("true")).unwrap();
+        let mut passed = runner::run(&config, &opts).results;
+        assert!(passed.failures.is_empty());
+        let mut failed2 = failed.clone();
+        assert!(
+            failed2
+                .same_dims(&passed, &HashMap::new(), &HashMap::new(), true)
+                .is_ok()
+        );
+        assert_eq!(failed2.remove_failed(&mut passed), ["bad/sh/"]);
+        assert_eq!(passed.data.keys().collect::<Vec<_>>(), ["ok/sh/"]);
+    }
+
+    #[test]
+    fn paired_comparison() {
+        use crate::paired::{paired_differences, paired_rows};
+        // Both executors drift a lot together, hiding a small constant difference from an
+        // unpaired comparison.
+        let drift = (0..30).map(|i| f64::from(i % 7) * 10.).collect::<Vec<_>>();
+        let base = drift.iter().map(|d| 100. + d).collect::<Vec<_>>();
+        let new = drift.iter().map(|d| 101. + d).collect::<Vec<_>>();
+        let rounds = (0..30).collect::<Vec<_>>();
+        let data = ResultFile {
+            data: HashMap::from([
+                ("b/x/".to_owned(), base.clone()),
+                ("b/y/".to_owned(), new.clone()),
+            ]),
+            ..Default::default()
+        };
+        let mut interleaving = Interleaving {
+            executors: vec!["x".to_owned(), "y".to_owned()],
+            order: Vec::new(),
+            pairs: HashMap::new(),
+        };
+        assert!(
+            paired_rows(
+                &data,
+                Some(&interleaving),
+                "x",
+                "y",
+                ConfidenceLevel::CL99,
+                5
+            )
+            .is_err()
+        );
+        interleaving.pairs = HashMap::from([
+            ("b/x/".to_owned(), rounds.clone()),
+            ("b/y/".to_owned(), rounds.clone()),
+        ]);
+
+        let stats_x = data.for_executor("x").summarise(ConfidenceLevel::CL99);
+        let stats_y = data.for_executor("y").summarise(ConfidenceLevel::CL99);
+        let unpaired = diff_rows(&stats_x, &stats_y, DEFAULT_MIN_SAMPLES, false);
+        assert!(!unpaired[0].significant);
+        let rows = paired_rows(
+            &data,
+            Some(&interleaving),
+            "x",
+            "y",
+            ConfidenceLevel::CL99,
+            5,
+        )
+        .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert!(rows[0].is_regression());
+        assert!((rows[0].diff.mean - 1.).abs() < 1e-9);
+
+        // A failed pexec leaves its partner unpaired.
+        assert_eq!(
+            paired_differences(&base[1..], &rounds[1..], &new, &rounds),
+            Err("1 pexecs have no partner (did some of them fail?)".to_owned())
+        );
+        assert!(paired_differences(&base, &rounds[1..], &new, &rounds).is_err());
+    }
+
+    #[test]
+    fn missing_executors() {
+        let config_content = r#"
+        proc_execs = 1
+        inproc_iters = 1
+
+        [executors]
+        sh = "/bin/sh"
+        bare = "sh"
+        shell = "shell"
+        gone = "/nonexistent/gone"
+        optional = { path = "/nonexistent/optional", allow_missing = true }
+        unbuilt = { build = { command = "make", artifact = "nonexistent" } }
+        far = { path = "/nonexistent/far", remote = "elsewhere" }
+
+        [suites.s]
+        dir = "."
+        benchmarks = { a = { command = "true" } }
+        "#;
+        let config: crate::config::Config = toml::from_str(config_content).unwrap();
+        assert!(config.executors["optional"].allow_missing);
+        let names = |check_built| {
+            config
+                .missing_executors(check_built)
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(false), ["gone", "optional"]);
+        assert_eq!(names(true), ["gone", "optional", "unbuilt"]);
+        assert_eq!(
+            config.missing_executors(false)[0].1,
+            PathBuf::from("/nonexistent/gone")
+        );
+    }
+
+    #[test]
+    fn executor_restrictions() {
+        use crate::config::Config;
+        let dir = tempfile::tempdir().unwrap();
+        let config_content = format!(
+            r#"
+            proc_execs = 2
+            inproc_iters = 1
+            executors = {{ jit = "shell", interp = "shell" }}
+
+            [suites.s]
+            dir = "{}"
+            benchmarks.all = {{ command = "true" }}
+            benchmarks.traces = {{ command = "true", executors = ["jit"] }}
+            benchmarks.slow = {{ command = "true", exclude_executors = ["jit"] }}
+            "#,
+            dir.path().display()
+        );
+        let config: Config = toml::from_str(&config_content).unwrap();
+        assert!(config.validate().is_ok());
+        let outcome = runner::run(&config, &runner::RunOptions::default());
+        let mut keys = outcome.results.data.keys().cloned().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(
+            keys,
+            ["all/interp/", "all/jit/", "slow/interp/", "traces/jit/"]
+        );
+        assert_eq!(
+            runner::restricted_keys(&config),
+            ["slow/jit/", "traces/interp/"]
+        );
+
+        let unknown = config_content.replace(r#"["jit"] }"#, r#"["yk-jit"] }"#);
+        let config: Config = toml::from_str(&unknown).unwrap();
+        assert_eq!(
+            config.validate(),
+            Err("suite s: benchmark slow names unknown executor yk-jit".to_owned())
+        );
+        let nowhere = config_content.replace(r#"executors = ["jit"]"#, "executors = []");
+        let config: Config = toml::from_str(&nowhere).unwrap();
+        assert_eq!(
+            config.validate(),
+            Err("suite s: benchmark traces doesn't run with any executor".to_owned())
+        );
+    }
+
+    #[test]
+    fn cache_policy() {
+        use crate::config::{CachePolicy, Config};
+        let dir = tempfile::tempdir().unwrap();
+        let config_content = |policy: &str, remote: &str| {
+            format!(
+                r#"
+                proc_execs = 2
+                inproc_iters = 1
+                executors = {{ sh = {{ path = "shell" {remote} }} }}
+
+                [suites.s]
+                dir = "{}"
+                cache_policy = "{policy}"
+                benchmarks = {{ a = {{ command = "echo $HASTE_PEXEC >> log" }} }}
+                "#,
+                dir.path().display()
+            )
+        };
+        let config: Config = toml::from_str(&config_content("warm", "")).unwrap();
+        assert_eq!(config.suites["s"].cache_policy, CachePolicy::Warm);
+        assert!(config.validate().is_ok());
+        let outcome = runner::run(&config, &runner::RunOptions::default());
+        // The warm-up pexec runs first, but isn't recorded.
+        assert_eq!(
+            fs::read_to_string(dir.path().join("log")).unwrap(),
+            "0\n0\n1\n"
+        );
+        assert_eq!(outcome.results.data["a/sh/"].len(), 2);
+
+        let config: Config =
+            toml::from_str(&config_content("cold", ", remote = \"elsewhere\"")).unwrap();
+        assert!(config.validate().is_err());
+        assert!(toml::from_str::<Config>(&config_content("lukewarm", "")).is_err());
+    }
+
+    #[test]
+    fn harness_overhead() {
+        let dir = tempfile::tempdir().unwrap();
+        let harness = dir.path().join("harness.sh");
+        // The harness reports 10ms for `nop` and 25ms for anything else.
+        fs::write(
+            &harness,
+            "#!/bin/sh\n\
+             echo \"$3\" >> log\n\
+             if [ \"$3\" = nop ]; then t=10; else t=25; fi\n\
+             echo PEXEC_WALLCLOCK_MS=$t > \"$1\"\n",
+        )
+        .unwrap();
+        fs::set_permissions(&harness, fs::Permissions::from_mode(0o755)).unwrap();
+        let config_content = format!(
+            r#"
+            proc_execs = 2
+            inproc_iters = 1
+            executors = {{ e = "/bin/true" }}
+
+            [suites.s]
+            dir = "{}"
+            harness = "./harness.sh"
+            calibration_benchmark = "nop"
+            benchmarks = {{ fib = {{}} }}
+            "#,
+            dir.path().display()
+        );
+        let config: crate::config::Config = toml::from_str(&config_content).unwrap();
+        assert!(config.validate().is_ok());
+        let outcome = runner::run(&config, &runner::RunOptions::default());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("log")).unwrap(),
+            "nop\nnop\nfib\nfib\n"
+        );
+        assert_eq!(
+            outcome.overheads,
+            HashMap::from([("fib/e/".to_owned(), 10.)])
+        );
+        // The stored samples aren't adjusted.
+        let mut results = outcome.results;
+        assert_eq!(results.data["fib/e/"], vec![25., 25.]);
+
+        results.data.insert("small/e/".to_owned(), vec![9., 11.]);
+        let overheads = HashMap::from([("fib/e/".to_owned(), 10.), ("small/e/".to_owned(), 10.)]);
+        let noisy = results.subtract_overhead(&overheads, ConfidenceLevel::CL99);
+        assert_eq!(noisy, vec!["small/e/".to_owned()]);
+        assert_eq!(results.data["fib/e/"], vec![15., 15.]);
+        assert_eq!(results.data["small/e/"], vec![0., 1.]);
+
+        let clash = config_content.replace("\"nop\"", "\"fib\"");
+        let config: crate::config::Config = toml::from_str(&clash).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn config_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = |executors: &str, benchmarks: &str| {
+            let content = format!(
+                r#"
+                proc_execs = 1
+                inproc_iters = 1
+                executors = {executors}
+
+                [suites.s1]
+                dir = "{0}"
+                benchmarks = {benchmarks}
+
+                [suites.s2]
+                dir = "{0}"
+                benchmarks = {{ fib = {{ command = "true" }} }}
+                "#,
+                dir.path().display()
+            );
+            toml::from_str::<crate::config::Config>(&content).unwrap()
+        };
+        let ok = config(
+            r#"{ jit = "/bin/sh", interp = "/bin/sh" }"#,
+            r#"{ fib = { command = "true" } }"#,
+        );
+        assert!(ok.validate().is_ok());
+
+        let e = config(
+            r#"{ JIT = "/bin/sh", jit = "/bin/sh", "a/b" = "/bin/sh" }"#,
+            r#"{ Fib = { command = "true" }, " x" = { command = "true" } }"#,
+        )
+        .validate()
+        .unwrap_err();
+        assert_eq!(
+            e,
+            "bad names:\n  \
+             executor a/b: the name contains '/'\n  \
+             executor JIT and executor jit differ only by case\n  \
+             suite s1: benchmark  x: the name has leading or trailing whitespace\n  \
+             suite s1: benchmark Fib and suite s2: benchmark fib differ only by case"
+        );
+    }
+
+    #[test]
+    fn descriptions() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = format!(
+            r#"
+            proc_execs = 1
+            inproc_iters = 1
+            executors = {{ e1 = "/bin/sh", e2 = "/bin/sh" }}
+
+            [suites.s]
+            dir = "{}"
+            harness = "h"
+            description = "Some loops"
+            benchmarks = {{ a = {{ description = "A loop", extra_args = ["1"] }}, b = {{}} }}
+            "#,
+            dir.path().display()
+        );
+        let config = toml::from_str::<crate::config::Config>(&content).unwrap();
+        assert_eq!(
+            config.suites["s"].description.as_deref(),
+            Some("Some loops")
+        );
+        let descs = runner::key_descriptions(&config);
+        assert_eq!(descs.len(), 2);
+        assert_eq!(descs["a/e1/1"], "A loop");
+        assert_eq!(descs["a/e2/1"], "A loop");
+
+        // Datums made before descriptions were recorded have none.
+        let extra = toml::from_str::<ExtraToml>("").unwrap();
+        assert!(extra.descriptions.is_empty() && extra.suite_descriptions.is_empty());
+    }
+
+    /// Compare `actual` with the golden file `testdata/{name}`, or overwrite the file with it if
+    /// `HASTE_UPDATE_GOLDEN` is set.
+    fn check_golden(name: &str, actual: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata")
+            .join(name);
+        if env::var_os("HASTE_UPDATE_GOLDEN").is_some() {
+            fs::write(&path, actual).unwrap();
+            return;
+        }
+        let expected = fs::read_to_string(&path).unwrap();
+        assert!(
+            actual == expected,
+            "{} differs (rerun with HASTE_UPDATE_GOLDEN=1 to update it):\n{actual}",
+            path.display()
+        );
+    }
+
+    #[test]
+    fn golden_diff_table() {
+        // Several benchmarks change by exactly the same amount, and so only their keys order them.
+        let stats = [
+            ("fib/jit/", 10., 20., 10),
+            ("fib/interp/", 10., 20., 10),
+            ("sort/jit/", 20., 10., 10),
+            ("sort/interp/", 20., 10., 10),
+            ("nbody/jit/", 10., 10.5, 10),
+            ("nbody/interp/", 10., 10., 10),
+            ("few/jit/", 10., 30., 2),
+        ];
+        let render = || {
+            let mut data1 = HashMap::new();
+            let mut data2 = HashMap::new();
+            for (k, m1, m2, n) in stats {
+                data1.insert(k.to_owned(), SummaryStats::new(m1, 1., n));
+                data2.insert(k.to_owned(), SummaryStats::new(m2, 1., n));
+            }
+            let (mut table, _) = diff_table(
+                &data1,
+                &data2,
+                "Datum1 (ms)",
+                "Datum2 (ms)",
+                false,
+                DEFAULT_MIN_SAMPLES,
+                "time",
+            );
+            table.force_no_tty();
+            let legend = super::diff_legend(
+                "Datum1",
+                "Datum2",
+                "time",
+                ConfidenceLevel::CL99,
+                DEFAULT_MIN_SAMPLES,
+                false,
+            );
+            format!("{table}\n\n{legend}\n")
+        };
+        let out = render();
+        // Each map iterates in a different order, which mustn't show.
+        for _ in 0..10 {
+            assert_eq!(render(), out);
+        }
+        check_golden("diff_table.txt", &out);
+    }
+
+    #[test]
+    fn git_comment() {
+        let dir = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            let out = Command::new("git")
+                .arg("-C")
+                .arg(dir.path())
+                .args([
+                    "-c",
+                    "user.name=haste",
+                    "-c",
+                    "user.email=haste@example.com",
+                ])
+                .args(args)
+                .output()
+                .unwrap();
+            assert!(
+                out.status.success(),
+                "{}",
+                String::from_utf8_lossy(&out.stderr)
+            );
+            String::from_utf8(out.stdout).unwrap().trim().to_owned()
+        };
+        // Not a repository yet.
+        assert!(crate::git::comment(dir.path()).is_err());
+
+        git(&["init", "-q", "-b", "mybranch"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "Inline small traces"]);
+        let hash = git(&["rev-parse", "--short", "HEAD"]);
+        assert_eq!(
+            crate::git::comment(dir.path()).unwrap(),
+            format!("mybranch @ {hash} \"Inline small traces\"")
+        );
+        git(&["checkout", "-q", "--detach"]);
+        assert_eq!(
+            crate::git::comment(dir.path()).unwrap(),
+            format!("HEAD @ {hash} \"Inline small traces\"")
+        );
+    }
+
+    #[test]
+    fn pexec_timeouts() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = |global: &str, suite: &str| {
+            let content = format!(
+                r#"
+                proc_execs = 2
+                inproc_iters = 1
+                executors = {{ sh = "shell" }}
+                {global}
+
+                [suites.s]
+                dir = "{0}"
+                {suite}
+                [suites.s.benchmarks]
+                own = {{ command = "sleep 10", max_pexec_secs = 0.2 }}
+                inherited = {{ command = "sleep 10" }}
+
+                [suites.t]
+                dir = "{0}"
+                benchmarks = {{ quick = {{ command = "true" }} }}
+                "#,
+                dir.path().display()
+            );
+            toml::from_str::<crate::config::Config>(&content).unwrap()
+        };
+        let c = config("max_pexec_secs = 3", "max_pexec_secs = 2");
+        let (s, t) = (&c.suites["s"], &c.suites["t"]);
+        let timeout = |suite: &crate::config::Suite, bench| {
+            c.max_pexec_for(suite, &suite.benchmarks[bench])
+                .map(|d| d.as_secs_f64())
+        };
+        assert_eq!(timeout(s, "own"), Some(0.2));
+        assert_eq!(timeout(s, "inherited"), Some(2.));
+        assert_eq!(timeout(t, "quick"), Some(3.));
+        let c = config("", "");
+        assert_eq!(
+            c.max_pexec_for(&c.suites["t"], &c.suites["t"].benchmarks["quick"]),
+            None
+        );
+        assert_eq!(
+            config("max_pexec_secs = 0", "").validate(),
+            Err("max_pexec_secs must be positive, not 0".to_owned())
+        );
+        assert_eq!(
+            config("", "max_suite_secs = -1").validate(),
+            Err("suite s: max_suite_secs must be positive, not -1".to_owned())
+        );
+
+        // The sleeps are killed rather than waited for. Whichever benchmark runs first uses up the
+        // suite's budget, so the other is skipped, but the other suite isn't.
+        let opts = runner::RunOptions {
+            keep_going: true,
+            ..Default::default()
+        };
+        let start = std::time::Instant::now();
+        let c = config("", "max_pexec_secs = 0.2\nmax_suite_secs = 0.3");
+        let outcome = runner::run(&c, &opts);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        let failures = &outcome.results.failures;
+        assert_eq!(failures.len(), 1);
+        let (failed, f) = failures.iter().next().unwrap();
+        assert_eq!((f.count, f.status.as_str()), (2, "timeout"));
+        assert_eq!(outcome.over_budget.len(), 1);
+        let (skipped, n) = outcome.over_budget.iter().next().unwrap();
+        assert!(skipped != failed && *n == 2);
+        assert_eq!(outcome.results.data["quick/sh/"].len(), 2);
+        assert!(outcome.skipped_pexecs.is_empty());
+    }
+
+    #[test]
+    fn fsck() {
+        use crate::fsck::datum_problems;
+        use crate::store::{self, StoreKind};
+        let results = ResultFile {
+            data: HashMap::from([("a/e/".to_owned(), vec![1., 2.])]),
+            timestamps: HashMap::from([("a/e/".to_owned(), vec![10, 20])]),
+            ..Default::default()
+        };
+        assert!(datum_problems(&results, &ExtraToml::default()).is_empty());
+        let mut bad = results.clone();
+        bad.data.insert("b/e/".to_owned(), Vec::new());
+        bad.timestamps.insert("a/e/".to_owned(), vec![10]);
+        bad.metrics.insert(
+            "cycles".to_owned(),
+            HashMap::from([("a/e/".to_owned(), vec![f64::INFINITY])]),
+        );
+        assert_eq!(
+            datum_problems(&bad, &ExtraToml::default()),
+            [
+                "a/e/: cycles sample 0 is inf (is the datum corrupt?)",
+                "b/e/: no time samples",
+                "a/e/: 1 cycles samples but 2 time samples",
+                "a/e/: 2 samples but 1 timestamps",
+            ]
+        );
+
+        // A truncated file is reported rather than panicking, and quarantining it hides it from
+        // the store without freeing its ID.
+        let dir = tempfile::tempdir().unwrap();
+        let fs_store = store::open(StoreKind::Fs, dir.path().to_owned());
+        for _ in 0..2 {
+            fs_store.store(&results, &ExtraToml::default());
+        }
+        let data = dir.path().join("1").join("data.toml");
+        let tml = fs::read_to_string(&data).unwrap();
+        fs::write(&data, &tml[..tml.len() - 3]).unwrap();
+        assert!(fs_store.try_load_results(0).is_ok());
+        assert!(fs_store.try_load_results(1).is_err());
+        assert!(fs_store.quarantine(1).unwrap().ends_with("1.broken"));
+        assert_eq!(fs_store.ids(), [0]);
+        assert_eq!(fs_store.store(&results, &ExtraToml::default()), 2);
+
+        let sqlite = store::open(StoreKind::Sqlite, dir.path().to_owned());
+        let id = sqlite.store(&results, &ExtraToml::default());
+        assert!(sqlite.quarantine(id).is_err());
+    }
+
+    #[test]
+    fn junit_report() {
+        use crate::{DiffOptions, junit};
+        let data1 = HashMap::from([
+            ("fib/jit/".to_owned(), SummaryStats::new(100., 1., 10)),
+            ("sort/jit/10".to_owned(), SummaryStats::new(100., 1., 10)),
+            ("\"q\"/jit/".to_owned(), SummaryStats::new(100., 1., 10)),
+        ]);
+        let data2 = HashMap::from([
+            ("fib/jit/".to_owned(), SummaryStats::new(150., 1., 10)),
+            ("sort/jit/10".to_owned(), SummaryStats::new(105., 1., 10)),
+            ("\"q\"/jit/".to_owned(), SummaryStats::new(50., 1., 10)),
+        ]);
+        let rows = diff_rows(&data1, &data2, DEFAULT_MIN_SAMPLES, false);
+        assert!(rows.iter().all(|r| r.significant));
+        let suites = HashMap::from([("fib/jit/".to_owned(), "s".to_owned())]);
+        let failed = [("nbody/jit/".to_owned(), "D2".to_owned())];
+        let mut opts = DiffOptions {
+            env_warnings: false,
+            ..Default::default()
+        };
+        let xml = junit::report(&rows, &failed, &suites, ["D1", "D2"], &opts);
+        assert!(
+            xml.contains(r#"tests="4" failures="2" errors="1""#),
+            "{xml}"
+        );
+        assert!(xml.contains(r#"<testcase classname="s/jit" name="fib" time="0.150000">"#));
+        assert!(xml.contains(
+            r#"<failure message="D2 is 50.00% slower: time mean 150.000 ms ±1.000 vs. 100.000 ms ±1.000 in D1, ratio 1.500">"#
+        ));
+        // The suites of datums made by older versions of haste are unknown.
+        assert!(xml.contains(r#"<testcase classname="jit" name="sort/10" time="0.105000">"#));
+        assert!(
+            xml.contains(r#"<testcase classname="jit" name="&quot;q&quot;" time="0.050000"/>"#)
+        );
+        assert!(xml.contains(
+            "<testcase classname=\"jit\" name=\"nbody\">\n      <error message=\"failed in D2\"/>"
+        ));
+
+        // Only regressions beyond the threshold fail.
+        opts.fail_on_regression = Some(10.);
+        let xml = junit::report(&rows, &failed, &suites, ["D1", "D2"], &opts);
+        assert!(xml.contains(r#"failures="1""#));
+        assert!(xml.contains(r#"name="sort/10" time="0.105000"/>"#));
+    }
+
+    #[test]
+    fn bimodality() {
+        use crate::stats::bimodal;
+        // Evenly spread samples, and a roughly normal spread with outlying tails.
+        let uniform = (0..30).map(|i| 100. + f64::from(i)).collect::<Vec<_>>();
+        assert_eq!(bimodal(&uniform), None);
+        let normal = [
+            85., 92., 95., 97., 98., 99., 99., 100., 100., 100., 100., 101., 101., 102., 103.,
+            105., 108., 115.,
+        ];
+        assert_eq!(bimodal(&normal), None);
+        // Identical samples have no spread, but no gap either.
+        assert_eq!(bimodal(&[7.; 20]), None);
+
+        let mut two = [
+            10., 10.2, 9.9, 10.1, 10., 9.8, 10., 20., 20.1, 19.9, 20.2, 19.8,
+        ]
+        .to_vec();
+        let [lo, hi] = bimodal(&two).unwrap();
+        assert_eq!((lo.n, hi.n), (7, 5));
+        assert!((lo.mean - 10.).abs() < 1e-9 && (hi.mean - 20.).abs() < 1e-9);
+        // A single outlier isn't a cluster.
+        two.truncate(8);
+        two.extend([10.; 4]);
+        assert_eq!(bimodal(&two), None);
+        // Nor is there anything to find in too few samples.
+        assert_eq!(bimodal(&[1., 1., 1., 3., 3.]), None);
+
+        let conf = ConfidenceLevel::default();
+        let s = SummaryStats::from_samples(&[5., 5.1, 4.9, 5., 5., 9., 9.1, 8.9, 9., 9.], conf);
+        assert!(s.bimodal);
+        assert!(!SummaryStats::from_samples(&uniform, conf).bimodal);
+        let summaries = |bimodal| {
+            HashMap::from([(
+                "b/e/".to_owned(),
+                SummaryStats {
+                    bimodal,
+                    ..SummaryStats::new(1., 0.1, 10)
+                },
+            )])
+        };
+        let (plain, marked) = (summaries(false), summaries(true));
+        let (table, _) = diff_table(&plain, &marked, "D1", "D2", false, 5, "time");
+        assert!(table.to_string().contains("indistinguishable bimodal?"));
+        let (table, _) = diff_table(&plain, &plain, "D1", "D2", false, 5, "time");
+        assert!(!table.to_string().contains("bimodal?"));
+    }
+
+    #[test]
+    fn diff_args_variants() {
+        use crate::variants::pair_variants;
+        let summaries = HashMap::from(
+            [
+                ("fib/jit/", 10.),
+                ("fib/jit/--opt", 5.),
+                ("fib/jit/--opt--inline", 4.),
+                ("fib/interp/", 50.),
+                ("load/jit/--from-/tmp/x", 1.),
+                ("alone/jit/--opt", 3.),
+            ]
+            .map(|(k, m)| (k.to_owned(), SummaryStats::new(m, 0.1, 10))),
+        );
+        let paired = pair_variants(&summaries, "", &[], &[]).unwrap();
+        let mut keys = paired.variants.keys().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, ["fib/jit/--opt", "fib/jit/--opt--inline"]);
+        assert_eq!(paired.base["fib/jit/--opt--inline"].mean, 10.);
+        assert_eq!(paired.variants["fib/jit/--opt--inline"].mean, 4.);
+        assert!(paired.no_baseline.is_empty());
+
+        let paired = pair_variants(&summaries, "--opt", &["fib".to_owned()], &[]).unwrap();
+        assert_eq!(paired.variants.len(), 2);
+        assert_eq!(paired.base["fib/jit/"].mean, 5.);
+        let paired = pair_variants(&summaries, "--x", &[], &["jit".to_owned()]).unwrap();
+        assert!(paired.variants.is_empty());
+        assert_eq!(paired.no_baseline, ["fib/jit"]);
+
+        let mut bad = summaries.clone();
+        bad.insert("nokey".to_owned(), SummaryStats::new(1., 0.1, 10));
+        assert!(pair_variants(&bad, "", &[], &[]).is_err());
+    }
+
+    #[test]
+    fn compact_storage() {
+        use crate::config::Storage;
+        use crate::store::{self, StoreKind};
+        let bits = |r: &ResultFile| {
+            let mut series = [("time", &r.data)]
+                .into_iter()
+                .chain(r.metrics.iter().map(|(m, d)| (m.as_str(), d)))
+                .flat_map(|(m, d)| {
+                    d.iter().map(move |(k, v)| {
+                        (
+                            m.to_owned(),
+                            k.clone(),
+                            v.iter().map(|x| x.to_bits()).collect(),
+                        )
+                    })
+                })
+                .collect::<Vec<(String, String, Vec<u64>)>>();
+            series.sort();
+            series
+        };
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut results = ResultFile::default();
+        for i in 0..20 {
+            let samples = (0..50)
+                .map(|_| rng.random_range(0.0..1000.0))
+                .collect::<Vec<f64>>();
+            results.data.insert(format!("b{i}/e/"), samples.clone());
+            results.timestamps.insert(
+                format!("b{i}/e/"),
+                (0..50).map(|t| 1_700_000_000_000 + t).collect(),
+            );
+            results
+                .metrics
+                .entry("instructions".to_owned())
+                .or_default()
+                .insert(
+                    format!("b{i}/e/"),
+                    samples.iter().map(|x| x * 1e6).collect(),
+                );
+        }
+        // Awkward values must survive exactly, even those an encoding might normalise.
+        let awkward = vec![-0., f64::MIN_POSITIVE / 3., f64::MAX, f64::NAN, 0.1 + 0.2];
+        results.data.insert("odd/e/--flag".to_owned(), awkward);
+        results.failures.insert(
+            "bad/e/".to_owned(),
+            Failure {
+                count: 1,
+                status: "2".to_owned(),
+                stderr: "".to_owned(),
+            },
+        );
+
+        let encoded = crate::compact::encode(&results);
+        assert!(crate::compact::is_compact(&encoded));
+        let decoded = crate::compact::decode(&encoded).unwrap();
+        assert_eq!(bits(&decoded), bits(&results));
+        assert_eq!(decoded.timestamps, results.timestamps);
+        assert_eq!(decoded.failures.keys().collect::<Vec<_>>(), ["bad/e/"]);
+        assert!(crate::compact::decode(&encoded[..encoded.len() - 3]).is_err());
+        let mut bad_len = encoded.clone();
+        bad_len.truncate(9);
+        bad_len.extend(u64::MAX.to_le_bytes());
+        assert!(crate::compact::decode(&bad_len).is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let store = store::open(StoreKind::Fs, dir.path().to_owned());
+        // Small datums are stored as TOML unless the config says otherwise...
+        let plain = store.store(&results, &ExtraToml::default());
+        assert!(
+            dir.path()
+                .join(plain.to_string())
+                .join("data.toml")
+                .exists()
+        );
+        store.set_storage(Some(Storage::Compact));
+        let compact = store.store(&results, &ExtraToml::default());
+        let datum_dir = dir.path().join(compact.to_string());
+        assert!(datum_dir.join("data.bin").exists() && !datum_dir.join("data.toml").exists());
+        // ...and loading doesn't care which.
+        assert_eq!(bits(&store.load_results(compact)), bits(&results));
+
+        let (before, after) = store.compact(plain).unwrap().unwrap();
+        assert!(after < before, "{after} >= {before}");
+        assert_eq!(store.compact(plain), Ok(None));
+        assert_eq!(bits(&store.load_results(plain)), bits(&results));
+        let sqlite = store::open(StoreKind::Sqlite, dir.path().to_owned());
+        assert!(sqlite.compact(0).is_err());
+    }
+
+    #[test]
+    fn mixed_metric_datums() {
+        use crate::{DiffOptions, TIME_METRIC};
+        let dir = tempfile::tempdir().unwrap();
+        let app = test_app(dir.path(), crate::store::StoreKind::Fs, 0);
+        let samples = |base: f64| (0..10).map(|i| base + f64::from(i)).collect::<Vec<_>>();
+        let plain = ResultFile {
+            data: HashMap::from([("b/e/".to_owned(), samples(10.))]),
+            ..Default::default()
+        };
+        let rich = ResultFile {
+            metrics: HashMap::from([(
+                "instructions".to_owned(),
+                HashMap::from([("b/e/".to_owned(), samples(1000.))]),
+            )]),
+            ..plain.clone()
+        };
+        let [p1, p2, r1, r2] =
+            [&plain, &plain, &rich, &rich].map(|r| app.store.store(r, &ExtraToml::default()));
+        let diff = |id1, id2, metric: &str| {
+            let opts = DiffOptions {
+                metric: metric.to_owned(),
+                deterministic: true,
+                env_warnings: false,
+                ..Default::default()
+            };
+            let mut out = Vec::new();
+            app.diff_to(
+                &DatumRef::local(id1),
+                &DatumRef::local(id2),
+                &opts,
+                &mut out,
+                false,
+            )
+            .map(|()| String::from_utf8(out).unwrap())
+        };
+        // Time is the common denominator of every combination.
+        for (id1, id2) in [(p1, p2), (r1, r2), (p1, r1), (r1, p1)] {
+            let out = diff(id1, id2, TIME_METRIC).unwrap();
+            assert!(
+                out.contains(&format!(" Datum{id1} (ms)  Datum{id2} (ms) ")),
+                "{out}"
+            );
+            assert!(!out.contains("metric:"), "{out}");
+        }
+        let out = diff(r1, r2, "instructions").unwrap();
+        assert!(out.contains("metric: instructions\n"), "{out}");
+        assert!(
+            out.contains(" b/e/       10/10  1004 ±2  1004 ±2 "),
+            "{out}"
+        );
+        // Only an explicitly requested metric that one side lacks is an error, which says which
+        // side, and what could be compared instead.
+        for (id1, id2) in [(p1, r1), (r1, p1)] {
+            let e = diff(id1, id2, "instructions").unwrap_err();
+            assert!(
+                e.starts_with(&format!("Datum{p1}: no data for metric instructions")),
+                "{e}"
+            );
+            assert!(e.contains(&format!("though Datum{r1} has it")), "{e}");
+            assert!(e.ends_with("metrics both datums have: ips, time"), "{e}");
+        }
+        let e = diff(p1, p2, "instructions").unwrap_err();
+        assert!(!e.contains("though"), "{e}");
+    }
+
+    #[test]
+    fn state_dir_discovery() {
+        use crate::{DOT_DIR, find_state_dir, resolve_state_dir};
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let nested = root.join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+        assert_eq!(find_state_dir(&nested), None);
+        // Without a `.haste` anywhere, read-only commands get an empty store in the current
+        // directory, which isn't created.
+        let config = Path::new(DEFAULT_CONFIG_FILE);
+        assert_eq!(
+            resolve_state_dir(&nested, config, None, false),
+            nested.join(DOT_DIR)
+        );
+
+        fs::create_dir(root.join(DOT_DIR)).unwrap();
+        assert_eq!(find_state_dir(&nested), Some(root.join(DOT_DIR)));
+        assert_eq!(
+            resolve_state_dir(&nested, config, None, false),
+            root.join(DOT_DIR)
+        );
+        // Commands which store datums use the `.haste` next to the config file.
+        assert_eq!(
+            resolve_state_dir(&nested, config, None, true),
+            nested.join(DOT_DIR)
+        );
+        assert_eq!(
+            resolve_state_dir(&nested, Path::new("../haste.toml"), None, true),
+            nested.join("..").join(DOT_DIR)
+        );
+        // An explicit state directory always wins.
+        for creates in [false, true] {
+            assert_eq!(
+                resolve_state_dir(&nested, config, Some(PathBuf::from("elsewhere")), creates),
+                nested.join("elsewhere")
+            );
+        }
+
+        // The search doesn't leave a git repository.
+        fs::create_dir(root.join("a").join(".git")).unwrap();
+        assert_eq!(find_state_dir(&nested), None);
+        fs::create_dir(root.join("a").join(DOT_DIR)).unwrap();
+        assert_eq!(find_state_dir(&nested), Some(root.join("a").join(DOT_DIR)));
+
+        // An empty store is just empty, and looking at it doesn't create it.
+        let missing = nested.join(DOT_DIR);
+        let store = crate::store::open(crate::store::StoreKind::Fs, missing.clone());
+        assert!(store.ids().is_empty());
+        assert!(!missing.exists());
+    }
+
+    #[test]
+    fn golden_diff_plot() {
+        use crate::{DiffOptions, plot};
+        let stats = [
+            ("fib/jit/", 10., 20.),
+            ("sort/jit/", 200., 100.),
+            ("nbody/<jit>/", 1., 1.01),
+        ];
+        let mut data1 = HashMap::new();
+        let mut data2 = HashMap::new();
+        for (k, m1, m2) in stats {
+            data1.insert(k.to_owned(), SummaryStats::new(m1, m1 / 20., 10));
+            data2.insert(k.to_owned(), SummaryStats::new(m2, m2 / 20., 10));
+        }
+        let rows = diff_rows(&data1, &data2, DEFAULT_MIN_SAMPLES, false);
+        let mut opts = DiffOptions {
+            deterministic: true,
+            env_warnings: false,
+            ..Default::default()
+        };
+        let comments = [Some("before"), Some("after & more")];
+        let svg = plot::render(
+            &rows,
+            ["Datum1", "Datum2"],
+            comments,
+            &HashMap::new(),
+            &opts,
+        );
+        check_golden("diff_plot.svg", &svg);
+        assert!(svg.contains(">+100.00%</text>") && svg.contains(">-50.00%</text>"));
+        assert!(!svg.contains("+1.00%"));
+        assert!(svg.contains("Datum2: after &amp; more") && svg.contains("nbody/&lt;jit&gt;/"));
+
+        opts.log_scale = true;
+        let svg = plot::render(
+            &rows,
+            ["Datum1", "Datum2"],
+            [None, None],
+            &HashMap::new(),
+            &opts,
+        );
+        check_golden("diff_plot_log.svg", &svg);
+        for decade in ["0.1", "1", "10", "100", "1000"] {
+            assert!(svg.contains(&format!("text-anchor=\"middle\">{decade}</text>")));
+        }
+    }
+
+    #[test]
+    fn stability_ranking() {
+        use crate::stability::{rank, stability};
+        // Samples alternating either side of `mean` by `spread` have a CV of about spread / mean.
+        let datum = |spreads: &[(&str, f64)]| ResultFile {
+            data: spreads
+                .iter()
+                .map(|(k, spread)| {
+                    let samples = (0..100)
+                        .map(|i| {
+                            if i % 2 == 0 {
+                                100. - spread
+                            } else {
+                                100. + spread
+                            }
+                        })
+                        .collect();
+                    ((*k).to_owned(), samples)
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let quiet = datum(&[("a/e/", 1.), ("b/e/", 2.), ("c/e/", 3.)]);
+        let noisy = datum(&[("a/e/", 5.), ("b/e/", 10.), ("c/e/", 30.), ("d/e/", 50.)]);
+        let mut extra = ExtraToml::default();
+        extra.hosts.insert("e".to_owned(), "box".to_owned());
+        let keys = std::collections::BTreeSet::from(["a/e/", "b/e/", "c/e/"]);
+        let mut ranked = vec![
+            stability("noisy".to_owned(), &noisy, &ExtraToml::default(), &keys),
+            stability("quiet".to_owned(), &quiet, &extra, &keys),
+            stability("empty".to_owned(), &ResultFile::default(), &extra, &keys),
+        ];
+        rank(&mut ranked);
+        assert_eq!(
+            ranked.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(),
+            ["quiet", "noisy", "empty"]
+        );
+        let close = |a: Option<f64>, b: f64| (a.unwrap() - b).abs() < b * 0.01;
+        assert!(close(ranked[0].median_cv, 0.02));
+        assert!(close(ranked[0].score, 0.02));
+        assert_eq!(ranked[0].hosts, ["box"]);
+        // The benchmark outside `keys` doesn't count.
+        assert_eq!(ranked[1].benchmarks, 3);
+        assert!(close(ranked[1].median_cv, 0.10));
+        assert!(close(ranked[1].score, 0.15));
+        assert_eq!(ranked[1].noisiest.as_ref().unwrap().0, "c/e/");
+        assert_eq!(ranked[2].benchmarks, 0);
+        assert!(ranked[2].score.is_none());
+    }
+
+    #[test]
+    fn bounded_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = format!(
+            r#"
+            proc_execs = 1
+            inproc_iters = 1
+            executors = {{ sh = "shell" }}
+            max_output_bytes = 1000
+
+            [suites.s]
+            dir = "{}"
+            [suites.s.benchmarks]
+            chatty = {{ command = "yes | head -c 20000000; yes | head -c 20000000 >&2" }}
+            failing = {{ command = "yes | head -c 5000000 >&2; echo last words >&2; exit 3" }}
+            "#,
+            dir.path().display()
+        );
+        let config = toml::from_str::<crate::config::Config>(&content).unwrap();
+        assert_eq!(config.max_output_bytes(), 1000);
+        let opts = runner::RunOptions {
+            keep_going: true,
+            ..Default::default()
+        };
+        let outcome = runner::run(&config, &opts);
+        assert_eq!(outcome.results.data["chatty/sh/"].len(), 1);
+        let f = &outcome.results.failures["failing/sh/"];
+        assert_eq!(f.status, "3");
+        assert!(f.stderr.contains("last words"));
+        assert!(f.stderr.contains("truncated to max_output_bytes"));
+    }
+
+    #[test]
+    fn extend_datum() {
+        use crate::store::StoreKind;
+        use crate::{BenchOptions, extend::restrict_to_missing};
+        let dir = tempfile::tempdir().unwrap();
+        let config = |proc_execs, benchmarks: &str| {
+            let content = format!(
+                r#"
+                proc_execs = {proc_execs}
+                inproc_iters = 1
+                executors = {{ e1 = "shell", e2 = "shell" }}
+                [suites.s]
+                dir = "{}"
+                [suites.s.benchmarks]
+                old = {{ command = "true" }}
+                {benchmarks}
+                "#,
+                dir.path().display()
+            );
+            toml::from_str::<crate::config::Config>(&content).unwrap()
+        };
+        let app = test_app(dir.path(), StoreKind::Fs, 0);
+        let opts = BenchOptions {
+            quiet: true,
+            ..Default::default()
+        };
+        let base = app.bench(&mut config(2, ""), &opts);
+
+        let new =
+            "new = { command = \"true\" }\nonly2 = { command = \"true\", executors = [\"e2\"] }";
+        let mut c = config(5, new);
+        let measured = restrict_to_missing(&mut c, &app.load_results(base));
+        assert_eq!(measured, ["new/e1/", "new/e2/", "only2/e2/"]);
+        assert_eq!(c.proc_execs, 2);
+        assert!(!c.suites["s"].benchmarks.contains_key("old"));
+        assert!(!c.suites["s"].benchmarks["only2"].runs_on("e1"));
+
+        let opts = BenchOptions {
+            extend: Some(base),
+            ..opts
+        };
+        let id = app.bench(&mut config(5, new), &opts);
+        let results = app.load_results(id);
+        let mut keys = results.data.keys().cloned().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(
+            keys,
+            ["new/e1/", "new/e2/", "old/e1/", "old/e2/", "only2/e2/"]
+        );
+        assert!(results.data.values().all(|v| v.len() == 2));
+        assert_eq!(
+            results.data["old/e1/"],
+            app.load_results(base).data["old/e1/"]
+        );
+        let extra = app.load_extra(id);
+        let composite = extra.composite.unwrap();
+        assert_eq!(composite.source, base);
+        assert_eq!(composite.measured, measured);
+        assert_eq!(extra.restricted, ["only2/e1/"]);
+    }
+
+    #[test]
+    fn pexec_orders() {
+        use crate::runner::{Order, RunOptions, planned_pexecs};
+        let config = toml::from_str::<crate::config::Config>(
+            r#"
+            proc_execs = 2
+            inproc_iters = 1
+            executors = { e1 = "shell", e2 = "shell" }
+            [suites.a]
+            dir = "."
+            benchmarks = { x = { command = "true" }, y = { command = "true" } }
+            [suites.b]
+            dir = "."
+            benchmarks = { z = { command = "true", executors = ["e1"] } }
+            "#,
+        )
+        .unwrap();
+        let plan = |order, max_duration| {
+            let opts = RunOptions {
+                order,
+                max_duration,
+                seed: 3,
+                ..Default::default()
+            };
+            planned_pexecs(&config, &opts)
+                .into_iter()
+                .map(|(k, n)| format!("{k}{n}"))
+                .collect::<Vec<_>>()
+        };
+        let grouped = [
+            "x/e1/0", "x/e1/1", "x/e2/0", "x/e2/1", "y/e1/0", "y/e1/1", "y/e2/0", "y/e2/1",
+            "z/e1/0", "z/e1/1",
+        ];
+        assert_eq!(plan(None, None), grouped);
+        assert_eq!(plan(Some(Order::Grouped), None), grouped);
+        // Suites alternate within each round.
+        let round_robin = [
+            "x/e1/0", "z/e1/0", "x/e2/0", "y/e1/0", "y/e2/0", "x/e1/1", "z/e1/1", "x/e2/1",
+            "y/e1/1", "y/e2/1",
+        ];
+        assert_eq!(plan(Some(Order::RoundRobin), None), round_robin);
+        assert_eq!(plan(None, Some(Duration::from_secs(60))), round_robin);
+        let shuffled = plan(Some(Order::Shuffle), None);
+        assert_eq!(
+            shuffled,
+            [
+                "y/e1/0", "y/e1/1", "x/e1/0", "x/e2/0", "z/e1/0", "y/e2/0", "z/e1/1", "y/e2/1",
+                "x/e2/1", "x/e1/1",
+            ]
+        );
+        assert_eq!(shuffled, plan(Some(Order::Shuffle), None));
+    }
+
+    #[test]
+    fn environment_drift() {
+        use crate::config::EnvWarnings;
+        use crate::environment::drift;
+        let day = 24 * 60 * 60;
+        let extra = |start_time, kernel: &str, boot_id: &str| ExtraToml {
+            start_time: Some(start_time),
+            kernel: Some(kernel.to_owned()),
+            boot_id: Some(boot_id.to_owned()),
+            ..Default::default()
+        };
+        let defaults = EnvWarnings::default();
+        let base = extra(100 * day, "6.1", "a");
+        assert!(drift(&base, &extra(103 * day, "6.1", "a"), true, &defaults).is_empty());
+        assert_eq!(
+            drift(&base, &extra(90 * day, "6.1", "b"), true, &defaults),
+            [
+                "they were collected 10.0 days apart",
+                "the machine was rebooted between them"
+            ]
+        );
+        // A kernel change implies a reboot, so only the former is mentioned.
+        assert_eq!(
+            drift(&base, &extra(100 * day, "6.2", "b"), true, &defaults),
+            ["the kernel changed from 6.1 to 6.2"]
+        );
+        // Different machines are bound to have booted separately.
+        assert!(drift(&base, &extra(100 * day, "6.2", "b"), false, &defaults).is_empty());
+        let lenient = EnvWarnings {
+            max_days: 30.,
+            reboots: false,
+        };
+        assert!(drift(&base, &extra(90 * day, "6.2", "b"), true, &lenient).is_empty());
+        // Datums from before any of this was recorded can't be said to differ.
+        assert!(drift(&base, &ExtraToml::default(), true, &defaults).is_empty());
+
+        let config = |env: &str| {
+            toml::from_str::<crate::config::Config>(&format!(
+                "proc_execs = 1\ninproc_iters = 1\nexecutors = {{}}\nsuites = {{}}\n{env}"
+            ))
+        };
+        let c = config("[env_warnings]\nmax_days = 2").unwrap();
+        assert_eq!(
+            (c.env_warnings.max_days, c.env_warnings.reboots),
+            (2., true)
+        );
+        assert_eq!(config("").unwrap().env_warnings.max_days, 7.);
+        assert_eq!(
+            config("[env_warnings]\nmax_days = -1").unwrap().validate(),
+            Err("env_warnings.max_days must be zero or more, not -1".to_owned())
+        );
+    }
+
+    #[test]
+    fn percentile_stat() {
+        use crate::{Statistic, stats, too_few_warning};
+        assert_eq!("p90".parse(), Ok(Statistic::Percentile(90.)));
+        assert_eq!("p99.9".parse(), Ok(Statistic::Percentile(99.9)));
+        for s in ["p0", "p100", "p", "px"] {
+            assert!(s.parse::<Statistic>().is_err(), "{s}");
+        }
+        assert_eq!(Statistic::Percentile(90.).to_string(), "p90");
+
+        // Linear interpolation between the closest ranks.
+        let sorted = [1., 2., 3., 4., 10.];
+        assert_eq!(stats::quantile(&sorted, 0.5), 3.);
+        assert_eq!(stats::quantile(&sorted, 0.875), 7.);
+        assert!((stats::quantile(&sorted, 0.9) - 7.6).abs() < 1e-12);
+        assert_eq!(stats::quantile(&sorted, 0.1), 1.4);
+        assert_eq!(stats::quantile(&sorted, 0.), 1.);
+        assert_eq!(stats::quantile(&sorted, 1.), 10.);
+        assert_eq!(stats::quantile(&[5.], 0.9), 5.);
+        assert_eq!(stats::quantile_min_samples(0.5), 2);
+        assert_eq!(stats::quantile_min_samples(0.9), 10);
+        assert_eq!(stats::quantile_min_samples(0.01), 100);
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let samples = (1..=100).map(f64::from).collect::<Vec<_>>();
+        let results = ResultFile {
+            data: HashMap::from([
+                ("a/x/".to_owned(), samples.clone()),
+                ("b/x/".to_owned(), vec![1., 2., 3., 4., 10.]),
+            ]),
+            ..Default::default()
+        };
+        let (summaries, too_few) = results
+            .summarise_by(Statistic::Percentile(90.), ConfidenceLevel::CL99, &mut rng)
+            .unwrap();
+        let a = &summaries["a/x/"];
+        assert!((a.mean - 90.1).abs() < 1e-9);
+        assert!(a.ci > 0. && a.ci < 10., "{}", a.ci);
+        // Too few samples for a p90: the interval covers at least their range.
+        assert_eq!(too_few, vec!["b/x/".to_owned()]);
+        let b = &summaries["b/x/"];
+        assert!((b.mean - 7.6).abs() < 1e-12);
+        assert!(b.ci >= 9.);
+        let warning = too_few_warning("Datum0", Statistic::Percentile(90.), &too_few).unwrap();
+        assert!(warning.starts_with("Datum0: fewer than 10 samples"));
+        assert!(too_few_warning("Datum0", Statistic::Mean, &too_few).is_none());
+
+        // The same seed gives the same intervals.
+        let (again, _) = results
+            .summarise_by(
+                Statistic::Percentile(90.),
+                ConfidenceLevel::CL99,
+                &mut StdRng::seed_from_u64(1),
+            )
+            .unwrap();
+        assert_eq!(again["a/x/"].ci, a.ci);
+    }
+
+    #[test]
+    fn benchmark_urls() {
+        use crate::multidiff::json_rows;
+        use crate::store::StoreKind;
+        use crate::{DiffOptions, plot};
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("haste.toml");
+        fs::write(
+            &config_path,
+            format!(
+                r#"
+                proc_execs = 1
+                inproc_iters = 1
+                executors = {{ e = "/bin/sh" }}
+
+                [suites.s]
+                dir = "{}"
+                harness = "h"
+                benchmarks = {{ a = {{ url = "https://example.com/a?x=1&y=2" }}, b = {{}} }}
+                "#,
+                dir.path().display()
+            ),
+        )
+        .unwrap();
+        let config =
+            toml::from_str::<crate::config::Config>(&fs::read_to_string(&config_path).unwrap())
+                .unwrap();
+        assert_eq!(
+            runner::key_urls(&config),
+            HashMap::from([(
+                "a/e/".to_owned(),
+                "https://example.com/a?x=1&y=2".to_owned()
+            )])
+        );
+
+        // Datums prefer the URLs they recorded, with older datums falling back to the config's.
+        let mut app = test_app(dir.path(), StoreKind::Fs, 0);
+        app.config_file = config_path;
+        let old = toml::from_str::<ExtraToml>("").unwrap();
+        assert!(old.urls.is_none());
+        let new = toml::from_str::<ExtraToml>(
+            "[urls]\n\"a/e/\" = \"https://example.com/new\"\n\"b/e/\" = \"https://example.com/b\"",
+        )
+        .unwrap();
+        let urls = app.benchmark_urls(&[&old]);
+        assert_eq!(urls["a/e/"], "https://example.com/a?x=1&y=2");
+        let urls = app.benchmark_urls(&[&old, &new]);
+        assert_eq!(urls["a/e/"], "https://example.com/new");
+        assert_eq!(urls.len(), 2);
+        let urls = app.benchmark_urls(&[&new, &old]);
+        assert_eq!(urls["a/e/"], "https://example.com/a?x=1&y=2");
+
+        let data = HashMap::from([
+            ("a/e/".to_owned(), SummaryStats::new(10., 1., 10)),
+            ("b/e/".to_owned(), SummaryStats::new(10., 1., 10)),
+        ]);
+        let rows = diff_rows(&data, &data, DEFAULT_MIN_SAMPLES, false);
+        let urls = HashMap::from([(
+            "a/e/".to_owned(),
+            "https://example.com/a?x=1&y=2".to_owned(),
+        )]);
+
+        // The plot links the benchmark's name.
+        let opts = DiffOptions {
+            deterministic: true,
+            env_warnings: false,
+            ..Default::default()
+        };
+        let svg = plot::render(&rows, ["Datum1", "Datum2"], [None, None], &urls, &opts);
+        assert!(svg.contains("<a href=\"https://example.com/a?x=1&amp;y=2\"><text "));
+        assert_eq!(svg.matches("<a href").count(), 1);
+
+        // The JSON includes it.
+        let keys = ["a/e/", "b/e/"];
+        let by_key = [rows
+            .iter()
+            .map(|r| (r.key, r.clone()))
+            .collect::<HashMap<_, _>>()];
+        let json = json_rows(&keys, &["time".to_owned()], &by_key, &urls);
+        assert_eq!(json["a/e/"]["url"], "https://example.com/a?x=1&y=2");
+        assert!(json["b/e/"].get("url").is_none());
+        assert_eq!(json["b/e/"]["time"]["ratio"], 1.);
+
+        // The plain table doesn't show it.
+        let (table, _) = diff_table(
+            &data,
+            &data,
+            "Datum1",
+            "Datum2",
+            false,
+            DEFAULT_MIN_SAMPLES,
+            "time",
+        );
+        assert!(!table.to_string().contains("example.com"));
+    }
+
+    #[test]
+    fn datum_labels() {
+        use crate::label;
+        use crate::store::StoreKind;
+        assert_eq!(
+            label::parse("machine=lab2"),
+            Ok(("machine".to_owned(), "lab2".to_owned()))
+        );
+        assert_eq!(
+            label::parse("flags=-O2=x"),
+            Ok(("flags".to_owned(), "-O2=x".to_owned()))
+        );
+        assert_eq!(
+            label::parse("discard="),
+            Ok(("discard".to_owned(), String::new()))
+        );
+        assert!(label::parse("machine").is_err() && label::parse("=lab2").is_err());
+
+        for kind in [StoreKind::Fs, StoreKind::Sqlite] {
+            let dir = tempfile::tempdir().unwrap();
+            let app = test_app(dir.path(), kind, 0);
+            let extra = ExtraToml {
+                comment: Some("hello".to_owned()),
+                ..Default::default()
+            };
+            for _ in 0..3 {
+                app.store.store(&ResultFile::default(), &extra);
+            }
+            let set = |id, labels: &[&str], remove: &[&str]| {
+                let labels = labels
+                    .iter()
+                    .map(|l| label::parse(l).unwrap())
+                    .collect::<Vec<_>>();
+                let remove = remove.iter().map(|k| (*k).to_owned()).collect::<Vec<_>>();
+                app.cmd_label(id, &labels, &remove);
+            };
+            set(0, &["machine=lab1", "discard=true"], &[]);
+            set(1, &["machine=lab2"], &[]);
+            set(2, &["machine=lab2", "discard=true"], &[]);
+            set(2, &["machine=lab1"], &["discard"]);
+
+            // Labels replace only themselves, and nothing else in the metadata changes.
+            let extra = app.store.try_load_extra(2).unwrap();
+            assert_eq!(label::format(&extra.labels), "machine=lab1");
+            assert_eq!(extra.comment.as_deref(), Some("hello"));
+            assert_eq!(
+                label::format(&app.store.try_load_extra(0).unwrap().labels),
+                "discard=true machine=lab1"
+            );
+
+            let filter = |labels: &[&str]| {
+                let labels = labels
+                    .iter()
+                    .map(|l| label::parse(l).unwrap())
+                    .collect::<Vec<_>>();
+                app.store
+                    .ids()
+                    .into_iter()
+                    .filter(|id| label::matches(&app.store.try_load_extra(*id).unwrap(), &labels))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(filter(&[]), vec![0, 1, 2]);
+            assert_eq!(filter(&["machine=lab1"]), vec![0, 2]);
+            assert_eq!(filter(&["machine=lab1", "discard=true"]), vec![0]);
+            assert_eq!(filter(&["machine=lab3"]), Vec::<usize>::new());
+
+            app.cmd_rm(&[], &[label::parse("discard=true").unwrap()], None);
+            assert_eq!(app.store.ids(), vec![1, 2]);
+            assert!(app.store.update_extra(0, &ExtraToml::default()).is_err());
+        }
+    }
+
+    #[test]
+    fn system_readings() {
+        use crate::config::EnvWarnings;
+        use crate::environment::{self, PexecReadings, Reading};
+        let sysfs = tempfile::tempdir().unwrap();
+        let root = sysfs.path();
+        assert!(
+            environment::unreadable(root)
+                .unwrap()
+                .contains("CPU frequency and temperature")
+        );
+        assert_eq!(environment::read(root), Reading::default());
+        for (policy, khz) in [("policy0", "3000000\n"), ("policy4", "2000000\n")] {
+            let dir = root.join("devices/system/cpu/cpufreq").join(policy);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("scaling_cur_freq"), khz).unwrap();
+        }
+        assert!(
+            environment::unreadable(root)
+                .unwrap()
+                .contains("the temperature can't")
+        );
+        for (zone, temp) in [("thermal_zone0", "45000"), ("thermal_zone1", "61500")] {
+            let dir = root.join("class/thermal").join(zone);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("temp"), temp).unwrap();
+        }
+        // A zone that can't be read is skipped.
+        fs::create_dir_all(root.join("class/thermal/thermal_zone2")).unwrap();
+        assert_eq!(environment::unreadable(root), None);
+        assert_eq!(
+            environment::read(root),
+            Reading {
+                mhz: Some(2500.),
+                celsius: Some(61.5)
+            }
+        );
+
+        assert_eq!(
+            environment::spread(&[3., 1., 2., 10.]),
+            Some([1., 2.5, 10.])
+        );
+        assert_eq!(environment::spread(&[]), None);
+        let readings = |mhz: &[f64]| {
+            mhz.iter()
+                .map(|m| {
+                    let r = Reading {
+                        mhz: Some(*m),
+                        celsius: Some(50.),
+                    };
+                    PexecReadings {
+                        key: "a/x/".to_owned(),
+                        before: r,
+                        after: r,
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+        let steady = readings(&[3000., 2990., 3010., 3000.]);
+        assert_eq!(environment::frequency_drop(&steady), None);
+        let throttled = readings(&[3000., 3000., 2800., 2500.]);
+        assert_eq!(
+            environment::frequency_drop(&throttled),
+            Some((3000., 2500.))
+        );
+        assert_eq!(environment::frequency_drop(&readings(&[3000.])), None);
+        let extra1 = ExtraToml {
+            system_readings: steady,
+            ..Default::default()
+        };
+        let extra2 = ExtraToml {
+            system_readings: throttled.clone(),
+            ..Default::default()
+        };
+        assert_eq!(
+            environment::drift(&extra1, &extra2, true, &EnvWarnings::default()),
+            ["the CPU frequency fell from 3000 to 2500 MHz during the second datum's run"]
+        );
+        let table = environment::readings_table(&throttled).unwrap().to_string();
+        assert!(table.contains("2500 / 2900 / 3000") && table.contains("50.0 / 50.0 / 50.0"));
+        assert!(environment::readings_table(&[]).is_none());
+
+        // Readings are only taken when asked for, around each pexec.
+        let dir = tempfile::tempdir().unwrap();
+        let harness = dir.path().join("harness.sh");
+        fs::write(&harness, "#!/bin/sh\necho PEXEC_WALLCLOCK_MS=1 > \"$1\"\n").unwrap();
+        fs::set_permissions(&harness, fs::Permissions::from_mode(0o755)).unwrap();
+        for sample in [false, true] {
+            let config = toml::from_str::<crate::config::Config>(&format!(
+                r#"
+                proc_execs = 2
+                inproc_iters = 1
+                sample_system = {sample}
+                executors = {{ e = "/bin/true" }}
+
+                [suites.s]
+                dir = "{}"
+                harness = "./harness.sh"
+                benchmarks = {{ fib = {{}} }}
+                "#,
+                dir.path().display()
+            ))
+            .unwrap();
+            let outcome = runner::run(&config, &runner::RunOptions::default());
+            let keys = outcome
+                .system_readings
+                .iter()
+                .map(|r| r.key.as_str())
+                .collect::<Vec<_>>();
+            // Nothing is recorded for pexecs around which this machine's sysfs couldn't be read.
+            if sample {
+                assert!(keys.len() <= 2 && keys.iter().all(|k| *k == "fib/e/"));
+            } else {
+                assert!(keys.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn unequal_sample_counts() {
+        use crate::DiffOptions;
+        let dir = tempfile::tempdir().unwrap();
+        let app = test_app(dir.path(), crate::store::StoreKind::Fs, 0);
+        let results = |counts: &[(&str, u32)]| ResultFile {
+            data: counts
+                .iter()
+                .map(|(k, n)| {
+                    (
+                        (*k).to_owned(),
+                        (0..*n).map(|i| 10. + f64::from(i)).collect(),
+                    )
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let same = results(&[("a/e/", 10), ("b/e/", 10)]);
+        let fewer = results(&[("a/e/", 10), ("b/e/", 6)]);
+        let missing = results(&[("a/e/", 10)]);
+        let none = HashMap::new();
+
+        assert_eq!(same.same_dims(&same, &none, &none, false), Ok(vec![]));
+        assert_eq!(
+            same.same_dims(&fewer, &none, &none, false),
+            Ok(vec!["b/e/".to_owned()])
+        );
+        assert!(
+            same.same_dims(&fewer, &none, &none, true)
+                .unwrap_err()
+                .starts_with("different number of process executions for b/e/: 10 vs. 6")
+        );
+        assert!(
+            same.same_dims(&missing, &none, &none, false)
+                .unwrap_err()
+                .starts_with("results files contain different benchmarks")
+        );
+
+        let [s, f, m] =
+            [&same, &fewer, &missing].map(|r| app.store.store(r, &ExtraToml::default()));
+        let diff = |id1, id2, relaxed, strict_dims| {
+            let opts = DiffOptions {
+                relaxed,
+                deterministic: true,
+                env_warnings: false,
+                strict_dims,
+                ..Default::default()
+            };
+            let mut out = Vec::new();
+            app.diff_to(
+                &DatumRef::local(id1),
+                &DatumRef::local(id2),
+                &opts,
+                &mut out,
+                false,
+            )
+            .map(|()| String::from_utf8(out).unwrap())
+        };
+        // Identical dimensions, and different counts, compare by default; only --strict-dims
+        // refuses the latter.
+        let out = diff(s, s, false, false).unwrap();
+        assert!(!out.contains("different numbers of pexecs"), "{out}");
+        let out = diff(s, f, false, false).unwrap();
+        assert!(
+            out.contains(&format!(
+                "WARNING: Datum{s} and Datum{f} have different numbers of pexecs of b/e/"
+            )),
+            "{out}"
+        );
+        assert!(out.contains(" b/e/        10/6 "), "{out}");
+        assert!(diff(s, f, false, true).is_err());
+        // Different benchmarks need --relaxed.
+        let e = diff(s, m, false, false).unwrap_err();
+        assert!(
+            e.ends_with("use --relaxed to compare the benchmarks both have"),
+            "{e}"
+        );
+        let out = diff(s, m, true, false).unwrap();
+        assert!(out.contains(&format!("only in Datum{s}: b/e/\n")), "{out}");
+        assert!(out.contains(" a/e/ "), "{out}");
+    }
+
+    #[test]
+    fn harness_probe() {
+        use crate::TIME_METRIC;
+        use crate::probe::{self, Capabilities};
+
+        let v1 = |metrics: &[&str]| Capabilities {
+            protocol: 1,
+            metrics: metrics.iter().map(|m| m.to_string()).collect(),
+            ..Default::default()
+        };
+        assert_eq!(
+            probe::parse("protocol = 1\nmetrics = [\"rss\"]"),
+            Some(v1(&["rss"]))
+        );
+        assert_eq!(
+            probe::parse(r#"{"protocol": 1, "metrics": ["rss"]}"#),
+            Some(v1(&["rss"]))
+        );
+        assert_eq!(probe::parse(""), None);
+        assert_eq!(probe::parse("usage: harness <out> <executor> ..."), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        let scripts = [
+            ("toml.sh", "echo protocol = 1; echo 'metrics = [\"rss\"]'"),
+            ("json.sh", "echo '{\"protocol\": 2, \"metrics\": []}'"),
+            // A harness from before the handshake, which can't make sense of the argument.
+            ("old.sh", "echo \"can't open $1\" >&2; exit 1"),
+        ];
+        for (name, body) in scripts {
+            let harness = dir.path().join(name);
+            fs::write(
+                &harness,
+                format!("#!/bin/sh\necho $0 >> probes.log\n{body}\n"),
+            )
+            .unwrap();
+            fs::set_permissions(&harness, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let config = toml::from_str::<crate::config::Config>(&format!(
+            r#"
+            proc_execs = 1
+            inproc_iters = 1
+            executors = {{ e1 = "/bin/true", e2 = "/bin/true" }}
+
+            [suites.new]
+            dir = "{0}"
+            harness = "./toml.sh"
+            benchmarks = {{ fib = {{}} }}
+
+            [suites.newer]
+            dir = "{0}"
+            harness = "./json.sh"
+            benchmarks = {{ fib = {{ executors = ["e1"] }} }}
+
+            [suites.old]
+            dir = "{0}"
+            harness = "./old.sh"
+            benchmarks = {{ fib = {{}} }}
+
+            [suites.commands]
+            dir = "{0}"
+            benchmarks = {{ ls = {{ command = "ls" }} }}
+            "#,
+            dir.path().display()
+        ))
+        .unwrap();
+        let harnesses = probe::probe_suites(&config);
+        let caps = |suite: &str, executor: &str| harnesses.get(suite)?.get(executor).cloned();
+        assert_eq!(caps("new", "e1"), Some(v1(&["rss"])));
+        assert_eq!(caps("new", "e2"), Some(v1(&["rss"])));
+        assert_eq!(caps("newer", "e1").map(|c| c.protocol), Some(2));
+        assert_eq!(caps("newer", "e2"), None);
+        assert_eq!(caps("old", "e1"), Some(Capabilities::default()));
+        assert!(!harnesses.contains_key("commands"));
+        // Each suite's harness is probed once, however many executors run it.
+        let log = fs::read_to_string(dir.path().join("probes.log")).unwrap();
+        assert_eq!(log.lines().count(), 3);
+
+        // Only harnesses that handshook are held to what they said.
+        assert_eq!(probe::unsupported("rss", &harnesses), ["newer (e1)"]);
+        assert_eq!(
+            probe::unsupported("gc", &harnesses),
+            ["new (e1)", "new (e2)", "newer (e1)"]
+        );
+        // haste measures these itself.
+        assert!(probe::unsupported(TIME_METRIC, &harnesses).is_empty());
+        assert!(probe::unsupported("instructions", &harnesses).is_empty());
+    }
+
+    #[test]
+    fn row_order() {
+        use crate::order::{self, RowOrder, SortBy, glob_match};
+        use crate::rows_table;
+
+        assert!(glob_match("fib/jit/", "fib/jit/"));
+        assert!(glob_match("fib/*", "fib/jit/10"));
+        assert!(glob_match("*/jit/*", "fib/jit/"));
+        assert!(glob_match("f?b*", "fib/jit/"));
+        assert!(glob_match("*b*t*", "fib/jit/"));
+        assert!(!glob_match("fib", "fib/jit/"));
+        assert!(!glob_match("*/interp/", "fib/jit/"));
+
+        let stats = |v: &[(&str, f64, f64)]| {
+            v.iter()
+                .map(|(k, mean, ci)| (k.to_string(), SummaryStats::new(*mean, *ci, 10)))
+                .collect::<HashMap<_, _>>()
+        };
+        let data1 = stats(&[
+            ("a/e/", 100., 1.),
+            ("b/e/", 100., 1.),
+            ("c/e/", 100., 1.),
+            ("d/e/", 100., 1.),
+        ]);
+        let data2 = stats(&[
+            ("a/e/", 110., 5.),
+            ("b/e/", 50., 1.),
+            ("c/e/", 101., 9.),
+            ("d/e/", 80., 2.),
+        ]);
+        let keys = |order: &RowOrder| {
+            let rows = diff_rows(&data1, &data2, 5, false);
+            let rows = order::arrange(rows, |r| r.key, |r| Some(r), order);
+            let keys = rows.rows.iter().map(|r| r.key).collect::<Vec<_>>();
+            (keys, rows.pinned, rows.unmatched)
+        };
+        // By default, significant improvements come first, then the indistinguishable.
+        let default = keys(&RowOrder::default());
+        assert_eq!(default.0, ["b/e/", "d/e/", "a/e/", "c/e/"]);
+        let sorted = |sort_by, reverse| {
+            keys(&RowOrder {
+                sort_by,
+                reverse,
+                ..Default::default()
+            })
+            .0
+        };
+        assert_eq!(sorted(None, true), ["c/e/", "a/e/", "d/e/", "b/e/"]);
+        assert_eq!(
+            sorted(Some(SortBy::Name), false),
+            ["a/e/", "b/e/", "c/e/", "d/e/"]
+        );
+        assert_eq!(
+            sorted(Some(SortBy::Ratio), true),
+            ["a/e/", "c/e/", "d/e/", "b/e/"]
+        );
+        assert_eq!(
+            sorted(Some(SortBy::Delta), false),
+            ["b/e/", "d/e/", "c/e/", "a/e/"]
+        );
+        assert_eq!(
+            sorted(Some(SortBy::CiWidth), true),
+            ["c/e/", "a/e/", "d/e/", "b/e/"]
+        );
+
+        // Pinned rows come first, in the order of the patterns, and the rest are sorted as asked.
+        let pinned = keys(&RowOrder {
+            pins: vec!["c/*".to_owned(), "nope/*".to_owned(), "*".to_owned()],
+            sort_by: Some(SortBy::Name),
+            reverse: true,
+        });
+        assert_eq!(pinned.0, ["c/e/", "d/e/", "b/e/", "a/e/"]);
+        assert_eq!(pinned.1, 4);
+        assert_eq!(pinned.2, ["nope/*"]);
+        let pinned = keys(&RowOrder {
+            pins: vec!["a/e/".to_owned(), "c/e/".to_owned()],
+            ..Default::default()
+        });
+        assert_eq!(pinned.0, ["a/e/", "c/e/", "b/e/", "d/e/"]);
+        assert_eq!(pinned.1, 2);
+        assert!(pinned.2.is_empty());
+        assert_eq!(
+            order::unmatched_warning(&["x".to_owned(), "y".to_owned()]).as_deref(),
+            Some("--pin matched no benchmark: x, y")
+        );
+
+        // A blank line separates the pinned rows, which keep their colours, from the rest.
+        let rows = diff_rows(&data1, &data2, 5, false);
+        let rows = order::arrange(
+            rows,
+            |r| r.key,
+            |r| Some(r),
+            &RowOrder {
+                pins: vec!["a/e/".to_owned()],
+                ..Default::default()
+            },
+        );
+        let (mut table, regressions) = rows_table(
+            &rows.rows,
+            "D1",
+            "D2",
+            false,
+            false,
+            rows.pinned,
+            &HashMap::new(),
+        );
+        table.force_no_tty();
+        assert_eq!(regressions, 1);
+        let lines = table
+            .to_string()
+            .lines()
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        assert!(lines[1].trim_start().starts_with("a/e/"), "{lines:?}");
+        assert!(lines[2].trim().is_empty());
+        assert!(lines[3].trim_start().starts_with("b/e/"));
+
+        // The JSON says in which order the rows were shown.
+        let by_key = [rows
+            .rows
+            .iter()
+            .map(|r| (r.key, r.clone()))
+            .collect::<HashMap<_, _>>()];
+        let order = rows.rows.iter().map(|r| r.key).collect::<Vec<_>>();
+        let json =
+            crate::multidiff::json_rows(&order, &["time".to_owned()], &by_key, &HashMap::new());
+        assert_eq!(json["a/e/"]["display_order"], 0);
+        assert_eq!(json["b/e/"]["display_order"], 1);
+        assert_eq!(json["c/e/"]["display_order"], 3);
+    }
+
+    #[test]
+    fn bisect_search() {
+        use crate::bisect::{Search, parse_threshold, regressions};
+        use crate::{Failure, TIME_METRIC, git};
+
+        assert_eq!(parse_threshold("3%"), Ok(3.));
+        assert_eq!(parse_threshold("2.5"), Ok(2.5));
+        assert!(parse_threshold("-1%").is_err());
+
+        // Run a search over 10 commits, the first bad one of which is `first_bad`, returning the
+        // commits tested and the result.
+        let search = |first_bad: usize, skipped: &[usize]| {
+            let mut search = Search::new(10, skipped.iter().copied().collect());
+            let mut tested = Vec::new();
+            while let Some(i) = search.next() {
+                tested.push(i);
+                assert!(tested.len() <= 10);
+                if skipped.contains(&i) {
+                    unreachable!("tested skipped commit {i}");
+                } else if i >= first_bad {
+                    search.bad(i);
+                } else {
+                    search.good(i);
+                }
+            }
+            (tested, search.result())
+        };
+        for first_bad in 0..10 {
+            let (tested, result) = search(first_bad, &[]);
+            assert_eq!(result, Ok(first_bad));
+            assert!(tested.len() <= 4, "{tested:?}");
+        }
+        assert_eq!(search(6, &[]).0, [4, 7, 6, 5]);
+        // Skipped commits are stepped around, unless they might be the first bad one.
+        assert_eq!(search(6, &[4]).1, Ok(6));
+        assert_eq!(search(6, &[5]).1, Err(vec![5, 6]));
+        let never = Search::new(3, [0, 1].into_iter().collect());
+        assert_eq!(never.next(), None);
+        assert_eq!(never.result(), Err(vec![0, 1, 2]));
+        assert_eq!(Search::new(1, Default::default()).next(), None);
+
+        // A commit is bad if anything is significantly worse by more than the threshold.
+        let results = |means: &[(&str, f64)]| ResultFile {
+            data: means
+                .iter()
+                .map(|(k, m)| (k.to_string(), vec![*m, m * 1.001, m * 0.999, *m, *m, *m]))
+                .collect(),
+            ..Default::default()
+        };
+        let good = results(&[("a/e/", 100.), ("b/e/", 100.)]);
+        let check = |candidate: &ResultFile, threshold| {
+            regressions(
+                &good,
+                candidate,
+                TIME_METRIC,
+                threshold,
+                ConfidenceLevel::CL99,
+            )
+        };
+        let slower = results(&[("a/e/", 104.), ("b/e/", 90.), ("c/e/", 500.)]);
+        assert_eq!(check(&slower, 3.), Ok(vec!["a/e/ (+4.0%)".to_owned()]));
+        assert_eq!(check(&slower, 5.), Ok(vec![]));
+        let mut failed = slower.clone();
+        failed.failures.insert(
+            "b/e/".to_owned(),
+            Failure {
+                count: 1,
+                status: "1".to_owned(),
+                stderr: String::new(),
+            },
+        );
+        assert!(
+            check(&failed, 3.)
+                .unwrap_err()
+                .starts_with("benchmarks failed: b/e/")
+        );
+        assert!(check(&results(&[("z/e/", 1.)]), 3.).is_err());
+
+        // Commits are found, and checked out in a worktree, without touching the repository's
+        // own tree.
+        let repo = tempfile::tempdir().unwrap();
+        let run = |args: &[&str]| {
+            let status = Command::new("git")
+                .args(["-c", "user.name=t", "-c", "user.email=t@t", "-C"])
+                .arg(repo.path())
+                .args(args)
+                .stdout(std::process::Stdio::null())
+                .status()
+                .unwrap();
+            assert!(status.success(), "git {args:?}");
+        };
+        run(&["init", "-q"]);
+        for i in 0..4 {
+            fs::write(repo.path().join("n"), i.to_string()).unwrap();
+            run(&["add", "n"]);
+            run(&["commit", "-q", "-m", &format!("commit {i}")]);
+        }
+        let good = git::resolve(repo.path(), "HEAD~3").unwrap();
+        let commits = git::commits_between(repo.path(), &good, "HEAD").unwrap();
+        assert_eq!(commits.len(), 3);
+        assert_eq!(commits[2], git::resolve(repo.path(), "HEAD").unwrap());
+        assert!(
+            git::commits_between(repo.path(), "HEAD", &good)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(git::resolve(repo.path(), "nope").is_err());
+        let worktree_dir = {
+            let worktree = git::Worktree::add(repo.path(), &good).unwrap();
+            assert_eq!(fs::read_to_string(worktree.path().join("n")).unwrap(), "0");
+            worktree.checkout(&commits[1]).unwrap();
+            assert_eq!(fs::read_to_string(worktree.path().join("n")).unwrap(), "2");
+            assert!(
+                git::describe(worktree.path(), "HEAD")
+                    .unwrap()
+                    .ends_with(" \"commit 2\"")
+            );
+            worktree.path().to_owned()
+        };
+        assert!(!worktree_dir.exists());
+        assert_eq!(fs::read_to_string(repo.path().join("n")).unwrap(), "3");
+    }
+
+    #[test]
+    fn benchmark_targets() {
+        use crate::TIME_METRIC;
+        use crate::targets::{self, Direction, Status};
+
+        let targets = targets::parse(
+            r#"
+            [[target]]
+            benchmark = "fasta/*"
+            value = 800
+            direction = "below"
+
+            [[target]]
+            benchmark = "*/jit/"
+            value = 1000
+            direction = "above"
+            metric = "ips"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].metric, TIME_METRIC);
+        assert_eq!(targets[1].direction, Direction::Above);
+        assert_eq!(targets[0].to_string(), "< 800 ms");
+        assert_eq!(targets[1].to_string(), "ips > 1000 iters/s");
+        assert!(targets::parse("").unwrap().is_empty());
+        for bad in [
+            "[[target]]\nbenchmark = \"a\"\nvalue = 1\ndirection = \"sideways\"",
+            "[[target]]\nbenchmark = \"a\"\ndirection = \"below\"",
+            "[[target]]\nbenchmark = \"a\"\nvalue = 1\ndirection = \"below\"\nmax = 2",
+            "[[target]]\nbenchmark = \"a\"\nvalue = nan\ndirection = \"below\"",
+        ] {
+            assert!(targets::parse(bad).is_err(), "{bad}");
+        }
+
+        // Patterns match whole keys.
+        assert!(targets[0].matches("fasta/jit/"));
+        assert!(!targets[0].matches("nfasta/jit/"));
+        assert!(targets[1].matches("richards/jit/"));
+        assert!(!targets[1].matches("richards/jit/10"));
+
+        // Whether the confidence interval is within, straddles or is outside the target.
+        let s = |mean| SummaryStats::new(mean, 10., 10);
+        assert_eq!(targets[0].evaluate(&s(700.)), Status::Pass);
+        assert_eq!(targets[0].evaluate(&s(790.)), Status::Pass);
+        assert_eq!(targets[0].evaluate(&s(795.)), Status::Warn);
+        assert_eq!(targets[0].evaluate(&s(805.)), Status::Warn);
+        assert_eq!(targets[0].evaluate(&s(811.)), Status::Fail);
+        assert_eq!(targets[1].evaluate(&s(1010.)), Status::Pass);
+        assert_eq!(targets[1].evaluate(&s(1000.)), Status::Warn);
+        assert_eq!(targets[1].evaluate(&s(989.)), Status::Fail);
+
+        // A diff notes the benchmarks which cross a target of the metric compared.
+        let stats = |means: &[(&str, f64)]| {
+            means
+                .iter()
+                .map(|(k, m)| (k.to_string(), s(*m)))
+                .collect::<HashMap<_, _>>()
+        };
+        let data1 = stats(&[
+            ("fasta/jit/", 700.),
+            ("fasta/interp/", 900.),
+            ("x/jit/", 1.),
+        ]);
+        let data2 = stats(&[
+            ("fasta/jit/", 900.),
+            ("fasta/interp/", 950.),
+            ("x/jit/", 1.),
+        ]);
+        let notes = targets::crossings(&targets, TIME_METRIC, &data1, &data2);
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes["fasta/jit/"], " [< 800 ms: pass -> FAIL]");
+        assert!(targets::crossings(&targets, "ips", &data1, &data2).is_empty());
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut app = test_app(dir.path(), crate::store::StoreKind::Fs, 0);
+        app.config_file = dir.path().join("bench").join("haste.toml");
+        assert_eq!(
+            app.default_targets_file(),
+            dir.path().join("bench").join("targets.toml")
+        );
+    }
+
+    #[test]
+    fn staged_datums() {
+        use crate::store::StoreKind;
+        use crate::{ShowOptions, TIME_METRIC};
+        let dir = tempfile::tempdir().unwrap();
+        let app = test_app(dir.path(), StoreKind::Fs, 0);
+        let results = ResultFile {
+            data: HashMap::from([("a/e/".to_owned(), vec![1., 2., 3.])]),
+            ..Default::default()
+        };
+        assert_eq!(app.store.store(&results, &ExtraToml::default()), 0);
+        assert!(!dir.path().join(".tmp-0").exists());
+
+        // Simulate a run that's part way through writing datum 1.
+        let staging = dir.path().join(".tmp-1");
+        fs::create_dir(&staging).unwrap();
+        fs::write(staging.join("data.toml"), "[data]\n\"a/e/\" = [1.0, 2").unwrap();
+        assert_eq!(app.store.ids(), [0]);
+        assert!(!app.store.contains(1));
+        app.cmd_list(true, &[], None);
+        app.cmd_show(
+            0,
+            &ShowOptions {
+                confidence: ConfidenceLevel::CL99,
+                thousands: false,
+                raw: true,
+                subtract_overhead: false,
+                metric: TIME_METRIC.to_owned(),
+                histogram_bins: None,
+                describe: false,
+                env: false,
+                output: Default::default(),
+            },
+        );
+        // fsck exits if any datum is broken.
+        app.cmd_fsck(false);
+
+        // The staged ID stays claimed, so a concurrent run gets the next one.
+        assert_eq!(app.store.store(&results, &ExtraToml::default()), 2);
+        assert_eq!(app.store.ids(), [0, 2]);
+        fs::remove_dir_all(&staging).unwrap();
+        assert_eq!(app.store.store(&results, &ExtraToml::default()), 3);
+    }
+
+    #[test]
+    fn human_units() {
+        use crate::mean_ci_formats;
+        use crate::units::Scale;
+        let golden = [
+            (0.4, Scale::Millis, "0ms"),
+            (384.2, Scale::Millis, "384ms"),
+            (1202., Scale::Secs, "1.2s"),
+            (24_250., Scale::Secs, "24.2s"),
+            (384_212., Scale::Mins, "6m 24.2s"),
+            (59_960., Scale::Mins, "1m 00.0s"),
+            (3_984_400., Scale::Hours, "1h 06m 24s"),
+            (-90_000_000., Scale::Hours, "-25h 00m 00s"),
+        ];
+        for (ms, scale, s) in golden {
+            assert_eq!(Scale::for_max(ms), scale);
+            assert_eq!(scale.render(ms, 0, false), s);
+        }
+        assert_eq!(Scale::Mins.render(5000., 0, true), "+0m 05.0s");
+        assert_eq!(Scale::Millis.render(0.52, 2, false), "0.52ms");
+
+        // Every value in a column has the shape of the largest, and the same width.
+        let means = [384_212., 5_000., 661_000.];
+        let fmt = NumFormat::new(&means, 0, false, false).human(&means);
+        assert_eq!(
+            means.map(|m| fmt.format(m)),
+            [" 6m 24.2s", " 0m 05.0s", "11m 01.0s"]
+        );
+        let cis = [1202., 80.];
+        let (mean_fmt, ci_fmt) = mean_ci_formats(
+            [
+                SummaryStats::new(384_212., 1202., 10),
+                SummaryStats::new(5_000., 80., 10),
+            ]
+            .iter(),
+            false,
+            true,
+        );
+        assert_eq!(mean_fmt, fmt.fit(&means[..2]));
+        assert_eq!(cis.map(|c| ci_fmt.format(c)), ["1.2s", "0.1s"]);
+        // Infinities aren't durations.
+        assert_eq!(fmt.format(f64::INFINITY), "      inf");
+        let hours = [7_200_000., 3_984_400.];
+        let fmt = NumFormat::new(&hours, 0, true, false).human(&hours);
+        assert_eq!(hours.map(|h| fmt.format(h)), ["+2h 00m 00s", "+1h 06m 24s"]);
+    }
+
+    #[test]
+    fn benchmark_dependencies() {
+        use crate::config::Config;
+        use crate::runner::{Order, RunOptions, planned_pexecs};
+        let parse = |benchmarks: &str| {
+            toml::from_str::<Config>(&format!(
+                r#"
+                proc_execs = 2
+                inproc_iters = 1
+                executors = {{ e = "shell" }}
+                [suites.t]
+                dir = "."
+                benchmarks = {{ gen = {{ command = "true", resources = ["port8080"] }} }}
+                [suites.s]
+                dir = "."
+                [suites.s.benchmarks]
+                {benchmarks}
+                "#
+            ))
+            .unwrap()
+        };
+        let config = parse(
+            r#"a = { command = "true", after = ["b"], resources = ["port8080"] }
+               b = { command = "true", after = ["gen"] }
+               c = { command = "true" }"#,
+        );
+        config.validate().unwrap();
+        let plan = |order| {
+            let opts = RunOptions {
+                order: Some(order),
+                seed: 1,
+                ..Default::default()
+            };
+            planned_pexecs(&config, &opts)
+                .into_iter()
+                .map(|(k, n)| format!("{k}{n}"))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            plan(Order::Grouped),
+            [
+                "c/e/0", "c/e/1", "gen/e/0", "gen/e/1", "b/e/0", "b/e/1", "a/e/0", "a/e/1"
+            ]
+        );
+        // Round-robin delays dependent pexecs until their dependencies have finished.
+        assert_eq!(
+            plan(Order::RoundRobin),
+            [
+                "c/e/0", "gen/e/0", "c/e/1", "gen/e/1", "b/e/0", "b/e/1", "a/e/0", "a/e/1"
+            ]
+        );
+        for seed in 0..20 {
+            let opts = RunOptions {
+                order: Some(Order::Shuffle),
+                seed,
+                ..Default::default()
+            };
+            let keys = planned_pexecs(&config, &opts)
+                .into_iter()
+                .map(|(k, _)| k)
+                .collect::<Vec<_>>();
+            assert_eq!(keys.len(), 8);
+            let first = |k: &str| keys.iter().position(|x| x == k).unwrap();
+            let last = |k: &str| keys.iter().rposition(|x| x == k).unwrap();
+            assert!(last("gen/e/") < first("b/e/"));
+            assert!(last("b/e/") < first("a/e/"));
+        }
+
+        assert_eq!(
+            parse(
+                r#"a = { command = "true", after = ["b"] }
+               b = { command = "true", after = ["a"] }"#
+            )
+            .validate()
+            .unwrap_err(),
+            "benchmarks must run after each other in a cycle: a -> b -> a"
+        );
+        assert_eq!(
+            parse(r#"a = { command = "true", after = ["a"] }"#)
+                .validate()
+                .unwrap_err(),
+            "benchmarks must run after each other in a cycle: a -> a"
+        );
+        assert_eq!(
+            parse(r#"a = { command = "true", after = ["nope"] }"#)
+                .validate()
+                .unwrap_err(),
+            "suite s: benchmark a runs after unknown benchmark nope"
+        );
+        assert!(
+            parse(r#"a = { command = "true", resources = [""] }"#)
+                .validate()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn ratio_confidence_interval() {
+        use crate::{RatioCi, SignificanceTest, ratio_ci, rows_table, tested_diff_rows};
+        // Without any uncertainty in the baseline, the interval is the new one scaled.
+        let ci = ratio_ci(
+            &SummaryStats::new(100., 0., 10),
+            &SummaryStats::new(103., 2., 10),
+        );
+        assert_eq!(ci, Some(RatioCi::Bounds(1.01, 1.05)));
+        let Some(RatioCi::Bounds(lo, hi)) = ratio_ci(
+            &SummaryStats::new(20., 1., 10),
+            &SummaryStats::new(10., 1., 10),
+        ) else {
+            panic!();
+        };
+        assert!((lo - 0.4453).abs() < 1e-4 && (hi - 0.5572).abs() < 1e-4);
+        // The interval widens, and moves away from the ratio of the means, as the baseline's
+        // interval approaches its mean, until it's unstable.
+        let Some(RatioCi::Bounds(lo, hi)) = ratio_ci(
+            &SummaryStats::new(10., 4., 10),
+            &SummaryStats::new(10., 1., 10),
+        ) else {
+            panic!();
+        };
+        assert!(lo > 0.5 && hi > 1.6 && (lo + hi) / 2. > 1.);
+        assert_eq!(
+            ratio_ci(
+                &SummaryStats::new(10., 5., 10),
+                &SummaryStats::new(10., 1., 10)
+            ),
+            Some(RatioCi::Unstable)
+        );
+        assert_eq!(
+            ratio_ci(
+                &SummaryStats::new(0., 1., 10),
+                &SummaryStats::new(10., 1., 10)
+            ),
+            None
+        );
+
+        // The intervals overlap, but the ratio's excludes 1.
+        let data1 = HashMap::from([
+            ("a/e/".to_owned(), SummaryStats::new(100., 2., 10)),
+            ("b/e/".to_owned(), SummaryStats::new(10., 6., 10)),
+        ]);
+        let data2 = HashMap::from([
+            ("a/e/".to_owned(), SummaryStats::new(103.5, 2., 10)),
+            ("b/e/".to_owned(), SummaryStats::new(30., 1., 10)),
+        ]);
+        let significant = |test| {
+            let mut rows = tested_diff_rows(&data1, &data2, DEFAULT_MIN_SAMPLES, false, test)
+                .into_iter()
+                .map(|r| (r.key, r.significant))
+                .collect::<Vec<_>>();
+            rows.sort();
+            rows
+        };
+        assert_eq!(
+            significant(SignificanceTest::Overlap),
+            [("a/e/", false), ("b/e/", true)]
+        );
+        // b's baseline is too uncertain for its ratio to have an interval.
+        assert_eq!(
+            significant(SignificanceTest::RatioCi),
+            [("a/e/", true), ("b/e/", false)]
+        );
+        let rows = tested_diff_rows(
+            &data1,
+            &data2,
+            DEFAULT_MIN_SAMPLES,
+            false,
+            SignificanceTest::RatioCi,
+        );
+        let (table, _) = rows_table(&rows, "D1", "D2", false, false, 0, &HashMap::new());
+        let table = table.to_string();
+        assert!(table.contains("1.03 ±0.03"), "{table}");
+        assert!(table.contains("3.00 unstable"), "{table}");
+        let by_key = rows
+            .into_iter()
+            .map(|r| (r.key, r))
+            .collect::<HashMap<_, _>>();
+        let json = crate::multidiff::json_rows(
+            &["a/e/", "b/e/"],
+            &["time".to_owned()],
+            &[by_key],
+            &HashMap::new(),
+        );
+        assert!(json["a/e/"]["time"]["ratio_lo"].as_f64().unwrap() > 1.);
+        assert!(json["b/e/"]["time"]["ratio_hi"].is_null());
+    }
+
+    #[test]
+    fn output_target() {
+        use crate::output::OutputTarget;
+        use crate::store::StoreKind;
+        use crate::{ShowOptions, TIME_METRIC};
+        let dir = tempfile::tempdir().unwrap();
+        let app = test_app(dir.path(), StoreKind::Fs, 0);
+        let results = ResultFile {
+            data: HashMap::from([("a/e/".to_owned(), vec![1., 2., 3.])]),
+            ..Default::default()
+        };
+        let extra = ExtraToml {
+            comment: Some("baseline".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(app.store.store(&results, &extra), 0);
+        let path = dir.path().join("out/nested/show.txt");
+        let output = OutputTarget {
+            path: Some(path.clone()),
+            overwrite: false,
+            quiet: true,
+        };
+        let opts = ShowOptions {
+            confidence: ConfidenceLevel::CL99,
+            thousands: false,
+            raw: false,
+            subtract_overhead: false,
+            metric: TIME_METRIC.to_owned(),
+            histogram_bins: None,
+            describe: false,
+            env: false,
+            output: output.clone(),
+        };
+
+        // Renderers write anywhere, such as to a buffer.
+        let mut buf = Vec::new();
+        app.show(0, &opts, &mut buf, false).unwrap();
+        let shown = String::from_utf8(buf).unwrap();
+        assert!(shown.starts_with("Datum0: baseline\n"), "{shown}");
+        assert!(shown.contains(" a/e/ "), "{shown}");
+
+        // The file's directories are made, and it has what was shown, without colour.
+        assert!(output.check().is_ok());
+        app.cmd_show(0, &opts);
+        assert_eq!(fs::read_to_string(&path).unwrap(), shown);
+
+        // An existing file is only replaced if asked.
+        assert!(output.check().unwrap_err().contains("--overwrite"));
+        let replace = |overwrite| {
+            OutputTarget {
+                overwrite,
+                ..output.clone()
+            }
+            .to_file(|out| out.write_all(b"replaced"))
+        };
+        assert!(replace(false).unwrap_err().contains("already exists"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), shown);
+        replace(true).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "replaced");
+    }
+
+    #[test]
+    fn duplicate_datums() {
+        use crate::{duplicate_warning, identical_samples};
+        let data = |vs: &[(&str, &[f64])]| {
+            vs.iter()
+                .map(|(k, v)| ((*k).to_owned(), v.to_vec()))
+                .collect::<HashMap<_, _>>()
+        };
+        let d1 = data(&[("a/e/", &[1., 2.]), ("b/e/", &[3., 4.]), ("c/e/", &[5.])]);
+
+        // A full copy.
+        let (identical, common) = identical_samples(&d1, &d1.clone());
+        assert_eq!(identical, ["a/e/", "b/e/", "c/e/"]);
+        assert_eq!(common, 3);
+        assert_eq!(
+            duplicate_warning("Datum1", "Datum2", &identical, common, 50., &["start time"])
+                .unwrap(),
+            "Datum1 and Datum2 may be copies of each other: the samples of all 3 benchmarks they \
+             share are identical\nWARNING: they also have the same start time"
+        );
+
+        // A partial copy, e.g. of a rerun aborted after its first benchmarks. Samples must be the
+        // same in order and number, and benchmarks only in one datum don't count.
+        let d2 = data(&[
+            ("a/e/", &[1., 2.]),
+            ("b/e/", &[3., 4.]),
+            ("c/e/", &[5., 5.]),
+            ("d/e/", &[6.]),
+        ]);
+        let (identical, common) = identical_samples(&d1, &d2);
+        assert_eq!(identical, ["a/e/", "b/e/"]);
+        assert_eq!(common, 3);
+        assert_eq!(
+            duplicate_warning("Datum1", "Datum2", &identical, common, 50., &[]).unwrap(),
+            "Datum1 and Datum2 may be copies of each other: the samples of 2 of the 3 benchmarks \
+             they share are identical: a/e/, b/e/"
+        );
+        assert_eq!(
+            duplicate_warning("Datum1", "Datum2", &identical, common, 70., &[]),
+            None
+        );
+
+        let d3 = data(&[("a/e/", &[1., 2.000001]), ("b/e/", &[4., 3.])]);
+        let (identical, common) = identical_samples(&d1, &d3);
+        assert_eq!((identical.len(), common), (0, 2));
+        assert_eq!(
+            duplicate_warning("Datum1", "Datum3", &identical, common, 0., &[]),
+            None
+        );
+    }
+
+    #[test]
+    fn warmup_pexecs() {
+        use crate::config::{Config, WarmupPexecs};
+        let dir = tempfile::tempdir().unwrap();
+        let config_content = |benchmarks: &str| {
+            format!(
+                r#"
+                proc_execs = 2
+                inproc_iters = 1
+                executors = {{ interp = "shell", jit = "shell" }}
+                [suites.s]
+                dir = "{}"
+                warmup_pexecs = 1
+                [suites.s.benchmarks]
+                {benchmarks}
+                "#,
+                dir.path().display()
+            )
+        };
+        let command = r#"command = "echo $HASTE_BENCHMARK/$HASTE_EXECUTOR >> log""#;
+        let config: Config = toml::from_str(&config_content(&format!(
+            "a = {{ {command}, warmup_pexecs = {{ jit = 2 }} }}\nb = {{ {command}, warmup_pexecs = 0 }}\n\
+             c = {{ {command} }}"
+        )))
+        .unwrap();
+        config.validate().unwrap();
+        let (suite, a) = (&config.suites["s"], &config.suites["s"].benchmarks["a"]);
+        assert_eq!(
+            a.warmup_pexecs,
+            Some(WarmupPexecs::PerExecutor(HashMap::from([(
+                "jit".to_owned(),
+                2
+            )])))
+        );
+        // Executors the benchmark's map doesn't list fall back to the suite's setting.
+        assert_eq!(config.warmup_pexecs_for(suite, a, "jit"), 2);
+        assert_eq!(config.warmup_pexecs_for(suite, a, "interp"), 1);
+
+        let outcome = runner::run(&config, &runner::RunOptions::default());
+        let log = fs::read_to_string(dir.path().join("log")).unwrap();
+        let count = |key: &str| log.lines().filter(|l| *l == key).count();
+        assert_eq!(
+            [
+                count("a/interp"),
+                count("a/jit"),
+                count("b/interp"),
+                count("b/jit")
+            ],
+            [3, 4, 2, 2]
+        );
+        assert_eq!(count("c/jit"), 3);
+        // Only the measured pexecs are recorded, with the warmups alongside them.
+        assert!(outcome.results.data.values().all(|d| d.len() == 2));
+        let mut warmups = outcome.warmup_pexecs.into_iter().collect::<Vec<_>>();
+        warmups.sort();
+        assert_eq!(
+            warmups,
+            [
+                ("a/interp/".to_owned(), 1),
+                ("a/jit/".to_owned(), 2),
+                ("c/interp/".to_owned(), 1),
+                ("c/jit/".to_owned(), 1)
+            ]
+        );
+
+        let config: Config = toml::from_str(&config_content(&format!(
+            "a = {{ {command}, warmup_pexecs = {{ pypy = 2 }} }}"
+        )))
+        .unwrap();
+        assert_eq!(
+            config.validate(),
+            Err("suite s: benchmark a: warmup_pexecs given for unknown executor pypy".to_owned())
+        );
+    }
+
+    #[test]
+    fn smoke() {
+        use crate::config::Config;
+        use crate::smoke::{Outcome, configure, outcomes};
+        let dir = tempfile::tempdir().unwrap();
+        let mut config: Config = toml::from_str(&format!(
+            r#"
+            proc_execs = 5
+            inproc_iters = 100
+            executors = {{ sh = "shell" }}
+            [suites.s]
+            dir = "{}"
+            cache_policy = "warm"
+            warmup_pexecs = 2
+            [suites.s.benchmarks]
+            ok = {{ command = "echo $HASTE_INPROC_ITERS >> log" }}
+            bad = {{ command = "echo oops >&2; exit 3" }}
+            "#,
+            dir.path().display()
+        ))
+        .unwrap();
+        config.validate().unwrap();
+        configure(&mut config, 1);
+        let keys = runner::planned_pexecs(&config, &runner::RunOptions::default())
+            .into_iter()
+            .map(|(k, _)| k)
+            .collect::<Vec<_>>();
+        assert_eq!(keys.len(), 2);
+        let opts = runner::RunOptions {
+            keep_going: true,
+            ..Default::default()
+        };
+        let results = runner::run(&config, &opts).results;
+        // One pexec with one iteration, and no warmups, even though the suite asks for them.
+        let log = fs::read_to_string(dir.path().join("log")).unwrap();
+        assert_eq!(log, "1\n");
+
+        let mut ran = outcomes(&keys, &results);
+        ran.sort_by_key(|(k, _)| *k);
+        assert_eq!(ran[0].0, "bad/sh/");
+        let Outcome::Fail(f) = &ran[0].1 else {
+            panic!("bad/sh/ passed");
+        };
+        assert_eq!((f.status.as_str(), f.stderr.trim()), ("3", "oops"));
+        assert!(matches!(ran[1], ("ok/sh/", Outcome::Pass(_))));
+
+        let missing = ["gone/sh/".to_owned()];
+        assert_eq!(
+            outcomes(&missing, &results),
+            [("gone/sh/", Outcome::NotRun)]
+        );
+    }
+
+    #[test]
+    fn key_aliases() {
+        use crate::aliases::KeyAliases;
+        use crate::config::Config;
+        let config_content = |benchmarks: &str| {
+            format!(
+                r#"
+                proc_execs = 1
+                inproc_iters = 1
+                executors = {{ sh = "shell", yk = {{ path = "/bin/yk", aliases = ["ykold"] }} }}
+                [suites.s]
+                dir = "."
+                [suites.s.benchmarks]
+                {benchmarks}
+                "#
+            )
+        };
+        let config: Config = toml::from_str(&config_content(
+            r#"richards = { command = "true", aliases = ["richards2"] }
+               fannkuch = { command = "true" }"#,
+        ))
+        .unwrap();
+        config.validate().unwrap();
+        let aliases = KeyAliases::new(&config);
+        assert_eq!(aliases.current("richards2/sh/"), Some("richards/sh/"));
+        assert_eq!(aliases.current("richards2/ykold/"), Some("richards/yk/"));
+        assert_eq!(aliases.current("fannkuch/ykold/"), Some("fannkuch/yk/"));
+        assert_eq!(aliases.current("richards/sh/"), None);
+
+        let results = |keys: &[&str]| ResultFile {
+            data: keys.iter().map(|k| (k.to_string(), vec![1.])).collect(),
+            ..Default::default()
+        };
+        let mut old = results(&["richards2/sh/", "fannkuch/sh/", "richards2/other/"]);
+        let new = results(&["richards/sh/", "fannkuch/sh/"]);
+        assert_eq!(
+            aliases.follow("Datum0", &mut old, &new).unwrap(),
+            "note: Datum0 recorded benchmarks under old names: richards2/sh/ (now richards/sh/)"
+        );
+        let mut keys = old.data.keys().cloned().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, ["fannkuch/sh/", "richards/sh/", "richards2/other/"]);
+        // A datum which already has the current key keeps its old one as it is.
+        let mut both = results(&["richards2/sh/", "richards/sh/"]);
+        assert_eq!(aliases.follow("Datum1", &mut both, &new), None);
+
+        for (benchmarks, err) in [
+            (
+                r#"a = { command = "true", aliases = ["old"] }
+                   b = { command = "true", aliases = ["old"] }"#,
+                "suite s: benchmark a and suite s: benchmark b both have the alias old",
+            ),
+            (
+                r#"a = { command = "true", aliases = ["b"] }
+                   b = { command = "true" }"#,
+                "suite s: benchmark a: alias b is the name of a benchmark",
+            ),
+        ] {
+            let config: Config = toml::from_str(&config_content(benchmarks)).unwrap();
+            assert_eq!(config.validate().unwrap_err(), err);
+        }
+    }
+
+    #[test]
+    fn nice_and_between_pexecs() {
+        use crate::config::Config;
+        use crate::log::{LogFormat, RunLog};
+        use std::panic;
+        let dir = tempfile::tempdir().unwrap();
+        let config_content = |nice: &str| {
+            format!(
+                r#"
+                proc_execs = 2
+                inproc_iters = 1
+                executors = {{ sh = "shell" }}
+                {nice}
+                [suites.s]
+                dir = "{}"
+                warmup_pexecs = 1
+                [suites.s.benchmarks]
+                a = {{ command = "nice >> nice" }}
+                b = {{ command = "exit 1" }}
+                "#,
+                dir.path().display()
+            )
+        };
+        let config: Config = toml::from_str(&config_content("nice = 19")).unwrap();
+        config.validate().unwrap();
+        let log_path = dir.path().join("run.log");
+        let opts = runner::RunOptions {
+            keep_going: true,
+            log: Some(RunLog::create(&log_path, LogFormat::Text).unwrap()),
+            ..Default::default()
+        };
+        // Every write to the log asserts that no pexec is running, so the run would panic if
+        // one were written during a pexec.
+        runner::run(&config, &opts);
+        let nice = fs::read_to_string(dir.path().join("nice")).unwrap();
+        assert_eq!(nice, "19\n19\n19\n");
+        // The log records each pexec as starting and then ending before the next starts.
+        let log = fs::read_to_string(&log_path).unwrap();
+        assert_eq!(log.matches("pexec_start").count(), 6);
+        let mut running = false;
+        for event in log.lines().filter_map(|l| l.split(' ').nth(2)) {
+            match event {
+                "pexec_start" => {
+                    assert!(!running);
+                    running = true;
+                }
+                "pexec_end" | "pexec_failed" => {
+                    assert!(running);
+                    running = false;
+                }
+                _ => assert!(!running, "{event} during a pexec"),
+            }
+        }
+        assert!(!running);
+
+        let in_pexec = runner::PexecRunning::start();
+        assert!(panic::catch_unwind(|| runner::between_pexecs("writing")).is_err());
+        drop(in_pexec);
+        runner::between_pexecs("writing");
+
+        let config: Config = toml::from_str(&config_content("nice = 20")).unwrap();
+        assert_eq!(
+            config.validate().unwrap_err(),
+            "nice must be from -20 to 19, not 20"
+        );
+    }
+
+    #[test]
+    fn selftest() {
+        let dir = tempfile::tempdir().unwrap();
+        let checks = crate::selftest::selftest(dir.path(), 0).unwrap();
+        let failed = checks
+            .iter()
+            .filter_map(|c| Some(format!("{}: {}", c.name, c.result.as_ref().err()?)))
+            .collect::<Vec<_>>();
+        assert!(failed.is_empty(), "{failed:#?}");
+        assert!(checks.iter().any(|c| c.name.starts_with("diff: ")));
+    }
+
+    #[test]
+    fn args_changes() {
+        use crate::{KeyParams, args_changes};
+        let params = |keys: &[(&str, &[&str])]| {
+            keys.iter()
+                .map(|(k, args)| {
+                    let p = KeyParams {
+                        inproc_iters: 1,
+                        proc_execs: 1,
+                        extra_args: args.iter().map(|a| a.to_string()).collect(),
+                        suite_args: Vec::new(),
+                        harness: None,
+                        command: None,
+                    };
+                    (k.to_string(), p)
+                })
+                .collect::<HashMap<_, _>>()
+        };
+        let strings = |keys: &[&str]| keys.iter().map(|k| k.to_string()).collect::<Vec<_>>();
+        let lhs = strings(&["a/sh/", "b/sh/x", "c/sh/1", "d/sh/", "e/sh/", "e/sh/1"]);
+        let rhs = strings(&["a/sh/new-arg", "b/sh/", "c/sh/2-3", "f/sh/", "e/sh/2"]);
+        // The second datum records its arguments, which can't be told from the key's suffix.
+        let params2 = params(&[("a/sh/new-arg", &["new-arg"]), ("c/sh/2-3", &["2", "3"])]);
+        let changes = args_changes(&lhs, &rhs, &HashMap::new(), &params2);
+        assert_eq!(
+            changes.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+            [
+                r#"a/sh: [] -> ["new-arg"] (arguments added)"#,
+                r#"b/sh: ["x"] -> [] (arguments removed)"#,
+                r#"c/sh: ["1"] -> ["2", "3"] (arguments changed)"#,
+            ]
+        );
+        assert_eq!(
+            changes[2].keys,
+            ("c/sh/1".to_owned(), "c/sh/2-3".to_owned())
+        );
+        // Without params, the suffix is split on `-`.
+        let changes = args_changes(&lhs[..1], &rhs[..1], &HashMap::new(), &HashMap::new());
+        assert_eq!(changes[0].after, ["new", "arg"]);
+    }
+
+    #[test]
+    fn events() {
+        use crate::events::{Destination, parse_destination};
+        use crate::{BenchOptions, StoreKind};
+        use serde_json::Value;
+        assert_eq!(parse_destination("jsonl"), Ok(Destination::Stdout));
+        assert_eq!(parse_destination("jsonl:3"), Ok(Destination::Fd(3)));
+        assert_eq!(
+            parse_destination("jsonl:./3"),
+            Ok(Destination::Path("./3".into()))
+        );
+        assert_eq!(
+            parse_destination("jsonl:a:b"),
+            Ok(Destination::Path("a:b".into()))
+        );
+        assert!(parse_destination("json:x").is_err());
+        assert!(parse_destination("jsonl:").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let config_file = dir.path().join("haste.toml");
+        fs::write(
+            &config_file,
+            format!(
+                r#"
+                proc_execs = 2
+                inproc_iters = 1
+                executors = {{ sh = "shell" }}
+                [suites.s]
+                dir = "{}"
+                warmup_pexecs = 1
+                [suites.s.benchmarks]
+                a = {{ command = "true" }}
+                b = {{ command = "exit 3" }}
+                "#,
+                dir.path().display()
+            ),
+        )
+        .unwrap();
+        let app = App::new(
+            Some(config_file),
+            Some(dir.path().join(".haste")),
+            true,
+            Some(StoreKind::Fs),
+            true,
+            Some(0),
+        );
+        let path = dir.path().join("events.jsonl");
+        let opts = BenchOptions {
+            skip_build: true,
+            keep_going: true,
+            quiet: true,
+            events: Some(Destination::Path(path.clone())),
+            ..Default::default()
+        };
+        let id = app.bench(&mut app.load_config(), &opts);
+
+        let events = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str::<Value>(l).unwrap())
+            .collect::<Vec<_>>();
+        assert!(
+            events
+                .iter()
+                .all(|e| e["version"] == 1 && e["time_ms"].is_u64())
+        );
+        let names = events
+            .iter()
+            .map(|e| e["event"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "run_started",
+                "pexec_started",
+                "pexec_finished",
+                "pexec_started",
+                "pexec_finished",
+                "benchmark_failed",
+                "pexec_started",
+                "benchmark_failed",
+                "pexec_started",
+                "benchmark_failed",
+                "run_finished",
+            ]
+        );
+        let jobs = events[0]["jobs"].as_array().unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0]["key"], "a/sh/");
+        assert_eq!(jobs[0]["pexecs"], 2);
+        assert_eq!(jobs[0]["warmup_pexecs"], 1);
+        for (e, pexec) in events[1..5].iter().zip([0, 0, 1, 1]) {
+            assert_eq!(e["key"], "a/sh/");
+            assert_eq!(e["pexec"], pexec);
+        }
+        assert!(events[2]["elapsed_ms"].as_f64().unwrap() > 0.);
+        // The failing warmup pexec isn't part of any measured one.
+        assert_eq!(events[5]["key"], "b/sh/");
+        assert_eq!(events[5]["warmup"], true);
+        assert_eq!(events[5]["status"], "3");
+        assert!(events[5].get("pexec").is_none());
+        for (e, pexec) in events[6..10].iter().zip([0, 0, 1, 1]) {
+            assert_eq!(e["key"], "b/sh/");
+            assert_eq!(e["pexec"], pexec);
+        }
+        assert_eq!(events[9]["warmup"], false);
+        assert_eq!(events[10]["datum"], id);
+    }
+
+    #[test]
+    fn predict_run_time() {
+        use crate::config::Config;
+        use crate::predict::{Expected, predict};
+        let config: Config = toml::from_str(
+            r#"
+            proc_execs = 2
+            inproc_iters = 10
+            executors = { sh = "shell" }
+            [suites.s]
+            dir = "."
+            expected_ms = 100
+            warmup_pexecs = 1
+            [suites.s.benchmarks]
+            a = { command = "true", expected_ms = 20 }
+            b = { command = "true" }
+            [suites.t]
+            dir = "."
+            benchmarks = { c = { command = "true" }, d = { command = "true" } }
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+        let jobs = runner::planned_jobs(&config, &runner::RunOptions::default());
+        let keys = jobs.iter().map(|j| j.key.as_str()).collect::<Vec<_>>();
+        assert_eq!(keys, ["a/sh/", "b/sh/", "c/sh/", "d/sh/"]);
+
+        // The newest datum ran c with half as many in-process iterations.
+        let mut newest = ResultFile::default();
+        for (key, samples, iters) in [("a/sh/", vec![50., 70.], 10), ("c/sh/", vec![4.], 5)] {
+            newest.data.insert(key.to_owned(), samples);
+            newest.params.insert(
+                key.to_owned(),
+                KeyParams {
+                    inproc_iters: iters,
+                    proc_execs: 2,
+                    extra_args: Vec::new(),
+                    suite_args: Vec::new(),
+                    harness: None,
+                    command: Some("true".to_owned()),
+                },
+            );
+        }
+        let expected = jobs
+            .iter()
+            .map(|j| Expected::new(j, Some(&newest)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            expected[0],
+            Expected {
+                hint: Some(20.),
+                recorded: Some(60.)
+            }
+        );
+        assert_eq!(
+            expected[1],
+            Expected {
+                hint: Some(100.),
+                recorded: None
+            }
+        );
+        assert_eq!(expected[2].ms(), Some(8.));
+        assert_eq!(expected[3].ms(), None);
+        // Three times the hint is too far from it; twice isn't.
+        assert!(expected[0].disagrees());
+        assert!(
+            !Expected {
+                hint: Some(20.),
+                recorded: Some(40.)
+            }
+            .disagrees()
+        );
+        assert!(!expected[1].disagrees());
+
+        // a and b have a warmup pexec as well as their two measured ones.
+        let (duration, unknown) = predict(&jobs, &expected);
+        assert_eq!(duration, Duration::from_millis(3 * 20 + 3 * 100 + 2 * 8));
+        assert_eq!(unknown, ["d/sh/"]);
+
+        let bad: Config = toml::from_str(
+            r#"
+            proc_execs = 1
+            inproc_iters = 1
+            executors = { sh = "shell" }
+            [suites.s]
+            dir = "."
+            benchmarks = { a = { command = "true", expected_ms = 0 } }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            bad.validate(),
+            Err("suite s: benchmark a: expected_ms must be positive, not 0".to_owned())
+        );
+    }
+
+    #[test]
+    fn harness_chosen_iters() {
+        use crate::config::Config;
+        use crate::probe::{Capabilities, check_auto_iters};
+        use crate::{IPS_METRIC, ITERS_METRIC};
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let harness = dir.path().join("h.sh");
+        fs::write(
+            &harness,
+            "#!/bin/sh\n[ \"$4\" = auto ] || exit 9\n\
+             echo \"@@HASTE_ITERS_USED: 5\"\necho \"@@HASTE_ITERS_USED: $HASTE_PEXEC$HASTE_PEXEC\"\n\
+             echo \"PEXEC_WALLCLOCK_MS=10\" > \"$1\"\n",
+        )
+        .unwrap();
+        fs::set_permissions(&harness, fs::Permissions::from_mode(0o755)).unwrap();
+        let config_content = |extra: &str| {
+            format!(
+                r#"
+                proc_execs = 2
+                inproc_iters = 0
+                executors = {{ sh = "/bin/sh" }}
+                {extra}
+                [suites.s]
+                dir = "{}"
+                harness = "{}"
+                benchmarks = {{ a = {{}} }}
+                "#,
+                dir.path().display(),
+                harness.display()
+            )
+        };
+        let config: Config = toml::from_str(&config_content("")).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.inproc_iters_for("sh"), 0);
+
+        let mut caps = Capabilities {
+            protocol: 1,
+            ..Default::default()
+        };
+        let harnesses = |caps: &Capabilities| {
+            HashMap::from([(
+                "s".to_owned(),
+                HashMap::from([("sh".to_owned(), caps.clone())]),
+            )])
+        };
+        assert_eq!(
+            check_auto_iters(&config, &harnesses(&caps)),
+            Err(
+                "inproc_iters = 0 leaves harnesses to choose their in-process iterations, but \
+                 these suites' harnesses didn't say they can (with auto_iters = true): s (sh)"
+                    .to_owned()
+            )
+        );
+        assert!(check_auto_iters(&config, &HashMap::new()).is_err());
+        caps.auto_iters = true;
+        check_auto_iters(&config, &harnesses(&caps)).unwrap();
+
+        // The last report of each pexec counts.
+        let results = runner::run(&config, &runner::RunOptions::default()).results;
+        assert_eq!(results.metrics[ITERS_METRIC]["a/sh/"], [0., 11.]);
+        assert_eq!(results.params["a/sh/"].inproc_iters, 0);
+        assert_eq!(
+            results.for_metric(IPS_METRIC).unwrap().data["a/sh/"],
+            [0., 1100.]
+        );
+        let mut fixed = results.clone();
+        fixed.metrics.clear();
+        fixed.params.get_mut("a/sh/").unwrap().inproc_iters = 10;
+        assert_eq!(
+            results.param_mismatches(&fixed, true),
+            ["a/sh/: inproc_iters auto vs. 10"]
+        );
+        fixed.params.get_mut("a/sh/").unwrap().inproc_iters = 0;
+        assert!(fixed.for_metric(IPS_METRIC).is_err());
+
+        let config: Config =
+            toml::from_str(&config_content("calibrate = { target_ms = 100 }")).unwrap();
+        assert_eq!(
+            config.validate(),
+            Err(
+                "calibrate can't be used with inproc_iters = 0, which leaves harnesses to choose"
+                    .to_owned()
+            )
+        );
+        let config: Config =
+            toml::from_str(&config_content("").replace("a = {}", "a = { command = \"true\" }"))
+                .unwrap();
+        assert_eq!(
+            config.validate(),
+            Err(
+                "suite s: benchmark a has a command, so can't be run with inproc_iters = 0 \
+                 (only harnesses can choose their iterations)"
+                    .to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn missing_and_corrupt_extra() {
+        use crate::store::StoreKind;
+        use crate::{ShowOptions, TIME_METRIC};
+        let dir = tempfile::tempdir().unwrap();
+        let app = test_app(dir.path(), StoreKind::Fs, 0);
+        let results = ResultFile {
+            data: HashMap::from([("a/e/".to_owned(), vec![1., 2., 3.])]),
+            ..Default::default()
+        };
+        for n in 0..3 {
+            let extra = ExtraToml {
+                comment: Some(format!("run {n}")),
+                ..Default::default()
+            };
+            app.store.store(&results, &extra);
+        }
+        fs::remove_file(dir.path().join("1/extra.toml")).unwrap();
+        let corrupt = dir.path().join("2/extra.toml");
+        fs::write(&corrupt, "comment = [\n").unwrap();
+
+        // Present, absent and corrupt are all told apart.
+        assert!(app.store.has_extra(0));
+        assert!(!app.store.has_extra(1));
+        assert!(app.store.has_extra(2));
+        assert_eq!(
+            app.store.try_load_extra(0).unwrap().comment.as_deref(),
+            Some("run 0")
+        );
+        assert_eq!(app.store.try_load_extra(1).unwrap().comment, None);
+        let e = app.store.try_load_extra(2).err().unwrap();
+        assert!(
+            e.starts_with(&format!("{}: TOML parse error", corrupt.display())),
+            "{e}"
+        );
+
+        // diff loads datums with load_ref, which reports the corrupt one, and notes the absent one.
+        assert!(app.load_ref(&DatumRef::local(1)).is_ok());
+        assert_eq!(app.load_ref(&DatumRef::local(2)).err(), Some(e));
+        assert_eq!(app.missing_extra_note(&DatumRef::local(0), "Datum0"), None);
+        assert_eq!(
+            app.missing_extra_note(&DatumRef::local(1), "Datum1")
+                .as_deref(),
+            Some(
+                "note: Datum1 has no extra.toml, so how it was made is unknown (`haste edit 1` \
+                 recreates it)"
+            )
+        );
+
+        let opts = ShowOptions {
+            confidence: ConfidenceLevel::CL99,
+            thousands: false,
+            raw: false,
+            subtract_overhead: false,
+            metric: TIME_METRIC.to_owned(),
+            histogram_bins: None,
+            describe: false,
+            env: false,
+            output: Default::default(),
+        };
+        let show = |id| {
+            let mut buf = Vec::new();
+            app.show(id, &opts, &mut buf, false).unwrap();
+            String::from_utf8(buf).unwrap()
+        };
+        assert!(!show(0).contains("note:"));
+        let shown = show(1);
+        assert!(
+            shown.starts_with("Datum1: (no comment)\nnote: Datum1 has no extra.toml"),
+            "{shown}"
+        );
+
+        // Editing recreates the missing metadata, but leaves the corrupt alone.
+        app.cmd_edit(1, Some("recreated".to_owned()), false);
+        assert!(app.store.has_extra(1));
+        assert_eq!(
+            app.store.try_load_extra(1).unwrap().comment.as_deref(),
+            Some("recreated")
+        );
+        app.cmd_edit(0, None, true);
+        assert_eq!(app.store.try_load_extra(0).unwrap().comment, None);
+        assert_eq!(fs::read_to_string(&corrupt).unwrap(), "comment = [\n");
+    }
+
+    #[test]
+    fn advise_never_significant() {
+        use crate::advise::{Streak, never_significant, streaks};
+        use crate::store::StoreKind;
+        let dir = tempfile::tempdir().unwrap();
+        let app = test_app(dir.path(), StoreKind::Fs, 0);
+        let noisy = |ms: f64| (0..10).map(|i| ms + (i % 3) as f64).collect::<Vec<_>>();
+        for (i, jump) in [100., 100., 300., 300.].into_iter().enumerate() {
+            let mut data = HashMap::from([
+                ("slow/e/".to_owned(), noisy(50.)),
+                ("fast/e/".to_owned(), noisy(5.)),
+                ("jump/e/".to_owned(), noisy(jump)),
+            ]);
+            if i == 3 {
+                data.insert("new/e/".to_owned(), noisy(1.));
+            }
+            let results = ResultFile {
+                data,
+                ..Default::default()
+            };
+            app.store.store(&results, &ExtraToml::default());
+        }
+        let summaries = app
+            .store
+            .ids()
+            .into_iter()
+            .map(|id| app.load_summary(id, ConfidenceLevel::default()))
+            .collect::<Vec<_>>();
+        let streaks = streaks(&summaries);
+        assert_eq!(streaks["jump/e/"].comparisons, 3);
+        assert_eq!(streaks["jump/e/"].significant, 1);
+        assert_eq!(streaks["new/e/"].comparisons, 0);
+        assert_eq!(
+            never_significant(&streaks),
+            vec![
+                (
+                    "slow/e/",
+                    &Streak {
+                        comparisons: 3,
+                        significant: 0,
+                        runtime_ms: 4. * 509.,
+                    }
+                ),
+                (
+                    "fast/e/",
+                    &Streak {
+                        comparisons: 3,
+                        significant: 0,
+                        runtime_ms: 4. * 59.,
+                    }
+                ),
+            ]
+        );
+        // The window leaves out the only change.
+        assert!(
+            never_significant(&crate::advise::streaks(&summaries[2..]))
+                .iter()
+                .any(|(k, _)| *k == "jump/e/")
+        );
+    }
+
+    #[test]
+    fn suspended_pexecs() {
+        use crate::ITERS_METRIC;
+        use crate::config::Config;
+        use crate::suspend::{Deltas, taint};
+        let ms = Duration::from_millis;
+        let secs = Duration::from_secs;
+        let clean = Deltas {
+            monotonic: secs(2),
+            boottime: Some(secs(2) + ms(5)),
+            wallclock: Some(secs(2)),
+            cpu: Some(ms(1900)),
+        };
+        assert_eq!(taint(&clean, None), None);
+        assert_eq!(taint(&clean, Some(1.5)), None);
+
+        // An hour asleep shows in the boot clock, whatever the wall clock says.
+        let asleep = Deltas {
+            boottime: Some(secs(3602)),
+            ..clean
+        };
+        assert!(taint(&asleep, None).unwrap().contains("suspended"));
+        let stepped = Deltas {
+            wallclock: Some(secs(60)),
+            ..clean
+        };
+        assert_eq!(taint(&stepped, None), None);
+        // Without a boot clock, only a wall clock far ahead counts.
+        let no_boottime = Deltas {
+            boottime: None,
+            ..clean
+        };
+        assert_eq!(taint(&no_boottime, None), None);
+        let ahead = Deltas {
+            wallclock: Some(secs(3602)),
+            ..no_boottime
+        };
+        assert!(taint(&ahead, None).unwrap().contains("suspended"));
+        assert_eq!(
+            taint(
+                &Deltas {
+                    wallclock: Some(secs(4)),
+                    ..ahead
+                },
+                None
+            ),
+            None
+        );
+
+        // Waiting without using the CPU only counts with a max_wall_cpu_ratio.
+        let idle = Deltas {
+            cpu: Some(ms(100)),
+            ..clean
+        };
+        assert_eq!(taint(&idle, None), None);
+        assert!(
+            taint(&idle, Some(10.))
+                .unwrap()
+                .contains("max_wall_cpu_ratio = 10")
+        );
+        assert_eq!(taint(&idle, Some(50.)), None);
+        assert_eq!(taint(&Deltas { cpu: None, ..idle }, Some(10.)), None);
+
+        let mut results = ResultFile {
+            data: HashMap::from([
+                ("a/e/".to_owned(), vec![1., 2.]),
+                ("b/e/".to_owned(), vec![3.]),
+            ]),
+            metrics: HashMap::from([(
+                ITERS_METRIC.to_owned(),
+                HashMap::from([("a/e/".to_owned(), vec![10., 20.])]),
+            )]),
+            timestamps: HashMap::from([("a/e/".to_owned(), vec![100, 200])]),
+            ..Default::default()
+        };
+        crate::runner::discard_last(&mut results, "a/e/");
+        assert_eq!(results.data["a/e/"], vec![1.]);
+        assert_eq!(results.data["b/e/"], vec![3.]);
+        assert_eq!(results.metrics[ITERS_METRIC]["a/e/"], vec![10.]);
+        assert_eq!(results.timestamps["a/e/"], vec![100]);
+
+        let config = |ratio| {
+            toml::from_str::<Config>(&format!(
+                "proc_execs = 1\ninproc_iters = 1\nmax_wall_cpu_ratio = {ratio}\n\
+                 executors = {{ sh = \"shell\" }}\n\
+                 [suites.s]\ndir = \".\"\nbenchmarks = {{ a = {{ command = \"true\" }} }}\n"
+            ))
+            .unwrap()
+        };
+        assert_eq!(config(20.).validate(), Ok(()));
+        assert_eq!(
+            config(0.5).validate(),
+            Err("max_wall_cpu_ratio must be at least 1, not 0.5".to_owned())
+        );
+    }
+
+    #[test]
+    fn executor_groups() {
+        use crate::config::Config;
+        use crate::{BenchOptions, OutputFormat, ShowOptions, StoreKind, TIME_METRIC};
+        let config_content = |groups: &str| {
+            format!(
+                "proc_execs = 2\ninproc_iters = 1\n\
+                 executors = {{ debug = \"shell\", release = \"shell\" }}\n\
+                 [executor_groups]\n{groups}\n\
+                 [suites.s]\ndir = \".\"\nbenchmarks = {{ a = {{ command = \"true\" }} }}\n"
+            )
+        };
+        let validate = |groups: &str| {
+            toml::from_str::<Config>(&config_content(groups))
+                .unwrap()
+                .validate()
+        };
+        assert_eq!(validate("yk = [\"release\", \"debug\"]"), Ok(()));
+        assert_eq!(
+            validate("yk = [\"release\", \"jit\"]"),
+            Err("executor group yk: no executor named jit".to_owned())
+        );
+        assert_eq!(
+            validate("yk = [\"release\", \"release\"]"),
+            Err("executor group yk: executor release is listed twice".to_owned())
+        );
+        assert_eq!(
+            validate("yk = [\"release\"]"),
+            Err("executor group yk needs at least two executors to compare".to_owned())
+        );
+        let config =
+            toml::from_str::<Config>(&config_content("yk = [\"release\", \"debug\"]")).unwrap();
+        assert_eq!(
+            config.executor_group("yk"),
+            Ok(&["release".to_owned(), "debug".to_owned()][..])
+        );
+        assert!(config.executor_group("cpython").is_err());
+
+        // The group a datum was made for is recorded with it, and is what compare defaults to.
+        let dir = tempfile::tempdir().unwrap();
+        let config_file = dir.path().join("haste.toml");
+        fs::write(
+            &config_file,
+            config_content("yk = [\"release\", \"debug\"]").replace(
+                "dir = \".\"",
+                &format!("dir = \"{}\"", dir.path().display()),
+            ),
+        )
+        .unwrap();
+        let app = App::new(
+            Some(config_file),
+            Some(dir.path().join(".haste")),
+            true,
+            Some(StoreKind::Fs),
+            true,
+            Some(0),
+        );
+        let opts = BenchOptions {
+            interleave: vec!["release".to_owned(), "debug".to_owned()],
+            executor_group: Some("yk".to_owned()),
+            skip_build: true,
+            quiet: true,
+            ..Default::default()
+        };
+        let id = app.bench(&mut app.load_config(), &opts);
+        assert_eq!(app.load_extra(id).executor_group.as_deref(), Some("yk"));
+        let mut shown = Vec::new();
+        let show_opts = ShowOptions {
+            confidence: ConfidenceLevel::default(),
+            thousands: false,
+            raw: false,
+            subtract_overhead: false,
+            metric: TIME_METRIC.to_owned(),
+            histogram_bins: None,
+            describe: false,
+            env: false,
+            output: Default::default(),
+        };
+        app.show(id, &show_opts, &mut shown, false).unwrap();
+        assert!(
+            String::from_utf8(shown)
+                .unwrap()
+                .contains("executor group: yk")
+        );
+        app.cmd_compare(
+            id,
+            None,
+            &[],
+            ConfidenceLevel::default(),
+            OutputFormat::Json,
+        );
+    }
+
+    #[test]
+    fn refine_borderline() {
+        use crate::StoreKind;
+        use crate::refine::{append, borderline, extra_pexecs};
+        use std::collections::BTreeMap;
+        let stats = |mean, ci, n| SummaryStats {
+            mean,
+            ci,
+            n,
+            bimodal: false,
+        };
+        let base = HashMap::from([
+            ("edge/e/".to_owned(), stats(100., 1., 10)),
+            ("overlap/e/".to_owned(), stats(100., 1., 10)),
+            ("apart/e/".to_owned(), stats(100., 1., 10)),
+            ("just_apart/e/".to_owned(), stats(100., 1., 10)),
+            ("few/e/".to_owned(), stats(100., 1., 3)),
+            ("exact/e/".to_owned(), stats(100., 0., 10)),
+        ]);
+        let new = HashMap::from([
+            ("edge/e/".to_owned(), stats(98.1, 1., 10)),
+            ("overlap/e/".to_owned(), stats(100.5, 1., 10)),
+            ("apart/e/".to_owned(), stats(110., 1., 10)),
+            ("just_apart/e/".to_owned(), stats(102.1, 1., 10)),
+            ("few/e/".to_owned(), stats(102., 1., 10)),
+            ("exact/e/".to_owned(), stats(101., 0., 10)),
+        ]);
+        assert_eq!(
+            borderline(&base, &new, 10., DEFAULT_MIN_SAMPLES),
+            ["edge/e/", "just_apart/e/"]
+        );
+        assert_eq!(
+            borderline(&base, &new, 1., DEFAULT_MIN_SAMPLES),
+            Vec::<String>::new()
+        );
+
+        assert_eq!(extra_pexecs(10, 10., 10), 3);
+        assert_eq!(extra_pexecs(10, 50., 10), 10);
+        assert_eq!(extra_pexecs(100, 0., 10), 1);
+
+        // The extra samples are appended, in order, and benchmarks aren't refined with different
+        // parameters.
+        let params = |proc_execs, inproc_iters| KeyParams {
+            inproc_iters,
+            proc_execs,
+            extra_args: Vec::new(),
+            suite_args: Vec::new(),
+            harness: None,
+            command: Some("true".to_owned()),
+        };
+        let results = |samples: Vec<f64>, proc_execs, inproc_iters| ResultFile {
+            timestamps: HashMap::from([("a/e/".to_owned(), vec![0; samples.len()])]),
+            metrics: HashMap::from([(
+                "cycles".to_owned(),
+                HashMap::from([("a/e/".to_owned(), samples.clone())]),
+            )]),
+            data: HashMap::from([("a/e/".to_owned(), samples), ("b/e/".to_owned(), vec![7.])]),
+            params: HashMap::from([("a/e/".to_owned(), params(proc_execs, inproc_iters))]),
+            ..Default::default()
+        };
+        let mut extra = results(vec![3.], 1, 1);
+        extra.data.remove("b/e/");
+        let merged = append(results(vec![1., 2.], 2, 1), extra).unwrap();
+        assert_eq!(merged.data["a/e/"], [1., 2., 3.]);
+        assert_eq!(merged.data["b/e/"], [7.]);
+        assert_eq!(merged.metrics["cycles"]["a/e/"], [1., 2., 3.]);
+        assert_eq!(merged.timestamps["a/e/"].len(), 3);
+        assert_eq!(merged.params["a/e/"].proc_execs, 2);
+        assert_eq!(
+            append(results(vec![1., 2.], 2, 1), results(vec![3.], 1, 5)).err(),
+            Some(
+                "the config now runs benchmarks differently to the datum refined: a/e/: \
+                 inproc_iters 1 vs. 5"
+                    .to_owned()
+            )
+        );
+
+        // Refining a borderline comparison makes a new datum with the extra pexecs, recording
+        // how many.
+        let dir = tempfile::tempdir().unwrap();
+        let config_file = dir.path().join("haste.toml");
+        fs::write(
+            &config_file,
+            format!(
+                "proc_execs = 10\ninproc_iters = 1\nexecutors = {{ sh = \"shell\" }}\n\
+                 [suites.s]\ndir = \"{}\"\n\
+                 benchmarks = {{ a = {{ command = \"true\" }}, b = {{ command = \"true\" }} }}\n",
+                dir.path().display()
+            ),
+        )
+        .unwrap();
+        let app = App::new(
+            Some(config_file),
+            Some(dir.path().join(".haste")),
+            true,
+            Some(StoreKind::Fs),
+            true,
+            Some(0),
+        );
+        let samples = (0..10).map(|i| 100. + i as f64).collect::<Vec<_>>();
+        let ci = SummaryStats::from_samples(&samples, ConfidenceLevel::default()).ci;
+        let datum = |shift: f64| ResultFile {
+            data: ["a/sh/", "b/sh/"]
+                .into_iter()
+                .map(|k| (k.to_owned(), samples.iter().map(|s| s + shift).collect()))
+                .collect(),
+            params: ["a/sh/", "b/sh/"]
+                .into_iter()
+                .map(|k| (k.to_owned(), params(10, 1)))
+                .collect(),
+            ..Default::default()
+        };
+        let baseline = app.store.store(&datum(0.), &ExtraToml::default());
+        let mut borderline_datum = datum(2. * ci);
+        borderline_datum
+            .data
+            .insert("b/sh/".to_owned(), samples.clone());
+        let extra = ExtraToml {
+            comment: Some("new".to_owned()),
+            ..Default::default()
+        };
+        let source = app.store.store(&borderline_datum, &extra);
+        let refined = app
+            .refine(source, baseline, 2, 10., ConfidenceLevel::default())
+            .unwrap();
+        assert_eq!(refined, 2);
+        let results = app.load_results(refined);
+        assert_eq!(results.data["a/sh/"].len(), 12);
+        assert_eq!(
+            results.data["a/sh/"][..10],
+            borderline_datum.data["a/sh/"][..]
+        );
+        assert_eq!(results.data["b/sh/"].len(), 10);
+        let extra = app.load_extra(refined);
+        assert_eq!(extra.comment.as_deref(), Some("new"));
+        let r = extra.refinement.unwrap();
+        assert_eq!((r.source, r.against), (source, baseline));
+        assert_eq!(r.extra_pexecs, BTreeMap::from([("a/sh/".to_owned(), 2)]));
+        let opts = crate::DiffOptions {
+            deterministic: true,
+            env_warnings: false,
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        app.diff_to(
+            &DatumRef::local(baseline),
+            &DatumRef::local(refined),
+            &opts,
+            &mut out,
+            false,
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(
+            out.contains(&format!(
+                "WARNING: Datum{refined} refines Datum{source} with more pexecs of 1 benchmarks"
+            )),
+            "{out}"
+        );
+        assert!(out.contains(" a/sh/      10/12 "), "{out}");
+        assert!(out.contains(" b/sh/      10/10 "), "{out}");
+        // Once nothing is borderline, there's nothing to refine.
+        assert_eq!(
+            app.refine(source, source, 2, 10., ConfidenceLevel::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn harness_output_decimal_separator() {
+        use crate::runner::parse_harness_output;
+        assert_eq!(parse_harness_output("PEXEC_WALLCLOCK_MS=12.5\n"), Ok(12.5));
+        assert_eq!(parse_harness_output("PEXEC_WALLCLOCK_MS=1200"), Ok(1200.));
+        for bad in ["12,5", "1,200"] {
+            assert_eq!(
+                parse_harness_output(&format!("PEXEC_WALLCLOCK_MS={bad}")),
+                Err(format!(
+                    "failed to parse {bad} as a number of milliseconds: use '.' as the decimal \
+                     separator, whatever the locale"
+                ))
+            );
+        }
+        assert_eq!(
+            parse_harness_output("PEXEC_WALLCLOCK_MS=twelve"),
+            Err("failed to parse twelve as a number of milliseconds".to_owned())
+        );
+    }
+
+    #[test]
+    fn diff_benchmark_filters() {
+        use crate::{Cli, Mode, select_benchmarks};
+        use clap::Parser;
+        let parse = |args: &[&str]| {
+            let cli = Cli::try_parse_from([&["haste", "diff"], args].concat()).unwrap();
+            let Mode::Diff {
+                id1,
+                id2,
+                confidence,
+                benchmarks,
+                ..
+            } = cli.mode
+            else {
+                panic!("not a diff");
+            };
+            (id1.id, id2.id, confidence, benchmarks)
+        };
+        // A trailing number is a benchmark name, not a confidence level.
+        assert_eq!(
+            parse(&["12", "13", "95"]),
+            (12, 13, ConfidenceLevel::default(), vec!["95".to_owned()])
+        );
+        assert_eq!(
+            parse(&["12", "13", "fasta", "-c", "99", "nbody"]),
+            (
+                12,
+                13,
+                ConfidenceLevel::CL99,
+                vec!["fasta".to_owned(), "nbody".to_owned()]
+            )
+        );
+        assert_eq!(parse(&["12", "13"]).3, Vec::<String>::new());
+        assert!(Cli::try_parse_from(["haste", "diff", "12"]).is_err());
+
+        let results = |keys: &[&str]| ResultFile {
+            data: keys.iter().map(|k| (k.to_string(), vec![1.])).collect(),
+            timestamps: keys.iter().map(|k| (k.to_string(), vec![0])).collect(),
+            ..Default::default()
+        };
+        let mut r1 = results(&["fasta/a/", "fasta/b/", "nbody/a/x", "richards/a/"]);
+        let mut r2 = results(&["fasta/a/", "nbody/a/y", "fannkuch/a/"]);
+        let wanted = ["fasta".to_owned(), "nbody".to_owned()];
+        select_benchmarks(&wanted, ("Datum1", &mut r1), ("Datum2", &mut r2)).unwrap();
+        let keys = |r: &ResultFile| {
+            let mut keys = r.data.keys().cloned().collect::<Vec<_>>();
+            keys.sort();
+            keys
+        };
+        assert_eq!(keys(&r1), ["fasta/a/", "fasta/b/", "nbody/a/x"]);
+        assert_eq!(keys(&r2), ["fasta/a/", "nbody/a/y"]);
+        assert_eq!(r2.timestamps.len(), 2);
+        let mut r1 = results(&["fasta/a/", "richards/a/"]);
+        let mut r2 = results(&["fannkuch/a/"]);
+        assert_eq!(
+            select_benchmarks(
+                &["fasta".to_owned(), "nbdoy".to_owned(), "fast".to_owned()],
+                ("Datum1", &mut r1),
+                ("Datum2", &mut r2)
+            ),
+            Err(
+                "error: neither Datum1 nor Datum2 has a benchmark named nbdoy, fast: they have \
+                 fannkuch, fasta, richards"
+                    .to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn experiments() {
+        use crate::StoreKind;
+        use crate::experiment::{State, Summary, summarise};
+        let mut state = State::default();
+        assert_eq!(state.end(), Err("no experiment is active".to_owned()));
+        state.start("inlining").unwrap();
+        assert_eq!(
+            state.start("gc"),
+            Err(
+                "experiment inlining is still active: end it with `haste experiment end` \
+                 before starting gc"
+                    .to_owned()
+            )
+        );
+        assert!(state.start("inlining").is_err());
+        assert_eq!(state.end(), Ok("inlining".to_owned()));
+        assert!(state.start(" gc").is_err());
+
+        // Datums are part of the experiment active when they're created, which state.toml
+        // records across runs.
+        let dir = tempfile::tempdir().unwrap();
+        let app = test_app(dir.path(), StoreKind::Fs, 0);
+        let store = |start_time| {
+            let extra = ExtraToml {
+                start_time: Some(start_time),
+                end_time: Some(start_time + 10),
+                ..Default::default()
+            };
+            app.store_datum(ResultFile::default(), extra)
+        };
+        let before = store(100);
+        app.cmd_experiment_start("inlining");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("state.toml")).unwrap(),
+            "experiment = \"inlining\"\n"
+        );
+        let (a, b) = (store(200), store(300));
+        app.cmd_experiment_end();
+        app.cmd_experiment_start("gc");
+        let c = store(400);
+        app.cmd_experiment_end();
+        let after = store(500);
+        app.cmd_experiment_start("inlining");
+        let d = store(600);
+        assert_eq!(app.active_experiment().as_deref(), Some("inlining"));
+        for (id, experiment) in [
+            (before, None),
+            (a, Some("inlining")),
+            (b, Some("inlining")),
+            (c, Some("gc")),
+            (after, None),
+            (d, Some("inlining")),
+        ] {
+            assert_eq!(app.load_extra(id).experiment.as_deref(), experiment);
+        }
+        assert_eq!(app.experiment_ids("inlining"), [a, b, d]);
+        let extras = app
+            .store
+            .ids()
+            .into_iter()
+            .map(|id| (id, app.load_extra(id)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summarise(&extras),
+            [
+                Summary {
+                    name: "inlining".to_owned(),
+                    ids: vec![a, b, d],
+                    first: Some(200),
+                    last: Some(610),
+                },
+                Summary {
+                    name: "gc".to_owned(),
+                    ids: vec![c],
+                    first: Some(400),
+                    last: Some(410),
+                },
+            ]
+        );
+
+        app.cmd_rm(&[], &[], Some("inlining"));
+        assert_eq!(app.store.ids(), [before, c, after]);
+    }
+
+    #[test]
+    fn explain_diff_row() {
+        use crate::explain::{Side, explain, resolve_key};
+        use crate::{DiffOptions, SignificanceTest, Statistic};
+        let mut opts = DiffOptions {
+            min_samples: 2,
+            deterministic: true,
+            env_warnings: false,
+            explain: Some("a/e/".to_owned()),
+            ..Default::default()
+        };
+        let results = |samples: &[(&str, Vec<f64>)]| ResultFile {
+            data: samples
+                .iter()
+                .map(|(k, s)| (k.to_string(), s.clone()))
+                .collect(),
+            ..Default::default()
+        };
+        let explained = |base: Vec<f64>, new: Vec<f64>, opts: &DiffOptions| {
+            let r1 = results(&[("a/e/", base)]);
+            let r2 = results(&[("a/e/", new)]);
+            let (s1, s2) = (r1.summarise(opts.confidence), r2.summarise(opts.confidence));
+            let sides = [
+                Side {
+                    name: "Datum1",
+                    results: &r1,
+                    summaries: &s1,
+                },
+                Side {
+                    name: "Datum2",
+                    results: &r2,
+                    summaries: &s2,
+                },
+            ];
+            explain("a/e/", &sides, opts)
+        };
+
+        // Every step from the samples to the classification is shown.
+        let e = explained(vec![9., 10., 11.], vec![10., 11., 12.], &opts);
+        for line in [
+            "a/e/: Datum1 vs. Datum2, comparing the mean of time at 99% confidence",
+            "  samples (3): 9, 10, 11",
+            "  mean: 10.0000",
+            "  standard deviation: 1.0000",
+            "  standard error: 1.0000 / √3 = 0.5774",
+            "  critical value: z = 2.5758 (the normal distribution's, for a two-sided 99% interval)",
+            "  interval: 10.0000 ± 2.5758 × 0.5774 = 10.0000 ± 1.4872 = [8.5128, 11.4872]",
+            "  interval: 11.0000 ± 2.5758 × 0.5774 = 11.0000 ± 1.4872 = [9.5128, 12.4872]",
+            "  ratio: 11.0000 / 10.0000 = 1.1000 (a change of +10.00%)",
+            "  the intervals [8.5128, 11.4872] and [9.5128, 12.4872] overlap (by 1.9743), so the \
+             difference may be noise",
+            "result: indistinguishable",
+        ] {
+            assert!(e.lines().any(|l| l == line), "{line:?} not in:\n{e}");
+        }
+        let e = explained(vec![9., 10., 11.], vec![20., 21., 22.], &opts);
+        assert!(
+            e.contains("don't overlap (8.0257 apart), so the values differ"),
+            "{e}"
+        );
+        assert!(e.ends_with("result: 110.00% slower\n"), "{e}");
+
+        // The wording follows the options.
+        opts.min_samples = 5;
+        let e = explained(vec![9., 10., 11.], vec![20., 21., 22.], &opts);
+        assert!(e.contains("fewer than 5 samples on a side"), "{e}");
+        assert!(
+            e.ends_with("result: indistinguishable (too few samples)\n"),
+            "{e}"
+        );
+        opts.min_samples = 2;
+        opts.stat = Statistic::TrimmedMean(25.);
+        let e = explained(vec![1., 9., 10., 11., 50.], vec![9., 10., 11.], &opts);
+        assert!(
+            e.contains("  after trimming 25% from each end (3 of 5 samples): 9, 10, 11"),
+            "{e}"
+        );
+        assert!(e.contains("  25% trimmed mean: 10.0000"), "{e}");
+        opts.stat = Statistic::Mean;
+        opts.test = SignificanceTest::RatioCi;
+        let e = explained(vec![9., 10., 11.], vec![20., 21., 22.], &opts);
+        assert!(
+            e.contains("  Fieller's g = (1.4872 / 10.0000)² = 0.0221"),
+            "{e}"
+        );
+        assert!(e.contains("which excludes 1, so the values differ"), "{e}");
+        let e = explained(vec![1., 10., 19.], vec![20., 21., 22.], &opts);
+        assert!(
+            e.contains("too wide for the ratio's to mean anything"),
+            "{e}"
+        );
+        assert!(e.ends_with("result: indistinguishable\n"), "{e}");
+
+        // A benchmark with one key can be named by itself.
+        let r1 = results(&[
+            ("a/e/", vec![1.]),
+            ("b/e/", vec![1.]),
+            ("b/f/", vec![1.]),
+            ("c/e/", vec![1.]),
+        ]);
+        let r2 = results(&[("a/e/", vec![1.]), ("b/e/", vec![1.]), ("b/f/", vec![1.])]);
+        let (s1, s2) = (r1.summarise(opts.confidence), r2.summarise(opts.confidence));
+        let sides = [
+            Side {
+                name: "Datum1",
+                results: &r1,
+                summaries: &s1,
+            },
+            Side {
+                name: "Datum2",
+                results: &r2,
+                summaries: &s2,
+            },
+        ];
+        assert_eq!(resolve_key("a", &sides), Ok("a/e/".to_owned()));
+        assert_eq!(resolve_key("b/f/", &sides), Ok("b/f/".to_owned()));
+        assert_eq!(
+            resolve_key("b", &sides),
+            Err("benchmark b has several keys, so give one of: b/e/, b/f/".to_owned())
+        );
+        assert_eq!(
+            resolve_key("c/e/", &sides),
+            Err("only Datum1 has c/e/".to_owned())
+        );
+        assert_eq!(
+            resolve_key("zz", &sides),
+            Err("Datum1 and Datum2 have no results for zz to compare".to_owned())
+        );
+    }
+
+    /// Accept one HTTP request on a loopback port, answering it with `status`. Returns the URL to
+    /// send to and a handle that yields the request's head (with lowercased header names) and body.
+    fn serve_once(status: u16) -> (String, std::thread::JoinHandle<(String, String)>) {
+        use std::io::{BufRead, BufReader, Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = String::new();
+            let mut len = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                let line = match line.split_once(':') {
+                    Some((k, v)) => format!("{}:{v}", k.to_lowercase()),
+                    None => line,
+                };
+                if let Some(v) = line.strip_prefix("content-length:") {
+                    len = v.trim().parse().unwrap();
+                }
+                head.push_str(&line);
+            }
+            let mut body = vec![0; len];
+            reader.read_exact(&mut body).unwrap();
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {status} X\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+            )
+            .unwrap();
+            (head, String::from_utf8(body).unwrap())
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn notify_payload() {
+        use crate::{notify, store::StoreKind};
+        let dir = tempfile::tempdir().unwrap();
+        let app = test_app(dir.path(), StoreKind::Fs, 0);
+        let results = |samples: Vec<f64>| ResultFile {
+            data: HashMap::from([
+                ("a/e/".to_owned(), samples.clone()),
+                ("b/e/".to_owned(), samples),
+            ]),
+            ..Default::default()
+        };
+        app.store
+            .store(&results(vec![1., 2., 3.]), &ExtraToml::default());
+        let extra = ExtraToml {
+            comment: Some("faster".to_owned()),
+            executor_group: Some("g".to_owned()),
+            start_time: Some(100),
+            end_time: Some(160),
+            duration_secs: Some(60.),
+            ..Default::default()
+        };
+        let id = app.store.store(&results(vec![10., 10.]), &extra);
+        let (results, extra) = (app.load_results(id), app.load_extra(id));
+        let payload = notify::payload(id, &extra, &results, ConfidenceLevel::CL99);
+        assert_eq!(
+            serde_json::to_value(&payload).unwrap(),
+            serde_json::json!({
+                "datum": id,
+                "comment": "faster",
+                "executor_group": "g",
+                "start_time": 100,
+                "end_time": 160,
+                "duration_secs": 60.0,
+                "truncated": false,
+                "confidence": 99.0,
+                "benchmarks": {
+                    "a/e/": { "mean": 10.0, "ci": 0.0, "samples": 2 },
+                    "b/e/": { "mean": 10.0, "ci": 0.0, "samples": 2 },
+                },
+            })
+        );
+
+        // Without a URL to send to, nothing is sent.
+        let notify = |url: &str| crate::config::Notify {
+            url: url.to_owned(),
+            headers: HashMap::new(),
+            token_env: None,
+            timeout_secs: 1,
+        };
+        let config = Path::new("haste.toml");
+        assert_eq!(
+            notify::configured(None, config).unwrap_err(),
+            "no [notify] section in haste.toml"
+        );
+        assert_eq!(
+            notify::configured(Some(&notify(" ")), config).unwrap_err(),
+            "the [notify] section of haste.toml has no url"
+        );
+        assert!(notify::configured(Some(&notify("http://x/")), config).is_ok());
+    }
+
+    #[test]
+    fn notify_post() {
+        use crate::notify;
+        let extra = ExtraToml::default();
+        let results = ResultFile {
+            data: HashMap::from([("a/e/".to_owned(), vec![5.])]),
+            ..Default::default()
+        };
+        let payload = notify::payload(1, &extra, &results, ConfidenceLevel::CL99);
+        let env = |var: &str| (var == "HASTE_TOKEN").then(|| "s3cret".to_owned());
+
+        let (url, server) = serve_once(200);
+        let n = crate::config::Notify {
+            url,
+            headers: HashMap::from([("X-Haste".to_owned(), "yes".to_owned())]),
+            token_env: Some("HASTE_TOKEN".to_owned()),
+            timeout_secs: 5,
+        };
+        notify::send(&n, &payload, env).unwrap();
+        let (head, body) = server.join().unwrap();
+        assert!(head.starts_with("POST /hook HTTP/1.1\r\n"), "{head}");
+        assert!(head.contains("\r\nx-haste: yes\r\n"), "{head}");
+        assert!(
+            head.contains("\r\nauthorization: Bearer s3cret\r\n"),
+            "{head}"
+        );
+        assert!(
+            head.contains("\r\ncontent-type: application/json"),
+            "{head}"
+        );
+        let body = serde_json::from_str::<serde_json::Value>(&body).unwrap();
+        assert_eq!(body["datum"], 1);
+        assert_eq!(body["benchmarks"]["a/e/"]["samples"], 1);
+
+        // Without a token, no Authorization header is sent.
+        let (url, server) = serve_once(200);
+        let n = crate::config::Notify {
+            url,
+            token_env: None,
+            ..n
+        };
+        notify::send(&n, &payload, env).unwrap();
+        let (head, _) = server.join().unwrap();
+        assert!(!head.contains("authorization:"), "{head}");
+
+        // An unset token variable is an error, and nothing is sent.
+        let n = crate::config::Notify {
+            token_env: Some("HASTE_NO_TOKEN".to_owned()),
+            ..n
+        };
+        assert_eq!(
+            notify::send(&n, &payload, env).unwrap_err(),
+            "environment variable HASTE_NO_TOKEN is not set"
+        );
+
+        // As is a server rejecting the datum.
+        let (url, server) = serve_once(500);
+        let n = crate::config::Notify {
+            url: url.clone(),
+            token_env: None,
+            ..n
+        };
+        let e = notify::send(&n, &payload, env).unwrap_err();
+        assert!(e.starts_with(&format!("failed to POST to {url}: ")), "{e}");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn tui_keys() {
+        use crate::tui::{State, View};
+        use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyEventKind};
+        let mut state = State::new(vec![3, 5, 8]);
+        let press = |state: &mut State, code| {
+            assert!(state.handle_key(KeyEvent::from(code)).is_continue());
+        };
+        assert_eq!(state.selected(), Some(8));
+
+        // Navigation stops at either end of the list.
+        press(&mut state, KeyCode::Down);
+        assert_eq!(state.selected(), Some(8));
+        press(&mut state, KeyCode::Up);
+        assert_eq!(state.selected(), Some(5));
+        press(&mut state, KeyCode::Char('k'));
+        press(&mut state, KeyCode::Char('k'));
+        assert_eq!(state.selected(), Some(3));
+        press(&mut state, KeyCode::Char('j'));
+        assert_eq!(state.selected(), Some(5));
+
+        // Diffing needs two marks, and compares the older datum with the newer.
+        press(&mut state, KeyCode::Char(' '));
+        press(&mut state, KeyCode::Char('d'));
+        assert_eq!(state.view, View::Summary);
+        assert_eq!(state.status, "mark two datums to diff them");
+        press(&mut state, KeyCode::Up);
+        press(&mut state, KeyCode::Char(' '));
+        assert_eq!(state.marked, vec![5, 3]);
+        press(&mut state, KeyCode::Char('d'));
+        assert_eq!(state.view, View::Diff(3, 5));
+        press(&mut state, KeyCode::Esc);
+        assert_eq!(state.view, View::Summary);
+
+        // Marking a third datum forgets the oldest mark, and marking a datum again unmarks it.
+        press(&mut state, KeyCode::Down);
+        press(&mut state, KeyCode::Down);
+        press(&mut state, KeyCode::Char(' '));
+        assert_eq!(state.marked, vec![3, 8]);
+        press(&mut state, KeyCode::Char(' '));
+        assert_eq!(state.marked, vec![3]);
+        press(&mut state, KeyCode::Char(' '));
+        press(&mut state, KeyCode::Char('d'));
+        assert_eq!(state.view, View::Diff(3, 8));
+        // Moving shows the selected datum again.
+        press(&mut state, KeyCode::Up);
+        assert_eq!(state.view, View::Summary);
+
+        // Only presses count.
+        let mut release = KeyEvent::from(KeyCode::Char('q'));
+        release.kind = KeyEventKind::Release;
+        assert!(state.handle_key(release).is_continue());
+        let quit = KeyEvent::from(KeyCode::Char('q'));
+        assert!(state.handle_key(quit).is_break());
+
+        // With no datums, nothing is selected or marked.
+        let mut empty = State::new(Vec::new());
+        press(&mut empty, KeyCode::Up);
+        press(&mut empty, KeyCode::Down);
+        press(&mut empty, KeyCode::Char(' '));
+        assert_eq!(empty.selected(), None);
+        assert!(empty.marked.is_empty());
+    }
+
+    #[test]
+    fn tui_restores_terminal_on_panic() {
+        use crate::tui::OnDrop;
+        use std::{cell::Cell, panic};
+        let restored = Cell::new(false);
+        let r = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            let _restore = OnDrop(|| restored.set(true));
+            panic!("drawing failed");
+        }));
+        assert!(r.is_err());
+        assert!(restored.get());
     }
 }
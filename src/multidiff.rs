@@ -0,0 +1,320 @@
+//! Comparing two datums on several metrics at once, with a group of columns per metric.
+
+use crate::{
+    App, DatumRef, DiffFormats, DiffOptions, DiffRow, IPS_METRIC, OutputFormat, RatioCi,
+    ResultFile, SummaryStats, check_comparable, colour_enabled, csv_field, higher_is_better,
+    human_durations, named_header, new_table, order, select_benchmarks, tested_diff_rows,
+    too_few_warning, write_descriptions,
+};
+use comfy_table::{Attribute, Cell, CellAlignment, Color};
+use rand::rngs::StdRng;
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, Write};
+use std::process;
+
+/// The summaries of one metric for each side of a comparison.
+pub(crate) type MetricSummaries = (HashMap<String, SummaryStats>, HashMap<String, SummaryStats>);
+
+impl App {
+    /// Print a comparison of two datums on each of `metrics`.
+    ///
+    /// A metric missing from either datum is shown as `n/a` rather than being an error, as are
+    /// benchmarks which only one datum has a metric for.
+    pub(crate) fn cmd_diff_metrics(
+        &self,
+        r1: &DatumRef,
+        r2: &DatumRef,
+        metrics: &[String],
+        wide: bool,
+        format: OutputFormat,
+        opts: &DiffOptions,
+    ) {
+        if let Err(e) = self.diff_metrics(r1, r2, metrics, wide, format, opts) {
+            eprintln!("{e}");
+            process::exit(1);
+        }
+    }
+
+    fn diff_metrics(
+        &self,
+        r1: &DatumRef,
+        r2: &DatumRef,
+        metrics: &[String],
+        wide: bool,
+        format: OutputFormat,
+        opts: &DiffOptions,
+    ) -> Result<(), String> {
+        opts.output.check().map_err(|e| format!("error: {e}"))?;
+        let qualify = r1.state_dir.is_some() || r2.state_dir.is_some();
+        let (name1, name2) = (r1.name(qualify), r2.name(qualify));
+        let (mut results1, extra1) = self.load_ref(r1).map_err(|e| format!("error: {e}"))?;
+        let (mut results2, extra2) = self.load_ref(r2).map_err(|e| format!("error: {e}"))?;
+        if !opts.benchmarks.is_empty() {
+            select_benchmarks(
+                &opts.benchmarks,
+                (&name1, &mut results1),
+                (&name2, &mut results2),
+            )?;
+        }
+        // Iterations per second are comparable whatever the number of iterations.
+        let compare_iters = metrics.iter().any(|m| m != IPS_METRIC);
+        check_comparable(
+            &results1,
+            &extra1,
+            &results2,
+            &extra2,
+            compare_iters,
+            opts.force,
+        )?;
+
+        let names = [name1.as_str(), name2.as_str()];
+        let mut summaries = Vec::new();
+        for metric in metrics {
+            match metric_summaries(
+                &results1,
+                &results2,
+                names,
+                metric,
+                opts,
+                &mut self.rng.borrow_mut(),
+            )? {
+                Some(s) => summaries.push(Some(s)),
+                None => {
+                    for (name, results) in names.iter().zip([&results1, &results2]) {
+                        if let Err(e) = results.for_metric(metric) {
+                            eprintln!("warning: {metric} is n/a: {name}: {e}");
+                        }
+                    }
+                    summaries.push(None);
+                }
+            }
+        }
+        let rows = metrics
+            .iter()
+            .zip(&summaries)
+            .map(|(metric, s)| {
+                s.as_ref().map_or_else(HashMap::new, |(s1, s2)| {
+                    tested_diff_rows(
+                        s1,
+                        s2,
+                        opts.min_samples,
+                        higher_is_better(metric),
+                        opts.test,
+                    )
+                    .into_iter()
+                    .map(|r| (r.key, r))
+                    .collect::<HashMap<_, _>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut keys = results1
+            .data
+            .keys()
+            .chain(results2.data.keys())
+            .map(String::as_str)
+            .collect::<BTreeSet<_>>();
+        keys.extend(rows.iter().flat_map(|r| r.keys().copied()));
+        let keys = order::arrange(
+            keys.into_iter().collect(),
+            |k| k,
+            |k| rows.first()?.get(k),
+            &opts.order,
+        );
+        if let Some(w) = order::unmatched_warning(&keys.unmatched) {
+            eprintln!("warning: {w}");
+        }
+        let (keys, pinned) = (keys.rows, keys.pinned);
+
+        let render = |out: &mut dyn Write, format: OutputFormat, colour: bool| {
+            match format {
+                OutputFormat::Table => {
+                    let fmts = rows
+                        .iter()
+                        .zip(metrics)
+                        .map(|(r, metric)| {
+                            let rows = r.values().cloned().collect::<Vec<_>>();
+                            DiffFormats::new(&rows, opts.thousands, human_durations(metric))
+                        })
+                        .collect::<Vec<_>>();
+                    let mut table = new_table(colour && !opts.deterministic);
+                    let right = |s: String| Cell::new(s).set_alignment(CellAlignment::Right);
+                    let mut header = vec![Cell::new("Benchmark")];
+                    for metric in metrics {
+                        if wide {
+                            header.push(right(named_header(&format!("{metric}: {name1}"), metric)));
+                            header.push(right(named_header(&format!("{metric}: {name2}"), metric)));
+                        }
+                        header.push(right(format!("{metric} ratio")));
+                    }
+                    table.set_header(header);
+                    for (i, k) in keys.iter().enumerate() {
+                        if i == pinned && pinned > 0 {
+                            table.add_row(Vec::<Cell>::new());
+                        }
+                        let mut cells = vec![Cell::new(*k)];
+                        for (rows, fmts) in rows.iter().zip(&fmts) {
+                            let Some(row) = rows.get(k) else {
+                                if wide {
+                                    cells.push(right("n/a".to_owned()));
+                                    cells.push(right("n/a".to_owned()));
+                                }
+                                cells.push(right("n/a".to_owned()).add_attribute(Attribute::Dim));
+                                continue;
+                            };
+                            let [v1, v2, _, ratio] = row.columns(fmts);
+                            if wide {
+                                cells.push(right(v1));
+                                cells.push(right(v2));
+                            }
+                            cells.push(ratio_cell(row, ratio));
+                        }
+                        table.add_row(cells);
+                    }
+                    writeln!(out, "confidence level: {}%\n", opts.confidence)?;
+                    writeln!(out, "{table}")?;
+                    writeln!(
+                        out,
+                        "\nratio = {name2} / {name1} of each metric; better/worse = significantly so \
+                         at {}% confidence, otherwise indistinguishable; rows with fewer than {} \
+                         samples are indistinguishable; n/a = the metric is missing from either datum",
+                        opts.confidence, opts.min_samples
+                    )?;
+                    if opts.describe {
+                        write_descriptions(out, keys.iter().copied(), &[&extra1, &extra2])?;
+                    }
+                }
+                OutputFormat::Csv => {
+                    writeln!(
+                        out,
+                        "benchmark,metric,mean1,ci1,n1,mean2,ci2,n2,ratio,significant"
+                    )?;
+                    for k in &keys {
+                        for (metric, rows) in metrics.iter().zip(&rows) {
+                            let Some(row) = rows.get(k) else {
+                                continue;
+                            };
+                            writeln!(
+                                out,
+                                "{},{},{},{},{},{},{},{},{},{}",
+                                csv_field(k),
+                                csv_field(metric),
+                                row.s1.mean,
+                                row.s1.ci,
+                                row.s1.n,
+                                row.s2.mean,
+                                row.s2.ci,
+                                row.s2.n,
+                                row.ratio.map_or(String::new(), |r| r.to_string()),
+                                row.significant
+                            )?;
+                        }
+                    }
+                }
+                OutputFormat::Json => {
+                    let urls = self.benchmark_urls(&[&extra1, &extra2]);
+                    writeln!(out, "{}", json_rows(&keys, metrics, &rows, &urls))?;
+                }
+            }
+            Ok(())
+        };
+        let output = &opts.output;
+        output
+            .to_file(|out| render(out, format, false))
+            .map_err(|e| format!("error: {e}"))?;
+        let stdout = &mut io::stdout().lock();
+        if output.path.is_none() {
+            render(stdout, format, colour_enabled()).map_err(|e| format!("error: {e}"))?;
+        } else if output.terminal() {
+            // The file gets the format asked for, and the terminal a table.
+            render(stdout, OutputFormat::Table, colour_enabled())
+                .map_err(|e| format!("error: {e}"))?;
+        }
+        Ok(())
+    }
+}
+
+/// The JSON output of a comparison of `metrics`, one of `rows` per metric, with an object per
+/// benchmark in `keys` holding its position in `keys` as `display_order`, its URL in `urls`, if
+/// any, and its row for each metric.
+pub(crate) fn json_rows(
+    keys: &[&str],
+    metrics: &[String],
+    rows: &[HashMap<&str, DiffRow>],
+    urls: &HashMap<String, String>,
+) -> serde_json::Value {
+    let mut benchmarks = serde_json::Map::new();
+    for (i, k) in keys.iter().enumerate() {
+        let mut by_metric = serde_json::Map::new();
+        by_metric.insert("display_order".to_owned(), i.into());
+        if let Some(url) = urls.get(*k) {
+            by_metric.insert("url".to_owned(), url.clone().into());
+        }
+        for (metric, rows) in metrics.iter().zip(rows) {
+            let value = rows.get(k).map_or(serde_json::Value::Null, |row| {
+                // Unstable intervals have no bounds worth giving.
+                let ratio_bounds = match row.ratio_ci {
+                    Some(RatioCi::Bounds(lo, hi)) => Some((lo, hi)),
+                    _ => None,
+                };
+                serde_json::json!({
+                    "mean1": row.s1.mean,
+                    "ci1": row.s1.ci,
+                    "n1": row.s1.n,
+                    "mean2": row.s2.mean,
+                    "ci2": row.s2.ci,
+                    "n2": row.s2.n,
+                    "ratio": row.ratio,
+                    "ratio_lo": ratio_bounds.map(|(lo, _)| lo),
+                    "ratio_hi": ratio_bounds.map(|(_, hi)| hi),
+                    "significant": row.significant,
+                    "improved": row.significant && row.improved(),
+                })
+            });
+            by_metric.insert(metric.clone(), value);
+        }
+        benchmarks.insert((*k).to_owned(), by_metric.into());
+    }
+    serde_json::Value::Object(benchmarks)
+}
+
+/// The summaries of `metric` in `results1` and `results2` (whose datums are called `names`),
+/// restricted to the benchmarks both have it for, or `None` if either lacks the metric.
+///
+/// Fails if the statistic in `opts` can't be computed.
+pub(crate) fn metric_summaries(
+    results1: &ResultFile,
+    results2: &ResultFile,
+    names: [&str; 2],
+    metric: &str,
+    opts: &DiffOptions,
+    rng: &mut StdRng,
+) -> Result<Option<MetricSummaries>, String> {
+    let (Ok(d1), Ok(d2)) = (results1.for_metric(metric), results2.for_metric(metric)) else {
+        return Ok(None);
+    };
+    let [s1, s2] = [(d1, names[0]), (d2, names[1])].map(|(d, name)| {
+        let (summaries, too_few) = d
+            .summarise_by(opts.stat, opts.confidence, rng)
+            .map_err(|e| format!("{name}: {e}"))?;
+        if let Some(w) = too_few_warning(name, opts.stat, &too_few) {
+            eprintln!("warning: {metric}: {w}");
+        }
+        Ok::<_, String>(summaries)
+    });
+    let (mut s1, mut s2) = (s1?, s2?);
+    s1.retain(|k, _| s2.contains_key(k));
+    s2.retain(|k, _| s1.contains_key(k));
+    Ok(Some((s1, s2)))
+}
+
+/// The ratio of a row, marked and coloured with whether it's significantly better or worse.
+fn ratio_cell(row: &DiffRow, ratio: String) -> Cell {
+    let cell = |s: String| Cell::new(s).set_alignment(CellAlignment::Right);
+    match (row.significant, row.improved()) {
+        (false, _) => cell(format!("{ratio}       "))
+            .fg(Color::Magenta)
+            .add_attribute(Attribute::Dim),
+        (true, true) => cell(format!("{ratio} better")).fg(Color::Green),
+        (true, false) => cell(format!("{ratio} worse ")).fg(Color::Red),
+    }
+}
@@ -0,0 +1,40 @@
+//! Setting the scheduling priority (niceness) of haste itself and of the pexecs it runs, so that a
+//! run can leave the machine usable, and haste's own work can be kept out of a benchmark's way.
+
+use std::ffi::{c_int, c_uint};
+use std::io;
+use std::ops::RangeInclusive;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+/// The niceness values Unix allows, from the highest priority to the lowest.
+pub(crate) const RANGE: RangeInclusive<i32> = -20..=19;
+
+/// `setpriority`'s `which` for a single process.
+const PRIO_PROCESS: c_int = 0;
+
+unsafe extern "C" {
+    fn setpriority(which: c_int, who: c_uint, prio: c_int) -> c_int;
+}
+
+/// Set the niceness of the current process, which the processes it starts inherit.
+///
+/// Only privileged users can lower a process's niceness.
+pub(crate) fn set(nice: i32) -> io::Result<()> {
+    // SAFETY: `setpriority` only changes the scheduling priority of the current process.
+    if unsafe { setpriority(PRIO_PROCESS, 0, nice) } == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Make `cmd` run with niceness `nice`, whatever haste's own is. If that can't be set, `cmd`
+/// fails to spawn.
+pub(crate) fn apply(cmd: &mut Command, nice: i32) {
+    // SAFETY: between `fork` and `exec`, `set` makes one system call and reads `errno`, neither
+    // of which allocates or takes locks.
+    unsafe {
+        cmd.pre_exec(move || set(nice));
+    }
+}
@@ -0,0 +1,105 @@
+//! Posting datums to an external service after a bench run.
+
+use crate::{ConfidenceLevel, ExtraToml, ResultFile, config::Notify};
+use serde::Serialize;
+use std::{collections::HashMap, env, path::Path, time::Duration};
+
+/// The JSON document sent for a datum.
+#[derive(Serialize)]
+pub(crate) struct Payload<'a> {
+    pub(crate) datum: usize,
+    pub(crate) comment: Option<&'a str>,
+    /// The executor group the datum was made to compare, if any.
+    pub(crate) executor_group: Option<&'a str>,
+    pub(crate) start_time: Option<u64>,
+    pub(crate) end_time: Option<u64>,
+    pub(crate) duration_secs: Option<f64>,
+    pub(crate) truncated: bool,
+    /// The confidence level (as a percentage) used for the confidence intervals.
+    pub(crate) confidence: f64,
+    /// Benchmark key -> summary statistics.
+    pub(crate) benchmarks: HashMap<&'a str, BenchSummary>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct BenchSummary {
+    pub(crate) mean: f64,
+    pub(crate) ci: f64,
+    pub(crate) samples: usize,
+}
+
+/// The payload for datum `id`, whose metadata is `extra` and results are `results`.
+pub(crate) fn payload<'a>(
+    id: usize,
+    extra: &'a ExtraToml,
+    results: &'a ResultFile,
+    confidence: ConfidenceLevel,
+) -> Payload<'a> {
+    let summaries = results.summarise(confidence);
+    Payload {
+        datum: id,
+        comment: extra.comment.as_deref(),
+        executor_group: extra.executor_group.as_deref(),
+        start_time: extra.start_time,
+        end_time: extra.end_time,
+        duration_secs: extra.duration_secs,
+        truncated: extra.truncated,
+        confidence: confidence.as_percent(),
+        benchmarks: results
+            .data
+            .iter()
+            .filter_map(|(k, samples)| {
+                let s = summaries.get(k)?;
+                let summary = BenchSummary {
+                    mean: s.mean,
+                    ci: s.ci,
+                    samples: samples.len(),
+                };
+                Some((k.as_str(), summary))
+            })
+            .collect(),
+    }
+}
+
+/// The `[notify]` section of the config in `config_file`, if it has one with a URL to send to.
+pub(crate) fn configured<'a>(
+    notify: Option<&'a Notify>,
+    config_file: &Path,
+) -> Result<&'a Notify, String> {
+    match notify {
+        None => Err(format!("no [notify] section in {}", config_file.display())),
+        Some(n) if n.url.trim().is_empty() => Err(format!(
+            "the [notify] section of {} has no url",
+            config_file.display()
+        )),
+        Some(n) => Ok(n),
+    }
+}
+
+/// POST `payload` as JSON to the URL in `notify`.
+pub(crate) fn post(notify: &Notify, payload: &Payload) -> Result<(), String> {
+    send(notify, payload, |var| env::var(var).ok())
+}
+
+/// POST `payload` as JSON to the URL in `notify`, looking up environment variables with `env`.
+pub(crate) fn send(
+    notify: &Notify,
+    payload: &Payload,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<(), String> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(Duration::from_secs(notify.timeout_secs)))
+        .build()
+        .into();
+    let mut req = agent.post(&notify.url);
+    for (k, v) in &notify.headers {
+        req = req.header(k, v);
+    }
+    if let Some(var) = &notify.token_env {
+        let token = env(var).ok_or_else(|| format!("environment variable {var} is not set"))?;
+        req = req.header("Authorization", format!("Bearer {token}"));
+    }
+    req.send_json(payload)
+        .map(|_| ())
+        .map_err(|e| format!("failed to POST to {}: {e}", notify.url))
+}
@@ -0,0 +1,149 @@
+//! Choosing the order in which the rows of a comparison are shown: pinning the benchmarks that
+//! should always come first, and sorting the rest.
+
+use crate::DiffRow;
+use clap::ValueEnum;
+
+/// What to sort the rows of a comparison by.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub(crate) enum SortBy {
+    /// The benchmark key.
+    Name,
+    /// The ratio of the second datum's value to the first's, smallest first.
+    Ratio,
+    /// The difference between the datums' values, smallest (most negative) first.
+    Delta,
+    /// The half-width of the wider of the two confidence intervals, narrowest first.
+    CiWidth,
+}
+
+/// How to order the rows of a comparison.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RowOrder {
+    /// Patterns of the benchmark keys to show first, in this order.
+    pub(crate) pins: Vec<String>,
+    /// What to sort the other rows by, if not by significance and then by change.
+    pub(crate) sort_by: Option<SortBy>,
+    /// Reverse the order of the other rows.
+    pub(crate) reverse: bool,
+}
+
+/// Rows put in the order a [RowOrder] asks for.
+pub(crate) struct Arranged<T> {
+    /// The rows, with the pinned ones first.
+    pub(crate) rows: Vec<T>,
+    /// How many of `rows` are pinned.
+    pub(crate) pinned: usize,
+    /// The pin patterns which matched no row.
+    pub(crate) unmatched: Vec<String>,
+}
+
+/// Does `key` match `pattern`, in which `*` stands for any run of characters (including `/`) and
+/// `?` for any one character?
+pub(crate) fn glob_match(pattern: &str, key: &str) -> bool {
+    let (pattern, key) = (
+        pattern.chars().collect::<Vec<_>>(),
+        key.chars().collect::<Vec<_>>(),
+    );
+    let (mut p, mut k) = (0, 0);
+    // Where to resume after the last `*`, if the characters after it fail to match.
+    let mut backtrack = None;
+    while k < key.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == key[k]) {
+            p += 1;
+            k += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, k));
+            p += 1;
+        } else if let Some((star, from)) = backtrack {
+            // Let the `*` swallow one more character.
+            backtrack = Some((star, from + 1));
+            p = star + 1;
+            k = from + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// The value of `row` that `sort_by` sorts by, if it has one.
+fn sort_value(row: &DiffRow, sort_by: SortBy) -> Option<f64> {
+    match sort_by {
+        SortBy::Name => None,
+        SortBy::Ratio => row.ratio,
+        SortBy::Delta => Some(row.s2.mean - row.s1.mean),
+        SortBy::CiWidth => Some(row.s1.ci.max(row.s2.ci)),
+    }
+    .filter(|v| !v.is_nan())
+}
+
+/// Put `rows`, which are in the default order, in the order `order` asks for, where `key` is a
+/// row's benchmark key and `diff` its comparison, if it has one.
+///
+/// Rows without a value to sort by come after those with one, however `order` sorts.
+pub(crate) fn arrange<T>(
+    mut rows: Vec<T>,
+    key: impl Fn(&T) -> &str,
+    diff: impl Fn(&T) -> Option<&DiffRow>,
+    order: &RowOrder,
+) -> Arranged<T> {
+    match order.sort_by {
+        None if order.reverse => rows.reverse(),
+        None => (),
+        Some(SortBy::Name) => {
+            rows.sort_by(|a, b| {
+                let by_key = key(a).cmp(key(b));
+                if order.reverse {
+                    by_key.reverse()
+                } else {
+                    by_key
+                }
+            });
+        }
+        Some(sort_by) => {
+            rows.sort_by(|a, b| {
+                let values = (
+                    diff(a).and_then(|r| sort_value(r, sort_by)),
+                    diff(b).and_then(|r| sort_value(r, sort_by)),
+                );
+                match values {
+                    (Some(v1), Some(v2)) if order.reverse => v2.total_cmp(&v1),
+                    (Some(v1), Some(v2)) => v1.total_cmp(&v2),
+                    (v1, v2) => v2.is_some().cmp(&v1.is_some()),
+                }
+                .then_with(|| key(a).cmp(key(b)))
+            });
+        }
+    }
+
+    let mut pinned = Vec::new();
+    let mut unmatched = Vec::new();
+    for pattern in &order.pins {
+        if !rows
+            .iter()
+            .chain(&pinned)
+            .any(|r| glob_match(pattern, key(r)))
+        {
+            unmatched.push(pattern.clone());
+            continue;
+        }
+        let (matched, rest) = rows
+            .into_iter()
+            .partition::<Vec<_>, _>(|r| glob_match(pattern, key(r)));
+        pinned.extend(matched);
+        rows = rest;
+    }
+    let n_pinned = pinned.len();
+    pinned.extend(rows);
+    Arranged {
+        rows: pinned,
+        pinned: n_pinned,
+        unmatched,
+    }
+}
+
+/// The warning for pin patterns that matched no benchmark, if there are any.
+pub(crate) fn unmatched_warning(unmatched: &[String]) -> Option<String> {
+    (!unmatched.is_empty()).then(|| format!("--pin matched no benchmark: {}", unmatched.join(", ")))
+}
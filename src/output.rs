@@ -0,0 +1,96 @@
+//! Where commands such as `diff` and `show` write what they show: to the terminal, and with
+//! `-o`, to a file as well.
+//!
+//! Renderers write to an [io::Write], so that the same rendering serves the terminal, a file, and
+//! tests, which can render into a buffer.
+
+use crate::colour_enabled;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// Where to write a command's output.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct OutputTarget {
+    /// A file to write the output to as well, without colour.
+    pub(crate) path: Option<PathBuf>,
+    /// Replace `path` if it already exists.
+    pub(crate) overwrite: bool,
+    /// Don't print the output to the terminal, only to `path`.
+    pub(crate) quiet: bool,
+}
+
+impl OutputTarget {
+    /// Check that the output file can be written, before doing the work of rendering it.
+    pub(crate) fn check(&self) -> Result<(), String> {
+        match &self.path {
+            Some(path) if !self.overwrite && path.exists() => Err(format!(
+                "{} already exists: use --overwrite to replace it",
+                path.display()
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Should the output be printed to the terminal?
+    pub(crate) fn terminal(&self) -> bool {
+        !self.quiet
+    }
+
+    /// Write what `render` writes to the output file, if there is one, creating the directories
+    /// it's in.
+    pub(crate) fn to_file(
+        &self,
+        render: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+    ) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut buf = Vec::new();
+        render(&mut buf).map_err(|e| e.to_string())?;
+        let err = |e: io::Error| format!("failed to write {}: {e}", path.display());
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(err)?;
+        }
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .create_new(!self.overwrite)
+            .open(path)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::AlreadyExists => format!(
+                    "{} already exists: use --overwrite to replace it",
+                    path.display()
+                ),
+                _ => err(e),
+            })?;
+        file.write_all(&buf).map_err(err)
+    }
+
+    /// Write what `render` writes to the output file (without colour), if there is one, and print
+    /// it to the terminal (in colour, if it's enabled), unless quiet. `render` is passed where to
+    /// write, and whether to use colour.
+    pub(crate) fn write(
+        &self,
+        render: impl Fn(&mut dyn Write, bool) -> io::Result<()>,
+    ) -> Result<(), String> {
+        self.write_to(&mut io::stdout().lock(), colour_enabled(), render)
+    }
+
+    /// As [OutputTarget::write], but with `terminal` standing in for the terminal, and `colour`
+    /// saying whether to use colour there.
+    pub(crate) fn write_to(
+        &self,
+        terminal: &mut dyn Write,
+        colour: bool,
+        render: impl Fn(&mut dyn Write, bool) -> io::Result<()>,
+    ) -> Result<(), String> {
+        // The file first, so that it's written even if the terminal's output is cut short.
+        self.to_file(|out| render(out, false))?;
+        if self.terminal() {
+            render(terminal, colour).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,189 @@
+//! Paired comparisons of two executors whose pexecs were interleaved.
+//!
+//! When pexecs alternate between two executors, the two samples of each round ran under nearly
+//! the same machine conditions. Testing the mean of the per-round differences, rather than
+//! comparing two independent means, cancels out drift that affects both executors alike, and so
+//! can detect much smaller differences.
+
+use crate::{ConfidenceLevel, Interleaving, NumFormat, ResultFile, SummaryStats, split_key};
+use comfy_table::{Cell, CellAlignment, Color, Table};
+use std::collections::{BTreeMap, HashMap};
+
+/// The paired comparison of one benchmark.
+pub(crate) struct PairedRow {
+    /// The benchmark key, without the executor.
+    pub(crate) key: String,
+    /// The mean time of the baseline executor over the pairs.
+    pub(crate) base_mean: f64,
+    /// The mean and confidence interval of the differences `new - base` of each pair.
+    pub(crate) diff: SummaryStats,
+    /// Is the difference significant, i.e. does its confidence interval exclude zero?
+    pub(crate) significant: bool,
+    /// Were there too few pairs for the difference to be significant?
+    pub(crate) too_few: bool,
+}
+
+impl PairedRow {
+    pub(crate) fn is_regression(&self) -> bool {
+        self.significant && self.diff.mean > 0.
+    }
+
+    /// The change relative to the baseline, as a percentage.
+    fn change(&self) -> f64 {
+        self.diff.mean / self.base_mean * 100.
+    }
+
+    /// Describe the change, in the same terms as `diff`.
+    fn summary(&self) -> String {
+        if self.too_few {
+            "indistinguishable (too few pairs)".to_owned()
+        } else if !self.significant {
+            "indistinguishable".to_owned()
+        } else if self.diff.mean < 0. {
+            format!("{:.2}% faster", self.change().abs())
+        } else {
+            format!("{:.2}% slower", self.change())
+        }
+    }
+}
+
+/// The differences `new - base` between the samples of each round, where `base_rounds` and
+/// `new_rounds` give the round of each sample in `base` and `new`.
+///
+/// Fails if the rounds don't match the samples, or if any round lacks a sample on either side.
+pub(crate) fn paired_differences(
+    base: &[f64],
+    base_rounds: &[usize],
+    new: &[f64],
+    new_rounds: &[usize],
+) -> Result<Vec<f64>, String> {
+    if base.len() != base_rounds.len() || new.len() != new_rounds.len() {
+        return Err("the pairing information doesn't match the samples".to_owned());
+    }
+    let by_round = |samples: &[f64], rounds: &[usize]| {
+        rounds
+            .iter()
+            .copied()
+            .zip(samples.iter().copied())
+            .collect::<BTreeMap<_, _>>()
+    };
+    let base = by_round(base, base_rounds);
+    let new = by_round(new, new_rounds);
+    let unpaired = base
+        .keys()
+        .filter(|r| !new.contains_key(r))
+        .chain(new.keys().filter(|r| !base.contains_key(r)))
+        .collect::<Vec<_>>();
+    if !unpaired.is_empty() {
+        return Err(format!(
+            "{} pexecs have no partner (did some of them fail?)",
+            unpaired.len()
+        ));
+    }
+    Ok(base.iter().map(|(r, b)| new[r] - b).collect())
+}
+
+/// Compare `base` with `new` in `data` pair by pair, using the pairing information recorded in
+/// `interleaving`.
+///
+/// Only benchmarks run with both executors are compared. Fails if the pairing information is
+/// absent or incomplete for any of them.
+pub(crate) fn paired_rows(
+    data: &ResultFile,
+    interleaving: Option<&Interleaving>,
+    base: &str,
+    new: &str,
+    confidence: ConfidenceLevel,
+    min_samples: usize,
+) -> Result<Vec<PairedRow>, String> {
+    let Some(interleaving) = interleaving.filter(|i| !i.pairs.is_empty()) else {
+        return Err("no pairing information (only datums made by `haste ab` have it)".to_owned());
+    };
+    for name in [base, new] {
+        if !interleaving.executors.iter().any(|e| e == name) {
+            return Err(format!("{name} wasn't one of the interleaved executors"));
+        }
+    }
+    let mut keys = HashMap::<String, HashMap<&str, &str>>::new();
+    for k in data.data.keys() {
+        if let Some((bench, exec)) = split_key(k) {
+            keys.entry(bench).or_default().insert(exec, k);
+        }
+    }
+    let mut rows = Vec::new();
+    for (bench, execs) in keys {
+        let (Some(k1), Some(k2)) = (execs.get(base), execs.get(new)) else {
+            continue;
+        };
+        let rounds = |k: &str| {
+            interleaving
+                .pairs
+                .get(k)
+                .ok_or_else(|| format!("{k}: no pairing information"))
+        };
+        let diffs = paired_differences(&data.data[*k1], rounds(k1)?, &data.data[*k2], rounds(k2)?)
+            .map_err(|e| format!("{bench}: {e}"))?;
+        let base_samples = &data.data[*k1];
+        let diff = SummaryStats::from_samples(&diffs, confidence);
+        let too_few = diff.n < min_samples;
+        let base_mean = base_samples.iter().sum::<f64>() / base_samples.len() as f64;
+        rows.push(PairedRow {
+            key: bench,
+            base_mean,
+            significant: !too_few && base_mean > 0. && diff.mean.abs() > diff.ci,
+            too_few,
+            diff,
+        });
+    }
+    rows.sort_by(|r1, r2| {
+        r2.significant
+            .cmp(&r1.significant)
+            .then(r1.change().total_cmp(&r2.change()))
+            .then(r1.key.cmp(&r2.key))
+    });
+    Ok(rows)
+}
+
+/// Make a table of paired comparisons of `base` with `new`.
+pub(crate) fn paired_table(rows: &[PairedRow], base: &str, new: &str, colour: bool) -> Table {
+    let means = rows.iter().map(|r| r.base_mean).collect::<Vec<_>>();
+    let mean_fmt = NumFormat::new(&means, 0, false, false);
+    let diffs = rows.iter().map(|r| r.diff.mean).collect::<Vec<_>>();
+    let diff_fmt = NumFormat::new(&diffs, 0, true, false);
+    let cis = rows.iter().map(|r| r.diff.ci).collect::<Vec<_>>();
+    let ci_fmt = NumFormat::new(&cis, 0, false, false);
+
+    let mut table = Table::new();
+    table.load_preset(comfy_table::presets::NOTHING);
+    if !colour {
+        table.force_no_tty();
+    }
+    let right = |s: String| Cell::new(s).set_alignment(CellAlignment::Right);
+    table.set_header(vec![
+        Cell::new("Benchmark"),
+        right("Pairs".to_owned()),
+        right(format!("{base} (ms)")),
+        right(format!("{new} - {base} (ms)")),
+        Cell::new("Summary"),
+    ]);
+    for row in rows {
+        let summary = Cell::new(row.summary());
+        let summary = match (row.significant, row.diff.mean < 0.) {
+            (false, _) => summary.fg(Color::Magenta),
+            (true, true) => summary.fg(Color::Green),
+            (true, false) => summary.fg(Color::Red),
+        };
+        table.add_row(vec![
+            Cell::new(&row.key),
+            right(row.diff.n.to_string()),
+            right(mean_fmt.format(row.base_mean)),
+            right(format!(
+                "{} ±{}",
+                diff_fmt.format(row.diff.mean),
+                ci_fmt.format(row.diff.ci)
+            )),
+            summary,
+        ]);
+    }
+    table
+}
@@ -0,0 +1,258 @@
+//! Plotting a comparison of two datums as an SVG chart of each benchmark's mean and confidence
+//! interval in either datum.
+//!
+//! The SVG is written by hand, with every number rounded to a fixed precision, so that the same
+//! comparison always renders to the same bytes.
+
+use crate::junit::escape;
+use crate::{DiffOptions, DiffRow, metric_unit};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// The colours of the two datums' series.
+const COLOURS: [&str; 2] = ["#1f77b4", "#ff7f0e"];
+/// The font size of all text.
+const FONT_SIZE: usize = 12;
+/// The approximate width of a character of [FONT_SIZE] monospace text.
+const CHAR_WIDTH: f64 = 7.3;
+/// The height of each benchmark's row.
+const ROW_HEIGHT: f64 = 28.;
+/// The width of the plotting area.
+const PLOT_WIDTH: f64 = 480.;
+/// The width reserved to the right of the plot for the percentage changes.
+const CHANGE_WIDTH: f64 = 90.;
+const MARGIN: f64 = 10.;
+
+/// Maps values to x coordinates in the plotting area.
+struct Axis {
+    lo: f64,
+    hi: f64,
+    log: bool,
+    /// The x coordinate of `lo`.
+    left: f64,
+}
+
+impl Axis {
+    /// An axis spanning `values`, which must not be empty. On a log scale, only the positive
+    /// values are spanned.
+    fn new(values: &[f64], log: bool, left: f64) -> Self {
+        let (lo, hi) = if log {
+            let positive = values.iter().copied().filter(|v| *v > 0.);
+            let lo = positive.clone().fold(f64::INFINITY, f64::min);
+            let hi = positive.fold(f64::NEG_INFINITY, f64::max);
+            if lo.is_finite() {
+                // Whole decades, so that every tick is a power of ten.
+                (
+                    10f64.powf(lo.log10().floor()),
+                    10f64.powf(hi.log10().ceil()),
+                )
+            } else {
+                (1., 10.)
+            }
+        } else {
+            let lo = values.iter().copied().fold(0f64, f64::min);
+            let hi = values.iter().copied().fold(0f64, f64::max);
+            let step = tick_step(hi - lo);
+            ((lo / step).floor() * step, (hi / step).ceil() * step)
+        };
+        let hi = if hi > lo { hi } else { lo + 1. };
+        Axis { lo, hi, log, left }
+    }
+
+    /// The x coordinate of `v`, clamped to the plotting area.
+    fn x(&self, v: f64) -> f64 {
+        let frac = if self.log {
+            (v.max(self.lo).log10() - self.lo.log10()) / (self.hi.log10() - self.lo.log10())
+        } else {
+            (v - self.lo) / (self.hi - self.lo)
+        };
+        self.left + frac.clamp(0., 1.) * PLOT_WIDTH
+    }
+
+    /// The values to draw ticks at.
+    fn ticks(&self) -> Vec<f64> {
+        if self.log {
+            let (lo, hi) = (
+                self.lo.log10().round() as i32,
+                self.hi.log10().round() as i32,
+            );
+            (lo..=hi).map(|e| 10f64.powi(e)).collect()
+        } else {
+            let step = tick_step(self.hi - self.lo);
+            let n = ((self.hi - self.lo) / step).round() as usize;
+            (0..=n).map(|i| self.lo + step * i as f64).collect()
+        }
+    }
+}
+
+/// A step of 1, 2 or 5 times a power of ten which divides `range` into about five ticks.
+fn tick_step(range: f64) -> f64 {
+    if range <= 0. || !range.is_finite() {
+        return 1.;
+    }
+    let rough = range / 5.;
+    let magnitude = 10f64.powf(rough.log10().floor());
+    let step = [1., 2., 5., 10.]
+        .into_iter()
+        .find(|m| m * magnitude >= rough)
+        .unwrap();
+    step * magnitude
+}
+
+/// Format a tick label, without needless decimal places.
+fn tick_label(v: f64) -> String {
+    if v.abs() >= 1. || v == 0. {
+        format!("{v:.0}")
+    } else {
+        // Enough places for the first significant digit.
+        let places = (-v.abs().log10().floor()) as usize;
+        format!("{v:.places$}")
+    }
+}
+
+/// Render `rows`, comparing the datums called `names` with `comments`, as an SVG chart of each
+/// benchmark's mean and confidence interval in either datum, annotating significant changes. The
+/// x axis has a log scale if `opts.log_scale`. Benchmarks with a URL in `urls` link to it.
+pub(crate) fn render(
+    rows: &[DiffRow],
+    names: [&str; 2],
+    comments: [Option<&str>; 2],
+    urls: &HashMap<String, String>,
+    opts: &DiffOptions,
+) -> String {
+    let log = opts.log_scale;
+    let label_width = rows
+        .iter()
+        .map(|r| r.key.chars().count())
+        .max()
+        .unwrap_or(0) as f64
+        * CHAR_WIDTH
+        + MARGIN;
+    let left = MARGIN + label_width;
+    let mut title = vec![format!(
+        "{} vs. {} ({}% confidence)",
+        names[0], names[1], opts.confidence
+    )];
+    for (name, comment) in names.iter().zip(comments) {
+        if let Some(c) = comment {
+            title.push(format!("{name}: {c}"));
+        }
+    }
+    let line = FONT_SIZE as f64 * 1.5;
+    // The title, then the legend.
+    let top = MARGIN + line * (title.len() + 1) as f64 + MARGIN;
+    let plot_height = ROW_HEIGHT * rows.len().max(1) as f64;
+    let width = left + PLOT_WIDTH + CHANGE_WIDTH + MARGIN;
+    let height = top + plot_height + line * 2. + MARGIN;
+
+    let values = rows
+        .iter()
+        .flat_map(|r| [r.s1, r.s2])
+        .flat_map(|s| [s.mean - s.ci, s.mean + s.ci])
+        .collect::<Vec<_>>();
+    let axis = Axis::new(&values, log, left);
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width:.0}\" height=\"{height:.0}\" \
+         viewBox=\"0 0 {width:.0} {height:.0}\" font-family=\"monospace\" \
+         font-size=\"{FONT_SIZE}\">"
+    )
+    .unwrap();
+    writeln!(svg, "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>").unwrap();
+    for (i, t) in title.iter().enumerate() {
+        let weight = if i == 0 { " font-weight=\"bold\"" } else { "" };
+        writeln!(
+            svg,
+            "<text x=\"{MARGIN}\" y=\"{:.1}\"{weight}>{}</text>",
+            MARGIN + line * (i + 1) as f64,
+            escape(t)
+        )
+        .unwrap();
+    }
+    let legend_y = MARGIN + line * (title.len() + 1) as f64;
+    let mut legend_x = left;
+    for (name, colour) in names.iter().zip(COLOURS) {
+        writeln!(
+            svg,
+            "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"4\" fill=\"{colour}\"/>\
+             <text x=\"{:.1}\" y=\"{legend_y:.1}\">{}</text>",
+            legend_x + 4.,
+            legend_y - 4.,
+            legend_x + 12.,
+            escape(name)
+        )
+        .unwrap();
+        legend_x += 12. + (name.chars().count() + 3) as f64 * CHAR_WIDTH;
+    }
+
+    // Grid lines and tick labels.
+    let bottom = top + plot_height;
+    for t in axis.ticks() {
+        let x = axis.x(t);
+        writeln!(
+            svg,
+            "<line x1=\"{x:.1}\" y1=\"{top:.1}\" x2=\"{x:.1}\" y2=\"{bottom:.1}\" \
+             stroke=\"#ddd\"/><text x=\"{x:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+            bottom + line,
+            tick_label(t)
+        )
+        .unwrap();
+    }
+    let unit = metric_unit(&opts.metric).map_or(String::new(), |u| format!(" ({u})"));
+    let scale = if log { ", log scale" } else { "" };
+    writeln!(
+        svg,
+        "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}{unit}{scale}</text>",
+        left + PLOT_WIDTH / 2.,
+        bottom + line * 2.,
+        escape(&opts.metric)
+    )
+    .unwrap();
+
+    for (i, row) in rows.iter().enumerate() {
+        let y = top + ROW_HEIGHT * i as f64;
+        let mid = y + ROW_HEIGHT / 2.;
+        let label = format!(
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\">{}</text>",
+            left - MARGIN,
+            mid + FONT_SIZE as f64 / 3.,
+            escape(row.key)
+        );
+        match urls.get(row.key) {
+            Some(url) => writeln!(svg, "<a href=\"{}\">{label}</a>", escape(url)).unwrap(),
+            None => writeln!(svg, "{label}").unwrap(),
+        }
+        for (j, (s, colour)) in [row.s1, row.s2].into_iter().zip(COLOURS).enumerate() {
+            let sy = mid + if j == 0 { -5. } else { 5. };
+            let (x1, x2) = (axis.x(s.mean - s.ci), axis.x(s.mean + s.ci));
+            writeln!(
+                svg,
+                "<line x1=\"{x1:.1}\" y1=\"{sy:.1}\" x2=\"{x2:.1}\" y2=\"{sy:.1}\" \
+                 stroke=\"{colour}\" stroke-width=\"2\"/>\
+                 <circle cx=\"{:.1}\" cy=\"{sy:.1}\" r=\"3.5\" fill=\"{colour}\"/>",
+                axis.x(s.mean)
+            )
+            .unwrap();
+        }
+        if row.significant {
+            let colour = if row.improved() { "green" } else { "red" };
+            let change = if row.change.is_infinite() {
+                row.summary()
+            } else {
+                format!("{:+.2}%", row.change)
+            };
+            writeln!(
+                svg,
+                "<text x=\"{:.1}\" y=\"{:.1}\" fill=\"{colour}\">{}</text>",
+                left + PLOT_WIDTH + MARGIN,
+                mid + FONT_SIZE as f64 / 3.,
+                escape(&change)
+            )
+            .unwrap();
+        }
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
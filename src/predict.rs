@@ -0,0 +1,119 @@
+//! Predicting how long a run will take, from the `expected_ms` hints in the config and the times
+//! recorded in the newest datum, and noticing when the two disagree so much that the hint looks
+//! out of date.
+
+use crate::config::Config;
+use crate::events::PlannedJob;
+use crate::{App, ResultFile, runner, stats, units};
+use std::time::Duration;
+
+/// How many times longer or shorter than its hint a benchmark can take before the hint is said to
+/// need updating.
+const MAX_RATIO: f64 = 2.;
+
+/// How long one pexec of a benchmark is expected to take, in milliseconds.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Expected {
+    /// The benchmark's `expected_ms`.
+    pub(crate) hint: Option<f64>,
+    /// The mean time of the benchmark's pexecs in the newest datum, scaled to the number of
+    /// in-process iterations it's now run with.
+    pub(crate) recorded: Option<f64>,
+}
+
+impl Expected {
+    /// How long `job` is expected to take, where `newest` is the newest datum, if there is one.
+    pub(crate) fn new(job: &PlannedJob, newest: Option<&ResultFile>) -> Self {
+        let recorded = newest.and_then(|r| {
+            let samples = r.data.get(&job.key).filter(|s| !s.is_empty())?;
+            let iters = r
+                .params
+                .get(&job.key)
+                .map_or(job.inproc_iters, |p| p.inproc_iters);
+            let mean = stats::mean(samples);
+            // Where a harness chooses its iterations, there's nothing to scale by.
+            Some(match (job.inproc_iters, iters) {
+                (0, _) | (_, 0) => mean,
+                (now, then) => mean * now as f64 / then as f64,
+            })
+        });
+        Expected {
+            hint: job.expected_ms,
+            recorded,
+        }
+    }
+
+    /// The expected time: the hint if there is one, and otherwise the recorded time.
+    pub(crate) fn ms(&self) -> Option<f64> {
+        self.hint.or(self.recorded)
+    }
+
+    /// Is the recorded time more than [MAX_RATIO] times longer or shorter than the hint?
+    pub(crate) fn disagrees(&self) -> bool {
+        match (self.hint, self.recorded) {
+            (Some(h), Some(r)) => r > h * MAX_RATIO || r < h / MAX_RATIO,
+            _ => false,
+        }
+    }
+}
+
+/// How long running `jobs` will take if each of their pexecs, warmup ones included, takes as
+/// long as `expected` (in the same order) says, and the keys of the jobs with no expected time,
+/// which the prediction leaves out.
+pub(crate) fn predict<'a>(
+    jobs: &'a [PlannedJob],
+    expected: &[Expected],
+) -> (Duration, Vec<&'a str>) {
+    let mut ms = 0.;
+    let mut unknown = Vec::new();
+    for (job, e) in jobs.iter().zip(expected) {
+        match e.ms() {
+            Some(t) => ms += t * (job.pexecs + job.warmup_pexecs) as f64,
+            None => unknown.push(job.key.as_str()),
+        }
+    }
+    (Duration::from_secs_f64(ms / 1000.), unknown)
+}
+
+impl App {
+    /// Note each benchmark of `config` whose `expected_ms` disagrees with the time recorded for it
+    /// in the newest datum and, if `show`, print how long running `config` with `opts` is
+    /// predicted to take.
+    pub(crate) fn predict_run(&self, config: &Config, opts: &runner::RunOptions, show: bool) {
+        let jobs = runner::planned_jobs(config, opts);
+        if !show && jobs.iter().all(|j| j.expected_ms.is_none()) {
+            return;
+        }
+        let newest = self.store.ids().last().copied();
+        let results = newest.and_then(|id| self.try_load_results(id).ok());
+        let expected = jobs
+            .iter()
+            .map(|j| Expected::new(j, results.as_ref()))
+            .collect::<Vec<_>>();
+        for (job, e) in jobs.iter().zip(&expected) {
+            if let (true, Some(hint), Some(recorded), Some(id)) =
+                (e.disagrees(), e.hint, e.recorded, newest)
+            {
+                println!(
+                    "note: {} took {recorded:.0}ms per pexec in Datum{id}, but its expected_ms \
+                     is {hint}: consider updating it",
+                    job.key
+                );
+            }
+        }
+        if show {
+            let (duration, unknown) = predict(&jobs, &expected);
+            let mut line = format!(
+                "haste: predicted run time: {}",
+                units::format_duration(duration)
+            );
+            if !unknown.is_empty() {
+                line.push_str(&format!(
+                    " (not counting {} benchmarks with no expected_ms or earlier times)",
+                    unknown.len()
+                ));
+            }
+            println!("{line}");
+        }
+    }
+}
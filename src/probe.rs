@@ -0,0 +1,208 @@
+//! The optional handshake by which a harness says which parts of the harness protocol it
+//! supports, so that haste can tell data a harness doesn't produce from data that's missing.
+//!
+//! Run with the single argument [PROBE_ARG], a conforming harness prints its [Capabilities] as
+//! TOML or JSON and exits successfully. Anything else means protocol version 0: the harness only
+//! writes `PEXEC_WALLCLOCK_MS` to its output file.
+
+use crate::config::Config;
+use crate::runner::{self, Finished, PERF_EVENTS};
+use crate::{App, IPS_METRIC, ITERS_METRIC, TIME_METRIC, new_table};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::process::{self, Command};
+use std::time::Duration;
+
+/// The argument a harness is probed with.
+pub(crate) const PROBE_ARG: &str = "--haste-probe";
+/// How long a harness has to answer a probe.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How much of a harness's answer to a probe is read.
+const PROBE_MAX_OUTPUT: usize = 64 * 1024;
+
+/// What a harness says it supports.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Capabilities {
+    /// The version of the harness protocol, where 0 is a harness that doesn't handshake.
+    #[serde(default)]
+    pub(crate) protocol: u32,
+    /// The metrics the harness reports, besides those haste measures itself.
+    #[serde(default)]
+    pub(crate) metrics: Vec<String>,
+    /// Can the harness choose its own number of in-process iterations, for `inproc_iters = 0`?
+    #[serde(default)]
+    pub(crate) auto_iters: bool,
+}
+
+/// Parse a harness's answer to a probe, as TOML or JSON.
+pub(crate) fn parse(answer: &str) -> Option<Capabilities> {
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return None;
+    }
+    toml::from_str(answer)
+        .ok()
+        .or_else(|| serde_json::from_str(answer).ok())
+}
+
+/// Probe the harness run by `cmd`, treating any failure as protocol version 0.
+fn probe(mut cmd: Command) -> Capabilities {
+    cmd.arg(PROBE_ARG);
+    match runner::run_bounded(&mut cmd, Some(PROBE_TIMEOUT), PROBE_MAX_OUTPUT) {
+        Ok(Finished {
+            output,
+            timed_out: false,
+            truncated: false,
+            ..
+        }) if output.status.success() => {
+            parse(&String::from_utf8_lossy(&output.stdout)).unwrap_or_default()
+        }
+        _ => Capabilities::default(),
+    }
+}
+
+/// Probe the harness of each suite with each local executor that runs a benchmark of the suite
+/// with it, returning the capabilities by suite and executor. Each distinct harness of a suite is
+/// probed once.
+pub(crate) fn probe_suites(config: &Config) -> HashMap<String, HashMap<String, Capabilities>> {
+    let mut all = HashMap::new();
+    for (suite_name, suite) in &config.suites {
+        let mut probed = HashMap::new();
+        let mut by_executor = HashMap::new();
+        for exec_name in config.executors.keys() {
+            let uses_harness = suite
+                .benchmarks
+                .values()
+                .any(|b| b.command.is_none() && b.runs_on(exec_name));
+            if !uses_harness || config.remote_for(exec_name).is_some() {
+                continue;
+            }
+            let Some(harness) = suite.harness_for(exec_name) else {
+                continue;
+            };
+            let caps = probed
+                .entry(harness.to_owned())
+                .or_insert_with(|| {
+                    let mut cmd = Command::new(harness);
+                    cmd.current_dir(&suite.dir).envs(&suite.env);
+                    probe(cmd)
+                })
+                .clone();
+            by_executor.insert(exec_name.clone(), caps);
+        }
+        if !by_executor.is_empty() {
+            all.insert(suite_name.clone(), by_executor);
+        }
+    }
+    all
+}
+
+/// The suites and executors (as `suite (executor)`, sorted) whose harnesses, in `harnesses`,
+/// handshook but don't report `metric`. Harnesses that didn't handshake may report anything.
+pub(crate) fn unsupported(
+    metric: &str,
+    harnesses: &HashMap<String, HashMap<String, Capabilities>>,
+) -> Vec<String> {
+    if [TIME_METRIC, IPS_METRIC, ITERS_METRIC].contains(&metric) || PERF_EVENTS.contains(&metric) {
+        return Vec::new();
+    }
+    let mut missing = harnesses
+        .iter()
+        .flat_map(|(suite, by_executor)| {
+            by_executor
+                .iter()
+                .filter(|(_, c)| c.protocol > 0 && !c.metrics.iter().any(|m| m == metric))
+                .map(move |(executor, _)| format!("{suite} ({executor})"))
+        })
+        .collect::<Vec<_>>();
+    missing.sort();
+    missing
+}
+
+/// If `config` leaves harnesses to choose their in-process iterations, check that every harness
+/// it runs locally said, in `harnesses`, that it can. Otherwise the harness would be asked to run
+/// `auto` iterations, which it might take as none.
+///
+/// Remote executors' harnesses aren't probed, so can't leave harnesses to choose.
+pub(crate) fn check_auto_iters(
+    config: &Config,
+    harnesses: &HashMap<String, HashMap<String, Capabilities>>,
+) -> Result<(), String> {
+    if config.inproc_iters != 0 {
+        return Ok(());
+    }
+    let mut unable = Vec::new();
+    for (suite_name, suite) in &config.suites {
+        for exec_name in config.executors.keys() {
+            if !suite.benchmarks.values().any(|b| b.runs_on(exec_name)) {
+                continue;
+            }
+            let caps = harnesses.get(suite_name).and_then(|h| h.get(exec_name));
+            if !caps.is_some_and(|c| c.auto_iters) {
+                unable.push(format!("{suite_name} ({exec_name})"));
+            }
+        }
+    }
+    if unable.is_empty() {
+        return Ok(());
+    }
+    unable.sort();
+    Err(format!(
+        "inproc_iters = 0 leaves harnesses to choose their in-process iterations, but these \
+         suites' harnesses didn't say they can (with auto_iters = true): {}",
+        unable.join(", ")
+    ))
+}
+
+impl App {
+    /// Check the config, then probe each suite's harness and show what it supports.
+    pub(crate) fn cmd_check(&self) {
+        let config = self.load_config();
+        if let Err(e) = runner::check_keys(&config) {
+            eprintln!("error: {e}");
+            process::exit(1);
+        }
+        let probed = probe_suites(&config);
+        if let Err(e) = check_auto_iters(&config, &probed) {
+            eprintln!("error: {e}");
+            process::exit(1);
+        }
+        println!("{}: ok", self.config_file.display());
+        let harnesses = probed
+            .into_iter()
+            .flat_map(|(suite, by_executor)| {
+                by_executor
+                    .into_iter()
+                    .map(move |(executor, caps)| ((suite.clone(), executor), caps))
+            })
+            .collect::<BTreeMap<_, _>>();
+        if harnesses.is_empty() {
+            println!("no suites run a harness locally");
+            return;
+        }
+        let mut table = new_table(false);
+        table.set_header(vec![
+            "Suite",
+            "Executor",
+            "Protocol",
+            "Auto iters",
+            "Metrics",
+        ]);
+        for ((suite, executor), caps) in harnesses {
+            let protocol = if caps.protocol == 0 {
+                "0 (no handshake)".to_owned()
+            } else {
+                caps.protocol.to_string()
+            };
+            let auto_iters = if caps.auto_iters { "yes" } else { "no" };
+            table.add_row(vec![
+                suite,
+                executor,
+                protocol,
+                auto_iters.to_owned(),
+                caps.metrics.join(", "),
+            ]);
+        }
+        println!("\n{table}");
+    }
+}
@@ -0,0 +1,252 @@
+//! Ranking the executors of a datum against each other, per benchmark and overall.
+
+use crate::stats::geomean;
+use crate::{App, ConfidenceLevel, NumFormat, OutputFormat, SummaryStats, csv_field, split_key};
+use comfy_table::{Cell, CellAlignment, Table};
+use std::collections::{BTreeMap, HashMap};
+
+/// An executor's place in a ranking.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Placing {
+    pub(crate) executor: String,
+    /// The rank, starting from 1. Executors which tie share a rank, and the next rank is skipped.
+    pub(crate) rank: usize,
+    /// Does this executor share its rank with another?
+    pub(crate) joint: bool,
+    /// The executor's mean time, or its geometric mean normalised time for the overall ranking.
+    pub(crate) value: f64,
+    /// The half-width of the confidence interval of `value`, if it has one.
+    pub(crate) ci: Option<f64>,
+}
+
+/// Rank `placings`, which must be sorted fastest first, so that each executor ties with the one
+/// before it if `tied` says so.
+fn assign_ranks(placings: &mut [Placing], tied: impl Fn(usize, usize) -> bool) {
+    for i in 0..placings.len() {
+        placings[i].rank = if i > 0 && tied(i - 1, i) {
+            placings[i - 1].rank
+        } else {
+            i + 1
+        };
+    }
+    for i in 0..placings.len() {
+        let rank = placings[i].rank;
+        placings[i].joint = placings.iter().filter(|p| p.rank == rank).count() > 1;
+    }
+}
+
+/// Rank the executors of one benchmark from fastest to slowest. Executors whose confidence
+/// intervals overlap that of the next fastest share its rank.
+pub(crate) fn rank_benchmark(stats: &[(&str, &SummaryStats)]) -> Vec<Placing> {
+    let mut stats = stats.to_vec();
+    stats.sort_by(|(e1, s1), (e2, s2)| s1.mean.total_cmp(&s2.mean).then(e1.cmp(e2)));
+    let mut placings = stats
+        .iter()
+        .map(|(e, s)| Placing {
+            executor: (*e).to_owned(),
+            rank: 0,
+            joint: false,
+            value: s.mean,
+            ci: Some(s.ci),
+        })
+        .collect::<Vec<_>>();
+    assign_ranks(&mut placings, |i, j| stats[i].1.ci_overlaps(stats[j].1));
+    placings
+}
+
+/// Rank the executors overall by the geometric mean of their times normalised to the fastest
+/// executor of each benchmark.
+///
+/// Only benchmarks which every executor ran, and whose fastest time is non-zero, count.
+/// Returns the ranking and the number of benchmarks counted.
+pub(crate) fn rank_overall(
+    benchmarks: &BTreeMap<String, Vec<(&str, &SummaryStats)>>,
+    executors: &[&str],
+) -> (Vec<Placing>, usize) {
+    let mut normalised = HashMap::<&str, Vec<f64>>::new();
+    let mut counted = 0;
+    for stats in benchmarks.values() {
+        let best = stats
+            .iter()
+            .map(|(_, s)| s.mean)
+            .fold(f64::INFINITY, f64::min);
+        if stats.len() < executors.len() || !(best > 0. && best.is_finite()) {
+            continue;
+        }
+        counted += 1;
+        for (e, s) in stats {
+            normalised.entry(e).or_default().push(s.mean / best);
+        }
+    }
+    if counted == 0 {
+        return (Vec::new(), 0);
+    }
+    let mut placings = executors
+        .iter()
+        .map(|e| Placing {
+            executor: (*e).to_owned(),
+            rank: 0,
+            joint: false,
+            value: geomean(&normalised[e]),
+            ci: None,
+        })
+        .collect::<Vec<_>>();
+    placings.sort_by(|p1, p2| {
+        p1.value
+            .total_cmp(&p2.value)
+            .then(p1.executor.cmp(&p2.executor))
+    });
+    let values = placings.iter().map(|p| p.value).collect::<Vec<_>>();
+    assign_ranks(&mut placings, |i, j| values[i] == values[j]);
+    (placings, counted)
+}
+
+/// Format a rank for a table, e.g. `2` or `=2` if it's shared.
+fn rank_str(p: &Placing) -> String {
+    if p.joint {
+        format!("={}", p.rank)
+    } else {
+        p.rank.to_string()
+    }
+}
+
+impl App {
+    /// Print the executors of a datum ranked from fastest to slowest, per benchmark and overall.
+    pub(crate) fn cmd_rank(&self, id: usize, confidence: ConfidenceLevel, format: OutputFormat) {
+        let summaries = self.load_summary(id, confidence);
+        let mut benchmarks = BTreeMap::<String, Vec<(&str, &SummaryStats)>>::new();
+        for (k, s) in &summaries {
+            let Some((bench, exec)) = split_key(k) else {
+                continue;
+            };
+            benchmarks.entry(bench).or_default().push((exec, s));
+        }
+        let mut executors = benchmarks
+            .values()
+            .flatten()
+            .map(|(e, _)| *e)
+            .collect::<Vec<_>>();
+        executors.sort();
+        executors.dedup();
+        if executors.len() < 2 {
+            eprintln!("error: Datum{id} needs at least two executors to rank");
+            std::process::exit(1);
+        }
+
+        let ranked = benchmarks
+            .iter()
+            .map(|(b, stats)| (b.as_str(), rank_benchmark(stats)))
+            .collect::<Vec<_>>();
+        let (overall, counted) = rank_overall(&benchmarks, &executors);
+
+        match format {
+            OutputFormat::Table => {
+                let means = ranked
+                    .iter()
+                    .flat_map(|(_, ps)| ps.iter().map(|p| p.value))
+                    .collect::<Vec<_>>();
+                let mean_fmt = NumFormat::new(&means, 0, false, false);
+                let cis = ranked
+                    .iter()
+                    .flat_map(|(_, ps)| ps.iter().filter_map(|p| p.ci))
+                    .collect::<Vec<_>>();
+                let ci_fmt = NumFormat::new(&cis, 0, false, false);
+                let mut table = Table::new();
+                table.load_preset(comfy_table::presets::NOTHING);
+                table.set_header(vec![
+                    Cell::new("Benchmark").set_alignment(CellAlignment::Left),
+                    Cell::new("Rank").set_alignment(CellAlignment::Right),
+                    Cell::new("Executor").set_alignment(CellAlignment::Left),
+                    Cell::new(format!("Datum{id} (ms)")).set_alignment(CellAlignment::Right),
+                ]);
+                for (bench, placings) in &ranked {
+                    for (i, p) in placings.iter().enumerate() {
+                        let mean = format!(
+                            "{} ±{}",
+                            mean_fmt.format(p.value),
+                            ci_fmt.format(p.ci.unwrap())
+                        );
+                        table.add_row(vec![
+                            Cell::new(if i == 0 { bench } else { "" }),
+                            Cell::new(rank_str(p)).set_alignment(CellAlignment::Right),
+                            Cell::new(&p.executor),
+                            Cell::new(mean).set_alignment(CellAlignment::Right),
+                        ]);
+                    }
+                }
+                println!("confidence level: {confidence}%\n");
+                println!("{table}");
+                if overall.is_empty() {
+                    println!(
+                        "\nno benchmark was run by every executor, so there's no overall ranking"
+                    );
+                    return;
+                }
+                println!(
+                    "\noverall (geometric mean of times normalised to the fastest executor, over \
+                     {counted} benchmarks):"
+                );
+                for p in &overall {
+                    println!("{:>4}  {:.3}  {}", rank_str(p), p.value, p.executor);
+                }
+            }
+            OutputFormat::Csv => {
+                println!("benchmark,executor,rank,joint,mean,ci,normalised");
+                for (bench, placings) in &ranked {
+                    let best = placings[0].value;
+                    for p in placings {
+                        println!(
+                            "{},{},{},{},{},{},{}",
+                            csv_field(bench),
+                            csv_field(&p.executor),
+                            p.rank,
+                            p.joint,
+                            p.value,
+                            p.ci.unwrap(),
+                            p.value / best
+                        );
+                    }
+                }
+                for p in &overall {
+                    println!(
+                        "(overall),{},{},{},,,{}",
+                        csv_field(&p.executor),
+                        p.rank,
+                        p.joint,
+                        p.value
+                    );
+                }
+            }
+            OutputFormat::Json => {
+                let mut rows = Vec::new();
+                for (bench, placings) in &ranked {
+                    let best = placings[0].value;
+                    for p in placings {
+                        rows.push(serde_json::json!({
+                            "benchmark": bench,
+                            "executor": p.executor,
+                            "rank": p.rank,
+                            "joint": p.joint,
+                            "mean": p.value,
+                            "ci": p.ci,
+                            "normalised": p.value / best,
+                        }));
+                    }
+                }
+                // The overall ranking has no benchmark, mean or confidence interval.
+                for p in &overall {
+                    rows.push(serde_json::json!({
+                        "benchmark": null,
+                        "executor": p.executor,
+                        "rank": p.rank,
+                        "joint": p.joint,
+                        "mean": null,
+                        "ci": null,
+                        "normalised": p.value,
+                    }));
+                }
+                println!("{}", serde_json::Value::Array(rows));
+            }
+        }
+    }
+}
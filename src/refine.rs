@@ -0,0 +1,249 @@
+//! Refining a datum's comparison with a baseline: running the benchmarks whose comparison is too
+//! close to call for more pexecs, and appending their samples, so that more data, rather than
+//! luck, decides which side of the significance boundary they fall on.
+
+use crate::{
+    App, BenchOptions, ConfidenceLevel, DEFAULT_MIN_SAMPLES, DatumRef, DiffOptions, ExtraToml,
+    ResultFile, SummaryStats, extend,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::process;
+
+/// How close to the significance boundary a comparison is borderline by default, as a
+/// percentage of the combined half-widths of the confidence intervals.
+pub(crate) const DEFAULT_MARGIN: f64 = 10.;
+
+/// A record of a datum made by refining an earlier datum's comparison with a baseline.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Refinement {
+    /// The ID of the datum refined.
+    pub(crate) source: usize,
+    /// The ID of the baseline it was compared with.
+    pub(crate) against: usize,
+    /// The number of pexecs run of each benchmark key refined, on top of those of `source`.
+    pub(crate) extra_pexecs: BTreeMap<String, usize>,
+}
+
+/// The datum that `haste bench` is refining, for [BenchOptions].
+pub(crate) struct Refining {
+    /// The ID of the datum refined.
+    pub(crate) source: usize,
+    /// The ID of the baseline it was compared with.
+    pub(crate) against: usize,
+    /// The in-process iterations the benchmarks of `source` were calibrated to, if any, which
+    /// the extra pexecs must be run with too.
+    pub(crate) inproc_iters: HashMap<String, usize>,
+}
+
+/// The benchmark keys, sorted, whose comparison of `base` with `new` is borderline: where making
+/// both confidence intervals `margin`% narrower or wider would change whether they overlap, and
+/// so whether they're significantly different. Benchmarks with fewer than `min_samples` samples
+/// on either side are never significant, so never borderline.
+pub(crate) fn borderline(
+    base: &HashMap<String, SummaryStats>,
+    new: &HashMap<String, SummaryStats>,
+    margin: f64,
+    min_samples: usize,
+) -> Vec<String> {
+    let mut keys = base
+        .iter()
+        .filter_map(|(k, s1)| {
+            let s2 = new.get(k)?;
+            let width = s1.ci + s2.ci;
+            let distance = (s1.mean - s2.mean).abs();
+            (s1.n >= min_samples
+                && s2.n >= min_samples
+                && width.is_finite()
+                && width > 0.
+                && (distance - width).abs() <= width * margin / 100.)
+                .then(|| k.clone())
+        })
+        .collect::<Vec<_>>();
+    keys.sort();
+    keys
+}
+
+/// How many more pexecs a benchmark with `n` samples needs for its confidence interval to
+/// narrow by `margin`%, if the interval narrows with the square root of the number of samples:
+/// at least one, and at most `max`.
+pub(crate) fn extra_pexecs(n: usize, margin: f64, max: usize) -> usize {
+    let narrowing = (1. - margin / 100.).max(0.01);
+    let needed = (n as f64 / (narrowing * narrowing)).ceil() as usize;
+    needed.saturating_sub(n).clamp(1, max.max(1))
+}
+
+/// Append the samples of `results`, measured by refining `base`, to those of `base`,
+/// returning the merged results, or why they can't be merged: that a benchmark was now run
+/// differently.
+pub(crate) fn append(mut base: ResultFile, results: ResultFile) -> Result<ResultFile, String> {
+    let mut keys = results.params.keys().collect::<Vec<_>>();
+    keys.sort();
+    for key in keys {
+        let Some(then) = base.params.get(key) else {
+            return Err(format!("{key} wasn't run in the datum refined"));
+        };
+        // The refining run has its own number of pexecs: the configured number stays that of
+        // the datum refined.
+        let mut now = results.params[key].clone();
+        now.proc_execs = then.proc_execs;
+        if now != *then {
+            let mismatches = ResultFile {
+                params: HashMap::from([(key.clone(), then.clone())]),
+                ..Default::default()
+            }
+            .param_mismatches(
+                &ResultFile {
+                    params: HashMap::from([(key.clone(), now)]),
+                    ..Default::default()
+                },
+                true,
+            );
+            return Err(format!(
+                "the config now runs benchmarks differently to the datum refined: {}",
+                mismatches.join(", ")
+            ));
+        }
+    }
+    for (k, samples) in results.data {
+        base.data.entry(k).or_default().extend(samples);
+    }
+    for (metric, data) in results.metrics {
+        let merged = base.metrics.entry(metric).or_default();
+        for (k, samples) in data {
+            merged.entry(k).or_default().extend(samples);
+        }
+    }
+    for (k, times) in results.timestamps {
+        base.timestamps.entry(k).or_default().extend(times);
+    }
+    base.failures.extend(results.failures);
+    Ok(base)
+}
+
+/// The metadata of the datum made by refining the datum whose metadata is `base`, where `extra`
+/// is that of the refining run, which measured `results`.
+pub(crate) fn merge_extra(
+    mut base: ExtraToml,
+    extra: ExtraToml,
+    results: &ResultFile,
+    refining: &Refining,
+) -> ExtraToml {
+    base.end_time = extra.end_time;
+    base.duration_secs = match (base.duration_secs, extra.duration_secs) {
+        (Some(a), Some(b)) => Some(a + b),
+        (a, b) => a.or(b),
+    };
+    for (k, n) in extra.warmup_pexecs {
+        *base.warmup_pexecs.entry(k).or_default() += n;
+    }
+    for (k, n) in extra.discarded_pexecs {
+        *base.discarded_pexecs.entry(k).or_default() += n;
+    }
+    base.system_readings.extend(extra.system_readings);
+    base.refinement = Some(Refinement {
+        source: refining.source,
+        against: refining.against,
+        extra_pexecs: results
+            .data
+            .iter()
+            .map(|(k, samples)| (k.clone(), samples.len()))
+            .collect(),
+    });
+    base
+}
+
+impl App {
+    /// Run the benchmarks whose comparison of baseline `against` with datum `id` is within
+    /// `margin`% of the significance boundary for more pexecs, up to `max_extra_pexecs`, and store
+    /// `id` with their samples appended as a new datum, returning its ID. If no comparison is
+    /// borderline, nothing is run.
+    pub(crate) fn refine(
+        &self,
+        id: usize,
+        against: usize,
+        max_extra_pexecs: usize,
+        margin: f64,
+        confidence: ConfidenceLevel,
+    ) -> Option<usize> {
+        if margin >= 100. {
+            eprintln!("error: the margin must be less than 100%, not {margin}%");
+            process::exit(1);
+        }
+        let base = self.load_summary(against, confidence);
+        let new = self.load_summary(id, confidence);
+        let keys = borderline(&base, &new, margin, DEFAULT_MIN_SAMPLES);
+        if keys.is_empty() {
+            println!(
+                "haste: no benchmark's comparison of Datum{against} with Datum{id} is within \
+                 {margin}% of significance"
+            );
+            return None;
+        }
+        let pexecs = keys
+            .iter()
+            .map(|k| extra_pexecs(new[k].n, margin, max_extra_pexecs))
+            .max()
+            .unwrap();
+        let mut config = self.load_config();
+        // The extra pexecs must be run with the datum's own in-process iterations.
+        config.calibrate = None;
+        let found = extend::restrict_keys(&mut config, |key, _| keys.iter().any(|k| k == key));
+        if found != keys {
+            let gone = keys
+                .iter()
+                .filter(|k| !found.contains(k))
+                .map(String::as_str)
+                .collect::<Vec<_>>();
+            eprintln!(
+                "error: no longer in the config, so can't be refined: {}",
+                gone.join(", ")
+            );
+            process::exit(1);
+        }
+        config.proc_execs = pexecs;
+        println!(
+            "haste: refining {} borderline benchmarks of Datum{id} with {pexecs} more pexecs \
+             each: {}",
+            keys.len(),
+            keys.join(", ")
+        );
+        let opts = BenchOptions {
+            // Rebuilding the executors could make the extra pexecs measure something different.
+            skip_build: true,
+            quiet: true,
+            refine: Some(Refining {
+                source: id,
+                against,
+                inproc_iters: self.load_extra(id).calibrated_iters,
+            }),
+            ..Default::default()
+        };
+        Some(self.bench(&mut config, &opts))
+    }
+
+    /// Refine the borderline comparisons of `against` with `id`, as [App::refine] does, and
+    /// compare the refined datum with `against`.
+    pub(crate) fn cmd_refine(
+        &self,
+        id: usize,
+        against: usize,
+        max_extra_pexecs: usize,
+        margin: f64,
+        confidence: ConfidenceLevel,
+    ) {
+        let Some(refined) = self.refine(id, against, max_extra_pexecs, margin, confidence) else {
+            return;
+        };
+        println!();
+        let diff_opts = DiffOptions {
+            confidence,
+            ..Default::default()
+        };
+        self.cmd_diff(
+            &DatumRef::local(against),
+            &DatumRef::local(refined),
+            &diff_opts,
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! Running benchmarks on another machine over `ssh`.
+//!
+//! The command for a pexec is wrapped in a small shell script which is run on the remote host. The
+//! script times the benchmark itself, so that connection latency isn't counted, and reports the
+//! duration (and a harness's output file) as trailer lines at the end of its stderr.
+
+use crate::config::Config;
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+/// The prefix of the trailer line giving the benchmark's duration in nanoseconds.
+const ELAPSED_NS: &str = "HASTE_REMOTE_NS=";
+/// The prefix of trailer lines containing the harness's output file.
+const HARNESS_OUT: &str = "HASTE_REMOTE_OUT:";
+
+/// How long to wait for a connection before giving up (in seconds).
+const CONNECT_TIMEOUT_SECS: u32 = 10;
+
+/// Make an `ssh` command that runs `script` on `host`.
+pub(crate) fn ssh_command(host: &str, script: &str) -> Command {
+    let mut cmd = Command::new("ssh");
+    // Never prompt for a password: there's no terminal to prompt on while benchmarking.
+    cmd.args(["-o", "BatchMode=yes"])
+        .arg("-o")
+        .arg(format!("ConnectTimeout={CONNECT_TIMEOUT_SECS}"))
+        .arg(host)
+        .arg(script);
+    cmd
+}
+
+/// Work out the hostname of the machine each executor runs on, returning a map from executor name
+/// to hostname.
+///
+/// This connects to each remote host, so that a bad host produces one clear error before any
+/// benchmarks run.
+pub(crate) fn executor_hosts(config: &Config) -> Result<HashMap<String, String>, String> {
+    let mut names = HashMap::new();
+    let mut exec_names = config.executors.keys().collect::<Vec<_>>();
+    exec_names.sort();
+    for exec_name in exec_names {
+        let name = match config.remote_for(exec_name) {
+            Some(host) => remote_hostname(host)?,
+            None => match local_hostname() {
+                Some(name) => name,
+                None => continue,
+            },
+        };
+        names.insert(exec_name.to_owned(), name);
+    }
+    Ok(names)
+}
+
+fn local_hostname() -> Option<String> {
+    let output = Command::new("uname").arg("-n").output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+fn remote_hostname(host: &str) -> Result<String, String> {
+    let output = ssh_command(host, "uname -n")
+        .output()
+        .map_err(|e| format!("failed to run ssh: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "can't connect to remote host {host}:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// Quote `s` so that the remote shell treats it as a single word.
+pub(crate) fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Make the script that runs `program` with `args` in `dir` with the extra environment `env`.
+///
+/// If `harness_out` is true, a temporary file is created on the remote host and passed as the
+/// first argument to `program` (as the local output file would be to a harness).
+pub(crate) fn script(
+    dir: &Path,
+    env: &[(&str, &str)],
+    program: &str,
+    args: &[String],
+    harness_out: bool,
+) -> String {
+    let mut cmdline = vec!["env".to_owned()];
+    for (k, v) in env {
+        cmdline.push(shell_quote(&format!("{k}={v}")));
+    }
+    cmdline.push(shell_quote(program));
+    if harness_out {
+        cmdline.push("\"$tmp\"".to_owned());
+    }
+    cmdline.extend(args.iter().map(|a| shell_quote(a)));
+    format!(
+        "cd {dir} || exit 1; tmp=$(mktemp) || exit 1; \
+         start=$(date +%s%N); {cmdline}; st=$?; end=$(date +%s%N); \
+         echo >&2; echo \"{ELAPSED_NS}$((end - start))\" >&2; \
+         sed 's/^/{HARNESS_OUT}/' \"$tmp\" >&2; rm -f \"$tmp\"; exit $st",
+        dir = shell_quote(dir.to_str().unwrap()),
+        cmdline = cmdline.join(" "),
+    )
+}
+
+/// What the remote wrapper script reported about a pexec.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Trailer {
+    /// The duration of the benchmark itself (in milliseconds).
+    pub(crate) elapsed_ms: f64,
+    /// The contents of the harness's output file.
+    pub(crate) harness_out: String,
+}
+
+/// Split the trailer written by [script] off the end of `stderr`, returning the benchmark's own
+/// stderr and the trailer.
+pub(crate) fn split_trailer(stderr: &str) -> Result<(&str, Trailer), String> {
+    let Some(pos) = stderr.rfind(&format!("\n{ELAPSED_NS}")) else {
+        return Err("remote output lacks a duration".to_owned());
+    };
+    let mut lines = stderr[pos + 1..].lines();
+    let ns = lines.next().unwrap().strip_prefix(ELAPSED_NS).unwrap();
+    let Ok(ns) = ns.parse::<u64>() else {
+        return Err(format!("failed to parse remote duration {ns}"));
+    };
+    let mut harness_out = String::new();
+    for line in lines {
+        if let Some(l) = line.strip_prefix(HARNESS_OUT) {
+            harness_out.push_str(l);
+            harness_out.push('\n');
+        }
+    }
+    // The script prints a newline before the trailer, which we found the trailer by.
+    Ok((
+        &stderr[..pos],
+        Trailer {
+            elapsed_ms: ns as f64 / 1_000_000.,
+            harness_out,
+        },
+    ))
+}
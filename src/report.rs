@@ -0,0 +1,273 @@
+//! A self-describing plain-text report comparing two datums, suitable for pasting into an email
+//! or an issue.
+
+use crate::output::OutputTarget;
+use crate::stats::geomean;
+use crate::{
+    App, ConfidenceLevel, DiffFormats, ExtraToml, NOISE_FLAG_FACTOR, ResultFile, diff_rows,
+    format_timestamp, noise_false_positives,
+};
+use comfy_table::{Cell, CellAlignment, ContentArrangement, Table};
+use rand::rngs::StdRng;
+
+/// The width that reports are wrapped to.
+pub(crate) const REPORT_WIDTH: usize = 100;
+/// The number of random splits used to look for noisy benchmarks.
+const NOISE_REPS: usize = 1000;
+
+/// Wrap `text` to `width` columns, indenting every line by `indent` spaces.
+///
+/// Words longer than the available width are put on a line of their own rather than broken.
+pub(crate) fn wrap(text: &str, width: usize, indent: usize) -> String {
+    let pad = " ".repeat(indent);
+    let mut out = String::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && indent + line.chars().count() + 1 + word.chars().count() > width {
+            out.push_str(&format!("{pad}{line}\n"));
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        out.push_str(&format!("{pad}{line}\n"));
+    }
+    out
+}
+
+/// Describe a datum's metadata, one `name: value` line each.
+fn metadata(id: usize, extra: &ExtraToml) -> String {
+    let mut fields = vec![
+        (
+            "comment",
+            extra.comment.clone().unwrap_or("(none)".to_owned()),
+        ),
+        ("started", format_timestamp(extra.start_time)),
+        ("finished", format_timestamp(extra.end_time)),
+        ("duration", extra.duration_str()),
+        (
+            "haste version",
+            extra.haste_version.clone().unwrap_or("unknown".to_owned()),
+        ),
+    ];
+    if !extra.args.is_empty() {
+        fields.push(("command line", extra.args.join(" ")));
+    }
+    let mut hosts = extra
+        .hosts
+        .iter()
+        .map(|(e, h)| format!("{e} on {h}"))
+        .collect::<Vec<_>>();
+    hosts.sort();
+    if !hosts.is_empty() {
+        fields.push(("hosts", hosts.join(", ")));
+    }
+    let mut builds = extra
+        .builds
+        .iter()
+        .map(|(e, b)| format!("{e} sha256 {}", &b.sha256[..b.sha256.len().min(12)]))
+        .collect::<Vec<_>>();
+    builds.sort();
+    if !builds.is_empty() {
+        fields.push(("builds", builds.join(", ")));
+    }
+    if extra.truncated {
+        let skipped = extra.skipped_pexecs.values().sum::<usize>();
+        fields.push((
+            "truncated",
+            format!("{skipped} pexecs skipped by --max-duration"),
+        ));
+    }
+
+    let mut out = format!("Datum{id}\n");
+    for (name, value) in fields {
+        out.push_str(&wrap(&format!("{name}: {value}"), REPORT_WIDTH, 2));
+    }
+    out
+}
+
+/// The benchmarks in `data` whose samples are noisy enough that the significance test would
+/// often flag a difference between two halves of them.
+fn noisy_benchmarks(
+    data: &ResultFile,
+    confidence: ConfidenceLevel,
+    rng: &mut StdRng,
+) -> Vec<String> {
+    let expected = 1. - confidence.as_percent() / 100.;
+    let mut keys = data.data.keys().collect::<Vec<_>>();
+    keys.sort();
+    keys.into_iter()
+        .filter(|k| {
+            noise_false_positives(&data.data[*k], confidence, NOISE_REPS, rng)
+                .is_some_and(|fps| fps as f64 / NOISE_REPS as f64 > NOISE_FLAG_FACTOR * expected)
+        })
+        .cloned()
+        .collect()
+}
+
+impl App {
+    /// Write a plain-text report comparing two datums to the terminal, and to `output`'s file.
+    pub(crate) fn cmd_report(
+        &self,
+        id1: usize,
+        id2: usize,
+        confidence: ConfidenceLevel,
+        min_samples: usize,
+        output: &OutputTarget,
+    ) {
+        let written = output.check().and_then(|()| {
+            let report = self.report(id1, id2, confidence, min_samples);
+            output.write(|out, _| out.write_all(report.as_bytes()))
+        });
+        if let Err(e) = written {
+            eprintln!("error: {e}");
+            std::process::exit(1);
+        }
+    }
+
+    /// Compose a report comparing the time metric of two datums.
+    ///
+    /// Unlike `diff`, problems such as differing parameters are reported as warnings rather than
+    /// errors, since a report is most useful when produced unattended.
+    pub(crate) fn report(
+        &self,
+        id1: usize,
+        id2: usize,
+        confidence: ConfidenceLevel,
+        min_samples: usize,
+    ) -> String {
+        let mut data1 = self.load_results(id1);
+        let mut data2 = self.load_results(id2);
+        let extra1 = self.load_extra(id1);
+        let extra2 = self.load_extra(id2);
+
+        let mut warnings = Vec::new();
+        let (lhs_only, rhs_only) = data1.retain_common(&mut data2);
+        if !lhs_only.is_empty() {
+            warnings.push(format!("only in Datum{id1}: {}", lhs_only.join(", ")));
+        }
+        if !rhs_only.is_empty() {
+            warnings.push(format!("only in Datum{id2}: {}", rhs_only.join(", ")));
+        }
+        for m in data1.param_mismatches(&data2, true) {
+            warnings.push(format!("collected with different parameters: {m}"));
+        }
+        if let Some(m) = extra1.version_mismatch(&extra2) {
+            warnings.push(format!("created by incompatible versions of haste: {m}"));
+        }
+        for m in extra1.host_mismatches(&extra2) {
+            warnings.push(format!("collected on different hosts: {m}"));
+        }
+        for (id, data) in [(id1, &data1), (id2, &data2)] {
+            let noisy = noisy_benchmarks(data, confidence, &mut self.rng.borrow_mut());
+            if !noisy.is_empty() {
+                warnings.push(format!(
+                    "noisy in Datum{id} (run `haste noise {id}` for details): {}",
+                    noisy.join(", ")
+                ));
+            }
+        }
+
+        let stats1 = data1.summarise(confidence);
+        let stats2 = data2.summarise(confidence);
+        let rows = diff_rows(&stats1, &stats2, min_samples, false);
+        let significant = rows.iter().filter(|r| r.significant).collect::<Vec<_>>();
+        let slower = significant.iter().filter(|r| r.is_regression()).count();
+        let ratios = rows
+            .iter()
+            .filter_map(|r| r.ratio)
+            .filter(|r| r.is_finite() && *r > 0.)
+            .collect::<Vec<_>>();
+
+        let title = format!("haste report: Datum{id1} vs. Datum{id2}");
+        let mut out = format!("{title}\n{}\n\n", "=".repeat(title.len()));
+        out.push_str(&metadata(id1, &extra1));
+        out.push('\n');
+        out.push_str(&metadata(id2, &extra2));
+        out.push('\n');
+
+        out.push_str("Summary\n");
+        out.push_str(&wrap(
+            &format!(
+                "Of {} benchmarks compared, {} differ significantly: {} faster and {slower} \
+                 slower in Datum{id2}.",
+                rows.len(),
+                significant.len(),
+                significant.len() - slower,
+            ),
+            REPORT_WIDTH,
+            2,
+        ));
+        if !ratios.is_empty() {
+            out.push_str(&wrap(
+                &format!(
+                    "The geometric mean of the ratios of Datum{id2}'s times to Datum{id1}'s is \
+                     {:.3}.",
+                    geomean(&ratios)
+                ),
+                REPORT_WIDTH,
+                2,
+            ));
+        }
+        out.push_str(&wrap(
+            &format!(
+                "Times are wallclock milliseconds, shown as the mean ± the half-width of its \
+                 {confidence}% confidence interval. A benchmark is \"indistinguishable\" when \
+                 the two confidence intervals overlap, or either datum has fewer than \
+                 {min_samples} samples of it: any difference is then too small to tell apart \
+                 from noise at this confidence level, not evidence that there is no difference. \
+                 Noisy benchmarks were looked for with random seed {} (use `--seed` to \
+                 reproduce this report).",
+                self.seed
+            ),
+            REPORT_WIDTH,
+            2,
+        ));
+        out.push('\n');
+
+        out.push_str("Significant differences\n");
+        if significant.is_empty() {
+            out.push_str("  (none)\n");
+        } else {
+            let fmts = DiffFormats::new(&rows, false, false);
+            let mut table = Table::new();
+            table
+                .load_preset(comfy_table::presets::NOTHING)
+                .force_no_tty()
+                .set_content_arrangement(ContentArrangement::Dynamic)
+                .set_width(REPORT_WIDTH as u16);
+            let right = |s: &str| Cell::new(s).set_alignment(CellAlignment::Right);
+            table.set_header(vec![
+                Cell::new("Benchmark"),
+                right("n"),
+                right(&format!("Datum{id1} (ms)")),
+                right(&format!("Datum{id2} (ms)")),
+                right("Delta"),
+                right("Ratio"),
+                Cell::new("Summary"),
+            ]);
+            for row in &significant {
+                let mut cells = vec![Cell::new(row.key), right(&row.samples())];
+                cells.extend(row.columns(&fmts).iter().map(|c| right(c)));
+                cells.push(Cell::new(row.summary()));
+                table.add_row(cells);
+            }
+            out.push_str(&format!("{table}\n"));
+        }
+        out.push('\n');
+
+        out.push_str("Warnings\n");
+        if warnings.is_empty() {
+            out.push_str("  (none)\n");
+        }
+        for w in &warnings {
+            // Hang continuation lines under the text, not the bullet.
+            let wrapped = wrap(w, REPORT_WIDTH, 4);
+            out.push_str(&format!("  - {}", &wrapped[4..]));
+        }
+        out
+    }
+}
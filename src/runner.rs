@@ -1,71 +1,1070 @@
 use crate::BenchKey;
-use crate::{ResultFile, config::*};
+use crate::environment::{self, PexecReadings, Reading};
+use crate::events::{Event, EventStream, PlannedJob};
+use crate::{
+    BuildRecord, Failure, ITERS_METRIC, KeyParams, ResultFile, config::*, log::RunLog, nice,
+    remote, suspend, units,
+};
+use clap::ValueEnum;
+use rand::{SeedableRng, rngs::StdRng, seq::SliceRandom};
+use sha2::{Digest, Sha256};
+use std::cell::Cell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::fs::{self, OpenOptions};
 use std::hint::black_box;
-use std::io::{self, Write};
+use std::io::{self, Read, Write};
+use std::os::unix::process::CommandExt;
 use std::path::Path;
-use std::process::{self, Command, Stdio};
-use std::time::Duration;
+use std::process::{self, Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use terminal_size::terminal_size;
 
 const PEXEC_WALLCLOCK_MS: &str = "PEXEC_WALLCLOCK_MS";
+/// The start of the line of stdout in which a harness left to choose its own number of in-process
+/// iterations reports how many it ran.
+const ITERS_USED: &str = "@@HASTE_ITERS_USED:";
+/// What a harness is asked to run instead of a number of in-process iterations when it's left to
+/// choose them, with `inproc_iters = 0`.
+pub(crate) const AUTO_ITERS: &str = "auto";
+/// The events that `perf stat` is asked to count when perf collection is enabled.
+pub(crate) const PERF_EVENTS: [&str; 3] = ["instructions", "cycles", "task-clock"];
 
-fn total_pexecs(config: &Config) -> usize {
-    let mut total_pexecs = 0;
-    for suite in &config.suites {
-        total_pexecs += suite.1.benchmarks.len();
+/// How many lines of a failing benchmark's stderr are kept with `--keep-going`.
+const STDERR_EXCERPT_LINES: usize = 20;
+
+/// Noted with failures whose output was too long to keep in full.
+const TRUNCATED_NOTE: &str = "note: the benchmark's output was truncated to max_output_bytes";
+
+/// How much of a pexec's output is read at a time.
+const READ_CHUNK: usize = 64 * 1024;
+
+/// How often a pexec with a timeout checks whether the user has pressed Ctrl-C.
+const INTERRUPT_POLL: Duration = Duration::from_millis(100);
+
+/// The file which the Linux kernel drops its caches when written to.
+const DROP_CACHES: &str = "/proc/sys/vm/drop_caches";
+
+/// Check that caches can be dropped for suites with `cache_policy = "cold"`.
+pub(crate) fn check_drop_caches() -> Result<(), String> {
+    OpenOptions::new()
+        .write(true)
+        .open(DROP_CACHES)
+        .map(|_| ())
+        .map_err(|e| {
+            format!(
+                "cache_policy = \"cold\" needs to write to {DROP_CACHES} ({e}): run haste as \
+                 root, or use cache_policy = \"warm\" instead"
+            )
+        })
+}
+
+/// Write dirty pages to disk and drop the page cache, dentries and inodes.
+fn drop_caches() {
+    Command::new("sync").status().ok();
+    if let Err(e) = fs::write(DROP_CACHES, "3\n") {
+        eprintln!("error: failed to drop caches: {e}");
+        show_cursor();
+        process::exit(1);
+    }
+}
+
+/// Check that `perf stat` can count the events we need.
+///
+/// This is done once before any benchmarks run, so that a lack of perf support produces a single
+/// clear error rather than a failure for every pexec.
+pub(crate) fn check_perf() -> Result<(), String> {
+    if !cfg!(target_os = "linux") {
+        return Err("perf counters are only supported on Linux".to_owned());
+    }
+    let output = Command::new("perf")
+        .args(["stat", "-x,", "-e", &PERF_EVENTS.join(","), "--", "true"])
+        .output()
+        .map_err(|e| format!("failed to run perf: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "perf stat failed (check perf_event_paranoid?):\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    parse_perf_output(&String::from_utf8_lossy(&output.stderr)).map(|_| ())
+}
+
+/// Parse the CSV output of `perf stat -x,` into a map from event name to counter value.
+pub(crate) fn parse_perf_output(s: &str) -> Result<HashMap<String, f64>, String> {
+    let mut counters = HashMap::new();
+    for line in s.lines() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // Fields are: value, unit, event, ...
+        let fields = line.split(',').collect::<Vec<_>>();
+        if fields.len() < 3 {
+            continue;
+        }
+        // Events may have modifiers attached, e.g. `instructions:u`.
+        let event = fields[2].split(':').next().unwrap();
+        if !PERF_EVENTS.contains(&event) {
+            continue;
+        }
+        let Ok(v) = fields[0].parse::<f64>() else {
+            return Err(format!("perf could not count {event}: {}", fields[0]));
+        };
+        counters.insert(event.to_owned(), v);
+    }
+    for event in PERF_EVENTS {
+        if !counters.contains_key(event) {
+            return Err(format!("perf output lacks a count for {event}"));
+        }
+    }
+    Ok(counters)
+}
+
+/// The size of a run, for showing its progress.
+struct Totals {
+    pexecs: usize,
+    /// The total work of the pexecs, where each pexec counts as its executor's `iters_scale`, as
+    /// that's roughly how its duration compares with other executors' pexecs.
+    work: f64,
+}
+
+impl Totals {
+    /// The size of a run of `proc_execs` pexecs of each of `jobs`, after their warmup pexecs.
+    fn new<'a>(
+        config: &Config,
+        jobs: impl Iterator<Item = &'a Job<'a>>,
+        proc_execs: usize,
+    ) -> Self {
+        jobs.fold(
+            Totals {
+                pexecs: 0,
+                work: 0.,
+            },
+            |t, j| {
+                let pexecs = proc_execs + j.warmup_pexecs;
+                Totals {
+                    pexecs: t.pexecs + pexecs,
+                    work: t.work + pexecs as f64 * job_work(config, j),
+                }
+            },
+        )
     }
-    total_pexecs *= config.executors.len();
-    total_pexecs *= config.proc_execs;
-    total_pexecs
 }
 
-fn get_progress_percentage(config: &Config, completed_pexecs: usize) -> f64 {
+/// The work of a pexec of `job`, measured as for [Totals::work].
+fn job_work(config: &Config, job: &Job) -> f64 {
+    config.executors[&job.key.executor].iters_scale
+}
+
+/// The work of a pexec of the benchmark key `key`, measured as for [Totals::work].
+fn key_work(config: &Config, key: &str) -> f64 {
+    crate::split_key(key)
+        .and_then(|(_, e)| config.executors.get(e))
+        .map_or(1., |e| e.iters_scale)
+}
+
+/// The work of the pexecs in `results` (including failed ones), measured as for [Totals::work].
+fn completed_work(config: &Config, results: &ResultFile) -> f64 {
+    let ok = results
+        .data
+        .iter()
+        .map(|(k, s)| s.len() as f64 * key_work(config, k));
+    let failed = results
+        .failures
+        .iter()
+        .map(|(k, f)| f.count as f64 * key_work(config, k));
+    ok.chain(failed).sum()
+}
+
+fn get_progress_percentage(totals: &Totals, completed_pexecs: usize) -> f64 {
     let completed_pexecs = f64::from(u32::try_from(completed_pexecs).unwrap());
-    let total_pexecs = f64::from(u32::try_from(total_pexecs(config)).unwrap());
+    let total_pexecs = f64::from(u32::try_from(totals.pexecs).unwrap());
     completed_pexecs / total_pexecs * 100.
 }
 
-fn get_eta(config: &Config, results: &ResultFile, completed_pexecs: usize) -> String {
-    if completed_pexecs == 0 {
+fn get_eta(
+    config: &Config,
+    results: &ResultFile,
+    state: &RunState,
+    totals: &Totals,
+    completed_pexecs: usize,
+) -> String {
+    let measured = completed_work(config, results);
+    if completed_pexecs == 0 || measured == 0. {
         return "...".to_owned();
     }
-    let msecs = (results.data.values().flatten().sum::<f64>()
-        / f64::from(u32::try_from(completed_pexecs).unwrap()))
-        * f64::from(u32::try_from(total_pexecs(config) - completed_pexecs).unwrap());
-    let dur = Duration::from_millis(msecs as u64);
-    let secs = dur.as_secs();
-    if secs >= 24 * 60 * 60 {
-        let days = secs / 86400;
-        let hours = (secs % 86400) / 3600;
-        format!("{}d:{}h", days, hours)
-    } else if secs < 60 * 60 {
-        let minutes = (secs % 3600) / 60;
-        let seconds = secs % 60;
-        format!("{}m:{:02}s", minutes, seconds)
-    } else {
-        let hours = secs / 3600;
-        let minutes = (secs % 3600) / 60;
-        format!("{}H:{:02}m", hours, minutes)
+    // Warmup pexecs aren't timed, so are assumed to take as long as measured ones.
+    let warmed = state
+        .warmups
+        .iter()
+        .map(|(k, n)| *n as f64 * key_work(config, k))
+        .sum::<f64>();
+    let remaining = (totals.work - measured - warmed).max(0.);
+    let msecs = results.data.values().flatten().sum::<f64>() / measured * remaining;
+    units::format_duration(Duration::from_millis(msecs as u64))
+}
+
+/// The outcome of running benchmarks.
+pub(crate) struct RunOutcome {
+    pub(crate) results: ResultFile,
+    /// The number of pexecs not run because the time budget ran out, per benchmark key.
+    ///
+    /// Keys for which every pexec ran are omitted.
+    pub(crate) skipped_pexecs: HashMap<String, usize>,
+    /// The number of pexecs not run because their suite's `max_suite_secs` ran out, per benchmark
+    /// key. These keys aren't in `skipped_pexecs`.
+    pub(crate) over_budget: HashMap<String, usize>,
+    /// When interleaving, the benchmark key of each pexec in the order they ran.
+    pub(crate) order: Vec<String>,
+    /// When interleaving, the round that each sample of each benchmark key was measured in.
+    pub(crate) pairs: HashMap<String, Vec<usize>>,
+    /// The mean harness overhead (in milliseconds) that applies to each benchmark key, for
+    /// suites with a `calibration_benchmark`.
+    pub(crate) overheads: HashMap<String, f64>,
+    /// With `sample_system`, the CPU's frequency and temperature around each pexec, in the order
+    /// they ran.
+    pub(crate) system_readings: Vec<PexecReadings>,
+    /// The number of warmup pexecs run of each benchmark key that had any.
+    pub(crate) warmup_pexecs: HashMap<String, usize>,
+    /// The number of measured pexecs of each benchmark key that had any discarded and rerun
+    /// because their times couldn't be trusted.
+    pub(crate) discarded_pexecs: HashMap<String, usize>,
+}
+
+/// Build each executor that has a `build` table, streaming the build output to the terminal.
+///
+/// Returns a record of each build, or an error describing the first build that failed.
+pub(crate) fn build_executors(config: &Config) -> Result<HashMap<String, BuildRecord>, String> {
+    let mut records = HashMap::new();
+    let mut names = config.executors.keys().collect::<Vec<_>>();
+    names.sort();
+    for name in names {
+        let executor = &config.executors[name];
+        let Some(build) = &executor.build else {
+            continue;
+        };
+        println!(">>> haste: building {name}: {}", build.command);
+        let start = Instant::now();
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(&build.command)
+            .current_dir(&build.dir)
+            .status()
+            .map_err(|e| format!("failed to run build command for {name}: {e}"))?;
+        if !status.success() {
+            return Err(format!("build command for {name} failed ({status})"));
+        }
+        let duration_secs = start.elapsed().as_secs_f64();
+        between_pexecs("hashing a build artifact");
+        let bin = std::fs::read(&executor.path).map_err(|e| {
+            format!(
+                "failed to read build artifact {} for {name}: {e}",
+                executor.path.display()
+            )
+        })?;
+        let sha256 = Sha256::digest(&bin)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>();
+        records.insert(
+            name.to_owned(),
+            BuildRecord {
+                command: build.command.clone(),
+                duration_secs,
+                sha256,
+            },
+        );
+    }
+    Ok(records)
+}
+
+/// A benchmark to be run with a particular executor.
+struct Job<'a> {
+    key: BenchKey,
+    executor: &'a Path,
+    /// The host to run on, if the executor is remote.
+    remote: Option<&'a str>,
+    suite_name: &'a str,
+    suite: &'a Suite,
+    bench_name: &'a str,
+    bench: &'a Benchmark,
+    /// The number of in-process iterations to ask the harness for.
+    inproc_iters: usize,
+    /// The number of unrecorded pexecs to run before the first measured one.
+    warmup_pexecs: usize,
+}
+
+impl Job<'_> {
+    fn planned(&self, config: &Config) -> PlannedJob {
+        PlannedJob {
+            key: self.key.to_string(),
+            suite: self.suite_name.to_owned(),
+            inproc_iters: self.inproc_iters,
+            pexecs: config.proc_execs,
+            warmup_pexecs: self.warmup_pexecs,
+            expected_ms: config.expected_ms_for(self.suite, self.bench),
+        }
+    }
+}
+
+/// Make the list of jobs for the configuration, sorted by suite, benchmark and executor, except
+/// that jobs come after those of the benchmarks they must run `after`.
+///
+/// `inproc_iters` may override the configured number of in-process iterations for specific
+/// benchmark keys.
+fn plan_jobs<'a>(config: &'a Config, inproc_iters: &HashMap<String, usize>) -> Vec<Job<'a>> {
+    let mut jobs = Vec::new();
+    for (executor_name, executor) in &config.executors {
+        for (suite_name, suite) in &config.suites {
+            for (bench_name, bench) in &suite.benchmarks {
+                if !bench.runs_on(executor_name) {
+                    continue;
+                }
+                let key = BenchKey {
+                    benchmark: bench_name.into(),
+                    executor: executor_name.into(),
+                    extra_args: bench.extra_args.clone(),
+                };
+                let inproc_iters = inproc_iters
+                    .get(&key.to_string())
+                    .copied()
+                    .unwrap_or_else(|| config.inproc_iters_for(executor_name));
+                jobs.push(Job {
+                    key,
+                    executor: &executor.path,
+                    remote: config.remote_for(executor_name),
+                    suite_name,
+                    suite,
+                    bench_name,
+                    bench,
+                    inproc_iters,
+                    warmup_pexecs: config.warmup_pexecs_for(suite, bench, executor_name),
+                });
+            }
+        }
+    }
+    jobs.sort_by(|a, b| {
+        (a.suite_name, a.bench_name, &a.key.executor).cmp(&(
+            b.suite_name,
+            b.bench_name,
+            &b.key.executor,
+        ))
+    });
+    order_dependencies(jobs)
+}
+
+/// The indices of the jobs in `jobs` that `jobs[i]` must run after, for each `i`.
+fn dependencies(jobs: &[Job]) -> Vec<Vec<usize>> {
+    jobs.iter()
+        .enumerate()
+        .map(|(i, job)| {
+            (0..jobs.len())
+                .filter(|&d| d != i && job.bench.after.iter().any(|a| a == jobs[d].bench_name))
+                .collect()
+        })
+        .collect()
+}
+
+/// Reorder `jobs` so that each comes after the jobs it must run after, otherwise keeping their
+/// order. Each time, the first job whose dependencies have all been placed is placed next.
+///
+/// Cycles are rejected when the config is validated, but if there were one, the jobs in it would
+/// be left at the end in their original order.
+fn order_dependencies(jobs: Vec<Job>) -> Vec<Job> {
+    let deps = dependencies(&jobs);
+    let mut placed = vec![false; jobs.len()];
+    let mut order = Vec::with_capacity(jobs.len());
+    while let Some(i) = (0..jobs.len()).find(|&i| !placed[i] && deps[i].iter().all(|&d| placed[d]))
+    {
+        placed[i] = true;
+        order.push(i);
+    }
+    order.extend((0..jobs.len()).filter(|&i| !placed[i]));
+    let mut jobs = jobs.into_iter().map(Some).collect::<Vec<_>>();
+    order.into_iter().map(|i| jobs[i].take().unwrap()).collect()
+}
+
+/// Delay the pexecs in `pexecs` which would start before every pexec of the jobs their job must
+/// run after, otherwise keeping their order. A delayed pexec runs as soon as it can.
+fn respect_dependencies(jobs: &[Job], pexecs: Vec<Pexec>) -> Vec<Pexec> {
+    let deps = dependencies(jobs);
+    let mut left = vec![0; jobs.len()];
+    for p in &pexecs {
+        left[p.job] += 1;
+    }
+    let ready = |left: &[usize], p: &Pexec| deps[p.job].iter().all(|&d| left[d] == 0);
+    let mut ordered = Vec::with_capacity(pexecs.len());
+    let mut delayed = Vec::new();
+    for p in pexecs {
+        if !ready(&left, &p) {
+            delayed.push(p);
+            continue;
+        }
+        left[p.job] -= 1;
+        ordered.push(p);
+        // Finishing a job may let delayed pexecs run, which may finish further jobs.
+        while let Some(i) = delayed.iter().position(|d| ready(&left, d)) {
+            let d = delayed.remove(i);
+            left[d.job] -= 1;
+            ordered.push(d);
+        }
+    }
+    ordered.extend(delayed);
+    ordered
+}
+
+/// One pexec in a run's schedule.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Pexec {
+    /// The index of the pexec's job.
+    job: usize,
+    /// Which of its job's pexecs this is, from 0.
+    n: usize,
+}
+
+/// The order to run `proc_execs` pexecs of each of `jobs` (as made by [plan_jobs]) in.
+///
+/// However `order` mixes the pexecs up, none starts before all those of the jobs it must run
+/// after.
+fn schedule(jobs: &[Job], order: Order, proc_execs: usize, seed: u64) -> Vec<Pexec> {
+    let grouped = (0..jobs.len()).flat_map(|job| (0..proc_execs).map(move |n| Pexec { job, n }));
+    let pexecs = match order {
+        Order::Grouped => grouped.collect(),
+        Order::RoundRobin => {
+            // Within a round, take each suite's next job in turn.
+            let mut within_suite = HashMap::<&str, usize>::new();
+            let mut round = (0..jobs.len())
+                .map(|i| {
+                    let n = within_suite.entry(jobs[i].suite_name).or_default();
+                    *n += 1;
+                    (*n, jobs[i].suite_name, i)
+                })
+                .collect::<Vec<_>>();
+            round.sort();
+            (0..proc_execs)
+                .flat_map(|n| round.iter().map(move |&(_, _, job)| Pexec { job, n }))
+                .collect()
+        }
+        Order::Shuffle => {
+            let mut pexecs = grouped.collect::<Vec<_>>();
+            pexecs.shuffle(&mut StdRng::seed_from_u64(seed));
+            // Number each job's pexecs in the order they'll run.
+            let mut done = vec![0; jobs.len()];
+            for p in &mut pexecs {
+                p.n = done[p.job];
+                done[p.job] += 1;
+            }
+            pexecs
+        }
+    };
+    respect_dependencies(jobs, pexecs)
+}
+
+/// The benchmark key of each pexec that a run of `config` with `opts` would make, and which of
+/// the key's pexecs it is (from 0), in the order they'd run.
+pub(crate) fn planned_pexecs(config: &Config, opts: &RunOptions) -> Vec<(String, usize)> {
+    let jobs = plan_jobs(config, &opts.inproc_iters);
+    schedule(&jobs, opts.order(), config.proc_execs, opts.seed)
+        .into_iter()
+        .map(|p| (jobs[p.job].key.to_string(), p.n))
+        .collect()
+}
+
+/// The benchmarks that a run of `config` with `opts` would run, in the order [plan_jobs] puts
+/// them in.
+pub(crate) fn planned_jobs(config: &Config, opts: &RunOptions) -> Vec<PlannedJob> {
+    plan_jobs(config, &opts.inproc_iters)
+        .iter()
+        .map(|j| j.planned(config))
+        .collect()
+}
+
+/// The keys of the benchmarks which the configuration says not to run with some executors, sorted.
+pub(crate) fn restricted_keys(config: &Config) -> Vec<String> {
+    let mut keys = Vec::new();
+    for executor_name in config.executors.keys() {
+        for suite in config.suites.values() {
+            for (bench_name, bench) in &suite.benchmarks {
+                if !bench.runs_on(executor_name) {
+                    let key = BenchKey {
+                        benchmark: bench_name.into(),
+                        executor: executor_name.into(),
+                        extra_args: bench.extra_args.clone(),
+                    };
+                    keys.push(key.to_string());
+                }
+            }
+        }
+    }
+    keys.sort();
+    keys
+}
+
+/// The value of `field` for each benchmark key in the configuration whose benchmark has one.
+fn key_fields(
+    config: &Config,
+    field: fn(&Benchmark) -> &Option<String>,
+) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+    for executor_name in config.executors.keys() {
+        for suite in config.suites.values() {
+            for (bench_name, bench) in &suite.benchmarks {
+                if let Some(v) = field(bench) {
+                    let key = BenchKey {
+                        benchmark: bench_name.into(),
+                        executor: executor_name.into(),
+                        extra_args: bench.extra_args.clone(),
+                    };
+                    values.insert(key.to_string(), v.clone());
+                }
+            }
+        }
+    }
+    values
+}
+
+/// The description of each benchmark key in the configuration which has one.
+pub(crate) fn key_descriptions(config: &Config) -> HashMap<String, String> {
+    key_fields(config, |b| &b.description)
+}
+
+/// The URL of each benchmark key in the configuration which has one.
+pub(crate) fn key_urls(config: &Config) -> HashMap<String, String> {
+    key_fields(config, |b| &b.url)
+}
+
+/// Check that no two benchmark definitions have the same key.
+///
+/// The string form of a [BenchKey] joins the extra arguments with `-`, so, for example,
+/// `extra_args = ["a-b"]` and `["a", "b"]`, or `[]` and `[""]`, would otherwise silently merge
+/// their samples.
+pub(crate) fn check_keys(config: &Config) -> Result<(), String> {
+    let mut defs = BTreeMap::<String, Vec<String>>::new();
+    let mut suite_names = config.suites.keys().collect::<Vec<_>>();
+    suite_names.sort();
+    let mut exec_names = config.executors.keys().collect::<Vec<_>>();
+    exec_names.sort();
+    for executor_name in exec_names {
+        for suite_name in &suite_names {
+            let suite = &config.suites[*suite_name];
+            let mut bench_names = suite.benchmarks.keys().collect::<Vec<_>>();
+            bench_names.sort();
+            for bench_name in bench_names {
+                let bench = &suite.benchmarks[bench_name];
+                if !bench.runs_on(executor_name) {
+                    continue;
+                }
+                let key = BenchKey {
+                    benchmark: bench_name.into(),
+                    executor: executor_name.into(),
+                    extra_args: bench.extra_args.clone(),
+                };
+                defs.entry(key.to_string()).or_default().push(format!(
+                    "suite {suite_name}: benchmark {bench_name} with executor {executor_name} \
+                     and extra_args {:?}",
+                    bench.extra_args
+                ));
+            }
+        }
+    }
+    let collisions = defs.iter().filter(|(_, d)| d.len() > 1).collect::<Vec<_>>();
+    if collisions.is_empty() {
+        return Ok(());
+    }
+    let mut msg = "benchmarks have the same key, so their results would be mixed up:".to_owned();
+    for (key, defs) in collisions {
+        msg.push_str(&format!("\n  {key}:"));
+        for d in defs {
+            msg.push_str(&format!("\n    {d}"));
+        }
+    }
+    msg.push_str("\n(keys join extra_args with '-')");
+    Err(msg)
+}
+
+/// Options controlling how benchmarks are run.
+#[derive(Default)]
+pub(crate) struct RunOptions {
+    /// If specified, benchmarks are run round-robin and no new pexecs are started once this has
+    /// elapsed.
+    pub(crate) max_duration: Option<Duration>,
+    /// Overrides the configured number of in-process iterations for specific benchmark keys.
+    pub(crate) inproc_iters: HashMap<String, usize>,
+    /// Print the command used for each pexec.
+    pub(crate) verbose: bool,
+    /// If non-empty, the pexecs of each benchmark alternate between these executors, in this
+    /// order.
+    pub(crate) interleave: Vec<String>,
+    /// Record failing pexecs in the results and carry on, rather than exiting.
+    pub(crate) keep_going: bool,
+    /// If present, where to log each pexec.
+    pub(crate) log: Option<RunLog>,
+    /// If present, where to stream the run's events.
+    pub(crate) events: Option<EventStream>,
+    /// The order to run pexecs in. Defaults to [Order::RoundRobin] with a `max_duration`, and
+    /// [Order::Grouped] otherwise. Ignored when interleaving.
+    pub(crate) order: Option<Order>,
+    /// The seed for [Order::Shuffle].
+    pub(crate) seed: u64,
+}
+
+/// The order in which the pexecs of every benchmark, with every executor, in every suite are run.
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub(crate) enum Order {
+    /// All the pexecs of each benchmark and executor in turn, suite by suite.
+    Grouped,
+    /// One pexec of each benchmark and executor per round, alternating between suites within
+    /// each round, so that drift affects every suite similarly.
+    RoundRobin,
+    /// Every pexec in a random order, seeded by `--seed`.
+    Shuffle,
+}
+
+impl RunOptions {
+    fn order(&self) -> Order {
+        self.order.unwrap_or(if self.max_duration.is_some() {
+            Order::RoundRobin
+        } else {
+            Order::Grouped
+        })
+    }
+}
+
+impl RunOptions {
+    fn log(&self, event: &str, fields: &[(&str, String)]) {
+        if let Some(log) = &self.log {
+            log.event(event, fields);
+        }
+    }
+
+    fn event(&self, event: &Event) {
+        if let Some(events) = &self.events {
+            events.emit(event);
+        }
+    }
+}
+
+/// The time spent so far running the pexecs of each suite with each executor, for enforcing
+/// `max_suite_secs`.
+#[derive(Default)]
+struct SuiteTimes(HashMap<(String, String), Duration>);
+
+impl SuiteTimes {
+    fn spent(&self, job: &Job) -> Duration {
+        let k = (job.suite_name.to_owned(), job.key.executor.clone());
+        self.0.get(&k).copied().unwrap_or_default()
+    }
+
+    /// Has the suite of `job` used up its `max_suite_secs` with the executor of `job`?
+    fn exhausted(&self, job: &Job) -> bool {
+        job.suite
+            .max_suite_secs
+            .is_some_and(|secs| self.spent(job).as_secs_f64() >= secs)
+    }
+
+    fn add(&mut self, job: &Job, t: Duration) {
+        let k = (job.suite_name.to_owned(), job.key.executor.clone());
+        *self.0.entry(k).or_default() += t;
+    }
+}
+
+/// What's recorded about the pexecs of a run as they go, besides their results.
+#[derive(Default)]
+struct RunState {
+    times: SuiteTimes,
+    system_readings: Vec<PexecReadings>,
+    /// The number of warmup pexecs run of each benchmark key.
+    warmups: HashMap<String, usize>,
+    /// The number of measured pexecs started of each benchmark key.
+    started: HashMap<String, usize>,
+    /// The number of measured pexecs of each benchmark key discarded and rerun.
+    discarded: HashMap<String, usize>,
+}
+
+impl RunState {
+    /// The number of warmup pexecs run so far.
+    fn warmups_run(&self) -> usize {
+        self.warmups.values().sum()
     }
 }
 
 /// Run all benchmarks from the configuration.
-pub(crate) fn run(config: &Config) -> ResultFile {
+pub(crate) fn run(config: &Config, opts: &RunOptions) -> RunOutcome {
+    let jobs = plan_jobs(config, &opts.inproc_iters);
     let mut results = ResultFile::default();
+    let mut skipped_pexecs = HashMap::new();
+    let mut order = Vec::new();
+    let mut pairs = HashMap::new();
+    let mut state = RunState::default();
+    if config.sample_system
+        && let Some(w) = environment::unreadable(Path::new(environment::SYSFS))
+    {
+        eprintln!("warning: {w}");
+        opts.log("warning", &[("message", w)]);
+    }
+    opts.event(&Event::RunStarted {
+        jobs: jobs.iter().map(|j| j.planned(config)).collect(),
+    });
+    hide_cursor();
+    install_interrupt_handler();
+    let overheads = measure_overheads(config, opts, &jobs);
+    if !opts.interleave.is_empty() {
+        run_interleaved(
+            &mut results,
+            &mut order,
+            &mut pairs,
+            &mut state,
+            config,
+            opts,
+            &jobs,
+        );
+    } else {
+        let schedule = schedule(&jobs, opts.order(), config.proc_execs, opts.seed);
+        run_scheduled(&mut results, &mut state, config, opts, &jobs, &schedule);
+        if opts.max_duration.is_some() {
+            for job in &jobs {
+                let key = job.key.to_string();
+                let done = pexecs_done(&results, &key);
+                if done < config.proc_execs {
+                    skipped_pexecs.insert(key, config.proc_execs - done);
+                }
+            }
+        }
+    }
+    show_cursor();
+    let over_budget = jobs
+        .iter()
+        .filter(|j| state.times.exhausted(j))
+        .filter_map(|j| {
+            let key = j.key.to_string();
+            let done = pexecs_done(&results, &key);
+            (done < config.proc_execs).then(|| (key, config.proc_execs - done))
+        })
+        .collect::<HashMap<_, _>>();
+    skipped_pexecs.retain(|k, _| !over_budget.contains_key(k));
+    RunOutcome {
+        results,
+        skipped_pexecs,
+        over_budget,
+        order,
+        pairs,
+        overheads,
+        system_readings: state.system_readings,
+        warmup_pexecs: state.warmups,
+        discarded_pexecs: state.discarded,
+    }
+}
+
+/// Run the `calibration_benchmark` of each suite that has one `proc_execs` times with each
+/// executor, returning the mean time of each for every benchmark key it applies to.
+fn measure_overheads(config: &Config, opts: &RunOptions, jobs: &[Job]) -> HashMap<String, f64> {
+    // The calibration benchmark exists only for the harness, so has no settings of its own.
+    let nop = Benchmark::default();
+    let mut measured: Vec<(&Suite, &str, f64)> = Vec::new();
+    let mut overheads = HashMap::new();
+    for job in jobs {
+        let Some(calibration) = &job.suite.calibration_benchmark else {
+            continue;
+        };
+        let executor = job.key.executor.as_str();
+        let overhead = match measured
+            .iter()
+            .find(|(s, e, _)| std::ptr::eq(*s, job.suite) && *e == executor)
+        {
+            Some((_, _, overhead)) => *overhead,
+            None => {
+                let cal_job = Job {
+                    key: BenchKey {
+                        benchmark: calibration.clone(),
+                        executor: executor.to_owned(),
+                        extra_args: Vec::new(),
+                    },
+                    bench_name: calibration,
+                    bench: &nop,
+                    inproc_iters: config.inproc_iters,
+                    ..*job
+                };
+                let lhs = format!(">>> haste: {} (harness overhead)", cal_job.key);
+                update_term_line(&format!("{lhs} ..."), "");
+                io::stdout().flush().ok();
+                let mut scratch = ResultFile::default();
+                for pexec in 0..config.proc_execs {
+                    run_benchmark(&mut scratch, config, opts, &cal_job, Some(pexec));
+                }
+                if let Some(f) = scratch.failures.get(&cal_job.key.to_string()) {
+                    println!();
+                    eprintln!(
+                        "error: calibration benchmark {} failed (exit status {}):\n{}",
+                        cal_job.key, f.status, f.stderr
+                    );
+                    show_cursor();
+                    process::exit(1);
+                }
+                let samples = &scratch.data[&cal_job.key.to_string()];
+                let overhead = samples.iter().sum::<f64>() / samples.len() as f64;
+                update_term_line(&format!("{lhs} {overhead:.0}ms"), "");
+                println!();
+                opts.log(
+                    "overhead",
+                    &[
+                        ("key", cal_job.key.to_string()),
+                        ("mean_ms", format!("{overhead:.3}")),
+                    ],
+                );
+                measured.push((job.suite, executor, overhead));
+                overhead
+            }
+        };
+        overheads.insert(job.key.to_string(), overhead);
+    }
+    overheads
+}
+
+/// Run each benchmark once with a small iteration count to work out how many in-process
+/// iterations are needed for it to take roughly `target_ms`.
+///
+/// Returns a map from benchmark key to iteration count.
+pub(crate) fn calibrate(
+    config: &Config,
+    target_ms: f64,
+    initial_iters: usize,
+) -> HashMap<String, usize> {
+    let mut jobs = plan_jobs(config, &HashMap::new());
+    let mut calibrated = HashMap::new();
+    hide_cursor();
+    install_interrupt_handler();
+    for job in &mut jobs {
+        job.inproc_iters = initial_iters;
+        update_term_line(&format!(">>> haste: calibrating {} ...", job.key), "");
+        io::stdout().flush().ok();
+        let mut scratch = ResultFile::default();
+        run_benchmark(&mut scratch, config, &RunOptions::default(), job, Some(0));
+        let elapsed = scratch.data[&job.key.to_string()][0];
+        let iters = calibrated_iters(target_ms, initial_iters, elapsed);
+        update_term_line(
+            &format!(
+                ">>> haste: calibrating {} {elapsed:.0}ms -> {iters} iters",
+                job.key
+            ),
+            "",
+        );
+        println!();
+        calibrated.insert(job.key.to_string(), iters);
+    }
+    show_cursor();
+    calibrated
+}
+
+/// Compute the number of in-process iterations needed to take `target_ms`, given that
+/// `measured_iters` iterations took `measured_ms`.
+pub(crate) fn calibrated_iters(target_ms: f64, measured_iters: usize, measured_ms: f64) -> usize {
+    if measured_ms <= 0. {
+        // Too fast to measure: the best we can do is to scale up as much as we dare.
+        return measured_iters.max(1) * 1000;
+    }
+    let per_iter = measured_ms / measured_iters as f64;
+    ((target_ms / per_iter).round() as usize).max(1)
+}
+
+/// Run the pexecs of `jobs` in the order of `schedule`, skipping those of suites which have run
+/// out of time, and starting no more once `opts.max_duration` has passed.
+fn run_scheduled(
+    results: &mut ResultFile,
+    state: &mut RunState,
+    config: &Config,
+    opts: &RunOptions,
+    jobs: &[Job],
+    schedule: &[Pexec],
+) {
+    let deadline = opts.max_duration.map(|d| Instant::now() + d);
+    let scheduled = schedule.iter().map(|p| p.job).collect::<BTreeSet<_>>();
+    let warmups = Totals::new(config, scheduled.into_iter().map(|j| &jobs[j]), 0);
+    let totals = Totals {
+        pexecs: schedule.len() + warmups.pexecs,
+        work: schedule
+            .iter()
+            .map(|p| job_work(config, &jobs[p.job]))
+            .sum::<f64>()
+            + warmups.work,
+    };
+    // When all of a job's pexecs run together, they share a line of output.
+    let grouped = opts.order() == Order::Grouped;
+    let mut open_line = None;
     let mut completed_pexecs = 0;
-    for (executor_name, executor) in &config.executors {
-        for suite in &config.suites {
-            run_suite(
-                &mut results,
-                config,
-                &mut completed_pexecs,
-                executor_name,
-                executor,
-                suite.1,
+    for p in schedule {
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            break;
+        }
+        if open_line.is_some_and(|j| j != p.job) {
+            println!();
+            open_line = None;
+        }
+        let job = &jobs[p.job];
+        if state.times.exhausted(job) {
+            continue;
+        }
+        let key = &job.key;
+        let progress = get_progress_percentage(&totals, completed_pexecs + state.warmups_run());
+        let eta = get_eta(config, results, state, &totals, completed_pexecs);
+        let status = format!("{:3.0}% (ETA {eta})", progress.round() as i64);
+        let round = format!("[{}/{}]", p.n + 1, config.proc_execs);
+        if !grouped {
+            update_term_line(&format!(">>> haste: {round} {key} ..."), &status);
+        } else if open_line.is_none() {
+            update_term_line(&format!(">>> haste: {key} ..."), &status);
+        }
+        io::stdout().flush().ok();
+        let ok = run_pexec(results, state, config, opts, job);
+        completed_pexecs += 1;
+        if !grouped {
+            let elapsed = pexec_time(results, &key.to_string(), ok);
+            update_term_line(&format!(">>> haste: {round} {key} {elapsed}"), "");
+            println!();
+            continue;
+        }
+        let progress = get_progress_percentage(&totals, completed_pexecs + state.warmups_run());
+        let eta = get_eta(config, results, state, &totals, completed_pexecs);
+        let so_far = results
+            .data
+            .get(&key.to_string())
+            .map_or(&[][..], Vec::as_slice)
+            .iter()
+            .map(|x| format!("{x:.0}ms"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if p.n + 1 < config.proc_execs {
+            update_term_line(
+                &format!(">>> haste: {key} {so_far} ..."),
+                &format!("{:3.0}% (ETA {eta})", progress.round() as i64),
             );
+        } else {
+            update_term_line(&format!(">>> haste: {key} {so_far}"), "");
         }
+        open_line = Some(p.job);
+    }
+    if open_line.is_some() {
+        println!();
+    }
+}
+
+/// For each benchmark, run one pexec with each of the executors in `opts.interleave` in turn, for
+/// `proc_execs` rounds, so that drift affects every executor similarly.
+///
+/// The key of each pexec is appended to `order` as it runs, and the round of each successful
+/// pexec is appended to its key's entry in `pairs`. Once any executor's suite runs out of time,
+/// the benchmark stops being run with every executor, so that the rounds stay paired.
+fn run_interleaved(
+    results: &mut ResultFile,
+    order: &mut Vec<String>,
+    pairs: &mut HashMap<String, Vec<usize>>,
+    state: &mut RunState,
+    config: &Config,
+    opts: &RunOptions,
+    jobs: &[Job],
+) {
+    // Group the jobs by benchmark, with the executors in the requested order.
+    let mut groups: Vec<Vec<&Job>> = Vec::new();
+    for job in jobs {
+        match groups
+            .iter_mut()
+            .find(|g| g[0].bench_name == job.bench_name && std::ptr::eq(g[0].suite, job.suite))
+        {
+            Some(g) => g.push(job),
+            None => groups.push(vec![job]),
+        }
+    }
+    for group in &mut groups {
+        group.retain(|j| opts.interleave.contains(&j.key.executor));
+        group.sort_by_key(|j| {
+            opts.interleave
+                .iter()
+                .position(|e| *e == j.key.executor)
+                .unwrap()
+        });
+    }
+
+    // A benchmark may not run with any of the interleaved executors.
+    groups.retain(|g| !g.is_empty());
+    let totals = Totals::new(config, groups.iter().flatten().copied(), config.proc_execs);
+
+    let mut completed_pexecs = 0;
+    for group in groups {
+        for round in 0..config.proc_execs {
+            if group.iter().any(|j| state.times.exhausted(j)) {
+                break;
+            }
+            let mut oks = Vec::with_capacity(group.len());
+            for job in &group {
+                let key = &job.key;
+                let progress =
+                    get_progress_percentage(&totals, completed_pexecs + state.warmups_run());
+                let eta = get_eta(config, results, state, &totals, completed_pexecs);
+                let lhs = format!(">>> haste: [{}/{}] {key} ...", round + 1, config.proc_execs);
+                update_term_line(
+                    &lhs,
+                    &format!("{:3.0}% (ETA {eta})", progress.round() as i64),
+                );
+                io::stdout().flush().ok();
+                let ok = run_pexec(results, state, config, opts, job);
+                if ok {
+                    pairs.entry(key.to_string()).or_default().push(round);
+                }
+                oks.push(ok);
+                completed_pexecs += 1;
+                order.push(key.to_string());
+            }
+            let times = group
+                .iter()
+                .zip(oks)
+                .map(|(j, ok)| pexec_time(results, &j.key.to_string(), ok))
+                .collect::<Vec<_>>()
+                .join(" vs. ");
+            let lhs = format!(
+                ">>> haste: [{}/{}] {} {times}",
+                round + 1,
+                config.proc_execs,
+                group[0].bench_name
+            );
+            update_term_line(&lhs, "");
+            println!();
+        }
+    }
+}
+
+/// The number of pexecs of `key` that have run, whether or not they succeeded.
+fn pexecs_done(results: &ResultFile, key: &str) -> usize {
+    results.data.get(key).map_or(0, Vec::len) + results.failures.get(key).map_or(0, |f| f.count)
+}
+
+/// Format the time of the last pexec of `key` for progress output, or say that it failed if not
+/// `ok`.
+fn pexec_time(results: &ResultFile, key: &str, ok: bool) -> String {
+    match results.data.get(key).and_then(|d| d.last()) {
+        Some(ms) if ok => format!("{ms:.0}ms"),
+        _ => "failed".to_owned(),
     }
-    results
+}
+
+/// Set when the user presses Ctrl-C.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Handle Ctrl-C by restoring the cursor and setting a flag that [interrupted] reports.
+///
+/// Benchmarks also receive the signal, so a run in progress will stop.
+pub(crate) fn install_interrupt_handler() {
+    // This fails if the handler is already installed, which is fine.
+    ctrlc::set_handler(|| {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+        show_cursor();
+    })
+    .ok();
+}
+
+/// Has the user pressed Ctrl-C?
+pub(crate) fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
 }
 
 fn hide_cursor() {
@@ -100,177 +1099,692 @@ fn update_term_line(lhs: &str, rhs: &str) {
     }
 }
 
-/// Run a suite with the specified executor.
-fn run_suite(
+/// Run one measured pexec of `job`, first running its warmup pexecs if it's the first, and
+/// applying its suite's cache policy, and add the time taken, the warmups, and with
+/// `sample_system` the readings around it, to `state`.
+///
+/// Returns `false` if the pexec failed (which only returns at all with `--keep-going`).
+fn run_pexec(
     results: &mut ResultFile,
+    state: &mut RunState,
     config: &Config,
-    completed_pexecs: &mut usize,
-    executor_name: &str,
-    executor: &Path,
-    suite: &Suite,
-) {
-    hide_cursor();
-    ctrlc::set_handler(show_cursor).ok();
-    for (bench_name, bench) in &suite.benchmarks {
-        let key = BenchKey {
-            benchmark: bench_name.into(),
-            executor: executor_name.into(),
-            extra_args: bench.extra_args.clone(),
+    opts: &RunOptions,
+    job: &Job,
+) -> bool {
+    let start = Instant::now();
+    let key = job.key.to_string();
+    if job.warmup_pexecs > 0 && pexecs_done(results, &key) == 0 {
+        let mut warmup = ResultFile::default();
+        for _ in 0..job.warmup_pexecs {
+            opts.log("warmup", &[("key", key.clone())]);
+            *state.warmups.entry(key.clone()).or_default() += 1;
+            if run_benchmark(&mut warmup, config, opts, job, None) == Ran::Failed {
+                break;
+            }
+        }
+        // A failing warmup is as much a failure of the benchmark as any other pexec.
+        results.failures.extend(warmup.failures);
+    }
+    if job.suite.cache_policy == CachePolicy::Cold {
+        drop_caches();
+    }
+    let sysfs = Path::new(environment::SYSFS);
+    let before = config.sample_system.then(|| environment::read(sysfs));
+    let started = state.started.entry(key.clone()).or_default();
+    let pexec = *started;
+    *started += 1;
+    opts.event(&Event::PexecStarted { key: &key, pexec });
+    let mut reruns = 0;
+    let ok = loop {
+        match run_benchmark(results, config, opts, job, Some(pexec)) {
+            Ran::Tainted(reason) if reruns < suspend::MAX_RERUNS => {
+                discard_last(results, &key);
+                reruns += 1;
+                *state.discarded.entry(key.clone()).or_default() += 1;
+                println!();
+                eprintln!("warning: discarding pexec {pexec} of {key} and rerunning it: {reason}");
+                opts.log(
+                    "pexec_discarded",
+                    &[
+                        ("key", key.clone()),
+                        ("pexec", pexec.to_string()),
+                        ("reason", reason),
+                    ],
+                );
+            }
+            Ran::Tainted(reason) => {
+                println!();
+                eprintln!(
+                    "warning: keeping pexec {pexec} of {key} after {MAX} reruns, although \
+                     {reason}",
+                    MAX = suspend::MAX_RERUNS
+                );
+                break true;
+            }
+            ran => break ran == Ran::Recorded,
+        }
+    };
+    if ok {
+        opts.event(&Event::PexecFinished {
+            key: &key,
+            pexec,
+            elapsed_ms: results.data[&key].last().copied().unwrap(),
+        });
+    }
+    if let Some(before) = before {
+        let after = environment::read(sysfs);
+        // Where nothing can be read, nothing is recorded.
+        if before != Reading::default() || after != Reading::default() {
+            state.system_readings.push(PexecReadings {
+                key: job.key.to_string(),
+                before,
+                after,
+            });
+        }
+    }
+    state.times.add(job, start.elapsed());
+    ok
+}
+
+/// Read `pipe` to its end, keeping at most `max` bytes of it: its start and end, with a note of
+/// how many bytes were discarded from between them. Also returns whether any were discarded.
+fn read_bounded(mut pipe: impl Read, max: usize) -> (Vec<u8>, bool) {
+    let head_max = max / 2;
+    let tail_max = max - head_max;
+    let mut head = Vec::new();
+    let mut tail = VecDeque::<u8>::new();
+    let mut discarded = 0;
+    let mut buf = vec![0; READ_CHUNK];
+    loop {
+        let n = match pipe.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => break,
         };
-        let progress = get_progress_percentage(config, *completed_pexecs);
-        let eta = get_eta(config, results, *completed_pexecs);
-        update_term_line(
-            &format!(">>> haste: {key} ..."),
-            &format!("{:3.0}% (ETA {eta})", progress.round() as i64),
+        let to_head = (head_max - head.len()).min(n);
+        head.extend_from_slice(&buf[..to_head]);
+        tail.extend(&buf[to_head..n]);
+        let excess = tail.len().saturating_sub(tail_max);
+        tail.drain(..excess);
+        discarded += excess;
+    }
+    if discarded > 0 {
+        head.extend_from_slice(
+            format!("\n[haste: {discarded} bytes discarded (max_output_bytes = {max})]\n")
+                .as_bytes(),
         );
+    }
+    head.extend(tail);
+    (head, discarded > 0)
+}
 
-        for i in 0..(config.proc_execs) {
-            io::stdout().flush().ok();
-            run_benchmark(
-                results,
-                config,
-                executor_name,
-                executor,
-                suite,
-                bench_name,
-                bench,
-            );
-            *completed_pexecs += 1;
-            let progress = get_progress_percentage(config, *completed_pexecs);
-            let eta = get_eta(config, results, *completed_pexecs);
-            let so_far = results
-                .data
-                .get(&key.to_string())
-                .unwrap()
-                .iter()
-                .map(|x| format!("{x:.0}ms"))
-                .collect::<Vec<_>>()
-                .join(" ");
-            let lhs = if i + 1 < config.proc_execs {
-                format!(">>> haste: {key} {so_far} ...")
-            } else {
-                format!(">>> haste: {key} {so_far}")
-            };
-            let rhs = if i + 1 < config.proc_execs {
-                format!("{:3.0}% (ETA {eta})", progress.round() as i64)
-            } else {
-                "".to_owned()
-            };
-            update_term_line(&lhs, &rhs);
-        }
-        println!();
+thread_local! {
+    /// Is a pexec running on this thread, in [run_bounded]?
+    static IN_PEXEC: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Marks a pexec as running on this thread until dropped.
+pub(crate) struct PexecRunning;
+
+impl PexecRunning {
+    pub(crate) fn start() -> Self {
+        IN_PEXEC.set(true);
+        PexecRunning
     }
-    show_cursor();
 }
 
-/// Run an individual benchmark.
+impl Drop for PexecRunning {
+    fn drop(&mut self) {
+        IN_PEXEC.set(false);
+    }
+}
+
+/// Check that no pexec is running before doing `what`. haste's own work, such as writing files
+/// and hashing binaries, is only ever done between pexecs, so that it never competes with a
+/// benchmark for the machine.
+pub(crate) fn between_pexecs(what: &str) {
+    assert!(!IN_PEXEC.get(), "{what} while a pexec is running");
+}
+
+/// How a pexec ran.
+pub(crate) struct Finished {
+    /// The exit status, and what was kept of stdout and stderr.
+    pub(crate) output: Output,
+    /// Was the pexec killed for running for too long?
+    pub(crate) timed_out: bool,
+    /// Was some of stdout or stderr discarded?
+    pub(crate) truncated: bool,
+    /// The time from starting the pexec to its exit, however long its output takes to read.
+    pub(crate) wallclock: Duration,
+    /// How far each clock advanced over the same time.
+    pub(crate) clocks: suspend::Deltas,
+}
+
+/// Run `cmd` to completion like [Command::output], but keeping at most `max_output` bytes of each
+/// of stdout and stderr (see [read_bounded]), and killing it, and anything it started, if it runs
+/// for longer than `timeout`.
+pub(crate) fn run_bounded(
+    cmd: &mut Command,
+    timeout: Option<Duration>,
+    max_output: usize,
+) -> io::Result<Finished> {
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if timeout.is_some() {
+        // In its own process group, the pexec can be killed along with its children, but no
+        // longer receives the terminal's Ctrl-C, so the watchdog passes that on.
+        cmd.process_group(0);
+    }
+    let running = PexecRunning::start();
+    let clocks = suspend::Clocks::read();
+    let start = Instant::now();
+    let mut child = cmd.spawn()?;
+    // Each pipe is drained by its own thread, so that a pexec blocked writing to one can't
+    // deadlock with us waiting for it to finish.
+    let stdout = child.stdout.take().unwrap();
+    let stderr = child.stderr.take().unwrap();
+    let stdout = thread::spawn(move || read_bounded(stdout, max_output));
+    let stderr = thread::spawn(move || read_bounded(stderr, max_output));
+    let pgid = child.id();
+    let (done, finished) = mpsc::channel::<()>();
+    let watchdog = timeout.map(|timeout| {
+        thread::spawn(move || {
+            let kill = |signal: &str| {
+                Command::new("kill")
+                    .args([signal, "--", &format!("-{pgid}")])
+                    .stderr(Stdio::null())
+                    .status()
+                    .ok();
+            };
+            let deadline = Instant::now() + timeout;
+            loop {
+                let left = deadline.saturating_duration_since(Instant::now());
+                if left.is_zero() {
+                    kill("-KILL");
+                    return true;
+                }
+                match finished.recv_timeout(left.min(INTERRUPT_POLL)) {
+                    Err(RecvTimeoutError::Timeout) if interrupted() => {
+                        kill("-INT");
+                        return false;
+                    }
+                    Err(RecvTimeoutError::Timeout) => (),
+                    _ => return false,
+                }
+            }
+        })
+    });
+    let status = child.wait();
+    let wallclock = start.elapsed();
+    let clocks = suspend::Clocks::read().since(&clocks);
+    drop(running);
+    drop(done);
+    let timed_out = watchdog.is_some_and(|w| w.join().unwrap());
+    let (stdout, stdout_truncated) = stdout.join().unwrap();
+    let (stderr, stderr_truncated) = stderr.join().unwrap();
+    Ok(Finished {
+        output: Output {
+            status: status?,
+            stdout,
+            stderr,
+        },
+        timed_out,
+        truncated: stdout_truncated || stderr_truncated,
+        wallclock,
+        clocks,
+    })
+}
+
+/// What became of a pexec run by [run_benchmark].
+#[derive(Debug, PartialEq)]
+enum Ran {
+    /// The pexec succeeded and its results were recorded.
+    Recorded,
+    /// The pexec succeeded and its results were recorded, but its time can't be trusted, for
+    /// the reason given (see [suspend::taint]).
+    Tainted(String),
+    /// The pexec failed and was recorded in `results.failures`.
+    Failed,
+}
+
+/// Run an individual benchmark as measured pexec number `measured`, or as a warmup pexec if
+/// that's `None`.
 fn run_benchmark(
     results: &mut ResultFile,
     config: &Config,
-    executor_name: &str,
-    executor: &Path,
-    suite: &Suite,
-    bench_name: &str,
-    bench: &Benchmark,
-) {
-    let harness = suite.harness.to_str().unwrap();
-    let inproc_iters = config.inproc_iters.to_string();
+    opts: &RunOptions,
+    job: &Job,
+    measured: Option<usize>,
+) -> Ran {
+    let Job {
+        executor,
+        suite,
+        bench_name,
+        bench,
+        ..
+    } = job;
+    let inproc_iters = match job.inproc_iters {
+        0 => AUTO_ITERS.to_owned(),
+        n => n.to_string(),
+    };
     use tempfile::NamedTempFile;
-    let mut tmpf = NamedTempFile::new().unwrap();
+    let tmpf = NamedTempFile::new().unwrap();
 
-    let mut args = vec![
-        tmpf.path().to_str().unwrap(),
-        executor.to_str().unwrap(),
-        bench_name,
-        &inproc_iters,
+    let cwd = suite.dir_for(bench);
+    let pexec = results
+        .data
+        .get(&job.key.to_string())
+        .map_or(0, Vec::len)
+        .to_string();
+    // Tell the benchmark what it's part of, e.g. so that it can name any files it writes.
+    let haste_env = [
+        ("HASTE_EXECUTOR", job.key.executor.as_str()),
+        ("HASTE_BENCHMARK", bench_name),
+        ("HASTE_PEXEC", &pexec),
+        ("HASTE_INPROC_ITERS", &inproc_iters),
     ];
-    args.extend(bench.extra_args.iter().map(String::as_str));
 
-    let mut cmd = Command::new(harness);
-    cmd.current_dir(&suite.dir)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
-    for (k, v) in &suite.env {
-        cmd.env(k, v);
+    // Work out what to run. Config validation ensures that benchmarks without a command have a
+    // harness.
+    let (program, args, harness) = if let Some(command) = &bench.command {
+        let script = if executor.as_os_str() == SHELL_EXECUTOR {
+            command.to_owned()
+        } else {
+            format!("\"$EXECUTOR\" {command}")
+        };
+        ("sh".into(), vec!["-c".to_owned(), script], None)
+    } else {
+        let harness = suite.harness_for(&job.key.executor).unwrap();
+        let mut args = vec![
+            tmpf.path().to_str().unwrap().to_owned(),
+            executor.to_str().unwrap().to_owned(),
+            bench_name.to_string(),
+            inproc_iters.clone(),
+        ];
+        args.extend(suite.extra_args.iter().cloned());
+        args.extend(bench.extra_args.iter().cloned());
+        (suite.resolve_harness(harness, bench), args, Some(harness))
+    };
+
+    // When collecting perf counters, the benchmark is run under `perf stat`, which writes its CSV
+    // output to a separate file so that it can't get mixed up with the benchmark's own stderr.
+    let perf_tmpf = config.perf.then(|| NamedTempFile::new().unwrap());
+    let mut cmd = if let Some(host) = job.remote {
+        // Config validation ensures that perf isn't used with remote executors.
+        let mut env = suite
+            .env
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .chain(haste_env)
+            .collect::<Vec<_>>();
+        if bench.command.is_some() {
+            env.push(("ITERS", &inproc_iters));
+            env.push(("EXECUTOR", executor.to_str().unwrap()));
+        }
+        // The harness's output file is made on the remote host instead of being `tmpf`.
+        let args = if harness.is_some() {
+            &args[1..]
+        } else {
+            &args[..]
+        };
+        let script = remote::script(
+            &cwd,
+            &env,
+            program.to_str().unwrap(),
+            args,
+            harness.is_some(),
+        );
+        remote::ssh_command(host, &script)
+    } else if let Some(perf_tmpf) = &perf_tmpf {
+        let mut cmd = Command::new("perf");
+        cmd.args(["stat", "-x,", "-o", perf_tmpf.path().to_str().unwrap()]);
+        cmd.args(["-e", &PERF_EVENTS.join(",")]);
+        cmd.arg("--").arg(&program);
+        cmd
+    } else {
+        Command::new(&program)
+    };
+    if job.remote.is_none() {
+        cmd.current_dir(&cwd);
+        for (k, v) in &suite.env {
+            cmd.env(k, v);
+        }
+        cmd.envs(haste_env);
+        if bench.command.is_some() {
+            cmd.env("ITERS", &inproc_iters).env("EXECUTOR", executor);
+        }
+        cmd.args(&args);
+        if let Some(n) = config.nice {
+            nice::apply(&mut cmd, n);
+        }
+    }
+    if opts.verbose {
+        println!();
+        println!(">>> haste: running {cmd:?} in {}", cwd.display());
     }
-    cmd.args(&args);
+    let log_key = ("key", job.key.to_string());
+    let log_pexec = ("pexec", pexec.clone());
+    let key = job.key.to_string();
+    let failed = |status: &str| {
+        opts.event(&Event::BenchmarkFailed {
+            key: &key,
+            pexec: measured,
+            warmup: measured.is_none(),
+            status,
+        });
+    };
+    opts.log(
+        "pexec_start",
+        &[
+            log_key.clone(),
+            log_pexec.clone(),
+            ("cmd", format!("{cmd:?}")),
+            ("cwd", cwd.display().to_string()),
+        ],
+    );
 
-    // We are careful to use `output()` and not `spawn()` here so as to avoid deadlocks for
-    // benchmarks that make a lot of output.
-    let Ok(output) = black_box(cmd.output()) else {
-        eprintln!("error: failed to spawn benchmark!");
-        eprintln!("args: {cmd:?}");
-        show_cursor();
-        process::exit(1)
+    let start_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let timeout = config.max_pexec_for(suite, bench);
+    let Finished {
+        output,
+        timed_out,
+        truncated,
+        wallclock,
+        clocks,
+    } = match black_box(run_bounded(&mut cmd, timeout, config.max_output_bytes())) {
+        Ok(finished) => finished,
+        Err(e) => {
+            opts.log(
+                "spawn_failed",
+                &[log_key, log_pexec, ("error", e.to_string())],
+            );
+            failed("spawn failed");
+            if opts.keep_going {
+                record_failure(
+                    results,
+                    &job.key.to_string(),
+                    "spawn failed",
+                    &e.to_string(),
+                );
+                return Ran::Failed;
+            }
+            eprintln!("error: failed to spawn benchmark: {e}");
+            eprintln!("args: {cmd:?}");
+            show_cursor();
+            process::exit(1)
+        }
     };
 
-    if !output.status.success() {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let trailer = job.remote.map(|_| remote::split_trailer(&stderr));
+    let status = match output.status.code() {
+        _ if timed_out => "timeout".to_owned(),
+        Some(code) => code.to_string(),
+        None => "signal".to_owned(),
+    };
+    if !output.status.success() || matches!(trailer, Some(Err(_))) {
+        let event = if interrupted() {
+            "interrupted"
+        } else {
+            "pexec_failed"
+        };
+        opts.log(event, &[log_key, log_pexec, ("status", status.clone())]);
         println!();
-        eprintln!("error: benchmark command exited non-zero!");
+        if interrupted() {
+            eprintln!("haste: interrupted");
+            show_cursor();
+            process::exit(1)
+        }
+        failed(&status);
+        let own_stderr = match &trailer {
+            Some(Ok((own, _))) => own,
+            _ => &*stderr,
+        };
+        if opts.keep_going {
+            let mut excerpt = own_stderr.to_owned();
+            if let Some(Err(e)) = &trailer {
+                excerpt.push_str(&format!("error: {e}\n"));
+            }
+            if truncated {
+                excerpt.push_str(&format!("{TRUNCATED_NOTE}\n"));
+            }
+            println!(
+                "haste: {} failed (exit status {status}), carrying on",
+                job.key
+            );
+            record_failure(results, &job.key.to_string(), &status, &excerpt);
+            return Ran::Failed;
+        }
+        match timeout {
+            Some(t) if timed_out => eprintln!(
+                "error: benchmark timed out after {}s (max_pexec_secs)!",
+                t.as_secs_f64()
+            ),
+            _ => eprintln!("error: benchmark command exited non-zero!"),
+        }
         eprintln!("args: {cmd:?}");
         let stdout = String::from_utf8_lossy(&output.stdout);
         eprintln!("--- Begin stdout ---");
         eprint!("{stdout}");
         eprintln!("--- End stdout ---");
-        let stderr = String::from_utf8_lossy(&output.stderr);
         eprintln!("--- Begin stderr ---");
-        eprint!("{stderr}");
+        eprint!("{own_stderr}");
         eprintln!("--- End stderr ---");
+        if let Some(Err(e)) = &trailer {
+            eprintln!("error: {e}");
+        }
+        if truncated {
+            eprintln!("{TRUNCATED_NOTE}");
+        }
         show_cursor();
         process::exit(1)
     }
 
-    // Parse the measurements out of the output file.
-    //
-    // For now there should be only a `WALLCLOCK_TIME_MS=x.y` line in there.
+    let elapsed = match trailer {
+        Some(Ok((_, trailer))) if harness.is_some() => parse_harness_output(&trailer.harness_out)
+            .unwrap_or_else(|e| {
+                eprintln!("{e} (from remote output file)");
+                eprintln!("args: {cmd:?}");
+                show_cursor();
+                process::exit(1)
+            }),
+        // The remote wrapper times the benchmark on the remote host, so that connection latency
+        // isn't counted.
+        Some(Ok((_, trailer))) => trailer.elapsed_ms,
+        Some(Err(_)) => unreachable!(),
+        None if harness.is_some() => read_harness_output(tmpf, &cmd),
+        // Command benchmarks have no harness to time them, so we use our own measurement.
+        None => wallclock.as_secs_f64() * 1000.,
+    };
+
+    opts.log(
+        "pexec_end",
+        &[
+            log_key,
+            log_pexec,
+            ("status", status),
+            ("elapsed_ms", format!("{elapsed:.3}")),
+            (
+                "wallclock_ms",
+                format!("{:.3}", wallclock.as_secs_f64() * 1000.),
+            ),
+        ],
+    );
+
+    let bench_key = &job.key;
+    if job.inproc_iters == 0 {
+        let iters =
+            parse_iters_used(&String::from_utf8_lossy(&output.stdout)).unwrap_or_else(|e| {
+                eprintln!("error: {e}");
+                eprintln!("args: {cmd:?}");
+                show_cursor();
+                process::exit(1)
+            });
+        results
+            .metrics
+            .entry(ITERS_METRIC.to_owned())
+            .or_default()
+            .entry(bench_key.to_string())
+            .or_default()
+            .push(iters as f64);
+    }
+    if let Some(perf_tmpf) = perf_tmpf {
+        let perf_out = std::fs::read_to_string(perf_tmpf.path()).unwrap();
+        let counters = parse_perf_output(&perf_out).unwrap_or_else(|e| {
+            eprintln!("error: {e}");
+            eprintln!("args: {cmd:?}");
+            show_cursor();
+            process::exit(1)
+        });
+        for (event, v) in counters {
+            results
+                .metrics
+                .entry(event)
+                .or_default()
+                .entry(bench_key.to_string())
+                .or_default()
+                .push(v);
+        }
+    }
+    results
+        .data
+        .entry(bench_key.to_string())
+        .or_default()
+        .push(elapsed);
+    results
+        .timestamps
+        .entry(bench_key.to_string())
+        .or_default()
+        .push(start_ms);
+    results.params.insert(
+        bench_key.to_string(),
+        KeyParams {
+            inproc_iters: job.inproc_iters,
+            proc_execs: config.proc_execs,
+            extra_args: bench.extra_args.clone(),
+            suite_args: if harness.is_some() {
+                suite.extra_args.clone()
+            } else {
+                Vec::new()
+            },
+            harness: harness.map(Path::to_owned),
+            command: bench.command.clone(),
+        },
+    );
+    // Over ssh, the CPU time is only that of the local ssh client.
+    let max_ratio = config.max_wall_cpu_ratio.filter(|_| job.remote.is_none());
+    match suspend::taint(&clocks, max_ratio) {
+        Some(reason) => Ran::Tainted(reason),
+        None => Ran::Recorded,
+    }
+}
+
+/// Discard the newest pexec of benchmark `key` from `results`.
+pub(crate) fn discard_last(results: &mut ResultFile, key: &str) {
+    let samples = results
+        .data
+        .iter_mut()
+        .chain(results.metrics.values_mut().flat_map(|m| m.iter_mut()));
+    for (_, samples) in samples.filter(|(k, _)| *k == key) {
+        samples.pop();
+    }
+    if let Some(t) = results.timestamps.get_mut(key) {
+        t.pop();
+    }
+}
+
+/// Record that a pexec of benchmark `key` failed with `status`, keeping the end of its `stderr`.
+fn record_failure(results: &mut ResultFile, key: &str, status: &str, stderr: &str) {
+    let f = results.failures.entry(key.to_owned()).or_insert(Failure {
+        count: 0,
+        status: String::new(),
+        stderr: String::new(),
+    });
+    f.count += 1;
+    f.status = status.to_owned();
+    f.stderr = stderr_excerpt(stderr);
+}
+
+/// The last [STDERR_EXCERPT_LINES] lines of `stderr`.
+fn stderr_excerpt(stderr: &str) -> String {
+    let lines = stderr.lines().collect::<Vec<_>>();
+    let start = lines.len().saturating_sub(STDERR_EXCERPT_LINES);
+    let mut excerpt = lines[start..].join("\n");
+    if start > 0 {
+        excerpt.insert_str(0, "...\n");
+    }
+    excerpt
+}
+
+/// Parse the wall-clock time (in milliseconds) out of the output file written by a harness.
+fn read_harness_output(mut tmpf: tempfile::NamedTempFile, cmd: &Command) -> f64 {
     let mut buf = String::new();
     use std::io::{Read, Seek};
     tmpf.as_file_mut()
         .seek(std::io::SeekFrom::Start(0))
         .unwrap();
     tmpf.read_to_string(&mut buf).unwrap();
-    let buf = buf.trim();
-
-    // Note: in error scenarios, we use `tmpf.keep()`, so the user can inspect their broken output
-    // file for debugging purposes.
-    let pth = tmpf.path().to_owned();
-    let elapsed = if let Some((k, v)) = buf.split_once("=") {
-        if k != PEXEC_WALLCLOCK_MS {
+    match parse_harness_output(&buf) {
+        Ok(v) => v,
+        Err(e) => {
+            // Keep the file, so the user can inspect their broken output file for debugging
+            // purposes.
+            let pth = tmpf.path().to_owned();
             let _ = tmpf.keep().ok();
-            eprintln!(
-                "failed to find {} key in output file {}",
-                PEXEC_WALLCLOCK_MS,
-                pth.to_str().unwrap()
-            );
+            eprintln!("{e} (from output file {})", pth.to_str().unwrap());
             eprintln!("args: {cmd:?}");
             std::process::exit(1);
         }
-        let Ok(v) = v.parse::<f64>() else {
-            let _ = tmpf.keep().ok();
-            eprintln!(
-                "failed to parse {} as a number of milliseconds (from output file {}). ",
-                v,
-                pth.to_str().unwrap()
-            );
-            eprintln!("args: {cmd:?}");
-            std::process::exit(1);
-        };
-        v
-    } else {
-        let _ = tmpf.keep().ok();
-        eprintln!("failed to parse output file: {}", pth.to_str().unwrap());
-        eprintln!("args: {cmd:?}");
-        std::process::exit(1);
+    }
+}
+
+/// Parse the number of in-process iterations that a harness left to choose them ran out of its
+/// stdout. If it reported more than once, the last report counts.
+fn parse_iters_used(stdout: &str) -> Result<usize, String> {
+    let Some(v) = stdout
+        .lines()
+        .rev()
+        .find_map(|l| l.strip_prefix(ITERS_USED))
+    else {
+        return Err(format!(
+            "the harness chose its own in-process iterations, but didn't report them with \
+             {ITERS_USED} on stdout"
+        ));
     };
+    v.trim()
+        .parse()
+        .map_err(|_| format!("failed to parse {ITERS_USED}{v} as a number of iterations"))
+}
 
-    let bench_key = BenchKey {
-        benchmark: bench_name.to_owned(),
-        executor: executor_name.to_owned(),
-        extra_args: bench.extra_args.to_owned(),
+/// Parse the wall-clock time (in milliseconds) out of the contents of a harness's output file.
+///
+/// The time must use `.` as its decimal separator whatever the harness's locale: a harness that
+/// formats it with the locale's separator as, e.g. `12,5`, is told so rather than having `,`
+/// guessed at, since it's also a common thousands separator.
+pub(crate) fn parse_harness_output(buf: &str) -> Result<f64, String> {
+    // For now there should be only a `WALLCLOCK_TIME_MS=x.y` line in there.
+    let buf = buf.trim();
+    let Some((k, v)) = buf.split_once("=") else {
+        return Err("failed to parse output file".to_owned());
     };
-    results
-        .data
-        .entry(bench_key.to_string())
-        .or_default()
-        .push(elapsed);
+    if k != PEXEC_WALLCLOCK_MS {
+        return Err(format!(
+            "failed to find {PEXEC_WALLCLOCK_MS} key in output file"
+        ));
+    }
+    v.parse::<f64>().map_err(|_| {
+        if v.contains(',') {
+            format!(
+                "failed to parse {v} as a number of milliseconds: use '.' as the decimal \
+                 separator, whatever the locale"
+            )
+        } else {
+            format!("failed to parse {v} as a number of milliseconds")
+        }
+    })
 }
@@ -0,0 +1,332 @@
+//! A self-test of haste's whole pipeline: benchmarking, storing and diffing, run against tiny
+//! synthetic harnesses whose results are known in advance, so that regressions in the runner
+//! show up without a real benchmark suite.
+//!
+//! The harnesses are shell scripts, written with a config to a temporary directory, which is
+//! also the state directory. Each behaves in one way that has caught, or could catch, the runner
+//! out: sleeping for a precise time, flooding stdout, failing, printing per-iteration lines, and
+//! ignoring its arguments.
+//!
+//! Only wall-clock times are checked. The harnesses can't report other metrics, and perf counters
+//! depend on the machine, so their collection isn't covered.
+
+use crate::store::StoreKind;
+use crate::{App, BenchOptions, ConfidenceLevel, DEFAULT_MIN_SAMPLES, ResultFile, diff_rows};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process;
+
+/// The number of pexecs of each benchmark in each of the two datums.
+const PROC_EXECS: usize = 5;
+/// The number of in-process iterations of each benchmark.
+const INPROC_ITERS: usize = 4;
+/// How long the `sleep` benchmark sleeps for in the first datum and the second, in milliseconds.
+const SLEEP_MS: [f64; 2] = [20., 60.];
+/// How much longer than it asks for a sleep may take, in milliseconds, for starting the shell
+/// and the scheduler's delays.
+const SLEEP_SLACK_MS: f64 = 50.;
+/// The time the `fixed` harness always reports, in milliseconds. Its fractional part catches
+/// times being truncated to whole milliseconds.
+const FIXED_MS: f64 = 12.345;
+/// The time the `iterations` harness reports for each iteration, in milliseconds.
+const ITERATION_MS: f64 = 0.5;
+/// How many bytes of each pexec's output to keep: much less than the `flood` harness writes.
+const MAX_OUTPUT_BYTES: usize = 4096;
+
+/// The harnesses, by file name. Each is run as `<harness> <output file> <executor> <benchmark>
+/// <iters>`, and answers a `--haste-probe` briefly.
+const HARNESSES: [(&str, &str); 4] = [
+    (
+        "fixed.sh",
+        r#"#!/bin/sh
+# Report the same time, whatever the arguments.
+[ "$1" = --haste-probe ] && exit 1
+echo "PEXEC_WALLCLOCK_MS=12.345" > "$1"
+"#,
+    ),
+    (
+        "flood.sh",
+        r#"#!/bin/sh
+# Write much more to stdout than haste keeps.
+[ "$1" = --haste-probe ] && exit 1
+head -c 1048576 /dev/zero | tr '\0' x
+echo "PEXEC_WALLCLOCK_MS=1.5" > "$1"
+"#,
+    ),
+    (
+        "fail.sh",
+        r#"#!/bin/sh
+# Fail, saying why on stderr.
+[ "$1" = --haste-probe ] && exit 1
+echo "selftest: deliberate failure" >&2
+exit 3
+"#,
+    ),
+    (
+        "iterations.sh",
+        r#"#!/bin/sh
+# Print a line for each in-process iteration, and report their total time. Answer a probe as a
+# harness of protocol version 1.
+if [ "$1" = --haste-probe ]; then
+    echo "protocol = 1"
+    exit 0
+fi
+i=0
+while [ "$i" -lt "$4" ]; do
+    echo "iteration $i: 0.5ms"
+    i=$((i + 1))
+done
+echo "PEXEC_WALLCLOCK_MS=$(awk "BEGIN { print $4 * 0.5 }")" > "$1"
+"#,
+    ),
+];
+
+/// The config for the harnesses in `dir`.
+fn config_toml(dir: &Path) -> String {
+    let dir = dir.display();
+    let suite = |name: &str| {
+        format!(
+            "[suites.{name}]\ndir = \"{dir}\"\nharness = \"{dir}/{name}.sh\"\n\
+             benchmarks = {{ {name} = {{}} }}\n"
+        )
+    };
+    let mut config = format!(
+        "proc_execs = {PROC_EXECS}\ninproc_iters = {INPROC_ITERS}\n\
+         max_output_bytes = {MAX_OUTPUT_BYTES}\nexecutors = {{ sh = \"shell\" }}\n\
+         [suites.sleep]\ndir = \"{dir}\"\n\
+         benchmarks = {{ sleep = {{ command = \"sleep $(cat sleep_secs)\" }} }}\n"
+    );
+    for (file, _) in HARNESSES {
+        config.push_str(&suite(file.strip_suffix(".sh").unwrap()));
+    }
+    config
+}
+
+/// Write the harnesses and the config to `dir`.
+fn write_suite(dir: &Path) -> Result<(), String> {
+    let err = |path: &Path, e: std::io::Error| format!("can't write {}: {e}", path.display());
+    for (file, script) in HARNESSES {
+        let path = dir.join(file);
+        fs::write(&path, script).map_err(|e| err(&path, e))?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).map_err(|e| err(&path, e))?;
+    }
+    let path = dir.join("haste.toml");
+    fs::write(&path, config_toml(dir)).map_err(|e| err(&path, e))
+}
+
+/// The outcome of one check.
+pub(crate) struct Check {
+    pub(crate) name: String,
+    pub(crate) result: Result<(), String>,
+}
+
+/// Check that `value` is within `tolerance` of `expected`.
+fn within(value: f64, expected: f64, tolerance: f64) -> Result<(), String> {
+    if (value - expected).abs() <= tolerance {
+        Ok(())
+    } else {
+        Err(format!("{value} is not within {tolerance} of {expected}"))
+    }
+}
+
+/// Check that benchmark `key` of `results` has [PROC_EXECS] samples, each within `tolerance` of
+/// `expected`.
+fn samples_within(
+    results: &ResultFile,
+    key: &str,
+    expected: f64,
+    tolerance: f64,
+) -> Result<(), String> {
+    let samples = results
+        .data
+        .get(key)
+        .ok_or_else(|| format!("no samples of {key}"))?;
+    if samples.len() != PROC_EXECS {
+        return Err(format!(
+            "{} samples of {key}, not {PROC_EXECS}",
+            samples.len()
+        ));
+    }
+    samples
+        .iter()
+        .try_for_each(|s| within(*s, expected, tolerance))
+}
+
+/// The checks of datum `n` (0 or 1) of the run, `results`.
+fn check_datum(n: usize, results: &ResultFile) -> Vec<Check> {
+    let check = |what: &str, result| Check {
+        name: format!("datum {}: {what}", n + 1),
+        result,
+    };
+    let sleep_ms = SLEEP_MS[n];
+    let sleep = samples_within(
+        results,
+        "sleep/sh/",
+        sleep_ms + SLEEP_SLACK_MS / 2.,
+        SLEEP_SLACK_MS / 2.,
+    );
+    let precise = match results.data.get("sleep/sh/") {
+        Some(samples) if samples.iter().all(|s| s.fract() == 0.) => Err(format!(
+            "every sample is a whole number of milliseconds: {samples:?}"
+        )),
+        _ => Ok(()),
+    };
+    let failed = match results.failures.get("fail/sh/") {
+        None => Err("the failure wasn't recorded".to_owned()),
+        Some(f) if f.status != "3" => Err(format!("exit status {}, not 3", f.status)),
+        Some(f) if !f.stderr.contains("selftest: deliberate failure") => Err(format!(
+            "stderr lacks the harness's message: {:?}",
+            f.stderr
+        )),
+        Some(_) if results.data.contains_key("fail/sh/") => {
+            Err("the failing benchmark has samples".to_owned())
+        }
+        Some(_) => Ok(()),
+    };
+    let iters = match results.params.get("iterations/sh/") {
+        Some(p) if p.inproc_iters == INPROC_ITERS => Ok(()),
+        Some(p) => Err(format!(
+            "run with {} in-process iterations, not {INPROC_ITERS}",
+            p.inproc_iters
+        )),
+        None => Err("no parameters recorded".to_owned()),
+    };
+    vec![
+        check(&format!("sleep of {sleep_ms}ms timed"), sleep),
+        check("sleep timed to below a millisecond", precise),
+        check(
+            "fixed time recorded exactly",
+            samples_within(results, "fixed/sh/", FIXED_MS, 1e-9),
+        ),
+        check(
+            "flooding stdout is harmless",
+            samples_within(results, "flood/sh/", 1.5, 1e-9),
+        ),
+        check("failure recorded", failed),
+        check(
+            "iteration lines ignored",
+            samples_within(
+                results,
+                "iterations/sh/",
+                INPROC_ITERS as f64 * ITERATION_MS,
+                1e-9,
+            ),
+        ),
+        check("in-process iterations recorded", iters),
+    ]
+}
+
+impl App {
+    /// Run the self-test in a temporary directory, printing each check, and exiting non-zero if
+    /// any fails. With `keep`, the directory is kept for inspection.
+    pub(crate) fn cmd_selftest(&self, keep: bool) {
+        let dir = tempfile::Builder::new()
+            .prefix("haste-selftest-")
+            .tempdir()
+            .unwrap_or_else(|e| {
+                eprintln!("error: can't create a temporary directory: {e}");
+                process::exit(1);
+            });
+        let checks = selftest(dir.path(), self.seed).unwrap_or_else(|e| {
+            eprintln!("error: {e}");
+            process::exit(1);
+        });
+        println!();
+        let mut failed = 0;
+        for Check { name, result } in &checks {
+            match result {
+                Ok(()) => println!("pass  {name}"),
+                Err(e) => {
+                    failed += 1;
+                    println!("FAIL  {name}: {e}");
+                }
+            }
+        }
+        if keep {
+            println!("\nhaste: kept {}", dir.keep().display());
+        }
+        println!("\nhaste: {} pass, {failed} fail", checks.len() - failed);
+        if failed > 0 {
+            process::exit(1);
+        }
+    }
+}
+
+/// Benchmark the synthetic harnesses twice in `dir`, sleeping for longer the second time, store
+/// both datums in `dir`, and check them and their comparison.
+pub(crate) fn selftest(dir: &Path, seed: u64) -> Result<Vec<Check>, String> {
+    write_suite(dir)?;
+    let app = App::new(
+        Some(dir.join("haste.toml")),
+        Some(dir.join(".haste")),
+        true,
+        Some(StoreKind::Fs),
+        true,
+        Some(seed),
+    );
+    let mut checks = Vec::new();
+    let mut ids = Vec::new();
+    for (n, ms) in SLEEP_MS.into_iter().enumerate() {
+        let path = dir.join("sleep_secs");
+        fs::write(&path, format!("{}\n", ms / 1000.))
+            .map_err(|e| format!("can't write {}: {e}", path.display()))?;
+        let opts = BenchOptions {
+            comment: Some(format!("selftest {}", n + 1)),
+            skip_build: true,
+            keep_going: true,
+            quiet: true,
+            ..Default::default()
+        };
+        let id = app.bench(&mut app.load_config(), &opts);
+        let results = app.load_results(id);
+        checks.extend(check_datum(n, &results));
+        let extra = app.load_extra(id);
+        let probed = extra
+            .harnesses
+            .get("iterations")
+            .and_then(|h| h.get("sh"))
+            .map(|c| c.protocol);
+        checks.push(Check {
+            name: format!("datum {}: harness probed", n + 1),
+            result: match probed {
+                Some(1) => Ok(()),
+                p => Err(format!("protocol {p:?}, not 1")),
+            },
+        });
+        ids.push(id);
+    }
+
+    let confidence = ConfidenceLevel::default();
+    let (s1, s2) = (
+        app.load_summary(ids[0], confidence),
+        app.load_summary(ids[1], confidence),
+    );
+    let rows = diff_rows(&s1, &s2, DEFAULT_MIN_SAMPLES, false);
+    let summary = |key: &str| {
+        rows.iter()
+            .find(|r| r.key == key)
+            .map(|r| (r.is_regression(), r.summary()))
+            .ok_or_else(|| format!("{key} wasn't compared"))
+    };
+    let classified = |key: &str, regression: bool| {
+        summary(key).and_then(|(r, s)| {
+            if r == regression {
+                Ok(())
+            } else {
+                Err(format!("classified as {s}"))
+            }
+        })
+    };
+    checks.push(Check {
+        name: "diff: the longer sleep is a regression".to_owned(),
+        result: classified("sleep/sh/", true),
+    });
+    for key in ["fixed/sh/", "flood/sh/", "iterations/sh/"] {
+        checks.push(Check {
+            name: format!("diff: {key} is unchanged"),
+            result: classified(key, false),
+        });
+    }
+    Ok(checks)
+}
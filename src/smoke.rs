@@ -0,0 +1,166 @@
+//! Checking that every benchmark runs with every executor, by running each once, as quickly as
+//! possible, without caring how good the timings are: for a new config, or after changing a
+//! benchmark, before committing to a long run.
+
+use crate::config::{CachePolicy, Config};
+use crate::{App, BenchOptions, Failure, ResultFile, new_table, runner, units};
+use comfy_table::{Cell, Color};
+use std::process;
+use std::time::Duration;
+
+/// How one benchmark fared in a smoke run.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Outcome<'a> {
+    /// It ran, and its one pexec took this many milliseconds.
+    Pass(f64),
+    Fail(&'a Failure),
+    /// It wasn't run, e.g. because the run was interrupted.
+    NotRun,
+}
+
+/// Change `config` so that it runs one pexec of each benchmark with `iters` in-process
+/// iterations, and nothing else: no warmup pexecs, no calibration, no overhead measurements, no
+/// dropping of caches, and no perf counters or system readings.
+pub(crate) fn configure(config: &mut Config, iters: usize) {
+    config.proc_execs = 1;
+    config.inproc_iters = iters;
+    config.calibrate = None;
+    config.perf = false;
+    config.sample_system = false;
+    for executor in config.executors.values_mut() {
+        executor.iters_scale = 1.;
+    }
+    for suite in config.suites.values_mut() {
+        suite.cache_policy = CachePolicy::None;
+        suite.calibration_benchmark = None;
+        suite.warmup_pexecs = None;
+        suite.max_suite_secs = None;
+        for bench in suite.benchmarks.values_mut() {
+            bench.warmup_pexecs = None;
+        }
+    }
+}
+
+/// How each of `keys` fared in `results`, in the order of `keys`.
+pub(crate) fn outcomes<'a>(
+    keys: &'a [String],
+    results: &'a ResultFile,
+) -> Vec<(&'a str, Outcome<'a>)> {
+    keys.iter()
+        .map(|k| {
+            let outcome = match (results.failures.get(k), results.data.get(k)) {
+                (Some(f), _) => Outcome::Fail(f),
+                (None, Some(samples)) if !samples.is_empty() => Outcome::Pass(samples[0]),
+                _ => Outcome::NotRun,
+            };
+            (k.as_str(), outcome)
+        })
+        .collect()
+}
+
+impl App {
+    /// Run every benchmark once with `iters` in-process iterations, reporting which failed, and
+    /// exiting non-zero if any did. The results are only stored as a datum if `save`.
+    pub(crate) fn cmd_smoke(&self, iters: usize, save: bool, skip_build: bool) {
+        let mut config = self.load_config();
+        configure(&mut config, iters);
+        for (name, path) in config.missing_executors(skip_build) {
+            if config.executors[&name].allow_missing {
+                println!(
+                    "WARNING: skipping executor {name}: {} does not exist",
+                    path.display()
+                );
+                config.executors.remove(&name);
+            }
+        }
+        let keys = runner::planned_pexecs(&config, &runner::RunOptions::default())
+            .into_iter()
+            .map(|(k, _)| k)
+            .collect::<Vec<_>>();
+        println!(
+            "haste: smoke-testing {} benchmarks with {iters} in-process iterations each",
+            keys.len()
+        );
+        let results = if save {
+            let opts = BenchOptions {
+                comment: Some("smoke run".to_owned()),
+                skip_build,
+                keep_going: true,
+                quiet: true,
+                smoke: true,
+                ..Default::default()
+            };
+            let id = self.bench(&mut config, &opts);
+            self.load_results(id)
+        } else {
+            self.smoke(&config, skip_build)
+        };
+
+        let mut table = new_table(crate::colour_enabled());
+        table.set_header(vec!["Benchmark", "Time", "Status"]);
+        let mut failed = Vec::new();
+        let outcomes = outcomes(&keys, &results);
+        for (k, outcome) in &outcomes {
+            let (time, status) = match outcome {
+                Outcome::Pass(ms) => (
+                    units::format_duration(Duration::from_secs_f64(ms / 1000.)),
+                    Cell::new("pass").fg(Color::Green),
+                ),
+                Outcome::Fail(f) => {
+                    failed.push((k, Some(f)));
+                    (
+                        String::new(),
+                        Cell::new(format!("FAIL ({})", f.status)).fg(Color::Red),
+                    )
+                }
+                Outcome::NotRun => {
+                    failed.push((k, None));
+                    (String::new(), Cell::new("not run").fg(Color::Red))
+                }
+            };
+            table.add_row(vec![Cell::new(k), Cell::new(time), status]);
+        }
+        println!("\n{table}");
+        for (k, f) in &failed {
+            let Some(f) = f else { continue };
+            println!("\n{k} failed with exit status {}:", f.status);
+            for line in f.stderr.lines() {
+                println!("  | {line}");
+            }
+        }
+        println!(
+            "\nhaste: {} pass, {} fail",
+            outcomes.len() - failed.len(),
+            failed.len()
+        );
+        if !failed.is_empty() {
+            process::exit(1);
+        }
+    }
+
+    /// Run `config`, which [configure] has made quick, without storing the results.
+    fn smoke(&self, config: &Config, skip_build: bool) -> ResultFile {
+        if let Err(e) = runner::check_keys(config) {
+            eprintln!("error: {e}");
+            process::exit(1);
+        }
+        let missing = config.missing_executors(skip_build);
+        if !missing.is_empty() {
+            eprintln!("error: executor binaries not found:");
+            for (name, path) in &missing {
+                eprintln!("  {name}: {}", path.display());
+            }
+            process::exit(1);
+        }
+        if !skip_build && let Err(e) = runner::build_executors(config) {
+            eprintln!("error: {e}");
+            process::exit(1);
+        }
+        let run_opts = runner::RunOptions {
+            keep_going: true,
+            seed: self.seed,
+            ..Default::default()
+        };
+        runner::run(config, &run_opts).results
+    }
+}
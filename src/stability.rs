@@ -0,0 +1,201 @@
+//! Ranking datums by how noisy their results are, so that the quietest of several machines
+//! running the same benchmarks can be found.
+
+use crate::{App, DatumRef, ExtraToml, OutputFormat, ResultFile, csv_field, stats};
+use comfy_table::{Cell, CellAlignment, Table};
+use std::collections::BTreeSet;
+use std::process;
+
+/// How noisy one datum's results are.
+pub(crate) struct Stability {
+    pub(crate) name: String,
+    /// The hosts the datum's executors ran on, if recorded.
+    pub(crate) hosts: Vec<String>,
+    /// The number of benchmarks with a coefficient of variation.
+    pub(crate) benchmarks: usize,
+    /// The median of the benchmarks' coefficients of variation.
+    pub(crate) median_cv: Option<f64>,
+    /// The mean of the benchmarks' coefficients of variation, which unlike the median is pulled
+    /// up by a few very noisy benchmarks.
+    pub(crate) score: Option<f64>,
+    /// The benchmark with the largest coefficient of variation, and that coefficient.
+    pub(crate) noisiest: Option<(String, f64)>,
+}
+
+/// The coefficient of variation (the standard deviation relative to the mean) of `samples`, or
+/// `None` if there are too few samples or the mean is zero.
+fn cv(samples: &[f64]) -> Option<f64> {
+    let mean = stats::mean(samples);
+    (mean != 0.).then_some(stats::stddev(samples)? / mean.abs())
+}
+
+/// How noisy the results of the benchmarks `keys` in the datum called `name` are.
+pub(crate) fn stability(
+    name: String,
+    results: &ResultFile,
+    extra: &ExtraToml,
+    keys: &BTreeSet<&str>,
+) -> Stability {
+    let mut cvs = keys
+        .iter()
+        .filter_map(|k| Some((*k, cv(results.data.get(*k)?)?)))
+        .collect::<Vec<_>>();
+    cvs.sort_by(|(k1, c1), (k2, c2)| c1.total_cmp(c2).then(k1.cmp(k2)));
+    let values = cvs.iter().map(|(_, c)| *c).collect::<Vec<_>>();
+    let mut hosts = extra.hosts.values().cloned().collect::<Vec<_>>();
+    hosts.sort();
+    hosts.dedup();
+    Stability {
+        name,
+        hosts,
+        benchmarks: cvs.len(),
+        median_cv: (!values.is_empty()).then(|| stats::quantile(&values, 0.5)),
+        score: (!values.is_empty()).then(|| stats::mean(&values)),
+        noisiest: cvs.last().map(|(k, c)| ((*k).to_owned(), *c)),
+    }
+}
+
+/// The benchmarks in `results` which didn't fail.
+fn usable(results: &ResultFile) -> BTreeSet<&str> {
+    results
+        .data
+        .keys()
+        .filter(|k| !results.failures.contains_key(*k))
+        .map(String::as_str)
+        .collect()
+}
+
+/// Sort `datums` from the quietest to the noisiest, with those that have no score last.
+pub(crate) fn rank(datums: &mut [Stability]) {
+    datums.sort_by(|a, b| match (a.score, b.score) {
+        (Some(s1), Some(s2)) => s1.total_cmp(&s2),
+        (s1, s2) => s2.is_some().cmp(&s1.is_some()),
+    });
+}
+
+impl App {
+    /// Rank the datums `refs` by the noisiness of `metric` in the benchmarks they all have.
+    pub(crate) fn cmd_stability(&self, refs: &[DatumRef], metric: &str, format: OutputFormat) {
+        let qualify = refs.iter().any(|r| r.state_dir.is_some());
+        let mut datums = Vec::new();
+        for r in refs {
+            let name = r.name(qualify);
+            let loaded = self
+                .load_ref(r)
+                .and_then(|(results, extra)| Ok((results.for_metric(metric)?, extra)));
+            match loaded {
+                Ok((results, extra)) => datums.push((name, results, extra)),
+                Err(e) => {
+                    eprintln!("error: {name}: {e}");
+                    process::exit(1);
+                }
+            }
+        }
+        // Comparing different benchmarks would say more about the benchmarks than the machines.
+        let all = datums
+            .iter()
+            .flat_map(|(_, results, _)| usable(results))
+            .collect::<BTreeSet<_>>();
+        let common = datums
+            .iter()
+            .map(|(_, results, _)| usable(results))
+            .reduce(|a, b| a.intersection(&b).copied().collect())
+            .unwrap_or_default();
+        if common.is_empty() {
+            eprintln!("error: no benchmark succeeded in every datum");
+            process::exit(1);
+        }
+        let mut ranked = datums
+            .iter()
+            .map(|(name, results, extra)| stability(name.clone(), results, extra, &common))
+            .collect::<Vec<_>>();
+        rank(&mut ranked);
+
+        let percent =
+            |cv: Option<f64>| cv.map_or("n/a".to_owned(), |c| format!("{:.2}%", c * 100.));
+        match format {
+            OutputFormat::Table => {
+                let skipped = all.difference(&common).copied().collect::<Vec<_>>();
+                if !skipped.is_empty() {
+                    println!("not in every datum, so ignored: {}\n", skipped.join(", "));
+                }
+                let mut table = Table::new();
+                table.load_preset(comfy_table::presets::NOTHING);
+                let right = |s: String| Cell::new(s).set_alignment(CellAlignment::Right);
+                table.set_header(vec![
+                    Cell::new("Rank"),
+                    Cell::new("Datum"),
+                    Cell::new("Hosts"),
+                    right("Benchmarks".to_owned()),
+                    right("Median CV".to_owned()),
+                    right("Noise score".to_owned()),
+                    Cell::new("Noisiest benchmark"),
+                ]);
+                for (i, s) in ranked.iter().enumerate() {
+                    let hosts = if s.hosts.is_empty() {
+                        "unknown".to_owned()
+                    } else {
+                        s.hosts.join(", ")
+                    };
+                    let noisiest = s.noisiest.as_ref().map_or(String::new(), |(k, c)| {
+                        format!("{k} ({})", percent(Some(*c)))
+                    });
+                    table.add_row(vec![
+                        Cell::new(i + 1),
+                        Cell::new(&s.name),
+                        Cell::new(hosts),
+                        right(s.benchmarks.to_string()),
+                        right(percent(s.median_cv)),
+                        right(percent(s.score)),
+                        Cell::new(noisiest),
+                    ]);
+                }
+                println!("{table}");
+                println!(
+                    "\nCV = coefficient of variation (stddev / mean) of each benchmark's {metric}; \
+                     noise score = the mean CV, lower is quieter"
+                );
+            }
+            OutputFormat::Csv => {
+                println!("rank,datum,hosts,benchmarks,median_cv,score,noisiest,noisiest_cv");
+                let opt = |v: Option<f64>| v.map_or(String::new(), |v| v.to_string());
+                for (i, s) in ranked.iter().enumerate() {
+                    let (noisiest, noisiest_cv) = s
+                        .noisiest
+                        .as_ref()
+                        .map_or((String::new(), String::new()), |(k, c)| {
+                            (csv_field(k), c.to_string())
+                        });
+                    println!(
+                        "{},{},{},{},{},{},{noisiest},{noisiest_cv}",
+                        i + 1,
+                        csv_field(&s.name),
+                        csv_field(&s.hosts.join(" ")),
+                        s.benchmarks,
+                        opt(s.median_cv),
+                        opt(s.score),
+                    );
+                }
+            }
+            OutputFormat::Json => {
+                let rows = ranked
+                    .iter()
+                    .enumerate()
+                    .map(|(i, s)| {
+                        serde_json::json!({
+                            "rank": i + 1,
+                            "datum": s.name,
+                            "hosts": s.hosts,
+                            "benchmarks": s.benchmarks,
+                            "median_cv": s.median_cv,
+                            "score": s.score,
+                            "noisiest": s.noisiest.as_ref().map(|(k, _)| k),
+                            "noisiest_cv": s.noisiest.as_ref().map(|(_, c)| c),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                println!("{}", serde_json::Value::Array(rows));
+            }
+        }
+    }
+}
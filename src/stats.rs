@@ -0,0 +1,267 @@
+//! Statistics of samples.
+
+use rand::Rng;
+
+/// The number of resamples drawn to bootstrap a confidence interval.
+pub(crate) const BOOTSTRAP_RESAMPLES: usize = 1000;
+
+/// The arithmetic mean of the non-empty `samples`.
+pub(crate) fn mean(samples: &[f64]) -> f64 {
+    samples.iter().sum::<f64>() / samples.len() as f64
+}
+
+/// The geometric mean of the non-empty, positive `samples`.
+pub(crate) fn geomean(samples: &[f64]) -> f64 {
+    (samples.iter().map(|x| x.ln()).sum::<f64>() / samples.len() as f64).exp()
+}
+
+/// The sample variance of `samples`, or `None` if there are fewer than two.
+pub(crate) fn variance(samples: &[f64]) -> Option<f64> {
+    if samples.len() < 2 {
+        return None;
+    }
+    let mean = mean(samples);
+    let n = samples.len() as f64;
+    Some(samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.))
+}
+
+/// The sample standard deviation of `samples`, or `None` if there are fewer than two.
+pub(crate) fn stddev(samples: &[f64]) -> Option<f64> {
+    variance(samples).map(f64::sqrt)
+}
+
+/// The confidence interval of the mean of some samples, with the values it's derived from.
+#[derive(Debug, PartialEq)]
+pub(crate) struct MeanInterval {
+    pub(crate) n: usize,
+    pub(crate) mean: f64,
+    /// The sample standard deviation, if there are at least two samples.
+    pub(crate) stddev: Option<f64>,
+    /// The standard error of the mean, `stddev / √n`, if there's a standard deviation.
+    pub(crate) stderr: Option<f64>,
+    /// The critical value that the standard error is multiplied by.
+    pub(crate) critical: f64,
+    /// The interval's half-width: the mean is `mean ± half_width`. There's no deviation with a
+    /// single sample, so its interval is `± 0`.
+    pub(crate) half_width: f64,
+}
+
+/// The confidence interval of the mean of `samples` whose half-width is `critical` standard
+/// errors.
+pub(crate) fn mean_interval(samples: &[f64], critical: f64) -> MeanInterval {
+    let n = f64::from(u32::try_from(samples.len()).unwrap());
+    let stddev = stddev(samples);
+    MeanInterval {
+        n: samples.len(),
+        mean: mean(samples),
+        stddev,
+        stderr: stddev.map(|sd| sd / n.sqrt()),
+        critical,
+        half_width: stddev.map_or(0., |sd| critical * sd / n.sqrt()),
+    }
+}
+
+/// The adjusted Fisher-Pearson skewness of `samples`, or `None` if there are fewer than three or
+/// they are all the same.
+pub(crate) fn skewness(samples: &[f64]) -> Option<f64> {
+    if samples.len() < 3 {
+        return None;
+    }
+    let n = samples.len() as f64;
+    let mean = mean(samples);
+    let m2 = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+    let m3 = samples.iter().map(|x| (x - mean).powi(3)).sum::<f64>() / n;
+    if m2 == 0. {
+        return None;
+    }
+    Some(m3 / m2.powf(1.5) * (n * (n - 1.)).sqrt() / (n - 2.))
+}
+
+/// A sorted copy of `samples`.
+pub(crate) fn sorted(samples: &[f64]) -> Vec<f64> {
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    sorted
+}
+
+/// A sorted copy of `samples` with `percent`% of them (rounded down) removed from each end.
+pub(crate) fn trim(samples: &[f64], percent: f64) -> Vec<f64> {
+    let sorted = sorted(samples);
+    let cut = (sorted.len() as f64 * percent / 100.).floor() as usize;
+    sorted[cut.min(sorted.len())..sorted.len().saturating_sub(cut)].to_vec()
+}
+
+/// The `p`th quantile (0 <= `p` <= 1) of the non-empty, sorted `sorted`, interpolating linearly
+/// between samples.
+pub(crate) fn quantile(sorted: &[f64], p: f64) -> f64 {
+    let pos = p * (sorted.len() - 1) as f64;
+    let lo = pos.floor() as usize;
+    let hi = pos.ceil() as usize;
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f64)
+}
+
+/// The fewest samples from which the `p`th quantile (0 < `p` < 1) can be estimated: enough that
+/// at least one sample is expected on either side of it.
+pub(crate) fn quantile_min_samples(p: f64) -> usize {
+    (1. / p.min(1. - p) - 1e-9).ceil() as usize
+}
+
+/// The half-width of a percentile bootstrap confidence interval, at `confidence`%, around the
+/// `p`th quantile of the non-empty, sorted `sorted`, drawing resamples with `rng`.
+///
+/// The bootstrap interval needn't be symmetric around the quantile, so its wider side is taken.
+pub(crate) fn bootstrap_quantile_ci(
+    sorted: &[f64],
+    p: f64,
+    confidence: f64,
+    rng: &mut impl Rng,
+) -> f64 {
+    let estimate = quantile(sorted, p);
+    let mut resample = vec![0.; sorted.len()];
+    let mut estimates = (0..BOOTSTRAP_RESAMPLES)
+        .map(|_| {
+            for x in &mut resample {
+                *x = sorted[rng.random_range(0..sorted.len())];
+            }
+            resample.sort_by(f64::total_cmp);
+            quantile(&resample, p)
+        })
+        .collect::<Vec<_>>();
+    estimates.sort_by(f64::total_cmp);
+    let alpha = (1. - confidence / 100.) / 2.;
+    (estimate - quantile(&estimates, alpha)).max(quantile(&estimates, 1. - alpha) - estimate)
+}
+
+/// Descriptive statistics of a non-empty set of samples.
+///
+/// Statistics which need more samples than there are are `None`.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Descriptive {
+    pub(crate) n: usize,
+    pub(crate) mean: f64,
+    pub(crate) stddev: Option<f64>,
+    pub(crate) variance: Option<f64>,
+    pub(crate) min: f64,
+    /// The first quartile.
+    pub(crate) q1: f64,
+    pub(crate) median: f64,
+    /// The third quartile.
+    pub(crate) q3: f64,
+    pub(crate) max: f64,
+    pub(crate) skewness: Option<f64>,
+}
+
+impl Descriptive {
+    pub(crate) fn from_samples(samples: &[f64]) -> Self {
+        assert!(!samples.is_empty());
+        let sorted = sorted(samples);
+        Descriptive {
+            n: samples.len(),
+            mean: mean(samples),
+            stddev: stddev(samples),
+            variance: variance(samples),
+            min: sorted[0],
+            q1: quantile(&sorted, 0.25),
+            median: quantile(&sorted, 0.5),
+            q3: quantile(&sorted, 0.75),
+            max: sorted[sorted.len() - 1],
+            skewness: skewness(samples),
+        }
+    }
+
+    /// The interquartile range.
+    pub(crate) fn iqr(&self) -> f64 {
+        self.q3 - self.q1
+    }
+}
+
+/// Perform a two-sample Kolmogorov-Smirnov test on the non-empty `a` and `b`, returning the KS
+/// statistic (the largest difference between their empirical distribution functions) and the
+/// approximate p-value of the samples coming from the same distribution.
+pub(crate) fn ks_test(a: &[f64], b: &[f64]) -> (f64, f64) {
+    let a = sorted(a);
+    let b = sorted(b);
+    let (n1, n2) = (a.len() as f64, b.len() as f64);
+    let (mut i, mut j) = (0, 0);
+    let mut d = 0f64;
+    while i < a.len() && j < b.len() {
+        // Step past every sample equal to the smallest remaining one, so that ties move both
+        // distribution functions at once.
+        let x = a[i].min(b[j]);
+        while i < a.len() && a[i] <= x {
+            i += 1;
+        }
+        while j < b.len() && b[j] <= x {
+            j += 1;
+        }
+        d = d.max((i as f64 / n1 - j as f64 / n2).abs());
+    }
+    // The asymptotic distribution, with the small sample correction from Numerical Recipes.
+    let en = (n1 * n2 / (n1 + n2)).sqrt();
+    (d, kolmogorov_q((en + 0.12 + 0.11 / en) * d))
+}
+
+/// The complementary cumulative distribution function of the Kolmogorov distribution.
+fn kolmogorov_q(lambda: f64) -> f64 {
+    // The series converges too slowly to be worth summing for small `lambda`, where it is 1 to
+    // many decimal places anyway.
+    if lambda < 0.2 {
+        return 1.;
+    }
+    let mut sum = 0.;
+    let mut sign = 1.;
+    for j in 1..=100 {
+        let term = (-2. * f64::from(j * j) * lambda * lambda).exp();
+        sum += sign * term;
+        if term < 1e-12 {
+            break;
+        }
+        sign = -sign;
+    }
+    (2. * sum).clamp(0., 1.)
+}
+
+/// The fewest samples in which bimodality is looked for.
+const BIMODAL_MIN_SAMPLES: usize = 10;
+/// How many times wider than the spread of the clusters on either side a gap must be to split
+/// them.
+const BIMODAL_GAP_FACTOR: f64 = 2.;
+/// The smallest gap, relative to the median, which can split clusters, so that identical or
+/// coarsely quantised samples (with no spread) aren't split by the timer's resolution.
+const BIMODAL_MIN_RELATIVE_GAP: f64 = 0.01;
+
+/// One of the clusters of a bimodal set of samples.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Cluster {
+    pub(crate) mean: f64,
+    pub(crate) n: usize,
+}
+
+/// The two clusters which `samples` fall into, lowest first, if they clearly do.
+///
+/// This is a cheap heuristic rather than a test: the samples are split at the widest gap between
+/// neighbouring sorted samples which leaves at least a tenth of them (and no fewer than 2) on each
+/// side, and are bimodal if that gap is much wider than the interquartile range of the samples on
+/// either side of it.
+pub(crate) fn bimodal(samples: &[f64]) -> Option<[Cluster; 2]> {
+    if samples.len() < BIMODAL_MIN_SAMPLES {
+        return None;
+    }
+    let sorted = sorted(samples);
+    let least = (sorted.len() / 10).max(2);
+    let (split, gap) = (least..=sorted.len() - least)
+        .map(|i| (i, sorted[i] - sorted[i - 1]))
+        .max_by(|(_, g1), (_, g2)| g1.total_cmp(g2))?;
+    let (lo, hi) = sorted.split_at(split);
+    let iqr = |s: &[f64]| quantile(s, 0.75) - quantile(s, 0.25);
+    let spread = iqr(lo).max(iqr(hi));
+    let median = quantile(&sorted, 0.5).abs();
+    if gap <= BIMODAL_GAP_FACTOR * spread || gap <= BIMODAL_MIN_RELATIVE_GAP * median {
+        return None;
+    }
+    let cluster = |s: &[f64]| Cluster {
+        mean: mean(s),
+        n: s.len(),
+    };
+    Some([cluster(lo), cluster(hi)])
+}
@@ -0,0 +1,670 @@
+//! Persistent storage of datums.
+//!
+//! There are two backends: the default stores each datum as a directory of TOML files, while the
+//! SQLite backend keeps everything in a single database file.
+
+use crate::config::Storage;
+use crate::{ExtraToml, Failure, KeyParams, ResultFile, TIME_METRIC, compact};
+use clap::ValueEnum;
+use rusqlite::{Connection, OptionalExtension, Transaction, TransactionBehavior, params};
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// The name of the SQLite database inside the state directory.
+pub(crate) const SQLITE_DB: &str = "haste.db";
+
+/// The available storage backends.
+#[derive(Copy, Clone, Debug, PartialEq, ValueEnum)]
+pub(crate) enum StoreKind {
+    /// One directory of TOML files per datum.
+    Fs,
+    /// A single SQLite database.
+    Sqlite,
+}
+
+/// A collection of datums.
+///
+/// IDs need not be contiguous, since datums can be deleted, but are never reused: a new datum
+/// always gets a higher ID than any datum stored before it, even if that datum has since been
+/// deleted.
+pub(crate) trait Store {
+    /// The IDs of all the datums in the store, in ascending order.
+    fn ids(&self) -> Vec<usize>;
+    /// Is there a datum with ID `id`?
+    fn contains(&self, id: usize) -> bool {
+        self.ids().contains(&id)
+    }
+    /// Store a new datum and return its ID.
+    ///
+    /// The first ID issued is zero. Allocating the ID is atomic, so concurrent runs of haste get
+    /// different IDs.
+    fn store(&self, results: &ResultFile, extra: &ExtraToml) -> usize;
+    /// Store a datum with a specific ID, which must not already be in use.
+    fn insert(&self, id: usize, results: &ResultFile, extra: &ExtraToml);
+    /// Replace the metadata of datum `id`. A concurrent update may be lost, but the metadata is
+    /// never left partially written.
+    fn update_extra(&self, id: usize, extra: &ExtraToml) -> Result<(), String>;
+    /// Delete datum `id`.
+    fn delete(&self, id: usize) -> Result<(), String>;
+    /// Load the results of datum `id`, failing if they're unreadable.
+    fn try_load_results(&self, id: usize) -> Result<ResultFile, String>;
+    /// Load the metadata of datum `id`, failing if it's unreadable.
+    fn try_load_extra(&self, id: usize) -> Result<ExtraToml, String>;
+    /// Does datum `id` have metadata? Without it, [Store::try_load_extra] gives the default.
+    fn has_extra(&self, _id: usize) -> bool {
+        true
+    }
+    fn load_results(&self, id: usize) -> ResultFile {
+        self.try_load_results(id).unwrap_or_else(|e| panic!("{e}"))
+    }
+    /// Move datum `id` out of the way, so that it's no longer in the store but can still be
+    /// examined, returning where it went.
+    fn quarantine(&self, id: usize) -> Result<String, String>;
+    /// Set how the samples of datums stored from now on are encoded, where `None` chooses by
+    /// their size. Only the filesystem store has a choice.
+    fn set_storage(&self, _storage: Option<Storage>) {}
+    /// Re-encode the samples of datum `id` compactly, returning their size in bytes before and
+    /// after, or `None` if they already were.
+    fn compact(&self, id: usize) -> Result<Option<(u64, u64)>, String>;
+    /// Where the run log of datum `id` is kept by default. The file may not exist.
+    fn log_path(&self, id: usize) -> PathBuf;
+    /// A cheaply computed string which changes if the results of datum `id` change.
+    fn fingerprint(&self, id: usize) -> String;
+}
+
+/// Open the store of kind `kind` in `state_dir`.
+pub(crate) fn open(kind: StoreKind, state_dir: PathBuf) -> Box<dyn Store> {
+    match kind {
+        StoreKind::Fs => Box::new(FsStore {
+            state_dir,
+            storage: Cell::new(None),
+        }),
+        StoreKind::Sqlite => Box::new(SqliteStore::open(state_dir.join(SQLITE_DB))),
+    }
+}
+
+/// TOML results larger than this are stored compactly, unless the config says otherwise.
+const COMPACT_THRESHOLD: usize = 4 << 20;
+
+/// Stores each datum in a numbered directory containing `extra.toml` and either `data.toml` or,
+/// if the results are stored compactly, `data.bin`.
+///
+/// A datum is written into a staging directory, named [STAGING_PREFIX] followed by its ID, which
+/// is renamed to the numbered directory once complete. Readers therefore never see a datum that's
+/// partially written, such as while `haste bench` is storing one, and ignore staging directories,
+/// including those left behind by a run that was interrupted.
+pub(crate) struct FsStore {
+    state_dir: PathBuf,
+    /// How to encode the results of new datums.
+    storage: Cell<Option<Storage>>,
+}
+
+impl FsStore {
+    fn get_datum_dir(&self, id: usize) -> PathBuf {
+        self.state_dir.join(id.to_string())
+    }
+
+    fn get_staging_dir(&self, id: usize) -> PathBuf {
+        self.state_dir.join(format!("{STAGING_PREFIX}{id}"))
+    }
+
+    fn get_datum_toml_path(&self, id: usize) -> PathBuf {
+        self.get_datum_dir(id).join("data.toml")
+    }
+
+    fn get_datum_compact_path(&self, id: usize) -> PathBuf {
+        self.get_datum_dir(id).join("data.bin")
+    }
+
+    /// The file holding the results of datum `id`, in whichever encoding they're stored.
+    fn get_datum_results_path(&self, id: usize) -> PathBuf {
+        let compact = self.get_datum_compact_path(id);
+        if compact.exists() {
+            compact
+        } else {
+            self.get_datum_toml_path(id)
+        }
+    }
+
+    fn get_datum_extra_path(&self, id: usize) -> PathBuf {
+        self.get_datum_dir(id).join("extra.toml")
+    }
+
+    /// The highest ID ever allocated, which may no longer exist.
+    fn high_water(&self) -> Option<usize> {
+        fs::read_to_string(self.state_dir.join(HIGH_WATER))
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+    }
+
+    fn record_id(&self, id: usize) {
+        if self.high_water().is_some_and(|hw| hw >= id) {
+            return;
+        }
+        // Write then rename, so that a concurrent reader never sees a partial file.
+        let tmp = self
+            .state_dir
+            .join(format!("{HIGH_WATER}.{}", std::process::id()));
+        fs::write(&tmp, format!("{id}\n")).unwrap();
+        fs::rename(&tmp, self.state_dir.join(HIGH_WATER)).unwrap();
+    }
+
+    /// Write datum `id` into its staging directory, which the caller has created, then move it
+    /// into place.
+    fn write_datum(&self, id: usize, results: &ResultFile, extra: &ExtraToml) {
+        let staging = self.get_staging_dir(id);
+        let storage = self.storage.get();
+        let compact = staging.join("data.bin");
+        if storage == Some(Storage::Compact) {
+            write_compact(&compact, results);
+        } else {
+            let tml = toml::to_string(results).unwrap();
+            if storage.is_none() && tml.len() > COMPACT_THRESHOLD {
+                write_compact(&compact, results);
+            } else {
+                fs::write(staging.join("data.toml"), tml).unwrap();
+            }
+        }
+
+        // Write out the extra metadata.
+        // FIXME: consider merging this into the main toml file.
+        let extra_data = toml::to_string(extra).unwrap();
+        fs::write(staging.join("extra.toml"), extra_data).unwrap();
+        let dir = self.get_datum_dir(id);
+        fs::rename(&staging, &dir).unwrap_or_else(|e| {
+            panic!(
+                "failed to move {} to {}: {e}",
+                staging.display(),
+                dir.display()
+            )
+        });
+        self.record_id(id);
+    }
+}
+
+/// Write the compact encoding of `results` to `path`, via a temporary file so that the results
+/// are never missing or partial.
+fn write_compact(path: &Path, results: &ResultFile) {
+    let tmp = path.with_extension(format!("bin.{}", std::process::id()));
+    fs::write(&tmp, compact::encode(results)).unwrap();
+    fs::rename(&tmp, path).unwrap();
+}
+
+/// The prefix of the name of the directory in which [FsStore] writes a datum before moving it into
+/// place.
+const STAGING_PREFIX: &str = ".tmp-";
+
+/// Is `name`, in the state directory, the name of a directory in which a datum is being written?
+fn is_staging(name: &str) -> bool {
+    name.strip_prefix(STAGING_PREFIX)
+        .is_some_and(|id| id.parse::<usize>().is_ok())
+}
+
+/// The file in the state directory recording the highest datum ID allocated by [FsStore], so
+/// that IDs aren't reused after deletions.
+const HIGH_WATER: &str = "last_id";
+
+impl Store for FsStore {
+    /// There are no datums if the state directory doesn't exist yet.
+    fn ids(&self) -> Vec<usize> {
+        let mut ids = Vec::new();
+        let entries = match fs::read_dir(&self.state_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return ids,
+            Err(e) => panic!("failed to read {}: {e}", self.state_dir.display()),
+        };
+        for ent in entries {
+            let ent = ent.unwrap();
+            let name = ent.file_name();
+            let name = name.to_str().unwrap();
+            // A datum that's still being written isn't in the store yet.
+            if is_staging(name) {
+                continue;
+            }
+            if let Ok(id) = name.parse::<usize>() {
+                ids.push(id);
+            }
+        }
+        ids.sort();
+        ids
+    }
+
+    fn store(&self, results: &ResultFile, extra: &ExtraToml) -> usize {
+        let mut id = self
+            .ids()
+            .last()
+            .copied()
+            .max(self.high_water())
+            .map_or(0, |x| x + 1);
+        // Creating the staging directory claims the ID. If another process got there first, or
+        // has already moved its datum into place, try the next.
+        loop {
+            let staging = self.get_staging_dir(id);
+            match fs::create_dir(&staging) {
+                Ok(()) if self.get_datum_dir(id).exists() => {
+                    fs::remove_dir(&staging).unwrap();
+                    id += 1;
+                }
+                Ok(()) => break,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => id += 1,
+                Err(e) => panic!("failed to create {}: {e}", staging.display()),
+            }
+        }
+        self.write_datum(id, results, extra);
+        id
+    }
+
+    fn insert(&self, id: usize, results: &ResultFile, extra: &ExtraToml) {
+        assert!(
+            !self.get_datum_dir(id).exists(),
+            "datum {id} already exists"
+        );
+        fs::create_dir(self.get_staging_dir(id)).unwrap();
+        self.write_datum(id, results, extra);
+    }
+
+    fn update_extra(&self, id: usize, extra: &ExtraToml) -> Result<(), String> {
+        if !self.contains(id) {
+            return Err(format!("no such datum {id}"));
+        }
+        // Write then rename, so that a concurrent reader never sees a partial file.
+        let path = self.get_datum_extra_path(id);
+        let tmp = path.with_extension(format!("toml.{}", std::process::id()));
+        fs::write(&tmp, toml::to_string(extra).unwrap())
+            .and_then(|_| fs::rename(&tmp, &path))
+            .map_err(|e| format!("failed to write {}: {e}", path.display()))
+    }
+
+    fn delete(&self, id: usize) -> Result<(), String> {
+        if !self.contains(id) {
+            return Err(format!("no such datum {id}"));
+        }
+        fs::remove_dir_all(self.get_datum_dir(id))
+            .map_err(|e| format!("failed to delete datum {id}: {e}"))
+    }
+
+    fn try_load_results(&self, id: usize) -> Result<ResultFile, String> {
+        let path = self.get_datum_results_path(id);
+        let bytes =
+            fs::read(&path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        if compact::is_compact(&bytes) {
+            return compact::decode(&bytes).map_err(|e| format!("{}: {e}", path.display()));
+        }
+        let tml = String::from_utf8(bytes)
+            .map_err(|_| format!("{}: neither TOML nor compact results", path.display()))?;
+        toml::from_str::<ResultFile>(&tml).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    /// Datums made by older versions of haste, or copied without it, may have no `extra.toml`,
+    /// and so no metadata.
+    fn try_load_extra(&self, id: usize) -> Result<ExtraToml, String> {
+        let path = self.get_datum_extra_path(id);
+        match fs::read_to_string(&path) {
+            Ok(data) => toml::from_str(&data).map_err(|e| format!("{}: {e}", path.display())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(ExtraToml::default()),
+            Err(e) => Err(format!("failed to read {}: {e}", path.display())),
+        }
+    }
+
+    fn has_extra(&self, id: usize) -> bool {
+        self.get_datum_extra_path(id).exists()
+    }
+
+    fn set_storage(&self, storage: Option<Storage>) {
+        self.storage.set(storage);
+    }
+
+    fn compact(&self, id: usize) -> Result<Option<(u64, u64)>, String> {
+        let toml_path = self.get_datum_toml_path(id);
+        if self.get_datum_compact_path(id).exists() {
+            return Ok(None);
+        }
+        let before = fs::metadata(&toml_path)
+            .map_err(|e| format!("failed to read {}: {e}", toml_path.display()))?
+            .len();
+        let results = self.try_load_results(id)?;
+        write_compact(&self.get_datum_compact_path(id), &results);
+        // The compact results take precedence, so removing the TOML can't lose anything.
+        fs::remove_file(&toml_path)
+            .map_err(|e| format!("failed to remove {}: {e}", toml_path.display()))?;
+        let after = fs::metadata(self.get_datum_compact_path(id))
+            .map_err(|e| e.to_string())?
+            .len();
+        Ok(Some((before, after)))
+    }
+
+    /// Rename the datum's directory with a `.broken` suffix, which [FsStore::ids] ignores.
+    fn quarantine(&self, id: usize) -> Result<String, String> {
+        let dir = self.get_datum_dir(id);
+        let to = self.state_dir.join(format!("{id}.broken"));
+        if to.exists() {
+            return Err(format!("{} already exists", to.display()));
+        }
+        fs::rename(&dir, &to).map_err(|e| format!("failed to rename {}: {e}", dir.display()))?;
+        // Don't let the ID be reused.
+        self.record_id(id);
+        Ok(to.display().to_string())
+    }
+
+    fn log_path(&self, id: usize) -> PathBuf {
+        self.get_datum_dir(id).join("run.log")
+    }
+
+    /// The size and modification time of the results file.
+    fn fingerprint(&self, id: usize) -> String {
+        let Ok(meta) = fs::metadata(self.get_datum_results_path(id)) else {
+            return String::new();
+        };
+        let mtime = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_nanos());
+        format!("fs:{}:{mtime}", meta.len())
+    }
+}
+
+/// Stores all datums in one SQLite database.
+///
+/// Samples are stored one per row, while the extra metadata and per-benchmark parameters are
+/// stored serialised, so that adding fields to them doesn't require a schema change. Run logs are
+/// kept as files in a `logs` directory next to the database.
+pub(crate) struct SqliteStore {
+    conn: Connection,
+    log_dir: PathBuf,
+}
+
+impl SqliteStore {
+    fn open(path: PathBuf) -> Self {
+        let conn = Connection::open(&path).unwrap_or_else(|e| {
+            panic!("failed to open {}: {e}", path.display());
+        });
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS datums (
+                 id INTEGER PRIMARY KEY,
+                 extra TEXT NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS samples (
+                 datum INTEGER NOT NULL REFERENCES datums(id),
+                 metric TEXT NOT NULL,
+                 key TEXT NOT NULL,
+                 idx INTEGER NOT NULL,
+                 value REAL NOT NULL,
+                 PRIMARY KEY (datum, metric, key, idx)
+             );
+             CREATE TABLE IF NOT EXISTS timestamps (
+                 datum INTEGER NOT NULL REFERENCES datums(id),
+                 key TEXT NOT NULL,
+                 idx INTEGER NOT NULL,
+                 ms INTEGER NOT NULL,
+                 PRIMARY KEY (datum, key, idx)
+             );
+             CREATE TABLE IF NOT EXISTS high_water (
+                 id INTEGER NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS params (
+                 datum INTEGER NOT NULL REFERENCES datums(id),
+                 key TEXT NOT NULL,
+                 params TEXT NOT NULL,
+                 PRIMARY KEY (datum, key)
+             );
+             CREATE TABLE IF NOT EXISTS failures (
+                 datum INTEGER NOT NULL REFERENCES datums(id),
+                 key TEXT NOT NULL,
+                 failure TEXT NOT NULL,
+                 PRIMARY KEY (datum, key)
+             );",
+        )
+        .unwrap();
+        let log_dir = path.with_file_name("logs");
+        Self { conn, log_dir }
+    }
+}
+
+impl Store for SqliteStore {
+    fn ids(&self) -> Vec<usize> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id FROM datums ORDER BY id")
+            .unwrap();
+        stmt.query_map([], |row| row.get::<_, usize>(0))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    fn store(&self, results: &ResultFile, extra: &ExtraToml) -> usize {
+        // An immediate transaction takes the write lock up front, so that concurrent processes
+        // can't allocate the same ID.
+        let tx = Transaction::new_unchecked(&self.conn, TransactionBehavior::Immediate).unwrap();
+        let max_id = |table: &str| {
+            tx.query_row(&format!("SELECT MAX(id) FROM {table}"), [], |row| {
+                row.get::<_, Option<usize>>(0)
+            })
+            .unwrap()
+        };
+        let id = max_id("datums")
+            .max(max_id("high_water"))
+            .map_or(0, |x| x + 1);
+        insert_datum(&tx, id, results, extra);
+        tx.commit().unwrap();
+        id
+    }
+
+    fn insert(&self, id: usize, results: &ResultFile, extra: &ExtraToml) {
+        let tx = self.conn.unchecked_transaction().unwrap();
+        insert_datum(&tx, id, results, extra);
+        tx.commit().unwrap();
+    }
+
+    fn update_extra(&self, id: usize, extra: &ExtraToml) -> Result<(), String> {
+        let updated = self
+            .conn
+            .execute(
+                "UPDATE datums SET extra = ?2 WHERE id = ?1",
+                params![id, toml::to_string(extra).unwrap()],
+            )
+            .map_err(|e| format!("failed to update datum {id}: {e}"))?;
+        if updated == 0 {
+            return Err(format!("no such datum {id}"));
+        }
+        Ok(())
+    }
+
+    fn delete(&self, id: usize) -> Result<(), String> {
+        let tx = self.conn.unchecked_transaction().unwrap();
+        let found = tx
+            .query_row("SELECT id FROM datums WHERE id = ?1", [id], |_| Ok(()))
+            .optional()
+            .unwrap();
+        if found.is_none() {
+            return Err(format!("no such datum {id}"));
+        }
+        for table in ["samples", "timestamps", "params", "failures"] {
+            tx.execute(&format!("DELETE FROM {table} WHERE datum = ?1"), [id])
+                .unwrap();
+        }
+        tx.execute("DELETE FROM datums WHERE id = ?1", [id])
+            .unwrap();
+        tx.commit().unwrap();
+        Ok(())
+    }
+
+    fn try_load_results(&self, id: usize) -> Result<ResultFile, String> {
+        let db_err = |e: rusqlite::Error| format!("failed to load datum {id}: {e}");
+        let mut results = ResultFile::default();
+        let mut stmt = self
+            .conn
+            .prepare("SELECT metric, key, value FROM samples WHERE datum = ?1 ORDER BY idx")
+            .map_err(db_err)?;
+        let rows = stmt
+            .query_map([id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, f64>(2)?,
+                ))
+            })
+            .map_err(db_err)?;
+        for row in rows {
+            let (metric, key, v) = row.map_err(db_err)?;
+            let data = if metric == TIME_METRIC {
+                &mut results.data
+            } else {
+                results.metrics.entry(metric).or_default()
+            };
+            data.entry(key).or_default().push(v);
+        }
+        let mut stmt = self
+            .conn
+            .prepare("SELECT key, ms FROM timestamps WHERE datum = ?1 ORDER BY idx")
+            .map_err(db_err)?;
+        let rows = stmt
+            .query_map([id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?))
+            })
+            .map_err(db_err)?;
+        for row in rows {
+            let (key, ms) = row.map_err(db_err)?;
+            results.timestamps.entry(key).or_default().push(ms);
+        }
+        let mut stmt = self
+            .conn
+            .prepare("SELECT key, params FROM params WHERE datum = ?1")
+            .map_err(db_err)?;
+        results.params = stmt
+            .query_map([id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(db_err)?
+            .map(|row| {
+                let (key, p) = row.map_err(db_err)?;
+                let p = serde_json::from_str::<KeyParams>(&p)
+                    .map_err(|e| format!("datum {id}: params of {key}: {e}"))?;
+                Ok((key, p))
+            })
+            .collect::<Result<HashMap<_, _>, String>>()?;
+        let mut stmt = self
+            .conn
+            .prepare("SELECT key, failure FROM failures WHERE datum = ?1")
+            .map_err(db_err)?;
+        results.failures = stmt
+            .query_map([id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(db_err)?
+            .map(|row| {
+                let (key, f) = row.map_err(db_err)?;
+                let f = serde_json::from_str::<Failure>(&f)
+                    .map_err(|e| format!("datum {id}: failure of {key}: {e}"))?;
+                Ok((key, f))
+            })
+            .collect::<Result<HashMap<_, _>, String>>()?;
+        Ok(results)
+    }
+
+    fn try_load_extra(&self, id: usize) -> Result<ExtraToml, String> {
+        let extra = self
+            .conn
+            .query_row("SELECT extra FROM datums WHERE id = ?1", [id], |row| {
+                row.get::<_, String>(0)
+            })
+            .map_err(|e| format!("failed to load datum {id}: {e}"))?;
+        toml::from_str(&extra).map_err(|e| format!("datum {id}: extra metadata: {e}"))
+    }
+
+    /// Datums are written in a single transaction, so can't be left half-written, and there's
+    /// nowhere else in the database to move one to.
+    fn quarantine(&self, id: usize) -> Result<String, String> {
+        Err(format!(
+            "datum {id} can't be quarantined in the sqlite store: delete it with `haste rm`"
+        ))
+    }
+
+    fn compact(&self, _id: usize) -> Result<Option<(u64, u64)>, String> {
+        Err("the sqlite store has only one encoding".to_owned())
+    }
+
+    fn log_path(&self, id: usize) -> PathBuf {
+        self.log_dir.join(format!("{id}.log"))
+    }
+
+    /// The number and sum of the time samples. Datums aren't modified once stored, and IDs aren't
+    /// reused, so this only needs to catch datums being replaced behind haste's back.
+    fn fingerprint(&self, id: usize) -> String {
+        let (n, sum) = self
+            .conn
+            .query_row(
+                "SELECT COUNT(*), TOTAL(value) FROM samples WHERE datum = ?1 AND metric = ?2",
+                params![id, TIME_METRIC],
+                |row| Ok((row.get::<_, usize>(0)?, row.get::<_, f64>(1)?)),
+            )
+            .unwrap();
+        format!("sqlite:{n}:{sum}")
+    }
+}
+
+/// Add datum `id` to the database as part of `tx`.
+fn insert_datum(tx: &Transaction, id: usize, results: &ResultFile, extra: &ExtraToml) {
+    tx.execute(
+        "INSERT INTO datums (id, extra) VALUES (?1, ?2)",
+        params![id, toml::to_string(extra).unwrap()],
+    )
+    .unwrap();
+    let metrics = [(TIME_METRIC, &results.data)]
+        .into_iter()
+        .chain(results.metrics.iter().map(|(m, d)| (m.as_str(), d)));
+    {
+        let mut stmt = tx
+            .prepare(
+                "INSERT INTO samples (datum, metric, key, idx, value)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )
+            .unwrap();
+        for (metric, data) in metrics {
+            for (key, samples) in data {
+                for (idx, v) in samples.iter().enumerate() {
+                    stmt.execute(params![id, metric, key, idx, v]).unwrap();
+                }
+            }
+        }
+        let mut stmt = tx
+            .prepare("INSERT INTO timestamps (datum, key, idx, ms) VALUES (?1, ?2, ?3, ?4)")
+            .unwrap();
+        for (key, timestamps) in &results.timestamps {
+            for (idx, ms) in timestamps.iter().enumerate() {
+                stmt.execute(params![id, key, idx, ms]).unwrap();
+            }
+        }
+        let mut stmt = tx
+            .prepare("INSERT INTO params (datum, key, params) VALUES (?1, ?2, ?3)")
+            .unwrap();
+        for (key, p) in &results.params {
+            stmt.execute(params![id, key, serde_json::to_string(p).unwrap()])
+                .unwrap();
+        }
+        let mut stmt = tx
+            .prepare("INSERT INTO failures (datum, key, failure) VALUES (?1, ?2, ?3)")
+            .unwrap();
+        for (key, f) in &results.failures {
+            stmt.execute(params![id, key, serde_json::to_string(f).unwrap()])
+                .unwrap();
+        }
+    }
+    // Remember the highest ID, so that it isn't reused if the datum is deleted.
+    tx.execute(
+        "INSERT INTO high_water (id)
+         SELECT MAX(id) FROM (SELECT id FROM datums UNION ALL SELECT id FROM high_water)",
+        [],
+    )
+    .unwrap();
+    tx.execute(
+        "DELETE FROM high_water WHERE id < (SELECT MAX(id) FROM high_water)",
+        [],
+    )
+    .unwrap();
+}
@@ -0,0 +1,98 @@
+//! The summary of a freshly collected datum printed at the end of `haste bench`.
+
+use crate::{
+    BenchKey, ConfidenceLevel, NumFormat, ResultFile, TIME_METRIC, config::Config, human_durations,
+    mean_ci_formats, named_header, new_table, split_key,
+};
+use comfy_table::{Cell, CellAlignment, Table};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// The suite that each benchmark key in `config` belongs to.
+pub(crate) fn key_suites(config: &Config) -> HashMap<String, String> {
+    let mut suites = HashMap::new();
+    for executor_name in config.executors.keys() {
+        for (suite_name, suite) in &config.suites {
+            for (bench_name, bench) in &suite.benchmarks {
+                let key = BenchKey {
+                    benchmark: bench_name.into(),
+                    executor: executor_name.into(),
+                    extra_args: bench.extra_args.clone(),
+                };
+                suites.insert(key.to_string(), suite_name.clone());
+            }
+        }
+    }
+    suites
+}
+
+/// Make a table of the time results in `data`, grouped by suite and then benchmark, with a column
+/// per executor giving the mean, confidence interval, and the fastest and slowest samples.
+///
+/// `suites` gives the suite of each benchmark key: keys not in it are grouped under `?`.
+pub(crate) fn summary_table(
+    data: &ResultFile,
+    suites: &HashMap<String, String>,
+    confidence: ConfidenceLevel,
+    thousands: bool,
+) -> Table {
+    let summaries = data.summarise(confidence);
+    let human = human_durations(TIME_METRIC);
+    let (mean_fmt, ci_fmt) = mean_ci_formats(summaries.values(), thousands, human);
+    let samples = data.data.values().flatten().copied().collect::<Vec<_>>();
+    let mut extreme_fmt = NumFormat::new(&samples, 0, false, thousands);
+    if human {
+        extreme_fmt = extreme_fmt.human(&samples);
+    }
+
+    // suite -> benchmark -> executor -> key
+    let mut groups = BTreeMap::<&str, BTreeMap<String, HashMap<&str, &str>>>::new();
+    let mut executors = BTreeSet::new();
+    for k in summaries.keys() {
+        let Some((bench, exec)) = split_key(k) else {
+            continue;
+        };
+        let suite = suites.get(k).map_or("?", String::as_str);
+        groups
+            .entry(suite)
+            .or_default()
+            .entry(bench)
+            .or_default()
+            .insert(exec, k);
+        executors.insert(exec);
+    }
+
+    let mut table = new_table(true);
+    let mut header = vec![Cell::new("Suite"), Cell::new("Benchmark")];
+    header.extend(
+        executors
+            .iter()
+            .map(|e| Cell::new(named_header(e, TIME_METRIC)).set_alignment(CellAlignment::Right)),
+    );
+    table.set_header(header);
+    for (suite, benches) in &groups {
+        for (i, (bench, keys)) in benches.iter().enumerate() {
+            let mut row = vec![
+                Cell::new(if i == 0 { *suite } else { "" }),
+                Cell::new(bench),
+            ];
+            for e in &executors {
+                let cell = keys.get(e).map_or(String::new(), |k| {
+                    let s = &summaries[*k];
+                    let times = &data.data[*k];
+                    let min = times.iter().copied().fold(f64::INFINITY, f64::min);
+                    let max = times.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                    format!(
+                        "{} ±{} [{}, {}]",
+                        mean_fmt.format(s.mean),
+                        ci_fmt.format(s.ci),
+                        extreme_fmt.format(min),
+                        extreme_fmt.format(max)
+                    )
+                });
+                row.push(Cell::new(cell).set_alignment(CellAlignment::Right));
+            }
+            table.add_row(row);
+        }
+    }
+    table
+}
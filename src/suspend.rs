@@ -0,0 +1,175 @@
+//! Noticing pexecs whose times can't be trusted, most often because the machine was suspended
+//! (e.g. a laptop's lid was closed) while they ran, so that they can be discarded and rerun
+//! rather than silently recorded.
+//!
+//! Rust's [Instant] doesn't advance while the machine is suspended, but Linux's boot clock does,
+//! so over a pexec the two differ by the time spent suspended. Elsewhere the wall clock stands in
+//! for the boot clock, less precisely, since it can also be stepped, e.g. by NTP.
+
+use crate::units;
+use std::time::{Duration, Instant, SystemTime};
+
+/// How much further than the monotonic clock the boot clock can advance over a pexec before the
+/// machine is taken to have been suspended.
+const BOOTTIME_SLACK: Duration = Duration::from_millis(100);
+/// How much further than the monotonic clock the wall clock can advance over a pexec before the
+/// machine is taken to have been suspended, where there's no boot clock.
+const WALLCLOCK_SLACK: Duration = Duration::from_secs(5);
+/// How many times in a row a tainted pexec is rerun before its time is kept anyway.
+pub(crate) const MAX_RERUNS: usize = 3;
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::ffi::{c_int, c_long};
+    use std::time::Duration;
+
+    /// `clock_gettime`'s clock that, unlike `CLOCK_MONOTONIC`, advances during suspend.
+    const CLOCK_BOOTTIME: c_int = 7;
+    /// `getrusage`'s `who` for the waited-for children of the current process.
+    const RUSAGE_CHILDREN: c_int = -1;
+
+    #[repr(C)]
+    struct Timespec {
+        tv_sec: c_long,
+        tv_nsec: c_long,
+    }
+
+    #[repr(C)]
+    struct Timeval {
+        tv_sec: c_long,
+        tv_usec: c_long,
+    }
+
+    #[repr(C)]
+    struct Rusage {
+        ru_utime: Timeval,
+        ru_stime: Timeval,
+        /// The counters, such as page faults, that aren't needed here.
+        ru_rest: [c_long; 14],
+    }
+
+    unsafe extern "C" {
+        fn clock_gettime(clockid: c_int, tp: *mut Timespec) -> c_int;
+        fn getrusage(who: c_int, usage: *mut Rusage) -> c_int;
+    }
+
+    fn from_timeval(t: &Timeval) -> Duration {
+        Duration::new(t.tv_sec as u64, 0) + Duration::from_micros(t.tv_usec as u64)
+    }
+
+    pub(super) fn boottime() -> Option<Duration> {
+        let mut tp = Timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // SAFETY: `clock_gettime` only writes to `tp`.
+        (unsafe { clock_gettime(CLOCK_BOOTTIME, &mut tp) } == 0)
+            .then(|| Duration::new(tp.tv_sec as u64, tp.tv_nsec as u32))
+    }
+
+    pub(super) fn children_cpu() -> Option<Duration> {
+        let mut usage = Rusage {
+            ru_utime: Timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+            ru_stime: Timeval {
+                tv_sec: 0,
+                tv_usec: 0,
+            },
+            ru_rest: [0; 14],
+        };
+        // SAFETY: `getrusage` only writes to `usage`.
+        (unsafe { getrusage(RUSAGE_CHILDREN, &mut usage) } == 0)
+            .then(|| from_timeval(&usage.ru_utime) + from_timeval(&usage.ru_stime))
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use std::time::Duration;
+
+    pub(super) fn boottime() -> Option<Duration> {
+        None
+    }
+
+    pub(super) fn children_cpu() -> Option<Duration> {
+        None
+    }
+}
+
+/// The clocks at one moment.
+pub(crate) struct Clocks {
+    monotonic: Instant,
+    boottime: Option<Duration>,
+    wallclock: SystemTime,
+    /// The CPU time used by every child process waited for so far.
+    cpu: Option<Duration>,
+}
+
+impl Clocks {
+    pub(crate) fn read() -> Self {
+        Clocks {
+            monotonic: Instant::now(),
+            boottime: sys::boottime(),
+            wallclock: SystemTime::now(),
+            cpu: sys::children_cpu(),
+        }
+    }
+
+    /// How far each clock advanced between `earlier` and `self`. Since only children that have
+    /// been waited for count, the CPU time is that of the pexecs waited for in between.
+    pub(crate) fn since(&self, earlier: &Clocks) -> Deltas {
+        Deltas {
+            monotonic: self.monotonic.duration_since(earlier.monotonic),
+            boottime: self
+                .boottime
+                .zip(earlier.boottime)
+                .map(|(b, e)| b.saturating_sub(e)),
+            wallclock: self.wallclock.duration_since(earlier.wallclock).ok(),
+            cpu: self.cpu.zip(earlier.cpu).map(|(c, e)| c.saturating_sub(e)),
+        }
+    }
+}
+
+/// How far each clock advanced over a pexec. Those that can't be read, or went backwards, are
+/// `None`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Deltas {
+    pub(crate) monotonic: Duration,
+    pub(crate) boottime: Option<Duration>,
+    pub(crate) wallclock: Option<Duration>,
+    pub(crate) cpu: Option<Duration>,
+}
+
+/// Why the time of a pexec over which the clocks advanced by `deltas` can't be trusted, if it
+/// can't: because the machine was suspended, or, with a `max_wall_cpu_ratio`, because the pexec
+/// took more than that many times longer than the CPU time it used.
+pub(crate) fn taint(deltas: &Deltas, max_wall_cpu_ratio: Option<f64>) -> Option<String> {
+    let time = units::format_duration;
+    let suspended = match (deltas.boottime, deltas.wallclock) {
+        (Some(boottime), _) => boottime
+            .checked_sub(deltas.monotonic)
+            .filter(|d| *d > BOOTTIME_SLACK),
+        (None, Some(wallclock)) => wallclock
+            .checked_sub(deltas.monotonic)
+            .filter(|d| *d > WALLCLOCK_SLACK),
+        (None, None) => None,
+    };
+    if let Some(d) = suspended {
+        return Some(format!(
+            "the machine was suspended for {} while it ran",
+            time(d)
+        ));
+    }
+    match (max_wall_cpu_ratio, deltas.cpu) {
+        (Some(ratio), Some(cpu)) if deltas.monotonic.as_secs_f64() > cpu.as_secs_f64() * ratio => {
+            Some(format!(
+                "it took {} but used only {} of CPU time (max_wall_cpu_ratio = {ratio})",
+                time(deltas.monotonic),
+                time(cpu)
+            ))
+        }
+        _ => None,
+    }
+}
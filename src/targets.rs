@@ -0,0 +1,259 @@
+//! Checking datums against fixed targets for benchmarks, such as "fasta must stay under 800ms on
+//! the reference machine", which are kept in a targets file (by default `targets.toml` next to
+//! the config file):
+//!
+//! ```toml
+//! [[target]]
+//! benchmark = "fasta/*"
+//! value = 800
+//! direction = "below"
+//! ```
+
+use crate::order::glob_match;
+use crate::{App, ConfidenceLevel, SummaryStats, TIME_METRIC, aliases, metric_unit, new_table};
+use comfy_table::{Cell, CellAlignment, Color};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::{fs, process};
+
+/// The name of the targets file, which by default is next to the config file.
+pub(crate) const DEFAULT_TARGETS_FILE: &str = "targets.toml";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TargetsFile {
+    #[serde(default)]
+    target: Vec<Target>,
+}
+
+/// Which side of its value a target wants a benchmark's results to be on.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Direction {
+    /// At most the value, as for times.
+    Below,
+    /// At least the value, as for throughput.
+    Above,
+}
+
+/// A target for the benchmarks whose keys match a pattern.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Target {
+    /// A pattern of the benchmark keys the target is for, in which `*` matches anything and `?`
+    /// any one character.
+    pub(crate) benchmark: String,
+    pub(crate) value: f64,
+    pub(crate) direction: Direction,
+    /// The metric the target is for.
+    #[serde(default = "default_metric")]
+    pub(crate) metric: String,
+}
+
+fn default_metric() -> String {
+    TIME_METRIC.to_owned()
+}
+
+/// How a benchmark's results compare with a target.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Status {
+    /// The confidence interval is entirely on the right side of the target.
+    Pass,
+    /// The confidence interval straddles the target.
+    Warn,
+    /// The confidence interval is entirely on the wrong side of the target.
+    Fail,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Status::Pass => "pass",
+            Status::Warn => "warn",
+            Status::Fail => "FAIL",
+        })
+    }
+}
+
+impl Target {
+    /// How `s` compares with this target.
+    pub(crate) fn evaluate(&self, s: &SummaryStats) -> Status {
+        let (lo, hi) = (s.mean - s.ci, s.mean + s.ci);
+        let (right, wrong) = match self.direction {
+            Direction::Below => (hi <= self.value, lo > self.value),
+            Direction::Above => (lo >= self.value, hi < self.value),
+        };
+        if right {
+            Status::Pass
+        } else if wrong {
+            Status::Fail
+        } else {
+            Status::Warn
+        }
+    }
+
+    pub(crate) fn matches(&self, key: &str) -> bool {
+        glob_match(&self.benchmark, key)
+    }
+}
+
+impl fmt::Display for Target {
+    /// The target as e.g. `< 800 ms`, with the metric if it isn't the time.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.metric != TIME_METRIC {
+            write!(f, "{} ", self.metric)?;
+        }
+        let op = match self.direction {
+            Direction::Below => '<',
+            Direction::Above => '>',
+        };
+        write!(f, "{op} {}", self.value)?;
+        if let Some(unit) = metric_unit(&self.metric) {
+            write!(f, " {unit}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse a targets file.
+pub(crate) fn parse(text: &str) -> Result<Vec<Target>, String> {
+    let targets = toml::from_str::<TargetsFile>(text)
+        .map_err(|e| e.to_string())?
+        .target;
+    for t in &targets {
+        if !t.value.is_finite() {
+            return Err(format!("{}: the target must be a number", t.benchmark));
+        }
+    }
+    Ok(targets)
+}
+
+/// Load the targets file at `path`.
+pub(crate) fn load(path: &Path) -> Result<Vec<Target>, String> {
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    parse(&text).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+/// The benchmarks whose results cross a target of `metric` between `data1` and `data2`, with
+/// notes saying how, for the rows of a comparison.
+pub(crate) fn crossings(
+    targets: &[Target],
+    metric: &str,
+    data1: &HashMap<String, SummaryStats>,
+    data2: &HashMap<String, SummaryStats>,
+) -> HashMap<String, String> {
+    let mut notes = HashMap::<String, String>::new();
+    for t in targets.iter().filter(|t| t.metric == metric) {
+        for (k, s1) in data1 {
+            let Some(s2) = data2.get(k).filter(|_| t.matches(k)) else {
+                continue;
+            };
+            let (before, after) = (t.evaluate(s1), t.evaluate(s2));
+            if before != after {
+                let note = notes.entry(k.clone()).or_default();
+                note.push_str(&format!(" [{t}: {before} -> {after}]"));
+            }
+        }
+    }
+    notes
+}
+
+impl App {
+    /// The targets file to use if none is given: the one next to the config file.
+    pub(crate) fn default_targets_file(&self) -> PathBuf {
+        self.config_file.with_file_name(DEFAULT_TARGETS_FILE)
+    }
+
+    /// Check the results of datum `id` against the targets in `path`, exiting non-zero if any
+    /// fails.
+    pub(crate) fn cmd_targets(&self, id: usize, path: Option<&Path>, confidence: ConfidenceLevel) {
+        let path = path.map_or_else(|| self.default_targets_file(), Path::to_owned);
+        let targets = load(&path).unwrap_or_else(|e| {
+            eprintln!("error: {e}");
+            process::exit(1);
+        });
+        let mut results = self.load_results(id);
+        let renames = self
+            .key_aliases()
+            .renames(results.data.keys().chain(results.failures.keys()), |_| true);
+        for (old, new) in &renames {
+            results.rename_key(old, new);
+        }
+        if let Some(note) = aliases::note(&format!("Datum{id}"), &renames) {
+            println!("{note}");
+        }
+        let mut table = new_table(crate::colour_enabled());
+        let right = |s: String| Cell::new(s).set_alignment(CellAlignment::Right);
+        table.set_header(vec![
+            Cell::new("Benchmark"),
+            right("Target".to_owned()),
+            right("Result".to_owned()),
+            Cell::new("Status"),
+        ]);
+        let (mut passed, mut warned, mut failed) = (0, 0, 0);
+        for t in &targets {
+            let data = match results.for_metric(&t.metric) {
+                Ok(data) => data,
+                Err(e) => {
+                    eprintln!("error: Datum{id}: {e}");
+                    process::exit(1);
+                }
+            };
+            let mut keys = data
+                .data
+                .keys()
+                .chain(data.failures.keys())
+                .filter(|k| t.matches(k))
+                .collect::<Vec<_>>();
+            keys.sort();
+            keys.dedup();
+            if keys.is_empty() {
+                println!(
+                    "WARNING: target {} matches no benchmark in Datum{id}",
+                    t.benchmark
+                );
+            }
+            for k in keys {
+                let (result, status) = if data.failures.contains_key(k) {
+                    ("failed".to_owned(), Status::Fail)
+                } else {
+                    let s = SummaryStats::from_samples(&data.data[k], confidence);
+                    (format!("{:.2} ±{:.2}", s.mean, s.ci), t.evaluate(&s))
+                };
+                let colour = match status {
+                    Status::Pass => {
+                        passed += 1;
+                        Color::Green
+                    }
+                    Status::Warn => {
+                        warned += 1;
+                        Color::Yellow
+                    }
+                    Status::Fail => {
+                        failed += 1;
+                        Color::Red
+                    }
+                };
+                table.add_row(vec![
+                    Cell::new(k),
+                    right(t.to_string()),
+                    right(result),
+                    Cell::new(status).fg(colour),
+                ]);
+            }
+        }
+        println!("confidence level: {confidence}%\n");
+        println!("{table}");
+        println!(
+            "\npass/FAIL = the confidence interval is entirely within/outside the target; warn = \
+             it straddles the target"
+        );
+        println!("\nhaste: {passed} pass, {warned} warn, {failed} fail");
+        if failed > 0 {
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,336 @@
+//! An interactive terminal browser for datums.
+
+use crate::{
+    App, ConfidenceLevel, DEFAULT_MIN_SAMPLES, DiffFormats, NumFormat, ResultFile, SummaryStats,
+    diff_rows, format_timestamp,
+};
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, List, ListItem, ListState, Paragraph, Row, Table},
+};
+use std::collections::HashMap;
+use std::io;
+use std::ops::ControlFlow;
+
+/// What the right-hand pane shows.
+#[derive(Debug, PartialEq)]
+pub(crate) enum View {
+    /// The selected datum.
+    Summary,
+    /// A comparison of two datums.
+    Diff(usize, usize),
+}
+
+/// What the browser shows, which only key presses change.
+pub(crate) struct State {
+    ids: Vec<usize>,
+    list: ListState,
+    /// The datums marked for diffing, oldest first.
+    pub(crate) marked: Vec<usize>,
+    pub(crate) view: View,
+    /// A message for the status line.
+    pub(crate) status: String,
+}
+
+impl State {
+    /// Browse the datums `ids`, starting at the newest.
+    pub(crate) fn new(ids: Vec<usize>) -> Self {
+        let mut list = ListState::default();
+        if !ids.is_empty() {
+            list.select(Some(ids.len() - 1));
+        }
+        State {
+            ids,
+            list,
+            marked: Vec::new(),
+            view: View::Summary,
+            status: "↑/↓: select  space: mark  d: diff marked  esc: back  q: quit".to_owned(),
+        }
+    }
+
+    /// Update the state for `key`, breaking if the user has quit.
+    pub(crate) fn handle_key(&mut self, key: KeyEvent) -> ControlFlow<()> {
+        if key.kind != KeyEventKind::Press {
+            return ControlFlow::Continue(());
+        }
+        match key.code {
+            KeyCode::Char('q') => return ControlFlow::Break(()),
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.list.select_previous();
+                self.view = View::Summary;
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                if self
+                    .list
+                    .selected()
+                    .is_some_and(|i| i < self.ids.len().saturating_sub(1))
+                {
+                    self.list.select_next();
+                }
+                self.view = View::Summary;
+            }
+            KeyCode::Char(' ') => self.toggle_mark(),
+            KeyCode::Char('d') => match self.marked[..] {
+                // Compare the older datum with the newer, as people usually want.
+                [id1, id2] => self.view = View::Diff(id1.min(id2), id1.max(id2)),
+                _ => self.status = "mark two datums to diff them".to_owned(),
+            },
+            KeyCode::Esc => self.view = View::Summary,
+            _ => (),
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// The datum the cursor is on, if there are any.
+    pub(crate) fn selected(&self) -> Option<usize> {
+        self.list
+            .selected()
+            .and_then(|i| self.ids.get(i.min(self.ids.len().saturating_sub(1))))
+            .copied()
+    }
+
+    fn toggle_mark(&mut self) {
+        let Some(id) = self.selected() else {
+            return;
+        };
+        if let Some(i) = self.marked.iter().position(|m| *m == id) {
+            self.marked.remove(i);
+        } else {
+            // Only two datums can be compared, so forget the oldest mark.
+            if self.marked.len() == 2 {
+                self.marked.remove(0);
+            }
+            self.marked.push(id);
+        }
+    }
+}
+
+/// Calls its function when dropped, including when a panic unwinds past it.
+pub(crate) struct OnDrop<F: FnMut()>(pub(crate) F);
+
+impl<F: FnMut()> Drop for OnDrop<F> {
+    fn drop(&mut self) {
+        (self.0)()
+    }
+}
+
+struct Browser<'a> {
+    app: &'a App,
+    confidence: ConfidenceLevel,
+    /// The line shown for each datum in the list.
+    entries: Vec<String>,
+    state: State,
+    /// Results loaded so far, by datum.
+    cache: HashMap<usize, Result<ResultFile, String>>,
+}
+
+/// Browse the datums of `app` until the user quits.
+pub(crate) fn run(app: &App, confidence: ConfidenceLevel) -> io::Result<()> {
+    let ids = app.store.ids();
+    let entries = ids
+        .iter()
+        .map(|id| {
+            let extra = app.load_extra(*id);
+            format!(
+                "{id:3}  {}  {}",
+                format_timestamp(extra.start_time),
+                extra.comment.as_deref().unwrap_or("")
+            )
+        })
+        .collect();
+    let mut browser = Browser {
+        app,
+        confidence,
+        entries,
+        state: State::new(ids),
+        cache: HashMap::new(),
+    };
+    // This also installs a panic hook which restores the terminal before the panic is reported.
+    let mut terminal = ratatui::init();
+    // However the loop ends, even by a panic unwinding, leave the terminal as it was.
+    let _restore = OnDrop(ratatui::restore);
+    browser.event_loop(&mut terminal)
+}
+
+impl Browser<'_> {
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|f| self.draw(f))?;
+            if let Event::Key(key) = event::read()?
+                && self.state.handle_key(key).is_break()
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    fn results(&mut self, id: usize) -> &Result<ResultFile, String> {
+        self.cache
+            .entry(id)
+            .or_insert_with(|| self.app.try_load_results(id))
+    }
+
+    fn draw(&mut self, f: &mut Frame) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(f.area());
+        let [left, right] =
+            Layout::horizontal([Constraint::Percentage(35), Constraint::Percentage(65)])
+                .areas(main);
+
+        let items = self
+            .state
+            .ids
+            .iter()
+            .zip(&self.entries)
+            .map(|(id, entry)| {
+                let mark = if self.state.marked.contains(id) {
+                    "*"
+                } else {
+                    " "
+                };
+                ListItem::new(format!("{mark}{entry}"))
+            })
+            .collect::<Vec<_>>();
+        let list = List::new(items)
+            .block(Block::bordered().title("Datums"))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_stateful_widget(list, left, &mut self.state.list);
+
+        match self.state.view {
+            View::Summary => match self.state.selected() {
+                Some(id) => self.draw_summary(f, right, id),
+                None => {
+                    f.render_widget(Paragraph::new("no datums").block(Block::bordered()), right)
+                }
+            },
+            View::Diff(id1, id2) => self.draw_diff(f, right, id1, id2),
+        }
+        f.render_widget(Line::from(self.state.status.as_str()), status);
+    }
+
+    fn draw_summary(&mut self, f: &mut Frame, area: Rect, id: usize) {
+        let confidence = self.confidence;
+        let block = Block::bordered().title(format!("Datum{id} ({confidence}% confidence)"));
+        let data = match self.results(id) {
+            Ok(data) => data,
+            Err(e) => {
+                f.render_widget(Paragraph::new(e.as_str()).block(block), area);
+                return;
+            }
+        };
+        let summaries = data.summarise(confidence);
+        let mut keys = summaries.keys().collect::<Vec<_>>();
+        keys.sort();
+        let means = summaries.values().map(|s| s.mean).collect::<Vec<_>>();
+        let mean_fmt = NumFormat::new(&means, 0, false, false);
+        let cis = summaries.values().map(|s| s.ci).collect::<Vec<_>>();
+        let ci_fmt = NumFormat::new(&cis, 0, false, false);
+        let rows = keys
+            .iter()
+            .map(|k| {
+                let s = &summaries[*k];
+                Row::new([
+                    k.to_string(),
+                    data.data[*k].len().to_string(),
+                    format!("{} ±{}", mean_fmt.format(s.mean), ci_fmt.format(s.ci)),
+                ])
+            })
+            .collect::<Vec<_>>();
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Fill(1),
+                Constraint::Length(7),
+                Constraint::Length(16),
+            ],
+        )
+        .header(Row::new(["Benchmark", "Samples", "Time (ms)"]).style(bold()))
+        .block(block);
+        f.render_widget(table, area);
+    }
+
+    fn draw_diff(&mut self, f: &mut Frame, area: Rect, id1: usize, id2: usize) {
+        let confidence = self.confidence;
+        let block = Block::bordered().title(format!(
+            "Datum{id1} vs. Datum{id2} ({confidence}% confidence)"
+        ));
+        let summaries = [id1, id2].map(|id| {
+            self.results(id)
+                .as_ref()
+                .map(|data| data.summarise(confidence))
+                .map_err(String::clone)
+        });
+        let (mut data1, mut data2) = match summaries {
+            [Ok(d1), Ok(d2)] => (d1, d2),
+            [Err(e), _] | [_, Err(e)] => {
+                f.render_widget(Paragraph::new(e).block(block), area);
+                return;
+            }
+        };
+        // As with `diff --relaxed`, only the benchmarks in both datums can be compared.
+        data1.retain(|k, _| data2.contains_key(k));
+        data2.retain(|k, _| data1.contains_key(k));
+        let rows = diff_rows(&data1, &data2, DEFAULT_MIN_SAMPLES, false);
+        let fmts = DiffFormats::new(&rows, false, false);
+        let table_rows = rows
+            .iter()
+            .map(|row| {
+                let [v1, v2, delta, ratio] = row.columns(&fmts);
+                let colour = match (row.significant, row.improved()) {
+                    (false, _) => Color::Magenta,
+                    (true, true) => Color::Green,
+                    (true, false) => Color::Red,
+                };
+                Row::new([
+                    Line::from(row.key),
+                    Line::from(v1).right_aligned(),
+                    Line::from(v2).right_aligned(),
+                    Line::from(delta).right_aligned(),
+                    Line::from(ratio).right_aligned(),
+                    Line::from(row.summary()).style(Style::default().fg(colour)),
+                ])
+            })
+            .collect::<Vec<_>>();
+        let width = |s: &SummaryStats| {
+            (fmts.mean.format(s.mean).len() + fmts.ci.format(s.ci).len() + 2) as u16
+        };
+        let value_width = rows
+            .iter()
+            .flat_map(|r| [width(r.s1), width(r.s2)])
+            .max()
+            .unwrap_or(0)
+            .max(10);
+        let table = Table::new(
+            table_rows,
+            [
+                Constraint::Fill(1),
+                Constraint::Length(value_width),
+                Constraint::Length(value_width),
+                Constraint::Length(fmts.delta.width.max(5) as u16),
+                Constraint::Length(fmts.ratio.width.max(5) as u16),
+                Constraint::Length(17),
+            ],
+        )
+        .header(
+            Row::new([
+                Line::from("Benchmark"),
+                Line::from(format!("Datum{id1}")).right_aligned(),
+                Line::from(format!("Datum{id2}")).right_aligned(),
+                Line::from("Delta").right_aligned(),
+                Line::from("Ratio").right_aligned(),
+                Line::from("Summary"),
+            ])
+            .style(bold()),
+        )
+        .block(block);
+        f.render_widget(table, area);
+    }
+}
+
+fn bold() -> Style {
+    Style::default().add_modifier(Modifier::BOLD)
+}
@@ -0,0 +1,114 @@
+//! Showing durations in units that can be read at a glance, e.g. `6m 24.2s ±1.2s` rather than
+//! `384212 ±1202` milliseconds.
+
+use clap::ValueEnum;
+use std::io::{self, IsTerminal};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// How durations are shown.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub(crate) enum Units {
+    /// As plain numbers of milliseconds (or seconds, for the length of a whole run), which suit
+    /// scripts.
+    Raw,
+    /// In hours, minutes, seconds or milliseconds, as suits each column's largest value.
+    Human,
+}
+
+/// The units chosen by `--units` or, failing that, by [human] the first time it's needed.
+static UNITS: OnceLock<Units> = OnceLock::new();
+
+/// Show durations in `units` for the rest of the run.
+pub(crate) fn set(units: Units) {
+    // Only `main` sets the units, before anything is shown.
+    UNITS.set(units).ok();
+}
+
+/// Are durations shown in human units? Unless `--units` says otherwise, they are only if stdout is
+/// a terminal, as with colour.
+pub(crate) fn human() -> bool {
+    let units = UNITS.get_or_init(|| {
+        if io::stdout().is_terminal() {
+            Units::Human
+        } else {
+            Units::Raw
+        }
+    });
+    *units == Units::Human
+}
+
+/// The largest unit a column of durations is shown in, chosen by the column's largest value, so
+/// that every value in the column has the same shape and they line up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Scale {
+    /// e.g. `384ms`.
+    Millis,
+    /// e.g. `24.2s`.
+    Secs,
+    /// e.g. `6m 24.2s`.
+    Mins,
+    /// e.g. `1h 06m 24s`.
+    Hours,
+}
+
+impl Scale {
+    /// The scale for a column of durations whose largest magnitude is `max_ms` milliseconds.
+    ///
+    /// Values which would round up to the next unit, e.g. 59.96s to `60.0s`, are shown in it.
+    pub(crate) fn for_max(max_ms: f64) -> Self {
+        let max_ms = max_ms.abs();
+        if max_ms >= 3_599_950. {
+            Scale::Hours
+        } else if max_ms >= 59_950. {
+            Scale::Mins
+        } else if max_ms >= 999.5 {
+            Scale::Secs
+        } else {
+            Scale::Millis
+        }
+    }
+
+    /// Render `ms` milliseconds in this scale, with `precision` decimal places if in milliseconds,
+    /// and with a `+` if it's positive and `signed`.
+    pub(crate) fn render(self, ms: f64, precision: usize, signed: bool) -> String {
+        let sign = if ms < 0. {
+            "-"
+        } else if signed {
+            "+"
+        } else {
+            ""
+        };
+        let ms = ms.abs();
+        match self {
+            Scale::Millis => format!("{sign}{ms:.precision$}ms"),
+            Scale::Secs => format!("{sign}{:.1}s", ms / 1000.),
+            Scale::Mins => {
+                // Round to tenths of a second first, so that e.g. 119.96s doesn't become `1m 60.0s`.
+                let tenths = (ms / 100.).round() as u64;
+                let (mins, tenths) = (tenths / 600, tenths % 600);
+                format!("{sign}{mins}m {:02}.{}s", tenths / 10, tenths % 10)
+            }
+            Scale::Hours => {
+                let secs = (ms / 1000.).round() as u64;
+                format!(
+                    "{sign}{}h {:02}m {:02}s",
+                    secs / 3600,
+                    secs % 3600 / 60,
+                    secs % 60
+                )
+            }
+        }
+    }
+}
+
+/// Format a single duration, such as how long a run took, in the largest unit it reaches if
+/// [human], otherwise in seconds.
+pub(crate) fn format_duration(d: Duration) -> String {
+    if human() {
+        let ms = d.as_secs_f64() * 1000.;
+        Scale::for_max(ms).render(ms, 0, false)
+    } else {
+        format!("{:.3}s", d.as_secs_f64())
+    }
+}
@@ -0,0 +1,153 @@
+//! Comparing the variants of a benchmark run with different `extra_args` in the same datum, so that
+//! the arguments can be the experimental variable.
+
+use crate::{
+    App, ConfidenceLevel, DEFAULT_MIN_SAMPLES, SummaryStats, colour_enabled, diff_legend,
+    diff_table, named_header,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::process;
+
+/// Split the benchmark key `key` into its benchmark, executor and extra arguments (as joined in
+/// the key).
+///
+/// Benchmark and executor names can't contain `/`, so anything after the second `/` is the
+/// arguments, even if they contain `/` themselves.
+fn split_variant(key: &str) -> Option<(&str, &str, &str)> {
+    let mut parts = key.splitn(3, '/');
+    Some((parts.next()?, parts.next()?, parts.next()?))
+}
+
+/// The benchmarks to compare, and the problems found pairing them up.
+pub(crate) struct Variants {
+    /// For each variant's key, the summary of its benchmark's baseline variant.
+    pub(crate) base: HashMap<String, SummaryStats>,
+    /// The summary of each variant, with the same keys as `base`.
+    pub(crate) variants: HashMap<String, SummaryStats>,
+    /// The `benchmark/executor`s with other variants but no baseline variant.
+    pub(crate) no_baseline: Vec<String>,
+}
+
+/// Pair up each variant in `summaries` with the variant of the same benchmark and executor whose
+/// arguments are `baseline` (as joined in keys), considering only the benchmarks and executors
+/// named in `benchmarks` and `executors` (or all of them, if they're empty).
+///
+/// Fails if any key can't be split into its benchmark, executor and arguments.
+pub(crate) fn pair_variants(
+    summaries: &HashMap<String, SummaryStats>,
+    baseline: &str,
+    benchmarks: &[String],
+    executors: &[String],
+) -> Result<Variants, String> {
+    let mut groups = BTreeMap::<(&str, &str), Vec<(&str, &str)>>::new();
+    for key in summaries.keys() {
+        let (bench, exec, args) =
+            split_variant(key).ok_or_else(|| format!("can't split benchmark key {key:?}"))?;
+        let wanted = |names: &[String], name| names.is_empty() || names.iter().any(|n| n == name);
+        if wanted(benchmarks, bench) && wanted(executors, exec) {
+            groups.entry((bench, exec)).or_default().push((args, key));
+        }
+    }
+    let mut paired = Variants {
+        base: HashMap::new(),
+        variants: HashMap::new(),
+        no_baseline: Vec::new(),
+    };
+    for ((bench, exec), variants) in groups {
+        if variants.len() < 2 {
+            continue;
+        }
+        let Some((_, base_key)) = variants.iter().find(|(args, _)| *args == baseline) else {
+            paired.no_baseline.push(format!("{bench}/{exec}"));
+            continue;
+        };
+        for (args, key) in &variants {
+            if *args != baseline {
+                paired
+                    .base
+                    .insert((*key).to_owned(), summaries[*base_key].clone());
+                paired
+                    .variants
+                    .insert((*key).to_owned(), summaries[*key].clone());
+            }
+        }
+    }
+    Ok(paired)
+}
+
+impl App {
+    /// Compare the variants of each benchmark in datum `id` with the variant run with the
+    /// arguments `baseline_args`, on `metric`.
+    pub(crate) fn cmd_diff_args(
+        &self,
+        id: usize,
+        benchmarks: &[String],
+        executors: &[String],
+        baseline_args: &str,
+        metric: &str,
+        confidence: ConfidenceLevel,
+    ) {
+        let data = self
+            .load_results(id)
+            .for_metric(metric)
+            .unwrap_or_else(|e| {
+                eprintln!("error: Datum{id}: {e}");
+                process::exit(1);
+            });
+        // Keys join the arguments with `-`, whatever whitespace separated them on the command
+        // line.
+        let baseline = baseline_args
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join("-");
+        let mut failed = data.failures.keys().cloned().collect::<Vec<_>>();
+        failed.sort();
+        if !failed.is_empty() {
+            println!("failed, so not compared: {}", failed.join(", "));
+        }
+        let mut summaries = data.summarise(confidence);
+        summaries.retain(|k, _| !data.failures.contains_key(k));
+        let paired =
+            pair_variants(&summaries, &baseline, benchmarks, executors).unwrap_or_else(|e| {
+                eprintln!("error: Datum{id}: {e}");
+                process::exit(1);
+            });
+        if !paired.no_baseline.is_empty() {
+            println!(
+                "no variant with the baseline arguments: {}",
+                paired.no_baseline.join(", ")
+            );
+        }
+        if paired.variants.is_empty() {
+            eprintln!(
+                "error: Datum{id} has no benchmarks run both with extra_args {:?} and with other \
+                 extra_args",
+                baseline_args.split_whitespace().collect::<Vec<_>>()
+            );
+            process::exit(1);
+        }
+        let base_name = format!("[{}]", baseline_args.trim());
+        let (table, _) = diff_table(
+            &paired.base,
+            &paired.variants,
+            &named_header(&base_name, metric),
+            &named_header("variant", metric),
+            false,
+            DEFAULT_MIN_SAMPLES,
+            metric,
+        );
+        println!("confidence level: {confidence}%\n");
+        println!("{table}");
+        println!(
+            "\n{}",
+            diff_legend(
+                &base_name,
+                "variant",
+                metric,
+                confidence,
+                DEFAULT_MIN_SAMPLES,
+                colour_enabled()
+            )
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! Comparing the executors of one datum with each other, as `haste compare` does: each with the
+//! first, the baseline, which by default is the first of the executor group the datum was made
+//! for.
+
+use crate::{
+    App, ConfidenceLevel, DEFAULT_MIN_SAMPLES, OutputFormat, ResultFile, TIME_METRIC,
+    colour_enabled, csv_field, diff_legend, diff_rows, diff_table, named_header,
+};
+use std::process;
+
+impl App {
+    /// Compare each of `executors`, or of the executors of executor group `group`, in datum `id`
+    /// with the first of them. With neither, the executors are those of the group the datum was
+    /// made for.
+    pub(crate) fn cmd_compare(
+        &self,
+        id: usize,
+        group: Option<&str>,
+        executors: &[String],
+        confidence: ConfidenceLevel,
+        format: OutputFormat,
+    ) {
+        let extra = self.load_extra(id);
+        let (group, executors) = if executors.is_empty() {
+            let Some(group) = group.or(extra.executor_group.as_deref()) else {
+                eprintln!(
+                    "error: Datum{id} wasn't made for an executor group: give --group or \
+                     --executor"
+                );
+                process::exit(1);
+            };
+            let config = self.load_config();
+            let members = config.executor_group(group).unwrap_or_else(|e| {
+                eprintln!("error: {e}");
+                process::exit(1);
+            });
+            (Some(group), members.to_vec())
+        } else {
+            (None, executors.to_vec())
+        };
+        if executors.len() < 2 {
+            eprintln!("error: need at least two executors to compare");
+            process::exit(1);
+        }
+        let results = self.load_results(id);
+        let for_executor = |e: &str| -> ResultFile {
+            let data = results.for_executor(e);
+            if data.data.is_empty() {
+                eprintln!("error: Datum{id} has no results for executor {e}");
+                process::exit(1);
+            }
+            data
+        };
+        let base = &executors[0];
+        let base_data = for_executor(base);
+        let pairs = executors[1..]
+            .iter()
+            .map(|e| {
+                let (mut b, mut n) = (base_data.clone(), for_executor(e));
+                let only = b.retain_common(&mut n);
+                (e, b.summarise(confidence), n.summarise(confidence), only)
+            })
+            .collect::<Vec<_>>();
+
+        match format {
+            OutputFormat::Table => {
+                if let Some(g) = group {
+                    println!("executor group {g}: {}", executors.join(", "));
+                }
+                println!("confidence level: {confidence}%");
+                for (new, b, n, (base_only, new_only)) in &pairs {
+                    println!("\n{base} vs. {new}:\n");
+                    for (name, only) in [(base, base_only), (new, new_only)] {
+                        if !only.is_empty() {
+                            println!("only run with {name}: {}", only.join(", "));
+                        }
+                    }
+                    let (table, _) = diff_table(
+                        b,
+                        n,
+                        &named_header(base, TIME_METRIC),
+                        &named_header(new, TIME_METRIC),
+                        false,
+                        DEFAULT_MIN_SAMPLES,
+                        TIME_METRIC,
+                    );
+                    println!("{table}");
+                    println!(
+                        "\n{}",
+                        diff_legend(
+                            base,
+                            new,
+                            TIME_METRIC,
+                            confidence,
+                            DEFAULT_MIN_SAMPLES,
+                            colour_enabled()
+                        )
+                    );
+                }
+            }
+            OutputFormat::Csv => {
+                println!(
+                    "group,baseline,executor,benchmark,baseline_mean,baseline_ci,mean,ci,change,\
+                     significant"
+                );
+                for (new, b, n, _) in &pairs {
+                    let mut rows = diff_rows(b, n, DEFAULT_MIN_SAMPLES, false);
+                    rows.sort_by_key(|r| r.key);
+                    for r in rows {
+                        println!(
+                            "{},{},{},{},{},{},{},{},{},{}",
+                            csv_field(group.unwrap_or("")),
+                            csv_field(base),
+                            csv_field(new),
+                            csv_field(r.key),
+                            r.s1.mean,
+                            r.s1.ci,
+                            r.s2.mean,
+                            r.s2.ci,
+                            r.change,
+                            r.significant
+                        );
+                    }
+                }
+            }
+            OutputFormat::Json => {
+                let mut json = Vec::new();
+                for (new, b, n, _) in &pairs {
+                    let mut rows = diff_rows(b, n, DEFAULT_MIN_SAMPLES, false);
+                    rows.sort_by_key(|r| r.key);
+                    for r in rows {
+                        json.push(serde_json::json!({
+                            "group": group,
+                            "baseline": base,
+                            "executor": new,
+                            "benchmark": r.key,
+                            "baseline_mean": r.s1.mean,
+                            "baseline_ci": r.s1.ci,
+                            "mean": r.s2.mean,
+                            "ci": r.s2.ci,
+                            "change": r.change,
+                            "significant": r.significant,
+                        }));
+                    }
+                }
+                println!("{}", serde_json::Value::Array(json));
+            }
+        }
+    }
+}